@@ -1,9 +1,13 @@
-use std::{fmt::Write as _, process::ExitCode};
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
 use anyhow::{Context, Result};
 use clap::Parser;
 
-use lune::Lune;
+use lune::{Bundle, Lune};
 use tokio::{
     fs::read as read_to_vec,
     io::{stdin, AsyncReadExt},
@@ -11,9 +15,11 @@ use tokio::{
 
 pub(crate) mod gen;
 pub(crate) mod repl;
+pub(crate) mod roblox;
 pub(crate) mod setup;
 pub(crate) mod utils;
 
+use roblox::validate_roblox_file;
 use setup::run_setup;
 use utils::{
     files::{discover_script_path_including_lune_dirs, strip_shebang},
@@ -25,13 +31,19 @@ use utils::{
 #[command(version, long_about = None)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct Cli {
-    /// Script name or full path to the file to run
+    /// Script name or full path to the file to run, or a zip or tar bundle
     script_path: Option<String>,
     /// Arguments to pass to the script, stored in process.args
     script_args: Vec<String>,
     /// List scripts found inside of a nearby `lune` directory
     #[clap(long, short = 'l')]
     list: bool,
+    /// Run the script in deterministic mode, using a virtual clock and the given random seed
+    #[clap(long, value_name = "SEED", num_args = 0..=1, default_missing_value = "0")]
+    deterministic: Option<i32>,
+    /// Check a Roblox place or model file for structural problems, exiting with a failure if any are found
+    #[clap(long, value_name = "FILE")]
+    validate_roblox: Option<PathBuf>,
     /// Set up type definitions and settings for development
     #[clap(long)]
     setup: bool,
@@ -80,6 +92,11 @@ impl Cli {
 
     #[allow(clippy::too_many_lines)]
     pub async fn run(self) -> Result<ExitCode> {
+        // Validate a Roblox file, if wanted
+        // This will also exit early and not run anything else
+        if let Some(path) = self.validate_roblox {
+            return validate_roblox_file(path).await;
+        }
         // List files in `lune` and `.lune` directories, if wanted
         // This will also exit early and not run anything else
         if self.list {
@@ -155,6 +172,7 @@ impl Cli {
         // reading from stdin is marked by passing a single "-"
         // (dash) as the script name to run to the cli
         let script_path = self.script_path.unwrap();
+        let mut bundle = None;
         let (script_display_name, script_contents) = if script_path == "-" {
             let mut stdin_contents = Vec::new();
             stdin()
@@ -162,6 +180,17 @@ impl Cli {
                 .await
                 .context("Failed to read script contents from stdin")?;
             ("stdin".to_string(), stdin_contents)
+        } else if let Some(kind) = bundle_kind(&script_path) {
+            // Bundles are archives containing an entrypoint along with any other
+            // modules and files it needs, which we mount and run the entrypoint of
+            let archive = read_to_vec(&script_path)
+                .await
+                .with_context(|| format!("Failed to read bundle at '{script_path}'"))?;
+            let archive_bundle = read_bundle(kind, archive)
+                .with_context(|| format!("Failed to read bundle at '{script_path}'"))?;
+            let entrypoint = archive_bundle.entrypoint();
+            bundle = Some(archive_bundle);
+            entrypoint
         } else {
             let file_path = discover_script_path_including_lune_dirs(&script_path)?;
             let file_contents = read_to_vec(&file_path).await?;
@@ -170,8 +199,14 @@ impl Cli {
             (file_display_name, file_contents)
         };
         // Create a new lune object with all globals & run the script
-        let result = Lune::new()
-            .with_args(self.script_args)
+        let mut lune = Lune::new().with_args(self.script_args);
+        if let Some(bundle) = bundle {
+            lune = lune.with_bundle(bundle);
+        }
+        if let Some(seed) = self.deterministic {
+            lune = lune.with_deterministic_mode(seed)?;
+        }
+        let result = lune
             .run(&script_display_name, strip_shebang(script_contents))
             .await;
         Ok(match result {
@@ -183,3 +218,34 @@ impl Cli {
         })
     }
 }
+
+/**
+    Reads a bundle from the contents of an archive of the given kind.
+*/
+#[cfg(feature = "archive")]
+fn read_bundle(kind: &str, archive: Vec<u8>) -> Result<Bundle> {
+    Ok(match kind {
+        "zip" => Bundle::from_zip(archive),
+        _ => Bundle::from_tar(archive),
+    }?)
+}
+
+#[cfg(not(feature = "archive"))]
+fn read_bundle(_kind: &str, _archive: Vec<u8>) -> Result<Bundle> {
+    anyhow::bail!("Lune was built without support for bundles, which requires the archive feature")
+}
+
+/**
+    Gets the kind of archive that the given path is, if it is a bundle that can be ran.
+*/
+fn bundle_kind(path: &str) -> Option<&'static str> {
+    let path = Path::new(path);
+    if !path.is_file() {
+        return None;
+    }
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("zip") => Some("zip"),
+        Some(ext) if ext.eq_ignore_ascii_case("tar") => Some("tar"),
+        _ => None,
+    }
+}
@@ -0,0 +1,44 @@
+use std::{path::PathBuf, process::ExitCode};
+
+use anyhow::Result;
+
+/**
+    Checks a place or model file for structural problems, printing any that were found.
+
+    Exits with a failure if there were any problems, for use in CI.
+*/
+#[cfg(feature = "roblox")]
+pub async fn validate_roblox_file(path: PathBuf) -> Result<ExitCode> {
+    use anyhow::Context;
+    use lune::roblox::document::{Document, DocumentKind};
+
+    let contents = tokio::fs::read(&path)
+        .await
+        .with_context(|| format!("Failed to read file at '{}'", path.display()))?;
+    let problems = tokio::task::spawn_blocking(move || {
+        // NOTE: The kind of document does not matter for validation
+        let doc = Document::from_bytes(contents, DocumentKind::Model)?;
+        Ok::<_, anyhow::Error>(doc.validate())
+    })
+    .await??;
+
+    if problems.is_empty() {
+        println!("No problems found in '{}'", path.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+    for problem in &problems {
+        println!("{problem}");
+    }
+    eprintln!(
+        "Found {} problem{} in '{}'",
+        problems.len(),
+        if problems.len() == 1 { "" } else { "s" },
+        path.display()
+    );
+    Ok(ExitCode::FAILURE)
+}
+
+#[cfg(not(feature = "roblox"))]
+pub async fn validate_roblox_file(_: PathBuf) -> Result<ExitCode> {
+    anyhow::bail!("Lune was built without support for Roblox files")
+}
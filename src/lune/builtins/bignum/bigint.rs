@@ -0,0 +1,171 @@
+use std::str::FromStr;
+
+use mlua::prelude::*;
+use num_bigint::BigInt;
+use num_traits::{Num, Signed, ToPrimitive};
+use serde::{Serialize, Serializer};
+
+use super::{arith, compare, Operand};
+
+/**
+    The largest integer that Luau numbers can represent exactly, 2^53.
+*/
+pub const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/**
+    An integer of any size, created using `bignum.bigint`.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LuaBigInt(pub BigInt);
+
+impl LuaBigInt {
+    /**
+        Creates a big integer from a number, string, or another big number,
+        erroring if the value is not an integer, or would lose precision.
+    */
+    pub fn from_value(value: &LuaValue) -> LuaResult<Self> {
+        match value {
+            LuaValue::Integer(i) => Ok(Self(BigInt::from(*i))),
+            LuaValue::Number(n) => Self::from_number(*n),
+            LuaValue::String(s) => Self::parse(s.to_str()?),
+            LuaValue::UserData(ud) => match Operand::from_userdata(ud)? {
+                Operand::Int(i) => Ok(i),
+                Operand::Dec(d) => {
+                    if d.0.fract().is_zero() {
+                        Self::parse(&d.0.trunc().to_string())
+                    } else {
+                        Err(LuaError::RuntimeError(format!(
+                            "Invalid bigint - {} is not an integer",
+                            d.0
+                        )))
+                    }
+                }
+            },
+            value => Err(LuaError::RuntimeError(format!(
+                "Invalid bigint - expected number, string, bigint or decimal, got {}",
+                value.type_name()
+            ))),
+        }
+    }
+
+    pub fn from_number(n: f64) -> LuaResult<Self> {
+        if !n.is_finite() || n.fract() != 0.0 {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid bigint - {n} is not an integer"
+            )));
+        }
+        if n.abs() > MAX_SAFE_INTEGER {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid bigint - {n} is too large to be exact, use a string instead"
+            )));
+        }
+        Ok(Self(BigInt::from(n as i64)))
+    }
+
+    /**
+        Parses a big integer from a string, which may be
+        in decimal, or in hexadecimal with a `0x` prefix.
+    */
+    pub fn parse(s: &str) -> LuaResult<Self> {
+        let trimmed = s.trim().replace('_', "");
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(&trimmed)),
+        };
+        let parsed = match digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            Some(hex) => BigInt::from_str_radix(hex, 16),
+            None => BigInt::from_str(digits),
+        };
+        match parsed {
+            Ok(n) if !digits.starts_with(['-', '+']) => Ok(Self(if negative { -n } else { n })),
+            _ => Err(LuaError::RuntimeError(format!(
+                "Invalid bigint - '{s}' is not an integer"
+            ))),
+        }
+    }
+
+    pub fn into_lua_value(self, lua: &Lua) -> LuaResult<LuaValue> {
+        lua.create_ser_userdata(self).map(LuaValue::UserData)
+    }
+
+    /**
+        Converts to a number, which may lose precision for integers larger than 2^53.
+    */
+    pub fn to_number(&self) -> f64 {
+        self.0.to_f64().unwrap_or(f64::NAN)
+    }
+}
+
+impl Serialize for LuaBigInt {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // NOTE: Integers that fit in 64 bits are kept as integers, which serializers
+        // write out exactly, and any larger integers are written out as strings
+        if let Some(i) = self.0.to_i64() {
+            serializer.serialize_i64(i)
+        } else if let Some(u) = self.0.to_u64() {
+            serializer.serialize_u64(u)
+        } else {
+            serializer.serialize_str(&self.0.to_string())
+        }
+    }
+}
+
+impl LuaUserData for LuaBigInt {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("toNumber", |_, this, ()| Ok(this.to_number()));
+        methods.add_method("toString", |_, this, radix: Option<u32>| {
+            match radix.unwrap_or(10) {
+                radix @ 2..=36 => Ok(this.0.to_str_radix(radix)),
+                radix => Err(LuaError::RuntimeError(format!(
+                    "Invalid radix {radix} - expected a number between 2 and 36"
+                ))),
+            }
+        });
+        methods.add_method("abs", |lua, this, ()| {
+            Self(this.0.abs()).into_lua_value(lua)
+        });
+        methods.add_method("sign", |_, this, ()| {
+            Ok(if this.0.is_positive() {
+                1
+            } else if this.0.is_negative() {
+                -1
+            } else {
+                0
+            })
+        });
+        methods.add_method("isSafe", |_, this, ()| {
+            Ok(this.0.abs() <= BigInt::from(MAX_SAFE_INTEGER as i64))
+        });
+
+        // NOTE: The version of Luau we use does not have the floor division operator yet
+        methods.add_function("idiv", |lua, (a, b)| arith(lua, "//", a, b));
+
+        methods.add_meta_function(LuaMetaMethod::Add, |lua, (a, b)| arith(lua, "+", a, b));
+        methods.add_meta_function(LuaMetaMethod::Sub, |lua, (a, b)| arith(lua, "-", a, b));
+        methods.add_meta_function(LuaMetaMethod::Mul, |lua, (a, b)| arith(lua, "*", a, b));
+        methods.add_meta_function(LuaMetaMethod::Div, |lua, (a, b)| arith(lua, "/", a, b));
+        methods.add_meta_function(LuaMetaMethod::Mod, |lua, (a, b)| arith(lua, "%", a, b));
+        methods.add_meta_function(LuaMetaMethod::Pow, |lua, (a, b)| arith(lua, "^", a, b));
+        methods.add_meta_method(LuaMetaMethod::Unm, |lua, this, ()| {
+            Self(-this.0.clone()).into_lua_value(lua)
+        });
+        methods.add_meta_function(LuaMetaMethod::Eq, |_, (a, b)| {
+            compare(a, b).map(|o| o.is_eq())
+        });
+        methods.add_meta_function(LuaMetaMethod::Lt, |_, (a, b)| {
+            compare(a, b).map(|o| o.is_lt())
+        });
+        methods.add_meta_function(LuaMetaMethod::Le, |_, (a, b)| {
+            compare(a, b).map(|o| o.is_le())
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(this.0.to_string())
+        });
+        methods.add_meta_function(LuaMetaMethod::Concat, |lua, (a, b)| {
+            super::concat(lua, a, b)
+        });
+    }
+}
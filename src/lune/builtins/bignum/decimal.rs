@@ -0,0 +1,143 @@
+use mlua::prelude::*;
+use num_traits::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Serialize, Serializer};
+
+use super::{arith, compare, Operand};
+
+/**
+    A decimal number with up to 28 significant digits,
+    which is exact for values such as `0.1`, created
+    using `bignum.decimal`.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LuaDecimal(pub Decimal);
+
+impl LuaDecimal {
+    /**
+        Creates a decimal from a number, string, or another big number.
+
+        Numbers are converted using their shortest representation, so that
+        `0.1` becomes exactly `0.1`, and not the closest binary float to it.
+    */
+    pub fn from_value(value: &LuaValue) -> LuaResult<Self> {
+        match value {
+            LuaValue::Integer(i) => Ok(Self(Decimal::from(*i))),
+            LuaValue::Number(n) => Self::from_number(*n),
+            LuaValue::String(s) => Self::parse(s.to_str()?),
+            LuaValue::UserData(ud) => match Operand::from_userdata(ud)? {
+                Operand::Int(i) => Self::parse(&i.0.to_string()),
+                Operand::Dec(d) => Ok(d),
+            },
+            value => Err(LuaError::RuntimeError(format!(
+                "Invalid decimal - expected number, string, bigint or decimal, got {}",
+                value.type_name()
+            ))),
+        }
+    }
+
+    pub fn from_number(n: f64) -> LuaResult<Self> {
+        if !n.is_finite() {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid decimal - {n} is not a finite number"
+            )));
+        }
+        Self::parse(&n.to_string())
+    }
+
+    pub fn parse(s: &str) -> LuaResult<Self> {
+        let trimmed = s.trim().replace('_', "");
+        Decimal::from_str_exact(&trimmed)
+            .or_else(|_| Decimal::from_scientific(&trimmed))
+            .map(Self)
+            .map_err(|e| LuaError::RuntimeError(format!("Invalid decimal '{s}' - {e}")))
+    }
+
+    pub fn into_lua_value(self, lua: &Lua) -> LuaResult<LuaValue> {
+        lua.create_ser_userdata(self).map(LuaValue::UserData)
+    }
+}
+
+impl Serialize for LuaDecimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // NOTE: Decimals are written out as strings, since most formats
+        // would otherwise store them as floats and lose their precision
+        serializer.serialize_str(&self.0.normalize().to_string())
+    }
+}
+
+fn rounding_strategy(mode: Option<String>) -> LuaResult<RoundingStrategy> {
+    Ok(match mode.as_deref() {
+        None | Some("half-even") => RoundingStrategy::MidpointNearestEven,
+        Some("half-up") => RoundingStrategy::MidpointAwayFromZero,
+        Some("half-down") => RoundingStrategy::MidpointTowardZero,
+        Some("up") => RoundingStrategy::AwayFromZero,
+        Some("down") => RoundingStrategy::ToZero,
+        Some("ceil") => RoundingStrategy::ToPositiveInfinity,
+        Some("floor") => RoundingStrategy::ToNegativeInfinity,
+        Some(mode) => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid rounding mode '{mode}' - expected one of 'half-even', \
+                'half-up', 'half-down', 'up', 'down', 'ceil' or 'floor'"
+            )))
+        }
+    })
+}
+
+impl LuaUserData for LuaDecimal {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("toNumber", |_, this, ()| {
+            Ok(this.0.to_f64().unwrap_or(f64::NAN))
+        });
+        methods.add_method("toString", |_, this, ()| Ok(this.0.to_string()));
+        methods.add_method(
+            "round",
+            |lua, this, (places, mode): (Option<u32>, Option<String>)| {
+                let strategy = rounding_strategy(mode)?;
+                let rounded = this.0.round_dp_with_strategy(places.unwrap_or(0), strategy);
+                Self(rounded).into_lua_value(lua)
+            },
+        );
+        methods.add_method("abs", |lua, this, ()| {
+            Self(this.0.abs()).into_lua_value(lua)
+        });
+        methods.add_method("scale", |_, this, ()| Ok(this.0.scale()));
+        methods.add_method("sign", |_, this, ()| {
+            Ok(if this.0.is_zero() {
+                0
+            } else if this.0.is_sign_negative() {
+                -1
+            } else {
+                1
+            })
+        });
+
+        // NOTE: The version of Luau we use does not have the floor division operator yet
+        methods.add_function("idiv", |lua, (a, b)| arith(lua, "//", a, b));
+
+        methods.add_meta_function(LuaMetaMethod::Add, |lua, (a, b)| arith(lua, "+", a, b));
+        methods.add_meta_function(LuaMetaMethod::Sub, |lua, (a, b)| arith(lua, "-", a, b));
+        methods.add_meta_function(LuaMetaMethod::Mul, |lua, (a, b)| arith(lua, "*", a, b));
+        methods.add_meta_function(LuaMetaMethod::Div, |lua, (a, b)| arith(lua, "/", a, b));
+        methods.add_meta_function(LuaMetaMethod::Mod, |lua, (a, b)| arith(lua, "%", a, b));
+        methods.add_meta_function(LuaMetaMethod::Pow, |lua, (a, b)| arith(lua, "^", a, b));
+        methods.add_meta_method(LuaMetaMethod::Unm, |lua, this, ()| {
+            Self(-this.0).into_lua_value(lua)
+        });
+        methods.add_meta_function(LuaMetaMethod::Eq, |_, (a, b)| {
+            compare(a, b).map(|o| o.is_eq())
+        });
+        methods.add_meta_function(LuaMetaMethod::Lt, |_, (a, b)| {
+            compare(a, b).map(|o| o.is_lt())
+        });
+        methods.add_meta_function(LuaMetaMethod::Le, |_, (a, b)| {
+            compare(a, b).map(|o| o.is_le())
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(this.0.to_string())
+        });
+        methods.add_meta_function(LuaMetaMethod::Concat, |lua, (a, b)| {
+            super::concat(lua, a, b)
+        });
+    }
+}
@@ -0,0 +1,249 @@
+use std::cmp::Ordering;
+
+use mlua::prelude::*;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Signed, ToPrimitive, Zero};
+use rust_decimal::Decimal;
+
+use crate::lune::util::TableBuilder;
+
+mod bigint;
+mod decimal;
+
+pub(super) use bigint::{LuaBigInt, MAX_SAFE_INTEGER};
+use decimal::LuaDecimal;
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("bigint", |lua, value: LuaValue| {
+            LuaBigInt::from_value(&value)?.into_lua_value(lua)
+        })?
+        .with_function("decimal", |lua, value: LuaValue| {
+            LuaDecimal::from_value(&value)?.into_lua_value(lua)
+        })?
+        .with_function("isBigInt", |_, value: LuaValue| {
+            Ok(matches!(value, LuaValue::UserData(ud) if ud.is::<LuaBigInt>()))
+        })?
+        .with_function("isDecimal", |_, value: LuaValue| {
+            Ok(matches!(value, LuaValue::UserData(ud) if ud.is::<LuaDecimal>()))
+        })?
+        .build_readonly()
+}
+
+/**
+    One side of an arithmetic operation or comparison,
+    after converting any plain numbers into big numbers.
+*/
+#[derive(Debug, Clone)]
+enum Operand {
+    Int(LuaBigInt),
+    Dec(LuaDecimal),
+}
+
+impl Operand {
+    fn from_userdata(ud: &LuaAnyUserData) -> LuaResult<Self> {
+        if let Ok(i) = ud.borrow::<LuaBigInt>() {
+            Ok(Self::Int(i.clone()))
+        } else if let Ok(d) = ud.borrow::<LuaDecimal>() {
+            Ok(Self::Dec(*d))
+        } else {
+            Err(LuaError::RuntimeError(
+                "Invalid operand - expected number, bigint or decimal, got userdata".to_string(),
+            ))
+        }
+    }
+
+    /**
+        Converts a value into an operand, where numbers that are
+        integers become bigints, and any other numbers become decimals.
+    */
+    fn from_value(value: &LuaValue) -> LuaResult<Self> {
+        match value {
+            LuaValue::UserData(ud) => Self::from_userdata(ud),
+            LuaValue::Integer(i) => Ok(Self::Int(LuaBigInt(BigInt::from(*i)))),
+            LuaValue::Number(n) if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER => {
+                Ok(Self::Int(LuaBigInt(BigInt::from(*n as i64))))
+            }
+            LuaValue::Number(n) => LuaDecimal::from_number(*n).map(Self::Dec),
+            value => Err(LuaError::RuntimeError(format!(
+                "Invalid operand - expected number, bigint or decimal, got {}",
+                value.type_name()
+            ))),
+        }
+    }
+
+    fn to_decimal(&self) -> LuaResult<Decimal> {
+        match self {
+            Self::Dec(d) => Ok(d.0),
+            Self::Int(i) => int_to_decimal(&i.0).ok_or_else(|| {
+                LuaError::RuntimeError(format!(
+                    "Failed to convert bigint {} to decimal - value is too large",
+                    i.0
+                ))
+            }),
+        }
+    }
+}
+
+fn int_to_decimal(i: &BigInt) -> Option<Decimal> {
+    Decimal::from_str_exact(&i.to_string()).ok()
+}
+
+fn overflow(op: &str) -> LuaError {
+    LuaError::RuntimeError(format!(
+        "Failed to compute '{op}' - result does not fit in a decimal"
+    ))
+}
+
+fn division_by_zero() -> LuaError {
+    LuaError::RuntimeError("Failed to divide - division by zero".to_string())
+}
+
+/**
+    Applies an arithmetic operator to two values, at least one of which is a big number.
+
+    Operations on two bigints give a bigint, except for `/` which gives a
+    decimal, and operations involving any decimal give a decimal.
+    Both `//` and `%` round towards negative infinity, same as for numbers.
+*/
+fn arith<'lua>(
+    lua: &'lua Lua,
+    op: &str,
+    a: LuaValue<'lua>,
+    b: LuaValue<'lua>,
+) -> LuaResult<LuaValue<'lua>> {
+    let a = Operand::from_value(&a)?;
+    let b = Operand::from_value(&b)?;
+    match (&a, &b) {
+        (Operand::Int(x), Operand::Int(y)) if op != "/" => {
+            let (x, y) = (&x.0, &y.0);
+            let result = match op {
+                "+" => x + y,
+                "-" => x - y,
+                "*" => x * y,
+                "//" | "%" if y.is_zero() => return Err(division_by_zero()),
+                "//" => x.div_floor(y),
+                "%" => x.mod_floor(y),
+                "^" => {
+                    let exp = match y.to_u32() {
+                        Some(exp) => exp,
+                        None => {
+                            return Err(LuaError::RuntimeError(format!(
+                                "Invalid exponent {y} for bigint - \
+                                expected a non-negative integer"
+                            )))
+                        }
+                    };
+                    num_traits::pow(x.clone(), exp as usize)
+                }
+                _ => unreachable!("unknown arithmetic operator '{op}'"),
+            };
+            LuaBigInt(result).into_lua_value(lua)
+        }
+        _ => {
+            let (x, y) = (a.to_decimal()?, b.to_decimal()?);
+            let result = match op {
+                "+" => x.checked_add(y),
+                "-" => x.checked_sub(y),
+                "*" => x.checked_mul(y),
+                "/" | "//" | "%" if y.is_zero() => return Err(division_by_zero()),
+                "/" => x.checked_div(y),
+                "//" => x.checked_div(y).map(|q| q.floor()),
+                "%" => x
+                    .checked_div(y)
+                    .and_then(|q| y.checked_mul(q.floor()))
+                    .and_then(|m| x.checked_sub(m)),
+                "^" => decimal_pow(x, y)?,
+                _ => unreachable!("unknown arithmetic operator '{op}'"),
+            };
+            LuaDecimal(result.ok_or_else(|| overflow(op))?.normalize()).into_lua_value(lua)
+        }
+    }
+}
+
+/**
+    Raises a decimal to an integer power, using repeated squaring.
+*/
+fn decimal_pow(base: Decimal, exp: Decimal) -> LuaResult<Option<Decimal>> {
+    let exp = match exp.to_i64() {
+        Some(i) if exp.fract().is_zero() => i,
+        _ => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid exponent {exp} for decimal - expected an integer"
+            )))
+        }
+    };
+    if exp < 0 && base.is_zero() {
+        return Err(division_by_zero());
+    }
+    let mut result = Some(Decimal::ONE);
+    let mut square = Some(base);
+    let mut remaining = exp.unsigned_abs();
+    while remaining > 0 {
+        if remaining & 1 == 1 {
+            result = result.zip(square).and_then(|(r, s)| r.checked_mul(s));
+        }
+        remaining >>= 1;
+        if remaining > 0 {
+            square = square.and_then(|s| s.checked_mul(s));
+        }
+    }
+    Ok(if exp < 0 {
+        result.and_then(|r| Decimal::ONE.checked_div(r))
+    } else {
+        result
+    })
+}
+
+/**
+    Compares two values, at least one of which is a big number.
+*/
+fn compare(a: LuaValue, b: LuaValue) -> LuaResult<Ordering> {
+    let a = Operand::from_value(&a)?;
+    let b = Operand::from_value(&b)?;
+    Ok(match (&a, &b) {
+        (Operand::Int(x), Operand::Int(y)) => x.0.cmp(&y.0),
+        (Operand::Dec(x), Operand::Dec(y)) => x.0.cmp(&y.0),
+        // NOTE: Bigints that are too large for a decimal are
+        // larger in magnitude than any decimal could ever be
+        (Operand::Int(x), Operand::Dec(y)) => match int_to_decimal(&x.0) {
+            Some(x) => x.cmp(&y.0),
+            None if x.0.is_negative() => Ordering::Less,
+            None => Ordering::Greater,
+        },
+        (Operand::Dec(x), Operand::Int(y)) => match int_to_decimal(&y.0) {
+            Some(y) => x.0.cmp(&y),
+            None if y.0.is_negative() => Ordering::Greater,
+            None => Ordering::Less,
+        },
+    })
+}
+
+/**
+    Concatenates two values, at least one of which is a big number, as strings.
+*/
+fn concat<'lua>(
+    lua: &'lua Lua,
+    a: LuaValue<'lua>,
+    b: LuaValue<'lua>,
+) -> LuaResult<LuaString<'lua>> {
+    let to_bytes = |value: LuaValue<'lua>| -> LuaResult<Vec<u8>> {
+        match value {
+            LuaValue::UserData(ud) => Ok(match Operand::from_userdata(&ud)? {
+                Operand::Int(i) => i.0.to_string().into_bytes(),
+                Operand::Dec(d) => d.0.to_string().into_bytes(),
+            }),
+            value => match lua.coerce_string(value.clone())? {
+                Some(s) => Ok(s.as_bytes().to_vec()),
+                None => Err(LuaError::RuntimeError(format!(
+                    "Failed to concatenate - expected string or number, got {}",
+                    value.type_name()
+                ))),
+            },
+        }
+    };
+    let mut bytes = to_bytes(a)?;
+    bytes.extend(to_bytes(b)?);
+    lua.create_string(bytes)
+}
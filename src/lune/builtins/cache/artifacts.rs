@@ -0,0 +1,371 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use mlua::prelude::*;
+use ring::digest::{self, Context};
+use tokio::fs;
+
+use crate::lune::{
+    scheduler::Scheduler,
+    util::{cache_dir, TableBuilder},
+};
+
+/**
+    Default maximum total size of all artifacts, in bytes.
+*/
+const DEFAULT_MAX_SIZE: u64 = 1024 * 1024 * 1024;
+
+/**
+    A key for an artifact, which is a hash of one or more inputs.
+
+    A single string is the same key as a list containing only that string.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactKey(String);
+
+impl ArtifactKey {
+    fn from_parts<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut context = Context::new(&digest::SHA256);
+        for part in parts {
+            // NOTE: Each part is prefixed with its length, so that
+            // { "ab", "c" } and { "a", "bc" } give different keys
+            context.update(&(part.len() as u64).to_le_bytes());
+            context.update(part);
+        }
+        let hex = context
+            .finish()
+            .as_ref()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        Self(hex)
+    }
+
+    fn relative_path(&self) -> PathBuf {
+        PathBuf::from(&self.0[..2]).join(&self.0)
+    }
+}
+
+impl<'lua> FromLua<'lua> for ArtifactKey {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::String(s) => Ok(Self::from_parts([s.as_bytes()])),
+            LuaValue::Table(t) => {
+                let parts = t
+                    .sequence_values::<LuaValue>()
+                    .map(|part| match lua.coerce_string(part?)? {
+                        Some(s) => Ok(s),
+                        None => Err(LuaError::RuntimeError(
+                            "Invalid artifact key - all inputs must be strings or numbers"
+                                .to_string(),
+                        )),
+                    })
+                    .collect::<LuaResult<Vec<_>>>()?;
+                if parts.is_empty() {
+                    return Err(LuaError::RuntimeError(
+                        "Invalid artifact key - expected at least one input".to_string(),
+                    ));
+                }
+                Ok(Self::from_parts(parts.iter().map(|s| s.as_bytes())))
+            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ArtifactKey",
+                message: Some(format!(
+                    "Invalid artifact key - expected string or table, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ArtifactCacheConfig {
+    dir: Option<PathBuf>,
+    max_size: u64,
+    max_age: Option<Duration>,
+}
+
+impl Default for ArtifactCacheConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_size: DEFAULT_MAX_SIZE,
+            max_age: None,
+        }
+    }
+}
+
+/**
+    A content-addressed cache for artifacts, stored on disk.
+
+    Artifacts that have not been used for the longest time are evicted
+    first, once the total size of all artifacts goes above the max size.
+*/
+#[derive(Debug, Default)]
+pub struct ArtifactCache {
+    config: Mutex<ArtifactCacheConfig>,
+}
+
+impl ArtifactCache {
+    fn config(&self) -> ArtifactCacheConfig {
+        self.config
+            .lock()
+            .expect("Failed to lock artifact cache config")
+            .clone()
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn configure(&self, options: LuaTable) -> LuaResult<()> {
+        let mut config = self
+            .config
+            .lock()
+            .expect("Failed to lock artifact cache config");
+        if let Some(dir) = options.raw_get::<_, Option<String>>("dir")? {
+            config.dir = Some(PathBuf::from(dir));
+        }
+        if let Some(max_size) = options.raw_get::<_, Option<f64>>("maxSize")? {
+            if !max_size.is_finite() || max_size < 0.0 {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid maxSize '{max_size}' - expected a positive number of bytes"
+                )));
+            }
+            config.max_size = max_size as u64;
+        }
+        if let Some(max_age) = options.raw_get::<_, Option<f64>>("maxAge")? {
+            config.max_age = Some(Duration::try_from_secs_f64(max_age).map_err(|_| {
+                LuaError::RuntimeError(format!(
+                    "Invalid maxAge '{max_age}' - expected a positive number of seconds"
+                ))
+            })?);
+        }
+        Ok(())
+    }
+
+    fn dir(&self) -> LuaResult<PathBuf> {
+        match self.config().dir {
+            Some(dir) => Ok(dir),
+            None => cache_dir("artifacts"),
+        }
+    }
+
+    fn is_expired(&self, modified: SystemTime) -> bool {
+        match self.config().max_age {
+            Some(max_age) => modified.elapsed().is_ok_and(|elapsed| elapsed > max_age),
+            None => false,
+        }
+    }
+
+    async fn get(&self, key: &ArtifactKey) -> LuaResult<Option<Vec<u8>>> {
+        let path = self.dir()?.join(key.relative_path());
+        let meta = match fs::metadata(&path).await {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if meta.modified().is_ok_and(|m| self.is_expired(m)) {
+            remove_if_exists(&path).await?;
+            return Ok(None);
+        }
+        let contents = match fs::read(&path).await {
+            Ok(contents) => contents,
+            // NOTE: Another process may have evicted the artifact in the meantime
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        touch(&path).await;
+        Ok(Some(contents))
+    }
+
+    async fn set(&self, key: &ArtifactKey, contents: &[u8]) -> LuaResult<()> {
+        let path = self.dir()?.join(key.relative_path());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        // NOTE: We write to a temporary file first and rename it so
+        // that other processes never see a partially written artifact
+        let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temp_path, contents).await?;
+        fs::rename(&temp_path, &path).await?;
+        self.evict().await?;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &ArtifactKey) -> LuaResult<bool> {
+        let path = self.dir()?.join(key.relative_path());
+        remove_if_exists(&path).await
+    }
+
+    async fn clear(&self) -> LuaResult<usize> {
+        let mut removed = 0;
+        for (path, _, _) in list_artifacts(&self.dir()?).await? {
+            if remove_if_exists(&path).await? {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /**
+        Removes all expired artifacts, and then the least recently used
+        artifacts until the total size is below the maximum size.
+
+        Returns the number of artifacts that were removed.
+    */
+    async fn evict(&self) -> LuaResult<usize> {
+        let config = self.config();
+        let artifacts = list_artifacts(&self.dir()?).await?;
+        let mut removed = 0;
+
+        let mut kept = Vec::with_capacity(artifacts.len());
+        for (path, size, modified) in artifacts {
+            if self.is_expired(modified) {
+                if remove_if_exists(&path).await? {
+                    removed += 1;
+                }
+            } else {
+                kept.push((path, size, modified));
+            }
+        }
+
+        kept.sort_by_key(|(_, _, modified)| *modified);
+        let mut total_size = kept.iter().map(|(_, size, _)| size).sum::<u64>();
+        for (path, size, _) in kept {
+            if total_size <= config.max_size {
+                break;
+            }
+            if remove_if_exists(&path).await? {
+                removed += 1;
+            }
+            total_size -= size;
+        }
+
+        Ok(removed)
+    }
+}
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    let cache = Arc::new(ArtifactCache::default());
+    let cache_get_or_compute = Arc::clone(&cache);
+    let cache_get = Arc::clone(&cache);
+    let cache_remove = Arc::clone(&cache);
+    let cache_clear = Arc::clone(&cache);
+    let cache_evict = Arc::clone(&cache);
+    TableBuilder::new(lua)?
+        .with_async_function(
+            "getOrCompute",
+            move |lua, (key, compute): (ArtifactKey, LuaFunction)| {
+                let cache = Arc::clone(&cache_get_or_compute);
+                async move { artifacts_get_or_compute(lua, &cache, key, compute).await }
+            },
+        )?
+        .with_async_function("get", move |lua, key: ArtifactKey| {
+            let cache = Arc::clone(&cache_get);
+            async move {
+                match cache.get(&key).await? {
+                    Some(contents) => Ok(Some(lua.create_string(contents)?)),
+                    None => Ok(None),
+                }
+            }
+        })?
+        .with_async_function("remove", move |_, key: ArtifactKey| {
+            let cache = Arc::clone(&cache_remove);
+            async move { cache.remove(&key).await }
+        })?
+        .with_async_function("clear", move |_, _: ()| {
+            let cache = Arc::clone(&cache_clear);
+            async move { cache.clear().await }
+        })?
+        .with_async_function("evict", move |_, _: ()| {
+            let cache = Arc::clone(&cache_evict);
+            async move { cache.evict().await }
+        })?
+        .with_function("configure", move |_, options: LuaTable| {
+            cache.configure(options)
+        })?
+        .build_readonly()
+}
+
+async fn artifacts_get_or_compute<'lua>(
+    lua: &'lua Lua,
+    cache: &ArtifactCache,
+    key: ArtifactKey,
+    compute: LuaFunction<'lua>,
+) -> LuaResult<LuaString<'lua>> {
+    if let Some(contents) = cache.get(&key).await? {
+        return lua.create_string(contents);
+    }
+
+    let sched = *lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    let thread_id = sched.push_back(lua, compute, ())?;
+    let thread_res = sched.wait_for_thread(lua, thread_id).await?;
+    let contents = match thread_res.into_iter().next() {
+        Some(LuaValue::String(s)) => s,
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Artifact compute function must return a string, got {}",
+                value.as_ref().map_or("nil", LuaValue::type_name)
+            )))
+        }
+    };
+
+    cache.set(&key, contents.as_bytes()).await?;
+    Ok(contents)
+}
+
+/**
+    Lists all artifacts in the given cache directory,
+    together with their sizes and last modified times.
+*/
+async fn list_artifacts(dir: &Path) -> LuaResult<Vec<(PathBuf, u64, SystemTime)>> {
+    let mut artifacts = Vec::new();
+    let mut prefixes = match fs::read_dir(dir).await {
+        Ok(prefixes) => prefixes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(artifacts),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(prefix) = prefixes.next_entry().await? {
+        if !prefix.file_type().await?.is_dir() {
+            continue;
+        }
+        let mut entries = fs::read_dir(prefix.path()).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            // NOTE: Temporary files may be in the middle of being written by another process
+            if path.extension().is_some() {
+                continue;
+            }
+            let meta = entry.metadata().await?;
+            if meta.is_file() {
+                artifacts.push((path, meta.len(), meta.modified()?));
+            }
+        }
+    }
+    Ok(artifacts)
+}
+
+async fn remove_if_exists(path: &Path) -> LuaResult<bool> {
+    match fs::remove_file(path).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/**
+    Marks the artifact at the given path as recently used.
+
+    Failing to do so only affects the order of eviction, so any errors are ignored.
+*/
+async fn touch(path: &Path) {
+    if let Ok(file) = fs::OpenOptions::new().write(true).open(path).await {
+        let _ = file.into_std().await.set_modified(SystemTime::now());
+    }
+}
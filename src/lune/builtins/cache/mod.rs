@@ -0,0 +1,11 @@
+use mlua::prelude::*;
+
+use crate::lune::util::TableBuilder;
+
+mod artifacts;
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_value("artifacts", artifacts::create(lua)?)?
+        .build_readonly()
+}
@@ -0,0 +1,152 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use mlua::prelude::*;
+
+use super::value::Value;
+
+/**
+    Which values are popped first from a heap.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeapOrder {
+    #[default]
+    Min,
+    Max,
+}
+
+impl<'lua> FromLua<'lua> for HeapOrder {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::String(s) => match s.to_str()? {
+                "min" => Ok(Self::Min),
+                "max" => Ok(Self::Max),
+                other => Err(LuaError::RuntimeError(format!(
+                    "Invalid heap order '{other}' - expected 'min' or 'max'"
+                ))),
+            },
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "HeapOrder",
+                message: Some("Expected 'min' or 'max'".to_string()),
+            }),
+        }
+    }
+}
+
+/**
+    An entry in a heap, ordered so that the entry that should be popped next is the greatest.
+*/
+#[derive(Debug)]
+struct HeapEntry {
+    order: HeapOrder,
+    priority: f64,
+    sequence: u64,
+    value: Value,
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let priority = match self.order {
+            HeapOrder::Min => other.priority.total_cmp(&self.priority),
+            HeapOrder::Max => self.priority.total_cmp(&other.priority),
+        };
+        // NOTE: Earlier entries have lower sequence numbers and should be popped first
+        priority.then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+/**
+    A binary heap of values with priorities, created using `collections.heap`.
+
+    Values with the same priority are popped in the order they were pushed in.
+*/
+#[derive(Debug, Default)]
+pub struct LuaHeap {
+    order: HeapOrder,
+    entries: BinaryHeap<HeapEntry>,
+    next_sequence: u64,
+}
+
+impl LuaHeap {
+    pub fn new(order: HeapOrder) -> Self {
+        Self {
+            order,
+            ..Self::default()
+        }
+    }
+
+    fn push(&mut self, lua: &Lua, value: LuaValue, priority: f64) -> LuaResult<()> {
+        if priority.is_nan() {
+            return Err(LuaError::RuntimeError(
+                "Invalid priority - NaN can not be used as a priority".to_string(),
+            ));
+        }
+        let value = match Value::from_lua_value(lua, value)? {
+            Some(value) => value,
+            None => {
+                return Err(LuaError::RuntimeError(
+                    "Invalid value - nil can not be pushed to a heap".to_string(),
+                ))
+            }
+        };
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.entries.push(HeapEntry {
+            order: self.order,
+            priority,
+            sequence,
+            value,
+        });
+        Ok(())
+    }
+}
+
+fn entry_to_lua<'lua>(
+    lua: &'lua Lua,
+    entry: Option<&HeapEntry>,
+) -> LuaResult<(LuaValue<'lua>, Option<f64>)> {
+    match entry {
+        Some(entry) => Ok((entry.value.to_lua_value(lua)?, Some(entry.priority))),
+        None => Ok((LuaValue::Nil, None)),
+    }
+}
+
+impl LuaUserData for LuaHeap {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("push", |lua, this, (value, priority): (LuaValue, f64)| {
+            this.push(lua, value, priority)
+        });
+        methods.add_method_mut("pop", |lua, this, ()| {
+            let entry = this.entries.pop();
+            entry_to_lua(lua, entry.as_ref())
+        });
+        methods.add_method("peek", |lua, this, ()| {
+            entry_to_lua(lua, this.entries.peek())
+        });
+        methods.add_method("len", |_, this, ()| Ok(this.entries.len()));
+        methods.add_method("isEmpty", |_, this, ()| Ok(this.entries.is_empty()));
+        methods.add_method_mut("clear", |_, this, ()| {
+            this.entries.clear();
+            Ok(())
+        });
+        methods.add_meta_method(LuaMetaMethod::Len, |_, this, ()| Ok(this.entries.len()));
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("Heap({})", this.entries.len()))
+        });
+    }
+}
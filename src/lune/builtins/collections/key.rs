@@ -0,0 +1,79 @@
+use std::cmp::Ordering;
+
+use mlua::prelude::*;
+
+/**
+    A key in one of the collections, which may be a string or a number.
+
+    Numbers are stored using their bits, with negative zero
+    normalized to zero, so that they can be hashed and compared.
+
+    Keys are ordered with all numbers before all strings, numbers
+    by their value, and strings by their bytes.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Number(u64),
+    String(Box<[u8]>),
+}
+
+impl Key {
+    pub fn from_lua_value(value: &LuaValue) -> LuaResult<Self> {
+        match value {
+            LuaValue::Integer(i) => Ok(Self::from_number(*i as f64)),
+            LuaValue::Number(n) if n.is_nan() => Err(LuaError::RuntimeError(
+                "Invalid key - NaN can not be used as a key".to_string(),
+            )),
+            LuaValue::Number(n) => Ok(Self::from_number(*n)),
+            LuaValue::String(s) => Ok(Self::String(s.as_bytes().into())),
+            value => Err(LuaError::RuntimeError(format!(
+                "Invalid key - expected string or number, got {}",
+                value.type_name()
+            ))),
+        }
+    }
+
+    fn from_number(n: f64) -> Self {
+        Self::Number(if n == 0.0 { 0.0f64 } else { n }.to_bits())
+    }
+
+    pub fn to_lua_value<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            Self::Number(bits) => Ok(LuaValue::Number(f64::from_bits(*bits))),
+            Self::String(s) => lua.create_string(s).map(LuaValue::String),
+        }
+    }
+
+    /**
+        Gets the number of bytes this key uses on the heap, outside of its collection.
+    */
+    pub fn heap_size(&self) -> usize {
+        match self {
+            Self::Number(_) => 0,
+            Self::String(s) => s.len(),
+        }
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => f64::from_bits(*a).total_cmp(&f64::from_bits(*b)),
+            (Self::Number(_), Self::String(_)) => Ordering::Less,
+            (Self::String(_), Self::Number(_)) => Ordering::Greater,
+            (Self::String(a), Self::String(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'lua> FromLua<'lua> for Key {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        Self::from_lua_value(&value)
+    }
+}
@@ -0,0 +1,131 @@
+use std::{collections::HashMap, mem};
+
+use mlua::prelude::*;
+
+use super::{key::Key, value::Value};
+
+/**
+    A map from strings and numbers to any values, created using `collections.map`.
+
+    Keys, and values that are booleans, numbers or strings, are stored in Rust
+    instead of in a Lua table, which uses much less memory per entry for maps with
+    millions of entries. Setting the value for a key to `nil` removes it, same as for tables.
+*/
+#[derive(Debug, Default)]
+pub struct LuaHashMap {
+    entries: HashMap<Key, Value>,
+}
+
+impl LuaHashMap {
+    pub fn from_entries(lua: &Lua, entries: Option<LuaTable>) -> LuaResult<Self> {
+        let mut map = Self::default();
+        if let Some(entries) = entries {
+            map.set_all(lua, entries)?;
+        }
+        Ok(map)
+    }
+
+    fn set(&mut self, lua: &Lua, key: Key, value: LuaValue) -> LuaResult<()> {
+        match Value::from_lua_value(lua, value)? {
+            Some(value) => self.entries.insert(key, value),
+            None => self.entries.remove(&key),
+        };
+        Ok(())
+    }
+
+    fn set_all(&mut self, lua: &Lua, entries: LuaTable) -> LuaResult<()> {
+        for pair in entries.pairs::<LuaValue, LuaValue>() {
+            let (key, value) = pair?;
+            self.set(lua, Key::from_lua_value(&key)?, value)?;
+        }
+        Ok(())
+    }
+
+    /**
+        Estimates the number of bytes used by the map, including unused
+        capacity, and any strings that are stored in it. Values that
+        are stored in the Lua registry are not included.
+    */
+    fn memory_usage(&self) -> usize {
+        // NOTE: Each slot in the table also has a single control byte
+        let table = self.entries.capacity() * (mem::size_of::<(Key, Value)>() + 1);
+        let heap = self
+            .entries
+            .iter()
+            .map(|(key, value)| key.heap_size() + value.heap_size())
+            .sum::<usize>();
+        mem::size_of::<Self>() + table + heap
+    }
+}
+
+impl LuaUserData for LuaHashMap {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method(
+            "get",
+            |lua, this, key: LuaValue| match Key::from_lua_value(&key) {
+                Ok(key) => match this.entries.get(&key) {
+                    Some(value) => value.to_lua_value(lua),
+                    None => Ok(LuaValue::Nil),
+                },
+                Err(_) => Ok(LuaValue::Nil),
+            },
+        );
+        methods.add_method_mut("set", |lua, this, (key, value): (Key, LuaValue)| {
+            this.set(lua, key, value)
+        });
+        methods.add_method_mut("setAll", |lua, this, entries: LuaTable| {
+            this.set_all(lua, entries)
+        });
+        methods.add_method("has", |_, this, key: LuaValue| {
+            Ok(match Key::from_lua_value(&key) {
+                Ok(key) => this.entries.contains_key(&key),
+                Err(_) => false,
+            })
+        });
+        methods.add_method_mut("remove", |lua, this, key: Key| {
+            match this.entries.remove(&key) {
+                Some(value) => value.to_lua_value(lua),
+                None => Ok(LuaValue::Nil),
+            }
+        });
+        methods.add_method("len", |_, this, ()| Ok(this.entries.len()));
+        methods.add_method_mut("clear", |_, this, ()| {
+            this.entries.clear();
+            Ok(())
+        });
+        methods.add_method_mut("reserve", |_, this, additional: usize| {
+            this.entries.reserve(additional);
+            Ok(())
+        });
+        methods.add_method_mut("shrink", |_, this, ()| {
+            this.entries.shrink_to_fit();
+            Ok(())
+        });
+        methods.add_method("keys", |lua, this, ()| {
+            let keys = lua.create_table_with_capacity(this.entries.len(), 0)?;
+            for (index, key) in this.entries.keys().enumerate() {
+                keys.raw_set(index + 1, key.to_lua_value(lua)?)?;
+            }
+            Ok(keys)
+        });
+        methods.add_method("values", |lua, this, ()| {
+            let values = lua.create_table_with_capacity(this.entries.len(), 0)?;
+            for (index, value) in this.entries.values().enumerate() {
+                values.raw_set(index + 1, value.to_lua_value(lua)?)?;
+            }
+            Ok(values)
+        });
+        methods.add_method("toTable", |lua, this, ()| {
+            let table = lua.create_table_with_capacity(0, this.entries.len())?;
+            for (key, value) in &this.entries {
+                table.raw_set(key.to_lua_value(lua)?, value.to_lua_value(lua)?)?;
+            }
+            Ok(table)
+        });
+        methods.add_method("memoryUsage", |_, this, ()| Ok(this.memory_usage()));
+        methods.add_meta_method(LuaMetaMethod::Len, |_, this, ()| Ok(this.entries.len()));
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("HashMap({})", this.entries.len()))
+        });
+    }
+}
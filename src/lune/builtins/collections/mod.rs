@@ -0,0 +1,30 @@
+use mlua::prelude::*;
+
+use crate::lune::util::TableBuilder;
+
+mod heap;
+mod key;
+mod map;
+mod set;
+mod sorted_map;
+mod value;
+
+use heap::{HeapOrder, LuaHeap};
+use map::LuaHashMap;
+use set::LuaHashSet;
+use sorted_map::LuaSortedMap;
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("set", |_, values: Option<LuaTable>| {
+            LuaHashSet::from_values(values)
+        })?
+        .with_function("map", |lua, entries: Option<LuaTable>| {
+            LuaHashMap::from_entries(lua, entries)
+        })?
+        .with_function("sortedMap", |lua, entries: Option<LuaTable>| {
+            LuaSortedMap::from_entries(lua, entries)
+        })?
+        .with_function("heap", |_, order: HeapOrder| Ok(LuaHeap::new(order)))?
+        .build_readonly()
+}
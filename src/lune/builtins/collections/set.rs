@@ -0,0 +1,132 @@
+use std::{collections::HashSet, mem};
+
+use mlua::prelude::*;
+
+use super::key::Key;
+
+/**
+    A set of strings and numbers, created using `collections.set`.
+
+    Values are stored in Rust instead of in a Lua table, which uses
+    much less memory per value for sets with millions of values.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct LuaHashSet {
+    values: HashSet<Key>,
+}
+
+impl LuaHashSet {
+    pub fn from_values(values: Option<LuaTable>) -> LuaResult<Self> {
+        let mut set = Self::default();
+        if let Some(values) = values {
+            set.add_all(values)?;
+        }
+        Ok(set)
+    }
+
+    fn add_all(&mut self, values: LuaTable) -> LuaResult<usize> {
+        let before = self.values.len();
+        self.values.reserve(values.raw_len());
+        for value in values.sequence_values::<Key>() {
+            self.values.insert(value?);
+        }
+        Ok(self.values.len() - before)
+    }
+
+    fn to_array<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaTable<'lua>> {
+        let array = lua.create_table_with_capacity(self.values.len(), 0)?;
+        for (index, value) in self.values.iter().enumerate() {
+            array.raw_set(index + 1, value.to_lua_value(lua)?)?;
+        }
+        Ok(array)
+    }
+
+    /**
+        Estimates the number of bytes used by the set, including
+        unused capacity, and any strings that are stored in it.
+    */
+    fn memory_usage(&self) -> usize {
+        // NOTE: Each slot in the table also has a single control byte
+        let table = self.values.capacity() * (mem::size_of::<Key>() + 1);
+        let heap = self.values.iter().map(Key::heap_size).sum::<usize>();
+        mem::size_of::<Self>() + table + heap
+    }
+}
+
+impl LuaUserData for LuaHashSet {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("add", |_, this, value: Key| Ok(this.values.insert(value)));
+        methods.add_method_mut("addAll", |_, this, values: LuaTable| this.add_all(values));
+        methods.add_method_mut("remove", |_, this, value: Key| {
+            Ok(this.values.remove(&value))
+        });
+        methods.add_method("has", |_, this, value: LuaValue| {
+            // NOTE: Values that can not be keys are never in the set, so we do not error
+            Ok(match Key::from_lua_value(&value) {
+                Ok(key) => this.values.contains(&key),
+                Err(_) => false,
+            })
+        });
+        methods.add_method("len", |_, this, ()| Ok(this.values.len()));
+        methods.add_method_mut("clear", |_, this, ()| {
+            this.values.clear();
+            Ok(())
+        });
+        methods.add_method_mut("reserve", |_, this, additional: usize| {
+            this.values.reserve(additional);
+            Ok(())
+        });
+        methods.add_method_mut("shrink", |_, this, ()| {
+            this.values.shrink_to_fit();
+            Ok(())
+        });
+        methods.add_method("union", |_, this, other: LuaUserDataRef<LuaHashSet>| {
+            let (larger, smaller) = if this.values.len() >= other.values.len() {
+                (&this.values, &other.values)
+            } else {
+                (&other.values, &this.values)
+            };
+            let mut values = larger.clone();
+            values.extend(smaller.iter().cloned());
+            Ok(Self { values })
+        });
+        methods.add_method(
+            "intersection",
+            |_, this, other: LuaUserDataRef<LuaHashSet>| {
+                let (larger, smaller) = if this.values.len() >= other.values.len() {
+                    (&this.values, &other.values)
+                } else {
+                    (&other.values, &this.values)
+                };
+                let values = smaller
+                    .iter()
+                    .filter(|value| larger.contains(value))
+                    .cloned()
+                    .collect();
+                Ok(Self { values })
+            },
+        );
+        methods.add_method(
+            "difference",
+            |_, this, other: LuaUserDataRef<LuaHashSet>| {
+                let values = this
+                    .values
+                    .iter()
+                    .filter(|value| !other.values.contains(value))
+                    .cloned()
+                    .collect();
+                Ok(Self { values })
+            },
+        );
+        methods.add_method(
+            "isSubsetOf",
+            |_, this, other: LuaUserDataRef<LuaHashSet>| Ok(this.values.is_subset(&other.values)),
+        );
+        methods.add_method("toArray", |lua, this, ()| this.to_array(lua));
+        methods.add_method("memoryUsage", |_, this, ()| Ok(this.memory_usage()));
+        methods.add_meta_method(LuaMetaMethod::Len, |_, this, ()| Ok(this.values.len()));
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("HashSet({})", this.values.len()))
+        });
+    }
+}
@@ -0,0 +1,168 @@
+use std::{collections::BTreeMap, mem, ops::Bound};
+
+use mlua::prelude::*;
+
+use super::{key::Key, value::Value};
+
+/**
+    A map from strings and numbers to any values, which keeps its keys
+    in sorted order, created using `collections.sortedMap`.
+
+    Keys are sorted with all numbers before all strings, see [`Key`].
+*/
+#[derive(Debug, Default)]
+pub struct LuaSortedMap {
+    entries: BTreeMap<Key, Value>,
+}
+
+impl LuaSortedMap {
+    pub fn from_entries(lua: &Lua, entries: Option<LuaTable>) -> LuaResult<Self> {
+        let mut map = Self::default();
+        if let Some(entries) = entries {
+            map.set_all(lua, entries)?;
+        }
+        Ok(map)
+    }
+
+    fn set(&mut self, lua: &Lua, key: Key, value: LuaValue) -> LuaResult<()> {
+        match Value::from_lua_value(lua, value)? {
+            Some(value) => self.entries.insert(key, value),
+            None => self.entries.remove(&key),
+        };
+        Ok(())
+    }
+
+    fn set_all(&mut self, lua: &Lua, entries: LuaTable) -> LuaResult<()> {
+        for pair in entries.pairs::<LuaValue, LuaValue>() {
+            let (key, value) = pair?;
+            self.set(lua, Key::from_lua_value(&key)?, value)?;
+        }
+        Ok(())
+    }
+
+    /**
+        Estimates the number of bytes used by the map, including any
+        strings that are stored in it, but not the nodes of the tree.
+    */
+    fn memory_usage(&self) -> usize {
+        let entries = self.entries.len() * mem::size_of::<(Key, Value)>();
+        let heap = self
+            .entries
+            .iter()
+            .map(|(key, value)| key.heap_size() + value.heap_size())
+            .sum::<usize>();
+        mem::size_of::<Self>() + entries + heap
+    }
+}
+
+fn entry_to_lua<'lua>(
+    lua: &'lua Lua,
+    entry: Option<(&Key, &Value)>,
+) -> LuaResult<(LuaValue<'lua>, LuaValue<'lua>)> {
+    match entry {
+        Some((key, value)) => Ok((key.to_lua_value(lua)?, value.to_lua_value(lua)?)),
+        None => Ok((LuaValue::Nil, LuaValue::Nil)),
+    }
+}
+
+fn bound(key: Option<Key>) -> Bound<Key> {
+    match key {
+        Some(key) => Bound::Included(key),
+        None => Bound::Unbounded,
+    }
+}
+
+impl LuaUserData for LuaSortedMap {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method(
+            "get",
+            |lua, this, key: LuaValue| match Key::from_lua_value(&key) {
+                Ok(key) => match this.entries.get(&key) {
+                    Some(value) => value.to_lua_value(lua),
+                    None => Ok(LuaValue::Nil),
+                },
+                Err(_) => Ok(LuaValue::Nil),
+            },
+        );
+        methods.add_method_mut("set", |lua, this, (key, value): (Key, LuaValue)| {
+            this.set(lua, key, value)
+        });
+        methods.add_method_mut("setAll", |lua, this, entries: LuaTable| {
+            this.set_all(lua, entries)
+        });
+        methods.add_method("has", |_, this, key: LuaValue| {
+            Ok(match Key::from_lua_value(&key) {
+                Ok(key) => this.entries.contains_key(&key),
+                Err(_) => false,
+            })
+        });
+        methods.add_method_mut("remove", |lua, this, key: Key| {
+            match this.entries.remove(&key) {
+                Some(value) => value.to_lua_value(lua),
+                None => Ok(LuaValue::Nil),
+            }
+        });
+        methods.add_method("len", |_, this, ()| Ok(this.entries.len()));
+        methods.add_method_mut("clear", |_, this, ()| {
+            this.entries.clear();
+            Ok(())
+        });
+        methods.add_method("first", |lua, this, ()| {
+            entry_to_lua(lua, this.entries.first_key_value())
+        });
+        methods.add_method("last", |lua, this, ()| {
+            entry_to_lua(lua, this.entries.last_key_value())
+        });
+        methods.add_method_mut("popFirst", |lua, this, ()| {
+            let entry = this.entries.pop_first();
+            entry_to_lua(lua, entry.as_ref().map(|(k, v)| (k, v)))
+        });
+        methods.add_method_mut("popLast", |lua, this, ()| {
+            let entry = this.entries.pop_last();
+            entry_to_lua(lua, entry.as_ref().map(|(k, v)| (k, v)))
+        });
+        methods.add_method("floor", |lua, this, key: Key| {
+            entry_to_lua(lua, this.entries.range(..=key).next_back())
+        });
+        methods.add_method("ceil", |lua, this, key: Key| {
+            entry_to_lua(lua, this.entries.range(key..).next())
+        });
+        methods.add_method(
+            "range",
+            |lua, this, (min, max): (Option<Key>, Option<Key>)| {
+                if let (Some(min), Some(max)) = (&min, &max) {
+                    if min > max {
+                        return lua.create_table();
+                    }
+                }
+                let entries = lua.create_table()?;
+                for (key, value) in this.entries.range((bound(min), bound(max))) {
+                    let entry = lua.create_table_with_capacity(0, 2)?;
+                    entry.raw_set("key", key.to_lua_value(lua)?)?;
+                    entry.raw_set("value", value.to_lua_value(lua)?)?;
+                    entries.raw_push(entry)?;
+                }
+                Ok(entries)
+            },
+        );
+        methods.add_method("keys", |lua, this, ()| {
+            let keys = lua.create_table_with_capacity(this.entries.len(), 0)?;
+            for (index, key) in this.entries.keys().enumerate() {
+                keys.raw_set(index + 1, key.to_lua_value(lua)?)?;
+            }
+            Ok(keys)
+        });
+        methods.add_method("values", |lua, this, ()| {
+            let values = lua.create_table_with_capacity(this.entries.len(), 0)?;
+            for (index, value) in this.entries.values().enumerate() {
+                values.raw_set(index + 1, value.to_lua_value(lua)?)?;
+            }
+            Ok(values)
+        });
+        methods.add_method("memoryUsage", |_, this, ()| Ok(this.memory_usage()));
+        methods.add_meta_method(LuaMetaMethod::Len, |_, this, ()| Ok(this.entries.len()));
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("SortedMap({})", this.entries.len()))
+        });
+    }
+}
@@ -0,0 +1,48 @@
+use mlua::prelude::*;
+
+/**
+    A value in a collection, where booleans, numbers and strings are stored
+    directly, and any other values are stored in the Lua registry.
+*/
+#[derive(Debug)]
+pub enum Value {
+    Boolean(bool),
+    Number(f64),
+    String(Box<[u8]>),
+    Other(LuaRegistryKey),
+}
+
+impl Value {
+    /**
+        Converts a Lua value into a stored value, returning `None` for `nil`.
+    */
+    pub fn from_lua_value(lua: &Lua, value: LuaValue) -> LuaResult<Option<Self>> {
+        Ok(Some(match value {
+            LuaValue::Nil => return Ok(None),
+            LuaValue::Boolean(b) => Self::Boolean(b),
+            LuaValue::Integer(i) => Self::Number(i as f64),
+            LuaValue::Number(n) => Self::Number(n),
+            LuaValue::String(s) => Self::String(s.as_bytes().into()),
+            value => Self::Other(lua.create_registry_value(value)?),
+        }))
+    }
+
+    pub fn to_lua_value<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            Self::Boolean(b) => Ok(LuaValue::Boolean(*b)),
+            Self::Number(n) => Ok(LuaValue::Number(*n)),
+            Self::String(s) => lua.create_string(s).map(LuaValue::String),
+            Self::Other(key) => lua.registry_value(key),
+        }
+    }
+
+    /**
+        Gets the number of bytes this value uses on the heap, outside of its collection.
+    */
+    pub fn heap_size(&self) -> usize {
+        match self {
+            Self::String(s) => s.len(),
+            _ => 0,
+        }
+    }
+}
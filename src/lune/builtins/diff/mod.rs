@@ -0,0 +1,71 @@
+use mlua::prelude::*;
+use similar::TextDiff;
+
+use crate::lune::util::{
+    formatting::{COLOR_CYAN, COLOR_GREEN, COLOR_RED, STYLE_BOLD},
+    TableBuilder,
+};
+
+mod options;
+mod patch;
+mod tables;
+
+use options::DiffLinesOptions;
+use patch::apply_patch;
+use tables::diff_tables;
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("lines", diff_lines)?
+        .with_function("colorize", diff_colorize)?
+        .with_function("patch", diff_patch)?
+        .with_function("tables", diff_tables)?
+        .build_readonly()
+}
+
+fn diff_lines<'lua>(
+    lua: &'lua Lua,
+    (old, new, options): (LuaString<'lua>, LuaString<'lua>, DiffLinesOptions),
+) -> LuaResult<LuaString<'lua>> {
+    let diff = TextDiff::from_lines(old.to_str()?, new.to_str()?);
+    let mut unified = diff.unified_diff();
+    unified
+        .context_radius(options.context)
+        .header(&options.old_name, &options.new_name);
+    lua.create_string(unified.to_string())
+}
+
+fn diff_colorize(_: &Lua, patch: String) -> LuaResult<String> {
+    let colorized = patch
+        .split_inclusive('\n')
+        .map(|line| {
+            let (content, newline) = match line.strip_suffix('\n') {
+                Some(content) => (content, "\n"),
+                None => (line, ""),
+            };
+            let style = if content.starts_with("+++") || content.starts_with("---") {
+                Some(&*STYLE_BOLD)
+            } else if content.starts_with("@@") {
+                Some(&*COLOR_CYAN)
+            } else if content.starts_with('+') {
+                Some(&*COLOR_GREEN)
+            } else if content.starts_with('-') {
+                Some(&*COLOR_RED)
+            } else {
+                None
+            };
+            match style {
+                Some(style) => format!("{}{newline}", style.apply_to(content)),
+                None => line.to_string(),
+            }
+        })
+        .collect();
+    Ok(colorized)
+}
+
+fn diff_patch<'lua>(
+    lua: &'lua Lua,
+    (text, patch): (LuaString<'lua>, LuaString<'lua>),
+) -> LuaResult<LuaString<'lua>> {
+    lua.create_string(apply_patch(text.to_str()?, patch.to_str()?)?)
+}
@@ -0,0 +1,70 @@
+use mlua::prelude::*;
+
+/**
+    Options for `diff.lines`.
+*/
+#[derive(Debug, Clone)]
+pub struct DiffLinesOptions {
+    pub context: usize,
+    pub old_name: String,
+    pub new_name: String,
+}
+
+impl Default for DiffLinesOptions {
+    fn default() -> Self {
+        Self {
+            context: 3,
+            old_name: "a".to_string(),
+            new_name: "b".to_string(),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for DiffLinesOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Table(t) => {
+                let defaults = Self::default();
+                let context = match t.raw_get::<_, Option<usize>>("context") {
+                    Ok(context) => context.unwrap_or(defaults.context),
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'context' in diff options - expected a non-negative integer"
+                                .to_string(),
+                        ))
+                    }
+                };
+                let old_name =
+                    match t.raw_get::<_, Option<String>>("oldName") {
+                        Ok(name) => name.unwrap_or(defaults.old_name),
+                        Err(_) => return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'oldName' in diff options - expected string"
+                                .to_string(),
+                        )),
+                    };
+                let new_name =
+                    match t.raw_get::<_, Option<String>>("newName") {
+                        Ok(name) => name.unwrap_or(defaults.new_name),
+                        Err(_) => return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'newName' in diff options - expected string"
+                                .to_string(),
+                        )),
+                    };
+                Ok(Self {
+                    context,
+                    old_name,
+                    new_name,
+                })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "DiffLinesOptions",
+                message: Some(format!(
+                    "Invalid diff options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
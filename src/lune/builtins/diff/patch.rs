@@ -0,0 +1,224 @@
+use mlua::prelude::*;
+
+/**
+    A single line of text, without its newline, and if it had one.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Line<'a> {
+    content: &'a str,
+    newline: bool,
+}
+
+impl<'a> Line<'a> {
+    fn split(text: &'a str) -> Vec<Self> {
+        text.split_inclusive('\n')
+            .map(|line| match line.strip_suffix('\n') {
+                Some(content) => Self {
+                    content,
+                    newline: true,
+                },
+                None => Self {
+                    content: line,
+                    newline: false,
+                },
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct Hunk<'a> {
+    old_start: usize,
+    old_len: usize,
+    lines: Vec<(char, Line<'a>)>,
+}
+
+fn patch_error(message: impl AsRef<str>) -> LuaError {
+    LuaError::RuntimeError(format!("Failed to apply patch - {}", message.as_ref()))
+}
+
+/**
+    Parses a range in a hunk header, such as `-12,3` or `+4`.
+*/
+fn parse_range(range: &str, prefix: char) -> Option<(usize, usize)> {
+    let range = range.strip_prefix(prefix)?;
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+fn parse_hunks<'a>(patch: &'a str) -> LuaResult<Vec<Hunk<'a>>> {
+    let lines = Line::split(patch);
+    let mut hunks = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let header = lines[index].content;
+        index += 1;
+        if !header.starts_with("@@") {
+            // NOTE: Anything outside of hunks such as file names is ignored
+            continue;
+        }
+        let mut parts = header.split_whitespace().skip(1);
+        let (old_start, old_len, new_len) = match (
+            parts.next().and_then(|range| parse_range(range, '-')),
+            parts.next().and_then(|range| parse_range(range, '+')),
+        ) {
+            (Some((old_start, old_len)), Some((_, new_len))) => (old_start, old_len, new_len),
+            _ => {
+                return Err(patch_error(format!(
+                    "invalid hunk header '{header}' on line {index}"
+                )))
+            }
+        };
+
+        let mut hunk = Hunk {
+            old_start,
+            old_len,
+            lines: Vec::new(),
+        };
+        let (mut old_remaining, mut new_remaining) = (old_len, new_len);
+        while old_remaining > 0
+            || new_remaining > 0
+            || lines
+                .get(index)
+                .is_some_and(|l| l.content.starts_with('\\'))
+        {
+            let line = match lines.get(index) {
+                Some(line) => *line,
+                None => return Err(patch_error(format!("hunk '{header}' ended unexpectedly"))),
+            };
+            index += 1;
+            let mut chars = line.content.chars();
+            let kind = chars.next().unwrap_or(' ');
+            let content = Line {
+                content: chars.as_str(),
+                newline: line.newline,
+            };
+            match kind {
+                ' ' if old_remaining > 0 && new_remaining > 0 => {
+                    old_remaining -= 1;
+                    new_remaining -= 1;
+                }
+                '-' if old_remaining > 0 => old_remaining -= 1,
+                '+' if new_remaining > 0 => new_remaining -= 1,
+                '\\' => {
+                    // NOTE: This is the "\ No newline at end of file" marker,
+                    // which applies to the line right before it in the hunk
+                    if let Some((_, previous)) = hunk.lines.last_mut() {
+                        previous.newline = false;
+                    }
+                    continue;
+                }
+                _ => {
+                    return Err(patch_error(format!(
+                        "unexpected line '{}' in hunk '{header}'",
+                        line.content
+                    )))
+                }
+            }
+            hunk.lines.push((kind, content));
+        }
+        hunks.push(hunk);
+    }
+    Ok(hunks)
+}
+
+/**
+    Applies a patch in the unified diff format to the given text.
+
+    Every hunk must match the text exactly, and hunks must
+    be in order, same as the patches created by `diff.lines`.
+*/
+pub fn apply_patch(text: &str, patch: &str) -> LuaResult<String> {
+    let original = Line::split(text);
+    let mut output = String::with_capacity(text.len());
+    let push = |output: &mut String, line: Line| {
+        output.push_str(line.content);
+        if line.newline {
+            output.push('\n');
+        }
+    };
+
+    let mut position = 0;
+    for (number, hunk) in parse_hunks(patch)?.into_iter().enumerate() {
+        // NOTE: Hunks that only add lines start after the given line instead of at it
+        let start = if hunk.old_len == 0 {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        };
+        if start < position || start > original.len() {
+            return Err(patch_error(format!(
+                "hunk #{} at line {} is out of order or outside of the text",
+                number + 1,
+                hunk.old_start
+            )));
+        }
+        for line in &original[position..start] {
+            push(&mut output, *line);
+        }
+        position = start;
+
+        for (kind, line) in hunk.lines {
+            if kind == '+' {
+                push(&mut output, line);
+                continue;
+            }
+            match original.get(position) {
+                Some(existing) if existing.content == line.content => {
+                    if kind == ' ' {
+                        push(&mut output, *existing);
+                    }
+                    position += 1;
+                }
+                _ => {
+                    return Err(patch_error(format!(
+                        "hunk #{} does not match the text at line {}",
+                        number + 1,
+                        position + 1
+                    )))
+                }
+            }
+        }
+    }
+    for line in &original[position..] {
+        push(&mut output, *line);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use similar::TextDiff;
+
+    use super::*;
+
+    fn round_trip(old: &str, new: &str) {
+        let patch = TextDiff::from_lines(old, new)
+            .unified_diff()
+            .context_radius(1)
+            .header("a", "b")
+            .to_string();
+        assert_eq!(apply_patch(old, &patch).unwrap(), new, "patch:\n{patch}");
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip("a\nb\nc\n", "a\nB\nc\n");
+        round_trip("", "a\nb\n");
+        round_trip("a\nb\n", "");
+        round_trip("1\n2\n3\n4\n5\n6\n7\n8\n", "0\n1\n2\n3\n5\n6\n7\n8\n9\n");
+        round_trip("a\nb", "a\nc");
+        round_trip("a\nb", "a\nb\n");
+        round_trip("a\nb\n", "a\nb");
+    }
+
+    #[test]
+    fn errors_on_mismatch() {
+        let patch = "@@ -1,2 +1,2 @@\n a\n-b\n+c\n";
+        assert!(apply_patch("a\nb\n", patch).is_ok());
+        assert!(apply_patch("a\nx\n", patch).is_err());
+        assert!(apply_patch("", patch).is_err());
+    }
+}
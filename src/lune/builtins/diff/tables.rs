@@ -0,0 +1,116 @@
+use std::{cmp::Ordering, collections::HashSet};
+
+use mlua::prelude::*;
+
+/**
+    Orders keys so that changes are always given in the same order,
+    with numbers first, then strings, and then any other keys.
+*/
+fn compare_keys(a: &LuaValue, b: &LuaValue) -> Ordering {
+    fn rank(value: &LuaValue) -> u8 {
+        match value {
+            LuaValue::Integer(_) | LuaValue::Number(_) => 0,
+            LuaValue::String(_) => 1,
+            _ => 2,
+        }
+    }
+    fn number(value: &LuaValue) -> Option<f64> {
+        match value {
+            LuaValue::Integer(i) => Some(*i as f64),
+            LuaValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+    match (a, b) {
+        (LuaValue::String(a), LuaValue::String(b)) => a.as_bytes().cmp(b.as_bytes()),
+        _ => match (number(a), number(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            _ => rank(a).cmp(&rank(b)),
+        },
+    }
+}
+
+struct TableDiffer<'lua> {
+    lua: &'lua Lua,
+    changes: Vec<LuaTable<'lua>>,
+    visited: HashSet<(usize, usize)>,
+}
+
+impl<'lua> TableDiffer<'lua> {
+    fn push(
+        &mut self,
+        path: &[LuaValue<'lua>],
+        kind: &'static str,
+        old: LuaValue<'lua>,
+        new: LuaValue<'lua>,
+    ) -> LuaResult<()> {
+        let change = self.lua.create_table()?;
+        change.raw_set("path", self.lua.create_sequence_from(path.iter().cloned())?)?;
+        change.raw_set("kind", kind)?;
+        change.raw_set("old", old)?;
+        change.raw_set("new", new)?;
+        self.changes.push(change);
+        Ok(())
+    }
+
+    fn diff(
+        &mut self,
+        path: &mut Vec<LuaValue<'lua>>,
+        old: LuaTable<'lua>,
+        new: LuaTable<'lua>,
+    ) -> LuaResult<()> {
+        // NOTE: Tables that reference themselves would otherwise be diffed
+        // forever, and since they were already diffed they can be skipped
+        let pointers = (old.to_pointer() as usize, new.to_pointer() as usize);
+        if !self.visited.insert(pointers) {
+            return Ok(());
+        }
+
+        let mut keys = Vec::new();
+        for pair in old.clone().pairs::<LuaValue, LuaValue>() {
+            keys.push(pair?.0);
+        }
+        for pair in new.clone().pairs::<LuaValue, LuaValue>() {
+            let (key, _) = pair?;
+            if !old.contains_key(key.clone())? {
+                keys.push(key);
+            }
+        }
+        keys.sort_by(compare_keys);
+
+        for key in keys {
+            let old_value: LuaValue = old.raw_get(key.clone())?;
+            let new_value: LuaValue = new.raw_get(key.clone())?;
+            path.push(key);
+            match (old_value, new_value) {
+                (LuaValue::Nil, new_value) => self.push(path, "added", LuaValue::Nil, new_value)?,
+                (old_value, LuaValue::Nil) => {
+                    self.push(path, "removed", old_value, LuaValue::Nil)?;
+                }
+                (LuaValue::Table(old_table), LuaValue::Table(new_table)) => {
+                    self.diff(path, old_table, new_table)?;
+                }
+                (old_value, new_value) => {
+                    if !old_value.equals(&new_value)? {
+                        self.push(path, "changed", old_value, new_value)?;
+                    }
+                }
+            }
+            path.pop();
+        }
+        Ok(())
+    }
+}
+
+pub fn diff_tables<'lua>(
+    lua: &'lua Lua,
+    (old, new): (LuaTable<'lua>, LuaTable<'lua>),
+) -> LuaResult<LuaTable<'lua>> {
+    let mut differ = TableDiffer {
+        lua,
+        changes: Vec::new(),
+        visited: HashSet::new(),
+    };
+    differ.diff(&mut Vec::new(), old, new)?;
+    lua.create_sequence_from(differ.changes)
+}
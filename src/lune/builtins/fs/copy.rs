@@ -1,21 +1,25 @@
 use std::collections::VecDeque;
-use std::io::ErrorKind;
+use std::io::{Error as IoError, ErrorKind};
 use std::path::{Path, PathBuf};
 
+use filetime::{set_file_times, FileTime};
 use mlua::prelude::*;
 use tokio::fs;
 
-use super::options::FsWriteOptions;
+use super::options::FsCopyOptions;
 
 pub struct CopyContents {
     // Vec<(relative depth, path)>
     pub dirs: Vec<(usize, PathBuf)>,
     pub files: Vec<(usize, PathBuf)>,
+    // Total size of all files, in bytes
+    pub bytes: u64,
 }
 
-async fn get_contents_at(root: PathBuf, _options: FsWriteOptions) -> LuaResult<CopyContents> {
+async fn get_contents_at(root: PathBuf) -> LuaResult<CopyContents> {
     let mut dirs = Vec::new();
     let mut files = Vec::new();
+    let mut bytes = 0;
 
     let mut queue = VecDeque::new();
 
@@ -47,6 +51,7 @@ async fn get_contents_at(root: PathBuf, _options: FsWriteOptions) -> LuaResult<C
             }
             dirs.push((current_depth, current_path));
         } else {
+            bytes += meta.len();
             files.push((current_depth, current_path));
         }
     }
@@ -66,7 +71,7 @@ async fn get_contents_at(root: PathBuf, _options: FsWriteOptions) -> LuaResult<C
     // - foo/bar/baz/
     // turn into a single foo/bar/baz/ and let create_dir_all do the heavy lifting
 
-    Ok(CopyContents { dirs, files })
+    Ok(CopyContents { dirs, files, bytes })
 }
 
 async fn ensure_no_dir_exists(path: impl AsRef<Path>) -> LuaResult<()> {
@@ -91,10 +96,42 @@ async fn ensure_no_file_exists(path: impl AsRef<Path>) -> LuaResult<()> {
     }
 }
 
+/**
+    Copies the permissions and the access & modification
+    timestamps of the file or directory at `source` to `target`.
+*/
+async fn preserve_metadata(source: impl AsRef<Path>, target: impl AsRef<Path>) -> LuaResult<()> {
+    let target = target.as_ref();
+    let meta = fs::metadata(source).await?;
+    // NOTE: Timestamps must be set before permissions, since
+    // they can not be set on read-only files on some platforms
+    set_file_times(
+        target,
+        FileTime::from_last_access_time(&meta),
+        FileTime::from_last_modification_time(&meta),
+    )?;
+    fs::set_permissions(target, meta.permissions()).await?;
+    Ok(())
+}
+
+fn report_progress(
+    options: &FsCopyOptions,
+    copied: u64,
+    total: u64,
+    path: impl AsRef<Path>,
+) -> LuaResult<()> {
+    match &options.progress {
+        None => Ok(()),
+        Some(progress) => {
+            progress.call::<_, ()>((copied, total, path.as_ref().display().to_string()))
+        }
+    }
+}
+
 pub async fn copy(
     source: impl AsRef<Path>,
     target: impl AsRef<Path>,
-    options: FsWriteOptions,
+    options: &FsCopyOptions<'_>,
 ) -> LuaResult<()> {
     let source = source.as_ref();
     let target = target.as_ref();
@@ -123,6 +160,8 @@ pub async fn copy(
     // 2. If we are allowed to overwrite, remove any previous entry at the path
     // 3. Write all directories first
     // 4. Write all files
+    // 5. Copy permissions & timestamps of directories, if wanted, once nothing more
+    //    will be written to them, starting with the deepest ones and ending at the root
 
     if !options.overwrite {
         if is_file {
@@ -133,23 +172,112 @@ pub async fn copy(
     }
 
     if is_file {
-        fs::copy(source, target).await?;
+        let bytes = fs::copy(source, target).await?;
+        if options.preserve {
+            preserve_metadata(source, target).await?;
+        }
+        report_progress(options, bytes, bytes, source)?;
     } else if is_dir {
-        let contents = get_contents_at(source.to_path_buf(), options).await?;
+        if !options.recursive {
+            return Err(LuaError::RuntimeError(format!(
+                "The path '{}' is a directory, and can only be copied using the recursive option",
+                source.display()
+            )));
+        }
+
+        let mut contents = get_contents_at(source.to_path_buf()).await?;
 
-        if options.overwrite {
+        if options.overwrite && fs::metadata(target).await.is_ok_and(|meta| meta.is_dir()) {
             fs::remove_dir_all(target).await?;
         }
 
         // FUTURE: Write dirs / files concurrently
         // to potentially speed these operations up
+        fs::create_dir_all(target).await?;
         for (_, dir) in &contents.dirs {
             fs::create_dir_all(target.join(dir)).await?;
         }
+        let mut copied = 0;
         for (_, file) in &contents.files {
-            fs::copy(source.join(file), target.join(file)).await?;
+            copied += fs::copy(source.join(file), target.join(file)).await?;
+            if options.preserve {
+                preserve_metadata(source.join(file), target.join(file)).await?;
+            }
+            report_progress(options, copied, contents.bytes, source.join(file))?;
+        }
+
+        if options.preserve {
+            contents.dirs.sort_by(|(a, _), (b, _)| b.cmp(a));
+            for (_, dir) in &contents.dirs {
+                preserve_metadata(source.join(dir), target.join(dir)).await?;
+            }
+            preserve_metadata(source, target).await?;
         }
     }
 
     Ok(())
 }
+
+/**
+    Checks if an error was caused by trying to rename a path to a different
+    mount point, which is `EXDEV` on unix, and `ERROR_NOT_SAME_DEVICE` on windows.
+
+    FUTURE: Use `ErrorKind::CrossesDevices` instead once it is stable
+*/
+fn is_cross_device_error(e: &IoError) -> bool {
+    let code = if cfg!(windows) { 17 } else { 18 };
+    e.raw_os_error() == Some(code)
+}
+
+/**
+    Renames (moves) a file or directory, copying it and then removing
+    the source if it is being moved to a different mount point.
+*/
+pub async fn rename(
+    source: impl AsRef<Path>,
+    target: impl AsRef<Path>,
+    options: &FsCopyOptions<'_>,
+) -> LuaResult<()> {
+    let source = source.as_ref();
+    let target = target.as_ref();
+
+    let source_meta = match fs::metadata(&source).await {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(LuaError::RuntimeError(format!(
+                "No file or directory exists at the path '{}'",
+                source.display()
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    match fs::metadata(&target).await {
+        Ok(_) if !options.overwrite => {
+            return Err(LuaError::RuntimeError(format!(
+                "A file or directory already exists at the path '{}'",
+                target.display()
+            )))
+        }
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(&target).await?,
+        _ => {}
+    }
+
+    match fs::rename(source, target).await {
+        Ok(()) => Ok(()),
+        Err(e) if is_cross_device_error(&e) => {
+            let copy_options = FsCopyOptions {
+                recursive: true,
+                preserve: true,
+                ..options.clone()
+            };
+            copy(source, target, &copy_options).await?;
+            if source_meta.is_dir() {
+                fs::remove_dir_all(source).await?;
+            } else {
+                fs::remove_file(source).await?;
+            }
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
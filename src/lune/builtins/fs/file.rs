@@ -0,0 +1,248 @@
+use std::{io::SeekFrom, sync::Arc};
+
+use async_trait::async_trait;
+use mlua::prelude::*;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    sync::Mutex as AsyncMutex,
+};
+
+use crate::lune::util::TableBuilder;
+
+use super::lines::{LineReader, LineReaderOptions, LineSource};
+
+const LINES_CHUNK_SIZE: u64 = 8 * 1024;
+
+/**
+    The mode to open a file with, same as the modes for `fopen` in C:
+
+    - `r` - Read only, the file must exist
+    - `w` - Write only, creating or truncating the file
+    - `a` - Write only at the end, creating the file if it does not exist
+    - `r+`, `w+`, `a+` - Same as above, but also allowing both reads and writes
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsOpenMode {
+    kind: char,
+    update: bool,
+}
+
+impl<'lua> FromLua<'lua> for FsOpenMode {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self {
+                kind: 'r',
+                update: false,
+            }),
+            LuaValue::String(s) => {
+                // NOTE: The binary flag is accepted for compatibility with
+                // fopen and io.open, but has no effect since files are bytes
+                let mode = s.to_string_lossy().trim().replace('b', "");
+                match mode.as_str() {
+                    "r" | "w" | "a" | "r+" | "w+" | "a+" => Ok(Self {
+                        kind: mode.chars().next().unwrap(),
+                        update: mode.ends_with('+'),
+                    }),
+                    _ => Err(LuaError::FromLuaConversionError {
+                        from: value.type_name(),
+                        to: "FsOpenMode",
+                        message: Some(format!(
+                            "Invalid mode '{mode}', valid modes are:  r, w, a, r+, w+, a+"
+                        )),
+                    }),
+                }
+            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "FsOpenMode",
+                message: None,
+            }),
+        }
+    }
+}
+
+impl FsOpenMode {
+    fn open_options(self) -> OpenOptions {
+        let mut options = OpenOptions::new();
+        match self.kind {
+            'w' => options.write(true).create(true).truncate(true),
+            'a' => options.append(true).create(true),
+            _ => options.read(true),
+        };
+        if self.update {
+            options.read(true).write(self.kind != 'a');
+        }
+        options
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SeekWhence {
+    #[default]
+    Set,
+    Current,
+    End,
+}
+
+impl<'lua> FromLua<'lua> for SeekWhence {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::String(s) => match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "set" => Ok(Self::Set),
+                "cur" => Ok(Self::Current),
+                "end" => Ok(Self::End),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "SeekWhence",
+                    message: Some(format!(
+                        "Invalid seek position '{kind}', valid positions are:  set, cur, end"
+                    )),
+                }),
+            },
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "SeekWhence",
+                message: None,
+            }),
+        }
+    }
+}
+
+/**
+    An open file, which is closed once it is dropped or `close` is called.
+*/
+#[derive(Debug)]
+pub struct FileHandle {
+    file: Option<File>,
+}
+
+impl FileHandle {
+    pub async fn open(path: &str, mode: FsOpenMode) -> LuaResult<Self> {
+        let file = mode.open_options().open(path).await.into_lua_err()?;
+        Ok(Self { file: Some(file) })
+    }
+
+    fn file(&mut self) -> LuaResult<&mut File> {
+        self.file
+            .as_mut()
+            .ok_or_else(|| LuaError::RuntimeError("File has already been closed".to_string()))
+    }
+
+    /**
+        Reads up to the given number of bytes, or until the end of the
+        file if no number is given, returning `None` at the end of the file.
+    */
+    async fn read(&mut self, count: Option<u64>) -> LuaResult<Option<Vec<u8>>> {
+        let file = self.file()?;
+        let mut bytes = Vec::new();
+        match count {
+            Some(0) => return Ok(Some(bytes)),
+            Some(count) => file.take(count).read_to_end(&mut bytes).await,
+            None => file.read_to_end(&mut bytes).await,
+        }
+        .into_lua_err()?;
+        Ok(if bytes.is_empty() { None } else { Some(bytes) })
+    }
+
+    async fn seek(&mut self, whence: SeekWhence, offset: i64) -> LuaResult<u64> {
+        let position = match whence {
+            SeekWhence::Set => SeekFrom::Start(u64::try_from(offset).map_err(|_| {
+                LuaError::RuntimeError(format!(
+                    "Invalid seek offset {offset} - must not be negative when seeking from the start"
+                ))
+            })?),
+            SeekWhence::Current => SeekFrom::Current(offset),
+            SeekWhence::End => SeekFrom::End(offset),
+        };
+        self.file()?.seek(position).await.into_lua_err()
+    }
+
+    async fn close(&mut self) -> LuaResult<()> {
+        let mut file = match self.file.take() {
+            Some(file) => file,
+            None => {
+                return Err(LuaError::RuntimeError(
+                    "File has already been closed".to_string(),
+                ))
+            }
+        };
+        file.flush().await.into_lua_err()
+    }
+
+    /**
+        Creates a Lua table for this file handle, with methods
+        for reading, writing, seeking, flushing and closing.
+    */
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable> {
+        let handle = Arc::new(AsyncMutex::new(self));
+        let handle_read = Arc::clone(&handle);
+        let handle_lines = Arc::clone(&handle);
+        let handle_write = Arc::clone(&handle);
+        let handle_seek = Arc::clone(&handle);
+        let handle_flush = Arc::clone(&handle);
+        TableBuilder::new(lua)?
+            .with_async_function("read", move |lua, (_, count): (LuaValue, Option<u64>)| {
+                let handle = Arc::clone(&handle_read);
+                async move {
+                    match handle.lock().await.read(count).await? {
+                        Some(bytes) => Ok(LuaValue::String(lua.create_string(bytes)?)),
+                        None => Ok(LuaValue::Nil),
+                    }
+                }
+            })?
+            .with_function(
+                "lines",
+                move |lua, (_, options): (LuaValue, LineReaderOptions)| {
+                    let source = FileLineSource(Arc::clone(&handle_lines));
+                    LineReader::from_source(source, options).into_lua_table(lua)
+                },
+            )?
+            .with_async_function("write", move |_, (_, data): (LuaValue, LuaString)| {
+                let handle = Arc::clone(&handle_write);
+                let data = data.as_bytes().to_vec();
+                async move {
+                    let mut handle = handle.lock().await;
+                    handle.file()?.write_all(&data).await.into_lua_err()
+                }
+            })?
+            .with_async_function(
+                "seek",
+                move |_, (_, offset, whence): (LuaValue, Option<i64>, SeekWhence)| {
+                    let handle = Arc::clone(&handle_seek);
+                    async move {
+                        let mut handle = handle.lock().await;
+                        handle.seek(whence, offset.unwrap_or_default()).await
+                    }
+                },
+            )?
+            .with_async_function("flush", move |_, _: LuaValue| {
+                let handle = Arc::clone(&handle_flush);
+                async move {
+                    let mut handle = handle.lock().await;
+                    handle.file()?.flush().await.into_lua_err()
+                }
+            })?
+            .with_async_function("close", move |_, _: LuaValue| {
+                let handle = Arc::clone(&handle);
+                async move { handle.lock().await.close().await }
+            })?
+            .build_readonly()
+    }
+}
+
+/**
+    Reads lines from a shared file handle, starting at its current position.
+
+    NOTE: Lines are read in chunks, so the position of the file
+    handle may end up past the lines that have been returned so far.
+*/
+struct FileLineSource(Arc<AsyncMutex<FileHandle>>);
+
+#[async_trait]
+impl LineSource for FileLineSource {
+    async fn read_chunk(&mut self) -> LuaResult<Option<Vec<u8>>> {
+        self.0.lock().await.read(Some(LINES_CHUNK_SIZE)).await
+    }
+}
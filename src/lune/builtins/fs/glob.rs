@@ -0,0 +1,147 @@
+use std::{path::PathBuf, sync::Arc};
+
+use glob::{MatchOptions, Paths};
+use mlua::prelude::*;
+use tokio::{sync::Mutex as AsyncMutex, task};
+
+use crate::lune::util::TableBuilder;
+
+#[derive(Debug, Clone, Copy)]
+pub struct GlobOptions {
+    pub(crate) iterator: bool,
+    pub(crate) case_sensitive: bool,
+    pub(crate) hidden: bool,
+}
+
+impl Default for GlobOptions {
+    fn default() -> Self {
+        Self {
+            iterator: false,
+            case_sensitive: true,
+            hidden: true,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for GlobOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Table(t) => {
+                let defaults = Self::default();
+                Ok(Self {
+                    iterator: t
+                        .get::<_, Option<bool>>("iterator")?
+                        .unwrap_or(defaults.iterator),
+                    case_sensitive: t
+                        .get::<_, Option<bool>>("caseSensitive")?
+                        .unwrap_or(defaults.case_sensitive),
+                    hidden: t
+                        .get::<_, Option<bool>>("hidden")?
+                        .unwrap_or(defaults.hidden),
+                })
+            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "GlobOptions",
+                message: Some(format!(
+                    "Invalid glob options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+impl GlobOptions {
+    fn match_options(self) -> MatchOptions {
+        MatchOptions {
+            case_sensitive: self.case_sensitive,
+            require_literal_separator: true,
+            require_literal_leading_dot: !self.hidden,
+        }
+    }
+}
+
+fn path_to_string(path: PathBuf) -> LuaResult<String> {
+    match path.to_str() {
+        Some(s) => Ok(s.to_owned()),
+        None => Err(LuaError::RuntimeError(format!(
+            "File path could not be converted into a string: '{}'",
+            path.display()
+        ))),
+    }
+}
+
+/**
+    Walks the filesystem for paths matching a glob pattern.
+
+    Walking is blocking, so the paths are always
+    read on a blocking thread, one or all at a time.
+*/
+pub struct Glob {
+    paths: Option<Paths>,
+}
+
+impl Glob {
+    pub fn new(pattern: &str, options: GlobOptions) -> LuaResult<Self> {
+        let paths = glob::glob_with(pattern, options.match_options()).map_err(|e| {
+            LuaError::RuntimeError(format!("Invalid glob pattern '{pattern}' - {e}"))
+        })?;
+        Ok(Self { paths: Some(paths) })
+    }
+
+    /**
+        Reads the next matching path, or `None` once there are no more matches.
+    */
+    pub async fn next_path(&mut self) -> LuaResult<Option<String>> {
+        let mut paths = match self.paths.take() {
+            Some(paths) => paths,
+            None => return Ok(None),
+        };
+        let (paths, next) = task::spawn_blocking(move || {
+            let next = paths.next();
+            (paths, next)
+        })
+        .await
+        .into_lua_err()?;
+        match next {
+            None => Ok(None),
+            Some(result) => {
+                self.paths = Some(paths);
+                path_to_string(result.into_lua_err()?).map(Some)
+            }
+        }
+    }
+
+    /**
+        Reads all of the remaining matching paths.
+    */
+    pub async fn collect_paths(mut self) -> LuaResult<Vec<String>> {
+        let paths = match self.paths.take() {
+            Some(paths) => paths,
+            None => return Ok(Vec::new()),
+        };
+        task::spawn_blocking(move || paths.collect::<Result<Vec<_>, _>>())
+            .await
+            .into_lua_err()?
+            .into_lua_err()?
+            .into_iter()
+            .map(path_to_string)
+            .collect()
+    }
+
+    /**
+        Creates a Lua table for this glob, with a single `next` method that
+        yields until the next matching path is found, or returns `nil` when done.
+    */
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable> {
+        let glob = Arc::new(AsyncMutex::new(self));
+        TableBuilder::new(lua)?
+            .with_async_function("next", move |_, _: LuaValue| {
+                let glob = Arc::clone(&glob);
+                async move { glob.lock().await.next_path().await }
+            })?
+            .build_readonly()
+    }
+}
@@ -0,0 +1,225 @@
+use std::{io, sync::Arc};
+
+use async_trait::async_trait;
+use mlua::prelude::*;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::Mutex as AsyncMutex,
+};
+
+use crate::lune::util::TableBuilder;
+
+const DEFAULT_DELIMITER: &[u8] = b"\n";
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct LineReaderOptions {
+    pub(crate) delimiter: Vec<u8>,
+    pub(crate) max_length: Option<usize>,
+}
+
+impl Default for LineReaderOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: DEFAULT_DELIMITER.to_vec(),
+            max_length: None,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for LineReaderOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Table(t) => {
+                let delimiter = match t.get::<_, Option<LuaString>>("delimiter")? {
+                    None => DEFAULT_DELIMITER.to_vec(),
+                    Some(s) if s.as_bytes().is_empty() => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid line options - delimiter must not be empty".to_string(),
+                        ))
+                    }
+                    Some(s) => s.as_bytes().to_vec(),
+                };
+                let max_length = match t.get::<_, Option<usize>>("maxLength")? {
+                    Some(0) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid line options - maxLength must be greater than zero"
+                                .to_string(),
+                        ))
+                    }
+                    max_length => max_length,
+                };
+                Ok(Self {
+                    delimiter,
+                    max_length,
+                })
+            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "LineReaderOptions",
+                message: Some(format!(
+                    "Invalid line options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    A source of bytes for a [`LineReader`], for handles that
+    are shared with other methods and can not be moved into it.
+*/
+#[async_trait]
+pub trait LineSource: Send {
+    /**
+        Reads the next chunk of bytes, returning `None` once there is nothing more to read.
+    */
+    async fn read_chunk(&mut self) -> LuaResult<Option<Vec<u8>>>;
+}
+
+struct AsyncReadSource<R>(R);
+
+#[async_trait]
+impl<R> LineSource for AsyncReadSource<R>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    async fn read_chunk(&mut self) -> LuaResult<Option<Vec<u8>>> {
+        let mut chunk = vec![0; READ_CHUNK_SIZE];
+        let read = self.0.read(&mut chunk).await?;
+        chunk.truncate(read);
+        Ok(if read == 0 { None } else { Some(chunk) })
+    }
+}
+
+/**
+    Reads delimited lines from any async reader or line source,
+    without reading more of the underlying source than necessary.
+
+    Once a line is longer than the max length, the reader fails and any
+    later reads return the same error, since the rest of that line is
+    never buffered and the reader can not know where the next line starts.
+*/
+pub struct LineReader {
+    source: Box<dyn LineSource>,
+    options: LineReaderOptions,
+    buffer: Vec<u8>,
+    searched: usize,
+    eof: bool,
+    failed: Option<String>,
+}
+
+impl LineReader {
+    pub fn new(
+        reader: impl AsyncRead + Unpin + Send + 'static,
+        options: LineReaderOptions,
+    ) -> Self {
+        Self::from_source(AsyncReadSource(reader), options)
+    }
+
+    pub fn from_source(source: impl LineSource + 'static, options: LineReaderOptions) -> Self {
+        Self {
+            source: Box::new(source),
+            options,
+            buffer: Vec::new(),
+            searched: 0,
+            eof: false,
+            failed: None,
+        }
+    }
+
+    fn find_delimiter(&self) -> Option<usize> {
+        let delimiter = &self.options.delimiter;
+        if self.buffer.len() < delimiter.len() {
+            return None;
+        }
+        // NOTE: The delimiter may have been split across two reads,
+        // so we need to search slightly before the previous search end
+        let start = self.searched.saturating_sub(delimiter.len() - 1);
+        self.buffer[start..]
+            .windows(delimiter.len())
+            .position(|window| window == delimiter.as_slice())
+            .map(|pos| start + pos)
+    }
+
+    fn check_length(&mut self, length: usize) -> LuaResult<()> {
+        match self.options.max_length {
+            Some(max_length) if length > max_length => {
+                let message = format!("Line exceeded maximum length of {max_length} bytes");
+                self.buffer = Vec::new();
+                self.searched = 0;
+                self.failed = Some(message.clone());
+                Err(io::Error::new(io::ErrorKind::InvalidData, message).into_lua_err())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /**
+        Reads the next line, without its delimiter.
+
+        If the default newline delimiter is being used, a trailing
+        carriage return will also be removed from the line.
+
+        Returns `None` once there are no more lines to read.
+    */
+    pub async fn next_line(&mut self) -> LuaResult<Option<Vec<u8>>> {
+        if let Some(message) = &self.failed {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message.clone()).into_lua_err());
+        }
+        loop {
+            if let Some(pos) = self.find_delimiter() {
+                self.check_length(pos)?;
+                let delimiter_len = self.options.delimiter.len();
+                let mut line = self.buffer.drain(..pos + delimiter_len).collect::<Vec<_>>();
+                line.truncate(pos);
+                if self.options.delimiter == DEFAULT_DELIMITER && line.ends_with(b"\r") {
+                    line.pop();
+                }
+                self.searched = 0;
+                return Ok(Some(line));
+            }
+
+            self.searched = self.buffer.len();
+            self.check_length(self.buffer.len())?;
+
+            if self.eof {
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+                self.searched = 0;
+                return Ok(Some(std::mem::take(&mut self.buffer)));
+            }
+
+            match self.source.read_chunk().await? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => self.eof = true,
+            }
+        }
+    }
+
+    /**
+        Creates a Lua table for this line reader, with a single `next` method
+        that yields until the next line is available, or returns `nil` when done.
+
+        NOTE: The table has no `__iter` metamethod, since Luau does not allow
+        iterator functions in generic for loops to yield, and reading does.
+    */
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable> {
+        let reader = Arc::new(AsyncMutex::new(self));
+        TableBuilder::new(lua)?
+            .with_async_function("next", move |lua, _: LuaValue| {
+                let reader = Arc::clone(&reader);
+                async move {
+                    let mut reader = reader.lock().await;
+                    match reader.next_line().await? {
+                        Some(line) => Ok(LuaValue::String(lua.create_string(line)?)),
+                        None => Ok(LuaValue::Nil),
+                    }
+                }
+            })?
+            .build_readonly()
+    }
+}
@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use mlua::prelude::*;
+use tokio::fs;
+
+/**
+    Creates a symbolic link at `link`, pointing to `target`.
+
+    Relative targets are relative to the directory that the link is in,
+    same as for `ln -s`, and are kept relative in the created link.
+*/
+pub async fn symlink(target: impl AsRef<Path>, link: impl AsRef<Path>) -> LuaResult<()> {
+    let target = target.as_ref();
+    let link = link.as_ref();
+
+    #[cfg(unix)]
+    fs::symlink(target, link).await?;
+
+    // NOTE: Windows has separate links for files and directories,
+    // so we need to know what the link will be pointing to first
+    #[cfg(windows)]
+    {
+        let resolved = match link.parent() {
+            Some(parent) => parent.join(target),
+            None => target.to_path_buf(),
+        };
+        if fs::metadata(&resolved)
+            .await
+            .is_ok_and(|meta| meta.is_dir())
+        {
+            fs::symlink_dir(target, link).await?;
+        } else {
+            fs::symlink_file(target, link).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/**
+    Reads the path that the symbolic link at `link` points to.
+*/
+pub async fn read_link(link: impl AsRef<Path>) -> LuaResult<String> {
+    let link = link.as_ref();
+    let target = fs::read_link(link).await?;
+    match target.to_str() {
+        Some(target) => Ok(target.to_string()),
+        None => Err(LuaError::RuntimeError(format!(
+            "Link target could not be converted into a string: '{}'",
+            target.display()
+        ))),
+    }
+}
+
+/**
+    Creates a hard link at `link`, for the existing file at `target`.
+*/
+pub async fn hard_link(target: impl AsRef<Path>, link: impl AsRef<Path>) -> LuaResult<()> {
+    fs::hard_link(target, link).await?;
+    Ok(())
+}
@@ -0,0 +1,356 @@
+use std::{
+    collections::BTreeMap,
+    env::current_dir,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use mlua::prelude::*;
+
+use crate::lune::util::TableBuilder;
+
+use super::{
+    metadata::{FsMetadata, FsMetadataKind, FsPermissions},
+    options::FsWriteOptions,
+};
+
+const REGISTRY_KEY: &str = "MemoryFs";
+
+#[derive(Debug, Clone)]
+enum MemoryEntryKind {
+    File(Vec<u8>),
+    Dir,
+}
+
+#[derive(Debug, Clone)]
+struct MemoryEntry {
+    kind: MemoryEntryKind,
+    created_at: f64,
+    modified_at: f64,
+}
+
+impl MemoryEntry {
+    fn new(kind: MemoryEntryKind) -> Self {
+        let now = timestamp_now();
+        Self {
+            kind,
+            created_at: now,
+            modified_at: now,
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        matches!(self.kind, MemoryEntryKind::Dir)
+    }
+}
+
+/**
+    An in-memory filesystem, created using `fs.useMemoryFs`.
+
+    While a memory filesystem is active, all `fs` functions operate on
+    it instead of the real filesystem. Relative paths are resolved using
+    the current working directory, which always exists in the memory
+    filesystem, along with all of its ancestors.
+*/
+#[derive(Debug)]
+pub struct MemoryFs {
+    cwd: PathBuf,
+    entries: Mutex<BTreeMap<PathBuf, MemoryEntry>>,
+}
+
+impl MemoryFs {
+    fn new() -> io::Result<Self> {
+        let cwd = current_dir()?;
+        let entries = cwd
+            .ancestors()
+            .map(|dir| (dir.to_path_buf(), MemoryEntry::new(MemoryEntryKind::Dir)))
+            .collect();
+        Ok(Self {
+            cwd,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /**
+        Gets the currently active memory filesystem, if any.
+    */
+    pub fn active(lua: &Lua) -> Option<Arc<MemoryFs>> {
+        match lua.named_registry_value::<LuaValue>(REGISTRY_KEY) {
+            Ok(LuaValue::UserData(ud)) => ud
+                .borrow::<MemoryFsHandle>()
+                .ok()
+                .map(|handle| Arc::clone(&handle.0)),
+            _ => None,
+        }
+    }
+
+    fn set_active(lua: &Lua, fs: Option<Arc<MemoryFs>>) -> LuaResult<()> {
+        match fs {
+            Some(fs) => lua.set_named_registry_value(REGISTRY_KEY, MemoryFsHandle(fs)),
+            None => lua.unset_named_registry_value(REGISTRY_KEY),
+        }
+    }
+
+    fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        path_clean::clean(self.cwd.join(path))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<PathBuf, MemoryEntry>> {
+        self.entries
+            .lock()
+            .expect("Failed to lock memory filesystem")
+    }
+
+    pub fn read_file(&self, path: &str) -> io::Result<Vec<u8>> {
+        let resolved = self.resolve(path);
+        match self.lock().get(&resolved).map(|entry| &entry.kind) {
+            Some(MemoryEntryKind::File(contents)) => Ok(contents.clone()),
+            Some(MemoryEntryKind::Dir) => Err(is_a_directory(path)),
+            None => Err(not_found(path)),
+        }
+    }
+
+    pub fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        let resolved = self.resolve(path);
+        let entries = self.lock();
+        match entries.get(&resolved) {
+            Some(entry) if entry.is_dir() => Ok(entries
+                .keys()
+                .filter(|child| child.parent() == Some(resolved.as_path()))
+                .filter_map(|child| child.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .collect()),
+            Some(_) => Err(not_a_directory(path)),
+            None => Err(not_found(path)),
+        }
+    }
+
+    pub fn write_file(&self, path: &str, contents: &[u8]) -> io::Result<()> {
+        let resolved = self.resolve(path);
+        let mut entries = self.lock();
+        ensure_parent_dir(&entries, &resolved, path)?;
+        match entries.get_mut(&resolved) {
+            Some(entry) if entry.is_dir() => Err(is_a_directory(path)),
+            Some(entry) => {
+                entry.kind = MemoryEntryKind::File(contents.to_vec());
+                entry.modified_at = timestamp_now();
+                Ok(())
+            }
+            None => {
+                entries.insert(
+                    resolved,
+                    MemoryEntry::new(MemoryEntryKind::File(contents.to_vec())),
+                );
+                Ok(())
+            }
+        }
+    }
+
+    pub fn write_dir(&self, path: &str) -> io::Result<()> {
+        let resolved = self.resolve(path);
+        let mut entries = self.lock();
+        // NOTE: Ancestors are created from the root and downwards, the
+        // same way as create_dir_all would, failing if a file is in the way
+        let mut ancestors = resolved.ancestors().collect::<Vec<_>>();
+        ancestors.reverse();
+        for dir in ancestors {
+            match entries.get(dir) {
+                Some(entry) if entry.is_dir() => {}
+                Some(_) => {
+                    return Err(io::Error::new(
+                        ErrorKind::AlreadyExists,
+                        format!("A file already exists at the path '{}'", dir.display()),
+                    ))
+                }
+                None => {
+                    entries.insert(dir.to_path_buf(), MemoryEntry::new(MemoryEntryKind::Dir));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove_file(&self, path: &str) -> io::Result<()> {
+        let resolved = self.resolve(path);
+        let mut entries = self.lock();
+        match entries.get(&resolved) {
+            Some(entry) if entry.is_dir() => Err(is_a_directory(path)),
+            Some(_) => {
+                entries.remove(&resolved);
+                Ok(())
+            }
+            None => Err(not_found(path)),
+        }
+    }
+
+    pub fn remove_dir(&self, path: &str) -> io::Result<()> {
+        let resolved = self.resolve(path);
+        let mut entries = self.lock();
+        match entries.get(&resolved) {
+            Some(entry) if entry.is_dir() => {
+                entries.retain(|entry_path, _| !entry_path.starts_with(&resolved));
+                Ok(())
+            }
+            Some(_) => Err(not_a_directory(path)),
+            None => Err(not_found(path)),
+        }
+    }
+
+    pub fn metadata(&self, path: &str) -> FsMetadata {
+        let resolved = self.resolve(path);
+        match self.lock().get(&resolved) {
+            None => FsMetadata::not_found(),
+            Some(entry) => FsMetadata {
+                kind: if entry.is_dir() {
+                    FsMetadataKind::Dir
+                } else {
+                    FsMetadataKind::File
+                },
+                exists: true,
+                created_at: Some(entry.created_at),
+                modified_at: Some(entry.modified_at),
+                accessed_at: Some(entry.modified_at),
+                permissions: Some(FsPermissions {
+                    read_only: false,
+                    mode: None,
+                }),
+            },
+        }
+    }
+
+    /**
+        Moves or copies the file or directory at `from`, including
+        all of its descendants, to `to`, following the same rules
+        as `fs.move` and `fs.copy` do for the real filesystem.
+    */
+    pub fn transfer(
+        &self,
+        from: &str,
+        to: &str,
+        options: FsWriteOptions,
+        remove_source: bool,
+    ) -> LuaResult<()> {
+        let resolved_from = self.resolve(from);
+        let resolved_to = self.resolve(to);
+        let mut entries = self.lock();
+        if !entries.contains_key(&resolved_from) {
+            return Err(LuaError::RuntimeError(format!(
+                "No file or directory exists at the path '{from}'"
+            )));
+        }
+        if entries.contains_key(&resolved_to) {
+            if !options.overwrite {
+                return Err(LuaError::RuntimeError(format!(
+                    "A file or directory already exists at the path '{to}'"
+                )));
+            }
+            entries.retain(|entry_path, _| !entry_path.starts_with(&resolved_to));
+        }
+        if resolved_to.starts_with(&resolved_from) {
+            return Err(LuaError::RuntimeError(format!(
+                "Can not move or copy '{from}' into itself at the path '{to}'"
+            )));
+        }
+        ensure_parent_dir(&entries, &resolved_to, to).into_lua_err()?;
+
+        let transferred = entries
+            .iter()
+            .filter(|(entry_path, _)| entry_path.starts_with(&resolved_from))
+            .map(|(entry_path, entry)| {
+                let relative = entry_path
+                    .strip_prefix(&resolved_from)
+                    .expect("Transferred path must be inside of the source path");
+                (resolved_to.join(relative), entry.clone())
+            })
+            .collect::<Vec<_>>();
+        if remove_source {
+            entries.retain(|entry_path, _| !entry_path.starts_with(&resolved_from));
+        }
+        entries.extend(transferred);
+
+        Ok(())
+    }
+}
+
+struct MemoryFsHandle(Arc<MemoryFs>);
+
+impl LuaUserData for MemoryFsHandle {}
+
+pub fn fs_use_memory_fs<'lua>(
+    lua: &'lua Lua,
+    files: Option<LuaTable<'lua>>,
+) -> LuaResult<LuaTable<'lua>> {
+    if MemoryFs::active(lua).is_some() {
+        return Err(LuaError::RuntimeError(
+            "A memory filesystem is already active - call restore on it before creating another"
+                .to_string(),
+        ));
+    }
+
+    let memory_fs = Arc::new(MemoryFs::new().into_lua_err()?);
+    if let Some(files) = files {
+        for pair in files.pairs::<String, LuaString>() {
+            let (path, contents) = pair?;
+            if let Some(parent) = Path::new(&path).parent() {
+                memory_fs
+                    .write_dir(&parent.to_string_lossy())
+                    .into_lua_err()?;
+            }
+            memory_fs
+                .write_file(&path, contents.as_bytes())
+                .into_lua_err()?;
+        }
+    }
+    MemoryFs::set_active(lua, Some(Arc::clone(&memory_fs)))?;
+
+    TableBuilder::new(lua)?
+        .with_function("restore", move |lua, _: LuaValue| {
+            // NOTE: Only remove the memory filesystem if it is still the active
+            // one, restoring an old one should not remove any newer one
+            if let Some(active) = MemoryFs::active(lua) {
+                if Arc::ptr_eq(&active, &memory_fs) {
+                    MemoryFs::set_active(lua, None)?;
+                }
+            }
+            Ok(())
+        })?
+        .build_readonly()
+}
+
+fn ensure_parent_dir(
+    entries: &BTreeMap<PathBuf, MemoryEntry>,
+    resolved: &Path,
+    path: &str,
+) -> io::Result<()> {
+    match resolved.parent().map(|parent| entries.get(parent)) {
+        Some(Some(parent)) if parent.is_dir() => Ok(()),
+        Some(Some(_)) => Err(not_a_directory(path)),
+        Some(None) => Err(not_found(path)),
+        // NOTE: The root has no parent, but always exists
+        None => Ok(()),
+    }
+}
+
+fn timestamp_now() -> f64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs_f64())
+        .unwrap_or_default()
+}
+
+fn not_found(path: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::NotFound,
+        format!("No such file or directory at the path '{path}'"),
+    )
+}
+
+fn not_a_directory(path: &str) -> io::Error {
+    io::Error::other(format!("Not a directory at the path '{path}'"))
+}
+
+fn is_a_directory(path: &str) -> io::Error {
+    io::Error::other(format!("Is a directory at the path '{path}'"))
+}
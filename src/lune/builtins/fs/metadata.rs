@@ -72,20 +72,30 @@ impl<'lua> IntoLua<'lua> for FsMetadataKind {
 #[derive(Debug, Clone)]
 pub struct FsPermissions {
     pub(crate) read_only: bool,
+    pub(crate) mode: Option<u32>,
 }
 
 impl From<StdPermissions> for FsPermissions {
     fn from(value: StdPermissions) -> Self {
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(value.mode() & 0o7777)
+        };
+        #[cfg(not(unix))]
+        let mode = None;
         Self {
             read_only: value.readonly(),
+            mode,
         }
     }
 }
 
 impl<'lua> IntoLua<'lua> for FsPermissions {
     fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
-        let tab = lua.create_table_with_capacity(0, 1)?;
+        let tab = lua.create_table_with_capacity(0, 2)?;
         tab.set("readOnly", self.read_only)?;
+        tab.set("mode", self.mode)?;
         tab.set_readonly(true);
         Ok(LuaValue::Table(tab))
     }
@@ -112,6 +122,28 @@ impl FsMetadata {
             permissions: None,
         }
     }
+
+    /**
+        Creates metadata for a file or directory inside of a bundle,
+        which are always read-only and have no known timestamps.
+    */
+    pub fn bundled(is_dir: bool) -> Self {
+        Self {
+            kind: if is_dir {
+                FsMetadataKind::Dir
+            } else {
+                FsMetadataKind::File
+            },
+            exists: true,
+            created_at: None,
+            modified_at: None,
+            accessed_at: None,
+            permissions: Some(FsPermissions {
+                read_only: true,
+                mode: None,
+            }),
+        }
+    }
 }
 
 impl<'lua> IntoLua<'lua> for FsMetadata {
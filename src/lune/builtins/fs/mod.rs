@@ -1,23 +1,48 @@
-use std::io::ErrorKind as IoErrorKind;
+use std::io::{Cursor, ErrorKind as IoErrorKind};
 use std::path::{PathBuf, MAIN_SEPARATOR};
 
 use mlua::prelude::*;
 use tokio::fs;
 
-use crate::lune::util::TableBuilder;
+use crate::lune::{bundle::Bundle, util::TableBuilder};
 
 mod copy;
+mod file;
+mod glob;
+pub(super) mod lines;
+mod links;
+mod memory;
 mod metadata;
 mod options;
+mod permissions;
+mod temp;
+#[cfg(feature = "watch")]
+mod watch;
 
-use copy::copy;
-use metadata::FsMetadata;
-use options::FsWriteOptions;
+use self::glob::{Glob, GlobOptions};
+use copy::{copy, rename};
+use file::{FileHandle, FsOpenMode};
+use lines::{LineReader, LineReaderOptions};
+use links::{hard_link, read_link, symlink};
+use memory::{fs_use_memory_fs, MemoryFs};
+use metadata::{FsMetadata, FsMetadataKind};
+use options::{FsCopyOptions, FsWriteOptions};
+use permissions::{set_permissions, FsPermissionsUpdate};
+use temp::{FsTempHandle, FsTempOptions};
+#[cfg(feature = "watch")]
+use watch::fs_watch;
 
 pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    #[cfg(feature = "watch")]
+    let watch = LuaValue::Function(lua.create_function(fs_watch)?);
+    #[cfg(not(feature = "watch"))]
+    let watch = LuaValue::Nil;
     TableBuilder::new(lua)?
         .with_async_function("readFile", fs_read_file)?
+        .with_async_function("readLines", fs_read_lines)?
         .with_async_function("readDir", fs_read_dir)?
+        .with_async_function("open", fs_open)?
+        .with_async_function("glob", fs_glob)?
         .with_async_function("writeFile", fs_write_file)?
         .with_async_function("writeDir", fs_write_dir)?
         .with_async_function("removeFile", fs_remove_file)?
@@ -27,15 +52,70 @@ pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
         .with_async_function("isDir", fs_is_dir)?
         .with_async_function("move", fs_move)?
         .with_async_function("copy", fs_copy)?
+        .with_async_function("rename", fs_rename)?
+        .with_async_function("symlink", fs_symlink)?
+        .with_async_function("readLink", fs_read_link)?
+        .with_async_function("hardlink", fs_hardlink)?
+        .with_async_function("setPermissions", fs_set_permissions)?
+        .with_async_function("chmod", fs_set_permissions)?
+        .with_function("tempDir", fs_temp_dir)?
+        .with_function("tempFile", fs_temp_file)?
+        .with_value("watch", watch)?
+        .with_function("useMemoryFs", fs_use_memory_fs)?
         .build_readonly()
 }
 
 async fn fs_read_file(lua: &Lua, path: String) -> LuaResult<LuaString> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        return lua.create_string(memory_fs.read_file(&path).into_lua_err()?);
+    }
+    if let Some(contents) = Bundle::active(lua).and_then(|b| b.read_file(&path)) {
+        return lua.create_string(contents.into_lua_err()?);
+    }
     let bytes = fs::read(&path).await.into_lua_err()?;
     lua.create_string(bytes)
 }
 
-async fn fs_read_dir(_: &Lua, path: String) -> LuaResult<Vec<String>> {
+async fn fs_read_lines(
+    lua: &'static Lua,
+    (path, options): (String, LineReaderOptions),
+) -> LuaResult<LuaTable> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        let contents = memory_fs.read_file(&path).into_lua_err()?;
+        return LineReader::new(Cursor::new(contents), options).into_lua_table(lua);
+    }
+    if let Some(contents) = Bundle::active(lua).and_then(|b| b.read_file(&path)) {
+        let contents = contents.into_lua_err()?;
+        return LineReader::new(Cursor::new(contents), options).into_lua_table(lua);
+    }
+    let file = fs::File::open(&path).await.into_lua_err()?;
+    LineReader::new(file, options).into_lua_table(lua)
+}
+
+async fn fs_open(lua: &'static Lua, (path, mode): (String, FsOpenMode)) -> LuaResult<LuaTable> {
+    ensure_real_fs(lua, "Opening files")?;
+    ensure_outside_bundle(lua, &path)?;
+    FileHandle::open(&path, mode).await?.into_lua_table(lua)
+}
+
+async fn fs_read_dir(lua: &Lua, path: String) -> LuaResult<Vec<String>> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        return memory_fs.read_dir(&path).into_lua_err();
+    }
+    if let Some(mut names) = Bundle::active(lua).and_then(|b| b.read_dir(&path)) {
+        // NOTE: The root of a bundle is also the current working directory,
+        // so any entries that exist in the real filesystem are also included
+        if let Ok(real_names) = read_dir(path).await {
+            names.extend(real_names);
+            names.sort();
+            names.dedup();
+        }
+        return Ok(names);
+    }
+    read_dir(path).await
+}
+
+async fn read_dir(path: String) -> LuaResult<Vec<String>> {
     let mut dir_strings = Vec::new();
     let mut dir = fs::read_dir(&path).await.into_lua_err()?;
     while let Some(dir_entry) = dir.next_entry().await.into_lua_err()? {
@@ -64,23 +144,62 @@ async fn fs_read_dir(_: &Lua, path: String) -> LuaResult<Vec<String>> {
     Ok(dir_strings_no_prefix)
 }
 
-async fn fs_write_file(_: &Lua, (path, contents): (String, LuaString<'_>)) -> LuaResult<()> {
+async fn fs_glob(
+    lua: &'static Lua,
+    (pattern, options): (String, GlobOptions),
+) -> LuaResult<LuaValue> {
+    ensure_real_fs(lua, "Globbing")?;
+    let glob = Glob::new(&pattern, options)?;
+    if options.iterator {
+        glob.into_lua_table(lua).map(LuaValue::Table)
+    } else {
+        glob.collect_paths().await?.into_lua(lua)
+    }
+}
+
+async fn fs_write_file(lua: &Lua, (path, contents): (String, LuaString<'_>)) -> LuaResult<()> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        return memory_fs
+            .write_file(&path, contents.as_bytes())
+            .into_lua_err();
+    }
+    ensure_outside_bundle(lua, &path)?;
     fs::write(&path, &contents.as_bytes()).await.into_lua_err()
 }
 
-async fn fs_write_dir(_: &Lua, path: String) -> LuaResult<()> {
+async fn fs_write_dir(lua: &Lua, path: String) -> LuaResult<()> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        return memory_fs.write_dir(&path).into_lua_err();
+    }
+    ensure_outside_bundle(lua, &path)?;
     fs::create_dir_all(&path).await.into_lua_err()
 }
 
-async fn fs_remove_file(_: &Lua, path: String) -> LuaResult<()> {
+async fn fs_remove_file(lua: &Lua, path: String) -> LuaResult<()> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        return memory_fs.remove_file(&path).into_lua_err();
+    }
+    ensure_outside_bundle(lua, &path)?;
     fs::remove_file(&path).await.into_lua_err()
 }
 
-async fn fs_remove_dir(_: &Lua, path: String) -> LuaResult<()> {
+async fn fs_remove_dir(lua: &Lua, path: String) -> LuaResult<()> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        return memory_fs.remove_dir(&path).into_lua_err();
+    }
+    ensure_outside_bundle(lua, &path)?;
     fs::remove_dir_all(&path).await.into_lua_err()
 }
 
-async fn fs_metadata(_: &Lua, path: String) -> LuaResult<FsMetadata> {
+async fn fs_metadata(lua: &Lua, path: String) -> LuaResult<FsMetadata> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        return Ok(memory_fs.metadata(&path));
+    }
+    if let Some(bundle) = Bundle::active(lua) {
+        if bundle.contains(&path) {
+            return Ok(FsMetadata::bundled(bundle.is_dir(&path)));
+        }
+    }
     match fs::metadata(path).await {
         Err(e) if e.kind() == IoErrorKind::NotFound => Ok(FsMetadata::not_found()),
         Ok(meta) => Ok(FsMetadata::from(meta)),
@@ -88,7 +207,13 @@ async fn fs_metadata(_: &Lua, path: String) -> LuaResult<FsMetadata> {
     }
 }
 
-async fn fs_is_file(_: &Lua, path: String) -> LuaResult<bool> {
+async fn fs_is_file(lua: &Lua, path: String) -> LuaResult<bool> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        return Ok(memory_fs.metadata(&path).kind == FsMetadataKind::File);
+    }
+    if Bundle::active(lua).is_some_and(|b| b.is_file(&path)) {
+        return Ok(true);
+    }
     match fs::metadata(path).await {
         Err(e) if e.kind() == IoErrorKind::NotFound => Ok(false),
         Ok(meta) => Ok(meta.is_file()),
@@ -96,7 +221,13 @@ async fn fs_is_file(_: &Lua, path: String) -> LuaResult<bool> {
     }
 }
 
-async fn fs_is_dir(_: &Lua, path: String) -> LuaResult<bool> {
+async fn fs_is_dir(lua: &Lua, path: String) -> LuaResult<bool> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        return Ok(memory_fs.metadata(&path).kind == FsMetadataKind::Dir);
+    }
+    if Bundle::active(lua).is_some_and(|b| b.is_dir(&path)) {
+        return Ok(true);
+    }
     match fs::metadata(path).await {
         Err(e) if e.kind() == IoErrorKind::NotFound => Ok(false),
         Ok(meta) => Ok(meta.is_dir()),
@@ -104,7 +235,15 @@ async fn fs_is_dir(_: &Lua, path: String) -> LuaResult<bool> {
     }
 }
 
-async fn fs_move(_: &Lua, (from, to, options): (String, String, FsWriteOptions)) -> LuaResult<()> {
+async fn fs_move(
+    lua: &Lua,
+    (from, to, options): (String, String, FsWriteOptions),
+) -> LuaResult<()> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        return memory_fs.transfer(&from, &to, options, true);
+    }
+    ensure_outside_bundle(lua, &from)?;
+    ensure_outside_bundle(lua, &to)?;
     let path_from = PathBuf::from(from);
     if !path_from.exists() {
         return Err(LuaError::RuntimeError(format!(
@@ -123,6 +262,92 @@ async fn fs_move(_: &Lua, (from, to, options): (String, String, FsWriteOptions))
     Ok(())
 }
 
-async fn fs_copy(_: &Lua, (from, to, options): (String, String, FsWriteOptions)) -> LuaResult<()> {
-    copy(from, to, options).await
+async fn fs_copy<'lua>(
+    lua: &'lua Lua,
+    (from, to, options): (String, String, FsCopyOptions<'lua>),
+) -> LuaResult<()> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        if !options.recursive && memory_fs.metadata(&from).kind == FsMetadataKind::Dir {
+            return Err(LuaError::RuntimeError(format!(
+                "The path '{from}' is a directory, and can only be copied using the recursive option"
+            )));
+        }
+        return memory_fs.transfer(&from, &to, options.write_options(), false);
+    }
+    ensure_outside_bundle(lua, &to)?;
+    copy(from, to, &options).await
+}
+
+async fn fs_rename<'lua>(
+    lua: &'lua Lua,
+    (from, to, options): (String, String, FsCopyOptions<'lua>),
+) -> LuaResult<()> {
+    if let Some(memory_fs) = MemoryFs::active(lua) {
+        return memory_fs.transfer(&from, &to, options.write_options(), true);
+    }
+    ensure_outside_bundle(lua, &from)?;
+    ensure_outside_bundle(lua, &to)?;
+    rename(from, to, &options).await
+}
+
+async fn fs_symlink(lua: &Lua, (target, link): (String, String)) -> LuaResult<()> {
+    ensure_real_fs(lua, "Creating links")?;
+    ensure_outside_bundle(lua, &link)?;
+    symlink(target, link).await
+}
+
+async fn fs_read_link(lua: &Lua, link: String) -> LuaResult<String> {
+    ensure_real_fs(lua, "Reading links")?;
+    read_link(link).await
+}
+
+async fn fs_hardlink(lua: &Lua, (target, link): (String, String)) -> LuaResult<()> {
+    ensure_real_fs(lua, "Creating links")?;
+    ensure_outside_bundle(lua, &link)?;
+    hard_link(target, link).await
+}
+
+async fn fs_set_permissions(
+    lua: &Lua,
+    (path, update): (String, FsPermissionsUpdate),
+) -> LuaResult<()> {
+    ensure_real_fs(lua, "Changing permissions")?;
+    ensure_outside_bundle(lua, &path)?;
+    set_permissions(path, update).await
+}
+
+fn fs_temp_dir(lua: &Lua, options: FsTempOptions) -> LuaResult<FsTempHandle> {
+    ensure_real_fs(lua, "Creating temporary directories")?;
+    FsTempHandle::dir(&options)
+}
+
+fn fs_temp_file(lua: &Lua, options: FsTempOptions) -> LuaResult<FsTempHandle> {
+    ensure_real_fs(lua, "Creating temporary files")?;
+    FsTempHandle::file(&options)
+}
+
+/**
+    Makes sure that no memory filesystem is active, for
+    operations that are only supported by the real filesystem.
+*/
+pub(super) fn ensure_real_fs(lua: &Lua, operation: &str) -> LuaResult<()> {
+    match MemoryFs::active(lua) {
+        Some(_) => Err(LuaError::RuntimeError(format!(
+            "{operation} is not supported while using a memory filesystem"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/**
+    Makes sure that the given path is not inside of the running bundle, if
+    any, since files in bundles are read-only and can not be opened or changed.
+*/
+fn ensure_outside_bundle(lua: &Lua, path: &str) -> LuaResult<()> {
+    match Bundle::active(lua) {
+        Some(bundle) if bundle.contains(path) => Err(LuaError::RuntimeError(format!(
+            "The path '{path}' is inside of a bundle, and is read-only"
+        ))),
+        _ => Ok(()),
+    }
 }
@@ -29,3 +29,105 @@ impl<'lua> FromLua<'lua> for FsWriteOptions {
         })
     }
 }
+
+#[cfg(feature = "watch")]
+#[derive(Debug, Clone, Copy)]
+pub struct FsWatchOptions {
+    pub(crate) recursive: bool,
+}
+
+#[cfg(feature = "watch")]
+impl<'lua> FromLua<'lua> for FsWatchOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self { recursive: true },
+            LuaValue::Table(t) => {
+                let recursive: Option<bool> = t.get("recursive")?;
+                Self {
+                    recursive: recursive.unwrap_or(true),
+                }
+            }
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsWatchOptions",
+                    message: Some(format!(
+                        "Invalid watch options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FsCopyOptions<'lua> {
+    pub(crate) overwrite: bool,
+    pub(crate) recursive: bool,
+    pub(crate) preserve: bool,
+    pub(crate) progress: Option<LuaFunction<'lua>>,
+}
+
+impl Default for FsCopyOptions<'_> {
+    fn default() -> Self {
+        Self {
+            overwrite: false,
+            recursive: true,
+            preserve: false,
+            progress: None,
+        }
+    }
+}
+
+impl<'lua> FsCopyOptions<'lua> {
+    pub fn write_options(&self) -> FsWriteOptions {
+        FsWriteOptions {
+            overwrite: self.overwrite,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for FsCopyOptions<'lua> {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let defaults = Self::default();
+        Ok(match value {
+            LuaValue::Nil => defaults,
+            LuaValue::Boolean(b) => Self {
+                overwrite: b,
+                ..defaults
+            },
+            LuaValue::Table(t) => Self {
+                overwrite: t
+                    .get::<_, Option<bool>>("overwrite")?
+                    .unwrap_or(defaults.overwrite),
+                recursive: t
+                    .get::<_, Option<bool>>("recursive")?
+                    .unwrap_or(defaults.recursive),
+                preserve: t
+                    .get::<_, Option<bool>>("preserve")?
+                    .unwrap_or(defaults.preserve),
+                progress: match t.get::<_, LuaValue>("progress")? {
+                    LuaValue::Nil => None,
+                    LuaValue::Function(f) => Some(f),
+                    value => {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Invalid option value for 'progress' in copy options - expected function, got {}",
+                            value.type_name()
+                        )))
+                    }
+                },
+            },
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "FsCopyOptions",
+                    message: Some(format!(
+                        "Invalid copy options - expected boolean or table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        })
+    }
+}
@@ -0,0 +1,149 @@
+use std::{fs::Permissions, path::Path};
+
+use mlua::prelude::*;
+use tokio::fs;
+
+/**
+    Changes to make to the permissions of a file or directory.
+
+    Modes use the same bits as `chmod` on unix, and on other platforms,
+    only the owner write bit is used, to set if the path is read-only.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FsPermissionsUpdate {
+    pub(crate) read_only: Option<bool>,
+    pub(crate) executable: Option<bool>,
+    pub(crate) mode: Option<u32>,
+}
+
+fn parse_mode(value: LuaValue) -> LuaResult<Option<u32>> {
+    let mode = match &value {
+        LuaValue::Nil => return Ok(None),
+        LuaValue::Integer(i) => u32::try_from(*i).ok(),
+        LuaValue::Number(n) if n.fract() == 0.0 && *n >= 0.0 => Some(*n as u32),
+        // NOTE: Strings are parsed as octal, the same way they are written for chmod
+        LuaValue::String(s) => s
+            .to_str()
+            .ok()
+            .and_then(|s| u32::from_str_radix(s.trim().trim_start_matches("0o"), 8).ok()),
+        _ => None,
+    };
+    match mode {
+        Some(mode) if mode <= 0o7777 => Ok(Some(mode)),
+        _ => Err(LuaError::RuntimeError(format!(
+            "Invalid permissions mode - expected a number or octal string between 0 and 7777, got {}",
+            match &value {
+                LuaValue::String(s) => format!("'{}'", s.to_string_lossy()),
+                value => value.type_name().to_string(),
+            }
+        ))),
+    }
+}
+
+impl<'lua> FromLua<'lua> for FsPermissionsUpdate {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Table(t) => Ok(Self {
+                read_only: t.get("readOnly")?,
+                executable: t.get("executable")?,
+                mode: parse_mode(t.get("mode")?)?,
+            }),
+            LuaValue::Integer(_) | LuaValue::Number(_) | LuaValue::String(_) => Ok(Self {
+                mode: parse_mode(value)?,
+                ..Self::default()
+            }),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "FsPermissionsUpdate",
+                message: Some(format!(
+                    "Invalid permissions - expected table, number or string, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+impl FsPermissionsUpdate {
+    #[cfg(unix)]
+    fn apply(self, permissions: &mut Permissions) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut mode = self.mode.unwrap_or(permissions.mode() & 0o7777);
+        // NOTE: We do not use set_readonly here since it makes files
+        // writable for everyone, instead of only for their owner
+        match self.read_only {
+            Some(true) => mode &= !0o222,
+            Some(false) => mode |= 0o200,
+            None => {}
+        }
+        // Executable bits are set for everyone that may read the file
+        match self.executable {
+            Some(true) => mode |= (mode & 0o444) >> 2,
+            Some(false) => mode &= !0o111,
+            None => {}
+        }
+        permissions.set_mode(mode);
+    }
+
+    #[cfg(not(unix))]
+    fn apply(self, permissions: &mut Permissions) {
+        if let Some(mode) = self.mode {
+            permissions.set_readonly(mode & 0o200 == 0);
+        }
+        if let Some(read_only) = self.read_only {
+            permissions.set_readonly(read_only);
+        }
+    }
+}
+
+pub async fn set_permissions(path: impl AsRef<Path>, update: FsPermissionsUpdate) -> LuaResult<()> {
+    let path = path.as_ref();
+    let mut permissions = fs::metadata(path).await?.permissions();
+    update.apply(&mut permissions);
+    fs::set_permissions(path, permissions).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg(unix)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    fn apply(mode: u32, update: FsPermissionsUpdate) -> u32 {
+        let mut permissions = Permissions::from_mode(mode);
+        update.apply(&mut permissions);
+        permissions.mode() & 0o7777
+    }
+
+    #[test]
+    fn updates_modes() {
+        let executable = FsPermissionsUpdate {
+            executable: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(apply(0o644, executable), 0o755);
+        assert_eq!(apply(0o600, executable), 0o700);
+
+        let read_only = FsPermissionsUpdate {
+            read_only: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(apply(0o664, read_only), 0o444);
+
+        let writable = FsPermissionsUpdate {
+            read_only: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(apply(0o444, writable), 0o644);
+
+        let mode = FsPermissionsUpdate {
+            mode: Some(0o750),
+            executable: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(apply(0o644, mode), 0o640);
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use mlua::prelude::*;
+use tempfile::{Builder, NamedTempFile, TempDir};
+
+const DEFAULT_PREFIX: &str = "lune-";
+
+#[derive(Debug, Clone, Default)]
+pub struct FsTempOptions {
+    pub(crate) prefix: Option<String>,
+    pub(crate) suffix: Option<String>,
+}
+
+impl<'lua> FromLua<'lua> for FsTempOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Table(t) => {
+                let validate = |key: &str| -> LuaResult<Option<String>> {
+                    match t.get::<_, Option<String>>(key)? {
+                        Some(s) if s.contains(['/', '\\']) => Err(LuaError::RuntimeError(
+                            format!("Invalid option value for '{key}' in temp options - must not contain path separators"),
+                        )),
+                        s => Ok(s),
+                    }
+                };
+                Ok(Self {
+                    prefix: validate("prefix")?,
+                    suffix: validate("suffix")?,
+                })
+            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "FsTempOptions",
+                message: Some(format!(
+                    "Invalid temp options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+impl FsTempOptions {
+    fn builder(&self) -> Builder {
+        let mut builder = Builder::new();
+        builder.prefix(self.prefix.as_deref().unwrap_or(DEFAULT_PREFIX));
+        if let Some(suffix) = &self.suffix {
+            builder.suffix(suffix);
+        }
+        builder
+    }
+}
+
+#[derive(Debug)]
+enum TempEntry {
+    Dir(TempDir),
+    File(NamedTempFile),
+}
+
+impl TempEntry {
+    fn path(&self) -> &Path {
+        match self {
+            Self::Dir(dir) => dir.path(),
+            Self::File(file) => file.path(),
+        }
+    }
+
+    fn remove(self) -> std::io::Result<()> {
+        match self {
+            Self::Dir(dir) => dir.close(),
+            Self::File(file) => file.close(),
+        }
+    }
+}
+
+/**
+    A temporary file or directory, created using `fs.tempDir` or `fs.tempFile`.
+
+    The temporary entry is removed when `cleanup` is called, or
+    otherwise once the handle is garbage collected, which means that
+    the handle must be kept around for as long as the entry is used.
+*/
+#[derive(Debug)]
+pub struct FsTempHandle {
+    path: String,
+    entry: Option<TempEntry>,
+}
+
+impl FsTempHandle {
+    fn new(entry: TempEntry) -> LuaResult<Self> {
+        let path = match entry.path().to_str() {
+            Some(path) => path.to_string(),
+            None => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Temporary path could not be converted into a string: '{}'",
+                    entry.path().display()
+                )))
+            }
+        };
+        Ok(Self {
+            path,
+            entry: Some(entry),
+        })
+    }
+
+    pub fn dir(options: &FsTempOptions) -> LuaResult<Self> {
+        Self::new(TempEntry::Dir(options.builder().tempdir().into_lua_err()?))
+    }
+
+    pub fn file(options: &FsTempOptions) -> LuaResult<Self> {
+        Self::new(TempEntry::File(
+            options.builder().tempfile().into_lua_err()?,
+        ))
+    }
+}
+
+impl LuaUserData for FsTempHandle {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("path", |_, this| Ok(this.path.clone()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("cleanup", |_, this, ()| match this.entry.take() {
+            // NOTE: Removing an entry that was already removed by
+            // the script is fine, there is nothing left to clean up
+            Some(entry) => match entry.remove() {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            },
+            None => Ok(()),
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("TempHandle({})", this.path))
+        });
+    }
+}
@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use mlua::prelude::*;
+use notify::{
+    event::{EventKind, ModifyKind},
+    Event, RecursiveMode, Watcher,
+};
+use tokio::sync::mpsc;
+
+use crate::lune::{
+    scheduler::Scheduler,
+    util::{traits::LuaEmitErrorExt, TableBuilder},
+};
+
+use super::{ensure_real_fs, options::FsWatchOptions};
+
+/**
+    Gets the name of the kind of an event given to lua,
+    or `None` if the event should not be given to lua.
+
+    Access events are skipped since they are not supported on all
+    platforms, and reading watched files would otherwise cause them.
+*/
+fn event_kind_name(kind: &EventKind) -> Option<&'static str> {
+    match kind {
+        EventKind::Create(_) => Some("create"),
+        EventKind::Modify(ModifyKind::Name(_)) => Some("rename"),
+        EventKind::Modify(_) => Some("modify"),
+        EventKind::Remove(_) => Some("remove"),
+        _ => None,
+    }
+}
+
+fn event_into_lua_table<'lua>(
+    lua: &'lua Lua,
+    kind: &'static str,
+    event: Event,
+) -> LuaResult<LuaTable<'lua>> {
+    let paths = event
+        .paths
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    TableBuilder::new(lua)?
+        .with_value("kind", kind)?
+        .with_value("paths", paths)?
+        .build_readonly()
+}
+
+pub fn fs_watch(
+    lua: &'static Lua,
+    (path, callback, options): (String, LuaFunction<'static>, FsWatchOptions),
+) -> LuaResult<LuaTable<'static>> {
+    ensure_real_fs(lua, "Watching")?;
+
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<notify::Result<Event>>();
+    let mut watcher = notify::recommended_watcher(move |result| {
+        // NOTE: Sending only fails once the watcher has been
+        // stopped, and any remaining events can be ignored
+        event_tx.send(result).ok();
+    })
+    .map_err(|e| LuaError::RuntimeError(format!("Failed to create watcher - {e}")))?;
+
+    let mode = if options.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher
+        .watch(Path::new(&path), mode)
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to watch '{path}' - {e}")))?;
+
+    // Note that we need to use a mpsc here and not
+    // a oneshot channel since we move the sender
+    // into our table with the stop function
+    let (stop_tx, mut stop_rx) = mpsc::channel::<()>(1);
+
+    let sched = *lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    sched.spawn_local(async move {
+        // NOTE: The watcher is moved into this thread so that it
+        // keeps watching until stopped, and is then dropped here
+        let _watcher = watcher;
+        loop {
+            let result = tokio::select! {
+                result = event_rx.recv() => result,
+                _ = stop_rx.recv() => None,
+            };
+            let handle_result = || match result {
+                None => Ok::<_, LuaError>(true),
+                Some(Err(e)) => Err(LuaError::RuntimeError(format!(
+                    "Failed to watch '{path}' - {e}"
+                ))),
+                Some(Ok(event)) => {
+                    if let Some(kind) = event_kind_name(&event.kind) {
+                        let event = event_into_lua_table(lua, kind, event)?;
+                        sched.push_back(lua, callback.clone(), event)?;
+                    }
+                    Ok(false)
+                }
+            };
+            match handle_result() {
+                Ok(true) => break,
+                Ok(false) => continue,
+                Err(e) => lua.emit_error(e),
+            }
+        }
+    });
+
+    TableBuilder::new(lua)?
+        .with_function("stop", move |_, _: ()| match stop_tx.try_send(()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(LuaError::RuntimeError(
+                "Watcher has already been stopped".to_string(),
+            )),
+        })?
+        .build_readonly()
+}
@@ -0,0 +1,168 @@
+use std::io::Cursor;
+
+use image::{imageops, ImageFormat as ExternalImageFormat, RgbaImage};
+use mlua::prelude::*;
+use tokio::task;
+
+use crate::lune::util::TableBuilder;
+
+mod options;
+mod qrcode;
+
+use self::qrcode::{generate_qrcode, QrCodeOptions};
+use options::{EncodeOptions, ImageFormat, ResizeFilter};
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("decode", image_decode)?
+        .with_async_function("encode", image_encode)?
+        .with_async_function("resize", image_resize)?
+        .with_function("crop", image_crop)?
+        .with_function("qrcode", image_qrcode)?
+        .build_readonly()
+}
+
+/**
+    An image with raw RGBA pixels, 4 bytes per pixel, row by row.
+
+    This is given to and returned from lua as a plain table with
+    `width`, `height` and `pixels` fields, so that pixels can also
+    come from other sources such as `roblox.bakeGradient`.
+*/
+#[derive(Debug, Clone)]
+pub struct ImageData(pub RgbaImage);
+
+impl<'lua> FromLua<'lua> for ImageData {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let t = match value {
+            LuaValue::Table(t) => t,
+            value => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "ImageData",
+                    message: Some(format!(
+                        "Invalid image - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        let width = t.raw_get::<_, u32>("width")?;
+        let height = t.raw_get::<_, u32>("height")?;
+        let pixels = t.raw_get::<_, LuaString>("pixels")?.as_bytes().to_vec();
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid image - expected {expected} bytes of pixels for {width}x{height}, got {}",
+                pixels.len()
+            )));
+        }
+        let image = RgbaImage::from_raw(width, height, pixels)
+            .expect("Pixel buffer length was checked above");
+        Ok(Self(image))
+    }
+}
+
+impl<'lua> IntoLua<'lua> for ImageData {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        TableBuilder::new(lua)?
+            .with_value("width", self.0.width())?
+            .with_value("height", self.0.height())?
+            .with_value("pixels", lua.create_string(self.0.as_raw())?)?
+            .build()
+            .map(LuaValue::Table)
+    }
+}
+
+async fn image_decode<'lua>(_: &'lua Lua, bytes: LuaString<'lua>) -> LuaResult<ImageData> {
+    let bytes = bytes.as_bytes().to_vec();
+    task::spawn_blocking(move || {
+        let format = image::guess_format(&bytes).map_err(|_| {
+            LuaError::RuntimeError(
+                "Failed to decode image - unknown format, expected png, jpeg or webp".to_string(),
+            )
+        })?;
+        if ImageFormat::from_external(format).is_none() {
+            return Err(LuaError::RuntimeError(format!(
+                "Failed to decode image - unsupported format {format:?}, expected png, jpeg or webp"
+            )));
+        }
+        let image = image::load_from_memory_with_format(&bytes, format)
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to decode image - {e}")))?;
+        Ok(ImageData(image.into_rgba8()))
+    })
+    .await
+    .into_lua_err()?
+}
+
+async fn image_encode<'lua>(
+    lua: &'lua Lua,
+    (format, image, options): (ImageFormat, ImageData, EncodeOptions),
+) -> LuaResult<LuaString<'lua>> {
+    let bytes = task::spawn_blocking(move || {
+        let mut bytes = Cursor::new(Vec::new());
+        let result = match format {
+            ImageFormat::Jpeg => {
+                // NOTE: Jpeg has no alpha channel, so it is dropped
+                let rgb = image::DynamicImage::ImageRgba8(image.0).into_rgb8();
+                let mut encoder =
+                    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, options.quality);
+                encoder.encode_image(&rgb)
+            }
+            _ => image
+                .0
+                .write_to(&mut bytes, ExternalImageFormat::from(format)),
+        };
+        result.map_err(|e| LuaError::RuntimeError(format!("Failed to encode image - {e}")))?;
+        Ok::<_, LuaError>(bytes.into_inner())
+    })
+    .await
+    .into_lua_err()??;
+    lua.create_string(bytes)
+}
+
+async fn image_resize(
+    _: &Lua,
+    (image, width, height, filter): (ImageData, u32, u32, ResizeFilter),
+) -> LuaResult<ImageData> {
+    if width == 0 || height == 0 {
+        return Err(LuaError::RuntimeError(
+            "Failed to resize image - width and height must be positive integers".to_string(),
+        ));
+    }
+    task::spawn_blocking(move || {
+        ImageData(imageops::resize(&image.0, width, height, filter.into()))
+    })
+    .await
+    .into_lua_err()
+}
+
+fn image_crop(
+    _: &Lua,
+    (image, x, y, width, height): (ImageData, u32, u32, u32, u32),
+) -> LuaResult<ImageData> {
+    let fits =
+        |offset: u32, size: u32, max: u32| offset.checked_add(size).is_some_and(|end| end <= max);
+    if width == 0
+        || height == 0
+        || !fits(x, width, image.0.width())
+        || !fits(y, height, image.0.height())
+    {
+        return Err(LuaError::RuntimeError(format!(
+            "Failed to crop image - the area {width}x{height} at ({x}, {y}) is not within the {}x{} image",
+            image.0.width(),
+            image.0.height()
+        )));
+    }
+    Ok(ImageData(
+        imageops::crop_imm(&image.0, x, y, width, height).to_image(),
+    ))
+}
+
+fn image_qrcode<'lua>(
+    lua: &'lua Lua,
+    (contents, options): (LuaString<'lua>, QrCodeOptions),
+) -> LuaResult<LuaString<'lua>> {
+    let bytes = generate_qrcode(contents.as_bytes(), options)?;
+    lua.create_string(bytes)
+}
@@ -0,0 +1,142 @@
+use image::{imageops::FilterType, ImageFormat as ExternalImageFormat};
+use mlua::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    WebP,
+}
+
+impl ImageFormat {
+    pub fn from_external(format: ExternalImageFormat) -> Option<Self> {
+        match format {
+            ExternalImageFormat::Png => Some(Self::Png),
+            ExternalImageFormat::Jpeg => Some(Self::Jpeg),
+            ExternalImageFormat::WebP => Some(Self::WebP),
+            _ => None,
+        }
+    }
+}
+
+impl From<ImageFormat> for ExternalImageFormat {
+    fn from(value: ImageFormat) -> Self {
+        match value {
+            ImageFormat::Png => Self::Png,
+            ImageFormat::Jpeg => Self::Jpeg,
+            ImageFormat::WebP => Self::WebP,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for ImageFormat {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "png" => Ok(Self::Png),
+                "jpeg" | "jpg" => Ok(Self::Jpeg),
+                "webp" => Ok(Self::WebP),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "ImageFormat",
+                    message: Some(format!(
+                        "Invalid format '{kind}', valid formats are:  png, jpeg, webp"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ImageFormat",
+                message: None,
+            })
+        }
+    }
+}
+
+/**
+    Options for `image.encode`.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct EncodeOptions {
+    pub quality: u8,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self { quality: 90 }
+    }
+}
+
+impl<'lua> FromLua<'lua> for EncodeOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Table(t) => match t.raw_get::<_, Option<u8>>("quality") {
+                Ok(None) => Ok(Self::default()),
+                Ok(Some(quality)) if (1..=100).contains(&quality) => Ok(Self { quality }),
+                _ => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'quality' in encode options - expected an integer between 1 and 100"
+                        .to_string(),
+                )),
+            },
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "EncodeOptions",
+                message: Some(format!(
+                    "Invalid encode options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    The filter used when resizing images, defaulting to a triangle (linear) filter.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResizeFilter {
+    Nearest,
+    #[default]
+    Linear,
+    Cubic,
+    Lanczos,
+}
+
+impl From<ResizeFilter> for FilterType {
+    fn from(value: ResizeFilter) -> Self {
+        match value {
+            ResizeFilter::Nearest => Self::Nearest,
+            ResizeFilter::Linear => Self::Triangle,
+            ResizeFilter::Cubic => Self::CatmullRom,
+            ResizeFilter::Lanczos => Self::Lanczos3,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for ResizeFilter {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::String(s) => match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "nearest" => Ok(Self::Nearest),
+                "linear" => Ok(Self::Linear),
+                "cubic" => Ok(Self::Cubic),
+                "lanczos" => Ok(Self::Lanczos),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "ResizeFilter",
+                    message: Some(format!(
+                        "Invalid filter '{kind}', valid filters are:  nearest, linear, cubic, lanczos"
+                    )),
+                }),
+            },
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ResizeFilter",
+                message: None,
+            }),
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::io::Cursor;
+
+use image::{ImageFormat as ExternalImageFormat, Rgba, RgbaImage};
+use mlua::prelude::*;
+use qrcode::{Color, EcLevel, QrCode};
+
+// NOTE: Most scanners need a margin of light modules around the code to find it
+const QUIET_ZONE: usize = 4;
+
+/**
+    What `image.qrcode` renders a code as.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QrCodeFormat {
+    #[default]
+    Png,
+    Text,
+}
+
+/**
+    Options for `image.qrcode`.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct QrCodeOptions {
+    pub size: u32,
+    pub ec_level: EcLevel,
+    pub format: QrCodeFormat,
+}
+
+impl Default for QrCodeOptions {
+    fn default() -> Self {
+        Self {
+            size: 8,
+            ec_level: EcLevel::M,
+            format: QrCodeFormat::Png,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for QrCodeOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Table(t) => {
+                let size = match t.raw_get::<_, Option<u32>>("size") {
+                    Ok(None) => 8,
+                    Ok(Some(size)) if (1..=64).contains(&size) => size,
+                    _ => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'size' in qrcode options - expected an integer between 1 and 64"
+                                .to_string(),
+                        ))
+                    }
+                };
+                let ec_level = match t.raw_get::<_, Option<String>>("ecLevel") {
+                    Ok(None) => EcLevel::M,
+                    Ok(Some(level)) => match level.to_ascii_uppercase().trim() {
+                        "L" => EcLevel::L,
+                        "M" => EcLevel::M,
+                        "Q" => EcLevel::Q,
+                        "H" => EcLevel::H,
+                        _ => {
+                            return Err(LuaError::RuntimeError(format!(
+                                "Invalid option value for 'ecLevel' in qrcode options - expected one of L, M, Q, H, got '{level}'"
+                            )))
+                        }
+                    },
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'ecLevel' in qrcode options - expected string"
+                                .to_string(),
+                        ))
+                    }
+                };
+                let format = match t.raw_get::<_, Option<String>>("format") {
+                    Ok(None) => QrCodeFormat::Png,
+                    Ok(Some(format)) => match format.to_ascii_lowercase().trim() {
+                        "png" => QrCodeFormat::Png,
+                        "text" => QrCodeFormat::Text,
+                        _ => {
+                            return Err(LuaError::RuntimeError(format!(
+                                "Invalid option value for 'format' in qrcode options - expected png or text, got '{format}'"
+                            )))
+                        }
+                    },
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'format' in qrcode options - expected string"
+                                .to_string(),
+                        ))
+                    }
+                };
+                Ok(Self {
+                    size,
+                    ec_level,
+                    format,
+                })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "QrCodeOptions",
+                message: Some(format!(
+                    "Invalid qrcode options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    A generated code, with a quiet zone added around it.
+*/
+struct Modules {
+    width: usize,
+    colors: Vec<Color>,
+}
+
+impl Modules {
+    fn new(contents: &[u8], ec_level: EcLevel) -> LuaResult<Self> {
+        let code = QrCode::with_error_correction_level(contents, ec_level)
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to create qrcode - {e}")))?;
+        Ok(Self {
+            width: code.width(),
+            colors: code.to_colors(),
+        })
+    }
+
+    fn full_width(&self) -> usize {
+        self.width + QUIET_ZONE * 2
+    }
+
+    /**
+        Checks if the module at the given position is dark,
+        where positions are relative to the top left of the quiet zone.
+    */
+    fn is_dark(&self, x: usize, y: usize) -> bool {
+        match (x.checked_sub(QUIET_ZONE), y.checked_sub(QUIET_ZONE)) {
+            (Some(x), Some(y)) if x < self.width && y < self.width => {
+                self.colors[y * self.width + x] == Color::Dark
+            }
+            _ => false,
+        }
+    }
+}
+
+fn render_png(modules: &Modules, size: u32) -> LuaResult<Vec<u8>> {
+    let pixels = modules.full_width() as u32 * size;
+    let image = RgbaImage::from_fn(pixels, pixels, |x, y| {
+        if modules.is_dark((x / size) as usize, (y / size) as usize) {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    });
+    let mut bytes = Cursor::new(Vec::new());
+    image
+        .write_to(&mut bytes, ExternalImageFormat::Png)
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to encode qrcode - {e}")))?;
+    Ok(bytes.into_inner())
+}
+
+/**
+    Renders a code using half blocks, with two rows of modules per line.
+
+    Light modules are drawn as blocks, so that codes scan correctly in
+    terminals with a dark background, which is the most common case.
+*/
+fn render_text(modules: &Modules) -> String {
+    let width = modules.full_width();
+    let mut text = String::with_capacity((width + 1) * width.div_ceil(2) * 3);
+    for y in (0..width).step_by(2) {
+        for x in 0..width {
+            let top = !modules.is_dark(x, y);
+            let bottom = y + 1 < width && !modules.is_dark(x, y + 1);
+            text.push(match (top, bottom) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/**
+    Generates a qr code for the given contents, rendered as
+    either the contents of a png file or as printable text.
+*/
+pub fn generate_qrcode(contents: &[u8], options: QrCodeOptions) -> LuaResult<Vec<u8>> {
+    let modules = Modules::new(contents, options.ec_level)?;
+    match options.format {
+        QrCodeFormat::Png => render_png(&modules, options.size),
+        QrCodeFormat::Text => Ok(render_text(&modules).into_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_zone_is_light() {
+        let modules = Modules::new(b"lune", EcLevel::M).unwrap();
+        let width = modules.full_width();
+        for i in 0..width {
+            assert!(!modules.is_dark(i, 0));
+            assert!(!modules.is_dark(0, i));
+            assert!(!modules.is_dark(i, width - 1));
+            assert!(!modules.is_dark(width - 1, i));
+        }
+        // The top left finder pattern starts right after the quiet zone
+        assert!(modules.is_dark(QUIET_ZONE, QUIET_ZONE));
+    }
+
+    #[test]
+    fn text_has_one_line_per_two_rows() {
+        let modules = Modules::new(b"lune", EcLevel::M).unwrap();
+        let text = render_text(&modules);
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), (modules.full_width() + 1) / 2);
+        assert!(lines
+            .iter()
+            .all(|line| line.chars().count() == modules.full_width()));
+    }
+}
@@ -0,0 +1,173 @@
+use std::{fmt, str::FromStr};
+
+use mlua::prelude::*;
+
+/**
+    The level of a log message, from least to most severe.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" | "warning" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(format!(
+                "Invalid log level '{s}' - expected one of 'debug', 'info', 'warn' or 'error'"
+            )),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for LogLevel {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::String(s) => s.to_str()?.parse().map_err(LuaError::RuntimeError),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "LogLevel",
+                message: Some("Expected 'debug', 'info', 'warn' or 'error'".to_string()),
+            }),
+        }
+    }
+}
+
+/**
+    Which messages are logged, for each module.
+
+    Filters are written the same way as for `RUST_LOG`, as a comma separated
+    list of directives, where each directive is either a level for all modules,
+    or a module name and a level, such as `warn,net=debug,net.server=off`.
+
+    The most specific directive for a module is used, where a directive for
+    `net` also applies to `net.server`, unless there is one for `net.server`.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    default: Option<LogLevel>,
+    modules: Vec<(String, Option<LogLevel>)>,
+}
+
+impl LogFilter {
+    pub fn enabled(&self, level: LogLevel, module: &str) -> bool {
+        let mut best: Option<(&str, Option<LogLevel>)> = None;
+        for (name, min) in &self.modules {
+            let matches = module == name
+                || (module.starts_with(name.as_str())
+                    && module[name.len()..].starts_with(['.', '/', ':']));
+            if matches && best.is_none_or(|(b, _)| name.len() > b.len()) {
+                best = Some((name, *min));
+            }
+        }
+        let min = match best {
+            Some((_, min)) => min,
+            None => self.default,
+        };
+        match min {
+            Some(min) => level >= min,
+            None => false,
+        }
+    }
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            default: Some(LogLevel::Info),
+            modules: Vec::new(),
+        }
+    }
+}
+
+fn parse_min_level(s: &str) -> Result<Option<LogLevel>, String> {
+    if s.trim().eq_ignore_ascii_case("off") {
+        Ok(None)
+    } else {
+        s.parse().map(Some)
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = Self::default();
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    let module = module.trim().to_string();
+                    let level = parse_min_level(level)?;
+                    filter.modules.push((module, level));
+                }
+                None => filter.default = parse_min_level(directive)?,
+            }
+        }
+        Ok(filter)
+    }
+}
+
+impl<'lua> FromLua<'lua> for LogFilter {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::String(s) => s.to_str()?.parse().map_err(LuaError::RuntimeError),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "LogFilter",
+                message: Some("Expected a filter string, such as 'warn,net=debug'".to_string()),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_by_most_specific_module() {
+        let filter: LogFilter = "warn,net=debug,net.server=off".parse().unwrap();
+        assert!(filter.enabled(LogLevel::Warn, "main"));
+        assert!(!filter.enabled(LogLevel::Info, "main"));
+        assert!(filter.enabled(LogLevel::Debug, "net"));
+        assert!(filter.enabled(LogLevel::Debug, "net.client"));
+        assert!(!filter.enabled(LogLevel::Error, "net.server"));
+        assert!(!filter.enabled(LogLevel::Error, "net.server.http"));
+        assert!(!filter.enabled(LogLevel::Info, "network"));
+    }
+
+    #[test]
+    fn defaults_to_info() {
+        let filter: LogFilter = "db=error".parse().unwrap();
+        assert!(filter.enabled(LogLevel::Info, "main"));
+        assert!(!filter.enabled(LogLevel::Debug, "main"));
+        assert!(!filter.enabled(LogLevel::Warn, "db"));
+        assert_eq!(LogFilter::default(), "".parse().unwrap());
+        assert!("verbose".parse::<LogFilter>().is_err());
+        assert!("net=loud".parse::<LogFilter>().is_err());
+    }
+}
@@ -0,0 +1,193 @@
+use std::{cell::RefCell, fs::OpenOptions, rc::Rc, time::SystemTime};
+
+use mlua::prelude::*;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use tracing::warn;
+
+use crate::lune::util::TableBuilder;
+
+mod filter;
+mod sink;
+
+use filter::{LogFilter, LogLevel};
+use sink::{LogFormat, LogRecord, LogSink, LogTarget};
+
+/**
+    The module that messages logged using the top-level functions belong to.
+*/
+const DEFAULT_MODULE: &str = "main";
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("debug", log_fn(LogLevel::Debug, DEFAULT_MODULE))?
+        .with_function("info", log_fn(LogLevel::Info, DEFAULT_MODULE))?
+        .with_function("warn", log_fn(LogLevel::Warn, DEFAULT_MODULE))?
+        .with_function("error", log_fn(LogLevel::Error, DEFAULT_MODULE))?
+        .with_function("module", log_module)?
+        .with_function("enabled", log_enabled)?
+        .with_function("configure", log_configure)?
+        .with_function("addFileSink", log_add_file_sink)?
+        .build_readonly()
+}
+
+/**
+    The logger for a single Lune runtime, which holds
+    the current filter and all of the sinks to write to.
+*/
+#[derive(Debug)]
+struct Logger {
+    filter: RefCell<LogFilter>,
+    console: RefCell<LogSink>,
+    files: RefCell<Vec<LogSink>>,
+}
+
+impl Logger {
+    /**
+        Gets the logger for the given Lua struct, creating it if it does not yet
+        exist, using the `LUNE_LOG` and `LUNE_LOG_FORMAT` environment variables.
+    */
+    fn get(lua: &Lua) -> Rc<Self> {
+        if let Some(logger) = lua.app_data_ref::<Rc<Logger>>() {
+            return Rc::clone(&logger);
+        }
+
+        let filter = match std::env::var("LUNE_LOG") {
+            Ok(value) => value.parse().unwrap_or_else(|e| {
+                warn!("Ignoring LUNE_LOG - {e}");
+                LogFilter::default()
+            }),
+            Err(_) => LogFilter::default(),
+        };
+        let logger = Rc::new(Logger {
+            filter: RefCell::new(filter),
+            console: RefCell::new(LogSink {
+                target: LogTarget::Stderr,
+                format: LogFormat::from_env().unwrap_or_default(),
+                level: LogLevel::Debug,
+                timestamps: true,
+            }),
+            files: RefCell::new(Vec::new()),
+        });
+        lua.set_app_data(Rc::clone(&logger));
+        logger
+    }
+
+    fn enabled(&self, level: LogLevel, module: &str) -> bool {
+        self.filter.borrow().enabled(level, module)
+    }
+
+    fn log(&self, record: &LogRecord) -> LuaResult<()> {
+        self.console
+            .borrow_mut()
+            .write(record)
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to write log message\n> {e}")))?;
+        for sink in self.files.borrow_mut().iter_mut() {
+            sink.write(record).map_err(|e| {
+                LuaError::RuntimeError(format!("Failed to write log message to file\n> {e}"))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+fn log_fn(
+    level: LogLevel,
+    module: impl Into<String>,
+) -> impl Fn(&Lua, (LuaString, Option<LuaTable>)) -> LuaResult<()> + 'static {
+    let module = module.into();
+    move |lua, (message, fields)| log_message(lua, level, &module, message, fields)
+}
+
+fn log_message(
+    lua: &Lua,
+    level: LogLevel,
+    module: &str,
+    message: LuaString,
+    fields: Option<LuaTable>,
+) -> LuaResult<()> {
+    let logger = Logger::get(lua);
+    if !logger.enabled(level, module) {
+        return Ok(());
+    }
+
+    let mut pairs = Vec::new();
+    if let Some(fields) = fields {
+        for pair in fields.pairs::<String, LuaValue>() {
+            let (key, value) = pair?;
+            let value = match lua.from_value::<JsonValue>(value.clone()) {
+                Ok(value) => value,
+                // NOTE: Values that can not be serialized, such as
+                // functions, are still logged, but only using their type
+                Err(_) => JsonValue::String(format!("<{}>", value.type_name())),
+            };
+            pairs.push((key, value));
+        }
+        // NOTE: Lua tables have no order, so we sort fields to keep output stable
+        pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+
+    logger.log(&LogRecord {
+        time: SystemTime::now(),
+        level,
+        module: module.to_string(),
+        message: message.to_string_lossy().to_string(),
+        fields: pairs.into_iter().collect::<JsonMap<_, _>>(),
+    })
+}
+
+fn log_module(lua: &Lua, name: String) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_value("name", name.as_str())?
+        .with_function("debug", log_fn(LogLevel::Debug, &name))?
+        .with_function("info", log_fn(LogLevel::Info, &name))?
+        .with_function("warn", log_fn(LogLevel::Warn, &name))?
+        .with_function("error", log_fn(LogLevel::Error, &name))?
+        .build_readonly()
+}
+
+fn log_enabled(lua: &Lua, (level, module): (LogLevel, Option<String>)) -> LuaResult<bool> {
+    let module = module.as_deref().unwrap_or(DEFAULT_MODULE);
+    Ok(Logger::get(lua).enabled(level, module))
+}
+
+fn log_configure(lua: &Lua, config: LuaTable) -> LuaResult<()> {
+    let filter = config.raw_get::<_, Option<LogFilter>>("filter")?;
+    let format = config.raw_get::<_, Option<LogFormat>>("format")?;
+    let timestamps = config.raw_get::<_, Option<bool>>("timestamps")?;
+
+    let logger = Logger::get(lua);
+    if let Some(filter) = filter {
+        logger.filter.replace(filter);
+    }
+    let mut console = logger.console.borrow_mut();
+    if let Some(format) = format {
+        console.format = format;
+    }
+    if let Some(timestamps) = timestamps {
+        console.timestamps = timestamps;
+    }
+    Ok(())
+}
+
+fn log_add_file_sink(lua: &Lua, (path, options): (String, Option<LuaTable>)) -> LuaResult<()> {
+    let (format, level, timestamps) = match &options {
+        Some(options) => (
+            options.raw_get::<_, Option<LogFormat>>("format")?,
+            options.raw_get::<_, Option<LogLevel>>("level")?,
+            options.raw_get::<_, Option<bool>>("timestamps")?,
+        ),
+        None => (None, None, None),
+    };
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to open log file '{path}'\n> {e}")))?;
+    Logger::get(lua).files.borrow_mut().push(LogSink {
+        target: LogTarget::File(file),
+        format: format.unwrap_or_default(),
+        level: level.unwrap_or(LogLevel::Debug),
+        timestamps: timestamps.unwrap_or(true),
+    });
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    time::SystemTime,
+};
+
+use console::Style;
+use mlua::prelude::*;
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+
+use super::filter::LogLevel;
+
+/**
+    The format that log messages are written in.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn from_env() -> Option<Self> {
+        match std::env::var("LUNE_LOG_FORMAT").ok()?.trim() {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for LogFormat {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::String(s) => match s.to_str()? {
+                "text" => Ok(Self::Text),
+                "json" => Ok(Self::Json),
+                other => Err(LuaError::RuntimeError(format!(
+                    "Invalid log format '{other}' - expected 'text' or 'json'"
+                ))),
+            },
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "LogFormat",
+                message: Some("Expected 'text' or 'json'".to_string()),
+            }),
+        }
+    }
+}
+
+/**
+    A single log message, along with any structured fields.
+*/
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub time: SystemTime,
+    pub level: LogLevel,
+    pub module: String,
+    pub message: String,
+    pub fields: JsonMap<String, JsonValue>,
+}
+
+impl LogRecord {
+    fn to_json(&self) -> String {
+        let mut value = json!({
+            "timestamp": humantime::format_rfc3339_millis(self.time).to_string(),
+            "level": self.level.as_str(),
+            "module": self.module,
+            "message": self.message,
+        });
+        if !self.fields.is_empty() {
+            value["fields"] = JsonValue::Object(self.fields.clone());
+        }
+        value.to_string()
+    }
+
+    fn to_text(&self, timestamps: bool, colored: bool) -> String {
+        let level_style = match self.level {
+            LogLevel::Debug => Style::new().blue(),
+            LogLevel::Info => Style::new().green(),
+            LogLevel::Warn => Style::new().yellow(),
+            LogLevel::Error => Style::new().red(),
+        };
+        let dim = Style::new().dim();
+        let (level_style, dim) = if colored {
+            (level_style.for_stderr(), dim.for_stderr())
+        } else {
+            (level_style.force_styling(false), dim.force_styling(false))
+        };
+
+        let mut line = String::new();
+        if timestamps {
+            let time = humantime::format_rfc3339_millis(self.time).to_string();
+            line.push_str(&format!("{} ", dim.apply_to(time)));
+        }
+        let level = format!("{:<5}", self.level.as_str().to_ascii_uppercase());
+        line.push_str(&format!("{} ", level_style.apply_to(level)));
+        line.push_str(&format!("{} ", dim.apply_to(format!("{}:", self.module))));
+        line.push_str(&self.message);
+        for (key, value) in &self.fields {
+            let value = match value {
+                JsonValue::String(s) if !s.is_empty() && !s.contains(char::is_whitespace) => {
+                    s.clone()
+                }
+                value => value.to_string(),
+            };
+            line.push_str(&format!(" {}{value}", dim.apply_to(format!("{key}="))));
+        }
+        line
+    }
+}
+
+#[derive(Debug)]
+pub enum LogTarget {
+    Stderr,
+    File(File),
+}
+
+/**
+    A destination that log messages are written to.
+*/
+#[derive(Debug)]
+pub struct LogSink {
+    pub target: LogTarget,
+    pub format: LogFormat,
+    pub level: LogLevel,
+    pub timestamps: bool,
+}
+
+impl LogSink {
+    pub fn write(&mut self, record: &LogRecord) -> io::Result<()> {
+        if record.level < self.level {
+            return Ok(());
+        }
+        let colored = matches!(self.target, LogTarget::Stderr);
+        let mut line = match self.format {
+            LogFormat::Json => record.to_json(),
+            LogFormat::Text => record.to_text(self.timestamps, colored),
+        };
+        line.push('\n');
+        match &mut self.target {
+            LogTarget::Stderr => io::stderr().write_all(line.as_bytes()),
+            LogTarget::File(file) => file.write_all(line.as_bytes()),
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use mlua::prelude::*;
+
+use super::options::MetricOptions;
+
+/**
+    The kind of a metric, which decides how it may be updated and how it is rendered.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl MetricKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Counter => "counter",
+            Self::Gauge => "gauge",
+            Self::Histogram => "histogram",
+        }
+    }
+}
+
+/**
+    A floating point number that can be updated atomically,
+    stored as its bits inside of an [`AtomicU64`].
+*/
+#[derive(Debug, Default)]
+struct AtomicF64(AtomicU64);
+
+impl AtomicF64 {
+    fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    fn add(&self, amount: f64) {
+        self.0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + amount).to_bits())
+            })
+            .ok();
+    }
+}
+
+/**
+    The values for a single set of label values of a metric.
+
+    Counters and gauges only use `value`, while histograms use `value`
+    for the sum of all observations, together with `buckets` and `count`.
+*/
+#[derive(Debug, Default)]
+struct MetricSeries {
+    value: AtomicF64,
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+}
+
+impl MetricSeries {
+    fn new(bucket_count: usize) -> Self {
+        Self {
+            buckets: (0..bucket_count).map(|_| AtomicU64::new(0)).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/**
+    A metric, containing a series of values for every set of label values it has been used with.
+*/
+#[derive(Debug)]
+pub struct Metric {
+    name: String,
+    kind: MetricKind,
+    help: String,
+    labels: Vec<String>,
+    buckets: Vec<f64>,
+    series: RwLock<BTreeMap<Vec<String>, Arc<MetricSeries>>>,
+}
+
+impl Metric {
+    pub fn new(name: String, kind: MetricKind, options: MetricOptions) -> Self {
+        let buckets = match kind {
+            MetricKind::Histogram => options.buckets(),
+            _ => Vec::new(),
+        };
+        // NOTE: Metrics without labels always have a single series, which we
+        // create right away so that it gets rendered even before it is used
+        let mut series = BTreeMap::new();
+        if options.labels.is_empty() {
+            series.insert(Vec::new(), Arc::new(MetricSeries::new(buckets.len())));
+        }
+        Self {
+            name,
+            kind,
+            help: options.help,
+            labels: options.labels,
+            buckets,
+            series: RwLock::new(series),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> MetricKind {
+        self.kind
+    }
+
+    /**
+        Checks if this metric was created using the same kind and options
+        as the given ones, meaning it may be shared instead of erroring.
+    */
+    pub fn is_compatible_with(&self, kind: MetricKind, options: &MetricOptions) -> bool {
+        self.kind == kind
+            && self.labels == options.labels
+            && (kind != MetricKind::Histogram || self.buckets == options.buckets())
+    }
+
+    /**
+        Gets the label values from the given lua table, in the same order as the label names.
+    */
+    fn label_values(&self, labels: Option<LuaTable>) -> LuaResult<Vec<String>> {
+        let table = match labels {
+            Some(table) => table,
+            None if self.labels.is_empty() => return Ok(Vec::new()),
+            None => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Metric '{}' requires values for the labels: {}",
+                    self.name,
+                    self.labels.join(", ")
+                )))
+            }
+        };
+        let mut values = Vec::with_capacity(self.labels.len());
+        for label in &self.labels {
+            let value = match table.raw_get::<_, LuaValue>(label.as_str())? {
+                LuaValue::String(s) => s.to_str()?.to_string(),
+                LuaValue::Integer(i) => i.to_string(),
+                LuaValue::Number(n) => n.to_string(),
+                LuaValue::Boolean(b) => b.to_string(),
+                LuaValue::Nil => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Missing value for label '{label}' of metric '{}'",
+                        self.name
+                    )))
+                }
+                value => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Invalid value for label '{label}' of metric '{}' - got {}",
+                        self.name,
+                        value.type_name()
+                    )))
+                }
+            };
+            values.push(value);
+        }
+        if table.clone().pairs::<LuaValue, LuaValue>().count() != self.labels.len() {
+            return Err(LuaError::RuntimeError(format!(
+                "Metric '{}' only has the labels: {}",
+                self.name,
+                self.labels.join(", ")
+            )));
+        }
+        Ok(values)
+    }
+
+    fn series(&self, labels: Option<LuaTable>) -> LuaResult<Arc<MetricSeries>> {
+        let values = self.label_values(labels)?;
+        if let Some(series) = self
+            .series
+            .read()
+            .expect("Failed to lock metric series")
+            .get(&values)
+        {
+            return Ok(Arc::clone(series));
+        }
+        let mut all_series = self.series.write().expect("Failed to lock metric series");
+        let series = all_series
+            .entry(values)
+            .or_insert_with(|| Arc::new(MetricSeries::new(self.buckets.len())));
+        Ok(Arc::clone(series))
+    }
+
+    pub fn get(&self, labels: Option<LuaTable>) -> LuaResult<f64> {
+        Ok(self.series(labels)?.value.get())
+    }
+
+    pub fn set(&self, labels: Option<LuaTable>, value: f64) -> LuaResult<()> {
+        self.series(labels)?.value.set(value);
+        Ok(())
+    }
+
+    pub fn add(&self, labels: Option<LuaTable>, amount: f64) -> LuaResult<()> {
+        if self.kind == MetricKind::Counter && (amount < 0.0 || amount.is_nan()) {
+            return Err(LuaError::RuntimeError(format!(
+                "Counters may only be increased, got {amount}"
+            )));
+        }
+        self.series(labels)?.value.add(amount);
+        Ok(())
+    }
+
+    pub fn observe(&self, labels: Option<LuaTable>, value: f64) -> LuaResult<()> {
+        let series = self.series(labels)?;
+        if let Some(index) = self.buckets.iter().position(|bucket| value <= *bucket) {
+            series.buckets[index].fetch_add(1, Ordering::Relaxed);
+        }
+        series.count.fetch_add(1, Ordering::Relaxed);
+        series.value.add(value);
+        Ok(())
+    }
+
+    /**
+        Renders this metric in the Prometheus text exposition format.
+    */
+    pub fn render(&self, out: &mut String) {
+        if !self.help.is_empty() {
+            let help = self.help.replace('\\', "\\\\").replace('\n', "\\n");
+            writeln!(out, "# HELP {} {help}", self.name).unwrap();
+        }
+        writeln!(out, "# TYPE {} {}", self.name, self.kind.name()).unwrap();
+
+        let all_series = self.series.read().expect("Failed to lock metric series");
+        for (values, series) in all_series.iter() {
+            let labels = self
+                .labels
+                .iter()
+                .zip(values)
+                .map(|(label, value)| format!("{label}=\"{}\"", escape_label_value(value)))
+                .collect::<Vec<_>>();
+            if self.kind != MetricKind::Histogram {
+                let value = format_value(series.value.get());
+                writeln!(out, "{}{} {value}", self.name, format_labels(&labels)).unwrap();
+                continue;
+            }
+
+            // NOTE: Buckets are stored separately, but rendered cumulatively
+            let mut cumulative = 0;
+            for (bucket, count) in self.buckets.iter().zip(&series.buckets) {
+                cumulative += count.load(Ordering::Relaxed);
+                write_bucket(out, &self.name, &labels, &format_value(*bucket), cumulative);
+            }
+            let count = series.count.load(Ordering::Relaxed);
+            write_bucket(out, &self.name, &labels, "+Inf", count);
+            let sum = format_value(series.value.get());
+            writeln!(out, "{}_sum{} {sum}", self.name, format_labels(&labels)).unwrap();
+            writeln!(out, "{}_count{} {count}", self.name, format_labels(&labels)).unwrap();
+        }
+    }
+}
+
+fn write_bucket(out: &mut String, name: &str, labels: &[String], le: &str, count: u64) {
+    let mut labels = labels.to_vec();
+    labels.push(format!("le=\"{le}\""));
+    writeln!(out, "{name}_bucket{} {count}", format_labels(&labels)).unwrap();
+}
+
+fn format_labels(labels: &[String]) -> String {
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
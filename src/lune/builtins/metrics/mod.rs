@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use mlua::prelude::*;
+
+use crate::lune::util::TableBuilder;
+
+mod metric;
+mod options;
+mod registry;
+
+use metric::{Metric, MetricKind};
+use registry::REGISTRY;
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("counter", |_, (name, options)| {
+            REGISTRY
+                .register(name, MetricKind::Counter, options)
+                .map(MetricCounter)
+        })?
+        .with_function("gauge", |_, (name, options)| {
+            REGISTRY
+                .register(name, MetricKind::Gauge, options)
+                .map(MetricGauge)
+        })?
+        .with_function("histogram", |_, (name, options)| {
+            REGISTRY
+                .register(name, MetricKind::Histogram, options)
+                .map(MetricHistogram)
+        })?
+        .with_function("render", |_, ()| Ok(REGISTRY.render()))?
+        .build_readonly()
+}
+
+type MetricArgs<'lua> = (Option<f64>, Option<LuaTable<'lua>>);
+
+/**
+    A counter, which may only be increased.
+*/
+#[derive(Debug, Clone)]
+struct MetricCounter(Arc<Metric>);
+
+impl LuaUserData for MetricCounter {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_, this| Ok(this.0.name().to_string()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("inc", |_, this, (amount, labels): MetricArgs| {
+            this.0.add(labels, amount.unwrap_or(1.0))
+        });
+        methods.add_method("get", |_, this, labels: Option<LuaTable>| {
+            this.0.get(labels)
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("Counter({})", this.0.name()))
+        });
+    }
+}
+
+/**
+    A gauge, which may be set to any value, and increased or decreased.
+*/
+#[derive(Debug, Clone)]
+struct MetricGauge(Arc<Metric>);
+
+impl LuaUserData for MetricGauge {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_, this| Ok(this.0.name().to_string()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method(
+            "set",
+            |_, this, (value, labels): (f64, Option<LuaTable>)| this.0.set(labels, value),
+        );
+        methods.add_method("inc", |_, this, (amount, labels): MetricArgs| {
+            this.0.add(labels, amount.unwrap_or(1.0))
+        });
+        methods.add_method("dec", |_, this, (amount, labels): MetricArgs| {
+            this.0.add(labels, -amount.unwrap_or(1.0))
+        });
+        methods.add_method("get", |_, this, labels: Option<LuaTable>| {
+            this.0.get(labels)
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("Gauge({})", this.0.name()))
+        });
+    }
+}
+
+/**
+    A histogram, which counts observed values in buckets.
+*/
+#[derive(Debug, Clone)]
+struct MetricHistogram(Arc<Metric>);
+
+impl LuaUserData for MetricHistogram {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("name", |_, this| Ok(this.0.name().to_string()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method(
+            "observe",
+            |_, this, (value, labels): (f64, Option<LuaTable>)| this.0.observe(labels, value),
+        );
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("Histogram({})", this.0.name()))
+        });
+    }
+}
@@ -0,0 +1,127 @@
+use mlua::prelude::*;
+
+/**
+    The default histogram buckets, same as in the official Prometheus client libraries.
+*/
+const DEFAULT_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/**
+    Options for creating a metric using `metrics.counter`, `metrics.gauge` or `metrics.histogram`.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct MetricOptions {
+    pub help: String,
+    pub labels: Vec<String>,
+    pub buckets: Option<Vec<f64>>,
+}
+
+impl MetricOptions {
+    /**
+        Gets the histogram buckets for these options, which are sorted
+        and do not contain the implicit `+Inf` bucket at the end.
+    */
+    pub fn buckets(&self) -> Vec<f64> {
+        match &self.buckets {
+            Some(buckets) => buckets.clone(),
+            None => DEFAULT_BUCKETS.to_vec(),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for MetricOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::String(s) => Ok(Self {
+                help: s.to_str()?.to_string(),
+                ..Default::default()
+            }),
+            LuaValue::Table(t) => {
+                let help: Option<String> = t.raw_get("help")?;
+                let labels: Option<Vec<String>> = t.raw_get("labels")?;
+                let buckets: Option<Vec<f64>> = t.raw_get("buckets")?;
+
+                let labels = labels.unwrap_or_default();
+                for (index, label) in labels.iter().enumerate() {
+                    if !is_valid_label_name(label) {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Invalid label name '{label}' - label names must contain only \
+                            letters, digits and underscores, and may not start with a digit or '__'"
+                        )));
+                    }
+                    if labels[..index].contains(label) {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Duplicate label name '{label}'"
+                        )));
+                    }
+                }
+
+                let buckets = match buckets {
+                    None => None,
+                    Some(mut buckets) => {
+                        // NOTE: The +Inf bucket is always added when rendering
+                        if buckets.last() == Some(&f64::INFINITY) {
+                            buckets.pop();
+                        }
+                        if buckets.is_empty() {
+                            return Err(LuaError::RuntimeError(
+                                "Histogram buckets must contain at least one bucket".to_string(),
+                            ));
+                        }
+                        if buckets.iter().any(|b| !b.is_finite())
+                            || buckets.windows(2).any(|w| w[0] >= w[1])
+                        {
+                            return Err(LuaError::RuntimeError(
+                                "Histogram buckets must be finite numbers in increasing order"
+                                    .to_string(),
+                            ));
+                        }
+                        Some(buckets)
+                    }
+                };
+
+                Ok(Self {
+                    help: help.unwrap_or_default(),
+                    labels,
+                    buckets,
+                })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "MetricOptions",
+                message: Some(format!(
+                    "Invalid metric options - expected string or table, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    Checks if the given metric name is valid in the Prometheus exposition format.
+*/
+pub fn is_valid_metric_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == ':' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+/**
+    Checks if the given label name is valid in the Prometheus exposition format.
+
+    Label names starting with `__` are reserved for internal use by Prometheus.
+*/
+pub fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    !name.starts_with("__") && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
@@ -0,0 +1,79 @@
+use std::sync::{Arc, Mutex};
+
+use mlua::prelude::*;
+use once_cell::sync::Lazy;
+
+use super::{
+    metric::{Metric, MetricKind},
+    options::{is_valid_metric_name, MetricOptions},
+};
+
+/**
+    The registry that all metrics are registered in.
+
+    This is shared by the entire process, same as the default registry in the
+    official Prometheus client libraries, so that metrics updated by workers
+    created using `task.parallel` are also included when rendering.
+*/
+pub static REGISTRY: Lazy<MetricRegistry> = Lazy::new(MetricRegistry::default);
+
+#[derive(Debug, Default)]
+pub struct MetricRegistry {
+    metrics: Mutex<Vec<Arc<Metric>>>,
+}
+
+impl MetricRegistry {
+    /**
+        Registers a new metric with the given name, kind and options.
+
+        If a metric with the same name was already registered using the same kind
+        and options, the existing metric is returned instead of erroring.
+    */
+    pub fn register(
+        &self,
+        name: String,
+        kind: MetricKind,
+        options: MetricOptions,
+    ) -> LuaResult<Arc<Metric>> {
+        if !is_valid_metric_name(&name) {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid metric name '{name}' - metric names must contain only \
+                letters, digits, underscores and colons, and may not start with a digit"
+            )));
+        }
+        if kind == MetricKind::Histogram && options.labels.iter().any(|label| label == "le") {
+            return Err(LuaError::RuntimeError(
+                "Histograms may not use the label name 'le'".to_string(),
+            ));
+        }
+
+        let mut metrics = self.metrics.lock().expect("Failed to lock metric registry");
+        if let Some(existing) = metrics.iter().find(|metric| metric.name() == name) {
+            return if existing.is_compatible_with(kind, &options) {
+                Ok(Arc::clone(existing))
+            } else {
+                Err(LuaError::RuntimeError(format!(
+                    "A {} named '{name}' has already been registered with different options",
+                    existing.kind().name()
+                )))
+            };
+        }
+
+        let metric = Arc::new(Metric::new(name, kind, options));
+        metrics.push(Arc::clone(&metric));
+        Ok(metric)
+    }
+
+    /**
+        Renders all registered metrics in the Prometheus text exposition
+        format, in the same order as they were registered in.
+    */
+    pub fn render(&self) -> String {
+        let metrics = self.metrics.lock().expect("Failed to lock metric registry");
+        let mut out = String::new();
+        for metric in metrics.iter() {
+            metric.render(&mut out);
+        }
+        out
+    }
+}
@@ -2,26 +2,57 @@ use std::str::FromStr;
 
 use mlua::prelude::*;
 
+mod cache;
+mod collections;
+mod diff;
 mod fs;
+mod log;
 mod luau;
+mod metrics;
 mod net;
 mod process;
 mod serde;
 mod stdio;
+mod r#struct;
 mod task;
+mod trace;
+mod unicode;
+
+#[cfg(feature = "bignum")]
+mod bignum;
+
+#[cfg(feature = "image")]
+mod image;
+
+#[cfg(feature = "sqlite")]
+mod queue;
 
 #[cfg(feature = "roblox")]
 mod roblox;
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum LuneBuiltin {
+    #[cfg(feature = "bignum")]
+    Bignum,
+    Cache,
+    Collections,
+    Diff,
     Fs,
+    #[cfg(feature = "image")]
+    Image,
+    Log,
     Luau,
+    Metrics,
     Net,
     Task,
     Process,
+    #[cfg(feature = "sqlite")]
+    Queue,
     Serde,
     Stdio,
+    Struct,
+    Trace,
+    Unicode,
     #[cfg(feature = "roblox")]
     Roblox,
 }
@@ -32,13 +63,27 @@ where
 {
     pub fn name(&self) -> &'static str {
         match self {
+            #[cfg(feature = "bignum")]
+            Self::Bignum => "bignum",
+            Self::Cache => "cache",
+            Self::Collections => "collections",
+            Self::Diff => "diff",
             Self::Fs => "fs",
+            #[cfg(feature = "image")]
+            Self::Image => "image",
+            Self::Log => "log",
             Self::Luau => "luau",
+            Self::Metrics => "metrics",
             Self::Net => "net",
             Self::Task => "task",
             Self::Process => "process",
+            #[cfg(feature = "sqlite")]
+            Self::Queue => "queue",
             Self::Serde => "serde",
             Self::Stdio => "stdio",
+            Self::Struct => "struct",
+            Self::Trace => "trace",
+            Self::Unicode => "unicode",
             #[cfg(feature = "roblox")]
             Self::Roblox => "roblox",
         }
@@ -46,13 +91,27 @@ where
 
     pub fn create(&self, lua: &'lua Lua) -> LuaResult<LuaMultiValue<'lua>> {
         let res = match self {
+            #[cfg(feature = "bignum")]
+            Self::Bignum => bignum::create(lua),
+            Self::Cache => cache::create(lua),
+            Self::Collections => collections::create(lua),
+            Self::Diff => diff::create(lua),
             Self::Fs => fs::create(lua),
+            #[cfg(feature = "image")]
+            Self::Image => image::create(lua),
+            Self::Log => log::create(lua),
             Self::Luau => luau::create(lua),
+            Self::Metrics => metrics::create(lua),
             Self::Net => net::create(lua),
             Self::Task => task::create(lua),
             Self::Process => process::create(lua),
+            #[cfg(feature = "sqlite")]
+            Self::Queue => queue::create(lua),
             Self::Serde => serde::create(lua),
             Self::Stdio => stdio::create(lua),
+            Self::Struct => r#struct::create(lua),
+            Self::Trace => trace::create(lua),
+            Self::Unicode => unicode::create(lua),
             #[cfg(feature = "roblox")]
             Self::Roblox => roblox::create(lua),
         };
@@ -70,13 +129,27 @@ impl FromStr for LuneBuiltin {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.trim().to_ascii_lowercase().as_str() {
+            #[cfg(feature = "bignum")]
+            "bignum" => Ok(Self::Bignum),
+            "cache" => Ok(Self::Cache),
+            "collections" => Ok(Self::Collections),
+            "diff" => Ok(Self::Diff),
             "fs" => Ok(Self::Fs),
+            #[cfg(feature = "image")]
+            "image" => Ok(Self::Image),
+            "log" => Ok(Self::Log),
             "luau" => Ok(Self::Luau),
+            "metrics" => Ok(Self::Metrics),
             "net" => Ok(Self::Net),
             "task" => Ok(Self::Task),
             "process" => Ok(Self::Process),
+            #[cfg(feature = "sqlite")]
+            "queue" => Ok(Self::Queue),
             "serde" => Ok(Self::Serde),
             "stdio" => Ok(Self::Stdio),
+            "struct" => Ok(Self::Struct),
+            "trace" => Ok(Self::Trace),
+            "unicode" => Ok(Self::Unicode),
             #[cfg(feature = "roblox")]
             "roblox" => Ok(Self::Roblox),
             _ => Err(format!("Unknown builtin library '{s}'")),
@@ -0,0 +1,538 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use hyper::{Body, Client, Uri};
+use hyper_rustls::HttpsConnectorBuilder;
+use instant_acme::{
+    Account, AccountCredentials, AuthorizationStatus, ChallengeType, HttpClient, Identifier,
+    LetsEncrypt, NewAccount, NewOrder, Order, OrderStatus,
+};
+use mlua::prelude::*;
+use rcgen::{Certificate as CertificateBuilder, CertificateParams, CustomExtension};
+use tokio::{fs, io::AsyncWriteExt, task::JoinHandle, time};
+use tokio_rustls::rustls::{
+    server::{ClientHello, ResolvesServerCert},
+    sign::{self, CertifiedKey},
+    Certificate, PrivateKey, ServerConfig,
+};
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+use super::{
+    config::ServeAcmeConfig,
+    tls::{read_certs, read_private_key, ACME_TLS_ALPN},
+};
+
+const ACCOUNT_FILE_NAME: &str = "account.json";
+const CERT_FILE_NAME: &str = "cert.pem";
+const KEY_FILE_NAME: &str = "key.pem";
+
+/**
+    How long to wait between checks of an order that is not done yet, at first.
+
+    This doubles after each check, up to [`POLL_DELAY_MAX`], since
+    most certificate authorities validate challenges within seconds.
+*/
+const POLL_DELAY_MIN: Duration = Duration::from_millis(250);
+const POLL_DELAY_MAX: Duration = Duration::from_secs(10);
+
+/**
+    How long an order may take before it is considered failed.
+*/
+const ORDER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/**
+    How long to wait before trying again after failing to get a certificate, at first.
+
+    This doubles after each failure, up to [`RETRY_DELAY_MAX`], to stay
+    well clear of the rate limits for failed validations at Let's Encrypt.
+*/
+const RETRY_DELAY_MIN: Duration = Duration::from_secs(60);
+const RETRY_DELAY_MAX: Duration = Duration::from_secs(60 * 60);
+
+/**
+    The longest time to sleep before checking if the certificate needs renewal.
+
+    Sleeping in shorter steps means that time spent suspended is also accounted for.
+*/
+const RENEWAL_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/**
+    Resolves the certificate for a server using ACME, which is
+    swapped out while the server is running once it has been renewed.
+
+    Certificate authorities connecting using the `acme-tls/1` protocol
+    are given the certificate for their TLS-ALPN-01 challenge instead.
+*/
+#[derive(Default)]
+struct AcmeResolver {
+    certificate: RwLock<Option<Arc<CertifiedKey>>>,
+    challenges: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+}
+
+impl AcmeResolver {
+    fn set_certificate(&self, certificate: Arc<CertifiedKey>) {
+        self.certificate
+            .write()
+            .expect("Failed to lock acme certificate")
+            .replace(certificate);
+    }
+
+    fn set_challenge(&self, domain: &str, certificate: Arc<CertifiedKey>) {
+        self.challenges
+            .write()
+            .expect("Failed to lock acme challenges")
+            .insert(domain.to_ascii_lowercase(), certificate);
+    }
+
+    fn clear_challenges(&self) {
+        self.challenges
+            .write()
+            .expect("Failed to lock acme challenges")
+            .clear();
+    }
+}
+
+impl ResolvesServerCert for AcmeResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let is_challenge = client_hello
+            .alpn()
+            .is_some_and(|mut protocols| protocols.any(|p| p == ACME_TLS_ALPN));
+        if is_challenge {
+            let domain = client_hello.server_name()?.to_ascii_lowercase();
+            self.challenges
+                .read()
+                .expect("Failed to lock acme challenges")
+                .get(&domain)
+                .cloned()
+        } else {
+            self.certificate
+                .read()
+                .expect("Failed to lock acme certificate")
+                .clone()
+        }
+    }
+}
+
+/**
+    A background task that keeps the certificate for a server
+    renewed, which is stopped once this handle is dropped.
+*/
+pub(super) struct AcmeRenewal(JoinHandle<()>);
+
+impl Drop for AcmeRenewal {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/**
+    Creates a TLS config that uses certificates from an ACME server,
+    and starts the background task that gets and renews them.
+
+    A certificate from a previous run in the cache directory is used right away if it covers
+    all of the domains, otherwise a new one is ordered in the background - until it has been
+    issued, TLS handshakes from clients other than the certificate authority will fail.
+*/
+pub(super) async fn create_acme_config(
+    config: &ServeAcmeConfig,
+) -> LuaResult<(ServerConfig, AcmeRenewal)> {
+    let directory_url = config
+        .directory_url
+        .clone()
+        .unwrap_or_else(|| LetsEncrypt::Production.url().to_string());
+    let dir = config.cache_dir.join(directory_name(&directory_url)?);
+    fs::create_dir_all(&dir).await.map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to create acme cache directory at '{}'\n> {e}",
+            dir.display()
+        ))
+    })?;
+
+    let resolver = Arc::new(AcmeResolver::default());
+    // NOTE: A cached certificate that can not be read is no
+    // different from a missing one, a new one will be ordered
+    let renew_at = match load_certificate(&dir, &config.domains).await {
+        Ok(Some((certificate, renew_at))) => {
+            resolver.set_certificate(certificate);
+            Some(renew_at)
+        }
+        Ok(None) | Err(_) => None,
+    };
+
+    let server_config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_cert_resolver(Arc::clone(&resolver) as Arc<dyn ResolvesServerCert>);
+
+    let renewer = AcmeRenewer {
+        resolver,
+        dir,
+        directory_url,
+        domains: config.domains.clone(),
+        contact: config.contact.clone(),
+    };
+    let renewal = AcmeRenewal(tokio::spawn(renewer.run(renew_at)));
+
+    Ok((server_config, renewal))
+}
+
+struct AcmeRenewer {
+    resolver: Arc<AcmeResolver>,
+    dir: PathBuf,
+    directory_url: String,
+    domains: Vec<String>,
+    contact: Vec<String>,
+}
+
+impl AcmeRenewer {
+    /**
+        Orders a new certificate whenever the current one is due for renewal,
+        which is once two thirds of its lifetime has passed, or right away if
+        there is no current certificate.
+    */
+    async fn run(self, mut renew_at: Option<SystemTime>) {
+        let mut retry_delay = RETRY_DELAY_MIN;
+        loop {
+            if let Some(renew_at) = renew_at {
+                let remaining = renew_at
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default();
+                if !remaining.is_zero() {
+                    time::sleep(remaining.min(RENEWAL_CHECK_INTERVAL)).await;
+                    continue;
+                }
+            }
+            match self.order_certificate().await {
+                Ok(next) => {
+                    renew_at = Some(next);
+                    retry_delay = RETRY_DELAY_MIN;
+                }
+                Err(e) => {
+                    let message = match e {
+                        LuaError::RuntimeError(message) => message,
+                        e => e.to_string(),
+                    };
+                    eprintln!(
+                        "Net serve error: Failed to get certificate for '{}'\n> {message}",
+                        self.domains.join("', '")
+                    );
+                    time::sleep(retry_delay).await;
+                    retry_delay = (retry_delay * 2).min(RETRY_DELAY_MAX);
+                }
+            }
+        }
+    }
+
+    /**
+        Orders a new certificate, stores it in the cache directory, and starts
+        using it for new connections, returning when it should be renewed.
+    */
+    async fn order_certificate(&self) -> LuaResult<SystemTime> {
+        let account = self.account().await?;
+        let identifiers = self
+            .domains
+            .iter()
+            .map(|domain| Identifier::Dns(domain.clone()))
+            .collect::<Vec<_>>();
+        let mut order = account
+            .new_order(&NewOrder {
+                identifiers: &identifiers,
+            })
+            .await
+            .map_err(|e| acme_error("create order", e))?;
+
+        // NOTE: Challenge certificates are only needed while the order is being
+        // validated, and must be removed even if it fails or is never validated
+        let result = self.complete_order(&mut order).await;
+        self.resolver.clear_challenges();
+        let (chain_pem, key_pem) = result?;
+
+        write_file(&self.dir.join(CERT_FILE_NAME), chain_pem.as_bytes(), false).await?;
+        write_file(&self.dir.join(KEY_FILE_NAME), key_pem.as_bytes(), true).await?;
+        match load_certificate(&self.dir, &self.domains).await? {
+            Some((certificate, renew_at)) => {
+                self.resolver.set_certificate(certificate);
+                Ok(renew_at)
+            }
+            None => Err(LuaError::RuntimeError(
+                "The issued certificate does not cover all of the domains".to_string(),
+            )),
+        }
+    }
+
+    /**
+        Answers the TLS-ALPN-01 challenges for an order, and then finalizes
+        it, returning the issued certificate chain and its private key.
+    */
+    async fn complete_order(&self, order: &mut Order) -> LuaResult<(String, String)> {
+        let authorizations = order
+            .authorizations()
+            .await
+            .map_err(|e| acme_error("get authorizations", e))?;
+        let mut challenge_urls = Vec::new();
+        for authorization in &authorizations {
+            let Identifier::Dns(domain) = &authorization.identifier;
+            match authorization.status {
+                AuthorizationStatus::Valid => continue,
+                AuthorizationStatus::Pending => {}
+                status => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Authorization for '{domain}' is {status:?}"
+                    )))
+                }
+            }
+            let challenge = authorization
+                .challenges
+                .iter()
+                .find(|challenge| challenge.r#type == ChallengeType::TlsAlpn01)
+                .ok_or_else(|| {
+                    LuaError::RuntimeError(format!(
+                        "No TLS-ALPN-01 challenge was offered for '{domain}'"
+                    ))
+                })?;
+            let digest = order.key_authorization(challenge).digest();
+            let certificate = challenge_certificate(domain, digest.as_ref())?;
+            self.resolver.set_challenge(domain, certificate);
+            challenge_urls.push(challenge.url.clone());
+        }
+        for url in &challenge_urls {
+            order
+                .set_challenge_ready(url)
+                .await
+                .map_err(|e| acme_error("mark challenge as ready", e))?;
+        }
+
+        let deadline = Instant::now() + ORDER_TIMEOUT;
+        let mut delay = POLL_DELAY_MIN;
+        loop {
+            let state = order
+                .refresh()
+                .await
+                .map_err(|e| acme_error("check order", e))?;
+            match state.status {
+                OrderStatus::Ready => break,
+                OrderStatus::Invalid => {
+                    return Err(match &state.error {
+                        Some(problem) => acme_error("validate order", problem),
+                        None => LuaError::RuntimeError("Order is invalid".to_string()),
+                    })
+                }
+                _ => wait_for_order(&mut delay, deadline).await?,
+            }
+        }
+
+        let mut params = CertificateParams::new(self.domains.clone());
+        params.distinguished_name = rcgen::DistinguishedName::new();
+        let key = CertificateBuilder::from_params(params).into_lua_err()?;
+        let csr = key.serialize_request_der().into_lua_err()?;
+        order
+            .finalize(&csr)
+            .await
+            .map_err(|e| acme_error("finalize order", e))?;
+
+        let mut delay = POLL_DELAY_MIN;
+        let chain_pem = loop {
+            match order.certificate().await {
+                Ok(Some(chain_pem)) => break chain_pem,
+                Ok(None) => wait_for_order(&mut delay, deadline).await?,
+                Err(e) => return Err(acme_error("download certificate", e)),
+            }
+        };
+
+        Ok((chain_pem, key.serialize_private_key_pem()))
+    }
+
+    /**
+        Gets the account for the certificate authority, creating
+        one and storing it in the cache directory if there is none.
+    */
+    async fn account(&self) -> LuaResult<Account> {
+        let path = self.dir.join(ACCOUNT_FILE_NAME);
+        if let Ok(contents) = fs::read(&path).await {
+            let credentials =
+                serde_json::from_slice::<AccountCredentials>(&contents).map_err(|e| {
+                    LuaError::RuntimeError(format!(
+                        "Failed to parse acme account at '{}'\n> {e}",
+                        path.display()
+                    ))
+                })?;
+            return Account::from_credentials_and_http(credentials, http_client())
+                .await
+                .map_err(|e| acme_error("load account", e));
+        }
+
+        let contact = self.contact.iter().map(String::as_str).collect::<Vec<_>>();
+        let (account, credentials) = Account::create_with_http(
+            &NewAccount {
+                contact: &contact,
+                terms_of_service_agreed: true,
+                only_return_existing: false,
+            },
+            &self.directory_url,
+            None,
+            http_client(),
+        )
+        .await
+        .map_err(|e| acme_error("create account", e))?;
+        let contents = serde_json::to_vec(&credentials).into_lua_err()?;
+        write_file(&path, &contents, true).await?;
+
+        Ok(account)
+    }
+}
+
+fn acme_error(action: &str, e: impl std::fmt::Display) -> LuaError {
+    LuaError::RuntimeError(format!("Failed to {action}\n> {e}"))
+}
+
+async fn wait_for_order(delay: &mut Duration, deadline: Instant) -> LuaResult<()> {
+    if Instant::now() >= deadline {
+        return Err(LuaError::RuntimeError(format!(
+            "Order was not done within {} seconds",
+            ORDER_TIMEOUT.as_secs()
+        )));
+    }
+    time::sleep(*delay).await;
+    *delay = (*delay * 2).min(POLL_DELAY_MAX);
+    Ok(())
+}
+
+fn http_client() -> Box<dyn HttpClient> {
+    // NOTE: Plain http is allowed for testing against local certificate authorities
+    let connector = HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    Box::new(Client::builder().build::<_, Body>(connector))
+}
+
+/**
+    Gets the name of the directory to cache the account and certificate in, which is
+    different for each certificate authority, such as the staging and production
+    environments of Let's Encrypt, so that switching between them is safe.
+*/
+fn directory_name(directory_url: &str) -> LuaResult<String> {
+    let uri = directory_url.parse::<Uri>().ok();
+    let host = match uri.as_ref().and_then(Uri::host) {
+        Some(host) => host,
+        None => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid 'directoryUrl' in serve acme config - '{directory_url}' is not a valid url"
+            )))
+        }
+    };
+    let name = match uri.as_ref().and_then(Uri::port_u16) {
+        Some(port) => format!("{host}_{port}"),
+        None => host.to_string(),
+    };
+    Ok(name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect())
+}
+
+/**
+    Creates the self-signed certificate for a TLS-ALPN-01 challenge,
+    which contains the digest of the key authorization for the domain.
+*/
+fn challenge_certificate(domain: &str, digest: &[u8]) -> LuaResult<Arc<CertifiedKey>> {
+    let mut params = CertificateParams::new(vec![domain.to_string()]);
+    params.custom_extensions = vec![CustomExtension::new_acme_identifier(digest)];
+    let certificate = CertificateBuilder::from_params(params).into_lua_err()?;
+    let cert = Certificate(certificate.serialize_der().into_lua_err()?);
+    let key = PrivateKey(certificate.serialize_private_key_der());
+    certified_key(vec![cert], &key)
+}
+
+fn certified_key(certs: Vec<Certificate>, key: &PrivateKey) -> LuaResult<Arc<CertifiedKey>> {
+    let key = sign::any_supported_type(key)
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to use private key\n> {e}")))?;
+    Ok(Arc::new(CertifiedKey::new(certs, key)))
+}
+
+/**
+    Loads the certificate from the cache directory, along with when it should be renewed.
+
+    Returns `None` if there is no certificate, or if it does not cover all of the domains.
+*/
+async fn load_certificate(
+    dir: &Path,
+    domains: &[String],
+) -> LuaResult<Option<(Arc<CertifiedKey>, SystemTime)>> {
+    let cert_path = dir.join(CERT_FILE_NAME);
+    let key_path = dir.join(KEY_FILE_NAME);
+    if !fs::try_exists(&cert_path).await.unwrap_or_default()
+        || !fs::try_exists(&key_path).await.unwrap_or_default()
+    {
+        return Ok(None);
+    }
+    let certs = read_certs(&cert_path).await?;
+    let key = read_private_key(&key_path).await?;
+    match renewal_time(&certs[0], domains) {
+        Some(renew_at) => Ok(Some((certified_key(certs, &key)?, renew_at))),
+        None => Ok(None),
+    }
+}
+
+/**
+    Gets the time at which two thirds of the lifetime of a certificate has passed,
+    or `None` if it could not be parsed or does not cover all of the domains.
+*/
+fn renewal_time(cert: &Certificate, domains: &[String]) -> Option<SystemTime> {
+    let (_, parsed) = X509Certificate::from_der(&cert.0).ok()?;
+    let names = parsed
+        .subject_alternative_name()
+        .ok()??
+        .value
+        .general_names
+        .iter()
+        .filter_map(|name| match name {
+            GeneralName::DNSName(name) => Some(*name),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let covers_domains = domains
+        .iter()
+        .all(|domain| names.iter().any(|name| name.eq_ignore_ascii_case(domain)));
+    if !covers_domains {
+        return None;
+    }
+
+    let not_before = parsed.validity().not_before.timestamp();
+    let not_after = parsed.validity().not_after.timestamp();
+    let renew_at = not_after - (not_after - not_before).max(0) / 3;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(renew_at).unwrap_or_default()))
+}
+
+/**
+    Writes a file, which is only readable by the current user if it contains a private key.
+*/
+async fn write_file(path: &Path, contents: &[u8], private: bool) -> LuaResult<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    let result = async {
+        let mut file = options.open(path).await?;
+        file.write_all(contents).await?;
+        file.flush().await
+    };
+    result.await.map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to write acme cache file at '{}'\n> {e}",
+            path.display()
+        ))
+    })
+}
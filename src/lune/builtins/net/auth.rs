@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+
+use md5::{Digest, Md5};
+use mlua::prelude::*;
+use reqwest::{Method, RequestBuilder, Url};
+use ring::{
+    digest,
+    rand::{SecureRandom, SystemRandom},
+};
+
+// Net request auth
+
+#[derive(Debug, Clone)]
+pub enum RequestAuth {
+    Basic { username: String, password: String },
+    Bearer { token: String },
+    Digest { username: String, password: String },
+}
+
+impl RequestAuth {
+    /**
+        Applies this auth to a request that is about to be sent.
+
+        Digest auth can not be applied up front since it needs a challenge
+        from the server, see [`RequestAuth::digest_authorization`] for it.
+    */
+    pub fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        match self {
+            Self::Basic { username, password } => request.basic_auth(username, Some(password)),
+            Self::Bearer { token } => request.bearer_auth(token),
+            Self::Digest { .. } => request,
+        }
+    }
+
+    /**
+        Computes the `Authorization` header value for a digest auth challenge
+        given by the server in a `WWW-Authenticate` header, for a request to `url`.
+
+        Returns `None` if this is not digest auth, or if the challenge was not a
+        digest challenge that can be answered, such as one for an unknown algorithm.
+    */
+    pub fn digest_authorization(
+        &self,
+        challenge: &str,
+        method: &Method,
+        url: &Url,
+    ) -> LuaResult<Option<String>> {
+        let (username, password) = match self {
+            Self::Digest { username, password } => (username, password),
+            _ => return Ok(None),
+        };
+        let challenge = match DigestChallenge::parse(challenge) {
+            Some(challenge) => challenge,
+            None => return Ok(None),
+        };
+        challenge
+            .authorization(username, password, method, url)
+            .map(Some)
+    }
+}
+
+impl<'lua> FromLua<'lua> for RequestAuth {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let tab = match value {
+            LuaValue::Table(tab) => tab,
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "RequestAuth",
+                    message: Some(format!(
+                        "Invalid request auth - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        let get_field = |name: &str| match tab.raw_get::<_, Option<String>>(name) {
+            Ok(Some(value)) => Ok(value),
+            _ => Err(LuaError::RuntimeError(format!(
+                "Missing or invalid '{name}' in request auth"
+            ))),
+        };
+        let kind = get_field("type")?;
+        match kind.to_ascii_lowercase().as_str() {
+            "basic" => Ok(Self::Basic {
+                username: get_field("username")?,
+                password: get_field("password")?,
+            }),
+            "bearer" => Ok(Self::Bearer {
+                token: get_field("token")?,
+            }),
+            "digest" => Ok(Self::Digest {
+                username: get_field("username")?,
+                password: get_field("password")?,
+            }),
+            _ => Err(LuaError::RuntimeError(format!(
+                "Invalid request auth type '{kind}' - expected one of 'basic', 'bearer', 'digest'"
+            ))),
+        }
+    }
+}
+
+// Digest auth, see RFC 7616
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DigestAlgorithm {
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+}
+
+impl DigestAlgorithm {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "MD5" => Some(Self::Md5),
+            "MD5-SESS" => Some(Self::Md5Sess),
+            "SHA-256" => Some(Self::Sha256),
+            "SHA-256-SESS" => Some(Self::Sha256Sess),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Md5Sess => "MD5-sess",
+            Self::Sha256 => "SHA-256",
+            Self::Sha256Sess => "SHA-256-sess",
+        }
+    }
+
+    fn is_session(&self) -> bool {
+        matches!(self, Self::Md5Sess | Self::Sha256Sess)
+    }
+
+    fn hash(&self, data: impl AsRef<[u8]>) -> String {
+        let bytes = match self {
+            Self::Md5 | Self::Md5Sess => Md5::digest(data.as_ref()).to_vec(),
+            Self::Sha256 | Self::Sha256Sess => digest::digest(&digest::SHA256, data.as_ref())
+                .as_ref()
+                .to_vec(),
+        };
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: DigestAlgorithm,
+    qop_auth: bool,
+}
+
+impl DigestChallenge {
+    fn parse(header: &str) -> Option<Self> {
+        let header = header.trim();
+        let (scheme, params) = header.split_once(char::is_whitespace)?;
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+
+        let params = parse_auth_params(params);
+        let algorithm = match params.get("algorithm") {
+            Some(algorithm) => DigestAlgorithm::parse(algorithm)?,
+            None => DigestAlgorithm::Md5,
+        };
+        // NOTE: We only support the "auth" quality of protection, not "auth-int",
+        // servers that offer neither are using the legacy RFC 2069 digest scheme
+        let qop_auth = match params.get("qop") {
+            Some(qop) => {
+                if !qop
+                    .split(',')
+                    .any(|q| q.trim().eq_ignore_ascii_case("auth"))
+                {
+                    return None;
+                }
+                true
+            }
+            None => false,
+        };
+
+        Some(Self {
+            realm: params.get("realm")?.to_string(),
+            nonce: params.get("nonce")?.to_string(),
+            opaque: params.get("opaque").map(ToString::to_string),
+            algorithm,
+            qop_auth,
+        })
+    }
+
+    fn authorization(
+        &self,
+        username: &str,
+        password: &str,
+        method: &Method,
+        url: &Url,
+    ) -> LuaResult<String> {
+        let uri = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        };
+
+        let cnonce = create_cnonce()?;
+        let nc = "00000001";
+
+        let mut ha1 = self
+            .algorithm
+            .hash(format!("{username}:{}:{password}", self.realm));
+        if self.algorithm.is_session() {
+            ha1 = self
+                .algorithm
+                .hash(format!("{ha1}:{}:{cnonce}", self.nonce));
+        }
+        let ha2 = self.algorithm.hash(format!("{}:{uri}", method.as_str()));
+        let response = if self.qop_auth {
+            self.algorithm
+                .hash(format!("{ha1}:{}:{nc}:{cnonce}:auth:{ha2}", self.nonce))
+        } else {
+            self.algorithm.hash(format!("{ha1}:{}:{ha2}", self.nonce))
+        };
+
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{uri}\", algorithm={}, response=\"{response}\"",
+            escape_quoted(username),
+            escape_quoted(&self.realm),
+            escape_quoted(&self.nonce),
+            self.algorithm.name(),
+        );
+        if self.qop_auth {
+            header.push_str(&format!(", qop=auth, nc={nc}, cnonce=\"{cnonce}\""));
+        }
+        if let Some(opaque) = &self.opaque {
+            header.push_str(&format!(", opaque=\"{}\"", escape_quoted(opaque)));
+        }
+
+        Ok(header)
+    }
+}
+
+/**
+    Parses comma-separated `key=value` / `key="quoted value"` auth parameters.
+
+    Keys are case-insensitive and will be returned in lowercase.
+*/
+fn parse_auth_params(params: &str) -> HashMap<String, String> {
+    let mut parsed = HashMap::new();
+    let mut chars = params.chars().peekable();
+    loop {
+        // Skip any separators and whitespace between params
+        while matches!(chars.peek(), Some(c) if *c == ',' || c.is_whitespace()) {
+            chars.next();
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| *c != '=' && *c != ',') {
+            key.push(c);
+        }
+        if chars.next() != Some('=') {
+            break;
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '"' => break,
+                    c => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| *c != ',') {
+                value.push(c);
+            }
+        }
+        parsed.insert(
+            key.trim().to_ascii_lowercase(),
+            value.trim_end().to_string(),
+        );
+    }
+    parsed
+}
+
+fn escape_quoted(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn create_cnonce() -> LuaResult<String> {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| LuaError::RuntimeError("Failed to generate digest auth cnonce".to_string()))?;
+    Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_auth_params_quoted_and_unquoted() {
+        let params = parse_auth_params(
+            r#"realm="test@example.com", qop="auth,auth-int", algorithm=MD5, nonce="abc\"def""#,
+        );
+        assert_eq!(params.get("realm").unwrap(), "test@example.com");
+        assert_eq!(params.get("qop").unwrap(), "auth,auth-int");
+        assert_eq!(params.get("algorithm").unwrap(), "MD5");
+        assert_eq!(params.get("nonce").unwrap(), "abc\"def");
+    }
+
+    #[test]
+    fn digest_response_rfc_2617_example() {
+        // Example from RFC 2617, section 3.5, with a fixed cnonce
+        let challenge = DigestChallenge::parse(
+            r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#,
+        )
+        .unwrap();
+        let algorithm = challenge.algorithm;
+        let ha1 = algorithm.hash("Mufasa:testrealm@host.com:Circle Of Life");
+        let ha2 = algorithm.hash("GET:/dir/index.html");
+        let response = algorithm.hash(format!(
+            "{ha1}:{}:00000001:0a4f113b:auth:{ha2}",
+            challenge.nonce
+        ));
+        assert_eq!(response, "6629fae49393a05397450978507c4ef1");
+    }
+
+    #[test]
+    fn digest_challenge_rejects_other_schemes() {
+        assert!(DigestChallenge::parse(r#"Basic realm="test""#).is_none());
+        assert!(
+            DigestChallenge::parse(r#"Digest realm="test", nonce="abc", algorithm=UNKNOWN"#)
+                .is_none()
+        );
+        assert!(
+            DigestChallenge::parse(r#"Digest realm="test", nonce="abc", qop="auth-int""#).is_none()
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use hyper::body::Bytes;
+use mlua::prelude::*;
+use reqwest::Response;
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::lune::{
+    builtins::fs::lines::{LineReader, LineReaderOptions, LineSource},
+    util::TableBuilder,
+};
+
+/**
+    A response body that is read incrementally, one chunk at a time,
+    instead of being buffered fully into memory up front.
+*/
+pub struct NetResponseBody {
+    response: Option<Response>,
+    pending: Bytes,
+}
+
+impl NetResponseBody {
+    pub fn new(response: Response) -> Self {
+        Self {
+            response: Some(response),
+            pending: Bytes::new(),
+        }
+    }
+
+    /**
+        Reads the next chunk of the body, containing at most `max_size` bytes.
+
+        If no maximum size is given, the next chunk will be returned exactly
+        as it was received, which may be any size depending on the server.
+
+        Returns `None` once the entire body has been read.
+    */
+    pub async fn read(&mut self, max_size: Option<usize>) -> LuaResult<Option<Bytes>> {
+        while self.pending.is_empty() {
+            let response = match self.response.as_mut() {
+                Some(response) => response,
+                None => return Ok(None),
+            };
+            match response.chunk().await.into_lua_err()? {
+                Some(chunk) => self.pending = chunk,
+                None => {
+                    // NOTE: Dropping the response here releases the connection
+                    // as soon as possible, instead of when the body is garbage collected
+                    self.response.take();
+                    return Ok(None);
+                }
+            }
+        }
+        let size = match max_size {
+            Some(max_size) => max_size.min(self.pending.len()),
+            None => self.pending.len(),
+        };
+        Ok(Some(self.pending.split_to(size)))
+    }
+
+    /**
+        Creates a Lua table for this response body, with a `read` method that
+        yields until the next chunk is available, or returns `nil` when done,
+        and a `lines` method for reading the rest of the body line by line.
+    */
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable> {
+        let body = Arc::new(AsyncMutex::new(self));
+        let body_lines = Arc::clone(&body);
+        TableBuilder::new(lua)?
+            .with_async_function(
+                "read",
+                move |lua, (_, chunk_size): (LuaValue, Option<usize>)| {
+                    let body = Arc::clone(&body);
+                    async move {
+                        if chunk_size == Some(0) {
+                            return Err(LuaError::RuntimeError(
+                                "Chunk size must be greater than zero".to_string(),
+                            ));
+                        }
+                        match body.lock().await.read(chunk_size).await? {
+                            Some(chunk) => Ok(LuaValue::String(lua.create_string(chunk)?)),
+                            None => Ok(LuaValue::Nil),
+                        }
+                    }
+                },
+            )?
+            .with_function(
+                "lines",
+                move |lua, (_, options): (LuaValue, LineReaderOptions)| {
+                    let source = BodyLineSource(Arc::clone(&body_lines));
+                    LineReader::from_source(source, options).into_lua_table(lua)
+                },
+            )?
+            .build_readonly()
+    }
+}
+
+struct BodyLineSource(Arc<AsyncMutex<NetResponseBody>>);
+
+#[async_trait]
+impl LineSource for BodyLineSource {
+    async fn read_chunk(&mut self) -> LuaResult<Option<Vec<u8>>> {
+        let chunk = self.0.lock().await.read(None).await?;
+        Ok(chunk.map(|chunk| chunk.to_vec()))
+    }
+}
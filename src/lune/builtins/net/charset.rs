@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+
+use mlua::prelude::*;
+
+use crate::lune::util::TableBuilder;
+
+/**
+    Characters for bytes `0x80` through `0x9F` in windows-1252, which
+    differ from latin-1 in that these are printable instead of control characters.
+
+    Bytes that are not mapped to any character decode to their latin-1 equivalent.
+*/
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/**
+    A parsed `Content-Type` header value, such as `text/html; charset=utf-8`.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType {
+    pub mime_type: String,
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl ContentType {
+    /**
+        Parses a `Content-Type` header value, returning `None` if it is not a valid media type.
+
+        The mime type and parameter names are lowercased, since they are case-insensitive.
+    */
+    pub fn parse(header: &str) -> Option<Self> {
+        let (mime_type, mut rest) = header.split_once(';').unwrap_or((header, ""));
+        let mime_type = mime_type.trim().to_ascii_lowercase();
+        match mime_type.split_once('/') {
+            Some((kind, subtype)) if !kind.is_empty() && !subtype.is_empty() => {}
+            _ => return None,
+        }
+
+        let mut parameters = BTreeMap::new();
+        while !rest.is_empty() {
+            // Parameters without a value are invalid, and skipped
+            let (name, after_name) = match rest.find([';', '=']) {
+                Some(index) if rest[index..].starts_with('=') => {
+                    (&rest[..index], &rest[index + 1..])
+                }
+                Some(index) => {
+                    rest = &rest[index + 1..];
+                    continue;
+                }
+                None => break,
+            };
+            let after_name = after_name.trim_start();
+            // NOTE: Quoted values may contain semicolons and escaped characters
+            let (value, after_value) = if let Some(quoted) = after_name.strip_prefix('"') {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((index, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        '"' => {
+                            end = index + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                let after_value = &quoted[end..];
+                let after_value = after_value.split_once(';').map_or("", |(_, r)| r);
+                (value, after_value)
+            } else {
+                let (value, after_value) = after_name.split_once(';').unwrap_or((after_name, ""));
+                (value.trim().to_string(), after_value)
+            };
+            let name = name.trim().to_ascii_lowercase();
+            if !name.is_empty() {
+                parameters.entry(name).or_insert(value);
+            }
+            rest = after_value;
+        }
+
+        Some(Self {
+            mime_type,
+            parameters,
+        })
+    }
+
+    pub fn charset(&self) -> Option<&str> {
+        self.parameters.get("charset").map(String::as_str)
+    }
+}
+
+impl<'lua> IntoLua<'lua> for ContentType {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        let (kind, subtype) = self
+            .mime_type
+            .split_once('/')
+            .expect("Content type must contain a subtype");
+        TableBuilder::new(lua)?
+            .with_value("mimeType", self.mime_type.as_str())?
+            .with_value("type", kind)?
+            .with_value("subtype", subtype)?
+            .with_value("charset", self.charset().map(str::to_ascii_lowercase))?
+            .with_value("parameters", lua.create_table_from(self.parameters)?)?
+            .build_readonly()
+            .map(LuaValue::Table)
+    }
+}
+
+/**
+    A character encoding that response bodies can be decoded from.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Utf8,
+    Utf16,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Windows1252,
+}
+
+impl Charset {
+    /**
+        Finds a charset from one of its labels, as used in `Content-Type` headers.
+    */
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" | "unicode-1-1-utf-8" => Some(Self::Utf8),
+            "utf-16" | "utf16" | "ucs-2" => Some(Self::Utf16),
+            "utf-16le" => Some(Self::Utf16Le),
+            "utf-16be" => Some(Self::Utf16Be),
+            "iso-8859-1" | "iso8859-1" | "iso_8859-1" | "latin1" | "latin-1" | "l1"
+            | "us-ascii" | "ascii" => Some(Self::Latin1),
+            "windows-1252" | "cp1252" | "x-cp1252" => Some(Self::Windows1252),
+            _ => None,
+        }
+    }
+
+    /**
+        Decodes the given bytes into a string.
+
+        Byte order marks are removed, and for plain `utf-16` they also decide
+        the byte order, which defaults to little endian without a byte order mark.
+
+        Invalid sequences are replaced with the unicode replacement character.
+    */
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Utf8 => {
+                let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+                String::from_utf8_lossy(bytes).into_owned()
+            }
+            Self::Utf16 => match bytes {
+                [0xFE, 0xFF, rest @ ..] => decode_utf16(rest, u16::from_be_bytes),
+                [0xFF, 0xFE, rest @ ..] => decode_utf16(rest, u16::from_le_bytes),
+                _ => decode_utf16(bytes, u16::from_le_bytes),
+            },
+            Self::Utf16Le => {
+                let bytes = bytes.strip_prefix(b"\xFF\xFE").unwrap_or(bytes);
+                decode_utf16(bytes, u16::from_le_bytes)
+            }
+            Self::Utf16Be => {
+                let bytes = bytes.strip_prefix(b"\xFE\xFF").unwrap_or(bytes);
+                decode_utf16(bytes, u16::from_be_bytes)
+            }
+            Self::Latin1 => bytes.iter().map(|&b| char::from(b)).collect(),
+            Self::Windows1252 => bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[usize::from(b - 0x80)],
+                    b => char::from(b),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = bytes
+        .chunks(2)
+        .map(|chunk| match chunk {
+            [a, b] => from_bytes([*a, *b]),
+            _ => 0xFFFD, // Odd trailing byte
+        })
+        .collect::<Vec<_>>();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_content_types() {
+        let content_type =
+            ContentType::parse("Text/HTML; Charset=\"ISO-8859-1\"; q=\"a;\\\"b\"").unwrap();
+        assert_eq!(content_type.mime_type, "text/html");
+        assert_eq!(content_type.charset(), Some("ISO-8859-1"));
+        assert_eq!(
+            content_type.parameters.get("q").map(String::as_str),
+            Some("a;\"b")
+        );
+
+        let content_type = ContentType::parse("application/json").unwrap();
+        assert_eq!(content_type.charset(), None);
+        assert_eq!(
+            ContentType::parse("text/plain; flowed; charset=utf-8")
+                .unwrap()
+                .charset(),
+            Some("utf-8")
+        );
+        assert!(ContentType::parse("json").is_none());
+        assert!(ContentType::parse("").is_none());
+    }
+
+    #[test]
+    fn decodes_charsets() {
+        assert_eq!(Charset::Latin1.decode(b"caf\xE9"), "café");
+        assert_eq!(
+            Charset::Windows1252.decode(b"\x93hi\x94 \x80"),
+            "\u{201C}hi\u{201D} €"
+        );
+        assert_eq!(Charset::Utf8.decode(b"\xEF\xBB\xBFcaf\xC3\xA9"), "café");
+        assert_eq!(Charset::Utf16.decode(b"\xFE\xFF\x00h\x00i"), "hi");
+        assert_eq!(Charset::Utf16.decode(b"h\x00i\x00"), "hi");
+        assert_eq!(Charset::Utf16Be.decode(b"\x00h\x00i"), "hi");
+        assert_eq!(Charset::from_label(" Latin1 "), Some(Charset::Latin1));
+        assert_eq!(Charset::from_label("koi8-r"), None);
+    }
+}
@@ -1,20 +1,46 @@
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc, time::Duration};
 
 use mlua::prelude::*;
 
 use hyper::{header::HeaderName, http::HeaderValue, HeaderMap};
-use reqwest::{IntoUrl, Method, RequestBuilder};
+use reqwest::{IntoUrl, Method, Request, RequestBuilder, Response};
+
+use super::{
+    cookies::NetCookieJar,
+    dns::NetDns,
+    happy_eyeballs::{HappyEyeballsResolver, IpVersion},
+    proxy::NetProxy,
+};
 
 const REGISTRY_KEY: &str = "NetClient";
 
+#[derive(Debug, Clone)]
 pub struct NetClientBuilder {
-    builder: reqwest::ClientBuilder,
+    headers: HeaderMap,
+    connect_timeout: Option<Duration>,
+    cookies: Option<Arc<NetCookieJar>>,
+    dns: Option<Arc<NetDns>>,
+    proxy: NetProxy,
+    ip_version: IpVersion,
+    http1_only: bool,
+    pool_max_idle_per_host: Option<usize>,
+    tcp_keepalive: Option<Duration>,
+    root_certificates: Vec<reqwest::Certificate>,
 }
 
 impl NetClientBuilder {
     pub fn new() -> NetClientBuilder {
         Self {
-            builder: reqwest::ClientBuilder::new(),
+            headers: HeaderMap::new(),
+            connect_timeout: None,
+            cookies: None,
+            dns: None,
+            proxy: NetProxy::default(),
+            ip_version: IpVersion::default(),
+            http1_only: false,
+            pool_max_idle_per_host: None,
+            tcp_keepalive: None,
+            root_certificates: Vec::new(),
         }
     }
 
@@ -29,22 +55,158 @@ impl NetClientBuilder {
             let hval = HeaderValue::from_bytes(val.as_ref()).into_lua_err()?;
             map.insert(hkey, hval);
         }
-        self.builder = self.builder.default_headers(map);
+        self.headers = map;
+        Ok(self)
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn cookies(mut self, jar: Arc<NetCookieJar>) -> Self {
+        self.cookies = Some(jar);
+        self
+    }
+
+    pub fn dns(mut self, dns: Arc<NetDns>) -> Self {
+        self.dns = Some(dns);
+        self
+    }
+
+    pub fn proxy(mut self, proxy: NetProxy) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    pub fn ip_version(mut self, version: IpVersion) -> Self {
+        self.ip_version = version;
+        self
+    }
+
+    pub fn http1_only(mut self) -> Self {
+        self.http1_only = true;
+        self
+    }
+
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    pub fn root_certificates(mut self, pem: &[u8]) -> LuaResult<Self> {
+        // NOTE: Reqwest only parses certificates once the client is built, and
+        // silently ignores anything that is not a certificate, so we parse here
+        let ders = rustls_pemfile::certs(&mut &pem[..]).map_err(|e| {
+            LuaError::RuntimeError(format!("Failed to parse root certificate\n> {e}"))
+        })?;
+        if ders.is_empty() {
+            return Err(LuaError::RuntimeError(
+                "Failed to parse root certificate\n> No certificates were found".to_string(),
+            ));
+        }
+        for der in ders {
+            let cert = reqwest::Certificate::from_der(&der).map_err(|e| {
+                LuaError::RuntimeError(format!("Failed to parse root certificate\n> {e}"))
+            })?;
+            self.root_certificates.push(cert);
+        }
         Ok(self)
     }
 
+    fn create_builder(&self, dns: &Arc<NetDns>) -> LuaResult<reqwest::ClientBuilder> {
+        let resolver = HappyEyeballsResolver::new(Arc::clone(dns), self.ip_version);
+        let mut builder = reqwest::ClientBuilder::new()
+            .default_headers(self.headers.clone())
+            .dns_resolver(Arc::new(resolver));
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        // NOTE: HTTP/2 is negotiated using ALPN, so it is only ever used for https urls,
+        // and we only turn it off when asked to, since servers may prefer using it
+        if self.http1_only {
+            builder = builder.http1_only();
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if self.tcp_keepalive.is_some() {
+            builder = builder.tcp_keepalive(self.tcp_keepalive);
+        }
+        // NOTE: Root certificates are trusted in addition to the default roots,
+        // so that clients can talk to servers using self-signed certificates
+        for cert in &self.root_certificates {
+            builder = builder.add_root_certificate(cert.clone());
+        }
+        self.proxy.apply(builder)
+    }
+
     pub fn build(self) -> LuaResult<NetClient> {
-        let client = self.builder.build().into_lua_err()?;
-        Ok(NetClient(client))
+        // NOTE: Cookies are opt-in for each request, so we create one client that
+        // uses the cookie jar and one that does not, since reqwest can only use a
+        // cookie jar for entire clients, including when following any redirects
+        let cookies = self.cookies.clone().unwrap_or_default();
+        let dns = self.dns.clone().unwrap_or_default();
+        let client = self.create_builder(&dns)?.build().into_lua_err()?;
+        let client_with_cookies = self
+            .create_builder(&dns)?
+            .cookie_provider(Arc::clone(&cookies))
+            .build()
+            .into_lua_err()?;
+        Ok(NetClient {
+            client,
+            client_with_cookies,
+            builder: Arc::new(self.cookies(Arc::clone(&cookies)).dns(Arc::clone(&dns))),
+            cookies,
+            dns,
+        })
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct NetClient(reqwest::Client);
+pub struct NetClient {
+    client: reqwest::Client,
+    client_with_cookies: reqwest::Client,
+    builder: Arc<NetClientBuilder>,
+    cookies: Arc<NetCookieJar>,
+    dns: Arc<NetDns>,
+}
 
 impl NetClient {
     pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
-        self.0.request(method, url)
+        self.client.request(method, url)
+    }
+
+    pub async fn execute(
+        &self,
+        request: Request,
+        use_cookies: bool,
+    ) -> Result<Response, reqwest::Error> {
+        if use_cookies {
+            self.client_with_cookies.execute(request).await
+        } else {
+            self.client.execute(request).await
+        }
+    }
+
+    /**
+        Creates a builder with the same options as this client, sharing its cookie jar and dns.
+    */
+    pub fn builder(&self) -> NetClientBuilder {
+        self.builder.as_ref().clone()
+    }
+
+    pub fn cookies(&self) -> Arc<NetCookieJar> {
+        Arc::clone(&self.cookies)
+    }
+
+    pub fn dns(&self) -> Arc<NetDns> {
+        Arc::clone(&self.dns)
     }
 
     pub fn into_registry(self, lua: &Lua) {
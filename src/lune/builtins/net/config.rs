@@ -1,24 +1,62 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
 
 use mlua::prelude::*;
 
 use reqwest::Method;
 
+use crate::lune::builtins::serde::compress_decompress::CompressDecompressFormat;
+
+use super::{
+    auth::RequestAuth,
+    form::RequestForm,
+    happy_eyeballs::IpVersion,
+    processing::{sort_routes, RoutePattern},
+    proxy::NetProxy,
+    response::NetServeResponse,
+    retry::{RequestRetry, RequestTimeout},
+    tunnel::TunnelProxy,
+};
+
 // Net request config
 
 #[derive(Debug, Clone)]
 pub struct RequestConfigOptions {
     pub decompress: bool,
+    pub compress: Option<CompressDecompressFormat>,
+    pub stream: bool,
+    pub sse: bool,
+    pub decode_text: bool,
+    pub cookies: bool,
+    pub timeout: RequestTimeout,
+    pub retry: Option<RequestRetry>,
+    pub proxy: Option<NetProxy>,
+    pub ip_version: IpVersion,
 }
 
 impl Default for RequestConfigOptions {
     fn default() -> Self {
-        Self { decompress: true }
+        Self {
+            decompress: true,
+            compress: None,
+            stream: false,
+            sse: false,
+            decode_text: false,
+            cookies: false,
+            timeout: RequestTimeout::default(),
+            retry: None,
+            proxy: None,
+            ip_version: IpVersion::Any,
+        }
     }
 }
 
 impl<'lua> FromLua<'lua> for RequestConfigOptions {
-    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
         // Nil means default options, table means custom options
         if let LuaValue::Nil = value {
             return Ok(Self::default());
@@ -30,7 +68,68 @@ impl<'lua> FromLua<'lua> for RequestConfigOptions {
                     "Invalid option value for 'decompress' in request config options".to_string(),
                 )),
             }?;
-            return Ok(Self { decompress });
+            let stream = match tab.raw_get::<_, Option<bool>>("stream") {
+                Ok(stream) => Ok(stream.unwrap_or(false)),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'stream' in request config options".to_string(),
+                )),
+            }?;
+            let sse = match tab.raw_get::<_, Option<bool>>("sse") {
+                Ok(sse) => Ok(sse.unwrap_or(false)),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'sse' in request config options".to_string(),
+                )),
+            }?;
+            let decode_text = match tab.raw_get::<_, Option<bool>>("decodeText") {
+                Ok(decode_text) => Ok(decode_text.unwrap_or(false)),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'decodeText' in request config options".to_string(),
+                )),
+            }?;
+            let cookies = match tab.raw_get::<_, Option<bool>>("cookies") {
+                Ok(cookies) => Ok(cookies.unwrap_or(false)),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'cookies' in request config options".to_string(),
+                )),
+            }?;
+            let compress = match tab.raw_get::<_, LuaValue>("compress")? {
+                LuaValue::Nil => None,
+                value => match CompressDecompressFormat::from_lua(value, lua) {
+                    Ok(format) if format.as_header_str().is_some() => Some(format),
+                    _ => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'compress' in request config options - \
+                            expected one of 'gzip', 'brotli', 'zlib'"
+                                .to_string(),
+                        ))
+                    }
+                },
+            };
+            let timeout = match tab.raw_get::<_, LuaValue>("timeout")? {
+                LuaValue::Nil => RequestTimeout::default(),
+                value => RequestTimeout::from_lua(value, lua)?,
+            };
+            let retry = match tab.raw_get::<_, LuaValue>("retry")? {
+                LuaValue::Nil => None,
+                value => Some(RequestRetry::from_lua(value, lua)?),
+            };
+            let proxy = match tab.raw_get::<_, LuaValue>("proxy")? {
+                LuaValue::Nil => None,
+                value => Some(NetProxy::from_lua(value, lua)?),
+            };
+            let ip_version = IpVersion::from_lua(tab.raw_get("ipVersion")?, lua)?;
+            return Ok(Self {
+                decompress,
+                compress,
+                stream,
+                sse,
+                decode_text,
+                cookies,
+                timeout,
+                retry,
+                proxy,
+                ip_version,
+            });
         }
         // Anything else is invalid
         Err(LuaError::FromLuaConversionError {
@@ -51,6 +150,8 @@ pub struct RequestConfig<'a> {
     pub query: HashMap<LuaString<'a>, LuaString<'a>>,
     pub headers: HashMap<LuaString<'a>, LuaString<'a>>,
     pub body: Option<Vec<u8>>,
+    pub form: Option<RequestForm>,
+    pub auth: Option<RequestAuth>,
     pub options: RequestConfigOptions,
 }
 
@@ -64,6 +165,8 @@ impl<'lua> FromLua<'lua> for RequestConfig<'lua> {
                 query: HashMap::new(),
                 headers: HashMap::new(),
                 body: None,
+                form: None,
+                auth: None,
                 options: Default::default(),
             });
         }
@@ -110,6 +213,21 @@ impl<'lua> FromLua<'lua> for RequestConfig<'lua> {
                 Ok(config_body) => Some(config_body.as_bytes().to_owned()),
                 Err(_) => None,
             };
+            // Extract form, which is mutually exclusive with body
+            let form = match tab.raw_get::<_, LuaValue>("form")? {
+                LuaValue::Nil => None,
+                value => Some(RequestForm::from_lua(value, lua)?),
+            };
+            if form.is_some() && body.is_some() {
+                return Err(LuaError::RuntimeError(
+                    "Request config may not contain both 'body' and 'form'".to_string(),
+                ));
+            }
+            // Extract auth
+            let auth = match tab.raw_get::<_, LuaValue>("auth")? {
+                LuaValue::Nil => None,
+                value => Some(RequestAuth::from_lua(value, lua)?),
+            };
             // Convert method string into proper enum
             let method = method.trim().to_ascii_uppercase();
             let method = match method.as_ref() {
@@ -137,6 +255,8 @@ impl<'lua> FromLua<'lua> for RequestConfig<'lua> {
                 query,
                 headers,
                 body,
+                form,
+                auth,
                 options,
             });
         };
@@ -152,11 +272,400 @@ impl<'lua> FromLua<'lua> for RequestConfig<'lua> {
     }
 }
 
+// Net client config
+
+#[derive(Debug, Clone, Default)]
+pub struct ClientConfig {
+    pub http2: Option<bool>,
+    pub pool_max_idle_per_host: Option<usize>,
+    pub tcp_keep_alive: Option<Duration>,
+    pub root_certificates: Vec<Vec<u8>>,
+}
+
+impl<'lua> FromLua<'lua> for ClientConfig {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        // Nil means default options, table means custom options
+        if let LuaValue::Nil = value {
+            return Ok(Self::default());
+        } else if let LuaValue::Table(tab) = value {
+            let http2 = match tab.raw_get::<_, Option<bool>>("http2") {
+                Ok(http2) => http2,
+                Err(_) => {
+                    return Err(LuaError::RuntimeError(
+                        "Invalid option value for 'http2' in client config".to_string(),
+                    ))
+                }
+            };
+            let pool_max_idle_per_host = match tab.raw_get::<_, Option<usize>>("poolMaxIdlePerHost")
+            {
+                Ok(max) => max,
+                Err(_) => {
+                    return Err(LuaError::RuntimeError(
+                        "Invalid option value for 'poolMaxIdlePerHost' in client config - \
+                        expected a positive integer"
+                            .to_string(),
+                    ))
+                }
+            };
+            let tcp_keep_alive = match tab.raw_get::<_, Option<f64>>("tcpKeepAlive") {
+                Ok(None) => None,
+                Ok(Some(secs)) if secs.is_finite() && secs > 0.0 => {
+                    Some(Duration::from_secs_f64(secs))
+                }
+                _ => {
+                    return Err(LuaError::RuntimeError(
+                        "Invalid option value for 'tcpKeepAlive' in client config - \
+                        expected a positive number of seconds"
+                            .to_string(),
+                    ))
+                }
+            };
+            let root_certificates =
+                match tab.raw_get::<_, Option<Vec<LuaString>>>("rootCertificates") {
+                    Ok(certs) => certs
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|cert| cert.as_bytes().to_vec())
+                        .collect(),
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'rootCertificates' in client config - \
+                        expected a list of PEM encoded certificates"
+                                .to_string(),
+                        ))
+                    }
+                };
+            return Ok(Self {
+                http2,
+                pool_max_idle_per_host,
+                tcp_keep_alive,
+                root_certificates,
+            });
+        }
+        // Anything else is invalid
+        Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "ClientConfig",
+            message: Some(format!(
+                "Invalid client config - expected table or nil, got {}",
+                value.type_name()
+            )),
+        })
+    }
+}
+
+// Net socket config
+
+#[derive(Debug, Clone, Default)]
+pub struct SocketConfigOptions {
+    pub proxy: Option<TunnelProxy>,
+    pub ip_version: IpVersion,
+    pub ping_interval: Option<Duration>,
+}
+
+impl<'lua> FromLua<'lua> for SocketConfigOptions {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        // Nil means default options, table means custom options
+        if let LuaValue::Nil = value {
+            return Ok(Self::default());
+        } else if let LuaValue::Table(tab) = value {
+            let proxy = match tab.raw_get::<_, Option<String>>("proxy") {
+                Ok(Some(proxy)) => Some(TunnelProxy::parse(proxy)?),
+                Ok(None) => None,
+                Err(_) => {
+                    return Err(LuaError::RuntimeError(
+                        "Invalid option value for 'proxy' in socket config options".to_string(),
+                    ))
+                }
+            };
+            let ip_version = IpVersion::from_lua(tab.raw_get("ipVersion")?, lua)?;
+            let ping_interval = match tab.raw_get::<_, Option<f64>>("pingInterval") {
+                Ok(None) => None,
+                Ok(Some(secs)) if secs.is_finite() && secs > 0.0 => {
+                    Some(Duration::from_secs_f64(secs))
+                }
+                _ => {
+                    return Err(LuaError::RuntimeError(
+                        "Invalid option value for 'pingInterval' in socket config options - \
+                        expected a positive number of seconds"
+                            .to_string(),
+                    ))
+                }
+            };
+            return Ok(Self {
+                proxy,
+                ip_version,
+                ping_interval,
+            });
+        }
+        // Anything else is invalid
+        Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "SocketConfigOptions",
+            message: Some(format!(
+                "Invalid socket config options - expected table or nil, got {}",
+                value.type_name()
+            )),
+        })
+    }
+}
+
 // Net serve config
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServeAddress {
+    Ip(IpAddr),
+    Socket(SocketAddr),
+}
+
+impl ServeAddress {
+    /**
+        Creates the socket address to bind to, using the given port.
+
+        Errors if this is a full socket address with a different port.
+    */
+    pub fn with_port(self, port: u16) -> LuaResult<SocketAddr> {
+        match self {
+            Self::Ip(ip) => Ok(SocketAddr::new(ip, port)),
+            Self::Socket(addr) if addr.port() == port => Ok(addr),
+            Self::Socket(addr) => Err(LuaError::RuntimeError(format!(
+                "Invalid address '{addr}' in serve config - port {} does not match the given port {port}",
+                addr.port()
+            ))),
+        }
+    }
+}
+
+impl Default for ServeAddress {
+    fn default() -> Self {
+        Self::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    }
+}
+
+impl<'lua> FromLua<'lua> for ServeAddress {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let s = match &value {
+            LuaValue::String(s) => s.to_str()?.trim(),
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "ServeAddress",
+                    message: Some(format!(
+                        "Invalid address in serve config - expected string, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        if s.eq_ignore_ascii_case("localhost") {
+            Ok(Self::default())
+        } else if let Ok(ip) = s.parse::<IpAddr>() {
+            Ok(Self::Ip(ip))
+        } else if let Ok(addr) = s.parse::<SocketAddr>() {
+            Ok(Self::Socket(addr))
+        } else {
+            Err(LuaError::RuntimeError(format!(
+                "Invalid address '{s}' in serve config - expected an ip address such as \
+                '0.0.0.0' or '::1', or a socket address such as '0.0.0.0:8080'"
+            )))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ServeTlsConfig {
+    Files {
+        cert_path: PathBuf,
+        key_path: PathBuf,
+    },
+    #[cfg(feature = "acme")]
+    Acme(ServeAcmeConfig),
+}
+
+impl<'lua> FromLua<'lua> for ServeTlsConfig {
+    #[cfg_attr(not(feature = "acme"), allow(unused_variables))]
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::Table(tab) = &value {
+            let acme = tab.raw_get::<_, LuaValue>("acme")?;
+            if !acme.is_nil() {
+                if tab.contains_key("certPath")? || tab.contains_key("keyPath")? {
+                    return Err(LuaError::RuntimeError(
+                        "Serve tls config can not contain both 'acme' and 'certPath' or 'keyPath'"
+                            .to_string(),
+                    ));
+                }
+                #[cfg(feature = "acme")]
+                return Ok(Self::Acme(ServeAcmeConfig::from_lua(acme, lua)?));
+                #[cfg(not(feature = "acme"))]
+                return Err(LuaError::RuntimeError(
+                    "Lune was built without support for acme certificates, \
+                    which must be enabled using a feature"
+                        .to_string(),
+                ));
+            }
+            let get_path = |name: &str| match tab.raw_get::<_, Option<String>>(name) {
+                Ok(Some(path)) => Ok(PathBuf::from(path)),
+                _ => Err(LuaError::RuntimeError(format!(
+                    "Missing or invalid '{name}' in serve tls config"
+                ))),
+            };
+            return Ok(Self::Files {
+                cert_path: get_path("certPath")?,
+                key_path: get_path("keyPath")?,
+            });
+        }
+        Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "ServeTlsConfig",
+            message: Some(format!(
+                "Invalid tls config - expected table, got {}",
+                value.type_name()
+            )),
+        })
+    }
+}
+
+/**
+    Configuration for getting certificates automatically using ACME.
+*/
+#[cfg(feature = "acme")]
+#[derive(Debug, Clone)]
+pub struct ServeAcmeConfig {
+    pub domains: Vec<String>,
+    pub cache_dir: PathBuf,
+    pub contact: Vec<String>,
+    pub directory_url: Option<String>,
+}
+
+#[cfg(feature = "acme")]
+impl<'lua> FromLua<'lua> for ServeAcmeConfig {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::Table(tab) = &value {
+            let domains = match tab.raw_get::<_, Option<Vec<String>>>("domains") {
+                Ok(Some(domains)) if !domains.is_empty() => domains,
+                _ => {
+                    return Err(LuaError::RuntimeError(
+                        "Missing or invalid 'domains' in serve acme config - \
+                        expected a list of at least one domain name"
+                            .to_string(),
+                    ))
+                }
+            };
+            let cache_dir = match tab.raw_get::<_, Option<String>>("cacheDir") {
+                Ok(Some(path)) => PathBuf::from(path),
+                _ => {
+                    return Err(LuaError::RuntimeError(
+                        "Missing or invalid 'cacheDir' in serve acme config".to_string(),
+                    ))
+                }
+            };
+            let contact = match tab.raw_get::<_, Option<Vec<String>>>("contact") {
+                Ok(contact) => contact.unwrap_or_default(),
+                Err(_) => {
+                    return Err(LuaError::RuntimeError(
+                        "Invalid 'contact' in serve acme config - expected a list of strings"
+                            .to_string(),
+                    ))
+                }
+            };
+            let directory_url = match tab.raw_get::<_, Option<String>>("directoryUrl") {
+                Ok(url) => url,
+                Err(_) => {
+                    return Err(LuaError::RuntimeError(
+                        "Invalid 'directoryUrl' in serve acme config - expected string".to_string(),
+                    ))
+                }
+            };
+            return Ok(Self {
+                domains,
+                cache_dir,
+                contact,
+                directory_url,
+            });
+        }
+        Err(LuaError::FromLuaConversionError {
+            from: value.type_name(),
+            to: "ServeAcmeConfig",
+            message: Some(format!(
+                "Invalid acme config - expected table, got {}",
+                value.type_name()
+            )),
+        })
+    }
+}
+
+fn get_handler_timeout(tab: &LuaTable, key: &str) -> LuaResult<Option<Duration>> {
+    match tab.raw_get::<_, Option<f64>>(key) {
+        Ok(None) => Ok(None),
+        Ok(Some(secs)) if secs.is_finite() && secs > 0.0 => Ok(Some(Duration::from_secs_f64(secs))),
+        _ => Err(LuaError::RuntimeError(format!(
+            "Invalid option value for '{key}' in serve config - \
+            expected a positive number of seconds"
+        ))),
+    }
+}
+
+/**
+    A handler for a route in `ServeConfig.routes`, given either as
+    a function, or as a table with a handler and a timeout for it.
+*/
+pub struct ServeRoute<'a> {
+    pub handler: LuaFunction<'a>,
+    pub timeout: Option<Duration>,
+}
+
+impl<'lua> FromLua<'lua> for ServeRoute<'lua> {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Function(f) => Ok(Self {
+                handler: f.clone(),
+                timeout: None,
+            }),
+            LuaValue::Table(t) => match t.raw_get::<_, Option<LuaFunction>>("handler")? {
+                Some(handler) => Ok(Self {
+                    handler,
+                    timeout: get_handler_timeout(t, "timeout")?,
+                }),
+                None => Err(LuaError::RuntimeError(
+                    "Invalid route - missing 'handler' function".to_string(),
+                )),
+            },
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ServeRoute",
+                message: Some(format!(
+                    "Invalid route - expected function or table, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
 pub struct ServeConfig<'a> {
+    pub address: ServeAddress,
+    pub tls: Option<ServeTlsConfig>,
     pub handle_request: LuaFunction<'a>,
     pub handle_web_socket: Option<LuaFunction<'a>>,
+    pub routes: Vec<(RoutePattern, ServeRoute<'a>)>,
+    pub handler_timeout: Option<Duration>,
+    pub timeout_response: Option<LuaValue<'a>>,
+}
+
+impl<'lua> ServeConfig<'lua> {
+    /**
+        Creates the response to send when a request handler has timed out.
+    */
+    pub fn create_timeout_response(&self, lua: &'lua Lua) -> LuaResult<NetServeResponse> {
+        match &self.timeout_response {
+            Some(value) => NetServeResponse::from_lua(value.clone(), lua),
+            None => Ok(NetServeResponse::new(
+                503,
+                HashMap::new(),
+                "Service Unavailable",
+            )),
+        }
+    }
 }
 
 impl<'lua> FromLua<'lua> for ServeConfig<'lua> {
@@ -164,33 +673,80 @@ impl<'lua> FromLua<'lua> for ServeConfig<'lua> {
         let message = match &value {
             LuaValue::Function(f) => {
                 return Ok(ServeConfig {
+                    address: ServeAddress::default(),
+                    tls: None,
                     handle_request: f.clone(),
                     handle_web_socket: None,
+                    routes: Vec::new(),
+                    handler_timeout: None,
+                    timeout_response: None,
                 })
             }
             LuaValue::Table(t) => {
                 let handle_request: Option<LuaFunction> = t.raw_get("handleRequest")?;
                 let handle_web_socket: Option<LuaFunction> = t.raw_get("handleWebSocket")?;
-                if handle_request.is_some() || handle_web_socket.is_some() {
+                let address = match t.raw_get::<_, LuaValue>("address")? {
+                    LuaValue::Nil => ServeAddress::default(),
+                    value => ServeAddress::from_lua(value, lua)?,
+                };
+                let tls = match t.raw_get::<_, LuaValue>("tls")? {
+                    LuaValue::Nil => None,
+                    value => Some(ServeTlsConfig::from_lua(value, lua)?),
+                };
+                let mut routes = Vec::new();
+                if let Some(route_table) = t.raw_get::<_, Option<LuaTable>>("routes")? {
+                    for pair in route_table.pairs::<String, ServeRoute>() {
+                        let (pattern, handler) = pair?;
+                        routes.push((RoutePattern::parse(&pattern)?, handler));
+                    }
+                    sort_routes(&mut routes);
+                }
+                let handler_timeout = get_handler_timeout(t, "handlerTimeout")?;
+                let timeout_response = match t.raw_get::<_, LuaValue>("timeoutResponse")? {
+                    LuaValue::Nil => None,
+                    value => {
+                        // NOTE: We convert the response once here to validate it, since
+                        // it would otherwise only error once a handler has timed out
+                        NetServeResponse::from_lua(value.clone(), lua)?;
+                        Some(value)
+                    }
+                };
+                if handle_request.is_some() || handle_web_socket.is_some() || !routes.is_empty() {
+                    // NOTE: Requests that match none of the routes fall through to
+                    // handleRequest, which defaults to a 404 when routes are given
+                    let default_chunk = if routes.is_empty() {
+                        r#"
+                        return {
+                            status = 426,
+                            body = "Upgrade Required",
+                            headers = {
+                                Upgrade = "websocket",
+                            },
+                        }
+                        "#
+                    } else {
+                        r#"
+                        return {
+                            status = 404,
+                            body = "Not Found",
+                        }
+                        "#
+                    };
                     return Ok(ServeConfig {
+                        address,
+                        tls,
                         handle_request: handle_request.unwrap_or_else(|| {
-                            let chunk = r#"
-                            return {
-                                status = 426,
-                                body = "Upgrade Required",
-                                headers = {
-                                    Upgrade = "websocket",
-                                },
-                            }
-                            "#;
-                            lua.load(chunk)
+                            lua.load(default_chunk)
                                 .into_function()
                                 .expect("Failed to create default http responder function")
                         }),
                         handle_web_socket,
+                        routes,
+                        handler_timeout,
+                        timeout_response,
                     });
                 } else {
-                    Some("Missing handleRequest and / or handleWebSocket".to_string())
+                    Some("Missing handleRequest, handleWebSocket and / or routes".to_string())
                 }
             }
             _ => None,
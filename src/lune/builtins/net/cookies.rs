@@ -0,0 +1,338 @@
+use std::{
+    cmp::Reverse,
+    net::IpAddr,
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use cookie::Cookie;
+use hyper::http::HeaderValue;
+use mlua::prelude::*;
+use reqwest::{cookie::CookieStore, Url};
+
+use crate::lune::util::TableBuilder;
+
+/**
+    A single cookie stored in a [`NetCookieJar`].
+*/
+#[derive(Debug, Clone)]
+pub struct StoredCookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    http_only: bool,
+    expires: Option<SystemTime>,
+}
+
+impl StoredCookie {
+    /**
+        Parses a `Set-Cookie` header value that was received in a response from `url`.
+
+        Returns `None` if the cookie is invalid or may not be set by the given url,
+        otherwise returns the cookie, which may already have expired if the server
+        wants to remove it from the jar.
+    */
+    fn parse(header: &str, url: &Url) -> Option<Self> {
+        let cookie = Cookie::parse(header).ok()?;
+        let host = url.host_str()?.to_ascii_lowercase();
+
+        let (domain, host_only) = match cookie.domain() {
+            Some(domain) if !domain.is_empty() => {
+                let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+                if !domain_matches(&host, &domain) {
+                    return None;
+                }
+                (domain, false)
+            }
+            _ => (host, true),
+        };
+
+        let path = match cookie.path() {
+            Some(path) if path.starts_with('/') => path.to_string(),
+            _ => default_path(url),
+        };
+
+        // NOTE: Max-Age takes precedence over Expires, see RFC 6265 section 5.3
+        let expires = match (cookie.max_age(), cookie.expires_datetime()) {
+            (Some(max_age), _) => Some(match Duration::try_from(max_age) {
+                Ok(max_age) => SystemTime::now() + max_age,
+                Err(_) => UNIX_EPOCH,
+            }),
+            (None, Some(expires)) => Some(SystemTime::from(expires)),
+            (None, None) => None,
+        };
+
+        let secure = cookie.secure().unwrap_or(false);
+        if secure && !is_secure_url(url) {
+            return None;
+        }
+
+        Some(Self {
+            name: cookie.name().to_string(),
+            value: cookie.value().to_string(),
+            domain,
+            host_only,
+            path,
+            secure,
+            http_only: cookie.http_only().unwrap_or(false),
+            expires,
+        })
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        matches!(self.expires, Some(expires) if expires <= now)
+    }
+
+    fn is_same_cookie(&self, other: &Self) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+
+    fn matches_url(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(&host, &self.domain)
+        };
+        domain_ok && path_matches(url.path(), &self.path) && (!self.secure || is_secure_url(url))
+    }
+
+    fn into_lua_table(self, lua: &Lua) -> LuaResult<LuaTable> {
+        let expires = self
+            .expires
+            .map(|expires| match expires.duration_since(UNIX_EPOCH) {
+                Ok(duration) => duration.as_secs_f64(),
+                Err(_) => 0.0,
+            });
+        TableBuilder::new(lua)?
+            .with_value("name", self.name)?
+            .with_value("value", self.value)?
+            .with_value("domain", self.domain)?
+            .with_value("path", self.path)?
+            .with_value("secure", self.secure)?
+            .with_value("httpOnly", self.http_only)?
+            .with_value("expires", expires)?
+            .build_readonly()
+    }
+}
+
+/**
+    A cookie jar shared by all requests sent using the same [`super::client::NetClient`].
+
+    Unlike the cookie jar that comes with reqwest, this jar
+    can list all of its cookies and have them cleared.
+*/
+#[derive(Debug, Default)]
+pub struct NetCookieJar {
+    cookies: RwLock<Vec<StoredCookie>>,
+}
+
+impl NetCookieJar {
+    /**
+        Gets all cookies that have not yet expired, optionally
+        only those that would be sent in a request to `url`.
+    */
+    pub fn list(&self, url: Option<&Url>) -> Vec<StoredCookie> {
+        let now = SystemTime::now();
+        let mut cookies = self
+            .cookies
+            .read()
+            .expect("Failed to lock cookie jar")
+            .iter()
+            .filter(|cookie| !cookie.is_expired(now))
+            .filter(|cookie| url.is_none_or(|url| cookie.matches_url(url)))
+            .cloned()
+            .collect::<Vec<_>>();
+        // NOTE: Cookies with longer paths should be sent first, see RFC 6265 section 5.4
+        cookies.sort_by_key(|cookie| Reverse(cookie.path.len()));
+        cookies
+    }
+
+    /**
+        Removes all cookies, or only the cookies for the given domain and its subdomains.
+
+        Returns the number of cookies that were removed.
+    */
+    pub fn clear(&self, domain: Option<&str>) -> usize {
+        let mut cookies = self.cookies.write().expect("Failed to lock cookie jar");
+        let before = cookies.len();
+        match domain {
+            Some(domain) => {
+                let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+                cookies.retain(|cookie| !domain_matches(&cookie.domain, &domain));
+            }
+            None => cookies.clear(),
+        }
+        before - cookies.len()
+    }
+
+    fn store(&self, cookie: StoredCookie) {
+        let now = SystemTime::now();
+        let mut cookies = self.cookies.write().expect("Failed to lock cookie jar");
+        cookies.retain(|existing| !existing.is_same_cookie(&cookie) && !existing.is_expired(now));
+        if !cookie.is_expired(now) {
+            cookies.push(cookie);
+        }
+    }
+}
+
+impl CookieStore for NetCookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        for header in cookie_headers {
+            let cookie = header
+                .to_str()
+                .ok()
+                .and_then(|header| StoredCookie::parse(header, url));
+            if let Some(cookie) = cookie {
+                self.store(cookie);
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let header = self
+            .list(Some(url))
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if header.is_empty() {
+            None
+        } else {
+            HeaderValue::from_str(&header).ok()
+        }
+    }
+}
+
+/**
+    Creates the `net.cookies` table, for inspecting and clearing the given cookie jar.
+*/
+pub fn create_cookies_table(lua: &'static Lua, jar: Arc<NetCookieJar>) -> LuaResult<LuaTable> {
+    let jar_list = Arc::clone(&jar);
+    TableBuilder::new(lua)?
+        .with_function("list", move |lua, url: Option<String>| {
+            let url =
+                match url {
+                    Some(url) => Some(Url::parse(&url).map_err(|e| {
+                        LuaError::RuntimeError(format!("Invalid url '{url}' - {e}"))
+                    })?),
+                    None => None,
+                };
+            let cookies = lua.create_table()?;
+            for cookie in jar_list.list(url.as_ref()) {
+                cookies.raw_set(cookies.raw_len() + 1, cookie.into_lua_table(lua)?)?;
+            }
+            Ok(cookies)
+        })?
+        .with_function("clear", move |_, domain: Option<String>| {
+            Ok(jar.clear(domain.as_deref()))
+        })?
+        .build_readonly()
+}
+
+fn is_secure_url(url: &Url) -> bool {
+    matches!(url.scheme(), "https" | "wss")
+}
+
+/**
+    Checks if `host` domain-matches `domain`, see RFC 6265 section 5.1.3.
+*/
+fn domain_matches(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    // NOTE: Ip addresses only ever match themselves, never as subdomains
+    host.parse::<IpAddr>().is_err()
+        && host.len() > domain.len()
+        && host.ends_with(domain)
+        && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+}
+
+/**
+    Checks if `request_path` path-matches `cookie_path`, see RFC 6265 section 5.1.4.
+*/
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/')
+                || request_path.as_bytes().get(cookie_path.len()) == Some(&b'/')))
+}
+
+/**
+    Gets the default path for a cookie without a path, see RFC 6265 section 5.1.4.
+*/
+fn default_path(url: &Url) -> String {
+    let path = url.path();
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => path[..index].to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn domain_and_path_matching() {
+        assert!(domain_matches("www.example.com", "example.com"));
+        assert!(!domain_matches("badexample.com", "example.com"));
+        assert!(!domain_matches("1.2.3.4", "2.3.4"));
+        assert!(path_matches("/docs/web", "/docs"));
+        assert!(path_matches("/docs/", "/docs/"));
+        assert!(!path_matches("/documents", "/docs"));
+        assert_eq!(default_path(&url("https://example.com/a/b")), "/a");
+        assert_eq!(default_path(&url("https://example.com/a")), "/");
+    }
+
+    #[test]
+    fn cookies_are_stored_and_sent() {
+        let jar = NetCookieJar::default();
+        let origin = url("https://www.example.com/login");
+        let headers = [
+            HeaderValue::from_static("session=abc; Path=/; Secure; HttpOnly"),
+            HeaderValue::from_static("shared=1; Domain=example.com; Path=/"),
+            HeaderValue::from_static("other=2; Domain=other.com"),
+        ];
+        jar.set_cookies(&mut headers.iter(), &origin);
+
+        let header = jar.cookies(&url("https://www.example.com/")).unwrap();
+        assert_eq!(header.to_str().unwrap(), "session=abc; shared=1");
+
+        // Host only cookies and secure cookies should not be sent elsewhere
+        let header = jar.cookies(&url("http://api.example.com/")).unwrap();
+        assert_eq!(header.to_str().unwrap(), "shared=1");
+        assert!(jar.cookies(&url("https://other.com/")).is_none());
+    }
+
+    #[test]
+    fn cookies_are_replaced_and_expired() {
+        let jar = NetCookieJar::default();
+        let origin = url("https://example.com/");
+        let set = |header: &'static str| {
+            jar.set_cookies(&mut [HeaderValue::from_static(header)].iter(), &origin);
+        };
+
+        set("a=1");
+        set("a=2");
+        assert_eq!(jar.list(None).len(), 1);
+        assert_eq!(jar.list(None)[0].value, "2");
+
+        set("a=3; Max-Age=0");
+        assert!(jar.list(None).is_empty());
+
+        set("b=1");
+        set("c=1; Domain=example.com");
+        assert_eq!(jar.clear(Some("example.com")), 2);
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+
+use mlua::prelude::*;
+use tokio::net::lookup_host;
+
+use crate::lune::util::TableBuilder;
+
+use super::util::strip_ipv6_brackets;
+
+#[cfg(feature = "dns-cache")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "dns-cache")]
+use hickory_resolver::TokioAsyncResolver;
+
+/**
+    Resolves host names for the net client, caching results in-process for as long as their TTLs allow.
+
+    Overrides take precedence over any lookups, which lets tests
+    point host names at local servers without touching the system.
+
+    Without the `dns-cache` feature, lookups always use the system resolver without any caching.
+*/
+#[derive(Debug, Default)]
+pub struct NetDns {
+    #[cfg(feature = "dns-cache")]
+    resolver: OnceLock<Option<TokioAsyncResolver>>,
+    overrides: Mutex<HashMap<String, Vec<IpAddr>>>,
+}
+
+impl NetDns {
+    /**
+        Gets the caching resolver, which uses the system configuration.
+
+        Returns `None` if the system configuration could not be read, in which
+        case lookups fall back to the system resolver without any caching.
+    */
+    #[cfg(feature = "dns-cache")]
+    fn resolver(&self) -> Option<&TokioAsyncResolver> {
+        self.resolver
+            .get_or_init(|| TokioAsyncResolver::tokio_from_system_conf().ok())
+            .as_ref()
+    }
+
+    /**
+        Looks up all addresses for the given host, which may also be an ip address.
+    */
+    pub async fn lookup(&self, host: &str) -> io::Result<Vec<IpAddr>> {
+        let host = normalize_host(host);
+        if let Some(ips) = self
+            .overrides
+            .lock()
+            .expect("Failed to lock dns overrides")
+            .get(&host)
+        {
+            return Ok(ips.clone());
+        }
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+        #[cfg(feature = "dns-cache")]
+        if let Some(resolver) = self.resolver() {
+            let lookup = resolver
+                .lookup_ip(host.as_str())
+                .await
+                .map_err(io::Error::other)?;
+            return Ok(lookup.iter().collect());
+        }
+        let addrs = lookup_host((host.as_str(), 0)).await?;
+        Ok(addrs.map(|addr| addr.ip()).collect())
+    }
+
+    pub fn set_override(&self, host: &str, ips: Vec<IpAddr>) {
+        self.overrides
+            .lock()
+            .expect("Failed to lock dns overrides")
+            .insert(normalize_host(host), ips);
+    }
+
+    pub fn remove_override(&self, host: &str) -> bool {
+        self.overrides
+            .lock()
+            .expect("Failed to lock dns overrides")
+            .remove(&normalize_host(host))
+            .is_some()
+    }
+
+    pub fn clear_overrides(&self) {
+        self.overrides
+            .lock()
+            .expect("Failed to lock dns overrides")
+            .clear();
+    }
+
+    pub fn clear_cache(&self) {
+        #[cfg(feature = "dns-cache")]
+        if let Some(resolver) = self.resolver.get().and_then(Option::as_ref) {
+            resolver.clear_cache();
+        }
+    }
+}
+
+/**
+    Normalizes a host name so that equivalent names share overrides,
+    host names are case insensitive and may end with a root dot.
+*/
+fn normalize_host(host: &str) -> String {
+    strip_ipv6_brackets(host)
+        .trim_end_matches('.')
+        .to_ascii_lowercase()
+}
+
+fn parse_ips(host: &str, value: LuaValue) -> LuaResult<Vec<IpAddr>> {
+    let strings = match value {
+        LuaValue::String(s) => vec![s.to_str()?.to_string()],
+        LuaValue::Table(t) => t
+            .sequence_values::<String>()
+            .collect::<LuaResult<Vec<_>>>()?,
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid dns override for '{host}' - expected string, table or nil, got {}",
+                value.type_name()
+            )))
+        }
+    };
+    if strings.is_empty() {
+        return Err(LuaError::RuntimeError(format!(
+            "Invalid dns override for '{host}' - expected at least one ip address"
+        )));
+    }
+    strings
+        .iter()
+        .map(|s| {
+            normalize_host(s).parse::<IpAddr>().map_err(|_| {
+                LuaError::RuntimeError(format!(
+                    "Invalid dns override for '{host}' - '{s}' is not an ip address"
+                ))
+            })
+        })
+        .collect()
+}
+
+/**
+    Creates the `net.dns` table, for looking up and overriding host names.
+*/
+pub fn create_dns_table(lua: &'static Lua, dns: Arc<NetDns>) -> LuaResult<LuaTable> {
+    let dns_lookup = Arc::clone(&dns);
+    let dns_set_override = Arc::clone(&dns);
+    let dns_clear_overrides = Arc::clone(&dns);
+    TableBuilder::new(lua)?
+        .with_async_function("lookup", move |_, host: String| {
+            let dns = Arc::clone(&dns_lookup);
+            async move {
+                let ips = dns
+                    .lookup(&host)
+                    .await
+                    .into_lua_err()
+                    .with_context(|_| format!("Failed to resolve host '{host}'"))?;
+                Ok(ips.iter().map(ToString::to_string).collect::<Vec<_>>())
+            }
+        })?
+        .with_function(
+            "setOverride",
+            move |_, (host, value): (String, LuaValue)| {
+                match value {
+                    LuaValue::Nil => {
+                        dns_set_override.remove_override(&host);
+                    }
+                    value => dns_set_override.set_override(&host, parse_ips(&host, value)?),
+                }
+                Ok(())
+            },
+        )?
+        .with_function("clearOverrides", move |_, _: ()| {
+            dns_clear_overrides.clear_overrides();
+            Ok(())
+        })?
+        .with_function("clearCache", move |_, _: ()| {
+            dns.clear_cache();
+            Ok(())
+        })?
+        .build_readonly()
+}
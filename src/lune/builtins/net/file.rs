@@ -0,0 +1,317 @@
+use std::{
+    io::{ErrorKind, SeekFrom},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use mlua::prelude::*;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+
+use crate::lune::util::TableBuilder;
+
+/**
+    A range of bytes in a file, where both the start and end are inclusive.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+impl ByteRange {
+    fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RangeRequest {
+    Satisfiable(ByteRange),
+    Unsatisfiable,
+}
+
+/**
+    Parses a `Range` header for a file with the given length, see RFC 9110 section 14.
+
+    Returns `None` if the header is invalid or asks for multiple ranges,
+    in which case the header should be ignored and the entire file sent.
+*/
+fn parse_range(header: &str, len: u64) -> Option<RangeRequest> {
+    let spec = header.trim().strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+    let range = if start.is_empty() {
+        // Suffix range, the last n bytes of the file
+        let suffix = end.parse::<u64>().ok()?;
+        if suffix == 0 || len == 0 {
+            return Some(RangeRequest::Unsatisfiable);
+        }
+        ByteRange {
+            start: len.saturating_sub(suffix),
+            end: len - 1,
+        }
+    } else {
+        let start = start.parse::<u64>().ok()?;
+        let end = if end.is_empty() {
+            u64::MAX
+        } else {
+            end.parse::<u64>().ok()?
+        };
+        if end < start {
+            return None;
+        }
+        if start >= len {
+            return Some(RangeRequest::Unsatisfiable);
+        }
+        ByteRange {
+            start,
+            end: end.min(len - 1),
+        }
+    };
+    Some(RangeRequest::Satisfiable(range))
+}
+
+/**
+    Checks if an `If-None-Match` header matches the given entity tag,
+    using the weak comparison from RFC 9110 section 8.8.3.2.
+*/
+fn etag_matches_weak(header: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/**
+    Headers from a request that are used for conditional and range requests.
+*/
+#[derive(Debug, Default)]
+struct FileRequestHeaders {
+    method: String,
+    range: Option<String>,
+    if_range: Option<String>,
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+impl FileRequestHeaders {
+    fn from_request(request: Option<LuaTable>) -> LuaResult<Self> {
+        let request = match request {
+            Some(request) => request,
+            None => {
+                return Ok(Self {
+                    method: "GET".to_string(),
+                    ..Default::default()
+                })
+            }
+        };
+        let method = request
+            .raw_get::<_, Option<String>>("method")?
+            .unwrap_or_else(|| "GET".to_string())
+            .to_ascii_uppercase();
+        let headers = match request.raw_get::<_, Option<LuaTable>>("headers")? {
+            Some(headers) => headers,
+            None => {
+                return Ok(Self {
+                    method,
+                    ..Default::default()
+                })
+            }
+        };
+        // NOTE: Header names in requests given to net.serve handlers are always
+        // lowercase, but users may also create their own request tables
+        let mut parsed = Self {
+            method,
+            ..Default::default()
+        };
+        for pair in headers.pairs::<String, String>() {
+            let (name, value) = pair?;
+            let slot = match name.to_ascii_lowercase().as_str() {
+                "range" => &mut parsed.range,
+                "if-range" => &mut parsed.if_range,
+                "if-none-match" => &mut parsed.if_none_match,
+                "if-modified-since" => &mut parsed.if_modified_since,
+                _ => continue,
+            };
+            *slot = Some(value);
+        }
+        Ok(parsed)
+    }
+}
+
+pub async fn net_file_response<'lua>(
+    lua: &'lua Lua,
+    (path, request): (String, Option<LuaTable<'lua>>),
+) -> LuaResult<LuaTable<'lua>> {
+    let request = FileRequestHeaders::from_request(request)?;
+
+    let meta = match fs::metadata(&path).await {
+        Ok(meta) if meta.is_file() => meta,
+        Ok(_) => return create_response(lua, 404, Vec::new(), "Not Found"),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return create_response(lua, 404, Vec::new(), "Not Found")
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if request.method != "GET" && request.method != "HEAD" {
+        return create_response(
+            lua,
+            405,
+            vec![("Allow", "GET, HEAD".to_string())],
+            "Method Not Allowed",
+        );
+    }
+
+    // Create validators for the file, note that http dates only have
+    // second precision, so we truncate the modification time to match
+    let len = meta.len();
+    let modified_secs = meta
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs());
+    let modified = modified_secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+    let etag = format!("\"{len:x}-{:x}\"", modified_secs.unwrap_or_default());
+    let last_modified = modified.map(httpdate::fmt_http_date);
+
+    let mut headers = vec![("ETag", etag.clone())];
+    if let Some(last_modified) = &last_modified {
+        headers.push(("Last-Modified", last_modified.clone()));
+    }
+
+    // Check if the client already has the file, If-None-Match takes
+    // precedence over If-Modified-Since, see RFC 9110 section 13.2.2
+    let not_modified = match (&request.if_none_match, &request.if_modified_since) {
+        (Some(if_none_match), _) => etag_matches_weak(if_none_match, &etag),
+        (None, Some(since)) => match (modified, httpdate::parse_http_date(since)) {
+            (Some(modified), Ok(since)) => modified <= since,
+            _ => false,
+        },
+        (None, None) => false,
+    };
+    if not_modified {
+        return create_response(lua, 304, headers, "");
+    }
+
+    headers.push(("Accept-Ranges", "bytes".to_string()));
+    headers.push((
+        "Content-Type",
+        mime_guess::from_path(&path)
+            .first_or_octet_stream()
+            .to_string(),
+    ));
+
+    // Ranges are only used if the file has not changed since
+    // the client got its validator from the If-Range header
+    let range_allowed = match &request.if_range {
+        Some(if_range) if if_range.trim().starts_with('"') => if_range.trim() == etag,
+        Some(if_range) => match (modified, httpdate::parse_http_date(if_range)) {
+            (Some(modified), Ok(date)) => modified == date,
+            _ => false,
+        },
+        None => true,
+    };
+    let range = match &request.range {
+        Some(range) if range_allowed && request.method == "GET" => parse_range(range, len),
+        _ => None,
+    };
+
+    let (status, range) = match range {
+        Some(RangeRequest::Unsatisfiable) => {
+            headers.push(("Content-Range", format!("bytes */{len}")));
+            return create_response(lua, 416, headers, "Range Not Satisfiable");
+        }
+        Some(RangeRequest::Satisfiable(range)) => {
+            headers.push((
+                "Content-Range",
+                format!("bytes {}-{}/{len}", range.start, range.end),
+            ));
+            (206, range)
+        }
+        None => (
+            200,
+            ByteRange {
+                start: 0,
+                end: len.saturating_sub(1),
+            },
+        ),
+    };
+    let body_len = if len == 0 { 0 } else { range.len() };
+    headers.push(("Content-Length", body_len.to_string()));
+
+    let body = if request.method == "HEAD" || body_len == 0 {
+        Vec::new()
+    } else {
+        let mut file = fs::File::open(&path).await?;
+        file.seek(SeekFrom::Start(range.start)).await?;
+        let mut body = Vec::new();
+        file.take(body_len).read_to_end(&mut body).await?;
+        body
+    };
+
+    create_response(lua, status, headers, body)
+}
+
+fn create_response<'lua>(
+    lua: &'lua Lua,
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: impl AsRef<[u8]>,
+) -> LuaResult<LuaTable<'lua>> {
+    let header_table = lua.create_table_with_capacity(0, headers.len())?;
+    for (name, value) in headers {
+        header_table.raw_set(name, value)?;
+    }
+    TableBuilder::new(lua)?
+        .with_value("status", status)?
+        .with_value("headers", header_table)?
+        .with_value("body", lua.create_string(body)?)?
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, end: u64) -> Option<RangeRequest> {
+        Some(RangeRequest::Satisfiable(ByteRange { start, end }))
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_range("bytes=0-9", 100), range(0, 9));
+        assert_eq!(parse_range("bytes=90-", 100), range(90, 99));
+        assert_eq!(parse_range("bytes=90-200", 100), range(90, 99));
+        assert_eq!(parse_range("bytes=-10", 100), range(90, 99));
+        assert_eq!(parse_range("bytes=-200", 100), range(0, 99));
+        assert_eq!(
+            parse_range("bytes=100-", 100),
+            Some(RangeRequest::Unsatisfiable)
+        );
+        assert_eq!(parse_range("bytes=9-0", 100), None);
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), None);
+        assert_eq!(parse_range("items=0-1", 100), None);
+    }
+
+    #[test]
+    fn etags() {
+        assert!(etag_matches_weak("\"a\"", "\"a\""));
+        assert!(etag_matches_weak("W/\"a\", \"b\"", "\"a\""));
+        assert!(etag_matches_weak("*", "\"a\""));
+        assert!(!etag_matches_weak("\"b\"", "\"a\""));
+    }
+
+    #[test]
+    fn validators_use_second_precision() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let formatted = httpdate::fmt_http_date(time);
+        assert_eq!(httpdate::parse_http_date(&formatted).ok(), Some(time));
+    }
+}
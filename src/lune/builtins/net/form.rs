@@ -0,0 +1,196 @@
+use std::path::PathBuf;
+
+use mlua::prelude::*;
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::fs;
+
+const DEFAULT_FILE_CONTENT_TYPE: &str = "application/octet-stream";
+
+#[derive(Debug, Clone)]
+enum FormPartSource {
+    Data(Vec<u8>),
+    Path(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+struct FormPart {
+    name: String,
+    source: FormPartSource,
+    file_name: Option<String>,
+    content_type: Option<String>,
+}
+
+impl FormPart {
+    fn from_lua_table(name: Option<String>, tab: LuaTable) -> LuaResult<Self> {
+        let name = match name {
+            Some(name) => name,
+            None => tab.raw_get::<_, Option<String>>("name")?.ok_or_else(|| {
+                LuaError::RuntimeError("Missing 'name' for part in request form".to_string())
+            })?,
+        };
+
+        let data = tab.raw_get::<_, Option<LuaString>>("data")?;
+        let path = tab.raw_get::<_, Option<String>>("path")?;
+        let source = match (data, path) {
+            (Some(data), None) => FormPartSource::Data(data.as_bytes().to_vec()),
+            (None, Some(path)) => FormPartSource::Path(PathBuf::from(path)),
+            _ => {
+                return Err(LuaError::RuntimeError(format!(
+                "Invalid part '{name}' in request form - expected exactly one of 'data' or 'path'"
+            )))
+            }
+        };
+
+        // NOTE: Files should always have a file name, otherwise
+        // most servers will treat the part as a plain text field
+        let file_name = match tab.raw_get::<_, Option<String>>("filename")? {
+            Some(file_name) => Some(file_name),
+            None => match &source {
+                FormPartSource::Path(path) => path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string()),
+                FormPartSource::Data(_) => None,
+            },
+        };
+        let content_type = match tab.raw_get::<_, Option<String>>("contentType")? {
+            Some(content_type) => Some(content_type),
+            None if file_name.is_some() => Some(DEFAULT_FILE_CONTENT_TYPE.to_string()),
+            None => None,
+        };
+
+        Ok(Self {
+            name,
+            source,
+            file_name,
+            content_type,
+        })
+    }
+}
+
+/**
+    A form that will be sent as a `multipart/form-data` request body.
+*/
+#[derive(Debug, Clone)]
+pub struct RequestForm {
+    parts: Vec<FormPart>,
+}
+
+impl RequestForm {
+    /**
+        Serializes the form into a request body, reading any files it contains.
+
+        Returns the content type to use for the request, which
+        contains the generated boundary, along with the body.
+    */
+    pub async fn into_body(self) -> LuaResult<(String, Vec<u8>)> {
+        let boundary = create_boundary()?;
+
+        let mut body = Vec::new();
+        for part in self.parts {
+            let contents = match part.source {
+                FormPartSource::Data(data) => data,
+                FormPartSource::Path(path) => fs::read(&path).await.map_err(|e| {
+                    LuaError::RuntimeError(format!(
+                        "Failed to read file '{}' for part '{}' in request form\n> {e}",
+                        path.display(),
+                        part.name
+                    ))
+                })?,
+            };
+
+            body.extend_from_slice(format!("--{boundary}\r\n").as_bytes());
+            let mut disposition = format!(
+                "Content-Disposition: form-data; name=\"{}\"",
+                escape_quoted(&part.name)
+            );
+            if let Some(file_name) = &part.file_name {
+                disposition.push_str(&format!("; filename=\"{}\"", escape_quoted(file_name)));
+            }
+            body.extend_from_slice(disposition.as_bytes());
+            body.extend_from_slice(b"\r\n");
+            if let Some(content_type) = &part.content_type {
+                body.extend_from_slice(format!("Content-Type: {content_type}\r\n").as_bytes());
+            }
+            body.extend_from_slice(b"\r\n");
+            body.extend_from_slice(&contents);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+        Ok((format!("multipart/form-data; boundary={boundary}"), body))
+    }
+}
+
+impl<'lua> FromLua<'lua> for RequestForm {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let tab = match value {
+            LuaValue::Table(tab) => tab,
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "RequestForm",
+                    message: Some(format!(
+                        "Invalid request form - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+
+        // Array entries are parts with their own names, and are sent first
+        // and in order, string keys are field names for strings or parts
+        let mut parts = Vec::new();
+        for value in tab.clone().sequence_values::<LuaTable>() {
+            parts.push(FormPart::from_lua_table(None, value?)?);
+        }
+        for pair in tab.pairs::<LuaValue, LuaValue>() {
+            let (key, value) = pair?;
+            let name = match key {
+                LuaValue::Integer(_) | LuaValue::Number(_) => continue,
+                LuaValue::String(s) => s.to_str()?.to_string(),
+                _ => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Invalid field name in request form - expected string, got {}",
+                        key.type_name()
+                    )))
+                }
+            };
+            parts.push(match value {
+                LuaValue::String(s) => FormPart {
+                    name,
+                    source: FormPartSource::Data(s.as_bytes().to_vec()),
+                    file_name: None,
+                    content_type: None,
+                },
+                LuaValue::Table(t) => FormPart::from_lua_table(Some(name), t)?,
+                _ => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Invalid field '{name}' in request form - expected string or table, got {}",
+                        value.type_name()
+                    )))
+                }
+            });
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+fn create_boundary() -> LuaResult<String> {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new().fill(&mut bytes).map_err(|_| {
+        LuaError::RuntimeError("Failed to generate request form boundary".to_string())
+    })?;
+    let hex = bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+    Ok(format!("----LuneFormBoundary{hex}"))
+}
+
+/**
+    Escapes a name for use in a quoted `Content-Disposition`
+    parameter, the same way that browsers escape them.
+*/
+fn escape_quoted(s: &str) -> String {
+    s.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
@@ -0,0 +1,496 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use mlua::prelude::*;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::Mutex as AsyncMutex,
+};
+use tokio_rustls::{
+    rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName},
+    TlsConnector,
+};
+
+use crate::lune::util::TableBuilder;
+
+use super::{
+    client::NetClient, happy_eyeballs, happy_eyeballs::IpVersion, util::strip_ipv6_brackets,
+};
+
+const DEFAULT_PORT: u16 = 21;
+
+trait FtpIo: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> FtpIo for T {}
+
+#[derive(Debug, Clone)]
+pub struct FtpConfig {
+    port: u16,
+    user: String,
+    password: String,
+    tls: bool,
+}
+
+impl<'lua> FromLua<'lua> for FtpConfig {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self {
+                port: DEFAULT_PORT,
+                user: "anonymous".to_string(),
+                password: String::new(),
+                tls: false,
+            }),
+            LuaValue::Table(tab) => Ok(Self {
+                port: tab.get::<_, Option<u16>>("port")?.unwrap_or(DEFAULT_PORT),
+                user: tab
+                    .get::<_, Option<String>>("user")?
+                    .unwrap_or_else(|| "anonymous".to_string()),
+                password: tab
+                    .get::<_, Option<String>>("password")?
+                    .unwrap_or_default(),
+                tls: tab.get::<_, Option<bool>>("tls")?.unwrap_or(false),
+            }),
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "FtpConfig",
+                message: Some(format!(
+                    "Invalid ftp config - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    Parses the port from a reply to `EPSV`, such as `Entering Extended Passive Mode (|||6446|)`.
+*/
+fn parse_epsv(message: &str) -> Option<u16> {
+    let start = message.find('(')?;
+    let end = message[start..].find(')')? + start;
+    message[start + 1..end]
+        .trim_matches(|c: char| !c.is_ascii_digit())
+        .parse()
+        .ok()
+}
+
+/**
+    Parses the port from a reply to `PASV`, such as `Entering Passive Mode (127,0,0,1,25,46)`.
+
+    The address in the reply is ignored, and the address of the control
+    connection is used instead, since servers behind NAT often give out
+    addresses that are not reachable.
+*/
+fn parse_pasv(message: &str) -> Option<u16> {
+    let start = message.find('(')?;
+    let end = message[start..].find(')')? + start;
+    let numbers = message[start + 1..end]
+        .split(',')
+        .map(|n| n.trim().parse::<u8>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    match numbers.as_slice() {
+        [_, _, _, _, high, low] => Some(u16::from(*high) << 8 | u16::from(*low)),
+        _ => None,
+    }
+}
+
+fn create_tls_connector() -> TlsConnector {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            anchor.subject,
+            anchor.spki,
+            anchor.name_constraints,
+        )
+    }));
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+/**
+    Reads a reply from an FTP server, which may span multiple lines, such as:
+
+    ```txt
+    211-Features:
+     EPSV
+     UTF8
+    211 End
+    ```
+*/
+async fn read_reply(reader: &mut (impl AsyncBufReadExt + Unpin)) -> LuaResult<(u16, String)> {
+    let mut message = String::new();
+    let mut code = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.into_lua_err()? == 0 {
+            return Err(LuaError::RuntimeError(
+                "FTP server closed the connection unexpectedly".to_string(),
+            ));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        let line_code = line.get(..3).and_then(|c| c.parse::<u16>().ok());
+        let separator = line.as_bytes().get(3).copied();
+        if !message.is_empty() {
+            message.push('\n');
+        }
+        match (code, line_code, separator) {
+            // The first line decides the code, and a dash means there are more lines
+            (None, Some(c), Some(b'-')) => {
+                code = Some(c);
+                message.push_str(&line[4..]);
+            }
+            (None, Some(c), _) => return Ok((c, line.get(4..).unwrap_or_default().to_string())),
+            // The last line repeats the code, followed by a space
+            (Some(c), Some(l), Some(b' ') | None) if c == l => {
+                message.push_str(line.get(4..).unwrap_or_default());
+                return Ok((c, message));
+            }
+            (Some(_), _, _) => message.push_str(line),
+            (None, None, _) => {
+                return Err(LuaError::RuntimeError(format!(
+                    "FTP server sent an invalid reply '{line}'"
+                )))
+            }
+        }
+    }
+}
+
+/**
+    A connection to an FTP server, created using `net.ftp.connect`.
+
+    Transfers use passive mode, and when using TLS, both
+    the control and data connections are encrypted.
+*/
+pub struct NetFtpConnection {
+    control: BufReader<Box<dyn FtpIo>>,
+    peer: IpAddr,
+    tls: Option<(TlsConnector, ServerName)>,
+}
+
+impl NetFtpConnection {
+    async fn connect(lua: &Lua, host: &str, config: FtpConfig) -> LuaResult<Self> {
+        let dns = NetClient::from_registry(lua).dns();
+        let mut stream = happy_eyeballs::connect(&dns, host, config.port, IpVersion::Any)
+            .await
+            .into_lua_err()
+            .with_context(|_| format!("Failed to connect to {host}:{}", config.port))?;
+        let peer = stream.peer_addr().into_lua_err()?.ip();
+
+        // NOTE: The greeting and upgrade to TLS are read without buffering
+        // more than the replies, since the TLS handshake follows right after
+        let mut plain = BufReader::new(&mut stream);
+        expect_reply(read_reply(&mut plain).await?, &[220], "connect")?;
+        let tls = if config.tls {
+            plain
+                .get_mut()
+                .write_all(b"AUTH TLS\r\n")
+                .await
+                .into_lua_err()?;
+            expect_reply(read_reply(&mut plain).await?, &[234], "AUTH TLS")?;
+            let server_name = ServerName::try_from(host).map_err(|_| {
+                LuaError::RuntimeError(format!("Invalid host name '{host}' for TLS"))
+            })?;
+            Some((create_tls_connector(), server_name))
+        } else {
+            None
+        };
+        drop(plain);
+
+        let control: Box<dyn FtpIo> = match &tls {
+            Some((connector, server_name)) => Box::new(
+                connector
+                    .connect(server_name.clone(), stream)
+                    .await
+                    .into_lua_err()?,
+            ),
+            None => Box::new(stream),
+        };
+        let mut connection = Self {
+            control: BufReader::new(control),
+            peer,
+            tls,
+        };
+
+        if connection.tls.is_some() {
+            connection.command("PBSZ 0", &[200]).await?;
+            connection.command("PROT P", &[200]).await?;
+        }
+        let (code, message) = connection.send(&format!("USER {}", config.user)).await?;
+        if code == 331 {
+            connection
+                .send(&format!("PASS {}", config.password))
+                .await
+                .and_then(|reply| expect_reply(reply, &[230, 202], "PASS"))?;
+        } else {
+            expect_reply((code, message), &[230], "USER")?;
+        }
+        connection.command("TYPE I", &[200]).await?;
+
+        Ok(connection)
+    }
+
+    async fn send(&mut self, command: &str) -> LuaResult<(u16, String)> {
+        let control = self.control.get_mut();
+        control
+            .write_all(format!("{command}\r\n").as_bytes())
+            .await
+            .into_lua_err()?;
+        control.flush().await.into_lua_err()?;
+        read_reply(&mut self.control).await
+    }
+
+    async fn command(&mut self, command: &str, expected: &[u16]) -> LuaResult<String> {
+        let reply = self.send(command).await?;
+        expect_reply(reply, expected, command)
+    }
+
+    /**
+        Opens a passive data connection, preferring extended passive
+        mode, which also works for servers using IPv6 addresses.
+    */
+    async fn open_data(&mut self) -> LuaResult<TcpStream> {
+        let port = match self.send("EPSV").await? {
+            (229, message) => parse_epsv(&message),
+            _ => {
+                let message = self.command("PASV", &[227]).await?;
+                parse_pasv(&message)
+            }
+        };
+        match port {
+            Some(port) => TcpStream::connect(SocketAddr::new(self.peer, port))
+                .await
+                .into_lua_err(),
+            None => Err(LuaError::RuntimeError(
+                "FTP server sent an invalid passive mode reply".to_string(),
+            )),
+        }
+    }
+
+    /**
+        Opens a data connection and sends a command that transfers data over it.
+    */
+    async fn transfer(&mut self, command: &str) -> LuaResult<Box<dyn FtpIo>> {
+        let data = self.open_data().await?;
+        self.command(command, &[125, 150]).await?;
+        // NOTE: The TLS handshake for data connections happens after the command,
+        // since servers only know that they should accept it once they receive it
+        Ok(match &self.tls {
+            Some((connector, server_name)) => Box::new(
+                connector
+                    .connect(server_name.clone(), data)
+                    .await
+                    .into_lua_err()?,
+            ),
+            None => Box::new(data),
+        })
+    }
+
+    async fn read(&mut self, command: &str) -> LuaResult<Vec<u8>> {
+        let mut data = self.transfer(command).await?;
+        let mut contents = Vec::new();
+        data.read_to_end(&mut contents).await.into_lua_err()?;
+        drop(data);
+        expect_reply(read_reply(&mut self.control).await?, &[226, 250], command)?;
+        Ok(contents)
+    }
+
+    async fn list(&mut self, path: Option<&str>) -> LuaResult<Vec<String>> {
+        let command = match path {
+            Some(path) => format!("NLST {path}"),
+            None => "NLST".to_string(),
+        };
+        let contents = self.read(&command).await?;
+        Ok(String::from_utf8_lossy(&contents)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            // NOTE: Some servers give full paths instead of names
+            .map(|line| match line.rsplit_once('/') {
+                Some((_, name)) => name.to_string(),
+                None => line,
+            })
+            .collect())
+    }
+
+    async fn put(&mut self, path: &str, contents: &[u8]) -> LuaResult<()> {
+        let command = format!("STOR {path}");
+        let mut data = self.transfer(&command).await?;
+        data.write_all(contents).await.into_lua_err()?;
+        data.shutdown().await.into_lua_err()?;
+        drop(data);
+        expect_reply(read_reply(&mut self.control).await?, &[226, 250], &command)?;
+        Ok(())
+    }
+
+    async fn close(&mut self) -> LuaResult<()> {
+        self.command("QUIT", &[221]).await?;
+        self.control.get_mut().shutdown().await.into_lua_err()
+    }
+}
+
+fn expect_reply(
+    (code, message): (u16, String),
+    expected: &[u16],
+    command: &str,
+) -> LuaResult<String> {
+    if expected.contains(&code) {
+        Ok(message)
+    } else {
+        // NOTE: Passwords should never end up in error messages
+        let command = if command.starts_with("PASS") {
+            "PASS"
+        } else {
+            command
+        };
+        Err(LuaError::RuntimeError(format!(
+            "FTP command '{command}' failed - {code} {message}"
+        )))
+    }
+}
+
+type SharedConnection = Arc<AsyncMutex<Option<NetFtpConnection>>>;
+
+fn closed_error() -> LuaError {
+    LuaError::RuntimeError("FTP connection is closed".to_string())
+}
+
+fn create_connection_table(lua: &'static Lua, connection: NetFtpConnection) -> LuaResult<LuaTable> {
+    let connection: SharedConnection = Arc::new(AsyncMutex::new(Some(connection)));
+    let (c_list, c_get, c_put, c_mkdir, c_remove, c_remove_dir, c_close) = (
+        Arc::clone(&connection),
+        Arc::clone(&connection),
+        Arc::clone(&connection),
+        Arc::clone(&connection),
+        Arc::clone(&connection),
+        Arc::clone(&connection),
+        Arc::clone(&connection),
+    );
+    TableBuilder::new(lua)?
+        .with_async_function("list", move |_, (_, path): (LuaValue, Option<String>)| {
+            let connection = Arc::clone(&c_list);
+            async move {
+                let mut connection = connection.lock().await;
+                let connection = connection.as_mut().ok_or_else(closed_error)?;
+                connection.list(path.as_deref()).await
+            }
+        })?
+        .with_async_function("get", move |lua, (_, path): (LuaValue, String)| {
+            let connection = Arc::clone(&c_get);
+            async move {
+                let mut connection = connection.lock().await;
+                let connection = connection.as_mut().ok_or_else(closed_error)?;
+                let contents = connection.read(&format!("RETR {path}")).await?;
+                lua.create_string(contents)
+            }
+        })?
+        .with_async_function(
+            "put",
+            move |_, (_, path, contents): (LuaValue, String, LuaString)| {
+                let connection = Arc::clone(&c_put);
+                let contents = contents.as_bytes().to_vec();
+                async move {
+                    let mut connection = connection.lock().await;
+                    let connection = connection.as_mut().ok_or_else(closed_error)?;
+                    connection.put(&path, &contents).await
+                }
+            },
+        )?
+        .with_async_function("mkdir", move |_, (_, path): (LuaValue, String)| {
+            let connection = Arc::clone(&c_mkdir);
+            async move {
+                let mut connection = connection.lock().await;
+                let connection = connection.as_mut().ok_or_else(closed_error)?;
+                connection.command(&format!("MKD {path}"), &[257]).await?;
+                Ok(())
+            }
+        })?
+        .with_async_function("remove", move |_, (_, path): (LuaValue, String)| {
+            let connection = Arc::clone(&c_remove);
+            async move {
+                let mut connection = connection.lock().await;
+                let connection = connection.as_mut().ok_or_else(closed_error)?;
+                connection.command(&format!("DELE {path}"), &[250]).await?;
+                Ok(())
+            }
+        })?
+        .with_async_function("removeDir", move |_, (_, path): (LuaValue, String)| {
+            let connection = Arc::clone(&c_remove_dir);
+            async move {
+                let mut connection = connection.lock().await;
+                let connection = connection.as_mut().ok_or_else(closed_error)?;
+                connection.command(&format!("RMD {path}"), &[250]).await?;
+                Ok(())
+            }
+        })?
+        .with_async_function("close", move |_, _: LuaValue| {
+            let connection = Arc::clone(&c_close);
+            async move {
+                match connection.lock().await.take() {
+                    Some(mut connection) => connection.close().await,
+                    None => Ok(()),
+                }
+            }
+        })?
+        .build_readonly()
+}
+
+/**
+    Creates the `net.ftp` table, for connecting to FTP servers.
+*/
+pub fn create_ftp_table(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("connect", net_ftp_connect)?
+        .build_readonly()
+}
+
+async fn net_ftp_connect(
+    lua: &'static Lua,
+    (host, config): (String, FtpConfig),
+) -> LuaResult<LuaTable> {
+    let host = strip_ipv6_brackets(&host);
+    let connection = NetFtpConnection::connect(lua, host, config).await?;
+    create_connection_table(lua, connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passive_replies() {
+        assert_eq!(
+            parse_epsv("Entering Extended Passive Mode (|||6446|)"),
+            Some(6446)
+        );
+        assert_eq!(parse_epsv("Entering Extended Passive Mode"), None);
+        assert_eq!(
+            parse_pasv("Entering Passive Mode (192,168,1,2,25,46)"),
+            Some(25 * 256 + 46)
+        );
+        assert_eq!(parse_pasv("Entering Passive Mode (192,168,1,2,25)"), None);
+    }
+
+    #[tokio::test]
+    async fn replies() {
+        let mut single = "220 Welcome\r\n".as_bytes();
+        assert_eq!(
+            read_reply(&mut single).await.unwrap(),
+            (220, "Welcome".to_string())
+        );
+
+        let mut multi = "211-Features:\r\n EPSV\r\n211 End\r\n".as_bytes();
+        assert_eq!(
+            read_reply(&mut multi).await.unwrap(),
+            (211, "Features:\n EPSV\nEnd".to_string())
+        );
+
+        let mut invalid = "hello\r\n".as_bytes();
+        assert!(read_reply(&mut invalid).await.is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+
+use hyper::client::connect::dns::Name;
+use mlua::prelude::*;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use tokio::{net::TcpStream, task::JoinSet, time::sleep};
+
+use super::dns::NetDns;
+
+/**
+    Time to wait for a connection attempt before starting the next one in parallel,
+    this is the recommended default "Connection Attempt Delay" from RFC 8305.
+*/
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/**
+    The ip versions that connections are allowed to use.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IpVersion {
+    #[default]
+    Any,
+    V4,
+    V6,
+}
+
+impl IpVersion {
+    fn allows(self, addr: &SocketAddr) -> bool {
+        match self {
+            Self::Any => true,
+            Self::V4 => addr.is_ipv4(),
+            Self::V6 => addr.is_ipv6(),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for IpVersion {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self::Any),
+            LuaValue::String(s) => match s.to_str()? {
+                "any" => Ok(Self::Any),
+                "v4" => Ok(Self::V4),
+                "v6" => Ok(Self::V6),
+                other => Err(LuaError::RuntimeError(format!(
+                    "Invalid ip version '{other}' - expected one of 'any', 'v4', 'v6'"
+                ))),
+            },
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "IpVersion",
+                message: Some(format!(
+                    "Invalid ip version - expected string, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    Filters addresses by the given ip version, and sorts them as described in RFC 8305 section 4.
+
+    The family of the first address is preferred, after which families are interleaved,
+    so that a host with broken connectivity for one family is quickly tried using the other.
+*/
+fn sort_addresses(addrs: Vec<SocketAddr>, version: IpVersion) -> Vec<SocketAddr> {
+    let addrs = addrs
+        .into_iter()
+        .filter(|addr| version.allows(addr))
+        .collect::<Vec<_>>();
+    let prefer_ipv6 = match addrs.first() {
+        Some(addr) => addr.is_ipv6(),
+        None => return addrs,
+    };
+    let mut sorted = Vec::with_capacity(addrs.len());
+    let (preferred, fallback): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == prefer_ipv6);
+    let (mut preferred, mut fallback) = (preferred.into_iter(), fallback.into_iter());
+    loop {
+        match (preferred.next(), fallback.next()) {
+            (None, None) => break,
+            (first, second) => sorted.extend(first.into_iter().chain(second)),
+        }
+    }
+    sorted
+}
+
+/**
+    Resolves the given host and port into addresses of the given ip version,
+    in the order that connections to them should be attempted.
+*/
+async fn resolve(
+    dns: &NetDns,
+    host: &str,
+    port: u16,
+    version: IpVersion,
+) -> io::Result<Vec<SocketAddr>> {
+    let addrs = dns
+        .lookup(host)
+        .await?
+        .into_iter()
+        .map(|ip| SocketAddr::new(ip, port))
+        .collect();
+    let addrs = sort_addresses(addrs, version);
+    if addrs.is_empty() {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            match version {
+                IpVersion::Any => format!("No addresses found for host '{host}'"),
+                IpVersion::V4 => format!("No ipv4 addresses found for host '{host}'"),
+                IpVersion::V6 => format!("No ipv6 addresses found for host '{host}'"),
+            },
+        ))
+    } else {
+        Ok(addrs)
+    }
+}
+
+/**
+    Connects to the given host and port, racing connection attempts
+    to all of its addresses as described in RFC 8305 section 5.
+
+    A new attempt is started whenever the previous attempt fails, or when
+    it has not succeeded within the connection attempt delay, and the
+    first attempt to succeed is used while all others are cancelled.
+*/
+pub async fn connect(
+    dns: &NetDns,
+    host: &str,
+    port: u16,
+    version: IpVersion,
+) -> io::Result<TcpStream> {
+    let mut addrs = resolve(dns, host, port, version).await?.into_iter();
+    let mut attempts = JoinSet::new();
+    let mut last_error = None;
+
+    if let Some(addr) = addrs.next() {
+        attempts.spawn(TcpStream::connect(addr));
+    }
+
+    // NOTE: Dropping the join set aborts all of the attempts
+    // that are still pending, once one of them has succeeded
+    while !attempts.is_empty() {
+        tokio::select! {
+            Some(result) = attempts.join_next() => {
+                match result {
+                    Ok(Ok(stream)) => return Ok(stream),
+                    Ok(Err(e)) => last_error = Some(e),
+                    Err(e) => last_error = Some(io::Error::other(e)),
+                }
+                if let Some(addr) = addrs.next() {
+                    attempts.spawn(TcpStream::connect(addr));
+                }
+            },
+            _ = sleep(CONNECTION_ATTEMPT_DELAY), if !addrs.as_slice().is_empty() => {
+                if let Some(addr) = addrs.next() {
+                    attempts.spawn(TcpStream::connect(addr));
+                }
+            },
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No addresses found for host '{host}'"),
+        )
+    }))
+}
+
+/**
+    A resolver for `reqwest` clients that filters and sorts addresses for
+    dual-stack hosts, the connector in `hyper` then races connections to
+    the preferred and fallback families using these addresses.
+*/
+#[derive(Debug, Clone)]
+pub struct HappyEyeballsResolver {
+    dns: Arc<NetDns>,
+    version: IpVersion,
+}
+
+impl HappyEyeballsResolver {
+    pub fn new(dns: Arc<NetDns>, version: IpVersion) -> Self {
+        Self { dns, version }
+    }
+}
+
+impl Resolve for HappyEyeballsResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let dns = Arc::clone(&self.dns);
+        let version = self.version;
+        Box::pin(async move {
+            // NOTE: The port is replaced by the connector, so any port works here
+            let addrs = resolve(&dns, name.as_str(), 0, version).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addrs(list: &[&str]) -> Vec<SocketAddr> {
+        list.iter().map(|addr| addr.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn interleaves_families() {
+        let sorted = sort_addresses(
+            addrs(&[
+                "[::1]:80",
+                "[::2]:80",
+                "[::3]:80",
+                "1.1.1.1:80",
+                "2.2.2.2:80",
+            ]),
+            IpVersion::Any,
+        );
+        assert_eq!(
+            sorted,
+            addrs(&[
+                "[::1]:80",
+                "1.1.1.1:80",
+                "[::2]:80",
+                "2.2.2.2:80",
+                "[::3]:80"
+            ])
+        );
+    }
+
+    #[test]
+    fn prefers_first_family() {
+        let sorted = sort_addresses(
+            addrs(&["1.1.1.1:80", "2.2.2.2:80", "[::1]:80"]),
+            IpVersion::Any,
+        );
+        assert_eq!(sorted, addrs(&["1.1.1.1:80", "[::1]:80", "2.2.2.2:80"]));
+    }
+
+    #[test]
+    fn filters_versions() {
+        let all = addrs(&["[::1]:80", "1.1.1.1:80", "[::2]:80"]);
+        assert_eq!(
+            sort_addresses(all.clone(), IpVersion::V4),
+            addrs(&["1.1.1.1:80"])
+        );
+        assert_eq!(
+            sort_addresses(all, IpVersion::V6),
+            addrs(&["[::1]:80", "[::2]:80"])
+        );
+    }
+}
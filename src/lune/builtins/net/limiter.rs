@@ -0,0 +1,193 @@
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use mlua::prelude::*;
+use tokio::{
+    sync::Mutex as AsyncMutex,
+    time::{sleep_until, Instant},
+};
+
+use crate::lune::{
+    builtins::trace::{AutoSpan, SpanContext},
+    util::TableBuilder,
+};
+
+use super::{
+    client::NetClient, config::RequestConfig, create_request_span, request_with_client,
+    retry::parse_retry_after,
+};
+
+/**
+    Maximum number of times that a single request is sent again
+    after being told to wait using a `Retry-After` header.
+*/
+const MAX_RETRY_AFTER_ATTEMPTS: u32 = 5;
+
+/**
+    Options for creating a rate limiter using `net.limiter`.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct LimiterConfig {
+    interval: Duration,
+    respect_retry_after: bool,
+}
+
+impl<'lua> FromLua<'lua> for LimiterConfig {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let tab = match value {
+            LuaValue::Table(tab) => tab,
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "LimiterConfig",
+                    message: Some(format!(
+                        "Invalid limiter config - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        let interval = match tab.raw_get::<_, Option<f64>>("requestsPerSecond") {
+            Ok(Some(rps)) if rps.is_finite() && rps > 0.0 => Duration::from_secs_f64(1.0 / rps),
+            _ => {
+                return Err(LuaError::RuntimeError(
+                    "Missing or invalid 'requestsPerSecond' in limiter config - \
+                    expected a positive number"
+                        .to_string(),
+                ))
+            }
+        };
+        let respect_retry_after = match tab.raw_get::<_, LuaValue>("respectRetryAfter")? {
+            LuaValue::Nil => true,
+            LuaValue::Boolean(b) => b,
+            _ => {
+                return Err(LuaError::RuntimeError(
+                    "Invalid 'respectRetryAfter' in limiter config - expected boolean".to_string(),
+                ))
+            }
+        };
+        Ok(Self {
+            interval,
+            respect_retry_after,
+        })
+    }
+}
+
+/**
+    A rate limiter, which spaces out requests evenly.
+
+    Requests wait for their turn in the same order as they were made, and if
+    the server asks us to slow down, all requests wait until the server is ready.
+*/
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Cell<Duration>,
+    queue: AsyncMutex<()>,
+    next_slot: Cell<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval: Cell::new(interval),
+            queue: AsyncMutex::new(()),
+            next_slot: Cell::new(Instant::now()),
+        }
+    }
+
+    /**
+        Sets the minimum amount of time between two requests.
+    */
+    #[cfg(feature = "roblox")]
+    pub fn set_interval(&self, interval: Duration) {
+        self.interval.set(interval);
+    }
+
+    /**
+        Waits until the next request may be sent.
+    */
+    pub async fn acquire(&self) {
+        // NOTE: The lock is fair, so requests get their turn in order, and
+        // the next slot may be pushed back while waiting, so we check it again
+        let _turn = self.queue.lock().await;
+        loop {
+            let slot = self.next_slot.get();
+            if slot <= Instant::now() {
+                break;
+            }
+            sleep_until(slot).await;
+        }
+        self.next_slot.set(Instant::now() + self.interval.get());
+    }
+
+    /**
+        Makes all requests wait for at least the given amount of time.
+    */
+    pub fn pause_for(&self, delay: Duration) {
+        let until = Instant::now() + delay;
+        if until > self.next_slot.get() {
+            self.next_slot.set(until);
+        }
+    }
+}
+
+/**
+    A rate limiter for requests, created using `net.limiter`.
+*/
+#[derive(Debug)]
+struct NetLimiter {
+    config: LimiterConfig,
+    limiter: RateLimiter,
+}
+
+impl NetLimiter {
+    fn new(config: LimiterConfig) -> Self {
+        Self {
+            config,
+            limiter: RateLimiter::new(config.interval),
+        }
+    }
+
+    async fn request(
+        &self,
+        lua: &'static Lua,
+        config: RequestConfig<'static>,
+        parent: Option<SpanContext>,
+    ) -> LuaResult<LuaTable<'static>> {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            self.limiter.acquire().await;
+
+            let span = create_request_span(lua, &config, parent);
+            let client = NetClient::from_registry(lua);
+            let res = request_with_client(lua, client, config.clone(), span).await?;
+            if !self.config.respect_retry_after || attempts > MAX_RETRY_AFTER_ATTEMPTS {
+                return Ok(res);
+            }
+
+            // NOTE: Header names in responses are always lowercase
+            let status = res.get::<_, u16>("statusCode")?;
+            let retry_after = res
+                .get::<_, LuaTable>("headers")?
+                .get::<_, Option<String>>("retry-after")?;
+            match retry_after.as_deref().and_then(parse_retry_after) {
+                Some(delay) if status == 429 => self.limiter.pause_for(delay),
+                _ => return Ok(res),
+            }
+        }
+    }
+}
+
+pub fn create_limiter_table(lua: &'static Lua, config: LimiterConfig) -> LuaResult<LuaTable> {
+    let limiter = Rc::new(NetLimiter::new(config));
+    TableBuilder::new(lua)?
+        .with_async_function(
+            "request",
+            move |lua, (_, config): (LuaValue, RequestConfig)| {
+                let limiter = Rc::clone(&limiter);
+                let parent = AutoSpan::current(lua);
+                async move { limiter.request(lua, config, parent).await }
+            },
+        )?
+        .build_readonly()
+}
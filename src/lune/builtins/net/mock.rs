@@ -0,0 +1,387 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use hyper::{body::to_bytes, HeaderMap};
+use mlua::prelude::*;
+use reqwest::{Method, Request, Response, ResponseBuilderExt, Url};
+use tokio::io::{duplex, DuplexStream};
+use tokio_tungstenite::WebSocketStream;
+
+use crate::lune::{scheduler::Scheduler, util::TableBuilder};
+
+use super::{response::NetServeResponse, websocket::NetWebSocket};
+
+const REGISTRY_KEY: &str = "NetMock";
+
+/**
+    Size of the in-memory buffer used for each direction of a mocked web socket.
+*/
+const MOCK_SOCKET_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+struct NetMockRoute {
+    key: String,
+    method: Option<Method>,
+    url: String,
+    prefix: bool,
+    response: LuaRegistryKey,
+}
+
+impl NetMockRoute {
+    /**
+        Parses a route key such as `"GET https://example.com/users"`,
+        where the method is optional and the url may end with a `*`
+        to match any url that starts with the given url.
+    */
+    fn parse(key: String, response: LuaRegistryKey) -> LuaResult<Self> {
+        let (method, url) = match key.trim().split_once(char::is_whitespace) {
+            Some((method, url)) => {
+                let method =
+                    Method::from_bytes(method.to_ascii_uppercase().as_bytes()).map_err(|_| {
+                        LuaError::RuntimeError(format!(
+                            "Invalid method '{method}' in mock route '{key}'"
+                        ))
+                    })?;
+                (Some(method), url.trim())
+            }
+            None => (None, key.trim()),
+        };
+        let (url, prefix) = match url.strip_suffix('*') {
+            Some(url) => (url, true),
+            None => (url, false),
+        };
+        // NOTE: Urls are normalized the same way as the urls of requests
+        // are, so that a route for "https://example.com" also matches
+        // requests for "https://example.com/" and the other way around
+        let parsed = Url::parse(url).map_err(|e| {
+            LuaError::RuntimeError(format!("Invalid url in mock route '{key}' - {e}"))
+        })?;
+        let url = if prefix {
+            url.to_string()
+        } else {
+            url_without_query(&parsed)
+        };
+        Ok(Self {
+            key,
+            method,
+            url,
+            prefix,
+            response,
+        })
+    }
+
+    fn matches(&self, method: &Method, url: &str) -> bool {
+        let method_matches = match &self.method {
+            Some(m) => m == method,
+            None => true,
+        };
+        let url_matches = if self.prefix {
+            url.starts_with(&self.url)
+        } else {
+            url == self.url
+        };
+        method_matches && url_matches
+    }
+
+    /**
+        Ordering for routes, where more specific routes come first.
+
+        Exact urls are more specific than prefixes, longer prefixes are more
+        specific than shorter ones, and routes with a method are more specific
+        than routes without one. Ties are broken by the route key itself, so
+        that the order never depends on the order of the routes table.
+    */
+    fn specificity(&self) -> (bool, usize, bool, &str) {
+        (
+            self.prefix,
+            usize::MAX - self.url.len(),
+            self.method.is_none(),
+            &self.key,
+        )
+    }
+}
+
+/**
+    A mock for the network, created using `net.mock`.
+
+    While a mock is active, requests and web sockets are answered by
+    the routes of the mock instead of going out to the network.
+*/
+#[derive(Debug)]
+pub struct NetMock {
+    routes: Vec<NetMockRoute>,
+    passthrough: bool,
+    calls: LuaRegistryKey,
+    call_counts: Mutex<HashMap<String, usize>>,
+}
+
+impl NetMock {
+    /**
+        Gets the currently active mock, if any.
+    */
+    pub fn active(lua: &Lua) -> Option<Arc<NetMock>> {
+        match lua.named_registry_value::<LuaValue>(REGISTRY_KEY) {
+            Ok(LuaValue::UserData(ud)) => ud
+                .borrow::<NetMockHandle>()
+                .ok()
+                .map(|handle| Arc::clone(&handle.0)),
+            _ => None,
+        }
+    }
+
+    fn set_active(lua: &Lua, mock: Option<Arc<NetMock>>) -> LuaResult<()> {
+        match mock {
+            Some(mock) => lua.set_named_registry_value(REGISTRY_KEY, NetMockHandle(mock)),
+            None => lua.unset_named_registry_value(REGISTRY_KEY),
+        }
+    }
+
+    fn call_count(&self, route: &str) -> usize {
+        self.call_counts
+            .lock()
+            .expect("Failed to lock mock call counts")
+            .get(route)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn find_route(&self, method: &Method, url: &Url) -> LuaResult<Option<&NetMockRoute>> {
+        let url = url_without_query(url);
+        match self.routes.iter().find(|route| route.matches(method, &url)) {
+            Some(route) => Ok(Some(route)),
+            None if self.passthrough => Ok(None),
+            None => Err(LuaError::RuntimeError(format!(
+                "No mock route matched request '{method} {url}'\
+                \n> Add a route for it to net.mock, or enable passthrough"
+            ))),
+        }
+    }
+
+    fn record_call<'lua>(
+        &self,
+        lua: &'lua Lua,
+        route: &NetMockRoute,
+        method: &Method,
+        url: &Url,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> LuaResult<LuaTable<'lua>> {
+        let query = lua.create_table()?;
+        for (key, value) in url.query_pairs() {
+            query.raw_set(key.as_ref(), value.as_ref())?;
+        }
+        let header_table = lua.create_table_with_capacity(0, headers.len())?;
+        for (key, value) in headers {
+            header_table.raw_set(key.as_str(), lua.create_string(value.as_bytes())?)?;
+        }
+        let call = TableBuilder::new(lua)?
+            .with_value("route", route.key.as_str())?
+            .with_value("method", method.as_str())?
+            .with_value("url", url.as_str())?
+            .with_value("path", url.path())?
+            .with_value("query", query)?
+            .with_value("headers", header_table)?
+            .with_value("body", lua.create_string(body)?)?
+            .build_readonly()?;
+
+        let calls = lua.registry_value::<LuaTable>(&self.calls)?;
+        calls.raw_set(calls.raw_len() + 1, call.clone())?;
+        *self
+            .call_counts
+            .lock()
+            .expect("Failed to lock mock call counts")
+            .entry(route.key.clone())
+            .or_default() += 1;
+
+        Ok(call)
+    }
+
+    /**
+        Answers a request using the routes of this mock.
+
+        Returns `None` if no route matched and passthrough is enabled,
+        meaning that the request should be sent over the network.
+    */
+    pub async fn respond_to_request(
+        &self,
+        lua: &Lua,
+        request: &Request,
+    ) -> LuaResult<Option<Response>> {
+        let url = request.url();
+        let route = match self.find_route(request.method(), url)? {
+            Some(route) => route,
+            None => return Ok(None),
+        };
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .unwrap_or_default();
+        let call = self.record_call(lua, route, request.method(), url, request.headers(), body)?;
+
+        let response = match lua.registry_value::<LuaValue>(&route.response)? {
+            LuaValue::Function(handler) => {
+                let sched = *lua
+                    .app_data_ref::<&Scheduler>()
+                    .expect("Lua struct is missing scheduler");
+                let thread_id = sched.push_back(lua, handler, call)?;
+                let thread_res = sched.wait_for_thread(lua, thread_id).await?;
+                NetServeResponse::from_lua_multi(thread_res, lua)?
+            }
+            value => NetServeResponse::from_lua(value, lua)?,
+        };
+
+        let (parts, body) = response.into_response()?.into_parts();
+        let body = to_bytes(body).await.into_lua_err()?;
+        let mut builder = hyper::Response::builder()
+            .status(parts.status)
+            .url(url.clone());
+        for (name, value) in &parts.headers {
+            builder = builder.header(name, value);
+        }
+        let response = builder.body(body.to_vec()).into_lua_err()?;
+        Ok(Some(Response::from(response)))
+    }
+
+    /**
+        Answers a web socket connection using the routes of this mock.
+
+        The handler for the route will receive the other end of the
+        web socket, same as a web socket handler given to `net.serve`.
+
+        Returns `None` if no route matched and passthrough is enabled,
+        meaning that the web socket should connect over the network.
+    */
+    pub async fn respond_to_socket<'lua>(
+        &self,
+        lua: &'lua Lua,
+        url: &Url,
+    ) -> LuaResult<Option<WebSocketStream<DuplexStream>>>
+    where
+        'lua: 'static,
+    {
+        let route = match self.find_route(&Method::GET, url)? {
+            Some(route) => route,
+            None => return Ok(None),
+        };
+        let handler = match lua.registry_value::<LuaValue>(&route.response)? {
+            LuaValue::Function(handler) => handler,
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Mock route '{}' must be a function to accept web sockets, got {}",
+                    route.key,
+                    value.type_name()
+                )))
+            }
+        };
+        self.record_call(lua, route, &Method::GET, url, &HeaderMap::new(), &[])?;
+
+        let (client_io, server_io) = duplex(MOCK_SOCKET_BUFFER_SIZE);
+        let ((client, _), server) = tokio::try_join!(
+            tokio_tungstenite::client_async(url.as_str(), client_io),
+            tokio_tungstenite::accept_async(server_io),
+        )
+        .into_lua_err()?;
+
+        let server_table = NetWebSocket::new(server).into_lua_table(lua)?;
+        let sched = lua
+            .app_data_ref::<&Scheduler>()
+            .expect("Lua struct is missing scheduler");
+        sched.push_back(lua, handler, server_table)?;
+
+        Ok(Some(client))
+    }
+}
+
+struct NetMockHandle(Arc<NetMock>);
+
+impl LuaUserData for NetMockHandle {}
+
+pub fn net_mock<'lua>(
+    lua: &'lua Lua,
+    (routes, options): (LuaTable<'lua>, Option<LuaTable<'lua>>),
+) -> LuaResult<LuaTable<'lua>> {
+    if NetMock::active(lua).is_some() {
+        return Err(LuaError::RuntimeError(
+            "A network mock is already active - call restore on it before creating another"
+                .to_string(),
+        ));
+    }
+
+    let mut parsed_routes = Vec::new();
+    for pair in routes.pairs::<String, LuaValue>() {
+        let (key, value) = pair?;
+        match value {
+            LuaValue::String(_) | LuaValue::Table(_) | LuaValue::Function(_) => {}
+            _ => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid mock route '{key}' - expected string, table or function, got {}",
+                    value.type_name()
+                )))
+            }
+        }
+        let response = lua.create_registry_value(value)?;
+        parsed_routes.push(NetMockRoute::parse(key, response)?);
+    }
+    parsed_routes.sort_by(|a, b| a.specificity().cmp(&b.specificity()));
+
+    let passthrough = match &options {
+        Some(options) => options
+            .raw_get::<_, Option<bool>>("passthrough")?
+            .unwrap_or(false),
+        None => false,
+    };
+
+    let calls = lua.create_table()?;
+    let mock = Arc::new(NetMock {
+        routes: parsed_routes,
+        passthrough,
+        calls: lua.create_registry_value(calls.clone())?,
+        call_counts: Mutex::new(HashMap::new()),
+    });
+    NetMock::set_active(lua, Some(Arc::clone(&mock)))?;
+
+    let mock_count = Arc::clone(&mock);
+    let mock_assert = Arc::clone(&mock);
+    let mock_restore = Arc::clone(&mock);
+    TableBuilder::new(lua)?
+        .with_value("calls", calls)?
+        .with_function("callCount", move |_, (_, route): (LuaValue, String)| {
+            Ok(mock_count.call_count(&route))
+        })?
+        .with_function(
+            "assertCalled",
+            move |_, (_, route, times): (LuaValue, String, Option<usize>)| {
+                let count = mock_assert.call_count(&route);
+                match times {
+                    Some(times) if count != times => Err(LuaError::RuntimeError(format!(
+                        "Expected mock route '{route}' to be called {times} time(s), \
+                        but it was called {count} time(s)"
+                    ))),
+                    None if count == 0 => Err(LuaError::RuntimeError(format!(
+                        "Expected mock route '{route}' to be called, but it was never called"
+                    ))),
+                    _ => Ok(()),
+                }
+            },
+        )?
+        .with_function("restore", move |lua, _: LuaValue| {
+            // NOTE: Only remove the mock if it is still the active one,
+            // restoring an old mock should not remove any newer mock
+            if let Some(active) = NetMock::active(lua) {
+                if Arc::ptr_eq(&active, &mock_restore) {
+                    NetMock::set_active(lua, None)?;
+                }
+            }
+            Ok(())
+        })?
+        .build_readonly()
+}
+
+fn url_without_query(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    url.to_string()
+}
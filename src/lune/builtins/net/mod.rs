@@ -1,43 +1,132 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, future::Future, sync::Arc};
 
 use mlua::prelude::*;
 
-use hyper::header::{CONTENT_ENCODING, CONTENT_LENGTH};
+use hyper::{
+    header::{
+        HeaderName, ACCEPT, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_ENCODING, CONTENT_LENGTH,
+        CONTENT_TYPE, WWW_AUTHENTICATE,
+    },
+    Server, StatusCode,
+};
+
+use reqwest::{Method, Response, Url};
+use tokio::time;
 
 use crate::lune::{scheduler::Scheduler, util::TableBuilder};
 
 use self::server::create_server;
 
+use super::trace::{AutoSpan, SpanContext, SpanKind};
+
 use super::serde::{
-    compress_decompress::{decompress, CompressDecompressFormat},
+    compress_decompress::{compress, decompress, CompressDecompressFormat, CompressOptions},
     encode_decode::{EncodeDecodeConfig, EncodeDecodeFormat},
 };
 
+mod auth;
+mod body;
+mod charset;
 mod client;
 mod config;
+mod cookies;
+mod dns;
+mod file;
+mod form;
+mod ftp;
+mod happy_eyeballs;
+mod limiter;
+mod mock;
 mod processing;
+mod proxy;
 mod response;
+mod retry;
 mod server;
+mod single_flight;
+mod sse;
+mod tcp;
+mod tls;
+mod tunnel;
+mod udp;
+mod url;
+mod util;
 mod websocket;
 
+#[cfg(feature = "ssh")]
+mod ssh;
+
+#[cfg(feature = "acme")]
+mod acme;
+
+use auth::RequestAuth;
+use body::NetResponseBody;
+use charset::{Charset, ContentType};
 use client::{NetClient, NetClientBuilder};
-use config::{RequestConfig, ServeConfig};
-use server::bind_to_localhost;
+use config::{ClientConfig, RequestConfig, RequestConfigOptions, ServeConfig, SocketConfigOptions};
+use cookies::create_cookies_table;
+use dns::create_dns_table;
+use file::net_file_response;
+use ftp::create_ftp_table;
+use happy_eyeballs::IpVersion;
+use limiter::create_limiter_table;
+use mock::{net_mock, NetMock};
+use server::bind_to_address;
+use single_flight::SingleFlight;
+use sse::{net_create_response_stream, NetEventStream};
+use tcp::create_tcp_table;
+use tls::{create_tls, tls_incoming};
+use udp::create_udp_table;
+use url::{net_url_build, net_url_parse};
 use websocket::NetWebSocket;
 
+#[cfg(feature = "roblox")]
+pub(super) use self::{limiter::RateLimiter, retry::parse_retry_after};
+
+#[cfg(feature = "ssh")]
+use ssh::create_ssh_table;
+
 pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
-    NetClientBuilder::new()
+    let client = NetClientBuilder::new()
         .headers(&[("User-Agent", create_user_agent_header())])?
-        .build()?
-        .into_registry(lua);
+        .build()?;
+    let cookies = create_cookies_table(lua, client.cookies())?;
+    let dns = create_dns_table(lua, client.dns())?;
+    #[cfg(feature = "ssh")]
+    let ssh = LuaValue::Table(create_ssh_table(lua)?);
+    #[cfg(not(feature = "ssh"))]
+    let ssh = LuaValue::Nil;
+    client.into_registry(lua);
+    let flights = Arc::new(SingleFlight::default());
     TableBuilder::new(lua)?
+        .with_value("cookies", cookies)?
+        .with_value("dns", dns)?
+        .with_function("createClient", net_create_client)?
+        .with_function("createResponseStream", net_create_response_stream)?
         .with_function("jsonEncode", net_json_encode)?
         .with_function("jsonDecode", net_json_decode)?
+        .with_function("msgpackEncode", net_msgpack_encode)?
+        .with_function("msgpackDecode", net_msgpack_decode)?
+        .with_async_function("fileResponse", net_file_response)?
+        .with_function("limiter", create_limiter_table)?
+        .with_function("mock", net_mock)?
         .with_async_function("request", net_request)?
         .with_async_function("socket", net_socket)?
         .with_async_function("serve", net_serve)?
+        .with_async_function(
+            "singleFlight",
+            move |lua, (key, func): (String, LuaFunction)| {
+                let flights = Arc::clone(&flights);
+                async move { flights.call(lua, key, func).await }
+            },
+        )?
+        .with_value("ftp", create_ftp_table(lua)?)?
+        .with_value("ssh", ssh)?
+        .with_value("tcp", create_tcp_table(lua)?)?
+        .with_value("udp", create_udp_table(lua)?)?
         .with_function("urlEncode", net_url_encode)?
         .with_function("urlDecode", net_url_decode)?
+        .with_function("urlParse", net_url_parse)?
+        .with_function("urlBuild", net_url_build)?
         .build_readonly()
 }
 
@@ -61,27 +150,296 @@ fn net_json_decode<'lua>(lua: &'lua Lua, json: LuaString<'lua>) -> LuaResult<Lua
     EncodeDecodeConfig::from(EncodeDecodeFormat::Json).deserialize_from_string(lua, json)
 }
 
-async fn net_request<'lua>(lua: &'lua Lua, config: RequestConfig<'lua>) -> LuaResult<LuaTable<'lua>>
+fn net_msgpack_encode<'lua>(lua: &'lua Lua, val: LuaValue<'lua>) -> LuaResult<LuaString<'lua>> {
+    EncodeDecodeConfig::from(EncodeDecodeFormat::MessagePack).serialize_to_string(lua, val)
+}
+
+fn net_msgpack_decode<'lua>(lua: &'lua Lua, encoded: LuaString<'lua>) -> LuaResult<LuaValue<'lua>> {
+    EncodeDecodeConfig::from(EncodeDecodeFormat::MessagePack).deserialize_from_string(lua, encoded)
+}
+
+fn net_create_client(lua: &'static Lua, config: ClientConfig) -> LuaResult<LuaTable> {
+    // NOTE: Clients share dns overrides with the global client, so that tests which
+    // point host names at local servers also work for code using its own client
+    let mut builder = NetClientBuilder::new()
+        .headers(&[("User-Agent", create_user_agent_header())])?
+        .dns(NetClient::from_registry(lua).dns());
+    if config.http2 == Some(false) {
+        builder = builder.http1_only();
+    }
+    if let Some(max) = config.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+    if let Some(interval) = config.tcp_keep_alive {
+        builder = builder.tcp_keepalive(interval);
+    }
+    for pem in &config.root_certificates {
+        builder = builder.root_certificates(pem)?;
+    }
+    let client = builder.build()?;
+    let cookies = create_cookies_table(lua, client.cookies())?;
+    TableBuilder::new(lua)?
+        .with_value("cookies", cookies)?
+        .with_async_function(
+            "request",
+            move |lua, (_, config): (LuaValue, RequestConfig)| {
+                let span = create_request_span(lua, &config, AutoSpan::current(lua));
+                request_with_client(lua, client.clone(), config, span)
+            },
+        )?
+        .build_readonly()
+}
+
+fn net_request<'lua>(
+    lua: &'lua Lua,
+    config: RequestConfig<'lua>,
+) -> impl Future<Output = LuaResult<LuaTable<'lua>>>
 where
     'lua: 'static, // FIXME: Get rid of static lifetime bound here
 {
-    // Create and send the request
-    let client = NetClient::from_registry(lua);
-    let mut request = client.request(config.method, &config.url);
-    for (query, value) in config.query {
-        request = request.query(&[(query.to_str()?, value.to_str()?)]);
+    let span = create_request_span(lua, &config, AutoSpan::current(lua));
+    request_with_client(lua, NetClient::from_registry(lua), config, span)
+}
+
+/**
+    Creates the span for a request, with the given parent.
+
+    Note that the parent must be the current span of the calling thread, which
+    must be read before the async request starts, using [`AutoSpan::current`].
+*/
+fn create_request_span(
+    lua: &'static Lua,
+    config: &RequestConfig,
+    parent: Option<SpanContext>,
+) -> AutoSpan {
+    let kind = SpanKind::Client;
+    let mut span = AutoSpan::start_with_parent(lua, config.method.as_str(), kind, parent);
+    span.set_attribute("http.request.method", config.method.as_str());
+    span.set_attribute("url.full", redact_url(&config.url));
+    span
+}
+
+/**
+    Query parameters that may contain credentials, the first few are from the
+    OpenTelemetry semantic conventions and the rest are commonly used for tokens.
+*/
+const REDACTED_QUERY_PARAMS: &[&str] = &[
+    "AWSAccessKeyId",
+    "Signature",
+    "sig",
+    "X-Goog-Signature",
+    "access_token",
+    "api_key",
+    "apikey",
+    "client_secret",
+    "password",
+    "secret",
+    "token",
+];
+
+/**
+    Redacts credentials from the given url, so that it can be recorded in a span.
+
+    Following the OpenTelemetry semantic conventions for `url.full`, any username
+    and password are replaced with `REDACTED`, and so are the values of query
+    parameters that may contain credentials, see [`REDACTED_QUERY_PARAMS`].
+
+    Urls that can not be parsed are not recorded at all, since
+    there is no way to know which parts of them are sensitive.
+*/
+fn redact_url(url: &str) -> String {
+    let mut url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return "REDACTED".to_string(),
+    };
+    if !url.username().is_empty() {
+        url.set_username("REDACTED").ok();
     }
-    for (header, value) in config.headers {
-        request = request.header(header.to_str()?, value.to_str()?);
+    if url.password().is_some() {
+        url.set_password(Some("REDACTED")).ok();
+    }
+    if let Some(query) = url.query() {
+        let redacted = query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((key, _))
+                    if REDACTED_QUERY_PARAMS
+                        .iter()
+                        .any(|param| param.eq_ignore_ascii_case(key)) =>
+                {
+                    format!("{key}=REDACTED")
+                }
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        url.set_query(Some(&redacted));
+    }
+    url.to_string()
+}
+
+async fn request_with_client<'lua>(
+    lua: &'lua Lua,
+    client: NetClient,
+    config: RequestConfig<'lua>,
+    mut span: AutoSpan,
+) -> LuaResult<LuaTable<'lua>>
+where
+    'lua: 'static, // FIXME: Get rid of static lifetime bound here
+{
+    let result = request_with_client_inner(lua, client, config, &mut span).await;
+    if let Err(e) = &result {
+        span.set_error(e.to_string());
+    }
+    result
+}
+
+async fn request_with_client_inner<'lua>(
+    lua: &'lua Lua,
+    client: NetClient,
+    config: RequestConfig<'lua>,
+    span: &mut AutoSpan,
+) -> LuaResult<LuaTable<'lua>>
+where
+    'lua: 'static, // FIXME: Get rid of static lifetime bound here
+{
+    // Collect headers and body, forms are serialized into a body with their own content type
+    let mut headers = config
+        .headers
+        .iter()
+        .map(|(header, value)| Ok((header.to_str()?.to_string(), value.to_str()?.to_string())))
+        .collect::<LuaResult<Vec<_>>>()?;
+    // Propagate the trace that this request is a part of, if
+    // any, unless the user has already set the header themselves
+    if let Some(traceparent) = span.traceparent() {
+        if !headers
+            .iter()
+            .any(|(h, _)| h.eq_ignore_ascii_case("traceparent"))
+        {
+            headers.push(("traceparent".to_string(), traceparent));
+        }
+    }
+    let body = match config.form {
+        Some(form) => {
+            let (content_type, body) = form.into_body().await?;
+            headers.retain(|(header, _)| !header.eq_ignore_ascii_case(CONTENT_TYPE.as_str()));
+            headers.push((CONTENT_TYPE.to_string(), content_type));
+            body
+        }
+        None => config.body.unwrap_or_default(),
+    };
+    // Compress the body if wanted, and let the server know we can decompress
+    // responses, unless either header has already been set explicitly
+    let has_header = |headers: &[(String, String)], name: &HeaderName| {
+        headers
+            .iter()
+            .any(|(header, _)| header.eq_ignore_ascii_case(name.as_str()))
+    };
+    let body = match config.options.compress {
+        Some(format) if !body.is_empty() && !has_header(&headers, &CONTENT_ENCODING) => {
+            let encoding = format
+                .as_header_str()
+                .expect("Request compression format must have a content encoding");
+            headers.push((CONTENT_ENCODING.to_string(), encoding.to_string()));
+            compress(format, body, CompressOptions::default()).await?
+        }
+        _ => body,
+    };
+    if config.options.decompress
+        && !config.options.stream
+        && !config.options.sse
+        && !has_header(&headers, &ACCEPT_ENCODING)
+    {
+        headers.push((ACCEPT_ENCODING.to_string(), "gzip, deflate, br".to_string()));
+    }
+    if config.options.sse && !has_header(&headers, &ACCEPT) {
+        headers.push((ACCEPT.to_string(), "text/event-stream".to_string()));
+    }
+    // Create the client, reqwest only supports connect timeouts and proxies for
+    // entire clients, so requests with either use their own separate client instead
+    let client = match (
+        config.options.timeout.connect,
+        &config.options.proxy,
+        config.options.ip_version,
+    ) {
+        (None, None, IpVersion::Any) => client,
+        (connect_timeout, proxy, ip_version) => {
+            let mut builder = client.builder();
+            if let Some(timeout) = connect_timeout {
+                builder = builder.connect_timeout(timeout);
+            }
+            if let Some(proxy) = proxy {
+                builder = builder.proxy(proxy.clone());
+            }
+            builder.ip_version(ip_version).build()?
+        }
+    };
+    let mut url = Url::parse(&config.url).into_lua_err()?;
+    if !config.query.is_empty() {
+        let mut pairs = url.query_pairs_mut();
+        for (query, value) in &config.query {
+            pairs.append_pair(query.to_str()?, value.to_str()?);
+        }
+    }
+    // Send the request
+    let origin = url.origin();
+    let mut res = send_request(
+        lua,
+        &client,
+        &config.method,
+        url,
+        &headers,
+        config.auth.as_ref(),
+        &body,
+        &config.options,
+    )
+    .await?;
+    // Answer any digest auth challenge and send the request once more, note
+    // that we use the final url since the challenge may come after redirects,
+    // but only if those redirects did not lead to some other origin, since
+    // that origin should never get to see anything derived from credentials
+    if let (Some(auth), StatusCode::UNAUTHORIZED) = (&config.auth, res.status()) {
+        let mut authorization = None;
+        if res.url().origin() == origin {
+            // NOTE: Servers may offer several challenges in separate headers,
+            // such as both a basic and a digest challenge, in any order
+            for challenge in res.headers().get_all(WWW_AUTHENTICATE) {
+                let challenge = match challenge.to_str() {
+                    Ok(challenge) => challenge,
+                    Err(_) => continue,
+                };
+                authorization = auth.digest_authorization(challenge, &config.method, res.url())?;
+                if authorization.is_some() {
+                    break;
+                }
+            }
+        }
+        if let Some(authorization) = authorization {
+            headers.push((AUTHORIZATION.to_string(), authorization));
+            // NOTE: The challenge response must be read fully, otherwise its
+            // connection is left busy and can not be reused for the retry
+            let url = res.url().clone();
+            res.bytes().await.ok();
+            res = send_request(
+                lua,
+                &client,
+                &config.method,
+                url,
+                &headers,
+                None,
+                &body,
+                &config.options,
+            )
+            .await?;
+        }
     }
-    let res = request
-        .body(config.body.unwrap_or_default())
-        .send()
-        .await
-        .into_lua_err()?;
     // Extract status, headers
     let res_status = res.status().as_u16();
     let res_status_text = res.status().canonical_reason();
+    span.set_attribute("http.response.status_code", i64::from(res_status));
+    if res_status >= 400 {
+        span.set_error(format!("HTTP {res_status}"));
+    }
     let mut res_headers = res
         .headers()
         .iter()
@@ -92,6 +450,32 @@ where
             )
         })
         .collect::<HashMap<String, String>>();
+    // Event streams are parsed incrementally as the user asks for events
+    if config.options.sse {
+        let events = NetEventStream::new(NetResponseBody::new(res)).into_lua_table(lua)?;
+        return TableBuilder::new(lua)?
+            .with_value("ok", (200..300).contains(&res_status))?
+            .with_value("statusCode", res_status)?
+            .with_value("statusMessage", res_status_text)?
+            .with_value("headers", res_headers)?
+            .with_value("events", events)?
+            .build_readonly();
+    }
+    let res_content_type = res_headers
+        .get(CONTENT_TYPE.as_str())
+        .and_then(|value| ContentType::parse(value));
+    // Streamed bodies are read incrementally by the user, and
+    // never decompressed, so we can return the response right away
+    if config.options.stream {
+        return TableBuilder::new(lua)?
+            .with_value("ok", (200..300).contains(&res_status))?
+            .with_value("statusCode", res_status)?
+            .with_value("statusMessage", res_status_text)?
+            .with_value("headers", res_headers)?
+            .with_value("contentType", res_content_type)?
+            .with_value("body", NetResponseBody::new(res).into_lua_table(lua)?)?
+            .build_readonly();
+    }
     // Read response bytes
     let mut res_bytes = res.bytes().await.into_lua_err()?.to_vec();
     // Check for extra options, decompression
@@ -114,22 +498,151 @@ where
             });
         }
     }
+    // Decode text bodies into utf-8 using their charset, if wanted,
+    // bodies without a charset are assumed to already be utf-8
+    if config.options.decode_text {
+        let label = res_content_type
+            .as_ref()
+            .and_then(ContentType::charset)
+            .unwrap_or("utf-8");
+        let charset = Charset::from_label(label).ok_or_else(|| {
+            LuaError::RuntimeError(format!(
+                "Failed to decode response body - unsupported charset '{label}'"
+            ))
+        })?;
+        res_bytes = charset.decode(&res_bytes).into_bytes();
+    }
     // Construct and return a readonly lua table with results
     TableBuilder::new(lua)?
         .with_value("ok", (200..300).contains(&res_status))?
         .with_value("statusCode", res_status)?
         .with_value("statusMessage", res_status_text)?
         .with_value("headers", res_headers)?
+        .with_value("contentType", res_content_type)?
         .with_value("body", lua.create_string(&res_bytes)?)?
         .build_readonly()
 }
 
-async fn net_socket<'lua>(lua: &'lua Lua, url: String) -> LuaResult<LuaTable>
+/**
+    Sends a request, retrying it using the retry policy in `options`, if any.
+
+    If a network mock is active, the request is answered by the mock instead.
+*/
+#[allow(clippy::too_many_arguments)]
+async fn send_request(
+    lua: &Lua,
+    client: &NetClient,
+    method: &Method,
+    url: Url,
+    headers: &[(String, String)],
+    auth: Option<&RequestAuth>,
+    body: &[u8],
+    options: &RequestConfigOptions,
+) -> LuaResult<Response> {
+    let mock = NetMock::active(lua);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let mut request = client.request(method.clone(), url.clone());
+        for (header, value) in headers {
+            request = request.header(header, value);
+        }
+        if let Some(auth) = auth {
+            request = auth.apply(request);
+        }
+        if let Some(timeout) = options.timeout.total {
+            request = request.timeout(timeout);
+        }
+        let request = request.body(body.to_vec()).build().into_lua_err()?;
+        let mocked = match &mock {
+            Some(mock) => mock.respond_to_request(lua, &request).await?,
+            None => None,
+        };
+        let result = match mocked {
+            Some(res) => Ok(res),
+            None => client.execute(request, options.cookies).await,
+        };
+
+        let delay = match (&options.retry, &result) {
+            (Some(retry), Ok(res)) if attempts <= retry.count => retry
+                .should_retry_status(res.status())
+                .then(|| retry.delay(attempts, Some(res.headers()))),
+            (Some(retry), Err(e)) if attempts <= retry.count => retry
+                .should_retry_error(method, e)
+                .then(|| retry.delay(attempts, None)),
+            _ => None,
+        };
+        match delay {
+            Some(delay) => time::sleep(delay).await,
+            None => {
+                return result.map_err(|e| {
+                    if e.is_timeout() {
+                        LuaError::RuntimeError(format!("Request to '{url}' timed out\n> {e}"))
+                    } else {
+                        LuaError::external(e)
+                    }
+                })
+            }
+        }
+    }
+}
+
+/**
+    Sends a request made by another builtin, using the shared client of this runtime.
+
+    Same as requests made using `net.request`, this is answered by a network mock, if one is active.
+*/
+#[cfg(feature = "roblox")]
+pub(super) async fn send_builtin_request(
+    lua: &Lua,
+    method: Method,
+    url: Url,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> LuaResult<Response> {
+    let client = NetClient::from_registry(lua);
+    let options = RequestConfigOptions::default();
+    send_request(lua, &client, &method, url, headers, None, body, &options).await
+}
+
+async fn net_socket<'lua>(
+    lua: &'lua Lua,
+    (url, options): (String, SocketConfigOptions),
+) -> LuaResult<LuaTable>
 where
     'lua: 'static, // FIXME: Get rid of static lifetime bound here
 {
-    let (ws, _) = tokio_tungstenite::connect_async(url).await.into_lua_err()?;
-    NetWebSocket::new(ws).into_lua_table(lua)
+    if let Some(mock) = NetMock::active(lua) {
+        let parsed = Url::parse(&url).into_lua_err()?;
+        if let Some(ws) = mock.respond_to_socket(lua, &parsed).await? {
+            return NetWebSocket::new(ws).into_lua_table(lua);
+        }
+    }
+    let parsed = Url::parse(&url).into_lua_err()?;
+    let (host, port) = match (parsed.host_str(), parsed.port_or_known_default()) {
+        (Some(host), Some(port)) => (host.trim_matches(&['[', ']'][..]), port),
+        _ => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid web socket url '{url}' - missing host or port"
+            )))
+        }
+    };
+    let dns = NetClient::from_registry(lua).dns();
+    let stream = match options.proxy {
+        None => happy_eyeballs::connect(&dns, host, port, options.ip_version)
+            .await
+            .into_lua_err()
+            .with_context(|_| format!("Failed to connect to {host}:{port}"))?,
+        Some(proxy) => proxy.connect(&dns, host, port).await?,
+    };
+    let (ws, _) = tokio_tungstenite::client_async_tls(url, stream)
+        .await
+        .into_lua_err()?;
+    let mut ws = NetWebSocket::new(ws);
+    if let Some(interval) = options.ping_interval {
+        ws = ws.with_ping_interval(interval);
+    }
+    ws.into_lua_table(lua)
 }
 
 async fn net_serve<'lua>(
@@ -139,13 +652,29 @@ async fn net_serve<'lua>(
 where
     'lua: 'static, // FIXME: Get rid of static lifetime bound here
 {
+    // NOTE: Certificates are loaded before binding so that
+    // the port is not taken if they fail to load or parse
+    let tls = match &config.tls {
+        Some(tls) => Some(create_tls(tls).await?),
+        None => None,
+    };
+
     let sched = lua
         .app_data_ref::<&Scheduler>()
         .expect("Lua struct is missing scheduler");
 
-    let builder = bind_to_localhost(port)?;
+    // NOTE: The local address may have a different port than the one given,
+    // when binding to port 0 the operating system picks a free port for us
+    let incoming = bind_to_address(config.address, port)?;
+    let local_addr = incoming.local_addr();
 
-    create_server(lua, &sched, config, builder)
+    match tls {
+        None => create_server(lua, &sched, config, local_addr, Server::builder(incoming)),
+        Some(tls) => {
+            let incoming = tls_incoming(incoming, tls);
+            create_server(lua, &sched, config, local_addr, Server::builder(incoming))
+        }
+    }
 }
 
 fn net_url_encode<'lua>(
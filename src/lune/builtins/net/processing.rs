@@ -1,10 +1,20 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    cmp::Reverse,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use hyper::{body::to_bytes, Body, Request};
 
 use mlua::prelude::*;
+use tokio::sync::watch;
 
-use crate::lune::util::TableBuilder;
+use crate::lune::{
+    scheduler::Scheduler,
+    util::{traits::LuaEmitErrorExt, TableBuilder},
+};
 
 static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
@@ -20,13 +30,204 @@ impl ProcessedRequestId {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RouteSegment {
+    Static(String),
+    Param(String),
+    Wildcard,
+}
+
+/**
+    A pattern for routes in `net.serve`, such as `"GET /users/:id"`.
+
+    The method is optional, and patterns without one match any method. Segments
+    starting with a colon are parameters that match any single segment, and a
+    trailing `*` matches the rest of the path, including any slashes.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct RoutePattern {
+    method: Option<String>,
+    segments: Vec<RouteSegment>,
+}
+
+impl RoutePattern {
+    pub fn parse(pattern: &str) -> LuaResult<Self> {
+        let pattern = pattern.trim();
+        let (method, path) = match pattern.split_once(char::is_whitespace) {
+            Some((method, path)) => (Some(method.to_ascii_uppercase()), path.trim()),
+            None => (None, pattern),
+        };
+        if !path.starts_with('/') {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid route '{pattern}' - path must start with '/'"
+            )));
+        }
+        let parts = split_path(path).collect::<Vec<_>>();
+        let mut segments = Vec::with_capacity(parts.len());
+        for (index, part) in parts.iter().enumerate() {
+            let segment = if *part == "*" {
+                if index != parts.len() - 1 {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Invalid route '{pattern}' - '*' may only be used as the last segment"
+                    )));
+                }
+                RouteSegment::Wildcard
+            } else if let Some(name) = part.strip_prefix(':') {
+                if name.is_empty() {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Invalid route '{pattern}' - parameters must have a name"
+                    )));
+                }
+                RouteSegment::Param(name.to_string())
+            } else {
+                RouteSegment::Static(part.to_string())
+            };
+            segments.push(segment);
+        }
+        Ok(Self { method, segments })
+    }
+
+    /**
+        How specific this pattern is, where more specific patterns are matched first.
+
+        Static segments are more specific than parameters, which are more
+        specific than wildcards, and patterns with a method are more specific
+        than the same patterns without one.
+    */
+    fn specificity(&self) -> (Vec<u8>, bool) {
+        let ranks = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                RouteSegment::Static(_) => 2,
+                RouteSegment::Param(_) => 1,
+                RouteSegment::Wildcard => 0,
+            })
+            .collect();
+        (ranks, self.method.is_some())
+    }
+
+    /**
+        Matches the given path against this pattern, ignoring the method.
+
+        Returns the values for any parameters in the pattern if the path matched.
+    */
+    fn match_path(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let mut parts = split_path(path);
+        let mut params = Vec::new();
+        for segment in &self.segments {
+            match segment {
+                RouteSegment::Wildcard => {
+                    let rest = parts.collect::<Vec<_>>().join("/");
+                    params.push(("*".to_string(), decode_segment(&rest)));
+                    return Some(params);
+                }
+                RouteSegment::Static(expected) => {
+                    if parts.next()? != expected {
+                        return None;
+                    }
+                }
+                RouteSegment::Param(name) => {
+                    params.push((name.clone(), decode_segment(parts.next()?)));
+                }
+            }
+        }
+        match parts.next() {
+            Some(_) => None,
+            None => Some(params),
+        }
+    }
+}
+
+/**
+    The result of matching a request against a list of routes.
+*/
+#[derive(Debug)]
+pub(super) enum RouteMatch<'a, T> {
+    Found(&'a T),
+    MethodNotAllowed(Vec<String>),
+    NotFound,
+}
+
+/**
+    Sorts routes so that more specific patterns come first, which
+    is the order that they should be matched against requests in.
+*/
+pub(super) fn sort_routes<T>(routes: &mut [(RoutePattern, T)]) {
+    routes.sort_by_key(|(route, _)| Reverse(route.specificity()));
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|part| !part.is_empty())
+}
+
+fn decode_segment(segment: &str) -> String {
+    match urlencoding::decode(segment) {
+        Ok(decoded) => decoded.into_owned(),
+        Err(_) => segment.to_string(),
+    }
+}
+
+/**
+    Marks a request as cancelled once dropped, unless disarmed first.
+
+    This is held by the service future for a request, which hyper
+    drops if the client disconnects before getting a response.
+*/
+pub(super) struct CancelOnDrop(Option<watch::Sender<bool>>);
+
+impl CancelOnDrop {
+    pub fn new() -> (Self, watch::Receiver<bool>) {
+        let (tx, rx) = watch::channel(false);
+        (Self(Some(tx)), rx)
+    }
+
+    /**
+        Disarms this guard, meaning the request will never be marked as cancelled.
+    */
+    pub fn disarm(mut self) {
+        self.0.take();
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(tx) = self.0.take() {
+            tx.send_replace(true);
+        }
+    }
+}
+
+/**
+    Counts a request as in flight for as long as it is held.
+
+    This is held by the service future for a request, so that the server
+    can tell when all requests have been answered while shutting down.
+*/
+pub(super) struct InFlight(Arc<watch::Sender<usize>>);
+
+impl InFlight {
+    pub fn new(counter: &Arc<watch::Sender<usize>>) -> Self {
+        counter.send_modify(|count| *count += 1);
+        Self(Arc::clone(counter))
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.send_modify(|count| *count -= 1);
+    }
+}
+
 pub(super) struct ProcessedRequest {
     pub id: ProcessedRequestId,
+    cancelled: Option<watch::Receiver<bool>>,
     method: String,
     path: String,
     query: Vec<(String, String)>,
     headers: Vec<(String, Vec<u8>)>,
     body: Vec<u8>,
+    params: Vec<(String, String)>,
 }
 
 impl ProcessedRequest {
@@ -68,15 +269,76 @@ impl ProcessedRequest {
 
         Ok(Self {
             id,
+            cancelled: None,
             method,
             path,
             query,
             headers,
             body,
+            params: Vec::new(),
         })
     }
 
-    pub fn into_lua_table(self, lua: &Lua) -> LuaResult<LuaTable> {
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /**
+        Gets the value of the first header with the given name, if any.
+    */
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /**
+        Sets the receiver used to know if the client has disconnected
+        before getting a response, see [`CancelOnDrop`] for details.
+    */
+    pub fn with_cancellation(mut self, cancelled: watch::Receiver<bool>) -> Self {
+        self.cancelled = Some(cancelled);
+        self
+    }
+
+    /**
+        Finds the route that matches this request, storing the values
+        of any path parameters so that they are included in its table.
+
+        Routes must already be sorted using [`sort_routes`].
+    */
+    pub fn route<'a, T>(&mut self, routes: &'a [(RoutePattern, T)]) -> RouteMatch<'a, T> {
+        let mut allowed = Vec::new();
+        for (pattern, value) in routes {
+            let params = match pattern.match_path(&self.path) {
+                Some(params) => params,
+                None => continue,
+            };
+            match &pattern.method {
+                Some(method) if *method != self.method => {
+                    if !allowed.contains(method) {
+                        allowed.push(method.clone());
+                    }
+                }
+                _ => {
+                    self.params = params;
+                    return RouteMatch::Found(value);
+                }
+            }
+        }
+        if allowed.is_empty() {
+            RouteMatch::NotFound
+        } else {
+            RouteMatch::MethodNotAllowed(allowed)
+        }
+    }
+
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable> {
         // FUTURE: Make inner tables for query keys that have multiple values?
         let query = lua.create_table_with_capacity(0, self.query.len())?;
         for (key, value) in self.query.into_iter() {
@@ -90,12 +352,108 @@ impl ProcessedRequest {
 
         let body = lua.create_string(self.body)?;
 
+        let params = lua.create_table_with_capacity(0, self.params.len())?;
+        for (key, value) in self.params.into_iter() {
+            params.set(key, value)?;
+        }
+
+        // NOTE: Requests that were not given a receiver can never be cancelled,
+        // and a receiver whose guard was disarmed will always stay uncancelled
+        let cancelled = self.cancelled.unwrap_or_else(|| watch::channel(false).1);
+        let cancelled_on = cancelled.clone();
+
         TableBuilder::new(lua)?
             .with_value("method", self.method)?
             .with_value("path", self.path)?
             .with_value("query", query)?
             .with_value("headers", headers)?
             .with_value("body", body)?
+            .with_value("params", params)?
+            .with_function("isCancelled", move |_, ()| Ok(*cancelled.borrow()))?
+            .with_function("onCancel", move |_, callback: LuaFunction| {
+                let mut cancelled = cancelled_on.clone();
+                let callback = lua.create_registry_value(callback)?;
+                let sched = *lua
+                    .app_data_ref::<&Scheduler>()
+                    .expect("Lua struct is missing scheduler");
+                sched.spawn_local(async move {
+                    // NOTE: Waiting errors once the request has been answered,
+                    // meaning that it can no longer be cancelled by the client
+                    let is_cancelled = cancelled.wait_for(|c| *c).await.is_ok();
+                    let callback = lua
+                        .registry_value::<LuaFunction>(&callback)
+                        .expect("Failed to get cancel callback from registry");
+                    if is_cancelled {
+                        if let Err(e) = sched.push_back(lua, callback, ()) {
+                            lua.emit_error(e);
+                        }
+                    }
+                });
+                Ok(())
+            })?
             .build_readonly()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> Option<Vec<(String, String)>> {
+        RoutePattern::parse(pattern).unwrap().match_path(path)
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> Option<Vec<(String, String)>> {
+        Some(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn static_routes() {
+        assert_eq!(matches("/", "/"), params(&[]));
+        assert_eq!(matches("/users", "/users/"), params(&[]));
+        assert_eq!(matches("/users", "/users/1"), None);
+        assert_eq!(matches("/users/1", "/users"), None);
+    }
+
+    #[test]
+    fn param_routes() {
+        assert_eq!(
+            matches("GET /users/:id", "/users/42"),
+            params(&[("id", "42")])
+        );
+        assert_eq!(
+            matches("/users/:id/posts/:post", "/users/a%20b/posts/7"),
+            params(&[("id", "a b"), ("post", "7")])
+        );
+        assert_eq!(matches("/users/:id", "/users"), None);
+    }
+
+    #[test]
+    fn wildcard_routes() {
+        assert_eq!(
+            matches("/files/*", "/files/a/b.txt"),
+            params(&[("*", "a/b.txt")])
+        );
+        assert_eq!(matches("/files/*", "/files"), params(&[("*", "")]));
+        assert!(RoutePattern::parse("/files/*/more").is_err());
+    }
+
+    #[test]
+    fn specificity_order() {
+        let mut routes = ["/users/*", "/users/:id", "GET /users/:id", "/users/me"]
+            .iter()
+            .map(|p| (RoutePattern::parse(p).unwrap(), *p))
+            .collect::<Vec<_>>();
+        sort_routes(&mut routes);
+        let order = routes.iter().map(|(_, p)| *p).collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            ["/users/me", "GET /users/:id", "/users/:id", "/users/*"]
+        );
+    }
+}
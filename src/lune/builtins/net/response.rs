@@ -3,6 +3,8 @@ use std::collections::HashMap;
 use hyper::{Body, Response};
 use mlua::prelude::*;
 
+use super::sse::NetResponseStream;
+
 #[derive(Debug, Clone, Copy)]
 pub enum NetServeResponseKind {
     PlainText,
@@ -15,9 +17,47 @@ pub struct NetServeResponse {
     status: u16,
     headers: HashMap<String, Vec<u8>>,
     body: Option<Vec<u8>>,
+    stream: Option<Body>,
 }
 
 impl NetServeResponse {
+    pub fn new(status: u16, headers: HashMap<String, Vec<u8>>, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            kind: NetServeResponseKind::Table,
+            status,
+            headers,
+            body: Some(body.into()),
+            stream: None,
+        }
+    }
+
+    fn from_stream(
+        status: u16,
+        mut headers: HashMap<String, Vec<u8>>,
+        stream: &NetResponseStream,
+    ) -> LuaResult<Self> {
+        let has_header = |headers: &HashMap<String, Vec<u8>>, name: &str| {
+            headers.keys().any(|key| key.eq_ignore_ascii_case(name))
+        };
+        if !has_header(&headers, "Content-Type") {
+            headers.insert("Content-Type".to_string(), b"text/event-stream".to_vec());
+        }
+        if !has_header(&headers, "Cache-Control") {
+            headers.insert("Cache-Control".to_string(), b"no-cache".to_vec());
+        }
+        Ok(Self {
+            kind: NetServeResponseKind::Table,
+            status,
+            headers,
+            body: None,
+            stream: Some(stream.take_body()?),
+        })
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
     pub fn into_response(self) -> LuaResult<Response<Body>> {
         Ok(match self.kind {
             NetServeResponseKind::PlainText => Response::builder()
@@ -30,10 +70,11 @@ impl NetServeResponse {
                 for (key, value) in self.headers {
                     response = response.header(&key, value);
                 }
-                response
-                    .status(self.status)
-                    .body(Body::from(self.body.unwrap_or_default()))
-                    .into_lua_err()?
+                let body = match self.stream {
+                    Some(stream) => stream,
+                    None => Body::from(self.body.unwrap_or_default()),
+                };
+                response.status(self.status).body(body).into_lua_err()?
             }
         })
     }
@@ -48,12 +89,18 @@ impl<'lua> FromLua<'lua> for NetServeResponse {
                 status: 200,
                 headers: HashMap::new(),
                 body: Some(s.as_bytes().to_vec()),
+                stream: None,
             }),
+            // Response streams send events for as long as they are open
+            LuaValue::UserData(ud) if ud.is::<NetResponseStream>() => {
+                let stream = ud.borrow::<NetResponseStream>()?;
+                Self::from_stream(200, HashMap::new(), &stream)
+            }
             // Tables are more detailed responses with potential status, headers, body
             LuaValue::Table(t) => {
                 let status: Option<u16> = t.get("status")?;
                 let headers: Option<LuaTable> = t.get("headers")?;
-                let body: Option<LuaString> = t.get("body")?;
+                let body: LuaValue = t.get("body")?;
 
                 let mut headers_map = HashMap::new();
                 if let Some(headers) = headers {
@@ -63,13 +110,27 @@ impl<'lua> FromLua<'lua> for NetServeResponse {
                     }
                 }
 
-                let body_bytes = body.map(|s| s.as_bytes().to_vec());
+                let body_bytes = match body {
+                    LuaValue::Nil => None,
+                    LuaValue::String(s) => Some(s.as_bytes().to_vec()),
+                    LuaValue::UserData(ud) if ud.is::<NetResponseStream>() => {
+                        let stream = ud.borrow::<NetResponseStream>()?;
+                        return Self::from_stream(status.unwrap_or(200), headers_map, &stream);
+                    }
+                    value => {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Invalid response body - expected string or response stream, got {}",
+                            value.type_name()
+                        )))
+                    }
+                };
 
                 Ok(Self {
                     kind: NetServeResponseKind::Table,
                     status: status.unwrap_or(200),
                     headers: headers_map,
                     body: body_bytes,
+                    stream: None,
                 })
             }
             // Anything else is an error
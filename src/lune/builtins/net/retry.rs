@@ -0,0 +1,195 @@
+use std::time::{Duration, SystemTime};
+
+use hyper::{header::RETRY_AFTER, HeaderMap, Method, StatusCode};
+use mlua::prelude::*;
+
+const DEFAULT_RETRY_ON: [u16; 2] = [429, 503];
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+
+/**
+    Maximum amount of time to wait between two retries, no matter what
+    the backoff or any `Retry-After` header given by the server says.
+*/
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// Net request timeouts
+
+#[derive(Debug, Clone, Default)]
+pub struct RequestTimeout {
+    pub connect: Option<Duration>,
+    pub total: Option<Duration>,
+}
+
+impl<'lua> FromLua<'lua> for RequestTimeout {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            // A single number is a shorthand for the total timeout
+            LuaValue::Integer(_) | LuaValue::Number(_) => Ok(Self {
+                connect: None,
+                total: Some(parse_seconds(value, "timeout")?),
+            }),
+            LuaValue::Table(tab) => {
+                let connect = match tab.raw_get::<_, LuaValue>("connect")? {
+                    LuaValue::Nil => None,
+                    value => Some(parse_seconds(value, "timeout.connect")?),
+                };
+                let total = match tab.raw_get::<_, LuaValue>("total")? {
+                    LuaValue::Nil => None,
+                    value => Some(parse_seconds(value, "timeout.total")?),
+                };
+                Ok(Self { connect, total })
+            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "RequestTimeout",
+                message: Some(format!(
+                    "Invalid option value for 'timeout' in request config options - expected number or table, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+// Net request retry policy
+
+#[derive(Debug, Clone)]
+pub struct RequestRetry {
+    pub count: u32,
+    pub backoff: Duration,
+    pub retry_on: Vec<u16>,
+    pub retry_non_idempotent: bool,
+}
+
+impl RequestRetry {
+    /**
+        Checks if a request that got a response with the given status should be retried.
+    */
+    pub fn should_retry_status(&self, status: StatusCode) -> bool {
+        self.retry_on.contains(&status.as_u16())
+    }
+
+    /**
+        Checks if a request with the given method that failed with the given error should be retried.
+
+        Only errors where the request most likely never reached
+        the server, or where the server never responded, are retried.
+
+        A timeout may happen after the server has already handled the request, so
+        requests with non-idempotent methods such as `POST` are not retried after
+        timing out, unless explicitly enabled, since that could repeat their effects.
+    */
+    pub fn should_retry_error(&self, method: &Method, error: &reqwest::Error) -> bool {
+        error.is_connect()
+            || (error.is_timeout() && (method.is_idempotent() || self.retry_non_idempotent))
+    }
+
+    /**
+        Gets the amount of time to wait before sending the request again,
+        after the given number of attempts, which must be at least one.
+
+        The backoff doubles for each attempt, unless the server told
+        us how long to wait using the `Retry-After` header.
+    */
+    pub fn delay(&self, attempts: u32, headers: Option<&HeaderMap>) -> Duration {
+        let retry_after = headers
+            .and_then(|headers| headers.get(RETRY_AFTER))
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        let delay = match retry_after {
+            Some(retry_after) => retry_after,
+            None => self
+                .backoff
+                .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1))),
+        };
+        delay.min(MAX_BACKOFF)
+    }
+}
+
+/**
+    Parses the value of a `Retry-After` header into the amount of time to wait,
+    which may be given either as a number of seconds, or as an http date.
+
+    The returned delay is capped to the maximum amount of time to wait.
+*/
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let delay = match value.parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .unwrap_or_default(),
+    };
+    Some(delay.min(MAX_BACKOFF))
+}
+
+impl<'lua> FromLua<'lua> for RequestRetry {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let tab = match value {
+            LuaValue::Table(tab) => tab,
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "RequestRetry",
+                    message: Some(format!(
+                        "Invalid option value for 'retry' in request config options - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        let count = match tab.raw_get::<_, Option<u32>>("count") {
+            Ok(Some(count)) => count,
+            _ => {
+                return Err(LuaError::RuntimeError(
+                    "Missing or invalid 'count' in request retry options".to_string(),
+                ))
+            }
+        };
+        let backoff = match tab.raw_get::<_, LuaValue>("backoffSeconds")? {
+            LuaValue::Nil => DEFAULT_BACKOFF,
+            value => parse_seconds(value, "retry.backoffSeconds")?,
+        };
+        let retry_on = match tab.raw_get::<_, LuaValue>("retryOn")? {
+            LuaValue::Nil => DEFAULT_RETRY_ON.to_vec(),
+            value => Vec::<u16>::from_lua(value, lua).map_err(|_| {
+                LuaError::RuntimeError(
+                    "Invalid 'retryOn' in request retry options - expected array of status codes"
+                        .to_string(),
+                )
+            })?,
+        };
+        let retry_non_idempotent = match tab.raw_get::<_, LuaValue>("retryNonIdempotent")? {
+            LuaValue::Nil => false,
+            LuaValue::Boolean(b) => b,
+            _ => {
+                return Err(LuaError::RuntimeError(
+                    "Invalid 'retryNonIdempotent' in request retry options - expected boolean"
+                        .to_string(),
+                ))
+            }
+        };
+        Ok(Self {
+            count,
+            backoff,
+            retry_on,
+            retry_non_idempotent,
+        })
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn parse_seconds(value: LuaValue, name: &str) -> LuaResult<Duration> {
+    let secs = match value {
+        LuaValue::Integer(i) => i as f64,
+        LuaValue::Number(n) => n,
+        _ => f64::NAN,
+    };
+    Duration::try_from_secs_f64(secs).map_err(|_| {
+        LuaError::RuntimeError(format!(
+            "Invalid option value for '{name}' in request config options - \
+            expected a non-negative number of seconds"
+        ))
+    })
+}
@@ -1,58 +1,101 @@
-use std::{collections::HashMap, convert::Infallible, net::SocketAddr, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    error::Error,
+    io,
+    net::SocketAddr,
+    rc::Rc,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
 
 use hyper::{
-    server::{conn::AddrIncoming, Builder},
+    server::{accept::Accept, conn::AddrIncoming, Builder},
     service::{make_service_fn, service_fn},
-    Server,
 };
 
+use futures_util::{future, stream::FuturesUnordered, FutureExt, StreamExt};
 use hyper_tungstenite::{is_upgrade_request, upgrade, HyperWebsocket};
 use mlua::prelude::*;
-use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{mpsc, oneshot, watch, Mutex},
+    time,
+};
 
 use crate::lune::{
-    scheduler::Scheduler,
+    builtins::trace::{AutoSpan, SpanContext, SpanKind},
+    scheduler::{Scheduler, SchedulerThreadId},
     util::{traits::LuaEmitErrorExt, TableBuilder},
 };
 
 use super::{
-    config::ServeConfig, processing::ProcessedRequest, response::NetServeResponse,
+    config::{ServeAddress, ServeConfig},
+    processing::{CancelOnDrop, InFlight, ProcessedRequest, ProcessedRequestId, RouteMatch},
+    response::NetServeResponse,
     websocket::NetWebSocket,
 };
 
-pub(super) fn bind_to_localhost(port: u16) -> LuaResult<Builder<AddrIncoming>> {
-    let addr = match SocketAddr::try_from(([127, 0, 0, 1], port)) {
-        Ok(a) => a,
+pub(super) fn bind_to_address(address: ServeAddress, port: u16) -> LuaResult<AddrIncoming> {
+    let addr = address.with_port(port)?;
+    match AddrIncoming::bind(&addr) {
+        Ok(b) => Ok(b),
         Err(e) => {
-            return Err(LuaError::external(format!(
-                "Failed to bind to localhost on port {port}\n{e}"
+            let hint = match e.source().and_then(|s| s.downcast_ref::<io::Error>()) {
+                Some(io_err) => match io_err.kind() {
+                    io::ErrorKind::AddrInUse => {
+                        "\n> Is another process already listening on this port?"
+                    }
+                    io::ErrorKind::AddrNotAvailable => {
+                        "\n> This address does not belong to any network interface on this machine"
+                    }
+                    io::ErrorKind::PermissionDenied => {
+                        "\n> Binding to ports below 1024 may require elevated privileges"
+                    }
+                    _ => "",
+                },
+                None => "",
+            };
+            Err(LuaError::external(format!(
+                "Failed to bind to address {addr}\n{}{hint}",
+                e.to_string()
+                    .replace("error creating server listener: ", "> ")
             )))
         }
-    };
-    match Server::try_bind(&addr) {
-        Ok(b) => Ok(b),
-        Err(e) => Err(LuaError::external(format!(
-            "Failed to bind to localhost on port {port}\n{}",
-            e.to_string()
-                .replace("error creating server listener: ", "> ")
-        ))),
     }
 }
 
-pub(super) fn create_server<'lua>(
+pub(super) fn create_server<'lua, I>(
     lua: &'lua Lua,
     sched: &'lua Scheduler,
     config: ServeConfig<'lua>,
-    builder: Builder<AddrIncoming>,
+    local_addr: SocketAddr,
+    builder: Builder<I>,
 ) -> LuaResult<LuaTable<'lua>>
 where
     'lua: 'static, // FIXME: Get rid of static lifetime bound here
+    I: Accept + Send + 'static,
+    I::Conn: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    I::Error: Into<Box<dyn Error + Send + Sync>>,
 {
     // Note that we need to use a mpsc here and not
     // a oneshot channel since we move the sender
     // into our table with the stop function
     let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
 
+    // The stopped channel is set once the server has fully shut down,
+    // after all of the requests that were in flight have been answered
+    let (stopped_tx, stopped_rx) = watch::channel(false);
+    let (draining_tx, draining_rx) = oneshot::channel::<()>();
+    let in_flight = Arc::new(watch::channel(0usize).0);
+    let mut in_flight_rx = in_flight.subscribe();
+
+    // Threads that are currently handling a request or web socket, these
+    // may not wait for the server to stop since that would never happen
+    let active_handlers = Arc::new(StdMutex::new(HashSet::<SchedulerThreadId>::new()));
+    let active_handlers_lua = Arc::clone(&active_handlers);
+
     // Communicate between background thread(s) and main lua thread using mpsc and oneshot
     let (tx_request, mut rx_request) = mpsc::channel::<ProcessedRequest>(64);
     let (tx_websocket, mut rx_websocket) = mpsc::channel::<HyperWebsocket>(64);
@@ -63,6 +106,11 @@ where
     let response_senders_bg = Arc::clone(&response_senders);
     let response_senders_lua = Arc::clone(&response_senders_bg);
 
+    // The request handler may be swapped out while the server is running using `update`,
+    // requests that are already being handled will keep using the handler they started with
+    let handle_request = Rc::new(RefCell::new(config.handle_request.clone()));
+    let handle_request_update = Rc::clone(&handle_request);
+
     // Create our background service which will accept
     // requests, do some processing, then forward to lua
     let has_websocket_handler = config.handle_web_socket.is_some();
@@ -70,12 +118,15 @@ where
         let tx_request = Arc::clone(&tx_request_arc);
         let tx_websocket = Arc::clone(&tx_websocket_arc);
         let response_senders = Arc::clone(&response_senders_bg);
+        let in_flight = Arc::clone(&in_flight);
 
         let handler = service_fn(move |mut req| {
             let tx_request = Arc::clone(&tx_request);
             let tx_websocket = Arc::clone(&tx_websocket);
             let response_senders = Arc::clone(&response_senders);
+            let in_flight = InFlight::new(&in_flight);
             async move {
+                let _in_flight = in_flight;
                 // FUTURE: Improve error messages when lua is busy and queue is full
                 if has_websocket_handler && is_upgrade_request(&req) {
                     let (response, ws) = match upgrade(&mut req, None) {
@@ -87,17 +138,27 @@ where
                     }
                     Ok(response)
                 } else {
-                    let processed = ProcessedRequest::from_request(req).await?;
+                    // NOTE: If the client disconnects before getting a response, hyper
+                    // drops this future, and the guard will cancel the lua request
+                    let (cancel_guard, cancelled) = CancelOnDrop::new();
+                    let processed = ProcessedRequest::from_request(req)
+                        .await?
+                        .with_cancellation(cancelled);
                     let request_id = processed.id;
-                    if (tx_request.send(processed).await).is_err() {
-                        return Err(LuaError::runtime("Lua handler is busy"));
-                    }
+                    // NOTE: The response channel must be registered before the request
+                    // is sent, since lua may answer it without ever yielding in between
                     let (response_tx, response_rx) = oneshot::channel::<NetServeResponse>();
                     response_senders
                         .lock()
                         .await
                         .insert(request_id, response_tx);
-                    match response_rx.await {
+                    if (tx_request.send(processed).await).is_err() {
+                        response_senders.lock().await.remove(&request_id);
+                        return Err(LuaError::runtime("Lua handler is busy"));
+                    }
+                    let response = response_rx.await;
+                    cancel_guard.disarm();
+                    match response {
                         Err(_) => Err(LuaError::runtime("Internal Server Error")),
                         Ok(r) => r.into_response(),
                     }
@@ -110,94 +171,319 @@ where
 
     // Start up our service
     sched.spawn(async move {
-        let result = builder
+        let server = builder
             .http1_only(true) // Web sockets can only use http1
             .http1_keepalive(true) // Web sockets must be kept alive
             .serve(hyper_make_service)
             .with_graceful_shutdown(async move {
                 shutdown_rx.recv().await;
+                draining_tx.send(()).ok();
             });
-        if let Err(e) = result.await {
-            eprintln!("Net serve error: {e}")
+        // NOTE: Graceful shutdown also waits for idle connections that never
+        // sent a request to close, which clients may keep open indefinitely,
+        // so the server stops once every request in flight has been answered
+        let drained = async move {
+            match draining_rx.await {
+                Ok(_) => {
+                    let _ = in_flight_rx.wait_for(|count| *count == 0).await;
+                }
+                Err(_) => future::pending().await,
+            }
+        };
+        tokio::select! {
+            result = server => {
+                if let Err(e) = result {
+                    eprintln!("Net serve error: {e}")
+                }
+            }
+            _ = drained => {}
         }
+        stopped_tx.send_replace(true);
     });
 
     // Spawn a local thread with access to lua and the same lifetime
+    let mut stopped_rx_local = stopped_rx.clone();
     sched.spawn_local(async move {
+        let config = &config;
+        let handle_request = &handle_request;
+        let response_senders = &response_senders_lua;
+        let active_handlers = &active_handlers_lua;
+
+        // NOTE: Handlers are driven here instead of awaited one at a time,
+        // so that a slow handler does not hold up any other requests
+        let mut handlers = FuturesUnordered::new();
         loop {
             // Wait for either a request or a websocket to handle,
             // if we got neither it means both channels were dropped
             // and our server has stopped, either gracefully or panic
+            // NOTE: Connections that are left open after stopping may keep
+            // the channels alive, so we also need to watch for the server
+            // stopping, since it will not send us any more requests
             let (req, sock) = tokio::select! {
                 req = rx_request.recv() => (req, None),
                 sock = rx_websocket.recv() => (None, sock),
-            };
-
-            // NOTE: The closure here is not really necessary, we
-            // make the closure so that we can use the `?` operator
-            let handle_req_or_sock = || async {
-                match (req, sock) {
-                    (None, None) => Ok::<_, LuaError>(true),
-                    (Some(req), _) => {
-                        let req_id = req.id;
-                        let req_handler = config.handle_request.clone();
-                        let req_table = req.into_lua_table(lua)?;
-
-                        let thread_id = sched.push_back(lua, req_handler, req_table)?;
-                        let thread_res = sched.wait_for_thread(lua, thread_id).await?;
-
-                        let response = NetServeResponse::from_lua_multi(thread_res, lua)?;
-                        let response_sender = response_senders_lua
-                            .lock()
-                            .await
-                            .remove(&req_id)
-                            .expect("Response channel was removed unexpectedly");
-
-                        // NOTE: We ignore the error here, if the sender is no longer
-                        // being listened to its because our client disconnected during
-                        // handler being called, which is fine and should not emit errors
-                        response_sender.send(response).ok();
-
-                        Ok(false)
-                    }
-                    (_, Some(sock)) => {
-                        let sock = sock.await.into_lua_err()?;
-
-                        let sock_handler = config
-                            .handle_web_socket
-                            .as_ref()
-                            .cloned()
-                            .expect("Got web socket but web socket handler is missing");
-                        let sock_table = NetWebSocket::new(sock).into_lua_table(lua)?;
-
-                        // NOTE: Web socket handler does not need to send any
-                        // response back, the websocket upgrade response is
-                        // automatically sent above in the background thread(s)
-                        let thread_id = sched.push_back(lua, sock_handler, sock_table)?;
-                        let _thread_res = sched.wait_for_thread(lua, thread_id).await?;
-
-                        Ok(false)
+                _ = stopped_rx_local.wait_for(|stopped| *stopped) => (None, None),
+                Some(res) = handlers.next() => {
+                    if let Err(e) = res {
+                        lua.emit_error(e);
                     }
+                    continue;
                 }
             };
 
-            match handle_req_or_sock().await {
-                Ok(true) => break,
-                Ok(false) => continue,
-                Err(e) => lua.emit_error(e),
+            match (req, sock) {
+                (None, None) => break,
+                (Some(req), _) => handlers.push(
+                    handle_req(
+                        lua,
+                        sched,
+                        config,
+                        handle_request,
+                        response_senders,
+                        active_handlers,
+                        req,
+                    )
+                    .boxed_local(),
+                ),
+                (_, Some(sock)) => handlers
+                    .push(handle_sock(lua, sched, config, active_handlers, sock).boxed_local()),
+            }
+        }
+
+        // Requests that were in flight when the server stopped must still be answered
+        while let Some(res) = handlers.next().await {
+            if let Err(e) = res {
+                lua.emit_error(e);
             }
         }
     });
 
     // Create a new read-only table that contains methods
     // for manipulating server behavior and shutting it down
-    let handle_stop = move |_, _: ()| match shutdown_tx.try_send(()) {
-        Ok(_) => Ok(()),
-        Err(_) => Err(LuaError::RuntimeError(
-            "Server has already been stopped".to_string(),
-        )),
+    let active_handlers_stop = Arc::clone(&active_handlers);
+    let stopped_rx_stop = stopped_rx.clone();
+    let handle_stop = move |lua: &'lua Lua, _: ()| {
+        let result = match shutdown_tx.try_send(()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(LuaError::RuntimeError(
+                "Server has already been stopped".to_string(),
+            )),
+        };
+        // NOTE: Handlers stopping their own server can not wait for in-flight
+        // requests to drain, since they are one of the requests in flight
+        let is_handler = is_current_thread_handler(lua, &active_handlers_stop);
+        let stopped_rx = stopped_rx_stop.clone();
+        async move {
+            result?;
+            if !is_handler {
+                wait_for_stopped(stopped_rx).await;
+            }
+            Ok(())
+        }
+    };
+    let handle_join = move |lua: &'lua Lua, _: ()| {
+        let result = if is_current_thread_handler(lua, &active_handlers) {
+            Err(LuaError::RuntimeError(
+                "Server can not be joined from inside one of its own handlers".to_string(),
+            ))
+        } else {
+            Ok(())
+        };
+        let stopped_rx = stopped_rx.clone();
+        async move {
+            result?;
+            wait_for_stopped(stopped_rx).await;
+            Ok(())
+        }
+    };
+    let handle_update = move |_: &'lua Lua, handler: LuaFunction<'lua>| {
+        handle_request_update.replace(handler);
+        Ok(())
     };
     TableBuilder::new(lua)?
-        .with_function("stop", handle_stop)?
+        .with_value("ip", local_addr.ip().to_string())?
+        .with_value("port", local_addr.port())?
+        .with_async_function("stop", handle_stop)?
+        .with_async_function("join", handle_join)?
+        .with_function("update", handle_update)?
         .build_readonly()
 }
+
+/**
+    Handles a single request using the handler for its route, and sends the response back.
+*/
+async fn handle_req<'lua>(
+    lua: &'static Lua,
+    sched: &'lua Scheduler<'lua>,
+    config: &ServeConfig<'lua>,
+    handle_request: &RefCell<LuaFunction<'lua>>,
+    response_senders: &Mutex<HashMap<ProcessedRequestId, oneshot::Sender<NetServeResponse>>>,
+    active_handlers: &StdMutex<HashSet<SchedulerThreadId>>,
+    mut req: ProcessedRequest,
+) -> LuaResult<()> {
+    let req_id = req.id;
+    let mut span = create_handler_span(lua, &req);
+    let req_handler = match req.route(&config.routes) {
+        RouteMatch::Found(route) => Ok((
+            route.handler.clone(),
+            route.timeout.or(config.handler_timeout),
+        )),
+        RouteMatch::NotFound => Ok((handle_request.borrow().clone(), config.handler_timeout)),
+        RouteMatch::MethodNotAllowed(allowed) => Err(allowed),
+    };
+
+    // NOTE: The client must always get a response, even if something
+    // went wrong here, otherwise its connection would hang forever
+    let response = async {
+        Ok::<_, LuaError>(match req_handler {
+            Ok((req_handler, timeout)) => {
+                let req_table = req.into_lua_table(lua)?;
+                let thread = lua.create_thread(req_handler)?;
+                span.enter(&thread);
+                sched.push_back(lua, thread.clone(), req_table)?;
+                let thread_res =
+                    wait_for_handler(lua, sched, active_handlers, &thread, timeout).await;
+                if let Err(e) = &thread_res {
+                    span.set_error(e.to_string());
+                }
+                match thread_res {
+                    Ok(Some(values)) => NetServeResponse::from_lua_multi(values, lua)?,
+                    Ok(None) => config.create_timeout_response(lua)?,
+                    // NOTE: Handler errors have already been emitted by the scheduler
+                    Err(_) => create_error_response(),
+                }
+            }
+            Err(allowed) => NetServeResponse::new(
+                405,
+                HashMap::from([("Allow".to_string(), allowed.join(", ").into_bytes())]),
+                "Method Not Allowed",
+            ),
+        })
+    }
+    .await
+    .unwrap_or_else(|e| {
+        lua.emit_error(e);
+        create_error_response()
+    });
+    let status = response.status();
+    span.set_attribute("http.response.status_code", i64::from(status));
+    if status >= 500 {
+        span.set_error(format!("HTTP {status}"));
+    }
+    drop(span);
+    let response_sender = response_senders
+        .lock()
+        .await
+        .remove(&req_id)
+        .expect("Response channel was removed unexpectedly");
+
+    // NOTE: We ignore the error here, if the sender is no longer
+    // being listened to its because our client disconnected during
+    // handler being called, which is fine and should not emit errors
+    response_sender.send(response).ok();
+
+    Ok(())
+}
+
+/**
+    Handles a single web socket using the web socket handler.
+*/
+async fn handle_sock<'lua>(
+    lua: &'static Lua,
+    sched: &'lua Scheduler<'lua>,
+    config: &ServeConfig<'lua>,
+    active_handlers: &StdMutex<HashSet<SchedulerThreadId>>,
+    sock: HyperWebsocket,
+) -> LuaResult<()> {
+    let sock = sock.await.into_lua_err()?;
+
+    let sock_handler = config
+        .handle_web_socket
+        .as_ref()
+        .cloned()
+        .expect("Got web socket but web socket handler is missing");
+    let sock_table = NetWebSocket::new(sock).into_lua_table(lua)?;
+
+    // NOTE: Web socket handler does not need to send any
+    // response back, the websocket upgrade response is
+    // automatically sent above in the background thread(s)
+    // NOTE: Web sockets may stay open for as long as they want,
+    // so the handler timeout only applies to http request handlers
+    let thread = lua.create_thread(sock_handler)?;
+    sched.push_back(lua, thread.clone(), sock_table)?;
+    wait_for_handler(lua, sched, active_handlers, &thread, None).await?;
+
+    Ok(())
+}
+
+fn create_error_response() -> NetServeResponse {
+    NetServeResponse::new(500, HashMap::new(), "Internal Server Error")
+}
+
+/**
+    Creates the span for handling a request, continuing the trace
+    that the client is a part of, if it sent a `traceparent` header.
+*/
+fn create_handler_span(lua: &'static Lua, req: &ProcessedRequest) -> AutoSpan {
+    let parent = req
+        .header("traceparent")
+        .and_then(SpanContext::from_traceparent);
+    let mut span = AutoSpan::start_with_parent(lua, req.method(), SpanKind::Server, parent);
+    span.set_attribute("http.request.method", req.method());
+    span.set_attribute("url.path", req.path());
+    span
+}
+
+/**
+    Waits for the given handler thread to finish, and returns its result.
+
+    If the handler does not finish before the given `timeout`, it is
+    cancelled using the scheduler, and `None` is returned instead.
+*/
+async fn wait_for_handler<'lua>(
+    lua: &'lua Lua,
+    sched: &'lua Scheduler<'lua>,
+    active_handlers: &StdMutex<HashSet<SchedulerThreadId>>,
+    thread: &LuaThread<'lua>,
+    timeout: Option<Duration>,
+) -> LuaResult<Option<LuaMultiValue<'lua>>> {
+    let thread_id = SchedulerThreadId::from(thread);
+    // NOTE: We must subscribe to the result right away, since the
+    // handler may finish before this future is polled again
+    let finished = sched.subscribe_to_thread(lua, thread_id)?;
+    active_handlers
+        .lock()
+        .expect("Failed to lock active handlers")
+        .insert(thread_id);
+    let result = match timeout {
+        None => Some(finished.await),
+        Some(timeout) => time::timeout(timeout, finished).await.ok(),
+    };
+    active_handlers
+        .lock()
+        .expect("Failed to lock active handlers")
+        .remove(&thread_id);
+    match result {
+        Some(result) => result.map(Some),
+        None => {
+            sched.cancel_thread(lua, thread.clone())?;
+            Ok(None)
+        }
+    }
+}
+
+fn is_current_thread_handler(
+    lua: &Lua,
+    active_handlers: &StdMutex<HashSet<SchedulerThreadId>>,
+) -> bool {
+    let thread_id = SchedulerThreadId::from(&lua.current_thread());
+    active_handlers
+        .lock()
+        .expect("Failed to lock active handlers")
+        .contains(&thread_id)
+}
+
+async fn wait_for_stopped(mut stopped_rx: watch::Receiver<bool>) {
+    // NOTE: If the sender was dropped the server task is gone, meaning it has stopped
+    let _ = stopped_rx.wait_for(|stopped| *stopped).await;
+}
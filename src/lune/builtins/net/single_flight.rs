@@ -0,0 +1,141 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use mlua::prelude::*;
+use tokio::sync::watch;
+
+use crate::lune::scheduler::{Scheduler, SchedulerThreadId};
+
+/**
+    The values returned by a function that was called by `net.singleFlight`,
+    stored in the registry so that they can be shared with all waiting threads.
+*/
+#[derive(Debug)]
+struct FlightValues {
+    key: LuaRegistryKey,
+    count: usize,
+}
+
+type FlightResult = Arc<Result<FlightValues, LuaError>>;
+type FlightReceiver = watch::Receiver<Option<FlightResult>>;
+
+/**
+    Coalesces concurrent calls with the same key into a single call, whose results are shared.
+*/
+#[derive(Debug, Default)]
+pub struct SingleFlight {
+    in_flight: Mutex<HashMap<String, FlightReceiver>>,
+}
+
+impl SingleFlight {
+    fn in_flight(&self) -> MutexGuard<HashMap<String, FlightReceiver>> {
+        self.in_flight
+            .lock()
+            .expect("Failed to lock single flight map")
+    }
+
+    /**
+        Calls the given function, unless a call with the same key is already
+        in flight, in which case its results are waited for and returned instead.
+    */
+    pub async fn call<'lua>(
+        &self,
+        lua: &'lua Lua,
+        key: String,
+        func: LuaFunction<'lua>,
+    ) -> LuaResult<LuaMultiValue<'lua>> {
+        // NOTE: We must check and insert while holding the lock,
+        // otherwise two callers could both become the leader
+        let leader = {
+            let mut in_flight = self.in_flight();
+            match in_flight.get(&key) {
+                Some(rx) => Err(rx.clone()),
+                None => {
+                    let (tx, rx) = watch::channel(None);
+                    in_flight.insert(key.clone(), rx);
+                    Ok(tx)
+                }
+            }
+        };
+        let tx = match leader {
+            Ok(tx) => tx,
+            Err(rx) => return wait_for_result(lua, &key, rx).await,
+        };
+
+        // NOTE: The guard removes the key even if this thread is cancelled
+        // while waiting, in which case waiting threads receive an error
+        let guard = FlightGuard {
+            flight: self,
+            key: &key,
+        };
+
+        let sched = *lua
+            .app_data_ref::<&Scheduler>()
+            .expect("Lua struct is missing scheduler");
+        let result = match lua.create_thread(func) {
+            Ok(thread) => {
+                // NOTE: Errors are instead rethrown to every caller waiting for the flight
+                sched.set_thread_errors_handled(SchedulerThreadId::from(&thread), true);
+                match sched.push_back(lua, thread, ()) {
+                    Ok(thread_id) => sched.wait_for_thread(lua, thread_id).await,
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        };
+
+        drop(guard);
+        let shared = match &result {
+            Ok(values) => {
+                let count = values.len();
+                let table = lua.create_sequence_from(values.clone())?;
+                Ok(FlightValues {
+                    key: lua.create_registry_value(table)?,
+                    count,
+                })
+            }
+            Err(e) => Err(e.clone()),
+        };
+        tx.send_replace(Some(Arc::new(shared)));
+
+        result
+    }
+}
+
+struct FlightGuard<'a> {
+    flight: &'a SingleFlight,
+    key: &'a str,
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        self.flight.in_flight().remove(self.key);
+    }
+}
+
+async fn wait_for_result<'lua>(
+    lua: &'lua Lua,
+    key: &str,
+    mut rx: FlightReceiver,
+) -> LuaResult<LuaMultiValue<'lua>> {
+    let result = match rx.wait_for(Option::is_some).await {
+        Ok(result) => Arc::clone(result.as_ref().expect("Flight result is missing")),
+        Err(_) => {
+            return Err(LuaError::RuntimeError(format!(
+                "Single flight for key '{key}' was cancelled"
+            )))
+        }
+    };
+    match result.as_ref() {
+        Ok(values) => {
+            let table = lua.registry_value::<LuaTable>(&values.key)?;
+            (1..=values.count)
+                .map(|index| table.raw_get::<_, LuaValue>(index))
+                .collect::<LuaResult<Vec<_>>>()
+                .map(LuaMultiValue::from_vec)
+        }
+        Err(e) => Err(e.clone()),
+    }
+}
@@ -0,0 +1,406 @@
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    sync::{Arc, Mutex},
+};
+
+use futures_util::stream;
+use hyper::{body::Bytes, Body};
+use mlua::prelude::*;
+use tokio::sync::{mpsc, Mutex as AsyncMutex};
+
+use crate::lune::util::TableBuilder;
+
+use super::body::NetResponseBody;
+
+const DEFAULT_EVENT_TYPE: &str = "message";
+
+/**
+    A single server-sent event, see the HTML living standard section 9.2.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    pub event: String,
+    pub data: String,
+    pub id: Option<String>,
+    pub retry: Option<u64>,
+}
+
+impl SseEvent {
+    /**
+        Encodes the event in the `text/event-stream` format, ready to be sent to a client.
+    */
+    pub fn encode(&self) -> String {
+        let mut encoded = String::new();
+        if let Some(id) = &self.id {
+            encoded.push_str(&format!("id: {id}\n"));
+        }
+        if self.event != DEFAULT_EVENT_TYPE {
+            encoded.push_str(&format!("event: {}\n", self.event));
+        }
+        if let Some(retry) = self.retry {
+            encoded.push_str(&format!("retry: {retry}\n"));
+        }
+        // NOTE: Each line of data needs its own field, and
+        // the client joins them back together using newlines
+        for line in self.data.replace("\r\n", "\n").split(['\n', '\r']) {
+            encoded.push_str(&format!("data: {line}\n"));
+        }
+        encoded.push('\n');
+        encoded
+    }
+}
+
+impl<'lua> FromLua<'lua> for SseEvent {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let (event, data, id, retry) = match &value {
+            LuaValue::String(s) => (None, s.to_str()?.to_string(), None, None),
+            LuaValue::Table(t) => (
+                t.raw_get::<_, Option<String>>("event")?,
+                t.raw_get::<_, Option<String>>("data")?.unwrap_or_default(),
+                t.raw_get::<_, Option<String>>("id")?,
+                t.raw_get::<_, Option<u64>>("retry")?,
+            ),
+            _ => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "SseEvent",
+                    message: Some(format!(
+                        "Invalid event - expected string or table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        // Newlines in the event type or id would start new fields, which is never intended
+        for (name, field) in [("event", &event), ("id", &id)] {
+            if field.as_ref().is_some_and(|f| f.contains(['\n', '\r'])) {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid event - '{name}' must not contain newlines"
+                )));
+            }
+        }
+        Ok(Self {
+            event: event.unwrap_or_else(|| DEFAULT_EVENT_TYPE.to_string()),
+            data,
+            id,
+            retry,
+        })
+    }
+}
+
+impl<'lua> IntoLua<'lua> for SseEvent {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        TableBuilder::new(lua)?
+            .with_value("event", self.event)?
+            .with_value("data", self.data)?
+            .with_value("id", self.id)?
+            .with_value("retry", self.retry)?
+            .build_readonly()
+            .map(LuaValue::Table)
+    }
+}
+
+/**
+    An incremental parser for the `text/event-stream` format,
+    which accepts chunks split at any byte boundary.
+*/
+#[derive(Debug, Default)]
+struct SseParser {
+    started: bool,
+    line: Vec<u8>,
+    last_was_cr: bool,
+    event: Option<String>,
+    data: String,
+    last_event_id: Option<String>,
+    retry: Option<u64>,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        for &byte in chunk {
+            // A line may end with CRLF, so a LF directly after a CR is skipped
+            if self.last_was_cr && byte == b'\n' {
+                self.last_was_cr = false;
+                continue;
+            }
+            self.last_was_cr = byte == b'\r';
+            if byte == b'\n' || byte == b'\r' {
+                let line = std::mem::take(&mut self.line);
+                if let Some(event) = self.process_line(&line) {
+                    events.push(event);
+                }
+            } else {
+                self.line.push(byte);
+            }
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Option<SseEvent> {
+        let mut line = String::from_utf8_lossy(line);
+        if !self.started {
+            self.started = true;
+            if let Some(stripped) = line.strip_prefix('\u{FEFF}') {
+                line = stripped.to_string().into();
+            }
+        }
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None; // Comment
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line.as_ref(), ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                self.retry = value.parse().ok();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        let retry = self.retry.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut data = std::mem::take(&mut self.data);
+        data.pop(); // Trailing newline
+        Some(SseEvent {
+            event: match event {
+                Some(event) if !event.is_empty() => event,
+                _ => DEFAULT_EVENT_TYPE.to_string(),
+            },
+            data,
+            id: self.last_event_id.clone().filter(|id| !id.is_empty()),
+            retry,
+        })
+    }
+}
+
+/**
+    A stream of server-sent events, read incrementally from a response body.
+*/
+pub struct NetEventStream {
+    body: NetResponseBody,
+    parser: SseParser,
+    pending: VecDeque<SseEvent>,
+}
+
+impl NetEventStream {
+    pub fn new(body: NetResponseBody) -> Self {
+        Self {
+            body,
+            parser: SseParser::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /**
+        Waits for the next event, returning `None` once the stream has ended.
+
+        An event that was not fully received before the stream ended is discarded.
+    */
+    pub async fn next(&mut self) -> LuaResult<Option<SseEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            match self.body.read(None).await? {
+                Some(chunk) => self.pending.extend(self.parser.push(&chunk)),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /**
+        Creates a Lua table for this event stream, with a single `next` method
+        that yields until the next event is available, or returns `nil` when done.
+    */
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable> {
+        let stream = Arc::new(AsyncMutex::new(self));
+        TableBuilder::new(lua)?
+            .with_async_function("next", move |lua, _: LuaValue| {
+                let stream = Arc::clone(&stream);
+                async move {
+                    match stream.lock().await.next().await? {
+                        Some(event) => event.into_lua(lua),
+                        None => Ok(LuaValue::Nil),
+                    }
+                }
+            })?
+            .build_readonly()
+    }
+}
+
+/**
+    A streaming response for `net.serve` handlers, that sends
+    server-sent events to the client for as long as it is open.
+*/
+#[derive(Debug)]
+pub struct NetResponseStream {
+    sender: Mutex<Option<mpsc::UnboundedSender<Bytes>>>,
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<Bytes>>>,
+}
+
+impl NetResponseStream {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            sender: Mutex::new(Some(sender)),
+            receiver: Mutex::new(Some(receiver)),
+        }
+    }
+
+    /**
+        Queues bytes to be sent to the client.
+
+        Returns `false` if the stream was closed, or if the client disconnected.
+    */
+    fn send(&self, bytes: impl Into<Bytes>) -> bool {
+        match self
+            .sender
+            .lock()
+            .expect("Failed to lock response stream")
+            .as_ref()
+        {
+            Some(sender) => sender.send(bytes.into()).is_ok(),
+            None => false,
+        }
+    }
+
+    /**
+        Closes the stream, ending the response once all queued events have been sent.
+    */
+    fn close(&self) {
+        self.sender
+            .lock()
+            .expect("Failed to lock response stream")
+            .take();
+    }
+
+    /**
+        Takes the body of this stream, to be sent as a response.
+
+        Errors if the stream was already returned from a handler.
+    */
+    pub fn take_body(&self) -> LuaResult<Body> {
+        let mut receiver = self
+            .receiver
+            .lock()
+            .expect("Failed to lock response stream")
+            .take()
+            .ok_or_else(|| {
+                LuaError::RuntimeError(
+                    "Response stream has already been used for a response".to_string(),
+                )
+            })?;
+        Ok(Body::wrap_stream(stream::poll_fn(move |cx| {
+            receiver
+                .poll_recv(cx)
+                .map(|bytes| bytes.map(Ok::<_, Infallible>))
+        })))
+    }
+}
+
+impl Default for NetResponseStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LuaUserData for NetResponseStream {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("send", |_, this, event: SseEvent| {
+            Ok(this.send(event.encode()))
+        });
+        methods.add_method("comment", |_, this, comment: Option<String>| {
+            // NOTE: Comments are ignored by clients, but keep idle connections alive
+            let comment = comment.unwrap_or_default().replace(['\r', '\n'], " ");
+            Ok(this.send(format!(": {comment}\n\n")))
+        });
+        methods.add_method("close", |_, this, ()| {
+            this.close();
+            Ok(())
+        });
+    }
+}
+
+pub fn net_create_response_stream(_: &Lua, _: ()) -> LuaResult<NetResponseStream> {
+    Ok(NetResponseStream::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event: &str, data: &str, id: Option<&str>) -> SseEvent {
+        SseEvent {
+            event: event.to_string(),
+            data: data.to_string(),
+            id: id.map(ToString::to_string),
+            retry: None,
+        }
+    }
+
+    #[test]
+    fn parses_events() {
+        let mut parser = SseParser::default();
+        let events = parser.push(
+            b"\xEF\xBB\xBFdata: first\n\n: comment\nevent: update\nid: 1\ndata: a\ndata:b\n\n",
+        );
+        assert_eq!(
+            events,
+            vec![
+                event("message", "first", None),
+                event("update", "a\nb", Some("1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_split_chunks_and_line_endings() {
+        let mut parser = SseParser::default();
+        let mut events = parser.push(b"data: one\r");
+        events.extend(parser.push(b"\n\r\ndata: tw"));
+        events.extend(parser.push(b"o\r\r"));
+        assert_eq!(
+            events,
+            vec![event("message", "one", None), event("message", "two", None)]
+        );
+    }
+
+    #[test]
+    fn ignores_events_without_data() {
+        let mut parser = SseParser::default();
+        let events = parser.push(b"event: empty\n\nid: 5\n\ndata: x\n\n");
+        assert_eq!(events, vec![event("message", "x", Some("5"))]);
+    }
+
+    #[test]
+    fn encodes_events() {
+        let mut encoded = event("update", "a\nb", Some("1"));
+        encoded.retry = Some(1000);
+        assert_eq!(
+            encoded.encode(),
+            "id: 1\nevent: update\nretry: 1000\ndata: a\ndata: b\n\n"
+        );
+        assert_eq!(event("message", "", None).encode(), "data: \n\n");
+
+        let mut parser = SseParser::default();
+        assert_eq!(parser.push(encoded.encode().as_bytes()), vec![encoded]);
+    }
+}
@@ -0,0 +1,456 @@
+use std::{path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use mlua::prelude::*;
+use russh::{
+    client::{self, Handle},
+    ChannelMsg,
+};
+use russh_keys::key::{KeyPair, PublicKey};
+use russh_sftp::client::SftpSession;
+use tokio::{
+    io::{copy_bidirectional, AsyncWriteExt},
+    net::TcpListener,
+    sync::{watch, Mutex as AsyncMutex},
+};
+
+use crate::lune::util::TableBuilder;
+
+use super::{
+    client::NetClient, happy_eyeballs, happy_eyeballs::IpVersion, util::strip_ipv6_brackets,
+};
+
+const DEFAULT_PORT: u16 = 22;
+
+/**
+    How to authenticate with an SSH server, using either a password or a private key.
+*/
+#[derive(Debug, Clone)]
+pub enum SshAuth {
+    Password(String),
+    PrivateKey {
+        key: String,
+        passphrase: Option<String>,
+    },
+    KeyFile {
+        path: PathBuf,
+        passphrase: Option<String>,
+    },
+}
+
+impl SshAuth {
+    fn key_pair(&self) -> LuaResult<Option<KeyPair>> {
+        let key_pair = match self {
+            Self::Password(_) => return Ok(None),
+            Self::PrivateKey { key, passphrase } => {
+                russh_keys::decode_secret_key(key, passphrase.as_deref())
+            }
+            Self::KeyFile { path, passphrase } => {
+                russh_keys::load_secret_key(path, passphrase.as_deref())
+            }
+        };
+        match key_pair {
+            Ok(key_pair) => Ok(Some(key_pair)),
+            Err(e) => Err(LuaError::RuntimeError(format!(
+                "Failed to read private key for ssh authentication - {e}"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SshConfig {
+    user: String,
+    port: u16,
+    auth: SshAuth,
+    host_fingerprint: Option<String>,
+    accept_unknown_hosts: bool,
+}
+
+impl<'lua> FromLua<'lua> for SshConfig {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let tab = match value {
+            LuaValue::Table(tab) => tab,
+            value => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "SshConfig",
+                    message: Some(format!(
+                        "Invalid ssh config - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        let user = match tab.get::<_, Option<String>>("user")? {
+            Some(user) => user,
+            None => {
+                return Err(LuaError::RuntimeError(
+                    "Invalid ssh config - missing 'user'".to_string(),
+                ))
+            }
+        };
+        let auth = match tab.get::<_, Option<LuaTable>>("auth")? {
+            None => {
+                return Err(LuaError::RuntimeError(
+                    "Invalid ssh config - missing 'auth'".to_string(),
+                ))
+            }
+            Some(auth) => {
+                let passphrase = auth.get::<_, Option<String>>("passphrase")?;
+                match (
+                    auth.get::<_, Option<String>>("password")?,
+                    auth.get::<_, Option<String>>("privateKey")?,
+                    auth.get::<_, Option<String>>("keyFile")?,
+                ) {
+                    (Some(password), None, None) => SshAuth::Password(password),
+                    (None, Some(key), None) => SshAuth::PrivateKey { key, passphrase },
+                    (None, None, Some(path)) => SshAuth::KeyFile {
+                        path: PathBuf::from(path),
+                        passphrase,
+                    },
+                    _ => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid ssh config - 'auth' must contain exactly one of 'password', 'privateKey' or 'keyFile'"
+                                .to_string(),
+                        ))
+                    }
+                }
+            }
+        };
+        Ok(Self {
+            user,
+            port: tab.get::<_, Option<u16>>("port")?.unwrap_or(DEFAULT_PORT),
+            auth,
+            host_fingerprint: tab.get("hostFingerprint")?,
+            accept_unknown_hosts: tab
+                .get::<_, Option<bool>>("acceptUnknownHosts")?
+                .unwrap_or(false),
+        })
+    }
+}
+
+/**
+    Checks server keys, either against a known fingerprint, or using the
+    `known_hosts` file for the current user, the same as the `ssh` command.
+*/
+struct SshHandler {
+    host: String,
+    port: u16,
+    host_fingerprint: Option<String>,
+    accept_unknown_hosts: bool,
+}
+
+#[async_trait]
+impl client::Handler for SshHandler {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        self,
+        server_public_key: &PublicKey,
+    ) -> Result<(Self, bool), Self::Error> {
+        let fingerprint = server_public_key.fingerprint();
+        let accepted = if let Some(expected) = &self.host_fingerprint {
+            let expected = expected.trim().trim_start_matches("SHA256:");
+            expected == fingerprint
+        } else {
+            match russh_keys::check_known_hosts(&self.host, self.port, server_public_key) {
+                Ok(true) => true,
+                Ok(false) => self.accept_unknown_hosts,
+                // NOTE: The key for this host has changed, which should never be accepted
+                Err(_) => false,
+            }
+        };
+        Ok((self, accepted))
+    }
+}
+
+/**
+    An authenticated SSH session, created using `net.ssh.connect`.
+*/
+pub struct NetSshSession {
+    handle: Handle<SshHandler>,
+}
+
+impl NetSshSession {
+    async fn connect(lua: &Lua, host: String, config: SshConfig) -> LuaResult<Self> {
+        let dns = NetClient::from_registry(lua).dns();
+        let stream = happy_eyeballs::connect(&dns, &host, config.port, IpVersion::Any)
+            .await
+            .into_lua_err()
+            .with_context(|_| format!("Failed to connect to {host}:{}", config.port))?;
+        let handler = SshHandler {
+            host: host.clone(),
+            port: config.port,
+            host_fingerprint: config.host_fingerprint.clone(),
+            accept_unknown_hosts: config.accept_unknown_hosts,
+        };
+        let mut handle = client::connect_stream(Arc::new(client::Config::default()), stream, handler)
+            .await
+            .map_err(|e| match e {
+                russh::Error::UnknownKey => LuaError::RuntimeError(format!(
+                    "Host key for {host} was not trusted - it did not match the given fingerprint, or was not found in known_hosts"
+                )),
+                e => LuaError::external(e),
+            })?;
+
+        let authenticated = match (&config.auth, config.auth.key_pair()?) {
+            (_, Some(key_pair)) => handle
+                .authenticate_publickey(&config.user, Arc::new(key_pair))
+                .await
+                .into_lua_err()?,
+            (SshAuth::Password(password), None) => handle
+                .authenticate_password(&config.user, password)
+                .await
+                .into_lua_err()?,
+            (_, None) => false,
+        };
+        if !authenticated {
+            return Err(LuaError::RuntimeError(format!(
+                "Failed to authenticate with {host} as '{}'",
+                config.user
+            )));
+        }
+
+        Ok(Self { handle })
+    }
+
+    /**
+        Runs a command on the server, and waits for it to exit.
+    */
+    async fn exec(&self, command: &str) -> LuaResult<(u32, Vec<u8>, Vec<u8>)> {
+        let mut channel = self.handle.channel_open_session().await.into_lua_err()?;
+        channel.exec(true, command).await.into_lua_err()?;
+        let (mut code, mut stdout, mut stderr) = (None, Vec::new(), Vec::new());
+        while let Some(msg) = channel.wait().await {
+            match msg {
+                ChannelMsg::Data { ref data } => stdout.extend_from_slice(data),
+                ChannelMsg::ExtendedData { ref data, ext: 1 } => stderr.extend_from_slice(data),
+                ChannelMsg::ExitStatus { exit_status } => code = Some(exit_status),
+                _ => {}
+            }
+        }
+        match code {
+            Some(code) => Ok((code, stdout, stderr)),
+            None => Err(LuaError::RuntimeError(format!(
+                "Command '{command}' exited without a status"
+            ))),
+        }
+    }
+
+    async fn sftp(&self) -> LuaResult<SftpSession> {
+        let channel = self.handle.channel_open_session().await.into_lua_err()?;
+        channel
+            .request_subsystem(true, "sftp")
+            .await
+            .into_lua_err()?;
+        SftpSession::new(channel.into_stream()).await.into_lua_err()
+    }
+
+    async fn close(&self) -> LuaResult<()> {
+        self.handle
+            .disconnect(russh::Disconnect::ByApplication, "", "en")
+            .await
+            .into_lua_err()
+    }
+
+    /**
+        Creates a Lua table for this session, with `exec`, `forward`, `sftp` and `close` methods.
+    */
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable> {
+        let session = Arc::new(self);
+        let session_exec = Arc::clone(&session);
+        let session_forward = Arc::clone(&session);
+        let session_sftp = Arc::clone(&session);
+        let session_close = Arc::clone(&session);
+        TableBuilder::new(lua)?
+            .with_async_function("exec", move |lua, (_, command): (LuaValue, String)| {
+                let session = Arc::clone(&session_exec);
+                async move {
+                    let (code, stdout, stderr) = session.exec(&command).await?;
+                    TableBuilder::new(lua)?
+                        .with_value("ok", code == 0)?
+                        .with_value("code", code)?
+                        .with_value("stdout", lua.create_string(&stdout)?)?
+                        .with_value("stderr", lua.create_string(&stderr)?)?
+                        .build_readonly()
+                }
+            })?
+            .with_async_function(
+                "forward",
+                move |lua, (_, local_port, remote_host, remote_port): (LuaValue, u16, String, u16)| {
+                    let session = Arc::clone(&session_forward);
+                    async move { forward(lua, session, local_port, remote_host, remote_port).await }
+                },
+            )?
+            .with_async_function("sftp", move |lua, _: LuaValue| {
+                let session = Arc::clone(&session_sftp);
+                async move { create_sftp_table(lua, session.sftp().await?) }
+            })?
+            .with_async_function("close", move |_, _: LuaValue| {
+                let session = Arc::clone(&session_close);
+                async move { session.close().await }
+            })?
+            .build_readonly()
+    }
+}
+
+/**
+    Forwards connections to a local port through the session, to
+    a host and port that can be reached from the remote server.
+*/
+async fn forward(
+    lua: &'static Lua,
+    session: Arc<NetSshSession>,
+    local_port: u16,
+    remote_host: String,
+    remote_port: u16,
+) -> LuaResult<LuaTable<'static>> {
+    let listener = TcpListener::bind(("127.0.0.1", local_port))
+        .await
+        .into_lua_err()?;
+    let bound_port = listener.local_addr().into_lua_err()?.port();
+    let (stop_tx, mut stop_rx) = watch::channel(false);
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, addr) = tokio::select! {
+                res = listener.accept() => match res {
+                    Ok(accepted) => accepted,
+                    Err(_) => continue,
+                },
+                _ = stop_rx.changed() => break,
+            };
+            let session = Arc::clone(&session);
+            let remote_host = remote_host.clone();
+            let mut stop_rx = stop_rx.clone();
+            tokio::spawn(async move {
+                let channel = session
+                    .handle
+                    .channel_open_direct_tcpip(
+                        remote_host,
+                        u32::from(remote_port),
+                        addr.ip().to_string(),
+                        u32::from(addr.port()),
+                    )
+                    .await;
+                if let Ok(channel) = channel {
+                    let mut channel = channel.into_stream();
+                    tokio::select! {
+                        _ = copy_bidirectional(&mut stream, &mut channel) => {}
+                        _ = stop_rx.changed() => {}
+                    }
+                }
+            });
+        }
+    });
+
+    TableBuilder::new(lua)?
+        .with_value("port", bound_port)?
+        .with_function("close", move |_, _: LuaValue| {
+            stop_tx.send_replace(true);
+            Ok(())
+        })?
+        .build_readonly()
+}
+
+fn create_sftp_table(lua: &'static Lua, sftp: SftpSession) -> LuaResult<LuaTable> {
+    let sftp = Arc::new(AsyncMutex::new(Some(sftp)));
+    let sftp_read = Arc::clone(&sftp);
+    let sftp_write = Arc::clone(&sftp);
+    let sftp_read_dir = Arc::clone(&sftp);
+    let sftp_write_dir = Arc::clone(&sftp);
+    let sftp_remove_file = Arc::clone(&sftp);
+    let sftp_remove_dir = Arc::clone(&sftp);
+    let sftp_close = Arc::clone(&sftp);
+    TableBuilder::new(lua)?
+        .with_async_function("readFile", move |lua, (_, path): (LuaValue, String)| {
+            let sftp = Arc::clone(&sftp_read);
+            async move {
+                let sftp = sftp.lock().await;
+                let contents = sftp_session(&sftp)?.read(path).await.into_lua_err()?;
+                lua.create_string(contents)
+            }
+        })?
+        .with_async_function(
+            "writeFile",
+            move |_, (_, path, contents): (LuaValue, String, LuaString)| {
+                let sftp = Arc::clone(&sftp_write);
+                let contents = contents.as_bytes().to_vec();
+                async move {
+                    let sftp = sftp.lock().await;
+                    // NOTE: SftpSession::write only opens the file for writing, which
+                    // fails for new files, and does not truncate existing ones
+                    let mut file = sftp_session(&sftp)?.create(path).await.into_lua_err()?;
+                    file.write_all(&contents).await.into_lua_err()?;
+                    file.shutdown().await.into_lua_err()
+                }
+            },
+        )?
+        .with_async_function("readDir", move |_, (_, path): (LuaValue, String)| {
+            let sftp = Arc::clone(&sftp_read_dir);
+            async move {
+                let sftp = sftp.lock().await;
+                let entries = sftp_session(&sftp)?.read_dir(path).await.into_lua_err()?;
+                Ok(entries
+                    .map(|entry| entry.file_name())
+                    .filter(|name| name != "." && name != "..")
+                    .collect::<Vec<_>>())
+            }
+        })?
+        .with_async_function("writeDir", move |_, (_, path): (LuaValue, String)| {
+            let sftp = Arc::clone(&sftp_write_dir);
+            async move {
+                let sftp = sftp.lock().await;
+                sftp_session(&sftp)?.create_dir(path).await.into_lua_err()
+            }
+        })?
+        .with_async_function("removeFile", move |_, (_, path): (LuaValue, String)| {
+            let sftp = Arc::clone(&sftp_remove_file);
+            async move {
+                let sftp = sftp.lock().await;
+                sftp_session(&sftp)?.remove_file(path).await.into_lua_err()
+            }
+        })?
+        .with_async_function("removeDir", move |_, (_, path): (LuaValue, String)| {
+            let sftp = Arc::clone(&sftp_remove_dir);
+            async move {
+                let sftp = sftp.lock().await;
+                sftp_session(&sftp)?.remove_dir(path).await.into_lua_err()
+            }
+        })?
+        .with_async_function("close", move |_, _: LuaValue| {
+            let sftp = Arc::clone(&sftp_close);
+            async move {
+                if let Some(sftp) = sftp.lock().await.take() {
+                    sftp.close().await.into_lua_err()?;
+                }
+                Ok(())
+            }
+        })?
+        .build_readonly()
+}
+
+fn sftp_session(sftp: &Option<SftpSession>) -> LuaResult<&SftpSession> {
+    sftp.as_ref()
+        .ok_or_else(|| LuaError::RuntimeError("SFTP session is closed".to_string()))
+}
+
+/**
+    Creates the `net.ssh` table, for connecting to SSH servers.
+*/
+pub fn create_ssh_table(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("connect", net_ssh_connect)?
+        .build_readonly()
+}
+
+async fn net_ssh_connect(
+    lua: &'static Lua,
+    (host, config): (String, SshConfig),
+) -> LuaResult<LuaTable> {
+    let host = strip_ipv6_brackets(&host).to_string();
+    NetSshSession::connect(lua, host, config)
+        .await?
+        .into_lua_table(lua)
+}
@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use mlua::prelude::*;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    sync::{watch, Mutex as AsyncMutex},
+};
+
+use crate::lune::util::TableBuilder;
+
+use super::{
+    client::NetClient, config::SocketConfigOptions, happy_eyeballs, util::strip_ipv6_brackets,
+};
+
+/**
+    Amount of bytes to read at most when no maximum size is given to `read`.
+*/
+const DEFAULT_READ_SIZE: usize = 8 * 1024;
+
+/**
+    A raw TCP client socket, created using `net.tcp.connect`.
+
+    The read and write halves are locked separately, so that one
+    thread may wait for data while another thread is writing.
+*/
+#[derive(Debug)]
+pub struct NetTcpSocket {
+    read_half: AsyncMutex<Option<OwnedReadHalf>>,
+    write_half: AsyncMutex<Option<OwnedWriteHalf>>,
+    closed: watch::Sender<bool>,
+}
+
+impl NetTcpSocket {
+    pub fn new(stream: TcpStream) -> Self {
+        let (read_half, write_half) = stream.into_split();
+        Self {
+            read_half: AsyncMutex::new(Some(read_half)),
+            write_half: AsyncMutex::new(Some(write_half)),
+            closed: watch::channel(false).0,
+        }
+    }
+
+    /**
+        Reads at most `max_size` bytes from the socket, waiting until at least one byte is available.
+
+        Returns `None` once the connection has been closed, by either end.
+    */
+    pub async fn read(&self, max_size: usize) -> LuaResult<Option<Vec<u8>>> {
+        // NOTE: We subscribe before locking, so that closing the socket
+        // while a read is waiting for data also stops the pending read
+        let mut closed = self.closed.subscribe();
+        let mut read_half = self.read_half.lock().await;
+        let read_half = match read_half.as_mut() {
+            Some(read_half) if !*closed.borrow_and_update() => read_half,
+            _ => return Err(closed_error()),
+        };
+        let mut buf = vec![0; max_size];
+        let size = tokio::select! {
+            res = read_half.read(&mut buf) => res.into_lua_err()?,
+            _ = closed.changed() => return Ok(None),
+        };
+        if size == 0 {
+            Ok(None)
+        } else {
+            buf.truncate(size);
+            Ok(Some(buf))
+        }
+    }
+
+    /**
+        Writes all of the given bytes to the socket.
+    */
+    pub async fn write(&self, bytes: &[u8]) -> LuaResult<()> {
+        let mut write_half = self.write_half.lock().await;
+        let write_half = match write_half.as_mut() {
+            Some(write_half) => write_half,
+            None => return Err(closed_error()),
+        };
+        write_half.write_all(bytes).await.into_lua_err()?;
+        write_half.flush().await.into_lua_err()
+    }
+
+    /**
+        Closes the socket, after which it may no longer be read from or written to.
+
+        Closing a socket that has already been closed does nothing.
+    */
+    pub async fn close(&self) -> LuaResult<()> {
+        self.closed.send_replace(true);
+        if let Some(mut write_half) = self.write_half.lock().await.take() {
+            // NOTE: Shutting down lets the other end know that we are done
+            // writing, the connection itself is closed once both halves drop
+            write_half.shutdown().await.into_lua_err()?;
+        }
+        self.read_half.lock().await.take();
+        Ok(())
+    }
+
+    /**
+        Creates a Lua table for this socket, with `read`, `write` and `close` methods.
+    */
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable> {
+        let socket = Arc::new(self);
+        let socket_read = Arc::clone(&socket);
+        let socket_write = Arc::clone(&socket);
+        let socket_close = Arc::clone(&socket);
+        TableBuilder::new(lua)?
+            .with_async_function(
+                "read",
+                move |lua, (_, max_size): (LuaValue, Option<usize>)| {
+                    let socket = Arc::clone(&socket_read);
+                    async move {
+                        if max_size == Some(0) {
+                            return Err(LuaError::RuntimeError(
+                                "Read size must be greater than zero".to_string(),
+                            ));
+                        }
+                        match socket.read(max_size.unwrap_or(DEFAULT_READ_SIZE)).await? {
+                            Some(bytes) => Ok(LuaValue::String(lua.create_string(bytes)?)),
+                            None => Ok(LuaValue::Nil),
+                        }
+                    }
+                },
+            )?
+            .with_async_function("write", move |_, (_, bytes): (LuaValue, LuaString)| {
+                let socket = Arc::clone(&socket_write);
+                let bytes = bytes.as_bytes().to_vec();
+                async move { socket.write(&bytes).await }
+            })?
+            .with_async_function("close", move |_, _: LuaValue| {
+                let socket = Arc::clone(&socket_close);
+                async move { socket.close().await }
+            })?
+            .build_readonly()
+    }
+}
+
+/**
+    Creates the `net.tcp` table, for connecting raw TCP sockets.
+*/
+pub fn create_tcp_table(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("connect", net_tcp_connect)?
+        .build_readonly()
+}
+
+async fn net_tcp_connect(
+    lua: &'static Lua,
+    (host, port, options): (String, u16, SocketConfigOptions),
+) -> LuaResult<LuaTable> {
+    // NOTE: Ipv6 hosts may be given in brackets, same as in urls
+    let host = strip_ipv6_brackets(&host);
+    let dns = NetClient::from_registry(lua).dns();
+    let stream = match options.proxy {
+        Some(proxy) => proxy.connect(&dns, host, port).await?,
+        None => happy_eyeballs::connect(&dns, host, port, options.ip_version)
+            .await
+            .into_lua_err()
+            .with_context(|_| format!("Failed to connect to {host}:{port}"))?,
+    };
+    // NOTE: Small writes such as commands in text protocols should
+    // be sent right away instead of being delayed and batched
+    stream.set_nodelay(true).into_lua_err()?;
+    NetTcpSocket::new(stream).into_lua_table(lua)
+}
+
+fn closed_error() -> LuaError {
+    LuaError::RuntimeError("Socket is closed".to_string())
+}
@@ -0,0 +1,173 @@
+use std::{io, path::Path, pin::Pin, sync::Arc};
+
+use futures_util::{stream, StreamExt};
+use hyper::server::{
+    accept::{self, Accept},
+    conn::{AddrIncoming, AddrStream},
+};
+use mlua::prelude::*;
+use tokio::fs;
+use tokio_rustls::{
+    rustls::{Certificate, PrivateKey, ServerConfig},
+    server::TlsStream,
+    TlsAcceptor,
+};
+
+use super::config::ServeTlsConfig;
+
+#[cfg(feature = "acme")]
+use super::acme::{create_acme_config, AcmeRenewal};
+
+/**
+    Maximum number of TLS handshakes that may be in progress at once.
+
+    Handshakes happen concurrently, so that one slow client
+    can not block any other clients from connecting.
+*/
+const MAX_CONCURRENT_HANDSHAKES: usize = 64;
+
+/**
+    Protocol negotiated by certificate authorities for TLS-ALPN-01 challenges.
+*/
+pub(super) const ACME_TLS_ALPN: &[u8] = b"acme-tls/1";
+
+/**
+    A TLS acceptor for a server, along with anything that must
+    keep running for as long as the server accepts connections.
+*/
+pub(super) struct ServeTls {
+    acceptor: TlsAcceptor,
+    #[cfg(feature = "acme")]
+    _renewal: Option<AcmeRenewal>,
+}
+
+impl ServeTls {
+    fn accept(&self, conn: AddrStream) -> tokio_rustls::Accept<AddrStream> {
+        self.acceptor.accept(conn)
+    }
+}
+
+/**
+    Creates a TLS acceptor using either the certificate chain and private key
+    at the paths in the given config, or certificates from an ACME server.
+
+    NOTE: Certificates from files are read once and used for the lifetime of the server,
+    while certificates from ACME are renewed and swapped out while the server is running.
+*/
+pub(super) async fn create_tls(config: &ServeTlsConfig) -> LuaResult<ServeTls> {
+    match config {
+        ServeTlsConfig::Files {
+            cert_path,
+            key_path,
+        } => {
+            let certs = read_certs(cert_path).await?;
+            let key = read_private_key(key_path).await?;
+
+            let mut server_config = ServerConfig::builder()
+                .with_safe_defaults()
+                .with_no_client_auth()
+                .with_single_cert(certs, key)
+                .map_err(|e| {
+                    LuaError::RuntimeError(format!(
+                        "Failed to use certificate at '{}' with private key at '{}'\n> {e}",
+                        cert_path.display(),
+                        key_path.display()
+                    ))
+                })?;
+            // NOTE: Web sockets can only use http1, same as our server builder
+            server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+            Ok(ServeTls {
+                acceptor: TlsAcceptor::from(Arc::new(server_config)),
+                #[cfg(feature = "acme")]
+                _renewal: None,
+            })
+        }
+        #[cfg(feature = "acme")]
+        ServeTlsConfig::Acme(acme) => {
+            let (mut server_config, renewal) = create_acme_config(acme).await?;
+            server_config.alpn_protocols = vec![b"http/1.1".to_vec(), ACME_TLS_ALPN.to_vec()];
+
+            Ok(ServeTls {
+                acceptor: TlsAcceptor::from(Arc::new(server_config)),
+                _renewal: Some(renewal),
+            })
+        }
+    }
+}
+
+/**
+    Wraps incoming connections so that they are accepted using TLS.
+
+    Connections that fail their TLS handshake are silently dropped,
+    and will not stop the server from accepting other connections.
+    The same goes for connections used for TLS-ALPN-01 challenges,
+    which are done once the handshake has completed.
+*/
+pub(super) fn tls_incoming(
+    mut incoming: AddrIncoming,
+    tls: ServeTls,
+) -> impl Accept<Conn = TlsStream<AddrStream>, Error = io::Error> {
+    // NOTE: The stream owns the acceptor, and with it any certificate renewal,
+    // which is dropped once the server stops accepting new connections
+    let connections = stream::poll_fn(move |cx| Pin::new(&mut incoming).poll_accept(cx))
+        .filter_map(|conn| async move { conn.ok() })
+        .map(move |conn| tls.accept(conn))
+        .buffer_unordered(MAX_CONCURRENT_HANDSHAKES)
+        .filter_map(|conn| async move {
+            conn.ok()
+                .filter(|conn| conn.get_ref().1.alpn_protocol() != Some(ACME_TLS_ALPN))
+                .map(Ok)
+        });
+    accept::from_stream(connections)
+}
+
+pub(super) async fn read_certs(path: &Path) -> LuaResult<Vec<Certificate>> {
+    let contents = read_pem_file(path, "certificate").await?;
+    let certs = rustls_pemfile::certs(&mut contents.as_slice()).map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to parse certificate at '{}'\n> {e}",
+            path.display()
+        ))
+    })?;
+    if certs.is_empty() {
+        return Err(LuaError::RuntimeError(format!(
+            "Failed to parse certificate at '{}'\n> No certificates were found in the file",
+            path.display()
+        )));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+pub(super) async fn read_private_key(path: &Path) -> LuaResult<PrivateKey> {
+    let contents = read_pem_file(path, "private key").await?;
+    let items = rustls_pemfile::read_all(&mut contents.as_slice()).map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to parse private key at '{}'\n> {e}",
+            path.display()
+        ))
+    })?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            LuaError::RuntimeError(format!(
+                "Failed to parse private key at '{}'\n> No private keys were found in the file",
+                path.display()
+            ))
+        })
+}
+
+async fn read_pem_file(path: &Path, kind: &str) -> LuaResult<Vec<u8>> {
+    fs::read(path).await.map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to read {kind} at '{}'\n> {e}",
+            path.display()
+        ))
+    })
+}
@@ -0,0 +1,239 @@
+use std::{
+    future::Future,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Mutex},
+};
+
+use mlua::prelude::*;
+use tokio::{
+    net::{lookup_host, UdpSocket},
+    sync::watch,
+};
+
+use crate::lune::util::TableBuilder;
+
+use super::util::strip_ipv6_brackets;
+
+/**
+    Maximum size of a single datagram, and the amount
+    of bytes to receive when no maximum size is given.
+*/
+const MAX_DATAGRAM_SIZE: usize = 65_535;
+
+/**
+    The contents of a received datagram, and the address that it was sent from.
+*/
+type Datagram = (Vec<u8>, SocketAddr);
+
+/**
+    A UDP socket, created using `net.udp.bind` or `net.udp.connect`.
+*/
+#[derive(Debug)]
+pub struct NetUdpSocket {
+    socket: Mutex<Option<Arc<UdpSocket>>>,
+    closed: watch::Sender<bool>,
+}
+
+impl NetUdpSocket {
+    pub fn new(socket: UdpSocket) -> Self {
+        Self {
+            socket: Mutex::new(Some(Arc::new(socket))),
+            closed: watch::channel(false).0,
+        }
+    }
+
+    fn socket(&self) -> LuaResult<Arc<UdpSocket>> {
+        match self
+            .socket
+            .lock()
+            .expect("Failed to lock udp socket")
+            .as_ref()
+        {
+            Some(socket) => Ok(Arc::clone(socket)),
+            None => Err(closed_error()),
+        }
+    }
+
+    /**
+        Sends a datagram to the address that the socket is connected to.
+    */
+    pub async fn send(&self, bytes: &[u8]) -> LuaResult<()> {
+        let socket = self.socket()?;
+        if socket.peer_addr().is_err() {
+            return Err(LuaError::RuntimeError(
+                "Socket is not connected - use sendTo to send to a specific address".to_string(),
+            ));
+        }
+        socket.send(bytes).await.into_lua_err()?;
+        Ok(())
+    }
+
+    /**
+        Sends a datagram to the given host and port.
+    */
+    pub async fn send_to(&self, bytes: &[u8], host: &str, port: u16) -> LuaResult<()> {
+        let socket = self.socket()?;
+        let local = socket.local_addr().into_lua_err()?;
+        let target = resolve(host, port, local.is_ipv6()).await?;
+        socket.send_to(bytes, target).await.into_lua_err()?;
+        Ok(())
+    }
+
+    /**
+        Waits for the next datagram, returning at most `max_size` bytes
+        of it, together with the address that it was sent from.
+
+        Returns `None` if the socket was closed while waiting.
+
+        Note that the socket is checked right away, and not once the returned future
+        is first polled, so that closing the socket after calling this stops the receive.
+    */
+    pub fn receive(
+        &self,
+        max_size: usize,
+    ) -> LuaResult<impl Future<Output = LuaResult<Option<Datagram>>>> {
+        let mut closed = self.closed.subscribe();
+        let socket = self.socket()?;
+        Ok(async move {
+            let mut buf = vec![0; max_size];
+            let (size, from) = tokio::select! {
+                res = socket.recv_from(&mut buf) => res.into_lua_err()?,
+                _ = closed.changed() => return Ok(None),
+            };
+            buf.truncate(size);
+            Ok(Some((buf, from)))
+        })
+    }
+
+    /**
+        Closes the socket, after which it may no longer send or receive datagrams.
+
+        Closing a socket that has already been closed does nothing.
+    */
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+        self.socket
+            .lock()
+            .expect("Failed to lock udp socket")
+            .take();
+    }
+
+    /**
+        Creates a Lua table for this socket, with `send`, `sendTo`, `receive` and `close` methods.
+    */
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable> {
+        let port = self.socket()?.local_addr().into_lua_err()?.port();
+        let socket = Arc::new(self);
+        let socket_send = Arc::clone(&socket);
+        let socket_send_to = Arc::clone(&socket);
+        let socket_receive = Arc::clone(&socket);
+        let socket_close = Arc::clone(&socket);
+        TableBuilder::new(lua)?
+            .with_value("port", port)?
+            .with_async_function("send", move |_, (_, bytes): (LuaValue, LuaString)| {
+                let socket = Arc::clone(&socket_send);
+                let bytes = bytes.as_bytes().to_vec();
+                async move { socket.send(&bytes).await }
+            })?
+            .with_async_function(
+                "sendTo",
+                move |_, (_, bytes, host, port): (LuaValue, LuaString, String, u16)| {
+                    let socket = Arc::clone(&socket_send_to);
+                    let bytes = bytes.as_bytes().to_vec();
+                    async move { socket.send_to(&bytes, &host, port).await }
+                },
+            )?
+            .with_async_function(
+                "receive",
+                move |lua, (_, max_size): (LuaValue, Option<usize>)| {
+                    let pending = match max_size {
+                        Some(0) => Err(LuaError::RuntimeError(
+                            "Receive size must be greater than zero".to_string(),
+                        )),
+                        _ => socket_receive.receive(max_size.unwrap_or(MAX_DATAGRAM_SIZE)),
+                    };
+                    async move {
+                        match pending?.await? {
+                            Some((bytes, from)) => (
+                                LuaValue::String(lua.create_string(bytes)?),
+                                from.ip().to_string(),
+                                from.port(),
+                            )
+                                .into_lua_multi(lua),
+                            None => Ok(LuaMultiValue::new()),
+                        }
+                    }
+                },
+            )?
+            .with_function("close", move |_, _: LuaValue| {
+                socket_close.close();
+                Ok(())
+            })?
+            .build_readonly()
+    }
+}
+
+/**
+    Creates the `net.udp` table, for binding and connecting UDP sockets.
+*/
+pub fn create_udp_table(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("bind", net_udp_bind)?
+        .with_async_function("connect", net_udp_connect)?
+        .build_readonly()
+}
+
+async fn net_udp_bind(
+    lua: &'static Lua,
+    (port, address): (u16, Option<String>),
+) -> LuaResult<LuaTable> {
+    let ip = match address {
+        Some(address) => strip_ipv6_brackets(&address)
+            .parse::<IpAddr>()
+            .map_err(|e| LuaError::RuntimeError(format!("Invalid address '{address}' - {e}")))?,
+        None => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+    };
+    let socket = UdpSocket::bind((ip, port))
+        .await
+        .into_lua_err()
+        .with_context(|_| format!("Failed to bind udp socket to port {port}"))?;
+    NetUdpSocket::new(socket).into_lua_table(lua)
+}
+
+async fn net_udp_connect(lua: &'static Lua, (host, port): (String, u16)) -> LuaResult<LuaTable> {
+    let target = resolve(&host, port, false).await?;
+    let local = if target.is_ipv6() {
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+    } else {
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+    };
+    let socket = UdpSocket::bind((local, 0)).await.into_lua_err()?;
+    socket
+        .connect(target)
+        .await
+        .into_lua_err()
+        .with_context(|_| format!("Failed to connect udp socket to {host}:{port}"))?;
+    NetUdpSocket::new(socket).into_lua_table(lua)
+}
+
+/**
+    Resolves the given host and port, preferring addresses of the given ip version.
+*/
+async fn resolve(host: &str, port: u16, prefer_ipv6: bool) -> LuaResult<SocketAddr> {
+    let host = strip_ipv6_brackets(host);
+    let addrs = lookup_host((host, port))
+        .await
+        .into_lua_err()
+        .with_context(|_| format!("Failed to resolve host '{host}'"))?
+        .collect::<Vec<_>>();
+    addrs
+        .iter()
+        .find(|addr| addr.is_ipv6() == prefer_ipv6)
+        .or_else(|| addrs.first())
+        .copied()
+        .ok_or_else(|| LuaError::RuntimeError(format!("Failed to resolve host '{host}'")))
+}
+
+fn closed_error() -> LuaError {
+    LuaError::RuntimeError("Socket is closed".to_string())
+}
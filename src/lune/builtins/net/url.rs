@@ -0,0 +1,127 @@
+use std::net::Ipv6Addr;
+
+use mlua::prelude::*;
+use reqwest::Url;
+
+use crate::lune::util::TableBuilder;
+
+use super::util::strip_ipv6_brackets;
+
+/**
+    Parses a url into a table of its components.
+
+    Query values are decoded, and keys that are given more than once
+    have all of their values collected into an array, in order.
+*/
+pub fn net_url_parse(lua: &Lua, url: String) -> LuaResult<LuaTable> {
+    let url = Url::parse(&url)
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to parse url '{url}' - {e}")))?;
+
+    let query = lua.create_table()?;
+    for (key, value) in url.query_pairs() {
+        match query.raw_get::<_, LuaValue>(key.as_ref())? {
+            LuaValue::Nil => query.raw_set(key.as_ref(), value.as_ref())?,
+            LuaValue::Table(values) => values.raw_push(value.as_ref())?,
+            existing => {
+                let values = lua.create_sequence_from([existing])?;
+                values.raw_push(value.as_ref())?;
+                query.raw_set(key.as_ref(), values)?;
+            }
+        }
+    }
+
+    // NOTE: Ipv6 hosts are returned without their brackets, to
+    // match what net.dns and the other net functions accept
+    let host = url.host_str().map(strip_ipv6_brackets);
+
+    TableBuilder::new(lua)?
+        .with_value("scheme", url.scheme())?
+        .with_value("username", Some(url.username()).filter(|u| !u.is_empty()))?
+        .with_value("password", url.password())?
+        .with_value("host", host)?
+        .with_value("port", url.port_or_known_default())?
+        .with_value("path", url.path())?
+        .with_value("query", query)?
+        .with_value("fragment", url.fragment())?
+        .build()
+}
+
+/**
+    Builds a url from a table of its components, the inverse of [`net_url_parse`].
+*/
+pub fn net_url_build(_: &Lua, parts: LuaTable) -> LuaResult<String> {
+    let scheme = parts
+        .raw_get::<_, Option<String>>("scheme")?
+        .ok_or_else(|| LuaError::RuntimeError("Missing 'scheme' in url parts".to_string()))?;
+    let host = parts.raw_get::<_, Option<String>>("host")?;
+
+    let mut url = match &host {
+        Some(host) => {
+            let host = match host.parse::<Ipv6Addr>() {
+                Ok(_) => format!("[{host}]"),
+                Err(_) => host.clone(),
+            };
+            Url::parse(&format!("{scheme}://{host}"))
+                .map_err(|e| LuaError::RuntimeError(format!("Invalid host '{host}' - {e}")))?
+        }
+        None => Url::parse(&format!("{scheme}:"))
+            .map_err(|e| LuaError::RuntimeError(format!("Invalid scheme '{scheme}' - {e}")))?,
+    };
+    if let Some(username) = parts.raw_get::<_, Option<String>>("username")? {
+        url.set_username(&username).map_err(|_| {
+            LuaError::RuntimeError("Urls without a host can not have a username".to_string())
+        })?;
+    }
+    if let Some(password) = parts.raw_get::<_, Option<String>>("password")? {
+        url.set_password(Some(&password)).map_err(|_| {
+            LuaError::RuntimeError("Urls without a host can not have a password".to_string())
+        })?;
+    }
+    if let Some(port) = parts.raw_get::<_, Option<u16>>("port")? {
+        url.set_port(Some(port)).map_err(|_| {
+            LuaError::RuntimeError("Urls without a host can not have a port".to_string())
+        })?;
+    }
+    if let Some(path) = parts.raw_get::<_, Option<String>>("path")? {
+        url.set_path(&path);
+    }
+
+    if let Some(query) = parts.raw_get::<_, Option<LuaTable>>("query")? {
+        // NOTE: Keys are sorted so that the same table always builds the same url
+        let mut pairs = Vec::new();
+        for pair in query.pairs::<String, LuaValue>() {
+            let (key, value) = pair?;
+            match value {
+                LuaValue::Table(values) => {
+                    for value in values.sequence_values::<LuaValue>() {
+                        pairs.push((key.clone(), query_value_to_string(&key, value?)?));
+                    }
+                }
+                value => pairs.push((key.clone(), query_value_to_string(&key, value)?)),
+            }
+        }
+        pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+        if !pairs.is_empty() {
+            url.query_pairs_mut().extend_pairs(pairs);
+        }
+    }
+
+    if let Some(fragment) = parts.raw_get::<_, Option<String>>("fragment")? {
+        url.set_fragment(Some(&fragment));
+    }
+
+    Ok(url.to_string())
+}
+
+fn query_value_to_string(key: &str, value: LuaValue) -> LuaResult<String> {
+    match value {
+        LuaValue::String(s) => Ok(s.to_str()?.to_string()),
+        LuaValue::Integer(i) => Ok(i.to_string()),
+        LuaValue::Number(n) => Ok(n.to_string()),
+        LuaValue::Boolean(b) => Ok(b.to_string()),
+        value => Err(LuaError::RuntimeError(format!(
+            "Invalid value for query '{key}' - expected string, number, boolean or array, got {}",
+            value.type_name()
+        ))),
+    }
+}
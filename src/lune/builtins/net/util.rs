@@ -0,0 +1,24 @@
+/**
+    Strips the brackets around an ipv6 address, such as `[::1]`, which
+    is how they are written in urls and may also be given by users.
+
+    Other hosts, and ipv6 addresses without brackets, are returned as-is.
+*/
+pub(super) fn strip_ipv6_brackets(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipv6_brackets() {
+        assert_eq!(strip_ipv6_brackets("[::1]"), "::1");
+        assert_eq!(strip_ipv6_brackets("::1"), "::1");
+        assert_eq!(strip_ipv6_brackets("example.com"), "example.com");
+        assert_eq!(strip_ipv6_brackets("[::1"), "[::1");
+    }
+}
@@ -1,4 +1,7 @@
-use std::sync::Arc;
+use std::{
+    sync::{Arc, Mutex as StdMutex, Weak},
+    time::Duration,
+};
 
 use hyper::upgrade::Upgraded;
 use mlua::prelude::*;
@@ -8,9 +11,10 @@ use futures_util::{
     SinkExt, StreamExt,
 };
 use tokio::{
-    io::{AsyncRead, AsyncWrite},
+    io::{AsyncRead, AsyncWrite, DuplexStream},
     net::TcpStream,
     sync::Mutex as AsyncMutex,
+    time::{interval, MissedTickBehavior},
 };
 
 use hyper_tungstenite::{
@@ -22,7 +26,23 @@ use hyper_tungstenite::{
 };
 use tokio_tungstenite::MaybeTlsStream;
 
-use crate::lune::util::TableBuilder;
+use crate::lune::{scheduler::Scheduler, util::TableBuilder};
+
+/**
+    Maximum size of the payload for a ping, see RFC 6455 section 5.5.
+*/
+const MAX_PING_PAYLOAD_SIZE: usize = 125;
+
+/**
+    Maximum size of the reason in a close frame, which is
+    the maximum payload size minus two bytes for the code.
+*/
+const MAX_CLOSE_REASON_SIZE: usize = MAX_PING_PAYLOAD_SIZE - 2;
+
+/**
+    Default number of queued bytes at which backpressure callbacks are told to slow down.
+*/
+const DEFAULT_HIGH_WATER_MARK: usize = 1024 * 1024;
 
 const WEB_SOCKET_IMPL_LUA: &str = r#"
 return freeze(setmetatable({
@@ -35,18 +55,144 @@ return freeze(setmetatable({
 	next = function(...)
 		return next(websocket, ...)
 	end,
+	ping = function(...)
+		return ping(websocket, ...)
+	end,
+	onBackpressure = function(...)
+		return on_backpressure(websocket, ...)
+	end,
 }, {
 	__index = function(self, key)
 		if key == "closeCode" then
 			return close_code(websocket)
+		elseif key == "closeReason" then
+			return close_reason(websocket)
+		elseif key == "bufferedAmount" then
+			return buffered_amount(websocket)
 		end
 	end,
 }))
 "#;
 
+/**
+    A level of backpressure for the outbound queue of a web socket.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackpressureLevel {
+    High,
+    Low,
+}
+
+impl BackpressureLevel {
+    fn name(self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Low => "low",
+        }
+    }
+}
+
+/**
+    Tracks messages that have been given to `send` but not yet written to the
+    web socket, and which backpressure level was last reported for them.
+*/
+#[derive(Debug)]
+struct NetWebSocketQueue {
+    buffered: usize,
+    high_water_mark: usize,
+    low_water_mark: usize,
+    is_high: bool,
+    callback: Option<LuaRegistryKey>,
+}
+
+impl Default for NetWebSocketQueue {
+    fn default() -> Self {
+        Self {
+            buffered: 0,
+            high_water_mark: DEFAULT_HIGH_WATER_MARK,
+            low_water_mark: DEFAULT_HIGH_WATER_MARK / 4,
+            is_high: false,
+            callback: None,
+        }
+    }
+}
+
+impl NetWebSocketQueue {
+    /**
+        Adds a message of the given size to the queue, returning
+        a new backpressure level if the high water mark was crossed.
+    */
+    fn push(&mut self, size: usize) -> Option<BackpressureLevel> {
+        self.buffered += size;
+        self.update()
+    }
+
+    /**
+        Removes a message of the given size from the queue, returning
+        a new backpressure level if the low water mark was crossed.
+    */
+    fn pop(&mut self, size: usize) -> Option<BackpressureLevel> {
+        self.buffered = self.buffered.saturating_sub(size);
+        self.update()
+    }
+
+    fn update(&mut self) -> Option<BackpressureLevel> {
+        if !self.is_high && self.buffered > self.high_water_mark {
+            self.is_high = true;
+            Some(BackpressureLevel::High)
+        } else if self.is_high && self.buffered <= self.low_water_mark {
+            self.is_high = false;
+            Some(BackpressureLevel::Low)
+        } else {
+            None
+        }
+    }
+}
+
+/**
+    Options for `onBackpressure` on a web socket.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+struct BackpressureOptions {
+    high_water_mark: Option<usize>,
+    low_water_mark: Option<usize>,
+}
+
+impl<'lua> FromLua<'lua> for BackpressureOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Table(tab) => {
+                let this = Self {
+                    high_water_mark: tab.get("highWaterMark")?,
+                    low_water_mark: tab.get("lowWaterMark")?,
+                };
+                if let (Some(high), Some(low)) = (this.high_water_mark, this.low_water_mark) {
+                    if low > high {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Invalid backpressure options - 'lowWaterMark' ({low}) must not be greater than 'highWaterMark' ({high})"
+                        )));
+                    }
+                }
+                Ok(this)
+            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "BackpressureOptions",
+                message: Some(format!(
+                    "Invalid backpressure options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct NetWebSocket<T> {
     close_code: Arc<AsyncMutex<Option<u16>>>,
+    close_reason: Arc<AsyncMutex<Option<String>>>,
+    queue: Arc<StdMutex<NetWebSocketQueue>>,
     read_stream: Arc<AsyncMutex<SplitStream<WebSocketStream<T>>>>,
     write_stream: Arc<AsyncMutex<SplitSink<WebSocketStream<T>, WsMessage>>>,
 }
@@ -55,6 +201,8 @@ impl<T> Clone for NetWebSocket<T> {
     fn clone(&self) -> Self {
         Self {
             close_code: Arc::clone(&self.close_code),
+            close_reason: Arc::clone(&self.close_reason),
+            queue: Arc::clone(&self.queue),
             read_stream: Arc::clone(&self.read_stream),
             write_stream: Arc::clone(&self.write_stream),
         }
@@ -70,11 +218,45 @@ where
 
         Self {
             close_code: Arc::new(AsyncMutex::new(None)),
+            close_reason: Arc::new(AsyncMutex::new(None)),
+            queue: Arc::new(StdMutex::new(NetWebSocketQueue::default())),
             read_stream: Arc::new(AsyncMutex::new(read)),
             write_stream: Arc::new(AsyncMutex::new(write)),
         }
     }
 
+    /**
+        Sends a ping to the other end of the web socket at the given interval, which
+        keeps connections alive through proxies and servers that drop idle connections.
+
+        Pinging stops once the web socket has been closed or dropped.
+    */
+    pub fn with_ping_interval(self, period: Duration) -> Self
+    where
+        T: Send + 'static,
+    {
+        let write_stream = Arc::downgrade(&self.write_stream);
+        tokio::spawn(async move {
+            let mut ticker = interval(period);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // NOTE: The first tick completes immediately, but
+            // there is no need to ping right after connecting
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let write_stream = match Weak::upgrade(&write_stream) {
+                    Some(write_stream) => write_stream,
+                    None => break,
+                };
+                let mut ws = write_stream.lock().await;
+                if ws.send(WsMessage::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+        });
+        self
+    }
+
     fn into_lua_table_with_env<'lua>(
         lua: &'lua Lua,
         env: LuaTable<'lua>,
@@ -97,9 +279,19 @@ impl NetWebSocket<NetWebSocketStreamClient> {
         let socket_env = TableBuilder::new(lua)?
             .with_value("websocket", self)?
             .with_function("close_code", close_code::<NetWebSocketStreamClient>)?
+            .with_function("close_reason", close_reason::<NetWebSocketStreamClient>)?
+            .with_function(
+                "buffered_amount",
+                buffered_amount::<NetWebSocketStreamClient>,
+            )?
+            .with_function(
+                "on_backpressure",
+                on_backpressure::<NetWebSocketStreamClient>,
+            )?
             .with_async_function("close", close::<NetWebSocketStreamClient>)?
             .with_async_function("send", send::<NetWebSocketStreamClient>)?
             .with_async_function("next", next::<NetWebSocketStreamClient>)?
+            .with_async_function("ping", ping::<NetWebSocketStreamClient>)?
             .with_value("setmetatable", setmetatable)?
             .with_value("freeze", table_freeze)?
             .build_readonly()?;
@@ -118,9 +310,44 @@ impl NetWebSocket<NetWebSocketStreamServer> {
         let socket_env = TableBuilder::new(lua)?
             .with_value("websocket", self)?
             .with_function("close_code", close_code::<NetWebSocketStreamServer>)?
+            .with_function("close_reason", close_reason::<NetWebSocketStreamServer>)?
+            .with_function(
+                "buffered_amount",
+                buffered_amount::<NetWebSocketStreamServer>,
+            )?
+            .with_function(
+                "on_backpressure",
+                on_backpressure::<NetWebSocketStreamServer>,
+            )?
             .with_async_function("close", close::<NetWebSocketStreamServer>)?
             .with_async_function("send", send::<NetWebSocketStreamServer>)?
             .with_async_function("next", next::<NetWebSocketStreamServer>)?
+            .with_async_function("ping", ping::<NetWebSocketStreamServer>)?
+            .with_value("setmetatable", setmetatable)?
+            .with_value("freeze", table_freeze)?
+            .build_readonly()?;
+        Self::into_lua_table_with_env(lua, socket_env)
+    }
+}
+
+type NetWebSocketStreamMock = DuplexStream;
+impl NetWebSocket<NetWebSocketStreamMock> {
+    pub fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable> {
+        let setmetatable = lua.globals().get::<_, LuaFunction>("setmetatable")?;
+        let table_freeze = lua
+            .globals()
+            .get::<_, LuaTable>("table")?
+            .get::<_, LuaFunction>("freeze")?;
+        let socket_env = TableBuilder::new(lua)?
+            .with_value("websocket", self)?
+            .with_function("close_code", close_code::<NetWebSocketStreamMock>)?
+            .with_function("close_reason", close_reason::<NetWebSocketStreamMock>)?
+            .with_function("buffered_amount", buffered_amount::<NetWebSocketStreamMock>)?
+            .with_function("on_backpressure", on_backpressure::<NetWebSocketStreamMock>)?
+            .with_async_function("close", close::<NetWebSocketStreamMock>)?
+            .with_async_function("send", send::<NetWebSocketStreamMock>)?
+            .with_async_function("next", next::<NetWebSocketStreamMock>)?
+            .with_async_function("ping", ping::<NetWebSocketStreamMock>)?
             .with_value("setmetatable", setmetatable)?
             .with_value("freeze", table_freeze)?
             .build_readonly()?;
@@ -149,13 +376,128 @@ where
     )
 }
 
+fn close_reason<'lua, T>(
+    lua: &'lua Lua,
+    socket: LuaUserDataRef<'lua, NetWebSocket<T>>,
+) -> LuaResult<LuaValue<'lua>>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    match &*socket
+        .close_reason
+        .try_lock()
+        .expect("Failed to lock close reason")
+    {
+        Some(reason) => Ok(LuaValue::String(lua.create_string(reason)?)),
+        None => Ok(LuaValue::Nil),
+    }
+}
+
+fn buffered_amount<'lua, T>(
+    _lua: &'lua Lua,
+    socket: LuaUserDataRef<'lua, NetWebSocket<T>>,
+) -> LuaResult<usize>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    Ok(socket
+        .queue
+        .lock()
+        .expect("Failed to lock web socket queue")
+        .buffered)
+}
+
+fn on_backpressure<'lua, T>(
+    lua: &'lua Lua,
+    (socket, callback, options): (
+        LuaUserDataRef<'lua, NetWebSocket<T>>,
+        LuaFunction<'lua>,
+        BackpressureOptions,
+    ),
+) -> LuaResult<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let key = lua.create_registry_value(callback)?;
+    let mut queue = socket
+        .queue
+        .lock()
+        .expect("Failed to lock web socket queue");
+    match (options.high_water_mark, options.low_water_mark) {
+        (Some(high), Some(low)) => {
+            queue.high_water_mark = high;
+            queue.low_water_mark = low;
+        }
+        (Some(high), None) => {
+            queue.high_water_mark = high;
+            queue.low_water_mark = high / 4;
+        }
+        (None, Some(low)) if low > queue.high_water_mark => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid backpressure options - 'lowWaterMark' ({low}) must not be greater than 'highWaterMark' ({})",
+                queue.high_water_mark
+            )))
+        }
+        (None, Some(low)) => queue.low_water_mark = low,
+        (None, None) => {}
+    }
+    if let Some(previous) = queue.callback.replace(key) {
+        lua.remove_registry_value(previous)?;
+    }
+    Ok(())
+}
+
+/**
+    Runs the backpressure callback for the web socket, if one has been set.
+
+    The callback runs on its own thread, so that it may yield
+    without holding up the send that changed the level.
+*/
+fn emit_backpressure<T>(
+    lua: &Lua,
+    socket: &NetWebSocket<T>,
+    level: Option<BackpressureLevel>,
+) -> LuaResult<()> {
+    let level = match level {
+        Some(level) => level,
+        None => return Ok(()),
+    };
+    let callback = {
+        let queue = socket
+            .queue
+            .lock()
+            .expect("Failed to lock web socket queue");
+        match &queue.callback {
+            Some(key) => lua.registry_value::<LuaFunction>(key)?,
+            None => return Ok(()),
+        }
+    };
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    sched.push_back(lua, callback, level.name())?;
+    Ok(())
+}
+
 async fn close<'lua, T>(
     _lua: &'lua Lua,
-    (socket, code): (LuaUserDataRef<'lua, NetWebSocket<T>>, Option<u16>),
+    (socket, code, reason): (
+        LuaUserDataRef<'lua, NetWebSocket<T>>,
+        Option<u16>,
+        Option<String>,
+    ),
 ) -> LuaResult<()>
 where
     T: AsyncRead + AsyncWrite + Unpin,
 {
+    let reason = reason.unwrap_or_default();
+    if reason.len() > MAX_CLOSE_REASON_SIZE {
+        return Err(LuaError::RuntimeError(format!(
+            "Close reason must be at most {MAX_CLOSE_REASON_SIZE} bytes, got {}",
+            reason.len()
+        )));
+    }
+
     let mut ws = socket.write_stream.lock().await;
 
     ws.send(WsMessage::Close(Some(WsCloseFrame {
@@ -168,7 +510,7 @@ where
             }
             None => WsCloseCode::Normal,
         },
-        reason: "".into(),
+        reason: reason.into(),
     })))
     .await
     .into_lua_err()?;
@@ -178,7 +520,7 @@ where
 }
 
 async fn send<'lua, T>(
-    _lua: &'lua Lua,
+    lua: &'lua Lua,
     (socket, string, as_binary): (
         LuaUserDataRef<'lua, NetWebSocket<T>>,
         LuaString<'lua>,
@@ -194,8 +536,48 @@ where
         let s = string.to_str().into_lua_err()?;
         WsMessage::Text(s.to_string())
     };
+
+    // NOTE: Messages are queued until they have been written, which
+    // includes waiting for any other sends that are already in progress
+    let size = msg.len();
+    let level = socket
+        .queue
+        .lock()
+        .expect("Failed to lock web socket queue")
+        .push(size);
+    emit_backpressure(lua, &socket, level)?;
+
+    let result = socket.write_stream.lock().await.send(msg).await;
+
+    let level = socket
+        .queue
+        .lock()
+        .expect("Failed to lock web socket queue")
+        .pop(size);
+    emit_backpressure(lua, &socket, level)?;
+
+    result.into_lua_err()
+}
+
+async fn ping<'lua, T>(
+    _lua: &'lua Lua,
+    (socket, payload): (
+        LuaUserDataRef<'lua, NetWebSocket<T>>,
+        Option<LuaString<'lua>>,
+    ),
+) -> LuaResult<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let payload = payload.map(|p| p.as_bytes().to_vec()).unwrap_or_default();
+    if payload.len() > MAX_PING_PAYLOAD_SIZE {
+        return Err(LuaError::RuntimeError(format!(
+            "Ping payload must be at most {MAX_PING_PAYLOAD_SIZE} bytes, got {}",
+            payload.len()
+        )));
+    }
     let mut ws = socket.write_stream.lock().await;
-    ws.send(msg).await.into_lua_err()
+    ws.send(WsMessage::Ping(payload)).await.into_lua_err()
 }
 
 async fn next<'lua, T>(
@@ -206,29 +588,46 @@ where
     T: AsyncRead + AsyncWrite + Unpin,
 {
     let mut ws = socket.read_stream.lock().await;
-    let item = ws.next().await.transpose().into_lua_err();
-    let msg = match item {
-        Ok(Some(WsMessage::Close(msg))) => {
-            if let Some(msg) = &msg {
-                let mut code = socket.close_code.lock().await;
-                *code = Some(msg.code.into());
-            }
-            Ok(Some(WsMessage::Close(msg)))
-        }
-        val => val,
-    }?;
-    while let Some(msg) = &msg {
-        let msg_string_opt = match msg {
-            WsMessage::Binary(bin) => Some(lua.create_string(bin)?),
-            WsMessage::Text(txt) => Some(lua.create_string(txt)?),
+    loop {
+        let msg = match ws.next().await.transpose().into_lua_err()? {
+            Some(msg) => msg,
+            None => return Ok(LuaValue::Nil),
+        };
+        match msg {
+            WsMessage::Binary(bin) => return Ok(LuaValue::String(lua.create_string(bin)?)),
+            WsMessage::Text(txt) => return Ok(LuaValue::String(lua.create_string(txt)?)),
             // Stop waiting for next message if we get a close message
-            WsMessage::Close(_) => return Ok(LuaValue::Nil),
+            WsMessage::Close(frame) => {
+                if let Some(frame) = frame {
+                    *socket.close_code.lock().await = Some(frame.code.into());
+                    *socket.close_reason.lock().await = Some(frame.reason.into_owned());
+                }
+                return Ok(LuaValue::Nil);
+            }
             // Ignore ping/pong/frame messages, they are handled by tungstenite
-            _ => None,
-        };
-        if let Some(msg_string) = msg_string_opt {
-            return Ok(LuaValue::String(msg_string));
+            _ => continue,
         }
     }
-    Ok(LuaValue::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backpressure_levels() {
+        let mut queue = NetWebSocketQueue {
+            high_water_mark: 100,
+            low_water_mark: 25,
+            ..Default::default()
+        };
+        assert_eq!(queue.push(60), None);
+        assert_eq!(queue.push(60), Some(BackpressureLevel::High));
+        assert_eq!(queue.push(60), None);
+        assert_eq!(queue.pop(60), None);
+        assert_eq!(queue.pop(60), None);
+        assert_eq!(queue.pop(40), Some(BackpressureLevel::Low));
+        assert_eq!(queue.pop(20), None);
+        assert_eq!(queue.buffered, 0);
+    }
 }
@@ -0,0 +1,291 @@
+use std::{process::ExitStatus, sync::Arc};
+
+use async_trait::async_trait;
+use mlua::prelude::*;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    process::{Child, ChildStdin},
+    sync::{mpsc, watch, Mutex as AsyncMutex},
+};
+
+use crate::lune::{
+    builtins::fs::lines::{LineReader, LineReaderOptions, LineSource},
+    scheduler::Scheduler,
+    util::TableBuilder,
+};
+
+const DEFAULT_READ_SIZE: usize = 8192;
+
+/**
+    A signal to send to a child process using `kill`.
+
+    Signals may be given by name, with or without the `SIG` prefix,
+    or by number. On platforms other than unix, any signal will
+    forcefully terminate the child process.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct ChildSignal(i32);
+
+impl Default for ChildSignal {
+    fn default() -> Self {
+        Self::from_name("TERM").expect("SIGTERM is a known signal")
+    }
+}
+
+impl ChildSignal {
+    #[cfg(unix)]
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "HUP" => Some(Self(libc::SIGHUP)),
+            "INT" => Some(Self(libc::SIGINT)),
+            "QUIT" => Some(Self(libc::SIGQUIT)),
+            "KILL" => Some(Self(libc::SIGKILL)),
+            "USR1" => Some(Self(libc::SIGUSR1)),
+            "USR2" => Some(Self(libc::SIGUSR2)),
+            "TERM" => Some(Self(libc::SIGTERM)),
+            _ => None,
+        }
+    }
+
+    // NOTE: Signal numbers do not matter on other platforms,
+    // but we still only accept names that are valid on unix
+    #[cfg(not(unix))]
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "HUP" | "INT" | "QUIT" | "KILL" | "USR1" | "USR2" | "TERM" => Some(Self(0)),
+            _ => None,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for ChildSignal {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Integer(i) if *i > 0 => Ok(Self(*i)),
+            LuaValue::Number(n) if n.fract() == 0.0 && *n > 0.0 => Ok(Self(*n as i32)),
+            LuaValue::String(s) => {
+                let name = s.to_str()?.to_ascii_uppercase();
+                Self::from_name(name.trim_start_matches("SIG")).ok_or_else(|| {
+                    LuaError::RuntimeError(format!(
+                        "Invalid signal '{}' - expected one of SIGHUP, SIGINT, SIGQUIT, SIGKILL, SIGUSR1, SIGUSR2 or SIGTERM",
+                        s.to_string_lossy()
+                    ))
+                })
+            }
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ChildSignal",
+                message: Some(format!(
+                    "Invalid signal - expected string, number or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+impl ChildSignal {
+    #[cfg(unix)]
+    fn send(self, child: &mut Child) -> std::io::Result<()> {
+        match child.id() {
+            Some(pid) => match unsafe { libc::kill(pid as libc::pid_t, self.0) } {
+                0 => Ok(()),
+                _ => Err(std::io::Error::last_os_error()),
+            },
+            // The child has already exited, there is nothing to signal
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn send(self, child: &mut Child) -> std::io::Result<()> {
+        child.start_kill()
+    }
+}
+
+/**
+    Waits for the child to exit, sending any signals given
+    through `kill` to it while it is still running.
+*/
+async fn wait_for_child(
+    mut child: Child,
+    mut signals: mpsc::UnboundedReceiver<ChildSignal>,
+    status: watch::Sender<Option<ExitStatus>>,
+) {
+    loop {
+        tokio::select! {
+            result = child.wait() => {
+                // NOTE: If waiting fails we can not know how the child exited,
+                // so we forcefully kill it, and dropping the status sender
+                // without a status will make any waiting threads error
+                match result {
+                    Ok(exit) => {
+                        status.send_replace(Some(exit));
+                    }
+                    Err(_) => {
+                        child.kill().await.ok();
+                    }
+                }
+                break;
+            }
+            Some(signal) = signals.recv() => {
+                signal.send(&mut child).ok();
+            }
+        }
+    }
+}
+
+/**
+    Reads up to the given number of bytes from a stream of the
+    child process, returning `None` once the stream has ended.
+*/
+async fn read_child_stream<R>(
+    reader: &AsyncMutex<Option<R>>,
+    size: usize,
+) -> LuaResult<Option<Vec<u8>>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut reader = reader.lock().await;
+    let reader = match reader.as_mut() {
+        Some(reader) => reader,
+        None => return Ok(None),
+    };
+    let mut buffer = vec![0; size.max(1)];
+    match reader.read(&mut buffer).await? {
+        0 => Ok(None),
+        n => {
+            buffer.truncate(n);
+            Ok(Some(buffer))
+        }
+    }
+}
+
+struct ChildLineSource<R>(Arc<AsyncMutex<Option<R>>>);
+
+#[async_trait]
+impl<R> LineSource for ChildLineSource<R>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    async fn read_chunk(&mut self) -> LuaResult<Option<Vec<u8>>> {
+        read_child_stream(&self.0, DEFAULT_READ_SIZE).await
+    }
+}
+
+fn create_reader_table<R>(lua: &'static Lua, reader: Option<R>) -> LuaResult<LuaTable>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    let reader = Arc::new(AsyncMutex::new(reader));
+    let reader_lines = Arc::clone(&reader);
+    TableBuilder::new(lua)?
+        .with_async_function("read", move |lua, (_, size): (LuaValue, Option<usize>)| {
+            let reader = Arc::clone(&reader);
+            async move {
+                match read_child_stream(&reader, size.unwrap_or(DEFAULT_READ_SIZE)).await? {
+                    Some(bytes) => Ok(LuaValue::String(lua.create_string(bytes)?)),
+                    None => Ok(LuaValue::Nil),
+                }
+            }
+        })?
+        .with_function(
+            "lines",
+            move |lua, (_, options): (LuaValue, LineReaderOptions)| {
+                let source = ChildLineSource(Arc::clone(&reader_lines));
+                LineReader::from_source(source, options).into_lua_table(lua)
+            },
+        )?
+        .build_readonly()
+}
+
+fn create_writer_table(lua: &'static Lua, writer: Option<ChildStdin>) -> LuaResult<LuaTable> {
+    let writer = Arc::new(AsyncMutex::new(writer));
+    let writer_close = Arc::clone(&writer);
+    TableBuilder::new(lua)?
+        .with_async_function("write", move |_, (_, data): (LuaValue, LuaString)| {
+            let writer = Arc::clone(&writer);
+            let data = data.as_bytes().to_vec();
+            async move {
+                match writer.lock().await.as_mut() {
+                    Some(writer) => {
+                        writer.write_all(&data).await?;
+                        writer.flush().await?;
+                        Ok(())
+                    }
+                    None => Err(LuaError::RuntimeError(
+                        "Stdin of the child process has been closed".to_string(),
+                    )),
+                }
+            }
+        })?
+        .with_async_function("close", move |_, _: LuaValue| {
+            let writer = Arc::clone(&writer_close);
+            async move {
+                if let Some(mut writer) = writer.lock().await.take() {
+                    writer.shutdown().await.ok();
+                }
+                Ok(())
+            }
+        })?
+        .build_readonly()
+}
+
+fn create_status_table(lua: &Lua, status: ExitStatus) -> LuaResult<LuaTable> {
+    #[cfg(unix)]
+    let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+    #[cfg(not(unix))]
+    let signal: Option<i32> = None;
+
+    // NOTE: A child that was terminated by a signal does not have
+    // an exit code, but should still be reported as having failed
+    let code = status.code().unwrap_or(1);
+
+    TableBuilder::new(lua)?
+        .with_value("ok", code == 0)?
+        .with_value("code", code)?
+        .with_value("signal", signal)?
+        .build_readonly()
+}
+
+/**
+    Creates a handle for a spawned child process, with
+    streams for its stdio and methods for controlling it.
+*/
+pub fn create_child_table(lua: &'static Lua, mut child: Child) -> LuaResult<LuaTable> {
+    let pid = child.id();
+    let stdin = create_writer_table(lua, child.stdin.take())?;
+    let stdout = create_reader_table(lua, child.stdout.take())?;
+    let stderr = create_reader_table(lua, child.stderr.take())?;
+
+    let (signal_tx, signal_rx) = mpsc::unbounded_channel();
+    let (status_tx, status_rx) = watch::channel(None);
+
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    sched.spawn(wait_for_child(child, signal_rx, status_tx));
+
+    TableBuilder::new(lua)?
+        .with_value("pid", pid)?
+        .with_value("stdin", stdin)?
+        .with_value("stdout", stdout)?
+        .with_value("stderr", stderr)?
+        .with_function("kill", move |_, (_, signal): (LuaValue, ChildSignal)| {
+            // NOTE: Sending only fails if the child has already
+            // exited, and killing an exited child does nothing
+            signal_tx.send(signal).ok();
+            Ok(())
+        })?
+        .with_async_function("status", move |lua, _: LuaValue| {
+            let mut status_rx = status_rx.clone();
+            async move {
+                let status = *status_rx.wait_for(Option::is_some).await.map_err(|_| {
+                    LuaError::RuntimeError("Failed to wait for child process".to_string())
+                })?;
+                create_status_table(lua, status.expect("Status was checked to exist"))
+            }
+        })?
+        .build_readonly()
+}
@@ -1,5 +1,6 @@
 use std::{
     env::{self, consts},
+    future::Future,
     path,
     process::{ExitStatus, Stdio},
 };
@@ -8,7 +9,11 @@ use dunce::canonicalize;
 use mlua::prelude::*;
 use os_str_bytes::RawOsString;
 
-use crate::lune::{scheduler::Scheduler, util::TableBuilder};
+use crate::lune::{
+    builtins::trace::{AutoSpan, SpanKind},
+    scheduler::{Scheduler, SchedulerSignal},
+    util::TableBuilder,
+};
 
 mod tee_writer;
 
@@ -18,6 +23,9 @@ use pipe_inherit::pipe_and_inherit_child_process_stdio;
 mod options;
 use options::ProcessSpawnOptions;
 
+mod child;
+use child::create_child_table;
+
 const PROCESS_EXIT_IMPL_LUA: &str = r#"
 exit(...)
 yield()
@@ -86,10 +94,35 @@ pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
         .with_value("cwd", cwd_str)?
         .with_value("env", env_tab)?
         .with_value("exit", process_exit)?
+        .with_function("onExit", process_on_exit)?
+        .with_function("onSignal", process_on_signal)?
         .with_async_function("spawn", process_spawn)?
+        .with_function("create", process_create)?
         .build_readonly()
 }
 
+fn process_on_exit<'lua>(lua: &'lua Lua, handler: LuaFunction<'lua>) -> LuaResult<()> {
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    sched.add_exit_handler(lua, handler)
+}
+
+fn process_on_signal<'lua>(
+    lua: &'lua Lua,
+    (signal, handler): (String, LuaFunction<'lua>),
+) -> LuaResult<()> {
+    let signal = SchedulerSignal::from_name(&signal).ok_or_else(|| {
+        LuaError::RuntimeError(format!(
+            "Invalid signal '{signal}' - expected one of SIGINT, SIGTERM or SIGHUP"
+        ))
+    })?;
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    sched.add_signal_handler(lua, signal, handler)
+}
+
 fn process_env_get<'lua>(
     lua: &'lua Lua,
     (_, key): (LuaValue<'lua>, String),
@@ -159,41 +192,81 @@ fn process_env_iter<'lua>(
     })
 }
 
-async fn process_spawn(
-    lua: &Lua,
+fn process_spawn(
+    lua: &'static Lua,
     (program, args, options): (String, Option<Vec<String>>, ProcessSpawnOptions),
-) -> LuaResult<LuaTable> {
-    /*
-        Spawn the new process in the background, letting the tokio
-        runtime place it on a different thread if possible / necessary
-
-        Note that we have to use our scheduler here, we can't
-        use anything like tokio::task::spawn because our lua
-        scheduler will not drive those futures to completion
-    */
-    let sched = lua
-        .app_data_ref::<&Scheduler>()
-        .expect("Lua struct is missing scheduler");
+) -> impl Future<Output = LuaResult<LuaTable<'static>>> {
+    // NOTE: The span must be created right away, since it
+    // uses the span of the calling thread as its parent
+    let mut span = AutoSpan::start(lua, program.clone(), SpanKind::Internal);
+    span.set_attribute("process.command", program.as_str());
 
-    let (status, stdout, stderr) = sched
-        .spawn(spawn_command(program, args, options))
-        .await
-        .expect("Failed to receive result of spawned process")?;
+    async move {
+        /*
+            Spawn the new process in the background, letting the tokio
+            runtime place it on a different thread if possible / necessary
 
-    // NOTE: If an exit code was not given by the child process,
-    // we default to 1 if it yielded any error output, otherwise 0
-    let code = status.code().unwrap_or(match stderr.is_empty() {
-        true => 0,
-        false => 1,
-    });
+            Note that we have to use our scheduler here, we can't
+            use anything like tokio::task::spawn because our lua
+            scheduler will not drive those futures to completion
+        */
+        let sched = lua
+            .app_data_ref::<&Scheduler>()
+            .expect("Lua struct is missing scheduler");
 
-    // Construct and return a readonly lua table with results
-    TableBuilder::new(lua)?
-        .with_value("ok", code == 0)?
-        .with_value("code", code)?
-        .with_value("stdout", lua.create_string(&stdout)?)?
-        .with_value("stderr", lua.create_string(&stderr)?)?
-        .build_readonly()
+        let result = sched
+            .spawn(spawn_command(program, args, options))
+            .await
+            .expect("Failed to receive result of spawned process");
+        let (status, stdout, stderr) = match result {
+            Ok(output) => output,
+            Err(e) => {
+                span.set_error(e.to_string());
+                return Err(e);
+            }
+        };
+
+        // NOTE: If an exit code was not given by the child process,
+        // we default to 1 if it yielded any error output, otherwise 0
+        let code = status.code().unwrap_or(match stderr.is_empty() {
+            true => 0,
+            false => 1,
+        });
+        span.set_attribute("process.exit.code", i64::from(code));
+        if code != 0 {
+            span.set_error(format!("Process exited with code {code}"));
+        }
+
+        // Construct and return a readonly lua table with results
+        TableBuilder::new(lua)?
+            .with_value("ok", code == 0)?
+            .with_value("code", code)?
+            .with_value("stdout", lua.create_string(&stdout)?)?
+            .with_value("stderr", lua.create_string(&stderr)?)?
+            .build_readonly()
+    }
+}
+
+fn process_create(
+    lua: &'static Lua,
+    (program, args, options): (String, Option<Vec<String>>, ProcessSpawnOptions),
+) -> LuaResult<LuaTable> {
+    // NOTE: Inherited stdio is written directly to our own stdout and
+    // stderr, and can not be read from the returned child handle
+    let (stdout, stderr) = match options.inherit_stdio {
+        true => (Stdio::inherit(), Stdio::inherit()),
+        false => (Stdio::piped(), Stdio::piped()),
+    };
+
+    let child = options
+        .into_command(program, args)
+        .stdin(Stdio::piped())
+        .stdout(stdout)
+        .stderr(stderr)
+        .kill_on_drop(true)
+        .spawn()?;
+
+    create_child_table(lua, child)
 }
 
 async fn spawn_command(
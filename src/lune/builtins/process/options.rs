@@ -12,6 +12,8 @@ use tokio::process::Command;
 pub struct ProcessSpawnOptions {
     pub(crate) cwd: Option<PathBuf>,
     pub(crate) envs: HashMap<String, String>,
+    pub(crate) env_remove: Vec<String>,
+    pub(crate) env_clear: bool,
     pub(crate) shell: Option<String>,
     pub(crate) inherit_stdio: bool,
 }
@@ -86,6 +88,39 @@ impl<'lua> FromLua<'lua> for ProcessSpawnOptions {
             }
         }
 
+        /*
+            If we got environment variables to remove, make sure they are strings
+        */
+        match value.get("envRemove")? {
+            LuaValue::Nil => {}
+            LuaValue::Table(e) => {
+                for key in e.sequence_values::<String>() {
+                    let key = key.context("Environment variables to remove must be strings")?;
+                    this.env_remove.push(key);
+                }
+            }
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid type for option 'envRemove' - expected table, got '{}'",
+                    value.type_name()
+                )))
+            }
+        }
+
+        /*
+            If we got a flag for clearing the environment, make sure its a boolean
+        */
+        match value.get("envClear")? {
+            LuaValue::Nil => {}
+            LuaValue::Boolean(clear) => this.env_clear = clear,
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid type for option 'envClear' - expected 'boolean', got '{}'",
+                    value.type_name()
+                )))
+            }
+        }
+
         /*
             If we got a shell to use:
 
@@ -164,10 +199,17 @@ impl ProcessSpawnOptions {
             }
         };
 
-        // Set dir to run in and env variables
+        // Set dir to run in and env variables, removing any inherited
+        // variables first so that explicitly given variables are kept
         if let Some(cwd) = self.cwd {
             cmd.current_dir(cwd);
         }
+        if self.env_clear {
+            cmd.env_clear();
+        }
+        for key in self.env_remove {
+            cmd.env_remove(key);
+        }
         if !self.envs.is_empty() {
             cmd.envs(self.envs);
         }
@@ -0,0 +1,301 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use mlua::prelude::*;
+use serde_json::Value as JsonValue;
+use tokio::task;
+
+use crate::lune::util::TableBuilder;
+
+mod store;
+
+use store::JobStore;
+
+const DEFAULT_VISIBILITY_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_FAILED_LIMIT: u32 = 100;
+
+const LUA_SERIALIZE_OPTIONS: LuaSerializeOptions = LuaSerializeOptions::new()
+    .set_array_metatable(false)
+    .serialize_none_to_null(false)
+    .serialize_unit_to_null(false);
+
+const LUA_DESERIALIZE_OPTIONS: LuaDeserializeOptions = LuaDeserializeOptions::new()
+    .sort_keys(true)
+    .deny_recursive_tables(true)
+    .deny_unsupported_types(true);
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_async_function("open", queue_open)?
+        .build_readonly()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn parse_seconds(value: LuaValue, name: &str) -> LuaResult<Option<Duration>> {
+    let secs = match value {
+        LuaValue::Nil => return Ok(None),
+        LuaValue::Integer(i) => i as f64,
+        LuaValue::Number(n) => n,
+        _ => f64::NAN,
+    };
+    match Duration::try_from_secs_f64(secs) {
+        Ok(duration) => Ok(Some(duration)),
+        Err(_) => Err(LuaError::RuntimeError(format!(
+            "Invalid option value for '{name}' in queue options - \
+            expected a non-negative number of seconds"
+        ))),
+    }
+}
+
+fn parse_max_attempts(value: LuaValue) -> LuaResult<Option<u32>> {
+    match value {
+        LuaValue::Nil => Ok(None),
+        LuaValue::Integer(i) if i >= 1 => Ok(Some(i as u32)),
+        LuaValue::Number(n) if n >= 1.0 && n.fract() == 0.0 => {
+            Ok(Some(n.min(u32::MAX as f64) as u32))
+        }
+        _ => Err(LuaError::RuntimeError(
+            "Invalid option value for 'maxAttempts' in queue options - \
+            expected a positive integer"
+                .to_string(),
+        )),
+    }
+}
+
+fn queue_error(e: rusqlite::Error) -> LuaError {
+    LuaError::RuntimeError(format!("Failed to access queue\n> {e}"))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct QueueConfig {
+    visibility_timeout: Duration,
+    max_attempts: u32,
+}
+
+impl QueueConfig {
+    fn from_options(options: Option<LuaTable>) -> LuaResult<Self> {
+        let mut config = Self {
+            visibility_timeout: DEFAULT_VISIBILITY_TIMEOUT,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+        };
+        if let Some(options) = options {
+            if let Some(timeout) =
+                parse_seconds(options.raw_get("visibilityTimeout")?, "visibilityTimeout")?
+            {
+                config.visibility_timeout = timeout;
+            }
+            if let Some(max_attempts) = parse_max_attempts(options.raw_get("maxAttempts")?)? {
+                config.max_attempts = max_attempts;
+            }
+        }
+        Ok(config)
+    }
+}
+
+/**
+    A job queue stored in a file, opened using `queue.open`.
+
+    The store is closed once the queue is dropped or `close` is called.
+*/
+#[derive(Debug, Clone)]
+struct JobQueue {
+    store: Arc<Mutex<Option<JobStore>>>,
+    config: QueueConfig,
+}
+
+impl JobQueue {
+    /**
+        Runs the given function with the store on a blocking thread, since
+        sqlite may have to wait for other processes that use the same file.
+    */
+    async fn with_store<T, F>(&self, f: F) -> LuaResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut JobStore) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let store = Arc::clone(&self.store);
+        let result = task::spawn_blocking(move || {
+            let mut store = store.lock().expect("Queue store lock was poisoned");
+            store.as_mut().map(f)
+        })
+        .await
+        .into_lua_err()?;
+        match result {
+            Some(result) => result.map_err(queue_error),
+            None => Err(LuaError::RuntimeError(
+                "Queue has already been closed".to_string(),
+            )),
+        }
+    }
+
+    fn into_lua_table(self, lua: &'static Lua) -> LuaResult<LuaTable> {
+        let queue_enqueue = self.clone();
+        let queue_dequeue = self.clone();
+        let queue_ack = self.clone();
+        let queue_nack = self.clone();
+        let queue_extend = self.clone();
+        let queue_retry = self.clone();
+        let queue_failed = self.clone();
+        let queue_stats = self.clone();
+        TableBuilder::new(lua)?
+            .with_async_function(
+                "enqueue",
+                move |lua, (_, payload, options): (LuaValue, LuaValue, Option<LuaTable>)| {
+                    let queue = queue_enqueue.clone();
+                    let parsed = parse_enqueue(lua, payload, options);
+                    async move {
+                        let (payload, delay, max_attempts) = parsed?;
+                        let max_attempts = max_attempts.unwrap_or(queue.config.max_attempts);
+                        queue
+                            .with_store(move |store| store.enqueue(&payload, delay, max_attempts))
+                            .await
+                    }
+                },
+            )?
+            .with_async_function("dequeue", move |lua, _: LuaValue| {
+                let queue = queue_dequeue.clone();
+                async move {
+                    let timeout = queue.config.visibility_timeout;
+                    let job = match queue
+                        .with_store(move |store| store.dequeue(timeout))
+                        .await?
+                    {
+                        Some(job) => job,
+                        None => return Ok(LuaValue::Nil),
+                    };
+                    TableBuilder::new(lua)?
+                        .with_value("id", job.id)?
+                        .with_value("payload", decode_payload(lua, &job.payload)?)?
+                        .with_value("attempts", job.attempts)?
+                        .build()
+                        .map(LuaValue::Table)
+                }
+            })?
+            .with_async_function("ack", move |_, (_, id): (LuaValue, i64)| {
+                let queue = queue_ack.clone();
+                async move { queue.with_store(move |store| store.ack(id)).await }
+            })?
+            .with_async_function(
+                "nack",
+                move |_, (_, id, options): (LuaValue, i64, Option<LuaTable>)| {
+                    let queue = queue_nack.clone();
+                    let parsed = parse_nack(options);
+                    async move {
+                        let (delay, error) = parsed?;
+                        queue
+                            .with_store(move |store| store.nack(id, delay, error.as_deref()))
+                            .await
+                    }
+                },
+            )?
+            .with_async_function(
+                "extend",
+                move |_, (_, id, timeout): (LuaValue, i64, LuaValue)| {
+                    let queue = queue_extend.clone();
+                    let parsed = parse_seconds(timeout, "timeout");
+                    async move {
+                        let timeout = parsed?.unwrap_or(queue.config.visibility_timeout);
+                        queue
+                            .with_store(move |store| store.extend(id, timeout))
+                            .await
+                    }
+                },
+            )?
+            .with_async_function("retry", move |_, (_, id): (LuaValue, i64)| {
+                let queue = queue_retry.clone();
+                async move { queue.with_store(move |store| store.retry(id)).await }
+            })?
+            .with_async_function("failed", move |lua, (_, limit): (LuaValue, Option<u32>)| {
+                let queue = queue_failed.clone();
+                async move {
+                    let limit = limit.unwrap_or(DEFAULT_FAILED_LIMIT);
+                    let jobs = queue.with_store(move |store| store.failed(limit)).await?;
+                    let list = lua.create_table_with_capacity(jobs.len(), 0)?;
+                    for job in jobs {
+                        list.raw_push(
+                            TableBuilder::new(lua)?
+                                .with_value("id", job.id)?
+                                .with_value("payload", decode_payload(lua, &job.payload)?)?
+                                .with_value("attempts", job.attempts)?
+                                .with_value("error", job.error)?
+                                .build()?,
+                        )?;
+                    }
+                    Ok(list)
+                }
+            })?
+            .with_async_function("stats", move |lua, _: LuaValue| {
+                let queue = queue_stats.clone();
+                async move {
+                    let counts = queue.with_store(|store| store.counts()).await?;
+                    TableBuilder::new(lua)?
+                        .with_value("ready", counts.ready)?
+                        .with_value("delayed", counts.delayed)?
+                        .with_value("inflight", counts.inflight)?
+                        .with_value("failed", counts.failed)?
+                        .build_readonly()
+                }
+            })?
+            .with_function("close", move |_, _: LuaValue| {
+                let mut store = self.store.lock().expect("Queue store lock was poisoned");
+                match store.take() {
+                    Some(_) => Ok(()),
+                    None => Err(LuaError::RuntimeError(
+                        "Queue has already been closed".to_string(),
+                    )),
+                }
+            })?
+            .build_readonly()
+    }
+}
+
+fn parse_enqueue(
+    lua: &Lua,
+    payload: LuaValue,
+    options: Option<LuaTable>,
+) -> LuaResult<(String, Duration, Option<u32>)> {
+    let json: JsonValue = lua.from_value_with(payload, LUA_DESERIALIZE_OPTIONS)?;
+    let payload = serde_json::to_string(&json).into_lua_err()?;
+    let (delay, max_attempts) = match options {
+        Some(options) => (
+            parse_seconds(options.raw_get("delay")?, "delay")?,
+            parse_max_attempts(options.raw_get("maxAttempts")?)?,
+        ),
+        None => (None, None),
+    };
+    Ok((payload, delay.unwrap_or_default(), max_attempts))
+}
+
+fn parse_nack(options: Option<LuaTable>) -> LuaResult<(Duration, Option<String>)> {
+    match options {
+        Some(options) => Ok((
+            parse_seconds(options.raw_get("delay")?, "delay")?.unwrap_or_default(),
+            options.raw_get("error")?,
+        )),
+        None => Ok((Duration::ZERO, None)),
+    }
+}
+
+fn decode_payload<'lua>(lua: &'lua Lua, payload: &str) -> LuaResult<LuaValue<'lua>> {
+    let json: JsonValue = serde_json::from_str(payload).into_lua_err()?;
+    lua.to_value_with(&json, LUA_SERIALIZE_OPTIONS)
+}
+
+async fn queue_open(
+    lua: &'static Lua,
+    (path, options): (String, Option<LuaTable<'static>>),
+) -> LuaResult<LuaTable<'static>> {
+    let config = QueueConfig::from_options(options)?;
+    let store = task::spawn_blocking(move || JobStore::open(path))
+        .await
+        .into_lua_err()?
+        .map_err(queue_error)?;
+    let queue = JobQueue {
+        store: Arc::new(Mutex::new(Some(store))),
+        config,
+    };
+    queue.into_lua_table(lua)
+}
@@ -0,0 +1,314 @@
+use std::{
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS jobs (
+        id           INTEGER PRIMARY KEY AUTOINCREMENT,
+        payload      TEXT    NOT NULL,
+        status       TEXT    NOT NULL DEFAULT 'pending',
+        leased       INTEGER NOT NULL DEFAULT 0,
+        attempts     INTEGER NOT NULL DEFAULT 0,
+        max_attempts INTEGER NOT NULL,
+        visible_at   INTEGER NOT NULL,
+        created_at   INTEGER NOT NULL,
+        last_error   TEXT
+    );
+    CREATE INDEX IF NOT EXISTS jobs_available ON jobs (status, visible_at, id);
+";
+
+/**
+    How long to wait for other processes that are using
+    the same queue file before giving up with an error.
+*/
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/**
+    A job that was taken from the queue by `dequeue`.
+*/
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: i64,
+    pub payload: String,
+    pub attempts: u32,
+}
+
+/**
+    A job that has used all of its attempts without being acknowledged.
+*/
+#[derive(Debug, Clone)]
+pub struct FailedJob {
+    pub id: i64,
+    pub payload: String,
+    pub attempts: u32,
+    pub error: Option<String>,
+}
+
+/**
+    The number of jobs in each state.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JobCounts {
+    pub ready: i64,
+    pub delayed: i64,
+    pub inflight: i64,
+    pub failed: i64,
+}
+
+/**
+    Jobs stored in a sqlite database, which are kept across restarts.
+
+    Jobs taken using `dequeue` are hidden from other consumers until their
+    visibility timeout runs out, after which they are given out again, unless
+    they have been acknowledged, or have used all of their attempts.
+*/
+#[derive(Debug)]
+pub struct JobStore {
+    conn: Connection,
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn millis(duration: Duration) -> i64 {
+    i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
+
+impl JobStore {
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // NOTE: Write-ahead logging lets other processes read the
+        // queue while a job is being written, and is faster overall
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    pub fn enqueue(
+        &self,
+        payload: &str,
+        delay: Duration,
+        max_attempts: u32,
+    ) -> rusqlite::Result<i64> {
+        let now = now_millis();
+        self.conn.execute(
+            "INSERT INTO jobs (payload, max_attempts, visible_at, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![payload, max_attempts, now.saturating_add(millis(delay)), now],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /**
+        Takes the oldest job that is ready, hiding it for the given visibility timeout.
+
+        Jobs that timed out on their last attempt are marked as failed first.
+    */
+    pub fn dequeue(&mut self, visibility_timeout: Duration) -> rusqlite::Result<Option<Job>> {
+        let now = now_millis();
+        let tx = self
+            .conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        tx.execute(
+            "UPDATE jobs SET status = 'failed', leased = 0,
+                last_error = COALESCE(last_error, 'Visibility timeout expired')
+            WHERE status = 'pending' AND visible_at <= ?1 AND attempts >= max_attempts",
+            params![now],
+        )?;
+        let job = tx
+            .query_row(
+                "SELECT id, payload, attempts FROM jobs
+                WHERE status = 'pending' AND visible_at <= ?1
+                ORDER BY id LIMIT 1",
+                params![now],
+                |row| {
+                    Ok(Job {
+                        id: row.get(0)?,
+                        payload: row.get(1)?,
+                        attempts: row.get::<_, u32>(2)? + 1,
+                    })
+                },
+            )
+            .optional()?;
+        if let Some(job) = &job {
+            tx.execute(
+                "UPDATE jobs SET attempts = ?2, leased = 1, visible_at = ?3 WHERE id = ?1",
+                params![
+                    job.id,
+                    job.attempts,
+                    now.saturating_add(millis(visibility_timeout))
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(job)
+    }
+
+    /**
+        Removes a job after it has been processed, returning `true` if it was in the queue.
+    */
+    pub fn ack(&self, id: i64) -> rusqlite::Result<bool> {
+        let removed = self.conn.execute(
+            "DELETE FROM jobs WHERE id = ?1 AND status = 'pending'",
+            params![id],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /**
+        Gives a job back to the queue after processing it failed, so that it is retried
+        after the given delay, or marks it as failed if it has used all of its attempts.
+
+        Returns `true` if the job was in the queue.
+    */
+    pub fn nack(&self, id: i64, delay: Duration, error: Option<&str>) -> rusqlite::Result<bool> {
+        let visible_at = now_millis().saturating_add(millis(delay));
+        let updated = self.conn.execute(
+            "UPDATE jobs SET
+                status = CASE WHEN attempts >= max_attempts THEN 'failed' ELSE 'pending' END,
+                leased = 0, visible_at = ?2, last_error = COALESCE(?3, last_error)
+            WHERE id = ?1 AND status = 'pending'",
+            params![id, visible_at, error],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /**
+        Hides a job that is being processed for longer, starting from now.
+
+        Returns `true` if the job was in the queue.
+    */
+    pub fn extend(&self, id: i64, timeout: Duration) -> rusqlite::Result<bool> {
+        let visible_at = now_millis().saturating_add(millis(timeout));
+        let updated = self.conn.execute(
+            "UPDATE jobs SET visible_at = ?2 WHERE id = ?1 AND status = 'pending'",
+            params![id, visible_at],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /**
+        Gives a failed job back to the queue with all of its attempts
+        reset, returning `true` if there was a failed job with the id.
+    */
+    pub fn retry(&self, id: i64) -> rusqlite::Result<bool> {
+        let updated = self.conn.execute(
+            "UPDATE jobs SET status = 'pending', leased = 0, attempts = 0, visible_at = ?2
+            WHERE id = ?1 AND status = 'failed'",
+            params![id, now_millis()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn failed(&self, limit: u32) -> rusqlite::Result<Vec<FailedJob>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, payload, attempts, last_error FROM jobs
+            WHERE status = 'failed' ORDER BY id LIMIT ?1",
+        )?;
+        let jobs = stmt
+            .query_map(params![limit], |row| {
+                Ok(FailedJob {
+                    id: row.get(0)?,
+                    payload: row.get(1)?,
+                    attempts: row.get(2)?,
+                    error: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(jobs)
+    }
+
+    pub fn counts(&self) -> rusqlite::Result<JobCounts> {
+        self.conn.query_row(
+            "SELECT
+                COUNT(*) FILTER (WHERE status = 'pending' AND visible_at <= ?1),
+                COUNT(*) FILTER (WHERE status = 'pending' AND visible_at > ?1 AND leased = 0),
+                COUNT(*) FILTER (WHERE status = 'pending' AND visible_at > ?1 AND leased = 1),
+                COUNT(*) FILTER (WHERE status = 'failed')
+            FROM jobs",
+            params![now_millis()],
+            |row| {
+                Ok(JobCounts {
+                    ready: row.get(0)?,
+                    delayed: row.get(1)?,
+                    inflight: row.get(2)?,
+                    failed: row.get(3)?,
+                })
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> JobStore {
+        JobStore::open(":memory:").unwrap()
+    }
+
+    #[test]
+    fn dequeue_in_order() {
+        let mut store = store();
+        let first = store.enqueue("1", Duration::ZERO, 3).unwrap();
+        let second = store.enqueue("2", Duration::ZERO, 3).unwrap();
+        assert_eq!(
+            store.dequeue(Duration::from_secs(30)).unwrap().unwrap().id,
+            first
+        );
+        assert_eq!(
+            store.dequeue(Duration::from_secs(30)).unwrap().unwrap().id,
+            second
+        );
+        assert!(store.dequeue(Duration::from_secs(30)).unwrap().is_none());
+    }
+
+    #[test]
+    fn expired_jobs_are_retried_then_failed() {
+        let mut store = store();
+        let id = store.enqueue("job", Duration::ZERO, 2).unwrap();
+
+        let job = store.dequeue(Duration::ZERO).unwrap().unwrap();
+        assert_eq!((job.id, job.attempts), (id, 1));
+        let job = store.dequeue(Duration::ZERO).unwrap().unwrap();
+        assert_eq!((job.id, job.attempts), (id, 2));
+
+        assert!(store.dequeue(Duration::ZERO).unwrap().is_none());
+        let failed = store.failed(10).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(
+            failed[0].error.as_deref(),
+            Some("Visibility timeout expired")
+        );
+
+        assert!(store.retry(id).unwrap());
+        assert_eq!(store.dequeue(Duration::ZERO).unwrap().unwrap().attempts, 1);
+    }
+
+    #[test]
+    fn ack_and_nack() {
+        let mut store = store();
+        let id = store.enqueue("job", Duration::ZERO, 2).unwrap();
+
+        store.dequeue(Duration::from_secs(30)).unwrap().unwrap();
+        assert_eq!(store.counts().unwrap().inflight, 1);
+        assert!(store
+            .nack(id, Duration::from_secs(30), Some("oops"))
+            .unwrap());
+        assert_eq!(store.counts().unwrap().delayed, 1);
+        assert!(store.dequeue(Duration::from_secs(30)).unwrap().is_none());
+
+        assert!(store.nack(id, Duration::ZERO, None).unwrap());
+        store.dequeue(Duration::from_secs(30)).unwrap().unwrap();
+        assert!(store.ack(id).unwrap());
+        assert!(!store.ack(id).unwrap());
+        assert_eq!(store.counts().unwrap().ready, 0);
+    }
+}
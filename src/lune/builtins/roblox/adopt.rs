@@ -0,0 +1,35 @@
+use mlua::prelude::*;
+
+/**
+    Options for `roblox.adopt`, which may also be given
+    as a single boolean deciding if the instance should be copied.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AdoptOptions {
+    pub copy: bool,
+}
+
+impl<'lua> FromLua<'lua> for AdoptOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Boolean(copy) => Ok(Self { copy }),
+            LuaValue::Table(t) => match t.raw_get::<_, Option<bool>>("copy") {
+                Ok(copy) => Ok(Self {
+                    copy: copy.unwrap_or(false),
+                }),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'copy' in adopt options".to_string(),
+                )),
+            },
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "AdoptOptions",
+                message: Some(format!(
+                    "Invalid adopt options - expected boolean, table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
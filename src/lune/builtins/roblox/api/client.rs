@@ -0,0 +1,179 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use mlua::prelude::*;
+use reqwest::{Method, Url};
+use serde_json::Value as JsonValue;
+
+use crate::lune::builtins::net::{parse_retry_after, send_builtin_request, RateLimiter};
+
+/**
+    Default number of requests per second sent to the web api.
+*/
+const DEFAULT_REQUESTS_PER_SECOND: f64 = 10.0;
+
+/**
+    Maximum number of times that a single request is sent
+    again after the web api told us to slow down.
+*/
+const MAX_RATE_LIMITED_ATTEMPTS: u32 = 5;
+
+const COOKIE_NAME: &str = ".ROBLOSECURITY";
+const CSRF_HEADER: &str = "x-csrf-token";
+
+/**
+    A client for the Roblox web api.
+
+    Requests are rate limited, sent again after being rate limited, and
+    authenticated using the cookie that was configured, if any. Requests that
+    change something need a CSRF token, which is fetched automatically, since
+    the web api sends a new token along with requests that are missing one.
+*/
+#[derive(Debug)]
+pub struct ApiClient {
+    cookie: RefCell<Option<String>>,
+    csrf_token: RefCell<Option<String>>,
+    limiter: RateLimiter,
+}
+
+impl ApiClient {
+    /**
+        Gets the web api client for the given Lua struct, creating it if it does
+        not yet exist, using the cookie of the logged in Roblox Studio user, if any.
+    */
+    pub fn get(lua: &Lua) -> Rc<Self> {
+        if let Some(client) = lua.app_data_ref::<Rc<ApiClient>>() {
+            return Rc::clone(&client);
+        }
+
+        let client = Rc::new(Self {
+            cookie: RefCell::new(rbx_cookie::get()),
+            csrf_token: RefCell::new(None),
+            limiter: RateLimiter::new(interval_for(DEFAULT_REQUESTS_PER_SECOND)),
+        });
+        lua.set_app_data(Rc::clone(&client));
+        client
+    }
+
+    /**
+        Sets the cookie used to authenticate requests, which may be
+        either the full cookie or only the value of the cookie.
+    */
+    pub fn set_cookie(&self, cookie: Option<String>) {
+        let cookie = cookie.map(|cookie| {
+            if cookie.starts_with(COOKIE_NAME) {
+                cookie
+            } else {
+                format!("{COOKIE_NAME}={cookie}")
+            }
+        });
+        self.cookie.replace(cookie);
+        self.csrf_token.replace(None);
+    }
+
+    pub fn set_requests_per_second(&self, rps: f64) {
+        self.limiter.set_interval(interval_for(rps));
+    }
+
+    /**
+        Sends a request to the web api, returning the decoded json body.
+    */
+    pub async fn send(
+        &self,
+        lua: &Lua,
+        method: Method,
+        url: Url,
+        body: Option<&JsonValue>,
+    ) -> LuaResult<JsonValue> {
+        let body = match body {
+            Some(body) => serde_json::to_vec(body).into_lua_err()?,
+            None => Vec::new(),
+        };
+
+        let mut attempts = 0;
+        let mut refreshed_token = false;
+        loop {
+            attempts += 1;
+            self.limiter.acquire().await;
+
+            let headers = self.headers(!body.is_empty());
+            let res =
+                send_builtin_request(lua, method.clone(), url.clone(), &headers, &body).await?;
+            let status = res.status();
+
+            // NOTE: The web api answers requests with a missing or outdated
+            // CSRF token with a new token, and the request should be sent again
+            if status.as_u16() == 403 && !refreshed_token {
+                let token = res
+                    .headers()
+                    .get(CSRF_HEADER)
+                    .and_then(|value| value.to_str().ok());
+                if let Some(token) = token {
+                    self.csrf_token.replace(Some(token.to_string()));
+                    refreshed_token = true;
+                    continue;
+                }
+            }
+
+            if status.as_u16() == 429 && attempts <= MAX_RATE_LIMITED_ATTEMPTS {
+                let delay = res
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_retry_after)
+                    .unwrap_or_else(|| Duration::from_secs(1 << attempts));
+                self.limiter.pause_for(delay);
+                continue;
+            }
+
+            let bytes = res.bytes().await.into_lua_err()?;
+            if !status.is_success() {
+                return Err(LuaError::RuntimeError(format!(
+                    "Request to '{}' failed with status {} - {}",
+                    url.path(),
+                    status.as_u16(),
+                    error_message(&bytes).unwrap_or_else(|| status.to_string())
+                )));
+            }
+            if bytes.is_empty() {
+                return Ok(JsonValue::Null);
+            }
+            return serde_json::from_slice(&bytes).map_err(|e| {
+                LuaError::RuntimeError(format!(
+                    "Request to '{}' returned an invalid json body\n> {e}",
+                    url.path()
+                ))
+            });
+        }
+    }
+
+    fn headers(&self, has_body: bool) -> Vec<(String, String)> {
+        let mut headers = vec![("Accept".to_string(), "application/json".to_string())];
+        if has_body {
+            headers.push(("Content-Type".to_string(), "application/json".to_string()));
+        }
+        if let Some(cookie) = self.cookie.borrow().as_ref() {
+            headers.push(("Cookie".to_string(), cookie.clone()));
+        }
+        if let Some(token) = self.csrf_token.borrow().as_ref() {
+            headers.push((CSRF_HEADER.to_string(), token.clone()));
+        }
+        headers
+    }
+}
+
+fn interval_for(rps: f64) -> Duration {
+    Duration::from_secs_f64(1.0 / rps)
+}
+
+/**
+    Gets the first error message from an error body of the web api,
+    which looks like `{ "errors": [{ "code": 0, "message": "..." }] }`.
+*/
+fn error_message(bytes: &[u8]) -> Option<String> {
+    let body = serde_json::from_slice::<JsonValue>(bytes).ok()?;
+    body.get("errors")?
+        .get(0)?
+        .get("message")?
+        .as_str()
+        .map(str::to_string)
+}
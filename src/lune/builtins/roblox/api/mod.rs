@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+
+use mlua::prelude::*;
+use reqwest::{Method, Url};
+use serde_json::{json, Value as JsonValue};
+
+use crate::lune::util::TableBuilder;
+
+mod client;
+mod pages;
+
+use client::ApiClient;
+use pages::create_pages_table;
+
+const USERS_URL: &str = "https://users.roblox.com";
+const GROUPS_URL: &str = "https://groups.roblox.com";
+const CATALOG_URL: &str = "https://catalog.roblox.com";
+const THUMBNAILS_URL: &str = "https://thumbnails.roblox.com";
+
+/**
+    Maximum number of ids that thumbnails can be requested for at once.
+*/
+const THUMBNAILS_BATCH_SIZE: usize = 100;
+
+const LUA_SERIALIZE_OPTIONS: LuaSerializeOptions = LuaSerializeOptions::new()
+    .set_array_metatable(false)
+    .serialize_none_to_null(false)
+    .serialize_unit_to_null(false);
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    let users = TableBuilder::new(lua)?
+        .with_async_function("get", users_get)?
+        .with_async_function("getByUsernames", users_get_by_usernames)?
+        .with_async_function("getGroupRoles", users_get_group_roles)?
+        .build_readonly()?;
+    let groups = TableBuilder::new(lua)?
+        .with_async_function("get", groups_get)?
+        .with_async_function("getRoles", groups_get_roles)?
+        .with_async_function("getMembers", groups_get_members)?
+        .build_readonly()?;
+    let catalog = TableBuilder::new(lua)?
+        .with_async_function("search", catalog_search)?
+        .build_readonly()?;
+    let thumbnails = TableBuilder::new(lua)?
+        .with_async_function("get", thumbnails_get)?
+        .build_readonly()?;
+    TableBuilder::new(lua)?
+        .with_value("users", users)?
+        .with_value("groups", groups)?
+        .with_value("catalog", catalog)?
+        .with_value("thumbnails", thumbnails)?
+        .with_function("configure", configure)?
+        .build_readonly()
+}
+
+fn json_to_lua<'lua>(lua: &'lua Lua, value: &JsonValue) -> LuaResult<LuaValue<'lua>> {
+    lua.to_value_with(value, LUA_SERIALIZE_OPTIONS)
+}
+
+fn api_url(base: &str, path: &str) -> LuaResult<Url> {
+    Url::parse(&format!("{base}{path}")).into_lua_err()
+}
+
+async fn get_json(lua: &Lua, url: Url) -> LuaResult<JsonValue> {
+    ApiClient::get(lua).send(lua, Method::GET, url, None).await
+}
+
+/**
+    Takes a single field out of a response, such as the `data`
+    array that most endpoints wrap their results in.
+*/
+fn take_field(mut body: JsonValue, field: &str) -> JsonValue {
+    match body.get_mut(field) {
+        Some(value) => value.take(),
+        None => JsonValue::Array(Vec::new()),
+    }
+}
+
+// Users
+
+async fn users_get(lua: &'static Lua, user_id: u64) -> LuaResult<LuaValue<'static>> {
+    let url = api_url(USERS_URL, &format!("/v1/users/{user_id}"))?;
+    json_to_lua(lua, &get_json(lua, url).await?)
+}
+
+async fn users_get_by_usernames(
+    lua: &'static Lua,
+    usernames: Vec<String>,
+) -> LuaResult<LuaValue<'static>> {
+    let url = api_url(USERS_URL, "/v1/usernames/users")?;
+    let body = json!({
+        "usernames": usernames,
+        "excludeBannedUsers": false,
+    });
+    let res = ApiClient::get(lua)
+        .send(lua, Method::POST, url, Some(&body))
+        .await?;
+    json_to_lua(lua, &take_field(res, "data"))
+}
+
+async fn users_get_group_roles(lua: &'static Lua, user_id: u64) -> LuaResult<LuaValue<'static>> {
+    let url = api_url(GROUPS_URL, &format!("/v2/users/{user_id}/groups/roles"))?;
+    json_to_lua(lua, &take_field(get_json(lua, url).await?, "data"))
+}
+
+// Groups
+
+async fn groups_get(lua: &'static Lua, group_id: u64) -> LuaResult<LuaValue<'static>> {
+    let url = api_url(GROUPS_URL, &format!("/v1/groups/{group_id}"))?;
+    json_to_lua(lua, &get_json(lua, url).await?)
+}
+
+async fn groups_get_roles(lua: &'static Lua, group_id: u64) -> LuaResult<LuaValue<'static>> {
+    let url = api_url(GROUPS_URL, &format!("/v1/groups/{group_id}/roles"))?;
+    json_to_lua(lua, &take_field(get_json(lua, url).await?, "roles"))
+}
+
+#[derive(Debug, Default)]
+struct MembersOptions {
+    role_id: Option<u64>,
+    limit: Option<u32>,
+    sort_order: Option<String>,
+    cursor: Option<String>,
+}
+
+impl<'lua> FromLua<'lua> for MembersOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let tab = match value {
+            LuaValue::Nil => return Ok(Self::default()),
+            LuaValue::Table(tab) => tab,
+            value => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "MembersOptions",
+                    message: Some(format!(
+                        "Invalid members options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        Ok(Self {
+            role_id: tab.raw_get("roleId")?,
+            limit: tab.raw_get("limit")?,
+            sort_order: tab.raw_get("sortOrder")?,
+            cursor: tab.raw_get("cursor")?,
+        })
+    }
+}
+
+async fn groups_get_members(
+    lua: &'static Lua,
+    (group_id, options): (u64, MembersOptions),
+) -> LuaResult<LuaTable<'static>> {
+    let path = match options.role_id {
+        Some(role_id) => format!("/v1/groups/{group_id}/roles/{role_id}/users"),
+        None => format!("/v1/groups/{group_id}/users"),
+    };
+    let mut url = api_url(GROUPS_URL, &path)?;
+    url.query_pairs_mut()
+        .append_pair("limit", &options.limit.unwrap_or(100).to_string())
+        .append_pair("sortOrder", options.sort_order.as_deref().unwrap_or("Asc"));
+    create_pages_table(lua, url, options.cursor).await
+}
+
+// Catalog
+
+async fn catalog_search(
+    lua: &'static Lua,
+    options: Option<LuaTable<'static>>,
+) -> LuaResult<LuaTable<'static>> {
+    let mut url = api_url(CATALOG_URL, "/v1/search/items/details")?;
+    let mut cursor = None;
+    let mut has_limit = false;
+    {
+        let mut pairs = url.query_pairs_mut();
+        if let Some(options) = options {
+            for pair in options.pairs::<String, LuaValue>() {
+                let (key, value) = pair?;
+                let value = match value {
+                    LuaValue::String(s) => s.to_str()?.to_string(),
+                    LuaValue::Integer(i) => i.to_string(),
+                    LuaValue::Number(n) => n.to_string(),
+                    LuaValue::Boolean(b) => b.to_string(),
+                    value => {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Invalid catalog search option '{key}' - \
+                            expected string, number or boolean, got {}",
+                            value.type_name()
+                        )))
+                    }
+                };
+                match key.as_str() {
+                    "cursor" => cursor = Some(value),
+                    key => {
+                        has_limit |= key == "limit";
+                        pairs.append_pair(key, &value);
+                    }
+                }
+            }
+        }
+        if !has_limit {
+            pairs.append_pair("limit", "30");
+        }
+    }
+    create_pages_table(lua, url, cursor).await
+}
+
+// Thumbnails
+
+#[derive(Debug)]
+struct ThumbnailOptions {
+    size: String,
+    format: String,
+    is_circular: bool,
+}
+
+impl<'lua> FromLua<'lua> for ThumbnailOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let tab = match value {
+            LuaValue::Nil => None,
+            LuaValue::Table(tab) => Some(tab),
+            value => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "ThumbnailOptions",
+                    message: Some(format!(
+                        "Invalid thumbnail options - expected table or nil, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        let get = |key: &str| match &tab {
+            Some(tab) => tab.raw_get::<_, Option<LuaValue>>(key),
+            None => Ok(None),
+        };
+        Ok(Self {
+            size: match get("size")? {
+                Some(LuaValue::String(s)) => s.to_str()?.to_string(),
+                _ => "150x150".to_string(),
+            },
+            format: match get("format")? {
+                Some(LuaValue::String(s)) => s.to_str()?.to_string(),
+                _ => "Png".to_string(),
+            },
+            is_circular: matches!(get("isCircular")?, Some(LuaValue::Boolean(true))),
+        })
+    }
+}
+
+fn thumbnail_endpoint(kind: &str) -> LuaResult<(&'static str, &'static str)> {
+    Ok(match kind {
+        "avatar" => ("/v1/users/avatar", "userIds"),
+        "avatar-headshot" => ("/v1/users/avatar-headshot", "userIds"),
+        "avatar-bust" => ("/v1/users/avatar-bust", "userIds"),
+        "asset" => ("/v1/assets", "assetIds"),
+        "group-icon" => ("/v1/groups/icons", "groupIds"),
+        "game-icon" => ("/v1/games/icons", "universeIds"),
+        _ => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid thumbnail kind '{kind}' - expected one of \
+                'avatar', 'avatar-headshot', 'avatar-bust', \
+                'asset', 'group-icon' or 'game-icon'"
+            )))
+        }
+    })
+}
+
+async fn thumbnails_get(
+    lua: &'static Lua,
+    (kind, ids, options): (String, Vec<u64>, ThumbnailOptions),
+) -> LuaResult<HashMap<u64, String>> {
+    let (path, ids_param) = thumbnail_endpoint(&kind)?;
+    let mut images = HashMap::new();
+    for chunk in ids.chunks(THUMBNAILS_BATCH_SIZE) {
+        let ids = chunk
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut url = api_url(THUMBNAILS_URL, path)?;
+        url.query_pairs_mut()
+            .append_pair(ids_param, &ids)
+            .append_pair("size", &options.size)
+            .append_pair("format", &options.format)
+            .append_pair("isCircular", &options.is_circular.to_string());
+
+        // NOTE: Thumbnails that are still being generated or that
+        // have been moderated do not have an image url, so we skip them
+        let data = take_field(get_json(lua, url).await?, "data");
+        for thumbnail in data.as_array().into_iter().flatten() {
+            let id = thumbnail.get("targetId").and_then(JsonValue::as_u64);
+            let state = thumbnail.get("state").and_then(JsonValue::as_str);
+            let image = thumbnail.get("imageUrl").and_then(JsonValue::as_str);
+            if let (Some(id), Some("Completed"), Some(image)) = (id, state, image) {
+                images.insert(id, image.to_string());
+            }
+        }
+    }
+    Ok(images)
+}
+
+// Configuration
+
+fn configure(lua: &Lua, config: LuaTable) -> LuaResult<()> {
+    let client = ApiClient::get(lua);
+    match config.raw_get::<_, LuaValue>("cookie")? {
+        LuaValue::Nil => {}
+        LuaValue::String(s) => client.set_cookie(Some(s.to_str()?.to_string())),
+        LuaValue::Boolean(false) => client.set_cookie(None),
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid 'cookie' in api config - expected string or false, got {}",
+                value.type_name()
+            )))
+        }
+    }
+    match config.raw_get::<_, Option<f64>>("requestsPerSecond") {
+        Ok(None) => {}
+        Ok(Some(rps)) if rps.is_finite() && rps > 0.0 => client.set_requests_per_second(rps),
+        _ => {
+            return Err(LuaError::RuntimeError(
+                "Invalid 'requestsPerSecond' in api config - expected a positive number"
+                    .to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
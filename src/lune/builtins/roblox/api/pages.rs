@@ -0,0 +1,99 @@
+use std::{cell::RefCell, rc::Rc};
+
+use mlua::prelude::*;
+use reqwest::{Method, Url};
+use serde_json::Value as JsonValue;
+
+use crate::lune::util::TableBuilder;
+
+use super::{client::ApiClient, json_to_lua};
+
+/**
+    Pages of results from a web api endpoint that uses cursors, where
+    each response looks like `{ "data": [...], "nextPageCursor": "..." }`.
+*/
+#[derive(Debug)]
+struct ApiPages {
+    url: Url,
+    page: RefCell<JsonValue>,
+    next_cursor: RefCell<Option<String>>,
+}
+
+impl ApiPages {
+    async fn fetch(lua: &Lua, url: Url, cursor: Option<&str>) -> LuaResult<Self> {
+        let mut page_url = url.clone();
+        if let Some(cursor) = cursor {
+            page_url.query_pairs_mut().append_pair("cursor", cursor);
+        }
+
+        let mut body = ApiClient::get(lua)
+            .send(lua, Method::GET, page_url, None)
+            .await?;
+        let page = match body.get_mut("data") {
+            Some(data) => data.take(),
+            None => JsonValue::Array(Vec::new()),
+        };
+        let next_cursor = body
+            .get("nextPageCursor")
+            .and_then(JsonValue::as_str)
+            .filter(|cursor| !cursor.is_empty())
+            .map(str::to_string);
+
+        Ok(Self {
+            url,
+            page: RefCell::new(page),
+            next_cursor: RefCell::new(next_cursor),
+        })
+    }
+
+    fn is_finished(&self) -> bool {
+        self.next_cursor.borrow().is_none()
+    }
+
+    async fn advance(&self, lua: &Lua) -> LuaResult<()> {
+        let cursor = match self.next_cursor.borrow().clone() {
+            Some(cursor) => cursor,
+            None => {
+                return Err(LuaError::RuntimeError(
+                    "Failed to advance pages - there are no more pages".to_string(),
+                ))
+            }
+        };
+        let next = Self::fetch(lua, self.url.clone(), Some(&cursor)).await?;
+        self.page.replace(next.page.into_inner());
+        self.next_cursor.replace(next.next_cursor.into_inner());
+        Ok(())
+    }
+}
+
+/**
+    Fetches the first page of results from the given url, starting
+    at the given cursor, and creates a table for reading the pages.
+
+    The url should not contain a cursor, since it is set for each page.
+*/
+pub async fn create_pages_table(
+    lua: &'static Lua,
+    url: Url,
+    cursor: Option<String>,
+) -> LuaResult<LuaTable<'static>> {
+    let pages = Rc::new(ApiPages::fetch(lua, url, cursor.as_deref()).await?);
+    TableBuilder::new(lua)?
+        .with_function("getCurrentPage", {
+            let pages = Rc::clone(&pages);
+            move |lua, _: LuaValue| json_to_lua(lua, &pages.page.borrow())
+        })?
+        .with_function("getNextCursor", {
+            let pages = Rc::clone(&pages);
+            move |_, _: LuaValue| Ok(pages.next_cursor.borrow().clone())
+        })?
+        .with_function("isFinished", {
+            let pages = Rc::clone(&pages);
+            move |_, _: LuaValue| Ok(pages.is_finished())
+        })?
+        .with_async_function("advanceToNextPage", move |lua, _: LuaValue| {
+            let pages = Rc::clone(&pages);
+            async move { pages.advance(lua).await }
+        })?
+        .build_readonly()
+}
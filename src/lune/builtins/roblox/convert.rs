@@ -0,0 +1,118 @@
+use mlua::prelude::*;
+use tokio::task;
+
+use crate::roblox::document::{Document, DocumentFormat, DocumentKind};
+
+/**
+    A kind and format of document, given as one of the canonical file extensions.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConvertTarget {
+    kind: DocumentKind,
+    format: DocumentFormat,
+}
+
+impl<'lua> FromLua<'lua> for ConvertTarget {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            let extension = s.to_string_lossy().to_ascii_lowercase();
+            let extension = extension.trim().trim_start_matches('.');
+            match (
+                DocumentKind::from_extension(extension),
+                DocumentFormat::from_extension(extension),
+            ) {
+                (Some(kind), Some(format)) => Ok(Self { kind, format }),
+                _ => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "ConvertTarget",
+                    message: Some(format!(
+                        "Invalid file format '{extension}', valid formats are: rbxl, rbxlx, rbxm, rbxmx"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ConvertTarget",
+                message: None,
+            })
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ConvertOptions {
+    from: Option<ConvertTarget>,
+    to: ConvertTarget,
+}
+
+impl<'lua> FromLua<'lua> for ConvertOptions {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::Table(tab) = value {
+            let from = match tab.raw_get::<_, LuaValue>("from")? {
+                LuaValue::Nil => None,
+                value => Some(ConvertTarget::from_lua(value, lua).map_err(|e| {
+                    LuaError::RuntimeError(format!(
+                        "Invalid option value for 'from' in convert options - {e}"
+                    ))
+                })?),
+            };
+            let to = match tab.raw_get::<_, LuaValue>("to")? {
+                LuaValue::Nil => {
+                    return Err(LuaError::RuntimeError(
+                        "Missing option value for 'to' in convert options".to_string(),
+                    ))
+                }
+                value => ConvertTarget::from_lua(value, lua).map_err(|e| {
+                    LuaError::RuntimeError(format!(
+                        "Invalid option value for 'to' in convert options - {e}"
+                    ))
+                })?,
+            };
+            Ok(Self { from, to })
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ConvertOptions",
+                message: Some(format!(
+                    "Invalid convert options - expected table, got {}",
+                    value.type_name()
+                )),
+            })
+        }
+    }
+}
+
+/**
+    Converts the contents of a place or model file into another kind
+    and / or format of file, working directly on the decoded dom.
+*/
+pub async fn convert_bytes(bytes: Vec<u8>, options: ConvertOptions) -> LuaResult<Vec<u8>> {
+    let fut = task::spawn_blocking(move || {
+        let doc = match options.from {
+            Some(from) => Document::from_bytes(bytes, from.kind)?,
+            None => Document::from_bytes_auto(bytes)?,
+        };
+        if let Some(from) = options.from {
+            if from.format != doc.format() {
+                return Err(LuaError::RuntimeError(format!(
+                    "Failed to convert document - expected {} contents, got {} contents",
+                    format_name(from.format),
+                    format_name(doc.format()),
+                )));
+            }
+        }
+        let bytes = doc
+            .into_kind(options.to.kind)?
+            .to_bytes_with_format(options.to.format)?;
+        Ok::<_, LuaError>(bytes)
+    });
+    fut.await.into_lua_err()?
+}
+
+fn format_name(format: DocumentFormat) -> &'static str {
+    match format {
+        DocumentFormat::Binary => "binary",
+        DocumentFormat::Xml => "xml",
+    }
+}
@@ -0,0 +1,301 @@
+use std::borrow::Cow;
+
+use mlua::prelude::*;
+
+use crate::{
+    lune::scheduler::Scheduler,
+    roblox::{
+        instance::{base::instance_property_get, Instance},
+        shared::instance::{class_is_a, find_property_info},
+    },
+};
+
+const COLUMN_PATH: &str = "Path";
+const COLUMN_CLASS_NAME: &str = "ClassName";
+
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+// NOTE: Spreadsheet applications treat cells starting with any of these
+// characters as formulas, which may run commands when the file is opened
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Tsv,
+}
+
+impl ExportFormat {
+    fn delimiter(&self) -> char {
+        match self {
+            Self::Csv => ',',
+            Self::Tsv => '\t',
+        }
+    }
+
+    /**
+        Escapes a single field so that it can be safely written as part of a row.
+
+        Fields containing delimiters, quotes, or newlines are quoted,
+        and any quotes inside of the field are doubled, as per RFC 4180.
+
+        If `escape_formulas` is set, fields that would be treated as formulas
+        by spreadsheet applications are also prefixed with a single quote.
+    */
+    fn escape_field(&self, field: &str, escape_formulas: bool) -> String {
+        let field = if escape_formulas && field.starts_with(FORMULA_PREFIXES) {
+            Cow::Owned(format!("'{field}"))
+        } else {
+            Cow::Borrowed(field)
+        };
+        let needs_quotes = field.contains(self.delimiter())
+            || field.contains('"')
+            || field.contains('\n')
+            || field.contains('\r');
+        if needs_quotes {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.into_owned()
+        }
+    }
+
+    fn format_row(&self, fields: &[String], escape_formulas: bool) -> String {
+        let mut row = fields
+            .iter()
+            .map(|field| self.escape_field(field, escape_formulas))
+            .collect::<Vec<_>>()
+            .join(&self.delimiter().to_string());
+        row.push('\n');
+        row
+    }
+}
+
+impl<'lua> FromLua<'lua> for ExportFormat {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "csv" => Ok(Self::Csv),
+                "tsv" => Ok(Self::Tsv),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "ExportFormat",
+                    message: Some(format!(
+                        "Invalid export format '{kind}', valid formats are: csv, tsv"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ExportFormat",
+                message: None,
+            })
+        }
+    }
+}
+
+pub enum ExportFilter<'lua> {
+    ClassName(String),
+    Function(LuaFunction<'lua>),
+}
+
+impl<'lua> ExportFilter<'lua> {
+    fn matches(&self, lua: &'lua Lua, instance: &Instance) -> LuaResult<bool> {
+        match self {
+            Self::ClassName(class_name) => {
+                Ok(class_is_a(instance.get_class_name(), class_name).unwrap_or(false))
+            }
+            Self::Function(f) => {
+                let keep: Option<bool> = f.call(instance.clone().into_lua(lua)?)?;
+                Ok(keep.unwrap_or(false))
+            }
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for ExportFilter<'lua> {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::String(s) => Ok(Self::ClassName(s.to_str()?.to_string())),
+            LuaValue::Function(f) => Ok(Self::Function(f)),
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ExportFilter",
+                message: Some(format!(
+                    "Invalid export filter - expected string or function, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+pub struct ExportOptions<'lua> {
+    pub(crate) format: ExportFormat,
+    pub(crate) columns: Vec<String>,
+    pub(crate) filter: Option<ExportFilter<'lua>>,
+    pub(crate) escape_formulas: bool,
+}
+
+impl<'lua> FromLua<'lua> for ExportOptions<'lua> {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self {
+                format: ExportFormat::Csv,
+                columns: Vec::new(),
+                filter: None,
+                escape_formulas: true,
+            }),
+            LuaValue::Table(t) => Ok(Self {
+                format: match t.get::<_, Option<LuaValue>>("format")? {
+                    Some(value) => ExportFormat::from_lua(value, lua)?,
+                    None => ExportFormat::Csv,
+                },
+                columns: t
+                    .get::<_, Option<Vec<String>>>("columns")?
+                    .unwrap_or_default(),
+                filter: t.get::<_, Option<ExportFilter>>("filter")?,
+                escape_formulas: t.get::<_, Option<bool>>("escapeFormulas")?.unwrap_or(true),
+            }),
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ExportOptions",
+                message: Some(format!(
+                    "Invalid export options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    A writable handle, such as a file handle returned by `fs.open`, that
+    rows are written to in chunks by calling its `write` method.
+*/
+pub struct ExportWriter<'lua> {
+    handle: LuaValue<'lua>,
+    write: LuaFunction<'lua>,
+    buffer: String,
+}
+
+impl<'lua> ExportWriter<'lua> {
+    async fn write(&mut self, lua: &'lua Lua, row: &str) -> LuaResult<()> {
+        self.buffer.push_str(row);
+        if self.buffer.len() >= WRITE_BUFFER_SIZE {
+            self.flush(lua).await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self, lua: &'lua Lua) -> LuaResult<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::take(&mut self.buffer);
+        let sched = *lua
+            .app_data_ref::<&Scheduler>()
+            .expect("Lua struct is missing scheduler");
+        let thread_id = sched.push_back(lua, self.write.clone(), (self.handle.clone(), chunk))?;
+        sched.wait_for_thread(lua, thread_id).await?;
+        Ok(())
+    }
+}
+
+impl<'lua> FromLua<'lua> for ExportWriter<'lua> {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let write = match &value {
+            LuaValue::Table(t) => t.get::<_, Option<LuaFunction>>("write")?,
+            _ => None,
+        };
+        match write {
+            Some(write) => Ok(Self {
+                handle: value,
+                write,
+                buffer: String::new(),
+            }),
+            None => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ExportWriter",
+                message: Some(
+                    "Invalid export handle - expected a handle with a write method, such as one returned by fs.open"
+                        .to_string(),
+                ),
+            }),
+        }
+    }
+}
+
+/**
+    Exports the given root instances, and all of their descendants, as rows to the given writer.
+
+    Instances are visited depth-first and rows are written as the instance tree is traversed,
+    meaning neither intermediate Lua tables nor a full list of descendants are created.
+
+    Returns the number of rows written, not including the header row.
+*/
+pub async fn export_to_writer<'lua>(
+    lua: &'lua Lua,
+    roots: Vec<Instance>,
+    mut writer: ExportWriter<'lua>,
+    options: ExportOptions<'lua>,
+) -> LuaResult<usize> {
+    let format = options.format;
+    let escape_formulas = options.escape_formulas;
+    let tostring: LuaFunction = lua.globals().get("tostring")?;
+
+    let mut header = vec![COLUMN_PATH.to_string(), COLUMN_CLASS_NAME.to_string()];
+    header.extend(options.columns.iter().cloned());
+    writer
+        .write(lua, &format.format_row(&header, escape_formulas))
+        .await?;
+
+    let mut rows = 0;
+    let mut stack = roots.into_iter().rev().collect::<Vec<_>>();
+    while let Some(instance) = stack.pop() {
+        stack.extend(instance.get_children().into_iter().rev());
+        if let Some(filter) = &options.filter {
+            if !filter.matches(lua, &instance)? {
+                continue;
+            }
+        }
+        let mut fields = vec![
+            instance.get_full_name(),
+            instance.get_class_name().to_string(),
+        ];
+        for column in &options.columns {
+            fields.push(format_column(lua, &tostring, &instance, column)?);
+        }
+        writer
+            .write(lua, &format.format_row(&fields, escape_formulas))
+            .await?;
+        rows += 1;
+    }
+
+    writer.flush(lua).await?;
+    Ok(rows)
+}
+
+fn format_column<'lua>(
+    lua: &'lua Lua,
+    tostring: &LuaFunction<'lua>,
+    instance: &Instance,
+    column: &str,
+) -> LuaResult<String> {
+    // NOTE: Columns that are not valid properties for the instance
+    // are left empty, audits commonly span many different classes
+    let is_property = matches!(column, "ClassName" | "Name" | "Parent")
+        || find_property_info(instance.get_class_name(), column).is_some();
+    if column == COLUMN_PATH {
+        return Ok(instance.get_full_name());
+    } else if !is_property {
+        return Ok(String::new());
+    }
+    Ok(
+        match instance_property_get(lua, instance, column.to_string())? {
+            LuaValue::Nil => String::new(),
+            LuaValue::String(s) => s.to_string_lossy().to_string(),
+            value => tostring.call::<_, String>(value)?,
+        },
+    )
+}
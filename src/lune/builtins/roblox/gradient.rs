@@ -0,0 +1,164 @@
+use mlua::prelude::*;
+
+use crate::roblox::datatypes::types::{ColorSequence, NumberSequence};
+
+/**
+    Options for `roblox.bakeGradient`.
+*/
+#[derive(Debug, Clone)]
+pub struct GradientOptions {
+    pub height: u32,
+    pub png: bool,
+    pub transparency: Option<NumberSequence>,
+}
+
+impl Default for GradientOptions {
+    fn default() -> Self {
+        Self {
+            height: 1,
+            png: false,
+            transparency: None,
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for GradientOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Table(t) => {
+                let height = match t.raw_get::<_, Option<u32>>("height") {
+                    Ok(Some(0)) | Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'height' in gradient options - expected a positive integer"
+                                .to_string(),
+                        ))
+                    }
+                    Ok(height) => height.unwrap_or(1),
+                };
+                let png = match t.raw_get::<_, Option<bool>>("png") {
+                    Ok(png) => png.unwrap_or(false),
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'png' in gradient options".to_string(),
+                        ))
+                    }
+                };
+                let transparency = match t.raw_get::<_, LuaValue>("transparency")? {
+                    LuaValue::Nil => None,
+                    LuaValue::UserData(ud) if ud.is::<NumberSequence>() => {
+                        Some(ud.borrow::<NumberSequence>()?.clone())
+                    }
+                    _ => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'transparency' in gradient options - expected a NumberSequence"
+                                .to_string(),
+                        ))
+                    }
+                };
+                Ok(Self {
+                    height,
+                    png,
+                    transparency,
+                })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "GradientOptions",
+                message: Some(format!(
+                    "Invalid gradient options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    Linearly interpolates between the keypoints surrounding the given time,
+    where keypoints are given as pairs of times and values.
+*/
+fn sample<const N: usize>(keypoints: &[(f32, [f32; N])], time: f32) -> [f32; N] {
+    let index = keypoints
+        .iter()
+        .position(|(t, _)| *t > time)
+        .unwrap_or(keypoints.len());
+    if index == 0 {
+        return keypoints[0].1;
+    }
+    if index == keypoints.len() {
+        return keypoints[index - 1].1;
+    }
+    let (t0, v0) = keypoints[index - 1];
+    let (t1, v1) = keypoints[index];
+    let alpha = if t1 > t0 {
+        (time - t0) / (t1 - t0)
+    } else {
+        0.0
+    };
+    let mut value = v0;
+    for (component, target) in value.iter_mut().zip(v1) {
+        *component += (target - *component) * alpha;
+    }
+    value
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/**
+    Bakes a color sequence, and optionally a transparency sequence, into
+    raw RGBA pixels, with the gradient going from left to right.
+*/
+pub fn bake_gradient(
+    colors: &ColorSequence,
+    options: &GradientOptions,
+    width: u32,
+) -> LuaResult<Vec<u8>> {
+    let color_keypoints = colors
+        .keypoints
+        .iter()
+        .map(|k| (k.time, [k.color.r, k.color.g, k.color.b]))
+        .collect::<Vec<_>>();
+    let transparency_keypoints = match &options.transparency {
+        Some(sequence) => sequence
+            .keypoints
+            .iter()
+            .map(|k| (k.time, [k.value]))
+            .collect::<Vec<_>>(),
+        None => vec![(0.0, [0.0])],
+    };
+
+    let mut row = Vec::with_capacity(width as usize * 4);
+    for x in 0..width {
+        let time = if width > 1 {
+            x as f32 / (width - 1) as f32
+        } else {
+            0.0
+        };
+        let [r, g, b] = sample(&color_keypoints, time);
+        let [transparency] = sample(&transparency_keypoints, time);
+        row.extend_from_slice(&[
+            to_byte(r),
+            to_byte(g),
+            to_byte(b),
+            to_byte(1.0 - transparency),
+        ]);
+    }
+    let pixels = row.repeat(options.height as usize);
+
+    if !options.png {
+        return Ok(pixels);
+    }
+
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, width, options.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to encode gradient as png - {e}")))?;
+    Ok(bytes)
+}
@@ -5,14 +5,28 @@ use crate::{
     lune::util::TableBuilder,
     roblox::{
         self,
+        datatypes::types::ColorSequence,
         document::{Document, DocumentError, DocumentFormat, DocumentKind},
-        instance::Instance,
+        instance::{data_model, Instance},
         reflection::Database as ReflectionDatabase,
     },
 };
 
 use tokio::task;
 
+mod adopt;
+mod api;
+mod convert;
+mod export;
+mod gradient;
+mod serialize;
+
+use adopt::AdoptOptions;
+use convert::{convert_bytes, ConvertOptions};
+use export::{export_to_writer, ExportOptions, ExportWriter};
+use gradient::{bake_gradient, GradientOptions};
+use serialize::SerializeOptions;
+
 static REFLECTION_DATABASE: OnceCell<ReflectionDatabase> = OnceCell::new();
 
 pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
@@ -29,8 +43,14 @@ pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
         .with_async_function("deserializeModel", deserialize_model)?
         .with_async_function("serializePlace", serialize_place)?
         .with_async_function("serializeModel", serialize_model)?
+        .with_function("adopt", adopt)?
+        .with_function("bakeGradient", bake_gradient_to_string)?
+        .with_async_function("convert", convert)?
+        .with_async_function("export", export)?
+        .with_async_function("validate", validate)?
         .with_function("getAuthCookie", get_auth_cookie)?
         .with_function("getReflectionDatabase", get_reflection_database)?
+        .with_value("api", api::create(lua)?)?
         .build_readonly()
 }
 
@@ -62,38 +82,156 @@ async fn deserialize_model<'lua>(
 
 async fn serialize_place<'lua>(
     lua: &'lua Lua,
-    (data_model, as_xml): (LuaUserDataRef<'lua, Instance>, Option<bool>),
+    (data_model, options): (LuaUserDataRef<'lua, Instance>, SerializeOptions<'lua>),
 ) -> LuaResult<LuaString<'lua>> {
-    let data_model = (*data_model).clone();
+    let (data_model, copy) = match &options.property_filter {
+        Some(filter) => {
+            let copy = filter.filtered_copy(lua, &data_model)?;
+            (copy.clone(), Some(copy))
+        }
+        None => ((*data_model).clone(), None),
+    };
+    let format = if options.xml {
+        DocumentFormat::Xml
+    } else {
+        DocumentFormat::Binary
+    };
     let fut = task::spawn_blocking(move || {
         let doc = Document::from_data_model_instance(data_model)?;
-        let bytes = doc.to_bytes_with_format(match as_xml {
-            Some(true) => DocumentFormat::Xml,
-            _ => DocumentFormat::Binary,
-        })?;
+        let bytes = doc.to_bytes_with_format(format)?;
         Ok::<_, DocumentError>(bytes)
     });
-    let bytes = fut.await.into_lua_err()??;
-    lua.create_string(bytes)
+    let result = fut.await;
+    if let Some(mut copy) = copy {
+        copy.destroy();
+    }
+    lua.create_string(result.into_lua_err()??)
 }
 
 async fn serialize_model<'lua>(
     lua: &'lua Lua,
-    (instances, as_xml): (Vec<LuaUserDataRef<'lua, Instance>>, Option<bool>),
+    (instances, options): (Vec<LuaUserDataRef<'lua, Instance>>, SerializeOptions<'lua>),
 ) -> LuaResult<LuaString<'lua>> {
-    let instances = instances.iter().map(|i| (*i).clone()).collect();
+    let mut copies = Vec::new();
+    if let Some(filter) = &options.property_filter {
+        for instance in &instances {
+            match filter.filtered_copy(lua, instance) {
+                Ok(copy) => copies.push(copy),
+                Err(e) => {
+                    for mut copy in copies {
+                        copy.destroy();
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+    let instances = if options.property_filter.is_some() {
+        copies.clone()
+    } else {
+        instances.iter().map(|i| (*i).clone()).collect()
+    };
+    let format = if options.xml {
+        DocumentFormat::Xml
+    } else {
+        DocumentFormat::Binary
+    };
     let fut = task::spawn_blocking(move || {
         let doc = Document::from_instance_array(instances)?;
-        let bytes = doc.to_bytes_with_format(match as_xml {
-            Some(true) => DocumentFormat::Xml,
-            _ => DocumentFormat::Binary,
-        })?;
+        let bytes = doc.to_bytes_with_format(format)?;
         Ok::<_, DocumentError>(bytes)
     });
-    let bytes = fut.await.into_lua_err()??;
+    let result = fut.await;
+    for mut copy in copies {
+        copy.destroy();
+    }
+    lua.create_string(result.into_lua_err()??)
+}
+
+fn adopt<'lua>(
+    _: &'lua Lua,
+    (target, instance, options): (
+        LuaUserDataRef<'lua, Instance>,
+        LuaUserDataRef<'lua, Instance>,
+        AdoptOptions,
+    ),
+) -> LuaResult<Instance> {
+    if !options.copy {
+        if instance.get_class_name() == data_model::CLASS_NAME {
+            return Err(LuaError::RuntimeError(
+                "Failed to adopt instance - DataModel can not be moved, use the 'copy' option instead"
+                    .to_string(),
+            ));
+        }
+        if *target == *instance
+            || target
+                .find_ancestor(|a| a.referent() == instance.dom_ref)
+                .is_some()
+        {
+            return Err(LuaError::RuntimeError(
+                "Failed to adopt instance - an instance can not be moved into itself or its descendants"
+                    .to_string(),
+            ));
+        }
+    }
+    Ok(instance.adopt_into(&target, options.copy))
+}
+
+fn bake_gradient_to_string<'lua>(
+    lua: &'lua Lua,
+    (colors, width, options): (LuaUserDataRef<'lua, ColorSequence>, u32, GradientOptions),
+) -> LuaResult<LuaString<'lua>> {
+    if width == 0 {
+        return Err(LuaError::RuntimeError(
+            "Failed to bake gradient - width must be a positive integer".to_string(),
+        ));
+    }
+    lua.create_string(bake_gradient(&colors, &options, width)?)
+}
+
+async fn convert<'lua>(
+    lua: &'lua Lua,
+    (contents, options): (LuaString<'lua>, ConvertOptions),
+) -> LuaResult<LuaString<'lua>> {
+    let bytes = convert_bytes(contents.as_bytes().to_vec(), options).await?;
     lua.create_string(bytes)
 }
 
+async fn export<'lua>(
+    lua: &'lua Lua,
+    (roots, writer, options): (LuaValue<'lua>, ExportWriter<'lua>, ExportOptions<'lua>),
+) -> LuaResult<usize> {
+    let roots = match roots {
+        LuaValue::Table(t) => t
+            .sequence_values::<LuaUserDataRef<Instance>>()
+            .map(|i| i.map(|i| (*i).clone()))
+            .collect::<LuaResult<Vec<_>>>()?,
+        value => vec![(*LuaUserDataRef::<Instance>::from_lua(value, lua)?).clone()],
+    };
+    export_to_writer(lua, roots, writer, options).await
+}
+
+async fn validate<'lua>(lua: &'lua Lua, contents: LuaString<'lua>) -> LuaResult<LuaTable<'lua>> {
+    let bytes = contents.as_bytes().to_vec();
+    let fut = task::spawn_blocking(move || {
+        // NOTE: The kind of document does not matter for validation
+        let doc = Document::from_bytes(bytes, DocumentKind::Model)?;
+        Ok::<_, DocumentError>(doc.validate())
+    });
+    let problems = fut.await.into_lua_err()??;
+    let table = lua.create_table_with_capacity(problems.len(), 0)?;
+    for problem in problems {
+        table.push(
+            TableBuilder::new(lua)?
+                .with_value("path", problem.path)?
+                .with_value("kind", problem.kind.as_str())?
+                .with_value("message", problem.message)?
+                .build_readonly()?,
+        )?;
+    }
+    Ok(table)
+}
+
 fn get_auth_cookie(_: &Lua, raw: Option<bool>) -> LuaResult<Option<String>> {
     if matches!(raw, Some(true)) {
         Ok(rbx_cookie::get_value())
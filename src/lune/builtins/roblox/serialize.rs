@@ -0,0 +1,215 @@
+use mlua::prelude::*;
+use rbx_dom_weak::types::Variant as DomValue;
+
+use crate::roblox::{
+    datatypes::conversion::DomValueToLua,
+    instance::{base::instance_property_get, Instance},
+    shared::instance::{class_is_a, find_property_info},
+};
+
+/**
+    A rule for excluding properties, parsed from strings such
+    as `"Script.LinkedSource"`, `"*.SourceAssetId"` or `"SourceAssetId"`.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyFilterRule {
+    class_name: Option<String>,
+    property_name: String,
+}
+
+impl PropertyFilterRule {
+    fn parse(rule: &str) -> LuaResult<Self> {
+        let (class_name, property_name) = match rule.trim().split_once('.') {
+            Some(("*", property_name)) => (None, property_name),
+            Some((class_name, property_name)) => (Some(class_name.to_string()), property_name),
+            None => (None, rule.trim()),
+        };
+        if property_name.is_empty() || class_name.as_ref().is_some_and(String::is_empty) {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid property filter rule '{rule}' - expected 'Class.Property' or 'Property'"
+            )));
+        }
+        Ok(Self {
+            class_name,
+            property_name: property_name.to_string(),
+        })
+    }
+
+    fn matches(&self, class_name: &str, property_name: &str) -> bool {
+        self.property_name == property_name
+            && match &self.class_name {
+                Some(rule_class) => class_is_a(class_name, rule_class).unwrap_or(false),
+                None => true,
+            }
+    }
+}
+
+/**
+    A filter deciding which properties to keep when serializing instances.
+*/
+#[derive(Debug, Clone)]
+pub enum PropertyFilter<'lua> {
+    Function(LuaFunction<'lua>),
+    Exclude(Vec<PropertyFilterRule>),
+}
+
+impl<'lua> PropertyFilter<'lua> {
+    fn keep(
+        &self,
+        lua: &'lua Lua,
+        instance: &Instance,
+        name: &str,
+        value: &DomValue,
+    ) -> LuaResult<bool> {
+        match self {
+            Self::Exclude(rules) => Ok(!rules
+                .iter()
+                .any(|rule| rule.matches(instance.get_class_name(), name))),
+            Self::Function(f) => {
+                // NOTE: Properties that are unknown to the reflection database
+                // may still be present in files, they are given as raw values
+                let lua_value = if find_property_info(instance.get_class_name(), name).is_some() {
+                    instance_property_get(lua, instance, name.to_string())?
+                } else {
+                    LuaValue::dom_value_to_lua(lua, value).unwrap_or(LuaValue::Nil)
+                };
+                let keep: LuaValue = f.call((instance.clone(), name, lua_value))?;
+                Ok(!matches!(keep, LuaValue::Nil | LuaValue::Boolean(false)))
+            }
+        }
+    }
+
+    /**
+        Creates a copy of the given instance and all of its
+        descendants, with all filtered properties removed.
+
+        The copy must be destroyed once it is no longer needed.
+    */
+    pub fn filtered_copy(&self, lua: &'lua Lua, instance: &Instance) -> LuaResult<Instance> {
+        let mut copy = instance.clone_instance();
+        let mut instances = vec![copy.clone()];
+        instances.extend(copy.get_descendants());
+        for instance in instances {
+            for (name, value) in instance.get_properties() {
+                match self.keep(lua, &instance, &name, &value) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        instance.remove_property(&name);
+                    }
+                    Err(e) => {
+                        copy.destroy();
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        Ok(copy)
+    }
+}
+
+impl<'lua> FromLua<'lua> for PropertyFilter<'lua> {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Function(f) => Ok(Self::Function(f)),
+            LuaValue::Table(t) => {
+                let rules = match t.raw_get::<_, Option<Vec<String>>>("exclude") {
+                    Ok(Some(rules)) => rules,
+                    _ => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'propertyFilter' in serialize options - \
+                            expected a function or a table with an 'exclude' list"
+                                .to_string(),
+                        ))
+                    }
+                };
+                let rules = rules
+                    .iter()
+                    .map(|rule| PropertyFilterRule::parse(rule))
+                    .collect::<LuaResult<Vec<_>>>()?;
+                Ok(Self::Exclude(rules))
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "PropertyFilter",
+                message: Some(format!(
+                    "Invalid property filter - expected function or table, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    Options for `serializePlace` and `serializeModel`, which may also
+    be given as a single boolean deciding if xml should be used.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct SerializeOptions<'lua> {
+    pub xml: bool,
+    pub property_filter: Option<PropertyFilter<'lua>>,
+}
+
+impl<'lua> FromLua<'lua> for SerializeOptions<'lua> {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Boolean(xml) => Ok(Self {
+                xml,
+                ..Default::default()
+            }),
+            LuaValue::Table(t) => {
+                let xml = match t.raw_get::<_, Option<bool>>("xml") {
+                    Ok(xml) => xml.unwrap_or(false),
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'xml' in serialize options".to_string(),
+                        ))
+                    }
+                };
+                let property_filter = match t.raw_get::<_, LuaValue>("propertyFilter")? {
+                    LuaValue::Nil => None,
+                    value => Some(PropertyFilter::from_lua(value, lua)?),
+                };
+                Ok(Self {
+                    xml,
+                    property_filter,
+                })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "SerializeOptions",
+                message: Some(format!(
+                    "Invalid serialize options - expected boolean, table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rules() {
+        let rule = PropertyFilterRule::parse("Script.LinkedSource").unwrap();
+        assert_eq!(rule.class_name.as_deref(), Some("Script"));
+        assert_eq!(rule.property_name, "LinkedSource");
+        assert_eq!(
+            PropertyFilterRule::parse("*.SourceAssetId").unwrap(),
+            PropertyFilterRule::parse("SourceAssetId").unwrap()
+        );
+        assert!(PropertyFilterRule::parse("Script.").is_err());
+        assert!(PropertyFilterRule::parse(".Name").is_err());
+    }
+
+    #[test]
+    fn rules_match_superclasses() {
+        let rule = PropertyFilterRule::parse("BasePart.Anchored").unwrap();
+        assert!(rule.matches("Part", "Anchored"));
+        assert!(!rule.matches("Part", "Locked"));
+        assert!(!rule.matches("Folder", "Anchored"));
+    }
+}
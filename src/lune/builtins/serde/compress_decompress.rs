@@ -1,15 +1,22 @@
+use futures_util::FutureExt;
 use lz4_flex::{compress_prepend_size, decompress_size_prepended};
 use mlua::prelude::*;
 use tokio::{
-    io::{copy, BufReader},
+    io::{copy, AsyncWriteExt, BufReader},
     task,
 };
 
 use async_compression::{
-    tokio::bufread::{
-        BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder, ZlibDecoder, ZlibEncoder,
+    tokio::{
+        bufread::{
+            BrotliDecoder, BrotliEncoder, GzipDecoder, GzipEncoder, ZlibDecoder, ZlibEncoder,
+        },
+        write::{
+            BrotliEncoder as BrotliStreamEncoder, GzipEncoder as GzipStreamEncoder,
+            ZlibEncoder as ZlibStreamEncoder,
+        },
     },
-    Level::Best as CompressionQuality,
+    Level,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -66,6 +73,16 @@ impl CompressDecompressFormat {
             _ => None,
         }
     }
+
+    pub fn as_header_str(&self) -> Option<&'static str> {
+        // https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Content-Encoding#directives
+        match self {
+            Self::Brotli => Some("br"),
+            Self::GZip => Some("gzip"),
+            Self::ZLib => Some("deflate"),
+            Self::LZ4 => None,
+        }
+    }
 }
 
 impl<'lua> FromLua<'lua> for CompressDecompressFormat {
@@ -94,10 +111,71 @@ impl<'lua> FromLua<'lua> for CompressDecompressFormat {
     }
 }
 
+/**
+    Options for compressing, which currently only contains the compression level.
+
+    Levels range from `0` to `11` for brotli, and `0` to `9` for gzip and zlib,
+    where higher levels compress better but are slower and use more memory.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompressOptions {
+    pub level: Option<u32>,
+}
+
+impl CompressOptions {
+    fn quality(self, format: CompressDecompressFormat) -> LuaResult<Level> {
+        let level = match self.level {
+            None => return Ok(Level::Best),
+            Some(level) => level,
+        };
+        let max = match format {
+            CompressDecompressFormat::Brotli => 11,
+            CompressDecompressFormat::GZip | CompressDecompressFormat::ZLib => 9,
+            CompressDecompressFormat::LZ4 => {
+                return Err(LuaError::RuntimeError(
+                    "Compression levels are not supported for lz4".to_string(),
+                ))
+            }
+        };
+        if level > max {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid compression level {level} - expected a level between 0 and {max}"
+            )));
+        }
+        Ok(Level::Precise(level as i32))
+    }
+}
+
+impl<'lua> FromLua<'lua> for CompressOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::Table(t) => match t.raw_get::<_, Option<u32>>("level") {
+                Ok(level) => Ok(Self { level }),
+                Err(_) => Err(LuaError::RuntimeError(
+                    "Invalid option value for 'level' in compress options - expected a non-negative integer"
+                        .to_string(),
+                )),
+            },
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "CompressOptions",
+                message: Some(format!(
+                    "Invalid compress options - expected table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
 pub async fn compress<'lua>(
     format: CompressDecompressFormat,
     source: impl AsRef<[u8]>,
+    options: CompressOptions,
 ) -> LuaResult<Vec<u8>> {
+    let quality = options.quality(format)?;
+
     if let CompressDecompressFormat::LZ4 = format {
         let source = source.as_ref().to_vec();
         return task::spawn_blocking(move || compress_prepend_size(&source))
@@ -110,15 +188,15 @@ pub async fn compress<'lua>(
 
     match format {
         CompressDecompressFormat::Brotli => {
-            let mut encoder = BrotliEncoder::with_quality(reader, CompressionQuality);
+            let mut encoder = BrotliEncoder::with_quality(reader, quality);
             copy(&mut encoder, &mut bytes).await?;
         }
         CompressDecompressFormat::GZip => {
-            let mut encoder = GzipEncoder::with_quality(reader, CompressionQuality);
+            let mut encoder = GzipEncoder::with_quality(reader, quality);
             copy(&mut encoder, &mut bytes).await?;
         }
         CompressDecompressFormat::ZLib => {
-            let mut encoder = ZlibEncoder::with_quality(reader, CompressionQuality);
+            let mut encoder = ZlibEncoder::with_quality(reader, quality);
             copy(&mut encoder, &mut bytes).await?;
         }
         CompressDecompressFormat::LZ4 => unreachable!(),
@@ -160,3 +238,89 @@ pub async fn decompress<'lua>(
 
     Ok(bytes)
 }
+
+/**
+    A stateful compressor, which compresses chunks as they are written to
+    it, so that large data never needs to be fully kept in memory.
+
+    Compressed output is returned as soon as it is available, and encoders
+    only ever write into in-memory buffers, so writes never need to wait.
+*/
+pub enum StreamCompressor {
+    Brotli(Box<BrotliStreamEncoder<Vec<u8>>>),
+    GZip(Box<GzipStreamEncoder<Vec<u8>>>),
+    ZLib(Box<ZlibStreamEncoder<Vec<u8>>>),
+    Finished,
+}
+
+impl StreamCompressor {
+    pub fn new(format: CompressDecompressFormat, options: CompressOptions) -> LuaResult<Self> {
+        let quality = options.quality(format)?;
+        Ok(match format {
+            CompressDecompressFormat::Brotli => Self::Brotli(Box::new(
+                BrotliStreamEncoder::with_quality(Vec::new(), quality),
+            )),
+            CompressDecompressFormat::GZip => Self::GZip(Box::new(
+                GzipStreamEncoder::with_quality(Vec::new(), quality),
+            )),
+            CompressDecompressFormat::ZLib => Self::ZLib(Box::new(
+                ZlibStreamEncoder::with_quality(Vec::new(), quality),
+            )),
+            CompressDecompressFormat::LZ4 => {
+                return Err(LuaError::RuntimeError(
+                    "Streaming compression is not supported for lz4".to_string(),
+                ))
+            }
+        })
+    }
+
+    fn output(&mut self) -> Vec<u8> {
+        match self {
+            Self::Brotli(encoder) => std::mem::take(encoder.get_mut()),
+            Self::GZip(encoder) => std::mem::take(encoder.get_mut()),
+            Self::ZLib(encoder) => std::mem::take(encoder.get_mut()),
+            Self::Finished => Vec::new(),
+        }
+    }
+
+    fn write(&mut self, chunk: &[u8]) -> LuaResult<Vec<u8>> {
+        let written = match self {
+            Self::Brotli(encoder) => encoder.write_all(chunk).now_or_never(),
+            Self::GZip(encoder) => encoder.write_all(chunk).now_or_never(),
+            Self::ZLib(encoder) => encoder.write_all(chunk).now_or_never(),
+            Self::Finished => {
+                return Err(LuaError::RuntimeError(
+                    "Compressor has already been finished".to_string(),
+                ))
+            }
+        };
+        written
+            .expect("Writing to an in-memory buffer should never wait")
+            .into_lua_err()?;
+        Ok(self.output())
+    }
+
+    fn finish(&mut self) -> LuaResult<Vec<u8>> {
+        let finished = match self {
+            Self::Brotli(encoder) => encoder.shutdown().now_or_never(),
+            Self::GZip(encoder) => encoder.shutdown().now_or_never(),
+            Self::ZLib(encoder) => encoder.shutdown().now_or_never(),
+            Self::Finished => return Ok(Vec::new()),
+        };
+        finished
+            .expect("Writing to an in-memory buffer should never wait")
+            .into_lua_err()?;
+        let output = self.output();
+        *self = Self::Finished;
+        Ok(output)
+    }
+}
+
+impl LuaUserData for StreamCompressor {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("write", |lua, this, chunk: LuaString| {
+            lua.create_string(this.write(chunk.as_bytes())?)
+        });
+        methods.add_method_mut("finish", |lua, this, ()| lua.create_string(this.finish()?));
+    }
+}
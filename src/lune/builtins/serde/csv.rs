@@ -0,0 +1,211 @@
+#[cfg(feature = "csv")]
+use std::collections::BTreeSet;
+
+use mlua::prelude::*;
+
+/**
+    How to handle the header row of a csv file.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CsvHeaders {
+    /**
+        Headers are detected from the keys of rows when encoding,
+        and the first row is used as headers when decoding.
+    */
+    #[default]
+    Auto,
+    /**
+        There is no header row, and rows are arrays.
+    */
+    None,
+    /**
+        The given columns are used as headers, in order.
+    */
+    Columns(Vec<String>),
+}
+
+/**
+    Options for the csv format.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    pub headers: CsvHeaders,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            headers: CsvHeaders::Auto,
+        }
+    }
+}
+
+impl CsvOptions {
+    /**
+        Reads csv options from a table of options, which
+        may also contain options for other formats.
+    */
+    pub fn from_table(t: &LuaTable) -> LuaResult<Self> {
+        let delimiter = match t.raw_get::<_, Option<String>>("delimiter") {
+            Ok(None) => b',',
+            Ok(Some(d)) if d.len() == 1 && d.is_ascii() => d.as_bytes()[0],
+            _ => {
+                return Err(LuaError::RuntimeError(
+                    "Invalid option value for 'delimiter' in csv options - expected a single ascii character"
+                        .to_string(),
+                ))
+            }
+        };
+        let headers = match t.raw_get::<_, LuaValue>("headers")? {
+            LuaValue::Nil | LuaValue::Boolean(true) => CsvHeaders::Auto,
+            LuaValue::Boolean(false) => CsvHeaders::None,
+            LuaValue::Table(columns) => match columns.sequence_values::<String>().collect() {
+                Ok(columns) => CsvHeaders::Columns(columns),
+                Err(_) => {
+                    return Err(LuaError::RuntimeError(
+                        "Invalid option value for 'headers' in csv options - expected an array of strings"
+                            .to_string(),
+                    ))
+                }
+            },
+            _ => {
+                return Err(LuaError::RuntimeError(
+                    "Invalid option value for 'headers' in csv options - expected boolean or array of strings"
+                        .to_string(),
+                ))
+            }
+        };
+        Ok(Self { delimiter, headers })
+    }
+}
+
+#[cfg(feature = "csv")]
+fn cell_to_string(value: LuaValue, row: usize) -> LuaResult<String> {
+    match value {
+        LuaValue::Nil => Ok(String::new()),
+        LuaValue::String(s) => Ok(s.to_str()?.to_string()),
+        LuaValue::Integer(i) => Ok(i.to_string()),
+        LuaValue::Number(n) => Ok(n.to_string()),
+        LuaValue::Boolean(b) => Ok(b.to_string()),
+        value => Err(LuaError::RuntimeError(format!(
+            "Failed to encode csv - row {row} contains a {}, expected string, number or boolean",
+            value.type_name()
+        ))),
+    }
+}
+
+/**
+    Encodes an array of rows into csv.
+
+    Rows are either arrays of values, or tables with column names as keys.
+    When headers are detected automatically from keyed rows, columns are sorted
+    by name, since tables do not preserve the order of their keys.
+*/
+#[cfg(feature = "csv")]
+pub fn encode_csv(value: LuaValue, options: &CsvOptions) -> LuaResult<Vec<u8>> {
+    let rows = match value {
+        LuaValue::Table(t) => t
+            .sequence_values::<LuaTable>()
+            .collect::<LuaResult<Vec<_>>>()?,
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Failed to encode csv - expected an array of rows, got {}",
+                value.type_name()
+            )))
+        }
+    };
+
+    let columns = match &options.headers {
+        CsvHeaders::Columns(columns) => Some(columns.clone()),
+        CsvHeaders::None => None,
+        CsvHeaders::Auto => {
+            let mut keys = BTreeSet::new();
+            for row in &rows {
+                for pair in row.clone().pairs::<LuaValue, LuaValue>() {
+                    if let (LuaValue::String(key), _) = pair? {
+                        keys.insert(key.to_str()?.to_string());
+                    }
+                }
+            }
+            if keys.is_empty() {
+                None
+            } else {
+                Some(keys.into_iter().collect::<Vec<_>>())
+            }
+        }
+    };
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(columns.is_none())
+        .from_writer(Vec::new());
+    if let Some(columns) = &columns {
+        writer.write_record(columns).into_lua_err()?;
+    }
+    for (index, row) in rows.into_iter().enumerate() {
+        let cells = match &columns {
+            Some(columns) => columns
+                .iter()
+                .map(|column| cell_to_string(row.raw_get(column.as_str())?, index + 1))
+                .collect::<LuaResult<Vec<_>>>()?,
+            None => row
+                .sequence_values::<LuaValue>()
+                .map(|value| cell_to_string(value?, index + 1))
+                .collect::<LuaResult<Vec<_>>>()?,
+        };
+        writer.write_record(cells).into_lua_err()?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to encode csv - {e}")))
+}
+
+/**
+    Decodes csv into an array of rows.
+
+    Values are always strings, since csv does not store any types.
+*/
+#[cfg(feature = "csv")]
+pub fn decode_csv<'lua>(
+    lua: &'lua Lua,
+    bytes: &[u8],
+    options: &CsvOptions,
+) -> LuaResult<LuaValue<'lua>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(bytes);
+    let mut records = reader.records();
+
+    let columns = match &options.headers {
+        CsvHeaders::None => None,
+        CsvHeaders::Columns(columns) => Some(columns.clone()),
+        CsvHeaders::Auto => match records.next() {
+            Some(header) => Some(header.into_lua_err()?.iter().map(String::from).collect()),
+            None => Some(Vec::new()),
+        },
+    };
+
+    let rows = lua.create_table()?;
+    for record in records {
+        let record = record.into_lua_err()?;
+        let row = lua.create_table()?;
+        match &columns {
+            Some(columns) => {
+                for (column, value) in columns.iter().zip(record.iter()) {
+                    row.raw_set(column.as_str(), value)?;
+                }
+            }
+            None => {
+                for value in record.iter() {
+                    row.raw_push(value)?;
+                }
+            }
+        }
+        rows.raw_push(row)?;
+    }
+    Ok(LuaValue::Table(rows))
+}
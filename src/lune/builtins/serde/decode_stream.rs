@@ -0,0 +1,344 @@
+use mlua::prelude::*;
+
+use crate::lune::util::TableBuilder;
+
+use super::encode_decode::EncodeDecodeFormat;
+
+/*
+    Reading chunks needs to call the reader, which may yield,
+    so we need to do that from lua and not rust, and then push
+    each chunk into a decoder that parses as much as it can
+*/
+const DECODE_STREAM_IMPL_LUA: &str = r#"
+local format, input = ...
+local decoder = createDecoder(format)
+local read = if type(input) == "function"
+    then input
+    else function()
+        return input:read()
+    end
+while true do
+    local chunk = read()
+    if chunk == nil then
+        break
+    end
+    decoder:push(chunk)
+end
+return decoder:finish()
+"#;
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaFunction> {
+    let env = TableBuilder::new(lua)?
+        .with_value("type", lua.globals().get::<_, LuaFunction>("type")?)?
+        .with_function(
+            "createDecoder",
+            |_, format: EncodeDecodeFormat| match format {
+                EncodeDecodeFormat::Json => Ok(JsonStreamDecoder::default()),
+                _ => Err(LuaError::RuntimeError(
+                    "Streaming decoding is currently only supported for json".to_string(),
+                )),
+            },
+        )?
+        .build_readonly()?;
+    lua.load(DECODE_STREAM_IMPL_LUA)
+        .set_name("serde.decodeStream")
+        .set_environment(env)
+        .into_function()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    Value { allow_end: bool },
+    Key { allow_end: bool },
+    Colon,
+    CommaOrEnd,
+    Done,
+}
+
+#[derive(Debug)]
+enum Container {
+    Array { len: usize },
+    Object { key: Option<String> },
+}
+
+#[derive(Debug)]
+struct Frame {
+    table: LuaRegistryKey,
+    container: Container,
+}
+
+#[derive(Debug, Default)]
+struct StringScan {
+    pos: usize,
+    escaped: bool,
+}
+
+/**
+    An incremental json decoder, which builds lua values directly
+    as chunks are pushed into it, without ever needing the full
+    json string in memory at once.
+
+    Only the current unfinished token is buffered between chunks.
+*/
+#[derive(Debug)]
+pub struct JsonStreamDecoder {
+    buffer: Vec<u8>,
+    offset: usize,
+    scan: StringScan,
+    expect: Expect,
+    stack: Vec<Frame>,
+    root: Option<LuaRegistryKey>,
+}
+
+impl Default for JsonStreamDecoder {
+    fn default() -> Self {
+        Self {
+            buffer: Vec::new(),
+            offset: 0,
+            scan: StringScan::default(),
+            expect: Expect::Value { allow_end: false },
+            stack: Vec::new(),
+            root: None,
+        }
+    }
+}
+
+impl JsonStreamDecoder {
+    fn error(&self, pos: usize, message: impl AsRef<str>) -> LuaError {
+        LuaError::RuntimeError(format!(
+            "Failed to decode json stream at byte {} - {}",
+            self.offset + pos + 1,
+            message.as_ref()
+        ))
+    }
+
+    fn push(&mut self, lua: &Lua, chunk: &[u8]) -> LuaResult<()> {
+        self.buffer.extend_from_slice(chunk);
+        self.process(lua, false)
+    }
+
+    fn finish<'lua>(&mut self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        self.process(lua, true)?;
+        if !self.buffer.is_empty() || self.expect != Expect::Done {
+            return Err(self.error(self.buffer.len(), "unexpected end of input"));
+        }
+        match self.root.take() {
+            Some(key) => {
+                let value = lua.registry_value(&key)?;
+                lua.remove_registry_value(key)?;
+                Ok(value)
+            }
+            None => Ok(LuaValue::Nil),
+        }
+    }
+
+    fn process(&mut self, lua: &Lua, eof: bool) -> LuaResult<()> {
+        let mut buffer = std::mem::take(&mut self.buffer);
+        let result = self.process_buffer(lua, &buffer, eof);
+        let consumed = match &result {
+            Ok(consumed) => *consumed,
+            Err(_) => 0,
+        };
+        buffer.drain(..consumed);
+        self.offset += consumed;
+        self.buffer = buffer;
+        result.map(|_| ())
+    }
+
+    fn process_buffer(&mut self, lua: &Lua, buffer: &[u8], eof: bool) -> LuaResult<usize> {
+        let mut pos = 0;
+        loop {
+            while buffer.get(pos).is_some_and(u8::is_ascii_whitespace) {
+                pos += 1;
+            }
+            let byte = match buffer.get(pos) {
+                Some(byte) => *byte,
+                None => return Ok(pos),
+            };
+            if self.expect == Expect::Done {
+                return Err(self.error(pos, "unexpected trailing characters"));
+            }
+            match byte {
+                b'{' | b'[' => {
+                    self.expect_value(pos)?;
+                    let container = if byte == b'{' {
+                        Container::Object { key: None }
+                    } else {
+                        Container::Array { len: 0 }
+                    };
+                    self.expect = match container {
+                        Container::Object { .. } => Expect::Key { allow_end: true },
+                        Container::Array { .. } => Expect::Value { allow_end: true },
+                    };
+                    self.stack.push(Frame {
+                        table: lua.create_registry_value(lua.create_table()?)?,
+                        container,
+                    });
+                    pos += 1;
+                }
+                b'}' | b']' => {
+                    let closes_object = byte == b'}';
+                    let valid = match (self.stack.last(), self.expect) {
+                        (Some(frame), Expect::CommaOrEnd) => {
+                            matches!(frame.container, Container::Object { .. }) == closes_object
+                        }
+                        (Some(_), Expect::Key { allow_end: true }) => closes_object,
+                        (Some(_), Expect::Value { allow_end: true }) => !closes_object,
+                        _ => false,
+                    };
+                    if !valid {
+                        return Err(self.error(pos, format!("unexpected '{}'", byte as char)));
+                    }
+                    let frame = self.stack.pop().expect("Stack was checked above");
+                    let table = lua.registry_value::<LuaValue>(&frame.table)?;
+                    lua.remove_registry_value(frame.table)?;
+                    self.complete_value(lua, table)?;
+                    pos += 1;
+                }
+                b',' => {
+                    self.expect = match (self.stack.last(), self.expect) {
+                        (Some(frame), Expect::CommaOrEnd) => match frame.container {
+                            Container::Object { .. } => Expect::Key { allow_end: false },
+                            Container::Array { .. } => Expect::Value { allow_end: false },
+                        },
+                        _ => return Err(self.error(pos, "unexpected ','")),
+                    };
+                    pos += 1;
+                }
+                b':' => {
+                    if self.expect != Expect::Colon {
+                        return Err(self.error(pos, "unexpected ':'"));
+                    }
+                    self.expect = Expect::Value { allow_end: false };
+                    pos += 1;
+                }
+                b'"' => {
+                    let len = match self.scan_string(&buffer[pos..]) {
+                        Some(len) => len,
+                        None => return Ok(pos),
+                    };
+                    let token = &buffer[pos..pos + len];
+                    let string: String = serde_json::from_slice(token)
+                        .map_err(|e| self.error(pos, format!("invalid string ({e})")))?;
+                    if let Expect::Key { .. } = self.expect {
+                        if let Some(Frame {
+                            container: Container::Object { key },
+                            ..
+                        }) = self.stack.last_mut()
+                        {
+                            *key = Some(string);
+                        }
+                        self.expect = Expect::Colon;
+                    } else {
+                        self.expect_value(pos)?;
+                        self.complete_value(lua, LuaValue::String(lua.create_string(string)?))?;
+                    }
+                    pos += len;
+                }
+                _ => {
+                    // Numbers and literals have no terminator, so unless the input
+                    // has ended, we need to see a delimiter before decoding them
+                    let len = match buffer[pos..].iter().position(is_delimiter) {
+                        Some(len) => len,
+                        None if eof => buffer.len() - pos,
+                        None => return Ok(pos),
+                    };
+                    let token = &buffer[pos..pos + len];
+                    self.expect_value(pos)?;
+                    let value = match token {
+                        b"true" => LuaValue::Boolean(true),
+                        b"false" => LuaValue::Boolean(false),
+                        b"null" => LuaValue::Nil,
+                        _ => match serde_json::from_slice::<f64>(token) {
+                            Ok(number) => LuaValue::Number(number),
+                            Err(_) => {
+                                return Err(self.error(
+                                    pos,
+                                    format!("unexpected '{}'", String::from_utf8_lossy(token)),
+                                ))
+                            }
+                        },
+                    };
+                    self.complete_value(lua, value)?;
+                    pos += len;
+                }
+            }
+        }
+    }
+
+    fn expect_value(&self, pos: usize) -> LuaResult<()> {
+        match self.expect {
+            Expect::Value { .. } => Ok(()),
+            Expect::Key { .. } => Err(self.error(pos, "expected an object key")),
+            Expect::Colon => Err(self.error(pos, "expected ':'")),
+            _ => Err(self.error(pos, "expected ',' or the end of a container")),
+        }
+    }
+
+    /**
+        Scans for the end of the string token at the start of the given bytes,
+        returning its length including quotes, or `None` if it is not yet complete.
+
+        Progress is saved, so that huge strings spanning
+        many chunks are only ever scanned through once.
+    */
+    fn scan_string(&mut self, bytes: &[u8]) -> Option<usize> {
+        let start = self.scan.pos.max(1);
+        for (index, &byte) in bytes.iter().enumerate().skip(start) {
+            if self.scan.escaped {
+                self.scan.escaped = false;
+            } else if byte == b'\\' {
+                self.scan.escaped = true;
+            } else if byte == b'"' {
+                self.scan = StringScan::default();
+                return Some(index + 1);
+            }
+        }
+        self.scan.pos = bytes.len();
+        None
+    }
+
+    /**
+        Adds a finished value to the current container,
+        or sets it as the root value if there is none.
+    */
+    fn complete_value<'lua>(&mut self, lua: &'lua Lua, value: LuaValue<'lua>) -> LuaResult<()> {
+        match self.stack.last_mut() {
+            None => {
+                self.root = Some(lua.create_registry_value(value)?);
+                self.expect = Expect::Done;
+            }
+            Some(frame) => {
+                let table = lua.registry_value::<LuaTable>(&frame.table)?;
+                match &mut frame.container {
+                    // NOTE: Null values in arrays leave holes, to match serde.decode
+                    Container::Array { len } => {
+                        *len += 1;
+                        if !value.is_nil() {
+                            table.raw_set(*len, value)?;
+                        }
+                    }
+                    Container::Object { key } => {
+                        let key = key.take().expect("Object key must be set before its value");
+                        table.raw_set(key, value)?;
+                    }
+                }
+                self.expect = Expect::CommaOrEnd;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LuaUserData for JsonStreamDecoder {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("push", |lua, this, chunk: LuaString| {
+            this.push(lua, chunk.as_bytes())
+        });
+        methods.add_method_mut("finish", |lua, this, ()| this.finish(lua));
+    }
+}
+
+fn is_delimiter(byte: &u8) -> bool {
+    byte.is_ascii_whitespace() || matches!(byte, b',' | b':' | b'[' | b']' | b'{' | b'}' | b'"')
+}
@@ -1,24 +1,41 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use mlua::prelude::*;
 
 use serde_json::Value as JsonValue;
 use serde_yaml::Value as YamlValue;
 use toml::Value as TomlValue;
 
+#[cfg(feature = "bignum")]
+use crate::lune::builtins::bignum::{LuaBigInt, MAX_SAFE_INTEGER};
+
+#[cfg(feature = "csv")]
+use super::csv::{decode_csv, encode_csv};
+use super::{
+    csv::CsvOptions,
+    jsonc::strip_jsonc,
+    ordered_map::{prepare_ordered_maps, serializable, OrderedMap},
+};
+
 const LUA_SERIALIZE_OPTIONS: LuaSerializeOptions = LuaSerializeOptions::new()
     .set_array_metatable(false)
     .serialize_none_to_null(false)
     .serialize_unit_to_null(false);
 
-const LUA_DESERIALIZE_OPTIONS: LuaDeserializeOptions = LuaDeserializeOptions::new()
-    .sort_keys(true)
-    .deny_recursive_tables(false)
-    .deny_unsupported_types(true);
+const LUA_SERIALIZE_OPTIONS_NULLS: LuaSerializeOptions = LuaSerializeOptions::new()
+    .set_array_metatable(false)
+    .serialize_none_to_null(true)
+    .serialize_unit_to_null(true);
 
 #[derive(Debug, Clone, Copy)]
 pub enum EncodeDecodeFormat {
     Json,
+    Jsonc,
     Yaml,
     Toml,
+    MessagePack,
+    Base64,
+    Hex,
+    Csv,
 }
 
 impl<'lua> FromLua<'lua> for EncodeDecodeFormat {
@@ -26,13 +43,18 @@ impl<'lua> FromLua<'lua> for EncodeDecodeFormat {
         if let LuaValue::String(s) = &value {
             match s.to_string_lossy().to_ascii_lowercase().trim() {
                 "json" => Ok(Self::Json),
+                "jsonc" => Ok(Self::Jsonc),
                 "yaml" => Ok(Self::Yaml),
                 "toml" => Ok(Self::Toml),
+                "messagepack" | "msgpack" => Ok(Self::MessagePack),
+                "base64" => Ok(Self::Base64),
+                "hex" => Ok(Self::Hex),
+                "csv" => Ok(Self::Csv),
                 kind => Err(LuaError::FromLuaConversionError {
                     from: value.type_name(),
                     to: "EncodeDecodeFormat",
                     message: Some(format!(
-                        "Invalid format '{kind}', valid formats are:  json, yaml, toml"
+                        "Invalid format '{kind}', valid formats are:  json, jsonc, yaml, toml, messagepack, base64, hex, csv"
                     )),
                 }),
             }
@@ -46,21 +68,129 @@ impl<'lua> FromLua<'lua> for EncodeDecodeFormat {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct EncodeDecodeConfig {
     pub format: EncodeDecodeFormat,
     pub pretty: bool,
+    pub lenient: bool,
+    pub preserve_nulls: bool,
+    pub preserve_order: bool,
+    pub bigints: bool,
+    pub precision: Option<u32>,
+    #[cfg_attr(not(feature = "csv"), allow(dead_code))]
+    pub csv: CsvOptions,
 }
 
 impl EncodeDecodeConfig {
+    /**
+        Creates a config from options given to `serde.encode` or `serde.decode`,
+        which may be nil, a boolean deciding if output should be pretty, or a table.
+    */
+    pub fn from_options(format: EncodeDecodeFormat, options: LuaValue) -> LuaResult<Self> {
+        match options {
+            LuaValue::Nil => Ok(Self::from(format)),
+            LuaValue::Boolean(pretty) => Ok(Self::from((format, pretty))),
+            LuaValue::Table(t) => {
+                let pretty = match t.raw_get::<_, Option<bool>>("pretty") {
+                    Ok(pretty) => pretty.unwrap_or(false),
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'pretty' in serde options".to_string(),
+                        ))
+                    }
+                };
+                let lenient = match t.raw_get::<_, Option<bool>>("lenient") {
+                    Ok(lenient) => lenient.unwrap_or(false),
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'lenient' in serde options".to_string(),
+                        ))
+                    }
+                };
+                let preserve_nulls = match t.raw_get::<_, Option<bool>>("preserveNulls") {
+                    Ok(preserve_nulls) => preserve_nulls.unwrap_or(false),
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'preserveNulls' in serde options".to_string(),
+                        ))
+                    }
+                };
+                let preserve_order = match t.raw_get::<_, Option<bool>>("preserveOrder") {
+                    Ok(preserve_order) => preserve_order.unwrap_or(false),
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'preserveOrder' in serde options".to_string(),
+                        ))
+                    }
+                };
+                let bigints = match t.raw_get::<_, Option<bool>>("bigints") {
+                    #[cfg(not(feature = "bignum"))]
+                    Ok(Some(true)) => return Err(unsupported("the 'bigints' option")),
+                    Ok(bigints) => bigints.unwrap_or(false),
+                    Err(_) => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'bigints' in serde options".to_string(),
+                        ))
+                    }
+                };
+                let precision = match t.raw_get::<_, Option<u32>>("precision") {
+                    Ok(precision) if precision.is_none_or(|p| p <= 15) => precision,
+                    _ => {
+                        return Err(LuaError::RuntimeError(
+                            "Invalid option value for 'precision' in serde options - expected an integer between 0 and 15"
+                                .to_string(),
+                        ))
+                    }
+                };
+                Ok(Self {
+                    format,
+                    pretty,
+                    lenient,
+                    preserve_nulls,
+                    preserve_order,
+                    bigints,
+                    precision,
+                    csv: CsvOptions::from_table(&t)?,
+                })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "EncodeDecodeConfig",
+                message: Some(format!(
+                    "Invalid serde options - expected boolean, table or nil, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+
+    fn serialize_options(&self) -> LuaSerializeOptions {
+        if self.preserve_nulls {
+            LUA_SERIALIZE_OPTIONS_NULLS
+        } else {
+            LUA_SERIALIZE_OPTIONS
+        }
+    }
+
     pub fn serialize_to_string<'lua>(
-        self,
+        &self,
         lua: &'lua Lua,
         value: LuaValue<'lua>,
     ) -> LuaResult<LuaString<'lua>> {
+        let value = match self.format {
+            EncodeDecodeFormat::Json
+            | EncodeDecodeFormat::Jsonc
+            | EncodeDecodeFormat::Yaml
+            | EncodeDecodeFormat::Toml
+            | EncodeDecodeFormat::MessagePack => prepare_ordered_maps(lua, value)?,
+            _ => value,
+        };
         let bytes = match self.format {
-            EncodeDecodeFormat::Json => {
-                let serialized: JsonValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
+            EncodeDecodeFormat::Json | EncodeDecodeFormat::Jsonc => {
+                let mut serialized = serde_json::to_value(serializable(&value)).into_lua_err()?;
+                if let Some(precision) = self.precision {
+                    round_json_floats(&mut serialized, precision);
+                }
                 if self.pretty {
                     serde_json::to_vec_pretty(&serialized).into_lua_err()?
                 } else {
@@ -68,13 +198,13 @@ impl EncodeDecodeConfig {
                 }
             }
             EncodeDecodeFormat::Yaml => {
-                let serialized: YamlValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
+                let serialized = serde_yaml::to_value(serializable(&value)).into_lua_err()?;
                 let mut writer = Vec::with_capacity(128);
                 serde_yaml::to_writer(&mut writer, &serialized).into_lua_err()?;
                 writer
             }
             EncodeDecodeFormat::Toml => {
-                let serialized: TomlValue = lua.from_value_with(value, LUA_DESERIALIZE_OPTIONS)?;
+                let serialized = TomlValue::try_from(serializable(&value)).into_lua_err()?;
                 let s = if self.pretty {
                     toml::to_string_pretty(&serialized).into_lua_err()?
                 } else {
@@ -82,44 +212,199 @@ impl EncodeDecodeConfig {
                 };
                 s.as_bytes().to_vec()
             }
+            #[cfg(feature = "msgpack")]
+            EncodeDecodeFormat::MessagePack => {
+                // NOTE: MessagePack is a binary format, so pretty
+                // printing does not apply, and strings that are not
+                // valid utf-8 are kept as-is using the binary type
+                let serialized = rmpv::ext::to_value(serializable(&value)).into_lua_err()?;
+                let mut writer = Vec::with_capacity(128);
+                rmpv::encode::write_value(&mut writer, &serialized).into_lua_err()?;
+                writer
+            }
+            #[cfg(not(feature = "msgpack"))]
+            EncodeDecodeFormat::MessagePack => return Err(unsupported("the messagepack format")),
+            EncodeDecodeFormat::Base64 => BASE64
+                .encode(binary_value_to_bytes(value, "base64")?)
+                .into_bytes(),
+            EncodeDecodeFormat::Hex => binary_value_to_bytes(value, "hex")?
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<String>()
+                .into_bytes(),
+            #[cfg(feature = "csv")]
+            EncodeDecodeFormat::Csv => encode_csv(value, &self.csv)?,
+            #[cfg(not(feature = "csv"))]
+            EncodeDecodeFormat::Csv => return Err(unsupported("the csv format")),
         };
         lua.create_string(bytes)
     }
 
     pub fn deserialize_from_string<'lua>(
-        self,
+        &self,
         lua: &'lua Lua,
         string: LuaString<'lua>,
     ) -> LuaResult<LuaValue<'lua>> {
         let bytes = string.as_bytes();
         match self.format {
-            EncodeDecodeFormat::Json => {
-                let value: JsonValue = serde_json::from_slice(bytes).into_lua_err()?;
-                lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS)
+            EncodeDecodeFormat::Json | EncodeDecodeFormat::Jsonc => {
+                // NOTE: Comments and trailing commas are stripped before
+                // parsing, which keeps the positions in any errors the same
+                let value: JsonValue =
+                    if self.lenient || matches!(self.format, EncodeDecodeFormat::Jsonc) {
+                        serde_json::from_slice(&strip_jsonc(bytes)).into_lua_err()?
+                    } else {
+                        serde_json::from_slice(bytes).into_lua_err()?
+                    };
+                if self.preserve_order || self.bigints {
+                    self.json_to_lua(lua, &value)
+                } else {
+                    lua.to_value_with(&value, self.serialize_options())
+                }
             }
             EncodeDecodeFormat::Yaml => {
                 let value: YamlValue = serde_yaml::from_slice(bytes).into_lua_err()?;
-                lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS)
+                lua.to_value_with(&value, self.serialize_options())
             }
             EncodeDecodeFormat::Toml => {
                 if let Ok(s) = string.to_str() {
                     let value: TomlValue = toml::from_str(s).into_lua_err()?;
-                    lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS)
+                    lua.to_value_with(&value, self.serialize_options())
                 } else {
                     Err(LuaError::RuntimeError(
                         "TOML must be valid utf-8".to_string(),
                     ))
                 }
             }
+            #[cfg(feature = "msgpack")]
+            EncodeDecodeFormat::MessagePack => {
+                let mut reader = bytes;
+                let value = rmpv::decode::read_value(&mut reader).into_lua_err()?;
+                if !reader.is_empty() {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Failed to decode MessagePack - found {} trailing bytes after value",
+                        reader.len()
+                    )));
+                }
+                lua.to_value_with(&value, self.serialize_options())
+            }
+            #[cfg(not(feature = "msgpack"))]
+            EncodeDecodeFormat::MessagePack => Err(unsupported("the messagepack format")),
+            EncodeDecodeFormat::Base64 => {
+                // NOTE: Whitespace is ignored, since base64 is
+                // commonly wrapped across lines, such as in PEM files
+                let stripped = bytes
+                    .iter()
+                    .copied()
+                    .filter(|b| !b.is_ascii_whitespace())
+                    .collect::<Vec<_>>();
+                let decoded = BASE64.decode(stripped).map_err(|e| {
+                    LuaError::RuntimeError(format!("Failed to decode base64 - {e}"))
+                })?;
+                lua.create_string(decoded).map(LuaValue::String)
+            }
+            EncodeDecodeFormat::Hex => lua.create_string(decode_hex(bytes)?).map(LuaValue::String),
+            #[cfg(feature = "csv")]
+            EncodeDecodeFormat::Csv => decode_csv(lua, bytes, &self.csv),
+            #[cfg(not(feature = "csv"))]
+            EncodeDecodeFormat::Csv => Err(unsupported("the csv format")),
+        }
+    }
+
+    /**
+        Converts json into lua, with objects as ordered maps that keep the order of their
+        keys if `preserve_order` is set, and integers that are too large to be exact as
+        numbers as bigints if `bigints` is set.
+    */
+    fn json_to_lua<'lua>(&self, lua: &'lua Lua, value: &JsonValue) -> LuaResult<LuaValue<'lua>> {
+        match value {
+            JsonValue::Object(object) if self.preserve_order => {
+                let mut map = OrderedMap::new(lua)?;
+                for (key, value) in object {
+                    map.insert(lua, key.clone(), self.json_to_lua(lua, value)?)?;
+                }
+                lua.create_userdata(map).map(LuaValue::UserData)
+            }
+            JsonValue::Object(object) => {
+                let table = lua.create_table_with_capacity(0, object.len())?;
+                for (key, value) in object {
+                    table.raw_set(key.as_str(), self.json_to_lua(lua, value)?)?;
+                }
+                Ok(LuaValue::Table(table))
+            }
+            JsonValue::Array(values) => {
+                let array = lua.create_table_with_capacity(values.len(), 0)?;
+                for (index, value) in values.iter().enumerate() {
+                    array.raw_set(index + 1, self.json_to_lua(lua, value)?)?;
+                }
+                Ok(LuaValue::Table(array))
+            }
+            #[cfg(feature = "bignum")]
+            JsonValue::Number(n) if self.bigints && is_unsafe_integer(n) => {
+                LuaBigInt::parse(&n.to_string())?.into_lua_value(lua)
+            }
+            value => lua.to_value_with(value, self.serialize_options()),
         }
     }
 }
 
+/**
+    Checks if a json number is an integer that can not be represented exactly as a Luau number.
+*/
+#[cfg(feature = "bignum")]
+fn is_unsafe_integer(n: &serde_json::Number) -> bool {
+    match (n.as_i64(), n.as_u64()) {
+        (Some(i), _) => i.unsigned_abs() > MAX_SAFE_INTEGER as u64,
+        (None, Some(u)) => u > MAX_SAFE_INTEGER as u64,
+        (None, None) => false,
+    }
+}
+
+/**
+    Creates an error for a format or option that Lune was built without support for.
+*/
+#[cfg(not(all(feature = "bignum", feature = "csv", feature = "msgpack")))]
+fn unsupported(what: &str) -> LuaError {
+    LuaError::RuntimeError(format!(
+        "Lune was built without support for {what}, which must be enabled using a feature"
+    ))
+}
+
+/**
+    Rounds all floats in the given json value to the given number of decimal places.
+*/
+fn round_json_floats(value: &mut JsonValue, precision: u32) {
+    match value {
+        JsonValue::Number(n) if n.is_f64() => {
+            let scale = 10f64.powi(precision as i32);
+            let rounded = (n.as_f64().unwrap_or_default() * scale).round() / scale;
+            *value = JsonValue::from(rounded);
+        }
+        JsonValue::Array(values) => {
+            for value in values {
+                round_json_floats(value, precision);
+            }
+        }
+        JsonValue::Object(object) => {
+            for value in object.values_mut() {
+                round_json_floats(value, precision);
+            }
+        }
+        _ => {}
+    }
+}
+
 impl From<EncodeDecodeFormat> for EncodeDecodeConfig {
     fn from(format: EncodeDecodeFormat) -> Self {
         Self {
             format,
             pretty: false,
+            lenient: false,
+            preserve_nulls: false,
+            preserve_order: false,
+            bigints: false,
+            precision: None,
+            csv: CsvOptions::default(),
         }
     }
 }
@@ -129,6 +414,46 @@ impl From<(EncodeDecodeFormat, bool)> for EncodeDecodeConfig {
         Self {
             format: value.0,
             pretty: value.1,
+            lenient: false,
+            preserve_nulls: false,
+            preserve_order: false,
+            bigints: false,
+            precision: None,
+            csv: CsvOptions::default(),
         }
     }
 }
+
+/**
+    Gets the bytes of a value for the binary-to-text formats, which only accept strings.
+*/
+fn binary_value_to_bytes(value: LuaValue, format_name: &str) -> LuaResult<Vec<u8>> {
+    match value {
+        LuaValue::String(s) => Ok(s.as_bytes().to_vec()),
+        value => Err(LuaError::RuntimeError(format!(
+            "Failed to encode {format_name} - expected string, got {}",
+            value.type_name()
+        ))),
+    }
+}
+
+fn decode_hex(bytes: &[u8]) -> LuaResult<Vec<u8>> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(LuaError::RuntimeError(format!(
+            "Failed to decode hex - expected an even number of characters, got {}",
+            bytes.len()
+        )));
+    }
+    let digit = |index: usize| {
+        char::from(bytes[index]).to_digit(16).ok_or_else(|| {
+            LuaError::RuntimeError(format!(
+                "Failed to decode hex - invalid character at position {}",
+                index + 1
+            ))
+        })
+    };
+    (0..bytes.len())
+        .step_by(2)
+        .map(|index| Ok((digit(index)? * 16 + digit(index + 1)?) as u8))
+        .collect()
+}
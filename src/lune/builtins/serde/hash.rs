@@ -0,0 +1,180 @@
+use md5::{Digest, Md5};
+use mlua::prelude::*;
+use ring::digest;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /**
+        The size of the blocks that the algorithm processes, in bytes.
+
+        This is used by HMAC to pad or hash keys, see RFC 2104.
+    */
+    fn block_size(self) -> usize {
+        match self {
+            Self::Sha384 | Self::Sha512 => 128,
+            _ => 64,
+        }
+    }
+
+    /**
+        Hashes all of the given parts, as if they were a single concatenated slice.
+    */
+    pub fn digest(self, parts: &[&[u8]]) -> Vec<u8> {
+        let ring_algorithm = match self {
+            Self::Md5 => {
+                let mut hasher = Md5::new();
+                for part in parts {
+                    hasher.update(part);
+                }
+                return hasher.finalize().to_vec();
+            }
+            Self::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                for part in parts {
+                    hasher.update(part);
+                }
+                return hasher.finalize().as_bytes().to_vec();
+            }
+            Self::Sha1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            Self::Sha256 => &digest::SHA256,
+            Self::Sha384 => &digest::SHA384,
+            Self::Sha512 => &digest::SHA512,
+        };
+        let mut context = digest::Context::new(ring_algorithm);
+        for part in parts {
+            context.update(part);
+        }
+        context.finish().as_ref().to_vec()
+    }
+
+    /**
+        Creates a keyed hash of the given data using HMAC, see RFC 2104.
+    */
+    pub fn hmac(self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        let block_size = self.block_size();
+        let mut block = if key.len() > block_size {
+            self.digest(&[key])
+        } else {
+            key.to_vec()
+        };
+        block.resize(block_size, 0);
+
+        let inner_pad = block.iter().map(|b| b ^ 0x36).collect::<Vec<_>>();
+        let outer_pad = block.iter().map(|b| b ^ 0x5C).collect::<Vec<_>>();
+
+        let inner = self.digest(&[&inner_pad, data]);
+        self.digest(&[&outer_pad, &inner])
+    }
+}
+
+impl<'lua> FromLua<'lua> for HashAlgorithm {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "md5" => Ok(Self::Md5),
+                "sha1" => Ok(Self::Sha1),
+                "sha256" => Ok(Self::Sha256),
+                "sha384" => Ok(Self::Sha384),
+                "sha512" => Ok(Self::Sha512),
+                "blake3" => Ok(Self::Blake3),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "HashAlgorithm",
+                    message: Some(format!(
+                        "Invalid algorithm '{kind}', valid algorithms are: md5, sha1, sha256, sha384, sha512, blake3"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "HashAlgorithm",
+                message: None,
+            })
+        }
+    }
+}
+
+/**
+    Creates a lua string from a digest, either as raw bytes or as lowercase hex.
+*/
+pub fn digest_to_lua(lua: &Lua, digest: Vec<u8>, raw: bool) -> LuaResult<LuaString> {
+    if raw {
+        lua.create_string(digest)
+    } else {
+        let hex = digest
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        lua.create_string(hex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(algorithm: HashAlgorithm, data: &[u8]) -> String {
+        let digest = algorithm.digest(&[data]);
+        digest.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    fn hmac_hex(algorithm: HashAlgorithm, key: &[u8], data: &[u8]) -> String {
+        let digest = algorithm.hmac(key, data);
+        digest.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn digests() {
+        assert_eq!(
+            hex(HashAlgorithm::Md5, b""),
+            "d41d8cd98f00b204e9800998ecf8427e"
+        );
+        assert_eq!(
+            hex(HashAlgorithm::Sha256, b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(HashAlgorithm::Blake3, b""),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        assert_eq!(
+            HashAlgorithm::Sha1.digest(&[b"ab", b"c"]),
+            HashAlgorithm::Sha1.digest(&[b"abc"])
+        );
+    }
+
+    #[test]
+    fn hmacs() {
+        // Test case 2 from RFC 2202 and RFC 4231
+        let key = b"Jefe";
+        let data = b"what do ya want for nothing?";
+        assert_eq!(
+            hmac_hex(HashAlgorithm::Md5, key, data),
+            "750c783e6ab0b503eaa86e310a5db738"
+        );
+        assert_eq!(
+            hmac_hex(HashAlgorithm::Sha1, key, data),
+            "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"
+        );
+        assert_eq!(
+            hmac_hex(HashAlgorithm::Sha256, key, data),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // Keys longer than the block size are hashed first
+        let long_key = [0xAA; 131];
+        assert_eq!(
+            HashAlgorithm::Sha512.hmac(&long_key, data),
+            HashAlgorithm::Sha512.hmac(&HashAlgorithm::Sha512.digest(&[&long_key]), data)
+        );
+    }
+}
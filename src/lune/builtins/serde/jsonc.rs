@@ -0,0 +1,99 @@
+/**
+    Strips comments and trailing commas from json, so that
+    it can then be parsed by a strict json parser.
+
+    Stripped characters are replaced with spaces, and newlines are kept,
+    so that positions in any errors from the json parser stay the same.
+*/
+pub fn strip_jsonc(bytes: &[u8]) -> Vec<u8> {
+    let mut output = bytes.to_vec();
+    let mut index = 0;
+    // The position of the last comma, if only whitespace and comments came after it
+    let mut pending_comma = None;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'"' => {
+                pending_comma = None;
+                index += 1;
+                while index < bytes.len() {
+                    match bytes[index] {
+                        b'\\' => index += 2,
+                        b'"' => break,
+                        _ => index += 1,
+                    }
+                }
+                index += 1;
+            }
+            b'/' if bytes.get(index + 1) == Some(&b'/') => {
+                while index < bytes.len() && bytes[index] != b'\n' {
+                    output[index] = b' ';
+                    index += 1;
+                }
+            }
+            b'/' if bytes.get(index + 1) == Some(&b'*') => {
+                let end = bytes[index + 2..]
+                    .windows(2)
+                    .position(|w| w == b"*/")
+                    .map_or(bytes.len(), |pos| index + 2 + pos + 2);
+                for byte in &mut output[index..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                index = end;
+            }
+            b',' => {
+                pending_comma = Some(index);
+                index += 1;
+            }
+            b'}' | b']' => {
+                if let Some(comma) = pending_comma.take() {
+                    output[comma] = b' ';
+                }
+                index += 1;
+            }
+            byte if byte.is_ascii_whitespace() => index += 1,
+            _ => {
+                pending_comma = None;
+                index += 1;
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(s: &str) -> String {
+        String::from_utf8(strip_jsonc(s.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn strips_comments() {
+        assert_eq!(strip("{ // hi\n\"a\": 1 }"), "{      \n\"a\": 1 }");
+        assert_eq!(strip("[1, /* two\n */ 2]"), "[1,       \n    2]");
+        assert_eq!(strip("[1] // unterminated"), "[1]                ");
+        assert_eq!(strip("[1 /* unterminated"), "[1                ");
+    }
+
+    #[test]
+    fn keeps_strings() {
+        assert_eq!(strip(r#"["// not a comment"]"#), r#"["// not a comment"]"#);
+        assert_eq!(strip(r#"["a\"/*", 1]"#), r#"["a\"/*", 1]"#);
+        assert_eq!(strip(r#"["a,", "]"]"#), r#"["a,", "]"]"#);
+    }
+
+    #[test]
+    fn strips_trailing_commas() {
+        assert_eq!(strip("[1, 2, ]"), "[1, 2  ]");
+        assert_eq!(
+            strip("{\"a\": 1, // comment\n}"),
+            "{\"a\": 1            \n}"
+        );
+        assert_eq!(strip("[1, 2]"), "[1, 2]");
+        // Extra commas that are not trailing are left for the parser to reject
+        assert_eq!(strip("[1,, 2]"), "[1,, 2]");
+    }
+}
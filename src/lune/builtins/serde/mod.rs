@@ -1,10 +1,22 @@
 use mlua::prelude::*;
 
 pub(super) mod compress_decompress;
+pub(super) mod csv;
+pub(super) mod decode_stream;
 pub(super) mod encode_decode;
+pub(super) mod hash;
+pub(super) mod jsonc;
+pub(super) mod ndjson;
+pub(super) mod ordered_map;
+pub(super) mod text_encoding;
 
-use compress_decompress::{compress, decompress, CompressDecompressFormat};
+use compress_decompress::{
+    compress, decompress, CompressDecompressFormat, CompressOptions, StreamCompressor,
+};
 use encode_decode::{EncodeDecodeConfig, EncodeDecodeFormat};
+use hash::{digest_to_lua, HashAlgorithm};
+use ordered_map::OrderedMap;
+use text_encoding::TextEncoding;
 
 use crate::lune::util::TableBuilder;
 
@@ -12,35 +24,62 @@ pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
     TableBuilder::new(lua)?
         .with_function("encode", serde_encode)?
         .with_function("decode", serde_decode)?
+        .with_value("null", lua.null())?
+        .with_function("orderedMap", serde_ordered_map)?
+        .with_value("decodeStream", decode_stream::create(lua)?)?
         .with_async_function("compress", serde_compress)?
         .with_async_function("decompress", serde_decompress)?
+        .with_function("compressStream", serde_compress_stream)?
+        .with_value("ndjson", ndjson::create(lua)?)?
+        .with_function("hash", serde_hash)?
+        .with_function("hmac", serde_hmac)?
+        .with_function("textDecode", serde_text_decode)?
+        .with_function("textEncode", serde_text_encode)?
         .build_readonly()
 }
 
 fn serde_encode<'lua>(
     lua: &'lua Lua,
-    (format, val, pretty): (EncodeDecodeFormat, LuaValue<'lua>, Option<bool>),
+    (format, val, options): (EncodeDecodeFormat, LuaValue<'lua>, LuaValue<'lua>),
 ) -> LuaResult<LuaString<'lua>> {
-    let config = EncodeDecodeConfig::from((format, pretty.unwrap_or_default()));
+    let config = EncodeDecodeConfig::from_options(format, options)?;
     config.serialize_to_string(lua, val)
 }
 
 fn serde_decode<'lua>(
     lua: &'lua Lua,
-    (format, str): (EncodeDecodeFormat, LuaString<'lua>),
+    (format, str, options): (EncodeDecodeFormat, LuaString<'lua>, LuaValue<'lua>),
 ) -> LuaResult<LuaValue<'lua>> {
-    let config = EncodeDecodeConfig::from(format);
+    let config = EncodeDecodeConfig::from_options(format, options)?;
     config.deserialize_from_string(lua, str)
 }
 
+fn serde_ordered_map(lua: &Lua, entries: Option<LuaTable>) -> LuaResult<OrderedMap> {
+    let mut map = OrderedMap::new(lua)?;
+    if let Some(entries) = entries {
+        for entry in entries.sequence_values::<LuaTable>() {
+            let entry = entry?;
+            map.insert(lua, entry.raw_get(1)?, entry.raw_get(2)?)?;
+        }
+    }
+    Ok(map)
+}
+
 async fn serde_compress<'lua>(
     lua: &'lua Lua,
-    (format, str): (CompressDecompressFormat, LuaString<'lua>),
+    (format, str, options): (CompressDecompressFormat, LuaString<'lua>, CompressOptions),
 ) -> LuaResult<LuaString<'lua>> {
-    let bytes = compress(format, str).await?;
+    let bytes = compress(format, str, options).await?;
     lua.create_string(bytes)
 }
 
+fn serde_compress_stream(
+    _: &Lua,
+    (format, options): (CompressDecompressFormat, CompressOptions),
+) -> LuaResult<StreamCompressor> {
+    StreamCompressor::new(format, options)
+}
+
 async fn serde_decompress<'lua>(
     lua: &'lua Lua,
     (format, str): (CompressDecompressFormat, LuaString<'lua>),
@@ -48,3 +87,38 @@ async fn serde_decompress<'lua>(
     let bytes = decompress(format, str).await?;
     lua.create_string(bytes)
 }
+
+fn serde_hash<'lua>(
+    lua: &'lua Lua,
+    (algorithm, data, raw): (HashAlgorithm, LuaString<'lua>, Option<bool>),
+) -> LuaResult<LuaString<'lua>> {
+    let digest = algorithm.digest(&[data.as_bytes()]);
+    digest_to_lua(lua, digest, raw.unwrap_or_default())
+}
+
+fn serde_hmac<'lua>(
+    lua: &'lua Lua,
+    (algorithm, key, data, raw): (
+        HashAlgorithm,
+        LuaString<'lua>,
+        LuaString<'lua>,
+        Option<bool>,
+    ),
+) -> LuaResult<LuaString<'lua>> {
+    let digest = algorithm.hmac(key.as_bytes(), data.as_bytes());
+    digest_to_lua(lua, digest, raw.unwrap_or_default())
+}
+
+fn serde_text_decode<'lua>(
+    lua: &'lua Lua,
+    (bytes, encoding): (LuaString<'lua>, TextEncoding),
+) -> LuaResult<LuaString<'lua>> {
+    lua.create_string(encoding.decode(bytes.as_bytes()))
+}
+
+fn serde_text_encode<'lua>(
+    lua: &'lua Lua,
+    (text, encoding): (LuaString<'lua>, TextEncoding),
+) -> LuaResult<LuaString<'lua>> {
+    lua.create_string(encoding.encode(text.to_str()?)?)
+}
@@ -0,0 +1,181 @@
+use std::sync::Arc;
+
+use mlua::prelude::*;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::Mutex as AsyncMutex};
+
+use crate::lune::util::TableBuilder;
+
+use super::encode_decode::{EncodeDecodeConfig, EncodeDecodeFormat};
+
+/*
+    Decoding from a reader needs to call its `next` method,
+    which may yield, so we need to do that from lua and not rust
+
+    1. Strings are decoded fully, right away, into an array of records
+    2. Anything else is assumed to be a line reader, we then return
+       a new reader that skips blank lines and decodes the others
+*/
+const NDJSON_DECODE_IMPL_LUA: &str = r#"
+local input = ...
+if type(input) == "string" then
+    return decodeString(input)
+end
+local lineNumber = 0
+return freeze({
+    next = function(_)
+        while true do
+            local line = input:next()
+            if line == nil then
+                return nil
+            end
+            lineNumber += 1
+            if not isBlank(line) then
+                return decodeLine(line, lineNumber)
+            end
+        end
+    end,
+})
+"#;
+
+/*
+    Writing to a handle needs to call its `write` method, which may yield,
+    so similar to decoding above we need to do that from lua and not rust
+
+    1. Strings are treated as file paths, and opened for appending in rust
+    2. Anything else is assumed to be a handle with `write` and maybe `close` methods
+*/
+const NDJSON_WRITER_IMPL_LUA: &str = r#"
+local target = ...
+if type(target) == "string" then
+    return openFile(target)
+end
+return freeze({
+    write = function(_, record)
+        target:write(encodeLine(record))
+    end,
+    close = function(_)
+        if target.close ~= nil then
+            target:close()
+        end
+    end,
+})
+"#;
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    let table_freeze = lua
+        .globals()
+        .get::<_, LuaTable>("table")?
+        .get::<_, LuaFunction>("freeze")?;
+    let type_of = lua.globals().get::<_, LuaFunction>("type")?;
+
+    let decode_env = TableBuilder::new(lua)?
+        .with_value("type", type_of.clone())?
+        .with_value("freeze", table_freeze.clone())?
+        .with_function("isBlank", |_, line: LuaString| {
+            Ok(line.as_bytes().iter().all(u8::is_ascii_whitespace))
+        })?
+        .with_function("decodeLine", ndjson_decode_line)?
+        .with_function("decodeString", ndjson_decode_string)?
+        .build_readonly()?;
+    let decode = lua
+        .load(NDJSON_DECODE_IMPL_LUA)
+        .set_name("serde.ndjson.decode")
+        .set_environment(decode_env)
+        .into_function()?;
+
+    let writer_env = TableBuilder::new(lua)?
+        .with_value("type", type_of)?
+        .with_value("freeze", table_freeze)?
+        .with_function("encodeLine", ndjson_encode_line)?
+        .with_async_function("openFile", ndjson_open_file)?
+        .build_readonly()?;
+    let writer = lua
+        .load(NDJSON_WRITER_IMPL_LUA)
+        .set_name("serde.ndjson.writer")
+        .set_environment(writer_env)
+        .into_function()?;
+
+    TableBuilder::new(lua)?
+        .with_value("decode", decode)?
+        .with_value("writer", writer)?
+        .build_readonly()
+}
+
+fn ndjson_decode_line<'lua>(
+    lua: &'lua Lua,
+    (line, line_number): (LuaString<'lua>, usize),
+) -> LuaResult<LuaValue<'lua>> {
+    EncodeDecodeConfig::from(EncodeDecodeFormat::Json)
+        .deserialize_from_string(lua, line)
+        .map_err(|e| {
+            LuaError::RuntimeError(format!(
+                "Failed to decode ndjson record on line {line_number} - {e}"
+            ))
+        })
+}
+
+fn ndjson_decode_string<'lua>(
+    lua: &'lua Lua,
+    contents: LuaString<'lua>,
+) -> LuaResult<LuaTable<'lua>> {
+    let records = lua.create_table()?;
+    for (index, line) in contents.as_bytes().split(|b| *b == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let line = lua.create_string(line)?;
+        records.raw_push(ndjson_decode_line(lua, (line, index + 1))?)?;
+    }
+    Ok(records)
+}
+
+fn ndjson_encode_line<'lua>(lua: &'lua Lua, record: LuaValue<'lua>) -> LuaResult<LuaString<'lua>> {
+    // NOTE: Compact json never contains any raw newlines,
+    // any newlines inside of strings are always escaped
+    let encoded =
+        EncodeDecodeConfig::from(EncodeDecodeFormat::Json).serialize_to_string(lua, record)?;
+    let mut bytes = encoded.as_bytes().to_vec();
+    bytes.push(b'\n');
+    lua.create_string(bytes)
+}
+
+async fn ndjson_open_file(lua: &'static Lua, path: String) -> LuaResult<LuaTable> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+        .into_lua_err()?;
+    let writer = Arc::new(AsyncMutex::new(Some(file)));
+    let writer_close = Arc::clone(&writer);
+
+    TableBuilder::new(lua)?
+        .with_async_function("write", move |lua, (_, record): (LuaValue, LuaValue)| {
+            let writer = Arc::clone(&writer);
+            async move {
+                let line = ndjson_encode_line(lua, record)?;
+                match writer.lock().await.as_mut() {
+                    Some(file) => {
+                        // NOTE: We flush after every record so that no records are
+                        // lost if the writer is never explicitly closed by the user
+                        file.write_all(line.as_bytes()).await.into_lua_err()?;
+                        file.flush().await.into_lua_err()
+                    }
+                    None => Err(LuaError::RuntimeError(
+                        "Failed to write ndjson record - writer is closed".to_string(),
+                    )),
+                }
+            }
+        })?
+        .with_async_function("close", move |_, _: LuaValue| {
+            let writer = Arc::clone(&writer_close);
+            async move {
+                if let Some(mut file) = writer.lock().await.take() {
+                    file.shutdown().await.into_lua_err()?;
+                }
+                Ok(())
+            }
+        })?
+        .build_readonly()
+}
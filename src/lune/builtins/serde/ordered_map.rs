@@ -0,0 +1,179 @@
+use std::{cell::Cell, collections::HashMap};
+
+use mlua::prelude::*;
+use serde::Serialize;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+/**
+    A map with string keys that remembers the order keys were inserted in,
+    created using `serde.orderedMap`, or when decoding with `preserveOrder`.
+
+    Values are kept in a lua table, while the order of keys is kept here,
+    and the map is used from lua through its index and iteration metamethods.
+*/
+#[derive(Debug)]
+pub struct OrderedMap {
+    keys: Vec<String>,
+    values: LuaRegistryKey,
+}
+
+impl OrderedMap {
+    pub fn new(lua: &Lua) -> LuaResult<Self> {
+        Ok(Self {
+            keys: Vec::new(),
+            values: lua.create_registry_value(lua.create_table()?)?,
+        })
+    }
+
+    fn values<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaTable<'lua>> {
+        lua.registry_value(&self.values)
+    }
+
+    pub fn get<'lua>(&self, lua: &'lua Lua, key: &str) -> LuaResult<LuaValue<'lua>> {
+        self.values(lua)?.raw_get(key)
+    }
+
+    /**
+        Inserts a value, keeping the position of existing keys,
+        or removes the key from the map if the value is nil.
+    */
+    pub fn insert(&mut self, lua: &Lua, key: String, value: LuaValue) -> LuaResult<()> {
+        let values = self.values(lua)?;
+        let exists = values.contains_key(key.as_str())?;
+        if value.is_nil() {
+            if exists {
+                self.keys.retain(|k| k != &key);
+            }
+        } else if !exists {
+            self.keys.push(key.clone());
+        }
+        values.raw_set(key, value)
+    }
+}
+
+impl LuaUserData for OrderedMap {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(LuaMetaMethod::Index, |lua, this, key: String| {
+            this.get(lua, &key)
+        });
+        methods.add_meta_method_mut(
+            LuaMetaMethod::NewIndex,
+            |lua, this, (key, value): (String, LuaValue)| this.insert(lua, key, value),
+        );
+        methods.add_meta_method(LuaMetaMethod::Len, |_, this, ()| Ok(this.keys.len()));
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("OrderedMap({})", this.keys.len()))
+        });
+        methods.add_meta_method(LuaMetaMethod::Iter, |lua, this, ()| {
+            // NOTE: Keys are copied so that the map may be changed while
+            // iterating, and keys removed in the meantime are skipped
+            let keys = this.keys.clone();
+            let values = lua.create_registry_value(this.values(lua)?)?;
+            let index = Cell::new(0);
+            let next = lua.create_function(move |lua, ()| {
+                let values = lua.registry_value::<LuaTable>(&values)?;
+                while let Some(key) = keys.get(index.get()) {
+                    index.set(index.get() + 1);
+                    let value = values.raw_get::<_, LuaValue>(key.as_str())?;
+                    if !value.is_nil() {
+                        return (key.as_str(), value).into_lua_multi(lua);
+                    }
+                }
+                ().into_lua_multi(lua)
+            })?;
+            Ok(next)
+        });
+    }
+}
+
+/**
+    A value that has already been converted, given back to the
+    serializer in place of an ordered map so that its order is kept.
+*/
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct Converted(JsonValue);
+
+impl LuaUserData for Converted {}
+
+/**
+    Creates a serializable version of the given value.
+
+    Values should be serialized using this instead of being converted using
+    `LuaSerdeExt::from_value`, which converts any userdata into intermediate
+    maps with sorted keys first, losing the order of keys in ordered maps.
+*/
+pub fn serializable<'a, 'lua>(value: &'a LuaValue<'lua>) -> impl Serialize + 'a {
+    value
+        .to_serializable()
+        .sort_keys(true)
+        .deny_recursive_tables(false)
+        .deny_unsupported_types(true)
+}
+
+/**
+    Replaces any ordered maps within the given value with their converted values,
+    so that the value can be serialized while keeping the order of their keys.
+
+    Tables are only copied if they contain an ordered map, and tables
+    that appear more than once are only prepared the first time.
+*/
+pub fn prepare_ordered_maps<'lua>(
+    lua: &'lua Lua,
+    value: LuaValue<'lua>,
+) -> LuaResult<LuaValue<'lua>> {
+    prepare(lua, value, &mut HashMap::new())
+}
+
+fn prepare<'lua>(
+    lua: &'lua Lua,
+    value: LuaValue<'lua>,
+    visited: &mut HashMap<usize, LuaValue<'lua>>,
+) -> LuaResult<LuaValue<'lua>> {
+    match value {
+        LuaValue::UserData(ud) if ud.is::<OrderedMap>() => {
+            let map = ud.borrow::<OrderedMap>()?;
+            let mut converted = JsonMap::new();
+            for key in &map.keys {
+                let value = prepare(lua, map.get(lua, key)?, visited)?;
+                let value = serde_json::to_value(serializable(&value)).into_lua_err()?;
+                converted.insert(key.clone(), value);
+            }
+            lua.create_ser_userdata(Converted(JsonValue::Object(converted)))
+                .map(LuaValue::UserData)
+        }
+        LuaValue::Table(t) => {
+            let pointer = t.to_pointer() as usize;
+            if let Some(prepared) = visited.get(&pointer) {
+                return Ok(prepared.clone());
+            }
+            // NOTE: Recursive tables are given back as-is, since
+            // they can not be copied before they are prepared
+            visited.insert(pointer, LuaValue::Table(t.clone()));
+            let mut changed = Vec::new();
+            for pair in t.clone().pairs::<LuaValue, LuaValue>() {
+                let (key, value) = pair?;
+                if matches!(value, LuaValue::Table(_) | LuaValue::UserData(_)) {
+                    let prepared = prepare(lua, value.clone(), visited)?;
+                    if prepared != value {
+                        changed.push((key, prepared));
+                    }
+                }
+            }
+            if changed.is_empty() {
+                return Ok(LuaValue::Table(t));
+            }
+            let copy = lua.create_table()?;
+            for pair in t.pairs::<LuaValue, LuaValue>() {
+                let (key, value) = pair?;
+                copy.raw_set(key, value)?;
+            }
+            for (key, value) in changed {
+                copy.raw_set(key, value)?;
+            }
+            visited.insert(pointer, LuaValue::Table(copy.clone()));
+            Ok(LuaValue::Table(copy))
+        }
+        value => Ok(value),
+    }
+}
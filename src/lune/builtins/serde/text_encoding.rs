@@ -0,0 +1,117 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use mlua::prelude::*;
+
+/**
+    A text encoding, such as `utf-16le`, `latin1` or `shift_jis`.
+
+    Encodings are looked up using their labels from the WHATWG encoding
+    standard, which also includes most common aliases for each encoding.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct TextEncoding(&'static Encoding);
+
+impl<'lua> FromLua<'lua> for TextEncoding {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let LuaValue::String(s) = &value {
+            let label = s.to_string_lossy();
+            match Encoding::for_label(label.trim().as_bytes()) {
+                Some(encoding) => Ok(Self(encoding)),
+                None => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "TextEncoding",
+                    message: Some(format!(
+                        "Invalid encoding '{label}', see https://encoding.spec.whatwg.org/#names-and-labels for valid encodings"
+                    )),
+                }),
+            }
+        } else {
+            Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "TextEncoding",
+                message: None,
+            })
+        }
+    }
+}
+
+impl TextEncoding {
+    /**
+        Decodes text in this encoding into utf-8.
+
+        Malformed sequences are replaced with the unicode
+        replacement character, same as in web browsers.
+    */
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let (decoded, _) = self.0.decode_with_bom_removal(bytes);
+        decoded.into_owned()
+    }
+
+    /**
+        Encodes utf-8 text into this encoding.
+
+        Errors if the text contains characters that can not be
+        represented in this encoding, instead of replacing them.
+    */
+    pub fn encode(self, text: &str) -> LuaResult<Vec<u8>> {
+        // NOTE: The encoding standard only allows decoding utf-16,
+        // so encoding_rs would give back utf-8, and we encode it here
+        if self.0 == UTF_16LE {
+            return Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect());
+        }
+        if self.0 == UTF_16BE {
+            return Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect());
+        }
+        let mut encoder = self.0.new_encoder();
+        let capacity = encoder
+            .max_buffer_length_from_utf8_without_replacement(text.len())
+            .ok_or_else(|| LuaError::RuntimeError("Text is too long to encode".to_string()))?;
+        let mut bytes = Vec::with_capacity(capacity);
+        let (result, read) =
+            encoder.encode_from_utf8_to_vec_without_replacement(text, &mut bytes, true);
+        match result {
+            encoding_rs::EncoderResult::InputEmpty => Ok(bytes),
+            encoding_rs::EncoderResult::Unmappable(c) => Err(LuaError::RuntimeError(format!(
+                "Failed to encode text as {} - the character '{c}' at byte {} can not be represented",
+                self.0.name(),
+                read - c.len_utf8() + 1
+            ))),
+            encoding_rs::EncoderResult::OutputFull => {
+                unreachable!("Buffer was allocated using the maximum length")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoding(label: &str) -> TextEncoding {
+        TextEncoding(Encoding::for_label(label.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn round_trips() {
+        for (label, text) in [
+            ("utf-16le", "héllo wörld"),
+            ("utf-16be", "héllo wörld"),
+            ("latin1", "héllo wörld"),
+            ("shift_jis", "こんにちは"),
+        ] {
+            let encoding = encoding(label);
+            let encoded = encoding.encode(text).unwrap();
+            assert_eq!(encoding.decode(&encoded), text, "{label}");
+        }
+    }
+
+    #[test]
+    fn encodes_utf16() {
+        assert_eq!(encoding("utf-16le").encode("hi").unwrap(), b"h\0i\0");
+        assert_eq!(encoding("utf-16be").encode("hi").unwrap(), b"\0h\0i");
+    }
+
+    #[test]
+    fn errors_on_unmappable() {
+        assert!(encoding("latin1").encode("snow ☃").is_err());
+    }
+}
@@ -1,8 +1,9 @@
 use mlua::prelude::*;
 
-use dialoguer::{theme::ColorfulTheme, Confirm, Input, MultiSelect, Select};
+use dialoguer::{theme::ColorfulTheme, Completion, Confirm, Input, MultiSelect, Password, Select};
 use tokio::{
     io::{self, AsyncWriteExt},
+    sync::{mpsc, oneshot},
     task,
 };
 
@@ -14,7 +15,12 @@ use crate::lune::util::{
 };
 
 mod prompt;
+mod terminal;
+
 use prompt::{PromptKind, PromptOptions, PromptResult};
+use terminal::{
+    stdio_clear, stdio_cursor_to, stdio_is_tty, stdio_raw_mode, stdio_read_key, stdio_terminal_size,
+};
 
 pub fn create(lua: &'static Lua) -> LuaResult<LuaTable<'_>> {
     TableBuilder::new(lua)?
@@ -24,6 +30,12 @@ pub fn create(lua: &'static Lua) -> LuaResult<LuaTable<'_>> {
         .with_async_function("write", stdio_write)?
         .with_async_function("ewrite", stdio_ewrite)?
         .with_async_function("prompt", stdio_prompt)?
+        .with_function("rawMode", stdio_raw_mode)?
+        .with_async_function("readKey", stdio_read_key)?
+        .with_function("terminalSize", stdio_terminal_size)?
+        .with_function("cursorTo", stdio_cursor_to)?
+        .with_function("clear", stdio_clear)?
+        .with_function("isTTY", stdio_is_tty)?
         .build_readonly()
 }
 
@@ -55,21 +67,93 @@ async fn stdio_ewrite(_: &Lua, s: LuaString<'_>) -> LuaResult<()> {
     Ok(())
 }
 
-async fn stdio_prompt(_: &Lua, options: PromptOptions) -> LuaResult<PromptResult> {
-    task::spawn_blocking(move || prompt(options))
-        .await
-        .into_lua_err()?
+type CompletionRequest = (String, oneshot::Sender<Option<String>>);
+
+async fn stdio_prompt(lua: &Lua, mut options: PromptOptions) -> LuaResult<PromptResult> {
+    let completion = match options.completion.take() {
+        Some(key) => {
+            let func = lua.registry_value::<LuaFunction>(&key)?;
+            lua.remove_registry_value(key)?;
+            Some(func)
+        }
+        None => None,
+    };
+
+    /*
+        Prompts block while waiting for input, so they run on a separate thread,
+        which means that completion callbacks can not be called directly from
+        the prompt - instead, the prompt sends the current input back to us here,
+        and we call the callback and send its result back to the waiting prompt
+    */
+    let (tx, mut rx) = mpsc::channel::<CompletionRequest>(1);
+    let completion_tx = completion.is_some().then_some(tx);
+    let mut handle = task::spawn_blocking(move || prompt(options, completion_tx));
+
+    let mut completion_error = None;
+    loop {
+        tokio::select! {
+            result = &mut handle => {
+                let result = result.into_lua_err()?;
+                return match completion_error {
+                    Some(e) => Err(e),
+                    None => result,
+                };
+            }
+            Some((input, respond)) = rx.recv() => {
+                let func = completion.as_ref().expect("Missing completion callback");
+                let completed = match func.call::<_, Option<String>>(input) {
+                    Ok(completed) => completed,
+                    Err(e) => {
+                        completion_error.get_or_insert(e);
+                        None
+                    }
+                };
+                respond.send(completed).ok();
+            }
+        }
+    }
 }
 
-fn prompt(options: PromptOptions) -> LuaResult<PromptResult> {
+/**
+    Completion for text prompts, which asks the
+    Lua completion callback to complete the input.
+*/
+struct PromptCompletion {
+    requests: mpsc::Sender<CompletionRequest>,
+}
+
+impl Completion for PromptCompletion {
+    fn get(&self, input: &str) -> Option<String> {
+        let (tx, rx) = oneshot::channel();
+        self.requests.blocking_send((input.to_string(), tx)).ok()?;
+        rx.blocking_recv().ok().flatten()
+    }
+}
+
+fn prompt(
+    options: PromptOptions,
+    completion_tx: Option<mpsc::Sender<CompletionRequest>>,
+) -> LuaResult<PromptResult> {
     let theme = ColorfulTheme::default();
     match options.kind {
         PromptKind::Text => {
-            let input: String = Input::with_theme(&theme)
+            let completion = completion_tx.map(|requests| PromptCompletion { requests });
+            let mut prompt = Input::with_theme(&theme);
+            prompt
                 .allow_empty(true)
                 .with_prompt(options.text.unwrap_or_default())
-                .with_initial_text(options.default_string.unwrap_or_default())
-                .interact_text()?;
+                .with_initial_text(options.default_string.unwrap_or_default());
+            if let Some(completion) = &completion {
+                prompt.completion_with(completion);
+            }
+            let input: String = prompt.interact_text()?;
+            Ok(PromptResult::String(input))
+        }
+        PromptKind::Password => {
+            let input = Password::with_theme(&theme)
+                .allow_empty_password(true)
+                .with_prompt(options.text.unwrap_or_default())
+                .interact()?;
             Ok(PromptResult::String(input))
         }
         PromptKind::Confirm => {
@@ -5,6 +5,7 @@ use mlua::prelude::*;
 #[derive(Debug, Clone, Copy)]
 pub enum PromptKind {
     Text,
+    Password,
     Confirm,
     Select,
     MultiSelect,
@@ -12,7 +13,13 @@ pub enum PromptKind {
 
 impl PromptKind {
     fn get_all() -> Vec<Self> {
-        vec![Self::Text, Self::Confirm, Self::Select, Self::MultiSelect]
+        vec![
+            Self::Text,
+            Self::Password,
+            Self::Confirm,
+            Self::Select,
+            Self::MultiSelect,
+        ]
     }
 }
 
@@ -29,6 +36,7 @@ impl fmt::Display for PromptKind {
             "{}",
             match self {
                 Self::Text => "Text",
+                Self::Password => "Password",
                 Self::Confirm => "Confirm",
                 Self::Select => "Select",
                 Self::MultiSelect => "MultiSelect",
@@ -66,6 +74,7 @@ impl<'lua> FromLua<'lua> for PromptKind {
             // show the user a descriptive error message
             match s.as_ref() {
                 "text" => Ok(Self::Text),
+                "password" => Ok(Self::Password),
                 "confirm" => Ok(Self::Confirm),
                 "select" => Ok(Self::Select),
                 "multiselect" => Ok(Self::MultiSelect),
@@ -98,6 +107,7 @@ pub struct PromptOptions {
     pub default_string: Option<String>,
     pub default_bool: Option<bool>,
     pub options: Option<Vec<String>>,
+    pub completion: Option<LuaRegistryKey>,
 }
 
 impl<'lua> FromLuaMulti<'lua> for PromptOptions {
@@ -139,11 +149,24 @@ impl<'lua> FromLuaMulti<'lua> for PromptOptions {
                 }
             },
         };
+        // Argument #4 - completion callback (optional)
+        let completion = match values.pop_front() {
+            None | Some(LuaValue::Nil) => None,
+            Some(LuaValue::Function(f)) => Some(f),
+            Some(value) => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "PromptOptions",
+                    message: Some("Argument #4 must be a function or nil".to_string()),
+                })
+            }
+        };
         /*
             Make sure we got the required values for the specific prompt kind:
 
             - "Confirm" requires a message to be present so the user knows what they are confirming
             - "Select" and "MultiSelect" both require a table of options to choose from
+            - Only "Text" may complete the input using a completion callback
         */
         if matches!(kind, PromptKind::Confirm) && text.is_none() {
             return Err(LuaError::FromLuaConversionError {
@@ -159,6 +182,13 @@ impl<'lua> FromLuaMulti<'lua> for PromptOptions {
                 message: Some("Argument #3 missing or nil".to_string()),
             });
         }
+        if completion.is_some() && !matches!(kind, PromptKind::Text) {
+            return Err(LuaError::FromLuaConversionError {
+                from: "function",
+                to: "PromptOptions",
+                message: Some(format!("Prompt kind '{kind}' does not support completion")),
+            });
+        }
         // All good, return the prompt options
         Ok(Self {
             kind,
@@ -166,6 +196,9 @@ impl<'lua> FromLuaMulti<'lua> for PromptOptions {
             default_bool,
             default_string,
             options,
+            completion: completion
+                .map(|f| lua.create_registry_value(f))
+                .transpose()?,
         })
     }
 }
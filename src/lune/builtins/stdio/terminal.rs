@@ -0,0 +1,180 @@
+use std::{
+    io::{self, IsTerminal},
+    time::Duration,
+};
+
+use crossterm::{
+    cursor::{MoveTo, MoveToColumn},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{self, Clear, ClearType},
+};
+use mlua::prelude::*;
+use tokio::{sync::oneshot, task};
+
+use crate::lune::{scheduler::Scheduler, util::TableBuilder};
+
+/**
+    How often to check for new key presses, and if the
+    thread that is waiting for a key press is still waiting.
+*/
+const KEY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/**
+    Marker stored in the app data of the Lua struct once the exit handler that
+    disables raw mode has been added, so that it is only ever added once.
+*/
+#[derive(Debug, Clone, Copy)]
+struct RawModeExitHandler;
+
+pub fn stdio_raw_mode(lua: &Lua, enabled: bool) -> LuaResult<()> {
+    if !enabled {
+        return terminal::disable_raw_mode()
+            .map_err(|e| LuaError::RuntimeError(format!("Failed to disable raw mode\n> {e}")));
+    }
+
+    terminal::enable_raw_mode()
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to enable raw mode\n> {e}")))?;
+
+    // NOTE: Leaving the terminal in raw mode after exiting would make it
+    // unusable for the user, so we always make sure to disable it again
+    if lua.app_data_ref::<RawModeExitHandler>().is_none() {
+        lua.set_app_data(RawModeExitHandler);
+        let handler = lua.create_function(|_, _: LuaValue| {
+            terminal::disable_raw_mode().ok();
+            Ok(())
+        })?;
+        let sched = lua
+            .app_data_ref::<&Scheduler>()
+            .expect("Lua struct is missing scheduler");
+        sched.add_exit_handler(lua, handler)?;
+    }
+
+    Ok(())
+}
+
+pub fn stdio_terminal_size(_: &Lua, _: ()) -> LuaResult<(Option<u16>, Option<u16>)> {
+    match terminal::size() {
+        Ok((columns, rows)) => Ok((Some(columns), Some(rows))),
+        Err(_) => Ok((None, None)),
+    }
+}
+
+pub fn stdio_cursor_to(_: &Lua, (x, y): (u16, Option<u16>)) -> LuaResult<()> {
+    if x == 0 || y == Some(0) {
+        return Err(LuaError::RuntimeError(
+            "Invalid cursor position - columns and rows start at 1".to_string(),
+        ));
+    }
+    // NOTE: Positions are one-based in Lua, but zero-based in crossterm
+    let result = match y {
+        Some(y) => execute!(io::stdout(), MoveTo(x - 1, y - 1)),
+        None => execute!(io::stdout(), MoveToColumn(x - 1)),
+    };
+    result.map_err(|e| LuaError::RuntimeError(format!("Failed to move cursor\n> {e}")))
+}
+
+pub fn stdio_clear(_: &Lua, mode: Option<String>) -> LuaResult<()> {
+    let clear_type = match mode.as_deref() {
+        None | Some("screen") => ClearType::All,
+        Some("line") => ClearType::CurrentLine,
+        Some("below") => ClearType::FromCursorDown,
+        Some("above") => ClearType::FromCursorUp,
+        Some("line-end") => ClearType::UntilNewLine,
+        Some(mode) => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid clear mode '{mode}' - expected one of \
+                'screen', 'line', 'below', 'above' or 'line-end'"
+            )))
+        }
+    };
+    execute!(io::stdout(), Clear(clear_type))
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to clear terminal\n> {e}")))
+}
+
+pub fn stdio_is_tty(_: &Lua, stream: String) -> LuaResult<bool> {
+    Ok(match stream.as_str() {
+        "stdin" => io::stdin().is_terminal(),
+        "stdout" => io::stdout().is_terminal(),
+        "stderr" => io::stderr().is_terminal(),
+        _ => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid stream '{stream}' - expected one of 'stdin', 'stdout' or 'stderr'"
+            )))
+        }
+    })
+}
+
+pub async fn stdio_read_key(lua: &Lua, _: ()) -> LuaResult<LuaTable> {
+    /*
+        Reading key presses blocks, so we read them on a separate thread, and
+        check regularly if we are still waiting for the key press, since the
+        Lua thread waiting for it may have been cancelled, and the blocking
+        thread would otherwise keep the runtime from exiting
+    */
+    let (tx, rx) = oneshot::channel();
+    task::spawn_blocking(move || {
+        while !tx.is_closed() {
+            match event::poll(KEY_POLL_INTERVAL) {
+                Ok(false) => {}
+                Ok(true) => match event::read() {
+                    Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => {
+                        tx.send(Ok(key)).ok();
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        tx.send(Err(e)).ok();
+                        break;
+                    }
+                },
+                Err(e) => {
+                    tx.send(Err(e)).ok();
+                    break;
+                }
+            }
+        }
+    });
+
+    let key = rx
+        .await
+        .into_lua_err()?
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to read key\n> {e}")))?;
+    key_to_table(lua, key)
+}
+
+fn key_to_table(lua: &Lua, key: KeyEvent) -> LuaResult<LuaTable> {
+    let (name, char) = match key.code {
+        KeyCode::Char(c) => (c.to_string(), Some(c.to_string())),
+        KeyCode::F(n) => (format!("f{n}"), None),
+        code => (key_code_name(code).to_string(), None),
+    };
+    TableBuilder::new(lua)?
+        .with_value("key", name)?
+        .with_value("char", char)?
+        .with_value("ctrl", key.modifiers.contains(KeyModifiers::CONTROL))?
+        .with_value("alt", key.modifiers.contains(KeyModifiers::ALT))?
+        .with_value("shift", key.modifiers.contains(KeyModifiers::SHIFT))?
+        .build_readonly()
+}
+
+fn key_code_name(code: KeyCode) -> &'static str {
+    match code {
+        KeyCode::Enter => "enter",
+        KeyCode::Backspace => "backspace",
+        KeyCode::Tab => "tab",
+        KeyCode::BackTab => "backtab",
+        KeyCode::Esc => "escape",
+        KeyCode::Delete => "delete",
+        KeyCode::Insert => "insert",
+        KeyCode::Up => "up",
+        KeyCode::Down => "down",
+        KeyCode::Left => "left",
+        KeyCode::Right => "right",
+        KeyCode::Home => "home",
+        KeyCode::End => "end",
+        KeyCode::PageUp => "pageup",
+        KeyCode::PageDown => "pagedown",
+        _ => "unknown",
+    }
+}
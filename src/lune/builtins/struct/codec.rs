@@ -0,0 +1,319 @@
+use mlua::prelude::*;
+
+use super::layout::{Endian, Field, FieldKind, Layout, Length, Scalar};
+
+// Packing
+
+pub fn pack_layout(
+    layout: &Layout,
+    value: &LuaTable,
+    path: &str,
+    out: &mut Vec<u8>,
+) -> LuaResult<()> {
+    for field in &layout.fields {
+        let field_path = join_path(path, &field.name);
+        let value = value.raw_get::<_, LuaValue>(field.name.as_str())?;
+        match field.count {
+            None => pack_value(field, value, &field_path, out)?,
+            Some(count) => {
+                let values = match value {
+                    LuaValue::Table(t) => t,
+                    value => return Err(expected(&field_path, "table", &value)),
+                };
+                let len = values.raw_len();
+                match count {
+                    Length::Fixed(expected_len) if len != expected_len => {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Failed to pack field '{field_path}' - \
+                            expected {expected_len} values, got {len}"
+                        )))
+                    }
+                    Length::Prefixed(prefix) => {
+                        pack_uint(prefix, len as u64, field.endian, &field_path, out)?
+                    }
+                    _ => {}
+                }
+                for (index, value) in values.sequence_values::<LuaValue>().enumerate() {
+                    let index_path = format!("{field_path}[{}]", index + 1);
+                    pack_value(field, value?, &index_path, out)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn pack_value(field: &Field, value: LuaValue, path: &str, out: &mut Vec<u8>) -> LuaResult<()> {
+    match &field.kind {
+        FieldKind::Scalar(scalar) => pack_scalar(*scalar, value, field.endian, path, out),
+        FieldKind::String(length) => {
+            let s = match &value {
+                LuaValue::String(s) => s.as_bytes(),
+                _ => return Err(expected(path, "string", &value)),
+            };
+            match *length {
+                Length::Fixed(len) => {
+                    if s.len() > len {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Failed to pack field '{path}' - \
+                            expected at most {len} bytes, got {}",
+                            s.len()
+                        )));
+                    }
+                    out.extend_from_slice(s);
+                    out.resize(out.len() + len - s.len(), 0);
+                }
+                Length::Prefixed(prefix) => {
+                    pack_uint(prefix, s.len() as u64, field.endian, path, out)?;
+                    out.extend_from_slice(s);
+                }
+                Length::ZeroTerminated => {
+                    if s.contains(&0) {
+                        return Err(LuaError::RuntimeError(format!(
+                            "Failed to pack field '{path}' - string contains zeros"
+                        )));
+                    }
+                    out.extend_from_slice(s);
+                    out.push(0);
+                }
+            }
+            Ok(())
+        }
+        FieldKind::Struct(layout) => match value {
+            LuaValue::Table(t) => pack_layout(layout, &t, path, out),
+            value => Err(expected(path, "table", &value)),
+        },
+    }
+}
+
+fn pack_scalar(
+    scalar: Scalar,
+    value: LuaValue,
+    endian: Endian,
+    path: &str,
+    out: &mut Vec<u8>,
+) -> LuaResult<()> {
+    if scalar == Scalar::Bool {
+        return match value {
+            LuaValue::Boolean(b) => {
+                out.push(u8::from(b));
+                Ok(())
+            }
+            value => Err(expected(path, "boolean", &value)),
+        };
+    }
+
+    let n = match value {
+        LuaValue::Integer(i) => i as f64,
+        LuaValue::Number(n) => n,
+        value => return Err(expected(path, "number", &value)),
+    };
+
+    macro_rules! write_num {
+        ($value:expr) => {{
+            let value = $value;
+            out.extend_from_slice(&match endian {
+                Endian::Little => value.to_le_bytes(),
+                Endian::Big => value.to_be_bytes(),
+            });
+        }};
+    }
+
+    macro_rules! write_int {
+        ($ty:ty) => {{
+            if n.fract() != 0.0 || n < <$ty>::MIN as f64 || n > <$ty>::MAX as f64 {
+                return Err(LuaError::RuntimeError(format!(
+                    "Failed to pack field '{path}' - {n} does not fit in {}",
+                    stringify!($ty)
+                )));
+            }
+            write_num!(n as $ty)
+        }};
+    }
+
+    match scalar {
+        Scalar::U8 => write_int!(u8),
+        Scalar::I8 => write_int!(i8),
+        Scalar::U16 => write_int!(u16),
+        Scalar::I16 => write_int!(i16),
+        Scalar::U32 => write_int!(u32),
+        Scalar::I32 => write_int!(i32),
+        Scalar::U64 => write_int!(u64),
+        Scalar::I64 => write_int!(i64),
+        Scalar::F32 => write_num!(n as f32),
+        Scalar::F64 => write_num!(n),
+        Scalar::Bool => unreachable!(),
+    }
+    Ok(())
+}
+
+fn pack_uint(
+    prefix: Scalar,
+    n: u64,
+    endian: Endian,
+    path: &str,
+    out: &mut Vec<u8>,
+) -> LuaResult<()> {
+    pack_scalar(prefix, LuaValue::Number(n as f64), endian, path, out)
+}
+
+// Unpacking
+
+/**
+    Reads bytes from the data that is being unpacked, keeping track of the position.
+*/
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pub position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8], position: usize) -> Self {
+        Self { data, position }
+    }
+
+    fn take(&mut self, len: usize, path: &str) -> LuaResult<&'a [u8]> {
+        let remaining = self.data.len().saturating_sub(self.position);
+        if remaining < len {
+            return Err(LuaError::RuntimeError(format!(
+                "Failed to unpack field '{path}' - expected {len} bytes \
+                at offset {}, but only {remaining} are left",
+                self.position + 1
+            )));
+        }
+        let bytes = &self.data[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn take_until_zero(&mut self, path: &str) -> LuaResult<&'a [u8]> {
+        let rest = self.data.get(self.position..).unwrap_or_default();
+        match rest.iter().position(|b| *b == 0) {
+            Some(len) => {
+                self.position += len + 1;
+                Ok(&rest[..len])
+            }
+            None => Err(LuaError::RuntimeError(format!(
+                "Failed to unpack field '{path}' - missing zero at the end of the string"
+            ))),
+        }
+    }
+}
+
+pub fn unpack_layout<'lua>(
+    lua: &'lua Lua,
+    layout: &Layout,
+    reader: &mut Reader,
+    path: &str,
+) -> LuaResult<LuaTable<'lua>> {
+    let table = lua.create_table_with_capacity(0, layout.fields.len())?;
+    for field in &layout.fields {
+        let field_path = join_path(path, &field.name);
+        let value = match field.count {
+            None => unpack_value(lua, field, reader, &field_path)?,
+            Some(count) => {
+                let count = match count {
+                    Length::Fixed(count) => count,
+                    Length::Prefixed(prefix) => {
+                        unpack_uint(prefix, field.endian, reader, &field_path)?
+                    }
+                    Length::ZeroTerminated => unreachable!(),
+                };
+                let values = lua.create_table()?;
+                for index in 0..count {
+                    let index_path = format!("{field_path}[{}]", index + 1);
+                    values.raw_push(unpack_value(lua, field, reader, &index_path)?)?;
+                }
+                LuaValue::Table(values)
+            }
+        };
+        table.raw_set(field.name.as_str(), value)?;
+    }
+    Ok(table)
+}
+
+fn unpack_value<'lua>(
+    lua: &'lua Lua,
+    field: &Field,
+    reader: &mut Reader,
+    path: &str,
+) -> LuaResult<LuaValue<'lua>> {
+    Ok(match &field.kind {
+        FieldKind::Scalar(scalar) => unpack_scalar(*scalar, field.endian, reader, path)?,
+        FieldKind::String(length) => {
+            let bytes = match *length {
+                Length::Fixed(len) => reader.take(len, path)?,
+                Length::Prefixed(prefix) => {
+                    let len = unpack_uint(prefix, field.endian, reader, path)?;
+                    reader.take(len, path)?
+                }
+                Length::ZeroTerminated => reader.take_until_zero(path)?,
+            };
+            LuaValue::String(lua.create_string(bytes)?)
+        }
+        FieldKind::Struct(layout) => LuaValue::Table(unpack_layout(lua, layout, reader, path)?),
+    })
+}
+
+fn unpack_scalar<'lua>(
+    scalar: Scalar,
+    endian: Endian,
+    reader: &mut Reader,
+    path: &str,
+) -> LuaResult<LuaValue<'lua>> {
+    let bytes = reader.take(scalar.size(), path)?;
+
+    macro_rules! read_num {
+        ($ty:ty) => {{
+            let bytes = bytes.try_into().expect("Invalid scalar size");
+            let value = match endian {
+                Endian::Little => <$ty>::from_le_bytes(bytes),
+                Endian::Big => <$ty>::from_be_bytes(bytes),
+            };
+            LuaValue::Number(value as f64)
+        }};
+    }
+
+    Ok(match scalar {
+        Scalar::U8 => read_num!(u8),
+        Scalar::I8 => read_num!(i8),
+        Scalar::U16 => read_num!(u16),
+        Scalar::I16 => read_num!(i16),
+        Scalar::U32 => read_num!(u32),
+        Scalar::I32 => read_num!(i32),
+        Scalar::U64 => read_num!(u64),
+        Scalar::I64 => read_num!(i64),
+        Scalar::F32 => read_num!(f32),
+        Scalar::F64 => read_num!(f64),
+        Scalar::Bool => LuaValue::Boolean(bytes[0] != 0),
+    })
+}
+
+fn unpack_uint(
+    prefix: Scalar,
+    endian: Endian,
+    reader: &mut Reader,
+    path: &str,
+) -> LuaResult<usize> {
+    match unpack_scalar(prefix, endian, reader, path)? {
+        LuaValue::Number(n) => Ok(n as usize),
+        _ => unreachable!(),
+    }
+}
+
+// Utilities
+
+fn join_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+fn expected(path: &str, kind: &str, value: &LuaValue) -> LuaError {
+    LuaError::RuntimeError(format!(
+        "Failed to pack field '{path}' - expected {kind}, got {}",
+        value.type_name()
+    ))
+}
@@ -0,0 +1,271 @@
+use std::rc::Rc;
+
+use mlua::prelude::*;
+
+/**
+    The byte order of a numeric field.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+impl<'lua> FromLua<'lua> for Endian {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::String(s) => match s.to_str()? {
+                "little" => Ok(Self::Little),
+                "big" => Ok(Self::Big),
+                other => Err(LuaError::RuntimeError(format!(
+                    "Invalid endianness '{other}' - expected 'little' or 'big'"
+                ))),
+            },
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Endian",
+                message: Some("Expected 'little' or 'big'".to_string()),
+            }),
+        }
+    }
+}
+
+/**
+    A numeric or boolean value with a fixed size.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scalar {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+    Bool,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "u8" => Self::U8,
+            "i8" => Self::I8,
+            "u16" => Self::U16,
+            "i16" => Self::I16,
+            "u32" => Self::U32,
+            "i32" => Self::I32,
+            "u64" => Self::U64,
+            "i64" => Self::I64,
+            "f32" => Self::F32,
+            "f64" => Self::F64,
+            "bool" => Self::Bool,
+            _ => return None,
+        })
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            Self::U8 | Self::I8 | Self::Bool => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 | Self::F32 => 4,
+            Self::U64 | Self::I64 | Self::F64 => 8,
+        }
+    }
+
+    /**
+        Parses the type of a length or count prefix, which must be an unsigned integer.
+    */
+    fn prefix_from_name(name: &str) -> Option<Self> {
+        match Self::from_name(name)? {
+            s @ (Self::U8 | Self::U16 | Self::U32 | Self::U64) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/**
+    How the length of a string field, or the number of values in an array field, is stored.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Length {
+    /// Always the same length, which is not stored.
+    Fixed(usize),
+    /// Stored as an unsigned integer right before the contents.
+    Prefixed(Scalar),
+    /// Not stored, the contents end with a zero byte instead.
+    ZeroTerminated,
+}
+
+impl Length {
+    fn from_lua(value: LuaValue, field: &str, key: &str, allow_zero: bool) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Integer(i) if *i >= 0 => return Ok(Self::Fixed(*i as usize)),
+            LuaValue::Number(n) if *n >= 0.0 && n.fract() == 0.0 => {
+                return Ok(Self::Fixed(*n as usize))
+            }
+            LuaValue::String(s) => {
+                let s = s.to_str()?;
+                if allow_zero && s == "zero" {
+                    return Ok(Self::ZeroTerminated);
+                }
+                if let Some(prefix) = Scalar::prefix_from_name(s) {
+                    return Ok(Self::Prefixed(prefix));
+                }
+            }
+            _ => {}
+        }
+        Err(LuaError::RuntimeError(format!(
+            "Invalid '{key}' for field '{field}' - expected a non-negative integer, {}",
+            if allow_zero {
+                "'u8', 'u16', 'u32', 'u64' or 'zero'"
+            } else {
+                "'u8', 'u16', 'u32' or 'u64'"
+            }
+        )))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FieldKind {
+    Scalar(Scalar),
+    String(Length),
+    Struct(Rc<Layout>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub name: String,
+    pub kind: FieldKind,
+    pub count: Option<Length>,
+    pub endian: Endian,
+}
+
+/**
+    A layout of a binary record, made up of fields in a fixed order.
+*/
+#[derive(Debug, Clone)]
+pub struct Layout {
+    pub fields: Vec<Field>,
+}
+
+impl Layout {
+    /**
+        Creates a layout from a list of field definitions, using the given
+        endianness for any numeric fields that do not specify their own.
+    */
+    pub fn from_lua_fields(fields: LuaTable, endian: Endian) -> LuaResult<Self> {
+        let mut parsed = Vec::new();
+        for (index, field) in fields.sequence_values::<LuaValue>().enumerate() {
+            let field = match field? {
+                LuaValue::Table(t) => t,
+                value => {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Invalid field #{} - expected table, got {}",
+                        index + 1,
+                        value.type_name()
+                    )))
+                }
+            };
+            parsed.push(parse_field(field, index + 1, endian)?);
+        }
+        if parsed.is_empty() {
+            return Err(LuaError::RuntimeError(
+                "Invalid layout - expected at least one field".to_string(),
+            ));
+        }
+        Ok(Self { fields: parsed })
+    }
+
+    /**
+        Gets the size of this layout in bytes, if it is
+        always the same, meaning it has no variable length fields.
+    */
+    pub fn fixed_size(&self) -> Option<usize> {
+        let mut total = 0;
+        for field in &self.fields {
+            let size = match &field.kind {
+                FieldKind::Scalar(s) => s.size(),
+                FieldKind::String(Length::Fixed(len)) => *len,
+                FieldKind::String(_) => return None,
+                FieldKind::Struct(layout) => layout.fixed_size()?,
+            };
+            total += match field.count {
+                None => size,
+                Some(Length::Fixed(count)) => size * count,
+                Some(_) => return None,
+            };
+        }
+        Some(total)
+    }
+}
+
+fn parse_field(field: LuaTable, index: usize, endian: Endian) -> LuaResult<Field> {
+    let name = match field.raw_get::<_, Option<String>>("name") {
+        Ok(Some(name)) => name,
+        _ => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid field #{index} - missing or invalid 'name'"
+            )))
+        }
+    };
+
+    let kind = match field.raw_get::<_, LuaValue>("type")? {
+        LuaValue::String(s) if s.to_str()? == "string" => FieldKind::String(Length::from_lua(
+            field.raw_get("length")?,
+            &name,
+            "length",
+            true,
+        )?),
+        LuaValue::String(s) => match Scalar::from_name(s.to_str()?) {
+            Some(scalar) => FieldKind::Scalar(scalar),
+            None => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid type '{}' for field '{name}'",
+                    s.to_string_lossy()
+                )))
+            }
+        },
+        LuaValue::UserData(ud) => match ud.borrow::<StructLayout>() {
+            Ok(layout) => FieldKind::Struct(Rc::clone(&layout.0)),
+            Err(_) => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid type for field '{name}' - expected a type name or a layout"
+                )))
+            }
+        },
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Invalid type for field '{name}' - expected a type name or a layout, got {}",
+                value.type_name()
+            )))
+        }
+    };
+
+    let count = match field.raw_get::<_, LuaValue>("count")? {
+        LuaValue::Nil => None,
+        value => Some(Length::from_lua(value, &name, "count", false)?),
+    };
+
+    let endian = match field.raw_get::<_, Option<Endian>>("endian")? {
+        Some(endian) => endian,
+        None => endian,
+    };
+
+    Ok(Field {
+        name,
+        kind,
+        count,
+        endian,
+    })
+}
+
+/**
+    A layout created using `struct.define`, which may be used
+    to pack and unpack records, or as the type of another field.
+*/
+#[derive(Debug, Clone)]
+pub struct StructLayout(pub Rc<Layout>);
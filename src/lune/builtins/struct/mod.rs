@@ -0,0 +1,65 @@
+use std::rc::Rc;
+
+use mlua::prelude::*;
+
+use crate::lune::util::TableBuilder;
+
+mod codec;
+mod layout;
+
+use codec::{pack_layout, unpack_layout, Reader};
+use layout::{Endian, Layout, StructLayout};
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("define", struct_define)?
+        .build_readonly()
+}
+
+fn struct_define(
+    _: &Lua,
+    (fields, options): (LuaTable, Option<LuaTable>),
+) -> LuaResult<StructLayout> {
+    let endian = match &options {
+        Some(options) => options.raw_get::<_, Option<Endian>>("endian")?,
+        None => None,
+    };
+    let layout = Layout::from_lua_fields(fields, endian.unwrap_or_default())?;
+    Ok(StructLayout(Rc::new(layout)))
+}
+
+impl LuaUserData for StructLayout {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("pack", |lua, this, value: LuaTable| {
+            let mut out = Vec::with_capacity(this.0.fixed_size().unwrap_or_default());
+            pack_layout(&this.0, &value, "", &mut out)?;
+            lua.create_string(out)
+        });
+        methods.add_method(
+            "unpack",
+            |lua, this, (data, offset): (LuaString, Option<usize>)| {
+                // NOTE: Offsets are one-based, same as for string.unpack
+                let offset = offset.unwrap_or(1);
+                if offset == 0 || offset > data.as_bytes().len() + 1 {
+                    return Err(LuaError::RuntimeError(format!(
+                        "Invalid offset {offset} - expected a number between 1 and {}",
+                        data.as_bytes().len() + 1
+                    )));
+                }
+                let mut reader = Reader::new(data.as_bytes(), offset - 1);
+                let value = unpack_layout(lua, &this.0, &mut reader, "")?;
+                Ok((value, reader.position + 1))
+            },
+        );
+        methods.add_method("size", |_, this, ()| Ok(this.0.fixed_size()));
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            let names = this
+                .0
+                .fields
+                .iter()
+                .map(|field| field.name.as_str())
+                .collect::<Vec<_>>();
+            Ok(format!("StructLayout({})", names.join(", ")))
+        });
+    }
+}
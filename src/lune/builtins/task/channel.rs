@@ -0,0 +1,118 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+};
+
+use mlua::prelude::*;
+use tokio::sync::Notify;
+
+use crate::lune::util::TableBuilder;
+
+/**
+    Shared state for a channel, containing any values that
+    have been sent but not yet received, stored in the registry.
+*/
+#[derive(Debug)]
+struct TaskChannelState {
+    values: RefCell<VecDeque<LuaRegistryKey>>,
+    capacity: Option<usize>,
+    closed: Cell<bool>,
+    sent: Notify,
+    received: Notify,
+}
+
+impl TaskChannelState {
+    fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    fn is_full(&self) -> bool {
+        match self.capacity {
+            Some(capacity) => self.values.borrow().len() >= capacity,
+            None => false,
+        }
+    }
+}
+
+/**
+    Creates a new channel, which is a table with methods to `send`
+    values, `receive` them in the same order, and `close` the channel.
+
+    If a capacity is given, senders will yield while the channel is full.
+*/
+pub(super) fn create_task_channel(
+    lua: &'static Lua,
+    capacity: Option<usize>,
+) -> LuaResult<LuaTable<'static>> {
+    if capacity == Some(0) {
+        return Err(LuaError::RuntimeError(
+            "Channel capacity must be at least 1".to_string(),
+        ));
+    }
+
+    let state = Rc::new(TaskChannelState {
+        values: RefCell::new(VecDeque::new()),
+        capacity,
+        closed: Cell::new(false),
+        sent: Notify::new(),
+        received: Notify::new(),
+    });
+    let state_send = Rc::clone(&state);
+    let state_receive = Rc::clone(&state);
+    let state_close = Rc::clone(&state);
+
+    TableBuilder::new(lua)?
+        .with_async_function("send", move |lua, (_, value): (LuaValue, LuaValue)| {
+            let state = Rc::clone(&state_send);
+            async move {
+                if value.is_nil() {
+                    return Err(LuaError::RuntimeError(
+                        "Can not send nil through a channel".to_string(),
+                    ));
+                }
+                let key = lua.create_registry_value(value)?;
+                loop {
+                    if state.is_closed() {
+                        lua.remove_registry_value(key)?;
+                        return Err(LuaError::RuntimeError(
+                            "Can not send through a channel that has been closed".to_string(),
+                        ));
+                    }
+                    if !state.is_full() {
+                        state.values.borrow_mut().push_back(key);
+                        state.sent.notify_one();
+                        return Ok(());
+                    }
+                    state.received.notified().await;
+                }
+            }
+        })?
+        .with_async_function("receive", move |lua, _: LuaValue| {
+            let state = Rc::clone(&state_receive);
+            async move {
+                loop {
+                    let key = state.values.borrow_mut().pop_front();
+                    if let Some(key) = key {
+                        state.received.notify_one();
+                        let value = lua.registry_value::<LuaValue>(&key)?;
+                        lua.remove_registry_value(key)?;
+                        return Ok(value);
+                    }
+                    // NOTE: Values sent before the channel was
+                    // closed may still be received, as above
+                    if state.is_closed() {
+                        return Ok(LuaValue::Nil);
+                    }
+                    state.sent.notified().await;
+                }
+            }
+        })?
+        .with_function("close", move |_, _: LuaValue| {
+            state_close.closed.set(true);
+            state_close.sent.notify_waiters();
+            state_close.received.notify_waiters();
+            Ok(())
+        })?
+        .build_readonly()
+}
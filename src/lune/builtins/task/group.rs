@@ -0,0 +1,237 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use mlua::prelude::*;
+use tokio::sync::watch;
+
+use crate::lune::{
+    scheduler::{Scheduler, SchedulerThreadId},
+    util::TableBuilder,
+};
+
+use super::{options::TaskSpawnOptions, tof::LuaThreadOrFunction};
+
+/*
+    Spawning a thread in a group works the same as `task.spawn`,
+    the spawned thread runs right away until it first yields
+
+    1. Schedule this current thread and then the spawned thread
+       at the front, making the spawned thread resume first
+    2. Give control over to the scheduler, which will
+       resume the above tasks in order when its ready
+*/
+const GROUP_SPAWN_IMPL_LUA: &str = r#"
+local thread = spawn(...)
+yield()
+return thread
+"#;
+
+/**
+    Shared state for a task group, tracking all of
+    its children that have not yet finished running.
+*/
+#[derive(Debug, Default)]
+struct TaskGroupState {
+    children: HashMap<SchedulerThreadId, LuaRegistryKey>,
+    errors: Vec<LuaError>,
+    cancelled: bool,
+}
+
+/**
+    Creates a new task group, which is a table with methods to
+    `spawn` child threads, `wait` for them all to finish, and `cancel` them.
+*/
+pub(super) fn create_task_group(lua: &'static Lua) -> LuaResult<LuaTable<'static>> {
+    let state = Rc::new(RefCell::new(TaskGroupState::default()));
+    let (pending_tx, pending_rx) = watch::channel(0_usize);
+    let pending_tx = Rc::new(pending_tx);
+
+    let coroutine_yield = lua
+        .globals()
+        .get::<_, LuaTable>("coroutine")?
+        .get::<_, LuaFunction>("yield")?;
+
+    let state_spawn = Rc::clone(&state);
+    let pending_tx_spawn = Rc::clone(&pending_tx);
+    let spawn_env = TableBuilder::new(lua)?
+        .with_value("yield", coroutine_yield.clone())?
+        .with_function(
+            "spawn",
+            move |lua, (_, tof, args): (LuaValue, LuaThreadOrFunction, LuaMultiValue)| {
+                group_spawn(lua, &state_spawn, &pending_tx_spawn, None, tof, args)
+            },
+        )?
+        .build_readonly()?;
+    let group_spawn_fn = lua
+        .load(GROUP_SPAWN_IMPL_LUA)
+        .set_name("group.spawn")
+        .set_environment(spawn_env)
+        .into_function()?;
+
+    let state_spawn_with = Rc::clone(&state);
+    let spawn_with_env = TableBuilder::new(lua)?
+        .with_value("yield", coroutine_yield)?
+        .with_function(
+            "spawn",
+            move |lua,
+                  (_, options, tof, args): (
+                LuaValue,
+                TaskSpawnOptions,
+                LuaThreadOrFunction,
+                LuaMultiValue,
+            )| {
+                group_spawn(
+                    lua,
+                    &state_spawn_with,
+                    &pending_tx,
+                    Some(options),
+                    tof,
+                    args,
+                )
+            },
+        )?
+        .build_readonly()?;
+    let group_spawn_with_fn = lua
+        .load(GROUP_SPAWN_IMPL_LUA)
+        .set_name("group.spawnWith")
+        .set_environment(spawn_with_env)
+        .into_function()?;
+
+    let state_wait = Rc::clone(&state);
+    TableBuilder::new(lua)?
+        .with_value("spawn", group_spawn_fn)?
+        .with_value("spawnWith", group_spawn_with_fn)?
+        .with_async_function("wait", move |lua, _: LuaValue| {
+            let result = check_not_child(lua, &state_wait);
+            let state = Rc::clone(&state_wait);
+            let mut pending_rx = pending_rx.clone();
+            async move {
+                result?;
+                // NOTE: The sender is owned by the group itself, so it can not
+                // be dropped while we are still waiting on it from inside the group
+                pending_rx
+                    .wait_for(|pending| *pending == 0)
+                    .await
+                    .expect("Task group was dropped while waiting");
+                let errors = state.borrow().errors.clone();
+                match combine_errors(errors) {
+                    Some(e) => Err(e),
+                    None => Ok(()),
+                }
+            }
+        })?
+        .with_function("cancel", move |lua, _: LuaValue| group_cancel(lua, &state))?
+        .build_readonly()
+}
+
+fn group_spawn(
+    lua: &'static Lua,
+    state: &Rc<RefCell<TaskGroupState>>,
+    pending_tx: &Rc<watch::Sender<usize>>,
+    options: Option<TaskSpawnOptions>,
+    tof: LuaThreadOrFunction<'static>,
+    args: LuaMultiValue<'static>,
+) -> LuaResult<LuaThread<'static>> {
+    if state.borrow().cancelled {
+        return Err(LuaError::RuntimeError(
+            "Can not spawn a task in a group that has been cancelled".to_string(),
+        ));
+    }
+
+    let thread = tof.into_thread(lua)?;
+    if let Some(options) = options {
+        options.apply_to(lua, &thread);
+    }
+    let sched = *lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    // NOTE: Errors are instead rethrown when waiting for the group
+    sched.set_thread_errors_handled(SchedulerThreadId::from(&thread), true);
+    sched.push_front(lua, lua.current_thread(), ())?;
+    let thread_id = sched.push_front(lua, thread.clone(), args)?;
+
+    // NOTE: We must subscribe to the result of the child before
+    // it runs, since it may finish running during its first resume
+    let result = sched.subscribe_to_thread(lua, thread_id)?;
+    state
+        .borrow_mut()
+        .children
+        .insert(thread_id, lua.create_registry_value(thread.clone())?);
+    pending_tx.send_modify(|pending| *pending += 1);
+
+    let state = Rc::clone(state);
+    let pending_tx = Rc::clone(pending_tx);
+    sched.spawn_local(async move {
+        let result = result.await;
+        let mut state = state.borrow_mut();
+        if let Some(key) = state.children.remove(&thread_id) {
+            lua.remove_registry_value(key)
+                .expect("Failed to remove task group child from registry");
+        }
+        // NOTE: Children that were cancelled along with the group
+        // get a cancellation error, which is not a real error
+        if let Err(e) = result {
+            if !state.cancelled {
+                state.errors.push(e);
+            }
+        }
+        pending_tx.send_modify(|pending| *pending -= 1);
+    });
+
+    Ok(thread)
+}
+
+fn group_cancel(lua: &Lua, state: &Rc<RefCell<TaskGroupState>>) -> LuaResult<()> {
+    let current_id = SchedulerThreadId::from(&lua.current_thread());
+    let children = {
+        let mut state = state.borrow_mut();
+        state.cancelled = true;
+        state
+            .children
+            .iter()
+            .filter(|(thread_id, _)| **thread_id != current_id)
+            .map(|(_, key)| lua.registry_value::<LuaThread>(key))
+            .collect::<LuaResult<Vec<_>>>()?
+    };
+
+    // NOTE: A child cancelling its own group can not cancel itself while it is
+    // running, it will instead finish normally once it returns or errors
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    for thread in children {
+        sched.cancel_thread(lua, thread)?;
+    }
+
+    Ok(())
+}
+
+fn check_not_child(lua: &Lua, state: &Rc<RefCell<TaskGroupState>>) -> LuaResult<()> {
+    let current_id = SchedulerThreadId::from(&lua.current_thread());
+    if state.borrow().children.contains_key(&current_id) {
+        Err(LuaError::RuntimeError(
+            "Task group can not be waited for from inside one of its own tasks".to_string(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/**
+    Combines the errors of all children that errored into a single error.
+*/
+fn combine_errors(errors: Vec<LuaError>) -> Option<LuaError> {
+    match errors.len() {
+        0 => None,
+        1 => errors.into_iter().next(),
+        count => {
+            let messages = errors
+                .iter()
+                .map(|e| format!("- {}", e.to_string().replace('\n', "\n  ")))
+                .collect::<Vec<_>>()
+                .join("\n");
+            Some(LuaError::RuntimeError(format!(
+                "{count} tasks in the group errored:\n{messages}"
+            )))
+        }
+    }
+}
@@ -4,9 +4,21 @@ use mlua::prelude::*;
 
 use tokio::time::{self, Instant};
 
-use crate::lune::{scheduler::Scheduler, util::TableBuilder};
+use crate::lune::{
+    scheduler::{Scheduler, SchedulerThreadId},
+    util::{traits::LuaEmitErrorExt, TableBuilder},
+};
 
+mod channel;
+mod group;
+mod options;
+mod parallel;
+mod timer;
 mod tof;
+mod transfer;
+
+use options::TaskSpawnOptions;
+use timer::TaskTimer;
 use tof::LuaThreadOrFunction;
 
 /*
@@ -27,6 +39,53 @@ yield()
 return thread
 "#;
 
+/*
+    Spawning with options works the same as the spawn function above,
+    the options are only applied to the spawned thread, not the current one
+*/
+const SPAWN_WITH_IMPL_LUA: &str = r#"
+push(currentThread())
+local thread = pushWith(...)
+yield()
+return thread
+"#;
+
+/*
+    The wait function also needs special treatment
+    when the scheduler is using a virtual clock
+
+    1. Put this current thread to sleep on the virtual clock,
+       which will only be resumed when the clock is advanced
+    2. Give control over to the scheduler, the thread will be
+       resumed with the time waited for once the clock advances
+    3. If there is no virtual clock, wait for real instead
+*/
+const WAIT_IMPL_LUA: &str = r#"
+if sleep(currentThread(), ...) then
+    return yield()
+end
+return wait(...)
+"#;
+
+/*
+    Advancing time on the virtual clock is done in steps, so that
+    threads that wait again after being woken up are also woken
+    up in order, if their new deadline is within the target time
+
+    1. Step the clock to the next deadline, scheduling all
+       threads that were waiting until that deadline
+    2. Schedule this current thread after those threads
+       and let them run until they are done or yield
+    3. Repeat until there are no more deadlines before the target
+*/
+const ADVANCE_TIME_IMPL_LUA: &str = r#"
+local target = targetTime(...)
+while step(target) do
+    defer(currentThread())
+    yield()
+end
+"#;
+
 pub fn create(lua: &'static Lua) -> LuaResult<LuaTable<'_>> {
     let coroutine_running = lua
         .globals()
@@ -38,50 +97,124 @@ pub fn create(lua: &'static Lua) -> LuaResult<LuaTable<'_>> {
         .get::<_, LuaFunction>("yield")?;
     let push_front =
         lua.create_function(|lua, (tof, args): (LuaThreadOrFunction, LuaMultiValue)| {
-            let thread = tof.into_thread(lua)?;
-            let sched = lua
-                .app_data_ref::<&Scheduler>()
-                .expect("Lua struct is missing scheduler");
-            sched.push_front(lua, thread.clone(), args)?;
-            Ok(thread)
+            push_front_thread(lua, None, tof, args)
         })?;
+    let push_front_with = lua.create_function(
+        |lua, (options, tof, args): (TaskSpawnOptions, LuaThreadOrFunction, LuaMultiValue)| {
+            push_front_thread(lua, Some(options), tof, args)
+        },
+    )?;
     let task_spawn_env = TableBuilder::new(lua)?
-        .with_value("currentThread", coroutine_running)?
-        .with_value("yield", coroutine_yield)?
-        .with_value("push", push_front)?
+        .with_value("currentThread", coroutine_running.clone())?
+        .with_value("yield", coroutine_yield.clone())?
+        .with_value("push", push_front.clone())?
         .build_readonly()?;
     let task_spawn = lua
         .load(SPAWN_IMPL_LUA)
         .set_name("task.spawn")
         .set_environment(task_spawn_env)
         .into_function()?;
+    let task_spawn_with_env = TableBuilder::new(lua)?
+        .with_value("currentThread", coroutine_running.clone())?
+        .with_value("yield", coroutine_yield.clone())?
+        .with_value("push", push_front)?
+        .with_value("pushWith", push_front_with)?
+        .build_readonly()?;
+    let task_spawn_with = lua
+        .load(SPAWN_WITH_IMPL_LUA)
+        .set_name("task.spawnWith")
+        .set_environment(task_spawn_with_env)
+        .into_function()?;
+    let task_wait_env = TableBuilder::new(lua)?
+        .with_value("currentThread", coroutine_running.clone())?
+        .with_value("yield", coroutine_yield.clone())?
+        .with_function("sleep", task_wait_virtual)?
+        .with_async_function("wait", task_wait)?
+        .build_readonly()?;
+    let task_wait = lua
+        .load(WAIT_IMPL_LUA)
+        .set_name("task.wait")
+        .set_environment(task_wait_env)
+        .into_function()?;
+    let task_advance_time_env = TableBuilder::new(lua)?
+        .with_value("currentThread", coroutine_running)?
+        .with_value("yield", coroutine_yield)?
+        .with_function("defer", task_defer)?
+        .with_function("targetTime", task_advance_time_target)?
+        .with_function("step", task_advance_time_step)?
+        .build_readonly()?;
+    let task_advance_time = lua
+        .load(ADVANCE_TIME_IMPL_LUA)
+        .set_name("task.advanceTime")
+        .set_environment(task_advance_time_env)
+        .into_function()?;
 
     TableBuilder::new(lua)?
+        .with_value("advanceTime", task_advance_time)?
         .with_function("cancel", task_cancel)?
+        .with_function("channel", |lua, capacity| {
+            channel::create_task_channel(lua, capacity)
+        })?
         .with_function("defer", task_defer)?
+        .with_function("deferWith", task_defer_with)?
         .with_function("delay", task_delay)?
+        .with_function("delayWith", task_delay_with)?
+        .with_function("group", |lua, ()| group::create_task_group(lua))?
+        .with_function("name", task_name)?
+        .with_async_function("parallel", parallel::task_parallel)?
         .with_value("spawn", task_spawn)?
-        .with_async_function("wait", task_wait)?
+        .with_value("spawnWith", task_spawn_with)?
+        .with_function("stats", task_stats)?
+        .with_function("timeout", task_timeout)?
+        .with_value("wait", task_wait)?
         .build_readonly()
 }
 
 fn task_cancel(lua: &Lua, thread: LuaThread) -> LuaResult<()> {
-    let close = lua
-        .globals()
-        .get::<_, LuaTable>("coroutine")?
-        .get::<_, LuaFunction>("close")?;
-    match close.call(thread) {
-        Err(LuaError::CoroutineInactive) => Ok(()),
-        Err(e) => Err(e),
-        Ok(()) => Ok(()),
-    }
+    // NOTE: Cancelling using the scheduler also drops any
+    // future the thread is waiting on, such as a request
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    sched.cancel_thread(lua, thread)
 }
 
-fn task_defer<'lua>(
+/**
+    Creates a thread for the given thread or function, applying any spawn options to it.
+*/
+fn create_thread<'lua>(
     lua: &'lua Lua,
-    (tof, args): (LuaThreadOrFunction<'lua>, LuaMultiValue<'_>),
+    options: Option<TaskSpawnOptions>,
+    tof: LuaThreadOrFunction<'lua>,
 ) -> LuaResult<LuaThread<'lua>> {
     let thread = tof.into_thread(lua)?;
+    if let Some(options) = options {
+        options.apply_to(lua, &thread);
+    }
+    Ok(thread)
+}
+
+fn push_front_thread<'lua>(
+    lua: &'lua Lua,
+    options: Option<TaskSpawnOptions>,
+    tof: LuaThreadOrFunction<'lua>,
+    args: LuaMultiValue<'lua>,
+) -> LuaResult<LuaThread<'lua>> {
+    let thread = create_thread(lua, options, tof)?;
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    sched.push_front(lua, thread.clone(), args)?;
+    Ok(thread)
+}
+
+fn push_back_thread<'lua>(
+    lua: &'lua Lua,
+    options: Option<TaskSpawnOptions>,
+    tof: LuaThreadOrFunction<'lua>,
+    args: LuaMultiValue<'lua>,
+) -> LuaResult<LuaThread<'lua>> {
+    let thread = create_thread(lua, options, tof)?;
     let sched = lua
         .app_data_ref::<&Scheduler>()
         .expect("Lua struct is missing scheduler");
@@ -89,30 +222,202 @@ fn task_defer<'lua>(
     Ok(thread)
 }
 
+fn task_defer<'lua>(
+    lua: &'lua Lua,
+    (tof, args): (LuaThreadOrFunction<'lua>, LuaMultiValue<'lua>),
+) -> LuaResult<LuaThread<'lua>> {
+    push_back_thread(lua, None, tof, args)
+}
+
+fn task_defer_with<'lua>(
+    lua: &'lua Lua,
+    (options, tof, args): (
+        TaskSpawnOptions,
+        LuaThreadOrFunction<'lua>,
+        LuaMultiValue<'lua>,
+    ),
+) -> LuaResult<LuaThread<'lua>> {
+    push_back_thread(lua, Some(options), tof, args)
+}
+
+fn task_delay<'lua>(
+    lua: &'lua Lua,
+    (secs, tof, args): (f64, LuaThreadOrFunction<'lua>, LuaMultiValue<'lua>),
+) -> LuaResult<(LuaThread<'lua>, TaskTimer)>
+where
+    'lua: 'static,
+{
+    delay_thread(lua, None, secs, tof, args)
+}
+
+fn task_delay_with<'lua>(
+    lua: &'lua Lua,
+    (options, secs, tof, args): (
+        TaskSpawnOptions,
+        f64,
+        LuaThreadOrFunction<'lua>,
+        LuaMultiValue<'lua>,
+    ),
+) -> LuaResult<(LuaThread<'lua>, TaskTimer)>
+where
+    'lua: 'static,
+{
+    delay_thread(lua, Some(options), secs, tof, args)
+}
+
 // FIXME: `self` escapes outside of method because we are borrowing `tof` and
 // `args` when we call `schedule_future_thread` in the lua function body below
 // For now we solve this by using the 'static lifetime bound in the impl
-fn task_delay<'lua>(
+fn delay_thread<'lua>(
     lua: &'lua Lua,
-    (secs, tof, args): (f64, LuaThreadOrFunction<'lua>, LuaMultiValue<'lua>),
-) -> LuaResult<LuaThread<'lua>>
+    options: Option<TaskSpawnOptions>,
+    secs: f64,
+    tof: LuaThreadOrFunction<'lua>,
+    args: LuaMultiValue<'lua>,
+) -> LuaResult<(LuaThread<'lua>, TaskTimer)>
 where
     'lua: 'static,
 {
-    let thread = tof.into_thread(lua)?;
-    let sched = lua
+    let thread = create_thread(lua, options, tof)?;
+    let duration = secs_to_duration(secs)?;
+    let sched = *lua
         .app_data_ref::<&Scheduler>()
         .expect("Lua struct is missing scheduler");
 
-    let thread2 = thread.clone();
-    sched.spawn_thread(lua, thread.clone(), async move {
-        let duration = Duration::from_secs_f64(secs);
-        time::sleep(duration).await;
-        sched.push_back(lua, thread2, args)?;
+    // NOTE: Using a virtual clock, the thread will be resumed
+    // when the clock is advanced, not after a real delay
+    if let Some(id) = sched.push_timer(lua, thread.clone(), args.clone(), duration)? {
+        return Ok((thread, TaskTimer::new_virtual(id)));
+    }
+
+    let (timer, sleep) = TaskTimer::new_real();
+    let thread_key = lua.create_registry_value(thread.clone())?;
+    let args_key = lua.create_registry_value(args.into_vec())?;
+    sched.spawn_local(async move {
+        let fired = sleep.sleep(duration).await;
+        let thread = lua
+            .registry_value::<LuaThread>(&thread_key)
+            .expect("Failed to get delayed thread from registry");
+        let args = lua
+            .registry_value::<Vec<LuaValue>>(&args_key)
+            .expect("Failed to get delayed thread args from registry");
+        lua.remove_registry_value(thread_key)
+            .expect("Failed to remove delayed thread from registry");
+        lua.remove_registry_value(args_key)
+            .expect("Failed to remove delayed thread args from registry");
+        if fired {
+            if let Err(e) = sched.push_back(lua, thread, LuaMultiValue::from_vec(args)) {
+                lua.emit_error(e);
+            }
+        }
+    });
+
+    Ok((thread, timer))
+}
+
+fn task_timeout<'lua>(
+    lua: &'lua Lua,
+    (secs, thread): (f64, LuaThread<'lua>),
+) -> LuaResult<TaskTimer>
+where
+    'lua: 'static,
+{
+    let duration = secs_to_duration(secs)?;
+    let sched = *lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+
+    // NOTE: Using a virtual clock, we put a separate thread to sleep that
+    // cancels the given thread once woken, unless it has already finished
+    let cancel_thread = lua.create_function(|lua, thread: LuaThread| {
+        if thread.status() == LuaThreadStatus::Resumable {
+            task_cancel(lua, thread)?;
+        }
         Ok(())
     })?;
+    if let Some(id) = sched.push_timer(lua, cancel_thread.clone(), thread.clone(), duration)? {
+        return Ok(TaskTimer::new_virtual(id));
+    }
 
-    Ok(thread)
+    // NOTE: A thread that is not tracked by the scheduler has either already
+    // finished, or may be resumed manually by the user, so for those we can
+    // only check if the thread finished once the full duration has passed
+    let finished = sched.thread_finished(SchedulerThreadId::from(&thread));
+    let (timer, sleep) = TaskTimer::new_real();
+    let thread_key = lua.create_registry_value(thread)?;
+    sched.spawn_local(async move {
+        let fired = match finished {
+            None => sleep.sleep(duration).await,
+            Some(finished) => tokio::select! {
+                fired = sleep.sleep(duration) => fired,
+                _ = finished => false,
+            },
+        };
+        let thread = lua
+            .registry_value::<LuaThread>(&thread_key)
+            .expect("Failed to get timed out thread from registry");
+        lua.remove_registry_value(thread_key)
+            .expect("Failed to remove timed out thread from registry");
+        if fired {
+            if let Err(e) = cancel_thread.call::<_, ()>(thread) {
+                lua.emit_error(e);
+            }
+        }
+    });
+
+    Ok(timer)
+}
+
+fn task_name<'lua>(
+    lua: &'lua Lua,
+    (target, name): (LuaValue<'lua>, Option<String>),
+) -> LuaResult<Option<String>> {
+    // NOTE: Passing only a string as the first argument
+    // is a shorthand for naming the current thread
+    let (thread, name) = match target {
+        LuaValue::Nil => (lua.current_thread(), name),
+        LuaValue::Thread(thread) => (thread, name),
+        LuaValue::String(s) => (lua.current_thread(), Some(s.to_str()?.to_string())),
+        value => {
+            return Err(LuaError::RuntimeError(format!(
+                "Expected thread or string, got {}",
+                value.type_name()
+            )))
+        }
+    };
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    let thread_id = SchedulerThreadId::from(&thread);
+    if let Some(name) = name {
+        sched.set_thread_name(thread_id, name);
+    }
+    Ok(sched.get_thread_name(thread_id))
+}
+
+fn task_stats(lua: &Lua, _: ()) -> LuaResult<LuaTable> {
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    let stats = sched.stats()?;
+    let profile = stats
+        .profile
+        .into_iter()
+        .map(|entry| {
+            TableBuilder::new(lua)?
+                .with_value("name", entry.name)?
+                .with_value("resumptions", entry.resumptions)?
+                .with_value("time", entry.time.as_secs_f64())?
+                .build_readonly()
+        })
+        .collect::<LuaResult<Vec<_>>>()?;
+    TableBuilder::new(lua)?
+        .with_value("resumptions", stats.resumptions)?
+        .with_value("errors", stats.errors)?
+        .with_value("pendingThreads", stats.pending_threads)?
+        .with_value("pendingThreadNames", stats.pending_thread_names)?
+        .with_value("profile", profile)?
+        .build_readonly()
 }
 
 async fn task_wait(_: &Lua, secs: Option<f64>) -> LuaResult<f64> {
@@ -124,3 +429,49 @@ async fn task_wait(_: &Lua, secs: Option<f64>) -> LuaResult<f64> {
 
     Ok((after - before).as_secs_f64())
 }
+
+fn task_wait_virtual(lua: &Lua, (thread, secs): (LuaThread, Option<f64>)) -> LuaResult<bool> {
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    let secs = secs.unwrap_or_default();
+    let duration = secs_to_duration(secs)?;
+    // NOTE: Waiting for no time at all does not need the
+    // clock to advance, same as deferring the thread
+    if duration.is_zero() && sched.is_using_virtual_clock() {
+        sched.push_back(lua, thread, 0.0)?;
+        return Ok(true);
+    }
+    sched.push_after(lua, thread, duration.as_secs_f64(), duration)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn task_advance_time_target(lua: &Lua, secs: f64) -> LuaResult<f64> {
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    match sched.virtual_clock_now() {
+        // NOTE: The target is passed back to us in nanoseconds, which
+        // unlike fractional seconds will always convert back exactly
+        Some(now) => Ok((now + secs_to_duration(secs)?).as_nanos() as f64),
+        None => Err(LuaError::runtime(
+            "task.advanceTime can only be used in deterministic mode",
+        )),
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn task_advance_time_step(lua: &Lua, target: f64) -> LuaResult<bool> {
+    let sched = lua
+        .app_data_ref::<&Scheduler>()
+        .expect("Lua struct is missing scheduler");
+    sched.step_clock(lua, Duration::from_nanos(target as u64))
+}
+
+fn secs_to_duration(secs: f64) -> LuaResult<Duration> {
+    Duration::try_from_secs_f64(secs).map_err(|_| {
+        LuaError::RuntimeError(format!(
+            "Expected a non-negative, finite amount of seconds, got {secs}"
+        ))
+    })
+}
@@ -0,0 +1,91 @@
+use mlua::prelude::*;
+
+use crate::lune::scheduler::{Scheduler, SchedulerPriority, SchedulerThreadId};
+
+/**
+    Options for spawning a thread using `task.spawnWith`, `task.deferWith` or `task.delayWith`.
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct TaskSpawnOptions {
+    pub priority: SchedulerPriority,
+    pub preemptible: bool,
+}
+
+impl TaskSpawnOptions {
+    /**
+        Applies the options to the given thread, which must not be scheduled yet.
+    */
+    pub fn apply_to(self, lua: &Lua, thread: &LuaThread) {
+        let sched = lua
+            .app_data_ref::<&Scheduler>()
+            .expect("Lua struct is missing scheduler");
+        let thread_id = SchedulerThreadId::from(thread);
+        sched.set_thread_priority(thread_id, self.priority);
+        sched.set_thread_preemptible(thread_id, self.preemptible);
+    }
+}
+
+impl<'lua> FromLua<'lua> for TaskSpawnOptions {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let table = match value {
+            LuaValue::Table(t) => t,
+            value => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "SpawnOptions",
+                    message: Some(format!(
+                        "Invalid spawn options - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+
+        for pair in table.clone().pairs::<LuaValue, LuaValue>() {
+            let (key, _) = pair?;
+            let is_known = match &key {
+                LuaValue::String(s) => matches!(s.to_str()?, "priority" | "preemptible"),
+                _ => false,
+            };
+            if !is_known {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid key '{}' in spawn options - expected 'priority' or 'preemptible'",
+                    key.to_string()?
+                )));
+            }
+        }
+
+        let priority = match table.raw_get::<_, LuaValue>("priority")? {
+            LuaValue::Nil => SchedulerPriority::default(),
+            LuaValue::String(s) => {
+                let name = s.to_str()?;
+                SchedulerPriority::from_name(name).ok_or_else(|| {
+                    LuaError::RuntimeError(format!(
+                        "Invalid priority '{name}' - expected one of 'high', 'normal' or 'low'"
+                    ))
+                })?
+            }
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid priority in spawn options - expected string, got {}",
+                    value.type_name()
+                )))
+            }
+        };
+        let preemptible = match table.raw_get::<_, LuaValue>("preemptible")? {
+            LuaValue::Nil => false,
+            LuaValue::Boolean(b) => b,
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid preemptible in spawn options - expected boolean, got {}",
+                    value.type_name()
+                )))
+            }
+        };
+
+        Ok(Self {
+            priority,
+            preemptible,
+        })
+    }
+}
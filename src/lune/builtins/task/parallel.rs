@@ -0,0 +1,207 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex as StdMutex},
+    thread,
+};
+
+use mlua::prelude::*;
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    watch, Mutex as AsyncMutex,
+};
+
+use crate::lune::{
+    globals::has_require_sandboxes, scheduler::SchedulerThreadId, util::TableBuilder, Bundle, Lune,
+};
+
+use super::transfer::TransferValue;
+
+type WorkerResult = Result<Vec<TransferValue>, String>;
+
+/**
+    Creates a table for one end of a worker channel, with functions to `send`
+    values to the other end, `receive` values from it, and `close` the channel.
+
+    The other end of the channel is either the worker, or the script that created it.
+*/
+fn create_channel_table(
+    lua: &'static Lua,
+    tx: UnboundedSender<TransferValue>,
+    rx: UnboundedReceiver<TransferValue>,
+) -> LuaResult<TableBuilder<'static>> {
+    let tx = Arc::new(StdMutex::new(Some(tx)));
+    let tx_close = Arc::clone(&tx);
+    let rx = Arc::new(AsyncMutex::new(rx));
+    TableBuilder::new(lua)?
+        .with_function("send", move |lua, (_, value): (LuaValue, LuaValue)| {
+            let value = TransferValue::from_lua(value, lua)?;
+            match tx.lock().expect("Failed to lock channel sender").as_ref() {
+                Some(tx) => tx.send(value).map_err(|_| {
+                    LuaError::RuntimeError(
+                        "The other end of the channel is no longer running".to_string(),
+                    )
+                }),
+                None => Err(LuaError::RuntimeError(
+                    "The channel has been closed".to_string(),
+                )),
+            }
+        })?
+        .with_async_function("receive", move |lua, _: LuaValue| {
+            let rx = Arc::clone(&rx);
+            async move {
+                match rx.lock().await.recv().await {
+                    Some(value) => value.into_lua(lua),
+                    None => Ok(LuaValue::Nil),
+                }
+            }
+        })?
+        .with_function("close", move |_, _: LuaValue| {
+            // NOTE: The other end will receive nil once it
+            // has received all of the previously sent values
+            tx_close
+                .lock()
+                .expect("Failed to lock channel sender")
+                .take();
+            Ok(())
+        })
+}
+
+/**
+    Runs a worker script to completion, in a new Lune runtime on the current thread.
+
+    The worker uses the same args and bundle as the runtime that created it.
+*/
+fn run_worker(
+    args: Vec<String>,
+    bundle: Option<Arc<Bundle>>,
+    path: PathBuf,
+    source: Vec<u8>,
+    data: TransferValue,
+    tx: UnboundedSender<TransferValue>,
+    rx: UnboundedReceiver<TransferValue>,
+) -> WorkerResult {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| format!("Failed to create worker runtime - {e}"))?;
+    let lune = Lune::new().with_args(args);
+    if let Some(bundle) = bundle {
+        lune.lua.set_app_data(bundle);
+    }
+    let (lua, sched) = (lune.lua, lune.scheduler);
+
+    let result = rt.block_on(async move {
+        let result = (|| {
+            let parent = create_channel_table(lua, tx, rx)?.build_readonly()?;
+            let data = data.into_lua(lua)?;
+            let name = path.with_extension("").display().to_string();
+            let main = lua.load(source).set_name(name).into_function()?;
+            let main = lua.create_thread(main)?;
+            // NOTE: Errors are instead rethrown when joining the worker
+            sched.set_thread_errors_handled(SchedulerThreadId::from(&main), true);
+            let thread_id = sched.push_back(lua, main, (data, parent))?;
+            let result = sched.subscribe_to_thread(lua, thread_id)?;
+            Ok::<_, LuaError>(sched.spawn_local(async move {
+                result
+                    .await
+                    .and_then(|values| TransferValue::from_lua_multi(values, lua))
+                    .map_err(|e| e.to_string())
+            }))
+        })();
+
+        match result {
+            Err(e) => Err(e.to_string()),
+            Ok(mut result_rx) => {
+                sched.run_to_completion(lua).await;
+                result_rx.try_recv().unwrap_or_else(|_| {
+                    Err("Worker exited before its script finished running".to_string())
+                })
+            }
+        }
+    });
+
+    // NOTE: Unlike the main runtime, any number of workers may be created, so their
+    // runtimes must be freed once done, which also closes their end of the channel
+    drop(rt);
+    // SAFETY: The scheduler has run to completion and the tokio runtime
+    // has been dropped, so nothing can be using the worker runtime anymore
+    unsafe { lune.free() };
+
+    result
+}
+
+/**
+    Starts a worker that runs the script at the given path on a separate thread.
+
+    Note that workers only take a script path, and not a function, since functions
+    can not be moved between Lua VMs, and any upvalues they have would be lost.
+*/
+pub(super) async fn task_parallel(
+    lua: &'static Lua,
+    (path, data): (String, LuaValue<'static>),
+) -> LuaResult<LuaTable<'static>> {
+    // NOTE: Sandboxes are set up using the embedding API and only apply to
+    // modules in the VM that they were added to, so workers would not be
+    // sandboxed, and sandboxed modules could use them to get around it
+    if has_require_sandboxes(lua)? {
+        return Err(LuaError::RuntimeError(
+            "Workers can not be started while require sandboxes are in use".to_string(),
+        ));
+    }
+
+    let path = PathBuf::from(path);
+    let bundle = Bundle::active(lua);
+    let read_result = match bundle.as_ref().and_then(|b| b.read_file(&path)) {
+        Some(contents) => contents,
+        None => tokio::fs::read(&path).await,
+    };
+    let source = read_result.map_err(|e| {
+        LuaError::RuntimeError(format!(
+            "Failed to read worker script at '{}' - {e}",
+            path.display()
+        ))
+    })?;
+    let data = TransferValue::from_lua(data, lua)?;
+    let args = lua
+        .app_data_ref::<Vec<String>>()
+        .map(|args| args.clone())
+        .unwrap_or_default();
+
+    let (to_worker_tx, to_worker_rx) = mpsc::unbounded_channel();
+    let (from_worker_tx, from_worker_rx) = mpsc::unbounded_channel();
+    let (result_tx, result_rx) = watch::channel::<Option<WorkerResult>>(None);
+
+    thread::Builder::new()
+        .name(format!("lune-worker-{}", path.display()))
+        .spawn(move || {
+            let result = run_worker(
+                args,
+                bundle,
+                path,
+                source,
+                data,
+                from_worker_tx,
+                to_worker_rx,
+            );
+            result_tx.send_replace(Some(result));
+        })
+        .map_err(|e| LuaError::RuntimeError(format!("Failed to spawn worker thread - {e}")))?;
+
+    create_channel_table(lua, to_worker_tx, from_worker_rx)?
+        .with_async_function("join", move |lua, _: LuaValue| {
+            let mut result_rx = result_rx.clone();
+            async move {
+                let result = result_rx
+                    .wait_for(Option::is_some)
+                    .await
+                    .map_err(|_| LuaError::RuntimeError("Worker thread panicked".to_string()))?
+                    .clone()
+                    .expect("Result was checked to exist");
+                match result {
+                    Ok(values) => TransferValue::into_lua_multi(values, lua),
+                    Err(e) => Err(LuaError::RuntimeError(format!("Worker errored: {e}"))),
+                }
+            }
+        })?
+        .build_readonly()
+}
@@ -0,0 +1,135 @@
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+use mlua::prelude::*;
+use tokio::{sync::oneshot, time};
+
+use crate::lune::scheduler::{Scheduler, SchedulerTimerId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TaskTimerState {
+    Pending,
+    Fired,
+    Cancelled,
+}
+
+#[derive(Debug)]
+enum TaskTimerKind {
+    Real(Option<oneshot::Sender<()>>),
+    Virtual(SchedulerTimerId),
+}
+
+/**
+    A handle to a timer created using `task.delay` or `task.timeout`.
+
+    Cancelling the timer before it fires will stop it from ever firing,
+    and timers that use real time will also stop keeping Lune alive.
+*/
+#[derive(Debug)]
+pub(super) struct TaskTimer {
+    state: Rc<Cell<TaskTimerState>>,
+    kind: TaskTimerKind,
+}
+
+impl TaskTimer {
+    /**
+        Creates a new timer that fires after a real amount of time has passed.
+
+        The returned [`TaskTimerSleep`] must be awaited to actually run the timer.
+    */
+    pub fn new_real() -> (Self, TaskTimerSleep) {
+        let state = Rc::new(Cell::new(TaskTimerState::Pending));
+        let (tx, rx) = oneshot::channel();
+        let timer = Self {
+            state: Rc::clone(&state),
+            kind: TaskTimerKind::Real(Some(tx)),
+        };
+        let sleep = TaskTimerSleep {
+            state,
+            cancel_rx: rx,
+        };
+        (timer, sleep)
+    }
+
+    /**
+        Creates a new timer for a thread that is sleeping on the virtual clock.
+    */
+    pub fn new_virtual(id: SchedulerTimerId) -> Self {
+        Self {
+            state: Rc::new(Cell::new(TaskTimerState::Pending)),
+            kind: TaskTimerKind::Virtual(id),
+        }
+    }
+
+    /**
+        Cancels the timer, returning `true` if it was
+        cancelled before it had a chance to fire.
+    */
+    fn cancel(&mut self, lua: &Lua) -> LuaResult<bool> {
+        if self.state.get() != TaskTimerState::Pending {
+            return Ok(false);
+        }
+        let cancelled = match &mut self.kind {
+            TaskTimerKind::Real(tx) => {
+                // NOTE: The receiving end may have already been dropped if the
+                // timer stopped for some other reason, which is fine to ignore
+                if let Some(tx) = tx.take() {
+                    tx.send(()).ok();
+                }
+                true
+            }
+            TaskTimerKind::Virtual(id) => {
+                let sched = lua
+                    .app_data_ref::<&Scheduler>()
+                    .expect("Lua struct is missing scheduler");
+                sched.cancel_timer(lua, *id)?
+            }
+        };
+        self.state.set(if cancelled {
+            TaskTimerState::Cancelled
+        } else {
+            TaskTimerState::Fired
+        });
+        Ok(cancelled)
+    }
+}
+
+impl LuaUserData for TaskTimer {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("cancelled", |_, this| {
+            Ok(this.state.get() == TaskTimerState::Cancelled)
+        });
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("cancel", |lua, this, ()| this.cancel(lua));
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, _, ()| Ok("TaskTimer"));
+    }
+}
+
+/**
+    The sleeping half of a [`TaskTimer`] that uses real time.
+*/
+#[derive(Debug)]
+pub(super) struct TaskTimerSleep {
+    state: Rc<Cell<TaskTimerState>>,
+    cancel_rx: oneshot::Receiver<()>,
+}
+
+impl TaskTimerSleep {
+    /**
+        Sleeps for the given duration, returning `true` if the timer
+        fired, or `false` if it was cancelled before it could fire.
+    */
+    pub async fn sleep(self, duration: Duration) -> bool {
+        tokio::select! {
+            _ = time::sleep(duration) => {
+                self.state.set(TaskTimerState::Fired);
+                true
+            }
+            // NOTE: A dropped sender means the timer handle was garbage
+            // collected, which should not cancel the timer, so we only
+            // match on the timer being explicitly cancelled here
+            Ok(()) = self.cancel_rx => false,
+        }
+    }
+}
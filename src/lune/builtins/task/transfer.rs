@@ -0,0 +1,140 @@
+use std::{collections::HashSet, ffi::c_void};
+
+use mlua::prelude::*;
+
+#[cfg(feature = "roblox")]
+use crate::roblox::datatypes::{
+    conversion::{DomValueToLua, LuaToDomValue},
+    DomValue,
+};
+
+/**
+    A value that has been copied out of one Lua VM, so that
+    it can be sent to and recreated inside of another Lua VM.
+
+    Tables are copied deeply, and Roblox datatypes are copied
+    using the same conversion as when saving them to files.
+*/
+#[derive(Debug, Clone)]
+pub(super) enum TransferValue {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    Vector(LuaVector),
+    String(Vec<u8>),
+    Table(Vec<(TransferValue, TransferValue)>),
+    #[cfg(feature = "roblox")]
+    Roblox(DomValue),
+}
+
+impl TransferValue {
+    /**
+        Copies the given Lua value, erroring if it contains any values that can
+        not be sent between VMs, such as functions, threads, or cyclic tables.
+    */
+    pub fn from_lua<'lua>(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        Self::from_lua_inner(value, lua, &mut HashSet::new())
+    }
+
+    fn from_lua_inner<'lua>(
+        value: LuaValue<'lua>,
+        lua: &'lua Lua,
+        visited: &mut HashSet<*const c_void>,
+    ) -> LuaResult<Self> {
+        Ok(match value {
+            LuaValue::Nil => Self::Nil,
+            LuaValue::Boolean(b) => Self::Boolean(b),
+            LuaValue::Integer(i) => Self::Number(f64::from(i)),
+            LuaValue::Number(n) => Self::Number(n),
+            LuaValue::Vector(v) => Self::Vector(v),
+            LuaValue::String(s) => Self::String(s.as_bytes().to_vec()),
+            LuaValue::Table(t) => {
+                let pointer = t.to_pointer();
+                if !visited.insert(pointer) {
+                    return Err(LuaError::RuntimeError(
+                        "Tables containing cycles can not be sent to or from a worker".to_string(),
+                    ));
+                }
+                let mut pairs = Vec::new();
+                for pair in t.pairs::<LuaValue, LuaValue>() {
+                    let (key, value) = pair?;
+                    pairs.push((
+                        Self::from_lua_inner(key, lua, visited)?,
+                        Self::from_lua_inner(value, lua, visited)?,
+                    ));
+                }
+                visited.remove(&pointer);
+                Self::Table(pairs)
+            }
+            #[cfg(feature = "roblox")]
+            LuaValue::UserData(ud) => match LuaValue::UserData(ud).lua_to_dom_value(lua, None) {
+                // NOTE: Instances only exist inside of the VM that created
+                // them, so we can not send references to them to another VM
+                Ok(DomValue::Ref(_)) => {
+                    return Err(LuaError::RuntimeError(
+                        "Instances can not be sent to or from a worker".to_string(),
+                    ))
+                }
+                Ok(value) => Self::Roblox(value),
+                Err(_) => {
+                    return Err(LuaError::RuntimeError(
+                        "Only Roblox datatypes can be sent to or from a worker, got userdata"
+                            .to_string(),
+                    ))
+                }
+            },
+            value => {
+                return Err(LuaError::RuntimeError(format!(
+                    "Values of type '{}' can not be sent to or from a worker",
+                    value.type_name()
+                )))
+            }
+        })
+    }
+
+    /**
+        Recreates the copied value inside of the given Lua VM.
+    */
+    pub fn into_lua(self, lua: &Lua) -> LuaResult<LuaValue> {
+        Ok(match self {
+            Self::Nil => LuaValue::Nil,
+            Self::Boolean(b) => LuaValue::Boolean(b),
+            Self::Number(n) => LuaValue::Number(n),
+            Self::Vector(v) => LuaValue::Vector(v),
+            Self::String(s) => LuaValue::String(lua.create_string(s)?),
+            Self::Table(pairs) => {
+                let table = lua.create_table_with_capacity(0, pairs.len())?;
+                for (key, value) in pairs {
+                    table.raw_set(key.into_lua(lua)?, value.into_lua(lua)?)?;
+                }
+                LuaValue::Table(table)
+            }
+            #[cfg(feature = "roblox")]
+            Self::Roblox(value) => LuaValue::dom_value_to_lua(lua, &value)?,
+        })
+    }
+
+    /**
+        Copies all of the given Lua values, same as [`TransferValue::from_lua`].
+    */
+    pub fn from_lua_multi<'lua>(
+        values: LuaMultiValue<'lua>,
+        lua: &'lua Lua,
+    ) -> LuaResult<Vec<Self>> {
+        values
+            .into_iter()
+            .map(|value| Self::from_lua(value, lua))
+            .collect()
+    }
+
+    /**
+        Recreates all of the given copied values, same as [`TransferValue::into_lua`].
+    */
+    pub fn into_lua_multi(values: Vec<Self>, lua: &Lua) -> LuaResult<LuaMultiValue> {
+        values
+            .into_iter()
+            .map(|value| value.into_lua(lua))
+            .collect::<LuaResult<Vec<_>>>()
+            .map(LuaMultiValue::from_vec)
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt::Write;
+
+use ring::rand::{SecureRandom, SystemRandom};
+
+/**
+    The ids that identify a span, and the trace that it belongs to.
+
+    These are propagated to other services using the W3C `traceparent` header.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+}
+
+impl SpanContext {
+    /**
+        Creates the context for a new span that starts a new trace.
+    */
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: random_id(),
+            span_id: random_id(),
+        }
+    }
+
+    /**
+        Creates the context for a new span that is a child of this one.
+    */
+    pub fn new_child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id,
+            span_id: random_id(),
+        }
+    }
+
+    /**
+        Parses a span context from the value of a `traceparent` header.
+
+        Returns `None` if the header is not valid, in which
+        case the header should be ignored, as per the spec.
+    */
+    pub fn from_traceparent(value: impl AsRef<[u8]>) -> Option<Self> {
+        let value = std::str::from_utf8(value.as_ref()).ok()?.trim();
+        let mut parts = value.split('-');
+        let version = parts.next()?;
+        let trace_id = parse_hex::<16>(parts.next()?)?;
+        let span_id = parse_hex::<8>(parts.next()?)?;
+        let _flags = parse_hex::<1>(parts.next()?)?;
+        // NOTE: Future versions may add more parts, but version 00 must have exactly four
+        if version.len() != 2 || version == "ff" || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+        Some(Self { trace_id, span_id })
+    }
+
+    /**
+        Formats this span context as the value of a `traceparent` header.
+    */
+    pub fn to_traceparent(self) -> String {
+        format!("00-{}-{}-01", self.trace_id_hex(), self.span_id_hex())
+    }
+
+    pub fn trace_id_hex(&self) -> String {
+        to_hex(&self.trace_id)
+    }
+
+    pub fn span_id_hex(&self) -> String {
+        to_hex(&self.span_id)
+    }
+}
+
+fn random_id<const N: usize>() -> [u8; N] {
+    let rng = SystemRandom::new();
+    let mut id = [0; N];
+    // NOTE: Ids that are all zeros are invalid, but also incredibly unlikely
+    while id == [0; N] {
+        rng.fill(&mut id)
+            .expect("Failed to generate random span id");
+    }
+    id
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        write!(hex, "{byte:02x}").unwrap();
+        hex
+    })
+}
+
+fn parse_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 || !hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
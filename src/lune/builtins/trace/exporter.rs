@@ -0,0 +1,243 @@
+use std::{
+    env,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use mlua::prelude::*;
+use serde_json::{json, Value as JsonValue};
+
+use super::span::{AttributeValue, Attributes, SpanData};
+
+/**
+    Maximum number of finished spans that may be waiting to be exported.
+
+    Any spans finished while the queue is full are dropped, so that a
+    collector that is unreachable can not make memory usage grow forever.
+*/
+const MAX_QUEUED_SPANS: usize = 2048;
+
+const DEFAULT_SERVICE_NAME: &str = "lune";
+
+/**
+    Configuration for exporting spans to an OTLP collector.
+*/
+#[derive(Debug, Clone)]
+pub struct TraceExporterConfig {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub service_name: String,
+}
+
+impl TraceExporterConfig {
+    /**
+        Reads the exporter configuration from the standard
+        `OTEL_*` environment variables, if an endpoint was set.
+    */
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.trim().is_empty());
+        // NOTE: The signal-specific endpoint is used as-is, while the
+        // general endpoint is a base url that signal paths are appended to
+        let url = var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT")
+            .or_else(|| var("OTEL_EXPORTER_OTLP_ENDPOINT").map(|base| traces_url(&base)))?;
+        let headers = var("OTEL_EXPORTER_OTLP_TRACES_HEADERS")
+            .or_else(|| var("OTEL_EXPORTER_OTLP_HEADERS"))
+            .map(|headers| parse_headers(&headers))
+            .unwrap_or_default();
+        let service_name =
+            var("OTEL_SERVICE_NAME").unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+        Some(Self {
+            url,
+            headers,
+            service_name,
+        })
+    }
+}
+
+impl<'lua> FromLua<'lua> for TraceExporterConfig {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let tab = match value {
+            LuaValue::Table(tab) => tab,
+            value => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "TraceExporterConfig",
+                    message: Some(format!(
+                        "Invalid trace config - expected table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        };
+        let endpoint = match tab.get::<_, Option<String>>("endpoint")? {
+            Some(endpoint) => endpoint,
+            None => {
+                return Err(LuaError::RuntimeError(
+                    "Invalid trace config - missing 'endpoint'".to_string(),
+                ))
+            }
+        };
+        let mut headers = Vec::new();
+        if let Some(tab) = tab.get::<_, Option<LuaTable>>("headers")? {
+            for pair in tab.pairs::<String, String>() {
+                headers.push(pair?);
+            }
+        }
+        let service_name = tab
+            .get::<_, Option<String>>("serviceName")?
+            .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+        Ok(Self {
+            url: traces_url(&endpoint),
+            headers,
+            service_name,
+        })
+    }
+}
+
+/**
+    An exporter that sends finished spans to an
+    OTLP collector, using OTLP/HTTP with JSON bodies.
+*/
+#[derive(Debug)]
+pub struct TraceExporter {
+    config: TraceExporterConfig,
+    client: reqwest::Client,
+    queued: Mutex<Vec<SpanData>>,
+}
+
+impl TraceExporter {
+    pub fn new(config: TraceExporterConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            queued: Mutex::new(Vec::new()),
+        }
+    }
+
+    /**
+        Queues a finished span to be sent on the next flush.
+    */
+    pub fn push(&self, span: SpanData) {
+        let mut queued = self.queued.lock().expect("Failed to lock span queue");
+        if queued.len() < MAX_QUEUED_SPANS {
+            queued.push(span);
+        }
+    }
+
+    /**
+        Sends all spans that are currently queued to the collector.
+    */
+    pub async fn flush(&self) -> LuaResult<()> {
+        let spans = std::mem::take(&mut *self.queued.lock().expect("Failed to lock span queue"));
+        if spans.is_empty() {
+            return Ok(());
+        }
+
+        let body = serde_json::to_vec(&self.create_body(&spans)).into_lua_err()?;
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .header("Content-Type", "application/json")
+            .body(body);
+        for (name, value) in &self.config.headers {
+            request = request.header(name, value);
+        }
+
+        let response = request.send().await.into_lua_err()?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(LuaError::RuntimeError(format!(
+                "Failed to export {} spans to '{}' - collector responded with {status}",
+                spans.len(),
+                self.config.url
+            )))
+        }
+    }
+
+    fn create_body(&self, spans: &[SpanData]) -> JsonValue {
+        let mut resource = Attributes::default();
+        resource.set("service.name", self.config.service_name.as_str());
+        json!({
+            "resourceSpans": [{
+                "resource": { "attributes": attributes_to_json(&resource) },
+                "scopeSpans": [{
+                    "scope": {
+                        "name": "lune",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                    "spans": spans.iter().map(span_to_json).collect::<Vec<_>>(),
+                }],
+            }],
+        })
+    }
+}
+
+fn span_to_json(span: &SpanData) -> JsonValue {
+    let mut value = json!({
+        "traceId": span.context.trace_id_hex(),
+        "spanId": span.context.span_id_hex(),
+        "name": span.name,
+        "kind": span.kind.otlp_value(),
+        "startTimeUnixNano": unix_nanos(span.start),
+        "endTimeUnixNano": unix_nanos(span.end.unwrap_or(span.start)),
+        "attributes": attributes_to_json(&span.attributes),
+        "events": span.events.iter().map(|event| json!({
+            "name": event.name,
+            "timeUnixNano": unix_nanos(event.time),
+            "attributes": attributes_to_json(&event.attributes),
+        })).collect::<Vec<_>>(),
+        "status": match &span.error {
+            Some(message) => json!({ "code": 2, "message": message }),
+            None => json!({ "code": 0 }),
+        },
+    });
+    if let Some(parent) = &span.parent {
+        value["parentSpanId"] = json!(parent.span_id_hex());
+    }
+    value
+}
+
+fn attributes_to_json(attributes: &Attributes) -> JsonValue {
+    attributes
+        .iter()
+        .map(|(key, value)| {
+            // NOTE: 64-bit integers are encoded as strings in OTLP/JSON
+            let value = match value {
+                AttributeValue::String(s) => json!({ "stringValue": s }),
+                AttributeValue::Bool(b) => json!({ "boolValue": b }),
+                AttributeValue::Int(i) => json!({ "intValue": i.to_string() }),
+                AttributeValue::Double(d) => json!({ "doubleValue": d }),
+            };
+            json!({ "key": key, "value": value })
+        })
+        .collect()
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{endpoint}/v1/traces")
+    }
+}
+
+fn parse_headers(headers: &str) -> Vec<(String, String)> {
+    headers
+        .split(',')
+        .filter_map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
@@ -0,0 +1,160 @@
+use std::{cell::RefCell, rc::Rc, time::SystemTime};
+
+use mlua::prelude::*;
+
+use crate::lune::{scheduler::SchedulerThreadId, util::TableBuilder};
+
+mod context;
+mod exporter;
+mod span;
+mod tracer;
+
+pub(super) use context::SpanContext;
+pub(super) use span::SpanKind;
+pub(super) use tracer::AutoSpan;
+
+use exporter::TraceExporterConfig;
+use span::{AttributeValue, Attributes, SpanData, SpanEvent};
+use tracer::Tracer;
+
+/*
+    Running a function inside of a span works like this:
+
+    1. Start the span, making it the current span of this thread
+    2. Call the function using pcall, which may yield, and
+       which also lets us know if the function errored or not
+    3. Finish the span, and then rethrow any error
+*/
+const SPAN_IMPL_LUA: &str = r#"
+local name, fn, attributes = ...
+local span = start(name, fn, attributes)
+local results = pack(pcall(fn, span))
+finish(span, if results[1] then nil else tostring(results[2]))
+if not results[1] then
+    error(results[2], 0)
+end
+return unpack(results, 2, results.n)
+"#;
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    let table = lua.globals().get::<_, LuaTable>("table")?;
+    let span_env = TableBuilder::new(lua)?
+        .with_value("pcall", lua.globals().get::<_, LuaFunction>("pcall")?)?
+        .with_value("error", lua.globals().get::<_, LuaFunction>("error")?)?
+        .with_value("tostring", lua.globals().get::<_, LuaFunction>("tostring")?)?
+        .with_value("pack", table.get::<_, LuaFunction>("pack")?)?
+        .with_value("unpack", table.get::<_, LuaFunction>("unpack")?)?
+        .with_function("start", span_start)?
+        .with_function("finish", span_finish)?
+        .build_readonly()?;
+    let span = lua
+        .load(SPAN_IMPL_LUA)
+        .set_name("trace.span")
+        .set_environment(span_env)
+        .into_function()?;
+
+    TableBuilder::new(lua)?
+        .with_value("span", span)?
+        .with_function("configure", trace_configure)?
+        .with_async_function("flush", trace_flush)?
+        .build_readonly()
+}
+
+fn span_start(
+    lua: &'static Lua,
+    (name, _, attributes): (String, LuaFunction<'static>, Attributes),
+) -> LuaResult<TraceSpan> {
+    let tracer = Tracer::get(lua);
+    let thread_id = SchedulerThreadId::from(&lua.current_thread());
+    let mut data = SpanData::new(name, SpanKind::Internal, tracer.current(thread_id));
+    data.attributes = attributes;
+    tracer.enter(thread_id, data.context);
+    Ok(TraceSpan {
+        tracer,
+        thread_id,
+        context: data.context,
+        data: RefCell::new(Some(data)),
+    })
+}
+
+fn span_finish<'lua>(
+    _: &'lua Lua,
+    (span, error): (LuaUserDataRef<'lua, TraceSpan>, Option<String>),
+) -> LuaResult<()> {
+    let data = span.data.borrow_mut().take();
+    if let Some(mut data) = data {
+        span.tracer.exit(span.thread_id, data.context);
+        if error.is_some() {
+            data.error = error;
+        }
+        data.end = Some(SystemTime::now());
+        span.tracer.record(data);
+    }
+    Ok(())
+}
+
+fn trace_configure(lua: &'static Lua, config: TraceExporterConfig) -> LuaResult<()> {
+    Tracer::get(lua).configure(lua, config)
+}
+
+async fn trace_flush(lua: &'static Lua, _: ()) -> LuaResult<()> {
+    Tracer::get(lua).flush().await
+}
+
+/**
+    A span created using `trace.span`, which is passed
+    to the function that runs inside of the span.
+*/
+#[derive(Debug)]
+struct TraceSpan {
+    tracer: Rc<Tracer>,
+    thread_id: SchedulerThreadId,
+    context: SpanContext,
+    data: RefCell<Option<SpanData>>,
+}
+
+impl TraceSpan {
+    fn with_data<R>(&self, f: impl FnOnce(&mut SpanData) -> R) -> LuaResult<R> {
+        match self.data.borrow_mut().as_mut() {
+            Some(data) => Ok(f(data)),
+            None => Err(LuaError::RuntimeError(
+                "Span has already finished".to_string(),
+            )),
+        }
+    }
+}
+
+impl LuaUserData for TraceSpan {
+    fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("traceId", |_, this| Ok(this.context.trace_id_hex()));
+        fields.add_field_method_get("spanId", |_, this| Ok(this.context.span_id_hex()));
+        fields.add_field_method_get("traceparent", |_, this| Ok(this.context.to_traceparent()));
+    }
+
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method(
+            "setAttribute",
+            |_, this, (key, value): (String, AttributeValue)| {
+                this.with_data(|data| data.attributes.set(key, value))
+            },
+        );
+        methods.add_method(
+            "addEvent",
+            |_, this, (name, attributes): (String, Attributes)| {
+                this.with_data(|data| {
+                    data.events.push(SpanEvent {
+                        name,
+                        time: SystemTime::now(),
+                        attributes,
+                    })
+                })
+            },
+        );
+        methods.add_method("setError", |_, this, message: String| {
+            this.with_data(|data| data.error = Some(message))
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            Ok(format!("TraceSpan({})", this.context.span_id_hex()))
+        });
+    }
+}
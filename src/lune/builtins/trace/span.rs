@@ -0,0 +1,175 @@
+use std::time::SystemTime;
+
+use mlua::prelude::*;
+
+use super::context::SpanContext;
+
+/**
+    The kind of a span, describing its relationship to other services.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    Internal,
+    Server,
+    Client,
+}
+
+impl SpanKind {
+    /**
+        Gets the numeric value of this kind, as used by OTLP.
+    */
+    pub fn otlp_value(&self) -> u8 {
+        match self {
+            Self::Internal => 1,
+            Self::Server => 2,
+            Self::Client => 3,
+        }
+    }
+}
+
+/**
+    The value of a single span attribute.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+}
+
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl From<bool> for AttributeValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl<'lua> FromLua<'lua> for AttributeValue {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::String(s) => Ok(Self::String(s.to_str()?.to_string())),
+            LuaValue::Boolean(b) => Ok(Self::Bool(b)),
+            LuaValue::Integer(i) => Ok(Self::Int(i.into())),
+            LuaValue::Number(n) => Ok(Self::Double(n)),
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "AttributeValue",
+                message: Some(format!(
+                    "Span attributes must be strings, booleans or numbers, got {}",
+                    value.type_name()
+                )),
+            }),
+        }
+    }
+}
+
+/**
+    A list of span attributes, in the order they were first set in.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Attributes(Vec<(String, AttributeValue)>);
+
+impl Attributes {
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<AttributeValue>) {
+        let key = key.into();
+        let value = value.into();
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, existing)) => *existing = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(String, AttributeValue)> {
+        self.0.iter()
+    }
+}
+
+impl<'lua> FromLua<'lua> for Attributes {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let mut attributes = Self::default();
+        match value {
+            LuaValue::Nil => {}
+            LuaValue::Table(t) => {
+                for pair in t.pairs::<String, LuaValue>() {
+                    let (key, value) = pair?;
+                    attributes.set(key, AttributeValue::from_lua(value, lua)?);
+                }
+            }
+            value => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "Attributes",
+                    message: Some(format!(
+                        "Span attributes must be a table, got {}",
+                        value.type_name()
+                    )),
+                })
+            }
+        }
+        Ok(attributes)
+    }
+}
+
+/**
+    An event that happened during a span.
+*/
+#[derive(Debug, Clone)]
+pub struct SpanEvent {
+    pub name: String,
+    pub time: SystemTime,
+    pub attributes: Attributes,
+}
+
+/**
+    All of the data recorded for a single span.
+*/
+#[derive(Debug, Clone)]
+pub struct SpanData {
+    pub context: SpanContext,
+    pub parent: Option<SpanContext>,
+    pub name: String,
+    pub kind: SpanKind,
+    pub start: SystemTime,
+    pub end: Option<SystemTime>,
+    pub attributes: Attributes,
+    pub events: Vec<SpanEvent>,
+    pub error: Option<String>,
+}
+
+impl SpanData {
+    pub fn new(name: impl Into<String>, kind: SpanKind, parent: Option<SpanContext>) -> Self {
+        let context = match &parent {
+            Some(parent) => parent.new_child(),
+            None => SpanContext::new_root(),
+        };
+        Self {
+            context,
+            parent,
+            name: name.into(),
+            kind,
+            start: SystemTime::now(),
+            end: None,
+            attributes: Attributes::default(),
+            events: Vec::new(),
+            error: None,
+        }
+    }
+}
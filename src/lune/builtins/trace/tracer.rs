@@ -0,0 +1,277 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime},
+};
+
+use mlua::prelude::*;
+use tracing::warn;
+
+use crate::lune::scheduler::{LuaSchedulerExt, Scheduler, SchedulerThreadId};
+
+use super::{
+    context::SpanContext,
+    exporter::{TraceExporter, TraceExporterConfig},
+    span::{AttributeValue, SpanData, SpanKind},
+};
+
+/**
+    How often spans are sent to the collector in the background.
+*/
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/**
+    The tracer for a single Lune runtime.
+
+    This keeps track of the current span for each Lua thread, so that
+    new spans can automatically use it as their parent, and owns the
+    exporter that finished spans are sent to, if one was configured.
+*/
+#[derive(Debug, Default)]
+pub struct Tracer {
+    stacks: RefCell<HashMap<SchedulerThreadId, Vec<SpanContext>>>,
+    exporter: RefCell<Option<Arc<TraceExporter>>>,
+}
+
+impl Tracer {
+    /**
+        Gets the tracer for the given Lua struct, creating it if
+        it does not yet exist, using any exporter configuration
+        that was given through environment variables.
+    */
+    pub fn get(lua: &'static Lua) -> Rc<Self> {
+        if let Some(tracer) = lua.app_data_ref::<Rc<Tracer>>() {
+            return Rc::clone(&tracer);
+        }
+
+        let tracer = Rc::new(Tracer::default());
+        lua.set_app_data(Rc::clone(&tracer));
+        if let Some(config) = TraceExporterConfig::from_env() {
+            if let Err(e) = tracer.configure(lua, config) {
+                warn!("Failed to configure trace exporter: {e}");
+            }
+        }
+        tracer
+    }
+
+    /**
+        Sets the exporter configuration, replacing any previous
+        exporter after flushing all of its remaining spans.
+    */
+    pub fn configure(&self, lua: &'static Lua, config: TraceExporterConfig) -> LuaResult<()> {
+        let is_first = self.exporter.borrow().is_none();
+        let exporter = Arc::new(TraceExporter::new(config));
+        let previous = self.exporter.replace(Some(Arc::clone(&exporter)));
+        if let Some(previous) = previous {
+            tokio::spawn(async move {
+                if let Err(e) = previous.flush().await {
+                    warn!("{e}");
+                }
+            });
+        }
+
+        // NOTE: Flushing in the background uses a weak reference so that
+        // the task stops once this exporter has been replaced, and is not
+        // spawned on the scheduler, to not prevent the runtime from exiting
+        let weak = Arc::downgrade(&exporter);
+        tokio::spawn(flush_periodically(weak));
+
+        if is_first {
+            // NOTE: Spans are flushed one last time when exiting, and same as when
+            // flushing in the background, failures are not errors for the script
+            let flush = lua.create_async_function(|lua, _: LuaValue| async move {
+                if let Err(e) = Tracer::get(lua).flush().await {
+                    warn!("{e}");
+                }
+                Ok(())
+            })?;
+            let sched = lua
+                .app_data_ref::<&Scheduler>()
+                .expect("Lua struct is missing scheduler");
+            sched.add_exit_handler(lua, flush)?;
+        }
+
+        Ok(())
+    }
+
+    /**
+        Sends all finished spans to the collector right away.
+    */
+    pub async fn flush(&self) -> LuaResult<()> {
+        let exporter = self.exporter.borrow().clone();
+        match exporter {
+            Some(exporter) => exporter.flush().await,
+            None => Ok(()),
+        }
+    }
+
+    /**
+        Checks if finished spans are being exported anywhere.
+    */
+    pub fn is_exporting(&self) -> bool {
+        self.exporter.borrow().is_some()
+    }
+
+    /**
+        Gets the current span of the given thread, if any.
+    */
+    pub fn current(&self, thread_id: SchedulerThreadId) -> Option<SpanContext> {
+        self.stacks
+            .borrow()
+            .get(&thread_id)
+            .and_then(|stack| stack.last().copied())
+    }
+
+    /**
+        Makes the given span the current span of the given thread.
+    */
+    pub fn enter(&self, thread_id: SchedulerThreadId, context: SpanContext) {
+        self.stacks
+            .borrow_mut()
+            .entry(thread_id)
+            .or_default()
+            .push(context);
+    }
+
+    /**
+        Removes the given span from the spans of the given thread.
+
+        Spans are usually exited in reverse order of entering them, but
+        this is not guaranteed when threads are cancelled or error.
+    */
+    pub fn exit(&self, thread_id: SchedulerThreadId, context: SpanContext) {
+        let mut stacks = self.stacks.borrow_mut();
+        if let Some(stack) = stacks.get_mut(&thread_id) {
+            if let Some(index) = stack.iter().rposition(|c| *c == context) {
+                stack.remove(index);
+            }
+            if stack.is_empty() {
+                stacks.remove(&thread_id);
+            }
+        }
+    }
+
+    /**
+        Records a finished span, queueing it for export.
+    */
+    pub fn record(&self, span: SpanData) {
+        if let Some(exporter) = self.exporter.borrow().as_ref() {
+            exporter.push(span);
+        }
+    }
+}
+
+async fn flush_periodically(exporter: Weak<TraceExporter>) {
+    loop {
+        tokio::time::sleep(FLUSH_INTERVAL).await;
+        match exporter.upgrade() {
+            Some(exporter) => {
+                if let Err(e) = exporter.flush().await {
+                    warn!("{e}");
+                }
+            }
+            None => break,
+        }
+    }
+}
+
+/**
+    A span that is created automatically by other builtins,
+    such as around requests made using `net.request`.
+
+    These spans are only recorded if an exporter has been configured,
+    and are finished and recorded once they are dropped.
+*/
+#[derive(Debug)]
+pub struct AutoSpan {
+    inner: Option<(Rc<Tracer>, SpanData)>,
+    entered: Option<SchedulerThreadId>,
+}
+
+impl AutoSpan {
+    /**
+        Starts a new span, using the current span of the
+        currently running Lua thread as its parent, if any.
+    */
+    pub fn start(lua: &'static Lua, name: impl Into<String>, kind: SpanKind) -> Self {
+        Self::start_with_parent(lua, name, kind, Self::current(lua))
+    }
+
+    /**
+        Gets the current span of the currently running Lua thread, if any,
+        to use as the parent of spans that are started later on.
+    */
+    pub fn current(lua: &'static Lua) -> Option<SpanContext> {
+        Tracer::get(lua).current(SchedulerThreadId::from(&lua.current_thread()))
+    }
+
+    /**
+        Starts a new span with the given parent, which may come
+        from a different service, such as through a request header.
+    */
+    pub fn start_with_parent(
+        lua: &'static Lua,
+        name: impl Into<String>,
+        kind: SpanKind,
+        parent: Option<SpanContext>,
+    ) -> Self {
+        let tracer = Tracer::get(lua);
+        let inner = if tracer.is_exporting() {
+            Some((tracer, SpanData::new(name, kind, parent)))
+        } else {
+            None
+        };
+        Self {
+            inner,
+            entered: None,
+        }
+    }
+
+    /**
+        Makes this span the current span of the given thread until it is
+        dropped, making it the parent of any spans created in that thread.
+    */
+    pub fn enter(&mut self, thread: &LuaThread) {
+        if let Some((tracer, data)) = &self.inner {
+            let thread_id = SchedulerThreadId::from(thread);
+            tracer.enter(thread_id, data.context);
+            self.entered = Some(thread_id);
+        }
+    }
+
+    pub fn set_attribute(&mut self, key: &str, value: impl Into<AttributeValue>) {
+        if let Some((_, data)) = &mut self.inner {
+            data.attributes.set(key, value);
+        }
+    }
+
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        if let Some((_, data)) = &mut self.inner {
+            data.error = Some(message.into());
+        }
+    }
+
+    /**
+        Gets the value of the `traceparent` header to send
+        to other services, if this span is being recorded.
+    */
+    pub fn traceparent(&self) -> Option<String> {
+        self.inner
+            .as_ref()
+            .map(|(_, data)| data.context.to_traceparent())
+    }
+}
+
+impl Drop for AutoSpan {
+    fn drop(&mut self) {
+        if let Some((tracer, mut data)) = self.inner.take() {
+            if let Some(thread_id) = self.entered.take() {
+                tracer.exit(thread_id, data.context);
+            }
+            data.end = Some(SystemTime::now());
+            tracer.record(data);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use caseless::default_case_fold_str;
+use mlua::prelude::*;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::lune::util::TableBuilder;
+
+mod options;
+
+use options::NormalizationForm;
+
+pub fn create(lua: &'static Lua) -> LuaResult<LuaTable> {
+    TableBuilder::new(lua)?
+        .with_function("graphemes", unicode_graphemes)?
+        .with_function("normalize", unicode_normalize)?
+        .with_function("caseFold", unicode_case_fold)?
+        .with_function("width", unicode_width)?
+        .build_readonly()
+}
+
+fn unicode_graphemes<'lua>(lua: &'lua Lua, s: LuaString<'lua>) -> LuaResult<LuaTable<'lua>> {
+    lua.create_sequence_from(s.to_str()?.graphemes(true))
+}
+
+fn unicode_normalize<'lua>(
+    lua: &'lua Lua,
+    (s, form): (LuaString<'lua>, NormalizationForm),
+) -> LuaResult<LuaString<'lua>> {
+    let s = s.to_str()?;
+    let normalized = match form {
+        NormalizationForm::Nfc => s.nfc().collect::<String>(),
+        NormalizationForm::Nfd => s.nfd().collect::<String>(),
+        NormalizationForm::Nfkc => s.nfkc().collect::<String>(),
+        NormalizationForm::Nfkd => s.nfkd().collect::<String>(),
+    };
+    lua.create_string(normalized)
+}
+
+fn unicode_case_fold<'lua>(lua: &'lua Lua, s: LuaString<'lua>) -> LuaResult<LuaString<'lua>> {
+    lua.create_string(default_case_fold_str(s.to_str()?))
+}
+
+fn unicode_width(_: &Lua, (s, cjk): (LuaString, Option<bool>)) -> LuaResult<usize> {
+    let s = s.to_str()?;
+    Ok(if cjk.unwrap_or_default() {
+        s.width_cjk()
+    } else {
+        s.width()
+    })
+}
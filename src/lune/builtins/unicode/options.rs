@@ -0,0 +1,39 @@
+use mlua::prelude::*;
+
+/**
+    A unicode normalization form, defaulting to NFC.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NormalizationForm {
+    #[default]
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+impl<'lua> FromLua<'lua> for NormalizationForm {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match &value {
+            LuaValue::Nil => Ok(Self::default()),
+            LuaValue::String(s) => match s.to_string_lossy().to_ascii_lowercase().trim() {
+                "nfc" => Ok(Self::Nfc),
+                "nfd" => Ok(Self::Nfd),
+                "nfkc" => Ok(Self::Nfkc),
+                "nfkd" => Ok(Self::Nfkd),
+                kind => Err(LuaError::FromLuaConversionError {
+                    from: value.type_name(),
+                    to: "NormalizationForm",
+                    message: Some(format!(
+                        "Invalid normalization form '{kind}', valid forms are:  nfc, nfd, nfkc, nfkd"
+                    )),
+                }),
+            },
+            _ => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "NormalizationForm",
+                message: None,
+            }),
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[cfg(feature = "archive")]
+use std::{
+    env::current_dir,
+    io::{Cursor, ErrorKind, Read},
+};
+
+use mlua::prelude::*;
+
+const ENTRYPOINT_NAMES: [&str; 2] = ["init.luau", "init.lua"];
+
+/**
+    A bundle of scripts and other files, read from a zip or tar archive.
+
+    A bundle must contain an `init.luau` or `init.lua` file, which is
+    used as the entrypoint when running it. If all files in the archive
+    are inside of a single directory, that directory is used as the root.
+
+    While running a bundle, it is mounted at the current working directory,
+    meaning that both `require` and reading files using the `fs` library
+    will see the files in the bundle, which are read-only, before any
+    files in the real filesystem.
+
+    Reading bundles requires the `archive` feature, which is enabled by default.
+*/
+#[derive(Debug, Clone)]
+pub struct Bundle {
+    root: PathBuf,
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    dirs: BTreeSet<PathBuf>,
+}
+
+#[cfg(feature = "archive")]
+impl Bundle {
+    fn new() -> io::Result<Self> {
+        Ok(Self {
+            root: current_dir()?,
+            files: BTreeMap::new(),
+            dirs: BTreeSet::new(),
+        })
+    }
+
+    /**
+        Reads a bundle from the contents of a zip archive.
+    */
+    pub fn from_zip(bytes: impl AsRef<[u8]>) -> io::Result<Self> {
+        let mut archive =
+            zip::ZipArchive::new(Cursor::new(bytes.as_ref())).map_err(invalid_data)?;
+        let mut bundle = Self::new()?;
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(invalid_data)?;
+            let path = archive_path(entry.name())?;
+            if entry.is_dir() {
+                bundle.insert_dir(path);
+            } else {
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents)?;
+                bundle.insert_file(path, contents);
+            }
+        }
+        bundle.finish()
+    }
+
+    /**
+        Reads a bundle from the contents of a tar archive.
+    */
+    pub fn from_tar(bytes: impl AsRef<[u8]>) -> io::Result<Self> {
+        let mut archive = tar::Archive::new(Cursor::new(bytes.as_ref()));
+        let mut bundle = Self::new()?;
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = archive_path(&entry.path()?.to_string_lossy())?;
+            let kind = entry.header().entry_type();
+            if kind.is_dir() {
+                bundle.insert_dir(path);
+            } else if kind.is_file() {
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents)?;
+                bundle.insert_file(path, contents);
+            }
+        }
+        bundle.finish()
+    }
+
+    fn insert_dir(&mut self, path: PathBuf) {
+        for ancestor in path.ancestors() {
+            if ancestor.as_os_str().is_empty() {
+                break;
+            }
+            self.dirs.insert(ancestor.to_path_buf());
+        }
+    }
+
+    fn insert_file(&mut self, path: PathBuf, contents: Vec<u8>) {
+        if let Some(parent) = path.parent() {
+            self.insert_dir(parent.to_path_buf());
+        }
+        self.files.insert(path, contents);
+    }
+
+    /**
+        Makes sure that the bundle has an entrypoint, using the only
+        directory in the bundle as its root if necessary, which is
+        common when archiving a directory instead of its contents.
+    */
+    fn finish(mut self) -> io::Result<Self> {
+        if self.find_entrypoint().is_some() {
+            return Ok(self);
+        }
+        let top_level = self
+            .files
+            .keys()
+            .chain(self.dirs.iter())
+            .filter_map(|path| path.components().next())
+            .map(|component| PathBuf::from(component.as_os_str()))
+            .collect::<BTreeSet<_>>();
+        if top_level.len() == 1 && self.dirs.iter().any(|dir| top_level.contains(dir)) {
+            let prefix = top_level.into_iter().next().unwrap();
+            let strip = |path: &PathBuf| path.strip_prefix(&prefix).map(Path::to_path_buf).ok();
+            self.files = self
+                .files
+                .iter()
+                .filter_map(|(path, contents)| strip(path).map(|path| (path, contents.clone())))
+                .collect();
+            self.dirs = self
+                .dirs
+                .iter()
+                .filter_map(strip)
+                .filter(|path| !path.as_os_str().is_empty())
+                .collect();
+        }
+        match self.find_entrypoint() {
+            Some(_) => Ok(self),
+            None => Err(io::Error::new(
+                ErrorKind::NotFound,
+                "Bundle is missing an entrypoint - expected an init.luau or init.lua file",
+            )),
+        }
+    }
+}
+
+impl Bundle {
+    fn find_entrypoint(&self) -> Option<&'static str> {
+        ENTRYPOINT_NAMES
+            .into_iter()
+            .find(|name| self.files.contains_key(Path::new(name)))
+    }
+
+    /**
+        Gets the name and contents of the entrypoint script for this bundle.
+    */
+    pub fn entrypoint(&self) -> (String, Vec<u8>) {
+        let name = self
+            .find_entrypoint()
+            .expect("Bundle was created without an entrypoint");
+        // NOTE: The extension is skipped here to remove it from stack traces,
+        // same as for scripts that are ran from files in the real filesystem
+        let display_name = Path::new(name).with_extension("");
+        (
+            display_name.display().to_string(),
+            self.files[Path::new(name)].clone(),
+        )
+    }
+
+    /**
+        Gets the currently running bundle, if any.
+    */
+    pub(crate) fn active(lua: &Lua) -> Option<Arc<Bundle>> {
+        lua.app_data_ref::<Arc<Bundle>>()
+            .map(|bundle| Arc::clone(&bundle))
+    }
+
+    /**
+        Resolves a path to a path relative to the root of the bundle,
+        if the path is inside of it, without checking if it exists.
+    */
+    fn resolve(&self, path: impl AsRef<Path>) -> Option<PathBuf> {
+        path_clean::clean(self.root.join(path))
+            .strip_prefix(&self.root)
+            .map(Path::to_path_buf)
+            .ok()
+    }
+
+    /**
+        Checks if a file or directory exists at the given path in the bundle.
+
+        Note that the root of the bundle is not considered to be in the
+        bundle, since it is also the current working directory.
+    */
+    pub(crate) fn contains(&self, path: impl AsRef<Path>) -> bool {
+        match self.resolve(path) {
+            Some(resolved) => self.files.contains_key(&resolved) || self.dirs.contains(&resolved),
+            None => false,
+        }
+    }
+
+    pub(crate) fn is_file(&self, path: impl AsRef<Path>) -> bool {
+        self.resolve(path)
+            .is_some_and(|resolved| self.files.contains_key(&resolved))
+    }
+
+    pub(crate) fn is_dir(&self, path: impl AsRef<Path>) -> bool {
+        self.resolve(path)
+            .is_some_and(|resolved| self.dirs.contains(&resolved))
+    }
+
+    /**
+        Reads the file at the given path, or `None` if
+        the path does not exist in the bundle at all.
+    */
+    pub(crate) fn read_file(&self, path: impl AsRef<Path>) -> Option<io::Result<Vec<u8>>> {
+        let resolved = self.resolve(path.as_ref())?;
+        if let Some(contents) = self.files.get(&resolved) {
+            Some(Ok(contents.clone()))
+        } else if self.dirs.contains(&resolved) {
+            Some(Err(io::Error::other(format!(
+                "Is a directory at the path '{}'",
+                path.as_ref().display()
+            ))))
+        } else {
+            None
+        }
+    }
+
+    /**
+        Reads the names of entries in the directory at the given path,
+        or `None` if the directory does not exist in the bundle.
+
+        This includes the root of the bundle, which contains the
+        entries at the top level of the archive it was read from.
+    */
+    pub(crate) fn read_dir(&self, path: impl AsRef<Path>) -> Option<Vec<String>> {
+        let resolved = self.resolve(path)?;
+        if !resolved.as_os_str().is_empty() && !self.dirs.contains(&resolved) {
+            return None;
+        }
+        let names = self
+            .files
+            .keys()
+            .chain(self.dirs.iter())
+            .filter(|child| child.parent() == Some(resolved.as_path()))
+            .filter_map(|child| child.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .collect::<BTreeSet<_>>();
+        Some(names.into_iter().collect())
+    }
+}
+
+/**
+    Turns the name of an entry in an archive into a relative path,
+    making sure that it can not point outside of the archive.
+*/
+#[cfg(feature = "archive")]
+fn archive_path(name: &str) -> io::Result<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in name.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Bundle contains an invalid path '{name}'"),
+                ))
+            }
+            component => path.push(component),
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(feature = "archive")]
+fn invalid_data(e: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, e)
+}
+
+#[cfg(all(test, feature = "archive"))]
+mod tests {
+    use std::io::Write;
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+
+    fn create_zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn reads_files_and_dirs() {
+        let bundle = Bundle::from_zip(create_zip(&[
+            ("init.luau", "return 1"),
+            ("modules/a.luau", "return 2"),
+            ("assets/data/b.txt", "hello"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            bundle.entrypoint(),
+            ("init".to_string(), b"return 1".to_vec())
+        );
+        assert_eq!(
+            bundle.read_file("./assets/data/b.txt").unwrap().unwrap(),
+            b"hello"
+        );
+        assert!(bundle.read_file("assets").unwrap().is_err());
+        assert!(bundle.read_file("missing.txt").is_none());
+        assert!(bundle.is_dir("assets/data"));
+        assert!(bundle.is_file("modules/a.luau"));
+        assert_eq!(
+            bundle.read_dir(".").unwrap(),
+            vec!["assets", "init.luau", "modules"]
+        );
+        assert!(!bundle.contains("."));
+    }
+
+    #[test]
+    fn strips_single_top_level_dir() {
+        let bundle = Bundle::from_zip(create_zip(&[
+            ("app/init.lua", "return 1"),
+            ("app/modules/a.luau", "return 2"),
+        ]))
+        .unwrap();
+        assert_eq!(bundle.entrypoint().0, "init");
+        assert!(bundle.is_file("modules/a.luau"));
+        assert!(!bundle.contains("app"));
+    }
+
+    #[test]
+    fn errors_on_invalid_bundles() {
+        assert!(Bundle::from_zip(create_zip(&[("main.luau", "")])).is_err());
+        assert!(Bundle::from_zip(create_zip(&[("init.luau", ""), ("../a.luau", "")])).is_err());
+        assert!(Bundle::from_zip(b"not a zip").is_err());
+    }
+}
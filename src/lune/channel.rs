@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use mlua::prelude::*;
+use serde::Serialize;
+use tokio::sync::{
+    mpsc::{self, error::SendError, UnboundedSender},
+    Mutex as AsyncMutex,
+};
+
+use super::util::TableBuilder;
+
+const LUA_SERIALIZE_OPTIONS: LuaSerializeOptions = LuaSerializeOptions::new()
+    .set_array_metatable(false)
+    .serialize_none_to_null(false)
+    .serialize_unit_to_null(false);
+
+/**
+    The sending half of a channel created using [`crate::Lune::channel`].
+
+    Senders can be cloned and used from any thread, and values sent
+    will be received in order by the Lua script using the channel.
+
+    The channel will be closed once all senders have been dropped.
+*/
+#[derive(Debug)]
+pub struct LuneSender<T> {
+    inner: UnboundedSender<T>,
+}
+
+impl<T> LuneSender<T> {
+    /**
+        Sends a value to the Lua script using the channel.
+
+        Returns the value back as an error if the channel is
+        no longer being received from, meaning the runtime was dropped.
+    */
+    pub fn send(&self, value: T) -> Result<(), T> {
+        self.inner.send(value).map_err(|SendError(value)| value)
+    }
+
+    /**
+        Checks if the channel is no longer being received from.
+    */
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+impl<T> Clone for LuneSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/**
+    Creates a new channel, with the receiving half set as a global
+    with the given name, and returns the sending half of the channel.
+
+    The receiving half is a Lua table with a single `next` method that
+    yields until the next value is available, or returns `nil` once the
+    channel has been closed and all values sent have been received.
+*/
+pub(super) fn create<T>(lua: &'static Lua, global_name: &str) -> LuaResult<LuneSender<T>>
+where
+    T: Serialize + Send + 'static,
+{
+    let (tx, rx) = mpsc::unbounded_channel::<T>();
+    let receiver = Arc::new(AsyncMutex::new(rx));
+    let receiver_table = TableBuilder::new(lua)?
+        .with_async_function("next", move |lua, _: LuaValue| {
+            let receiver = Arc::clone(&receiver);
+            async move {
+                match receiver.lock().await.recv().await {
+                    Some(value) => lua.to_value_with(&value, LUA_SERIALIZE_OPTIONS),
+                    None => Ok(LuaValue::Nil),
+                }
+            }
+        })?
+        .build_readonly()?;
+    lua.globals().set(global_name, receiver_table)?;
+    Ok(LuneSender { inner: tx })
+}
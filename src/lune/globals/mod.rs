@@ -9,6 +9,9 @@ mod r#typeof;
 mod version;
 mod warn;
 
+pub use require::add_sandbox as add_require_sandbox;
+pub use require::has_sandboxes as has_require_sandboxes;
+
 pub fn inject_all(lua: &'static Lua) -> LuaResult<()> {
     let all = TableBuilder::new(lua)?
         .with_value("_G", g_table::create(lua)?)?
@@ -10,15 +10,90 @@ use tokio::{
     fs,
     sync::{
         broadcast::{self, Sender},
-        Mutex as AsyncMutex,
+        Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard,
     },
 };
 
 use crate::lune::{
     builtins::LuneBuiltin,
+    bundle::Bundle,
     scheduler::{IntoLuaThread, Scheduler},
 };
 
+/**
+    Globals that are copied into the environment of sandboxed modules.
+
+    Anything that could be used to get to the real global environment,
+    such as `getfenv` and `loadstring`, must never be added here.
+*/
+const SANDBOX_GLOBALS: &[&str] = &[
+    "assert",
+    "error",
+    "gcinfo",
+    "ipairs",
+    "newproxy",
+    "next",
+    "pairs",
+    "pcall",
+    "print",
+    "rawequal",
+    "rawget",
+    "rawlen",
+    "rawset",
+    "select",
+    "setmetatable",
+    "tonumber",
+    "tostring",
+    "type",
+    "typeof",
+    "unpack",
+    "warn",
+    "xpcall",
+    "_VERSION",
+];
+
+/**
+    Standard libraries that are copied into the environment of sandboxed modules.
+
+    These are copied and not shared, so that sandboxed modules can not replace
+    library functions used by other modules. The original libraries are also
+    made readonly once a sandbox is added, since strings share the original
+    `string` library through their metatable.
+*/
+const SANDBOX_LIBRARIES: &[&str] = &[
+    "bit32",
+    "coroutine",
+    "math",
+    "os",
+    "string",
+    "table",
+    "utf8",
+];
+
+/**
+    A sandbox that modules can be loaded in, see [`RequireContext::add_sandbox`].
+*/
+#[derive(Debug)]
+struct RequireSandbox {
+    path: PathBuf,
+    require_fn: LuaRegistryKey,
+}
+
+/**
+    Key for cached require results, which are cached separately for each
+    sandbox, so that modules loaded outside of a sandbox are never shared.
+*/
+type RequireCacheKey = (PathBuf, Option<usize>);
+
+/**
+    Locks the given mutex, returning an error instead of waiting if it is already locked.
+*/
+fn try_lock<T>(mutex: &AsyncMutex<T>) -> LuaResult<AsyncMutexGuard<'_, T>> {
+    mutex
+        .try_lock()
+        .map_err(|_| LuaError::runtime("RequireContext may not be used from multiple threads"))
+}
+
 /**
     Context containing cached results for all `require` operations.
 
@@ -31,8 +106,10 @@ pub(super) struct RequireContext<'lua> {
     use_cwd_relative_paths: bool,
     working_directory: PathBuf,
     cache_builtins: Arc<AsyncMutex<HashMap<LuneBuiltin, LuaResult<LuaRegistryKey>>>>,
-    cache_results: Arc<AsyncMutex<HashMap<PathBuf, LuaResult<LuaRegistryKey>>>>,
-    cache_pending: Arc<AsyncMutex<HashMap<PathBuf, Sender<()>>>>,
+    cache_results: Arc<AsyncMutex<HashMap<RequireCacheKey, LuaResult<LuaRegistryKey>>>>,
+    cache_pending: Arc<AsyncMutex<HashMap<RequireCacheKey, Sender<()>>>>,
+    sandboxes: Arc<AsyncMutex<Vec<RequireSandbox>>>,
+    sandbox: Option<usize>,
 }
 
 impl<'lua> RequireContext<'lua> {
@@ -54,6 +131,21 @@ impl<'lua> RequireContext<'lua> {
             cache_builtins: Arc::new(AsyncMutex::new(HashMap::new())),
             cache_results: Arc::new(AsyncMutex::new(HashMap::new())),
             cache_pending: Arc::new(AsyncMutex::new(HashMap::new())),
+            sandboxes: Arc::new(AsyncMutex::new(Vec::new())),
+            sandbox: None,
+        }
+    }
+
+    /**
+        Creates a view of this require context for a module that is loaded
+        in the given sandbox, or outside of any sandbox if `None` is given.
+
+        All views share the same caches and sandboxes.
+    */
+    pub fn with_sandbox(&self, sandbox: Option<usize>) -> Self {
+        Self {
+            sandbox,
+            ..self.clone()
         }
     }
 
@@ -89,15 +181,145 @@ impl<'lua> RequireContext<'lua> {
         Ok((rel_path, abs_path))
     }
 
+    /**
+        Adds a sandbox for all modules at or inside of the given path, meaning
+        those modules will use a `require` function created using the given
+        `create_require_fn`, which receives the index of the new sandbox.
+
+        If a module is inside of more than one sandbox, the sandbox
+        with the longest matching path will be used for it.
+    */
+    pub fn add_sandbox(
+        &self,
+        path: impl AsRef<Path>,
+        create_require_fn: impl FnOnce(usize) -> LuaResult<LuaFunction<'lua>>,
+    ) -> LuaResult<()> {
+        let path = path_clean::clean(path.as_ref());
+        let abs_path = if path.is_absolute() {
+            path
+        } else {
+            self.working_directory.join(path)
+        };
+        let mut sandboxes = try_lock(&self.sandboxes)?;
+        if sandboxes.is_empty() {
+            self.make_libraries_readonly()?;
+        }
+        let require_fn = create_require_fn(sandboxes.len())?;
+        sandboxes.push(RequireSandbox {
+            path: abs_path,
+            require_fn: self.lua.create_registry_value(require_fn)?,
+        });
+        Ok(())
+    }
+
+    /**
+        Makes the original standard libraries, and the metatable
+        shared by all strings, readonly for every module.
+    */
+    fn make_libraries_readonly(&self) -> LuaResult<()> {
+        let globals = self.lua.globals();
+        for name in SANDBOX_LIBRARIES {
+            globals.raw_get::<_, LuaTable>(*name)?.set_readonly(true);
+        }
+        let string_meta = globals
+            .raw_get::<_, LuaFunction>("getmetatable")?
+            .call::<_, LuaTable>("")?;
+        string_meta.set_readonly(true);
+        Ok(())
+    }
+
+    /**
+        Checks if any sandboxes have been added.
+    */
+    pub fn has_sandboxes(&self) -> LuaResult<bool> {
+        Ok(!try_lock(&self.sandboxes)?.is_empty())
+    }
+
+    /**
+        Gets the index of the sandbox that the module at the given path should be loaded in.
+
+        Modules required from a sandboxed module are always loaded in the same sandbox,
+        even if they are inside of a different sandbox or none at all, so that sandboxed
+        modules can not get access to other builtins through modules that re-export them.
+    */
+    fn sandbox_for(&self, abs_path: impl AsRef<Path>) -> LuaResult<Option<usize>> {
+        if self.sandbox.is_some() {
+            return Ok(self.sandbox);
+        }
+        Ok(try_lock(&self.sandboxes)?
+            .iter()
+            .enumerate()
+            .filter(|(_, sandbox)| abs_path.as_ref().starts_with(&sandbox.path))
+            .max_by_key(|(_, sandbox)| sandbox.path.components().count())
+            .map(|(index, _)| index))
+    }
+
+    fn cache_key(&self, abs_path: impl AsRef<Path>) -> LuaResult<RequireCacheKey> {
+        let abs_path = abs_path.as_ref();
+        Ok((abs_path.to_path_buf(), self.sandbox_for(abs_path)?))
+    }
+
+    /**
+        Creates a new environment table for a module in the given sandbox.
+
+        The environment only contains copies of safe globals and libraries, and
+        does not read through to the real global environment, which would let
+        sandboxed modules get to the unrestricted `require` function.
+    */
+    fn create_sandbox_env(&self, sandbox: usize) -> LuaResult<LuaTable<'lua>> {
+        let require_fn = {
+            let sandboxes = try_lock(&self.sandboxes)?;
+            self.lua
+                .registry_value::<LuaFunction>(&sandboxes[sandbox].require_fn)?
+        };
+
+        let globals = self.lua.globals();
+        let env = self.lua.create_table()?;
+        for name in SANDBOX_GLOBALS {
+            env.raw_set(*name, globals.raw_get::<_, LuaValue>(*name)?)?;
+        }
+        env.raw_set("getmetatable", self.create_sandbox_getmetatable()?)?;
+        for name in SANDBOX_LIBRARIES {
+            let library = globals.raw_get::<_, LuaTable>(*name)?;
+            let copy = self.lua.create_table()?;
+            for pair in library.pairs::<LuaValue, LuaValue>() {
+                let (key, value) = pair?;
+                copy.raw_set(key, value)?;
+            }
+            env.raw_set(*name, copy)?;
+        }
+        env.raw_set("require", require_fn)?;
+        env.raw_set("_G", env.clone())?;
+
+        Ok(env)
+    }
+
+    /**
+        Creates a `getmetatable` function for sandboxed modules, which refuses to
+        get the metatable of strings, since it contains the original `string` library.
+    */
+    fn create_sandbox_getmetatable(&self) -> LuaResult<LuaFunction<'lua>> {
+        let getmetatable = self.lua.create_registry_value(
+            self.lua
+                .globals()
+                .raw_get::<_, LuaFunction>("getmetatable")?,
+        )?;
+        self.lua
+            .create_function(move |lua, value: LuaValue| match value {
+                LuaValue::String(_) => Err(LuaError::runtime(
+                    "getmetatable can not be used on strings in sandboxed modules",
+                )),
+                value => lua
+                    .registry_value::<LuaFunction>(&getmetatable)?
+                    .call::<_, LuaValue>(value),
+            })
+    }
+
     /**
         Checks if the given path has a cached require result.
     */
     pub fn is_cached(&self, abs_path: impl AsRef<Path>) -> LuaResult<bool> {
-        let is_cached = self
-            .cache_results
-            .try_lock()
-            .expect("RequireContext may not be used from multiple threads")
-            .contains_key(abs_path.as_ref());
+        let is_cached = try_lock(&self.cache_results)?.contains_key(&self.cache_key(abs_path)?);
         Ok(is_cached)
     }
 
@@ -105,11 +327,7 @@ impl<'lua> RequireContext<'lua> {
         Checks if the given path is currently being used in `require`.
     */
     pub fn is_pending(&self, abs_path: impl AsRef<Path>) -> LuaResult<bool> {
-        let is_pending = self
-            .cache_pending
-            .try_lock()
-            .expect("RequireContext may not be used from multiple threads")
-            .contains_key(abs_path.as_ref());
+        let is_pending = try_lock(&self.cache_pending)?.contains_key(&self.cache_key(abs_path)?);
         Ok(is_pending)
     }
 
@@ -119,13 +337,10 @@ impl<'lua> RequireContext<'lua> {
         Will panic if the path has not been cached, use [`is_cached`] first.
     */
     pub fn get_from_cache(&self, abs_path: impl AsRef<Path>) -> LuaResult<LuaMultiValue<'lua>> {
-        let results = self
-            .cache_results
-            .try_lock()
-            .expect("RequireContext may not be used from multiple threads");
+        let results = try_lock(&self.cache_results)?;
 
         let cached = results
-            .get(abs_path.as_ref())
+            .get(&self.cache_key(abs_path)?)
             .expect("Path does not exist in results cache");
         match cached {
             Err(e) => Err(e.clone()),
@@ -149,12 +364,9 @@ impl<'lua> RequireContext<'lua> {
         abs_path: impl AsRef<Path>,
     ) -> LuaResult<LuaMultiValue<'lua>> {
         let mut thread_recv = {
-            let pending = self
-                .cache_pending
-                .try_lock()
-                .expect("RequireContext may not be used from multiple threads");
+            let pending = self.cache_pending.lock().await;
             let thread_id = pending
-                .get(abs_path.as_ref())
+                .get(&self.cache_key(abs_path.as_ref())?)
                 .expect("Path is not currently pending require");
             thread_id.subscribe()
         };
@@ -168,6 +380,7 @@ impl<'lua> RequireContext<'lua> {
         &self,
         abs_path: impl AsRef<Path>,
         rel_path: impl AsRef<Path>,
+        contents: Option<Vec<u8>>,
     ) -> LuaResult<LuaRegistryKey> {
         let abs_path = abs_path.as_ref();
         let rel_path = rel_path.as_ref();
@@ -179,13 +392,21 @@ impl<'lua> RequireContext<'lua> {
 
         // Read the file at the given path, try to parse and
         // load it into a new lua thread that we can schedule
-        let file_contents = fs::read(&abs_path).await?;
-        let file_thread = self
+        let file_contents = match contents {
+            Some(contents) => contents,
+            None => match Bundle::active(self.lua).and_then(|b| b.read_file(abs_path)) {
+                Some(contents) => contents?,
+                None => fs::read(&abs_path).await?,
+            },
+        };
+        let mut file_chunk = self
             .lua
             .load(file_contents)
-            .set_name(rel_path.to_string_lossy().to_string())
-            .into_function()?
-            .into_lua_thread(self.lua)?;
+            .set_name(rel_path.to_string_lossy().to_string());
+        if let Some(sandbox) = self.sandbox_for(abs_path)? {
+            file_chunk = file_chunk.set_environment(self.create_sandbox_env(sandbox)?);
+        }
+        let file_thread = file_chunk.into_function()?.into_lua_thread(self.lua)?;
 
         // Schedule the thread to run, wait for it to finish running
         let thread_id = sched.push_back(self.lua, file_thread, ())?;
@@ -212,19 +433,37 @@ impl<'lua> RequireContext<'lua> {
         &self,
         abs_path: impl AsRef<Path>,
         rel_path: impl AsRef<Path>,
+    ) -> LuaResult<LuaMultiValue<'lua>> {
+        self.load_contents_with_caching(abs_path, rel_path, None)
+            .await
+    }
+
+    /**
+        Loads (requires) the given contents, or the file at the given path if
+        no contents are given, caching the result using the given path.
+
+        Passing contents is useful for files that were already read and checked,
+        since reading the file again could give different contents than were checked.
+    */
+    pub async fn load_contents_with_caching(
+        &self,
+        abs_path: impl AsRef<Path>,
+        rel_path: impl AsRef<Path>,
+        contents: Option<Vec<u8>>,
     ) -> LuaResult<LuaMultiValue<'lua>> {
         let abs_path = abs_path.as_ref();
         let rel_path = rel_path.as_ref();
 
         // Set this abs path as currently pending
+        let cache_key = self.cache_key(abs_path)?;
         let (broadcast_tx, _) = broadcast::channel(1);
         self.cache_pending
-            .try_lock()
-            .expect("RequireContext may not be used from multiple threads")
-            .insert(abs_path.to_path_buf(), broadcast_tx);
+            .lock()
+            .await
+            .insert(cache_key.clone(), broadcast_tx);
 
         // Try to load at this abs path
-        let load_res = self.load(abs_path, rel_path).await;
+        let load_res = self.load(abs_path, rel_path, contents).await;
         let load_val = match &load_res {
             Err(e) => Err(e.clone()),
             Ok(k) => {
@@ -242,16 +481,16 @@ impl<'lua> RequireContext<'lua> {
         self.cache_results
             .lock()
             .await
-            .insert(abs_path.to_path_buf(), load_res);
+            .insert(cache_key.clone(), load_res);
 
         // Remove the pending thread id from the require context,
         // broadcast a message to let any listeners know that this
         // path has now finished the require process and is cached
         let broadcast_tx = self
             .cache_pending
-            .try_lock()
-            .expect("RequireContext may not be used from multiple threads")
-            .remove(abs_path)
+            .lock()
+            .await
+            .remove(&cache_key)
             .expect("Pending require broadcaster was unexpectedly removed");
         broadcast_tx.send(()).ok();
 
@@ -270,10 +509,7 @@ impl<'lua> RequireContext<'lua> {
             Ok(b) => b,
         };
 
-        let mut cache = self
-            .cache_builtins
-            .try_lock()
-            .expect("RequireContext may not be used from multiple threads");
+        let mut cache = try_lock(&self.cache_builtins)?;
 
         if let Some(res) = cache.get(&builtin) {
             return match res {
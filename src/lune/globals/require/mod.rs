@@ -1,6 +1,8 @@
+use std::{collections::HashSet, path::Path, sync::Arc};
+
 use mlua::prelude::*;
 
-use crate::lune::{scheduler::LuaSchedulerExt, util::TableBuilder};
+use crate::lune::{builtins::LuneBuiltin, scheduler::LuaSchedulerExt, util::TableBuilder};
 
 mod context;
 use context::RequireContext;
@@ -8,6 +10,7 @@ use context::RequireContext;
 mod alias;
 mod builtin;
 mod path;
+mod remote;
 
 const REQUIRE_IMPL: &str = r#"
 return require(source(), ...)
@@ -15,7 +18,58 @@ return require(source(), ...)
 
 pub fn create(lua: &'static Lua) -> LuaResult<impl IntoLua<'_>> {
     lua.set_app_data(RequireContext::new(lua));
+    create_require_function(lua, None)
+}
 
+/**
+    Restricts all modules at or inside of the given path to
+    only be able to require the given builtin libraries.
+
+    Sandboxed modules each get their own environment table with only safe
+    globals, and any modules they require are loaded in the same sandbox.
+*/
+pub fn add_sandbox(
+    lua: &'static Lua,
+    path: impl AsRef<Path>,
+    builtins: impl IntoIterator<Item = LuneBuiltin>,
+) -> LuaResult<()> {
+    let builtins = Arc::new(builtins.into_iter().collect::<HashSet<_>>());
+    let context = lua
+        .app_data_ref::<RequireContext>()
+        .expect("Failed to get RequireContext from app data");
+    context.add_sandbox(path, |index| {
+        create_require_function(lua, Some(RequireSandbox { index, builtins }))
+    })
+}
+
+/**
+    Checks if any modules have been restricted using [`add_sandbox`].
+*/
+pub fn has_sandboxes(lua: &Lua) -> LuaResult<bool> {
+    lua.app_data_ref::<RequireContext>()
+        .expect("Failed to get RequireContext from app data")
+        .has_sandboxes()
+}
+
+/**
+    The sandbox that a `require` function was created for.
+*/
+#[derive(Debug, Clone)]
+struct RequireSandbox {
+    index: usize,
+    builtins: Arc<HashSet<LuneBuiltin>>,
+}
+
+/**
+    Creates a new `require` function.
+
+    If a sandbox is given, requiring any builtin library that the sandbox does not
+    allow using this function will error, and required modules are loaded in it.
+*/
+fn create_require_function(
+    lua: &'static Lua,
+    sandbox: Option<RequireSandbox>,
+) -> LuaResult<LuaFunction<'static>> {
     /*
         Require implementation needs a few workarounds:
 
@@ -35,7 +89,10 @@ pub fn create(lua: &'static Lua) -> LuaResult<impl IntoLua<'_>> {
         3. The lua chunk we are require-ing from
     */
 
-    let require_fn = lua.create_async_function(require)?;
+    let require_fn = lua.create_async_function(move |lua, args| {
+        let sandbox = sandbox.clone();
+        async move { require(lua, args, sandbox.as_ref()).await }
+    })?;
     let get_source_fn = lua.create_function(move |lua, _: ()| match lua.inspect_stack(2) {
         None => Err(LuaError::runtime(
             "Failed to get stack info for require source",
@@ -62,6 +119,7 @@ pub fn create(lua: &'static Lua) -> LuaResult<impl IntoLua<'_>> {
 async fn require<'lua>(
     lua: &'lua Lua,
     (source, path): (LuaString<'lua>, LuaString<'lua>),
+    sandbox: Option<&RequireSandbox>,
 ) -> LuaResult<LuaMultiValue<'lua>>
 where
     'lua: 'static, // FIXME: Remove static lifetime bound here when builtin libraries no longer need it
@@ -79,19 +137,29 @@ where
         .to_string();
 
     let context = lua
-        .app_data_ref()
-        .expect("Failed to get RequireContext from app data");
+        .app_data_ref::<RequireContext>()
+        .expect("Failed to get RequireContext from app data")
+        .with_sandbox(sandbox.map(|s| s.index));
 
     if let Some(builtin_name) = path
         .strip_prefix("@lune/")
         .map(|name| name.to_ascii_lowercase())
     {
+        if let (Some(sandbox), Ok(builtin)) = (sandbox, builtin_name.parse()) {
+            if !sandbox.builtins.contains(&builtin) {
+                return Err(LuaError::runtime(format!(
+                    "Builtin library '@lune/{builtin_name}' is not available to this module"
+                )));
+            }
+        }
         builtin::require(&context, &builtin_name).await
     } else if let Some(aliased_path) = path.strip_prefix('@') {
         let (alias, name) = aliased_path.split_once('/').ok_or(LuaError::runtime(
             "Require with custom alias must contain '/' delimiter",
         ))?;
         alias::require(&context, alias, name).await
+    } else if path.starts_with("https://") || path.starts_with("http://") {
+        remote::require(&context, &path).await
     } else {
         path::require(&context, &source, &path).await
     }
@@ -12,7 +12,7 @@ pub(super) async fn require<'lua, 'ctx>(
 where
     'lua: 'ctx,
 {
-    let (abs_path, rel_path) = ctx.resolve_paths(source, path)?;
+    let (rel_path, abs_path) = ctx.resolve_paths(source, path)?;
 
     // 1. Try to require the exact path
     if let Ok(res) = require_inner(ctx, &abs_path, &rel_path).await {
@@ -0,0 +1,190 @@
+use std::{net::IpAddr, path::Path};
+
+use mlua::prelude::*;
+use reqwest::Url;
+use ring::digest::{self, Algorithm};
+use tokio::fs;
+
+use crate::lune::util::cache_dir;
+
+use super::context::*;
+
+/**
+    An integrity hash that the contents of a remote module must match.
+
+    Parsed from the `#<algorithm>-<hex digest>` suffix of a remote require path.
+*/
+struct Integrity {
+    algorithm_name: &'static str,
+    algorithm: &'static Algorithm,
+    digest: String,
+}
+
+impl Integrity {
+    fn parse(integrity: &str) -> LuaResult<Self> {
+        let (algorithm_name, digest) = integrity.split_once('-').ok_or_else(|| {
+            LuaError::runtime(format!(
+                "Invalid integrity '{integrity}' - expected format 'sha256-<hex digest>'"
+            ))
+        })?;
+
+        let (algorithm_name, algorithm) = match algorithm_name.to_ascii_lowercase().as_str() {
+            "sha256" => ("sha256", &digest::SHA256),
+            "sha384" => ("sha384", &digest::SHA384),
+            "sha512" => ("sha512", &digest::SHA512),
+            _ => {
+                return Err(LuaError::runtime(format!(
+                    "Invalid integrity '{integrity}' - unsupported algorithm '{algorithm_name}', \
+                    expected one of 'sha256', 'sha384', 'sha512'"
+                )))
+            }
+        };
+
+        let digest = digest.to_ascii_lowercase();
+        let expected_len = algorithm.output_len * 2;
+        if digest.len() != expected_len || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(LuaError::runtime(format!(
+                "Invalid integrity '{integrity}' - expected {expected_len} hex characters for {algorithm_name}"
+            )));
+        }
+
+        Ok(Self {
+            algorithm_name,
+            algorithm,
+            digest,
+        })
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}-{}.luau", self.algorithm_name, self.digest)
+    }
+
+    fn verify(&self, contents: &[u8]) -> Result<(), String> {
+        let actual = digest::digest(self.algorithm, contents)
+            .as_ref()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        if actual == self.digest {
+            Ok(())
+        } else {
+            Err(format!("{}-{actual}", self.algorithm_name))
+        }
+    }
+}
+
+pub(super) async fn require<'lua, 'ctx>(
+    ctx: &'ctx RequireContext<'lua>,
+    path: &str,
+) -> LuaResult<LuaMultiValue<'lua>>
+where
+    'lua: 'ctx,
+{
+    let (url, integrity) = match path.split_once('#') {
+        Some((url, integrity)) => (url.trim(), Integrity::parse(integrity.trim())?),
+        None => {
+            return Err(LuaError::runtime(format!(
+                "Remote require of '{path}' is missing an integrity hash\
+                \nAppend one to the url, eg. 'https://example.com/module.luau#sha256-<hex digest>'"
+            )))
+        }
+    };
+    ensure_secure_url(url)?;
+
+    // NOTE: Cached files are named after their hashes, so the same
+    // module required from two different urls is only loaded once
+    let cache_path = cache_dir("require")?.join(integrity.file_name());
+    if ctx.is_cached(&cache_path)? {
+        return ctx.get_from_cache(&cache_path);
+    } else if ctx.is_pending(&cache_path)? {
+        return ctx.wait_for_cache(&cache_path).await;
+    }
+
+    // Files in the cache were verified when written, but they may have
+    // been modified since then, so we verify again before every use
+    // NOTE: We load the exact contents that were verified, since the
+    // file could be modified again between verifying and reading it
+    let cached = match fs::read(&cache_path).await {
+        Ok(contents) if integrity.verify(&contents).is_ok() => Some(contents),
+        _ => None,
+    };
+
+    let contents = match cached {
+        Some(contents) => contents,
+        None => {
+            let contents = download(url).await?;
+            if let Err(actual) = integrity.verify(&contents) {
+                return Err(LuaError::runtime(format!(
+                    "Remote require of '{url}' failed integrity check\
+                    \nExpected: {}-{}\
+                    \nReceived: {actual}",
+                    integrity.algorithm_name, integrity.digest
+                )));
+            }
+            write_to_cache(&cache_path, &contents).await?;
+            contents
+        }
+    };
+
+    ctx.load_contents_with_caching(&cache_path, Path::new(url), Some(contents))
+        .await
+}
+
+/**
+    Makes sure that the given url uses https, unless it points to this machine.
+
+    Plain http is allowed for loopback addresses since nothing
+    can be intercepted there, which is useful for local servers.
+*/
+fn ensure_secure_url(url: &str) -> LuaResult<()> {
+    let parsed = Url::parse(url)
+        .into_lua_err()
+        .with_context(|_| format!("Invalid url for remote require '{url}'"))?;
+    let is_loopback = match parsed.host_str() {
+        Some(host) if host.eq_ignore_ascii_case("localhost") => true,
+        Some(host) => host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback()),
+        None => false,
+    };
+    match parsed.scheme() {
+        "https" => Ok(()),
+        "http" if is_loopback => Ok(()),
+        scheme => Err(LuaError::runtime(format!(
+            "Remote require of '{url}' uses '{scheme}' - remote modules must use https"
+        ))),
+    }
+}
+
+async fn download(url: &str) -> LuaResult<Vec<u8>> {
+    let res = reqwest::get(url)
+        .await
+        .into_lua_err()
+        .with_context(|_| format!("Failed to download remote module '{url}'"))?;
+    if !res.status().is_success() {
+        return Err(LuaError::runtime(format!(
+            "Failed to download remote module '{url}' - server responded with {}",
+            res.status()
+        )));
+    }
+    let bytes = res
+        .bytes()
+        .await
+        .into_lua_err()
+        .with_context(|_| format!("Failed to download remote module '{url}'"))?;
+    Ok(bytes.to_vec())
+}
+
+async fn write_to_cache(cache_path: &Path, contents: &[u8]) -> LuaResult<()> {
+    if let Some(parent) = cache_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    // NOTE: We write to a temporary file first and rename it so
+    // that other processes never see a partially written module
+    let temp_path = cache_path.with_extension(format!("luau.{}.tmp", std::process::id()));
+    fs::write(&temp_path, contents).await?;
+    fs::rename(&temp_path, cache_path).await?;
+    Ok(())
+}
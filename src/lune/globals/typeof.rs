@@ -1,7 +1,9 @@
 use mlua::prelude::*;
 
+#[cfg(feature = "roblox")]
 use crate::roblox::datatypes::extension::RobloxUserdataTypenameExt;
 
+#[cfg(feature = "roblox")]
 const REGISTRY_KEY: &str = "LuauTypeof";
 
 pub fn create(lua: &Lua) -> LuaResult<impl IntoLua<'_>> {
@@ -23,5 +25,5 @@ pub fn create(lua: &Lua) -> LuaResult<impl IntoLua<'_>> {
         })
     }
     #[cfg(not(feature = "roblox"))]
-    original
+    Ok(original)
 }
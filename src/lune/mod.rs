@@ -1,8 +1,11 @@
-use std::process::ExitCode;
+use std::{path::Path, process::ExitCode, sync::Arc};
 
-use mlua::Lua;
+use mlua::prelude::*;
+use serde::Serialize;
 
 mod builtins;
+mod bundle;
+mod channel;
 mod error;
 mod globals;
 mod scheduler;
@@ -11,6 +14,8 @@ pub(crate) mod util;
 
 use self::scheduler::{LuaSchedulerExt, Scheduler};
 
+pub use bundle::Bundle;
+pub use channel::LuneSender;
 pub use error::LuneError;
 
 // TODO: Rename this struct to "Runtime" instead for the
@@ -48,6 +53,23 @@ impl Lune {
         }
     }
 
+    /**
+        Frees the Luau VM and task scheduler for this runtime, which
+        are otherwise leaked and only freed once the process exits.
+
+        # Safety
+
+        This runtime and any clones of it must not be used after this, and nothing may still
+        hold on to its Luau VM or scheduler, including futures that have not yet been dropped,
+        so this must only be called once the tokio runtime that it ran in has been dropped.
+    */
+    pub(crate) unsafe fn free(self) {
+        // NOTE: The scheduler must be dropped first, since
+        // its threads and futures may still reference lua
+        drop(Scheduler::from_static(self.scheduler));
+        drop(Lua::from_static(self.lua));
+    }
+
     /**
         Sets arguments to give in `process.args` for Lune scripts.
     */
@@ -60,6 +82,128 @@ impl Lune {
         self
     }
 
+    /**
+        Enables deterministic mode, meant for testing time-dependent scripts.
+
+        In deterministic mode, `task.wait` and `task.delay` use a virtual clock that only
+        moves forward when scripts call `task.advanceTime`, meaning that tests run instantly,
+        and threads waiting on the clock are always resumed in the same order. The random
+        number generator used by `math.random` is also seeded using the given `seed`:
+
+        ```lua
+        local task = require("@lune/task")
+
+        task.delay(60, function()
+            print("One minute has passed")
+        end)
+
+        task.advanceTime(60) -- Prints right away
+        ```
+
+        Note that threads which are still waiting on the clock when the
+        script finishes will never be resumed, and that anything else which
+        waits for real time or other events, such as `net`, is not affected.
+    */
+    pub fn with_deterministic_mode(self, seed: i32) -> Result<Self, LuneError> {
+        self.scheduler.use_virtual_clock();
+        self.lua
+            .globals()
+            .get::<_, LuaTable>("math")?
+            .get::<_, LuaFunction>("randomseed")?
+            .call::<_, ()>(seed)?;
+        Ok(self)
+    }
+
+    /**
+        Mounts a bundle at the current working directory, to run scripts from.
+
+        Files in the bundle can then be required, and read using the `fs` library,
+        but can not be changed. Use [`Bundle::entrypoint`] to get the script to run:
+
+        ```rust,no_run
+        # use lune::{Bundle, Lune};
+        # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+        let bundle = Bundle::from_zip(std::fs::read("bundle.zip")?)?;
+        let (name, contents) = bundle.entrypoint();
+        Lune::new().with_bundle(bundle).run(name, contents).await?;
+        # Ok(())
+        # }
+        ```
+    */
+    pub fn with_bundle(self, bundle: Bundle) -> Self {
+        self.lua.set_app_data(Arc::new(bundle));
+        self
+    }
+
+    /**
+        Restricts modules at or inside of the given path to only
+        be able to require the given builtin libraries, by name.
+
+        This can be used to give less trusted modules, such as plugins,
+        access to only a subset of builtins, for example no `process`:
+
+        ```rust,no_run
+        # use lune::Lune;
+        let lune = Lune::new()
+            .with_sandbox("plugins", ["serde", "task"])
+            .expect("Failed to create sandbox");
+        ```
+
+        Sandboxed modules each get their own environment table, meaning
+        any globals they set will not be visible to any other modules. This
+        environment only contains the standard libraries and safe globals,
+        and functions such as `getfenv` and `loadstring` are not available.
+
+        Once a sandbox has been added, the original standard libraries are
+        readonly, meaning no module can replace functions in them anymore.
+
+        Note that this applies to modules loaded using `require`, and that
+        any module required by a sandboxed module is also loaded in the same
+        sandbox, even if it is outside of the given path. Relative paths are
+        resolved using the current working directory.
+    */
+    pub fn with_sandbox<P, B, S>(self, path: P, builtins: B) -> Result<Self, LuneError>
+    where
+        P: AsRef<Path>,
+        B: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let builtins = builtins
+            .into_iter()
+            .map(|name| name.as_ref().parse().map_err(LuaError::runtime))
+            .collect::<LuaResult<Vec<_>>>()?;
+        globals::add_require_sandbox(self.lua, path, builtins)?;
+        Ok(self)
+    }
+
+    /**
+        Creates a new channel for sending values from Rust to Lune scripts.
+
+        The receiving half of the channel is set as a global with the given name, and
+        Lune scripts can call `next` on it to wait for the next value, which returns
+        `nil` once all senders have been dropped and all values have been received:
+
+        ```lua
+        while true do
+            local event = events:next()
+            if event == nil then
+                break
+            end
+            print(event)
+        end
+        ```
+
+        Values are converted into Lua values using [`serde`], the same way as `serde.decode` would.
+
+        Note that any script waiting on the channel will keep running until all senders have been dropped.
+    */
+    pub fn channel<T>(&self, global_name: impl AsRef<str>) -> Result<LuneSender<T>, LuneError>
+    where
+        T: Serialize + Send + 'static,
+    {
+        Ok(channel::create(self.lua, global_name.as_ref())?)
+    }
+
     /**
         Runs a Lune script inside of the current runtime.
 
@@ -0,0 +1,79 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use super::thread::SchedulerThread;
+
+/**
+    Unique id for a thread sleeping on a [`SchedulerClock`],
+    which may be used to wake it up early or cancel it.
+*/
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct SchedulerTimerId(Duration, u64);
+
+/**
+    A virtual clock for a [`Scheduler`], used in deterministic mode.
+
+    Time on this clock only moves forward when explicitly advanced,
+    and sleeping threads are woken in order of their deadlines, with
+    threads that share the same deadline woken in the order they slept.
+*/
+#[derive(Debug, Default)]
+pub(super) struct SchedulerClock {
+    now: Duration,
+    next_sequence: u64,
+    sleepers: BTreeMap<(Duration, u64), SchedulerThread>,
+}
+
+impl SchedulerClock {
+    /**
+        Gets the current time on the clock, in time elapsed since it was created.
+    */
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    /**
+        Puts the given thread to sleep until the clock has advanced by `duration`.
+    */
+    pub fn sleep(&mut self, duration: Duration, thread: SchedulerThread) -> SchedulerTimerId {
+        let deadline = self.now + duration;
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.sleepers.insert((deadline, sequence), thread);
+        SchedulerTimerId(deadline, sequence)
+    }
+
+    /**
+        Removes a sleeping thread from the clock, so that it is never woken up.
+
+        Returns `None` if the thread has already been woken up.
+    */
+    pub fn cancel(&mut self, id: SchedulerTimerId) -> Option<SchedulerThread> {
+        self.sleepers.remove(&(id.0, id.1))
+    }
+
+    /**
+        Advances the clock to the earliest deadline at or before `target`,
+        and returns all threads that were sleeping until that deadline.
+
+        If there are no threads with such a deadline, the clock is advanced
+        to `target` and an empty vec is returned, meaning we are done stepping.
+    */
+    pub fn step(&mut self, target: Duration) -> Vec<SchedulerThread> {
+        let deadline = match self.sleepers.keys().next() {
+            Some((deadline, _)) if *deadline <= target => *deadline,
+            _ => {
+                self.now = self.now.max(target);
+                return Vec::new();
+            }
+        };
+        self.now = deadline;
+        let mut woken = Vec::new();
+        while let Some(entry) = self.sleepers.first_entry() {
+            if entry.key().0 != deadline {
+                break;
+            }
+            woken.push(entry.remove());
+        }
+        woken
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::hash_map::Entry;
+
 use futures_util::Future;
 use mlua::prelude::*;
 use tokio::{
@@ -5,7 +7,7 @@ use tokio::{
     task,
 };
 
-use super::{IntoLuaThread, Scheduler};
+use super::{IntoLuaThread, Scheduler, SchedulerThreadId};
 
 impl<'fut> Scheduler<'fut> {
     /**
@@ -99,6 +101,9 @@ impl<'fut> Scheduler<'fut> {
         Schedules the given `thread` to run when the given `fut` completes.
 
         If the given future returns a [`LuaError`], that error will be passed to the given `thread`.
+
+        If the given `thread` is cancelled using [`Scheduler::cancel_thread`] before the
+        future completes, the future is dropped, stopping any work it was still doing.
     */
     pub fn spawn_thread<F, FR>(
         &'fut self,
@@ -111,13 +116,38 @@ impl<'fut> Scheduler<'fut> {
         F: Future<Output = LuaResult<FR>> + 'fut,
     {
         let thread = thread.into_lua_thread(lua)?;
+        let thread_id = SchedulerThreadId::from(&thread);
         let futs = self.futures_lua.try_lock().expect(
             "Failed to lock futures queue - \
             can't schedule future lua threads during futures resumption",
         );
 
+        let (abort_tx, abort_rx) = oneshot::channel();
+        self.thread_aborts
+            .try_lock()
+            .expect("Failed to lock thread aborts")
+            .insert(thread_id, abort_tx);
+
         futs.push(Box::pin(async move {
-            match fut.await.and_then(|rets| rets.into_lua_multi(lua)) {
+            let res = tokio::select! {
+                res = fut => res,
+                Ok(()) = abort_rx => return,
+            };
+
+            // NOTE: The thread may have been resumed some other way and started waiting
+            // on a new future, in which case the abort sender is no longer ours to remove
+            if let Entry::Occupied(entry) = self
+                .thread_aborts
+                .try_lock()
+                .expect("Failed to lock thread aborts")
+                .entry(thread_id)
+            {
+                if entry.get().is_closed() {
+                    entry.remove();
+                }
+            }
+
+            match res.and_then(|rets| rets.into_lua_multi(lua)) {
                 Err(e) => {
                     self.push_err(lua, thread, e)
                         .expect("Failed to schedule future err thread");
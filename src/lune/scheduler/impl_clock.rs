@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use mlua::prelude::*;
+
+use super::{
+    clock::{SchedulerClock, SchedulerTimerId},
+    thread::SchedulerThread,
+    IntoLuaThread, Scheduler,
+};
+
+impl<'fut> Scheduler<'fut> {
+    /**
+        Switches the scheduler over to using a virtual clock.
+
+        While using a virtual clock, threads scheduled using
+        [`Scheduler::push_after`] will only be resumed once
+        the clock has been advanced using [`Scheduler::step_clock`].
+    */
+    pub fn use_virtual_clock(&self) {
+        let mut clock = self.clock.try_lock().expect("Failed to lock virtual clock");
+        if clock.is_none() {
+            *clock = Some(SchedulerClock::default());
+        }
+    }
+
+    /**
+        Checks if the scheduler is using a virtual clock.
+    */
+    pub fn is_using_virtual_clock(&self) -> bool {
+        self.clock
+            .try_lock()
+            .expect("Failed to lock virtual clock")
+            .is_some()
+    }
+
+    /**
+        Gets the current time of the virtual clock, if the scheduler is using one.
+    */
+    pub fn virtual_clock_now(&self) -> Option<Duration> {
+        self.clock
+            .try_lock()
+            .expect("Failed to lock virtual clock")
+            .as_ref()
+            .map(SchedulerClock::now)
+    }
+
+    /**
+        Schedules the `thread` to be resumed with the given `args`
+        once the virtual clock has been advanced by `duration`.
+
+        Returns `false` and does not schedule the thread
+        if the scheduler is not using a virtual clock.
+    */
+    pub fn push_after<'a>(
+        &self,
+        lua: &'a Lua,
+        thread: impl IntoLuaThread<'a>,
+        args: impl IntoLuaMulti<'a>,
+        duration: Duration,
+    ) -> LuaResult<bool> {
+        Ok(self.push_timer(lua, thread, args, duration)?.is_some())
+    }
+
+    /**
+        Same as [`Scheduler::push_after`], but returns an id that may be
+        used to cancel the sleeping thread using [`Scheduler::cancel_timer`].
+
+        Returns `None` and does not schedule the thread
+        if the scheduler is not using a virtual clock.
+    */
+    pub fn push_timer<'a>(
+        &self,
+        lua: &'a Lua,
+        thread: impl IntoLuaThread<'a>,
+        args: impl IntoLuaMulti<'a>,
+        duration: Duration,
+    ) -> LuaResult<Option<SchedulerTimerId>> {
+        let mut clock = self
+            .clock
+            .try_lock()
+            .into_lua_err()
+            .context("Failed to lock virtual clock")?;
+        match clock.as_mut() {
+            None => Ok(None),
+            Some(clock) => {
+                let thread = thread.into_lua_thread(lua)?;
+                let args = args.into_lua_multi(lua)?;
+                let id = clock.sleep(duration, SchedulerThread::new(lua, thread, args));
+                Ok(Some(id))
+            }
+        }
+    }
+
+    /**
+        Cancels a thread that was scheduled using [`Scheduler::push_timer`].
+
+        Returns `true` if the thread was cancelled, or `false`
+        if the virtual clock had already advanced past it.
+    */
+    pub fn cancel_timer(&self, lua: &Lua, id: SchedulerTimerId) -> LuaResult<bool> {
+        let cancelled = self
+            .clock
+            .try_lock()
+            .into_lua_err()
+            .context("Failed to lock virtual clock")?
+            .as_mut()
+            .and_then(|clock| clock.cancel(id));
+        match cancelled {
+            None => Ok(false),
+            Some(thread) => {
+                // NOTE: Extracting the thread also cleans up its registry values
+                thread.into_inner(lua);
+                Ok(true)
+            }
+        }
+    }
+
+    /**
+        Advances the virtual clock to the earliest deadline at or before
+        `target`, and schedules all threads sleeping until that deadline.
+
+        Returns `true` if any threads were woken up, in which case those
+        threads should be resumed before stepping the clock any further.
+    */
+    pub fn step_clock(&self, lua: &Lua, target: Duration) -> LuaResult<bool> {
+        let woken = match self
+            .clock
+            .try_lock()
+            .into_lua_err()
+            .context("Failed to lock virtual clock")?
+            .as_mut()
+        {
+            Some(clock) => clock.step(target),
+            None => {
+                return Err(LuaError::runtime(
+                    "The scheduler is not using a virtual clock",
+                ))
+            }
+        };
+        let any_woken = !woken.is_empty();
+        for thread in woken {
+            let (thread, args) = thread.into_inner(lua);
+            self.push_back(lua, thread, args)?;
+        }
+        Ok(any_woken)
+    }
+}
@@ -0,0 +1,162 @@
+use std::{mem, time::Duration};
+
+use mlua::prelude::*;
+use tokio::{
+    signal,
+    task::JoinHandle,
+    time::{timeout_at, Instant},
+};
+use tracing::debug;
+
+use crate::lune::util::traits::LuaEmitErrorExt;
+
+use super::{Scheduler, SchedulerSignal, SchedulerThreadId};
+
+/**
+    Exit code used when the scheduler is cancelled using Ctrl-C.
+
+    This follows the common convention of `128 + SIGINT`.
+*/
+const EXIT_CODE_CANCELLED: u8 = 130;
+
+/**
+    Maximum amount of time that all exit handlers may run for, combined.
+*/
+const EXIT_HANDLERS_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl<'fut> Scheduler<'fut> {
+    /**
+        Adds a handler function that will run when the scheduler exits.
+
+        Handlers run in reverse order of registration, one at a time, and
+        receive the exit code of the scheduler as their only argument.
+    */
+    pub fn add_exit_handler<'lua>(
+        &self,
+        lua: &'lua Lua,
+        handler: LuaFunction<'lua>,
+    ) -> LuaResult<()> {
+        let key = lua.create_registry_value(handler)?;
+        self.exit_handlers
+            .try_lock()
+            .into_lua_err()
+            .context("Failed to lock exit handlers vec")?
+            .push(key);
+        Ok(())
+    }
+
+    /**
+        Spawns a background task that listens for Ctrl-C.
+
+        The first Ctrl-C will cancel the scheduler gracefully, letting exit
+        handlers run, and the second Ctrl-C will exit the process immediately.
+
+        If any handlers have been added for `SIGINT`, the first Ctrl-C will
+        run those handlers instead, and not cancel the scheduler. The second
+        Ctrl-C will always exit the process, no matter which handlers exist.
+
+        The returned handle should be aborted once the scheduler has finished.
+    */
+    pub(super) fn spawn_ctrl_c_listener(&self) -> JoinHandle<()> {
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let mut presses = 0;
+            while signal::ctrl_c().await.is_ok() {
+                presses += 1;
+                if presses > 1 {
+                    debug!("received second ctrl-c, exiting");
+                    std::process::exit(EXIT_CODE_CANCELLED.into());
+                }
+                if state.is_signal_handled(SchedulerSignal::Interrupt) {
+                    debug!("received ctrl-c, running signal handlers");
+                    state.push_pending_signal(SchedulerSignal::Interrupt);
+                } else {
+                    debug!("received ctrl-c, cancelling scheduler");
+                    state.cancel(EXIT_CODE_CANCELLED);
+                }
+            }
+        })
+    }
+
+    /**
+        Runs all exit handlers, in reverse order of registration.
+
+        Any lua threads and futures that were still pending will be dropped
+        before the exit handlers run, and exit handlers will stop running
+        once they have used up their combined time budget.
+    */
+    pub(super) async fn run_exit_handlers(&self, lua: &Lua) {
+        let handlers = mem::take(&mut *self.exit_handlers.lock().await);
+        if handlers.is_empty() {
+            return;
+        }
+
+        self.drop_pending(lua).await;
+        self.state.set_running_exit_handlers(true);
+
+        let code = match self.state.exit_code() {
+            Some(code) => code,
+            None if self.state.has_errored() => 1,
+            None => 0,
+        };
+
+        let deadline = Instant::now() + EXIT_HANDLERS_TIMEOUT;
+        for key in handlers.into_iter().rev() {
+            let handler = lua
+                .registry_value::<LuaFunction>(&key)
+                .expect("Failed to get exit handler from registry");
+            lua.remove_registry_value(key)
+                .expect("Failed to remove exit handler from registry");
+
+            let thread_id = match self.push_back(lua, handler, code) {
+                Ok(id) => id,
+                Err(e) => {
+                    lua.emit_error(e);
+                    continue;
+                }
+            };
+
+            let fut = self.run_until_finished(lua, thread_id);
+            if timeout_at(deadline, fut).await.is_err() {
+                lua.emit_error(LuaError::RuntimeError(format!(
+                    "Exit handlers did not finish within {} seconds",
+                    EXIT_HANDLERS_TIMEOUT.as_secs()
+                )));
+                break;
+            }
+        }
+
+        self.state.set_running_exit_handlers(false);
+    }
+
+    /**
+        Drops all pending lua threads and futures, without resuming them.
+    */
+    async fn drop_pending(&self, lua: &Lua) {
+        for thread in self.threads.lock().await.drain() {
+            thread.into_inner(lua);
+        }
+        self.thread_senders.lock().await.clear();
+        self.thread_aborts.lock().await.clear();
+        self.futures_lua.lock().await.clear();
+        self.futures_background.lock().await.clear();
+    }
+
+    /**
+        Runs lua threads and futures until the given thread has finished,
+        or until there are no more lua threads or futures left to run.
+    */
+    async fn run_until_finished(&self, lua: &Lua, thread_id: SchedulerThreadId) {
+        loop {
+            self.run_lua_threads(lua);
+
+            let is_pending = self.thread_senders.lock().await.contains_key(&thread_id);
+            let (has_future_lua, has_future_background) = self.has_futures();
+            if !is_pending || (!has_future_lua && !has_future_background && !self.has_thread()) {
+                break;
+            }
+
+            self.run_futures().await;
+        }
+    }
+}
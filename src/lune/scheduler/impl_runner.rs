@@ -1,4 +1,8 @@
-use std::{process::ExitCode, sync::Arc};
+use std::{
+    process::ExitCode,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use futures_util::StreamExt;
 use mlua::prelude::*;
@@ -8,14 +12,19 @@ use tracing::debug;
 
 use crate::lune::util::traits::LuaEmitErrorExt;
 
-use super::Scheduler;
+use super::{thread::store_multi_value, Scheduler};
+
+/**
+    The amount of time a preemptible thread may run for before it gets yielded.
+*/
+const PREEMPT_TIME_SLICE: Duration = Duration::from_millis(10);
 
 impl<'fut> Scheduler<'fut> {
     /**
         Runs all lua threads to completion.
     */
-    fn run_lua_threads(&self, lua: &Lua) {
-        if self.state.has_exit_code() {
+    pub(super) fn run_lua_threads(&self, lua: &Lua) {
+        if self.state.should_stop() {
             return;
         }
 
@@ -38,23 +47,49 @@ impl<'fut> Scheduler<'fut> {
 
             // Resume the thread, ensuring that the schedulers
             // current thread id is set correctly for error catching
+            if self.state.is_thread_preemptible(thread_id) {
+                self.state
+                    .set_preempt_deadline(Some(Instant::now() + PREEMPT_TIME_SLICE));
+            }
             self.state.set_current_thread_id(Some(thread_id));
+            let resumed_at = Instant::now();
             let res = thread.resume::<_, LuaMultiValue>(args);
+            self.state
+                .record_thread_resume(thread_id, resumed_at.elapsed());
             self.state.set_current_thread_id(None);
+            let was_preempted = self.state.was_preempted();
+            self.state.set_preempt_deadline(None);
 
             count += 1;
 
             // If we got any resumption (lua-side) error, increment
             // the error count of the scheduler so we can exit with
-            // a non-zero exit code, and print it out to stderr
+            // a non-zero exit code, and print it out to stderr,
+            // unless the error is handled by whatever spawned the thread
             if let Err(err) = &res {
-                self.state.increment_error_count();
-                lua.emit_error(err.clone());
+                if !self.state.are_thread_errors_handled(thread_id) {
+                    self.state.increment_error_count();
+                    match self.state.get_thread_name(thread_id) {
+                        Some(name) => lua.emit_error_in_thread(err.clone(), name),
+                        None => lua.emit_error(err.clone()),
+                    }
+                }
+            }
+
+            // If the thread was preempted it is not waiting for anything, and
+            // should continue after other threads have had a chance to run, we
+            // also break here to give any ready futures a chance to run first
+            if was_preempted && res.is_ok() && thread.status() == LuaThreadStatus::Resumable {
+                self.push_back(lua, thread, ())
+                    .expect("Failed to reschedule preempted thread");
+                break;
             }
 
             // If the thread has finished running completely,
             // send results of final resume to any listeners
             if thread.status() != LuaThreadStatus::Resumable {
+                self.state.remove_thread_name(thread_id);
+                self.state.remove_thread_options(thread_id);
                 // NOTE: Threads that were spawned to resume
                 // with an error will not have a result sender
                 if let Some(sender) = self
@@ -66,7 +101,7 @@ impl<'fut> Scheduler<'fut> {
                     if sender.receiver_count() > 0 {
                         let stored = match res {
                             Err(e) => Err(e),
-                            Ok(v) => Ok(Arc::new(lua.create_registry_value(v.into_vec()).expect(
+                            Ok(v) => Ok(Arc::new(store_multi_value(lua, v).expect(
                                 "Failed to store thread results in registry - out of memory",
                             ))),
                         };
@@ -77,7 +112,7 @@ impl<'fut> Scheduler<'fut> {
                 }
             }
 
-            if self.state.has_exit_code() {
+            if self.state.should_stop() {
                 break;
             }
         }
@@ -146,7 +181,7 @@ impl<'fut> Scheduler<'fut> {
         and break out whenever the other corresponding queue has
         a new future, since the other queue may resume sooner.
     */
-    async fn run_futures(&self) {
+    pub(super) async fn run_futures(&self) {
         let (mut has_lua, mut has_background) = self.has_futures();
         if !has_lua && !has_background {
             return;
@@ -220,8 +255,12 @@ impl<'fut> Scheduler<'fut> {
         let set = LocalSet::new();
         let _guard = set.enter();
 
+        let ctrl_c_listener = self.spawn_ctrl_c_listener();
+
         loop {
-            // 1. Run lua threads until exit or there are none left
+            // 1. Run handlers for any signals we received, and
+            // then lua threads until exit or there are none left
+            self.push_signal_handlers(lua);
             self.run_lua_threads(lua);
 
             // 2. If we got a manual exit code from lua we should
@@ -243,11 +282,21 @@ impl<'fut> Scheduler<'fut> {
             // 5. If we have no lua threads or futures remaining,
             // we have now run the scheduler until completion
             let (has_future_lua, has_future_background) = self.has_futures();
-            if !has_future_lua && !has_future_background && !self.has_thread() {
+            if !has_future_lua
+                && !has_future_background
+                && !self.has_thread()
+                && !self.state.has_pending_signals()
+            {
                 break;
             }
         }
 
+        // 6. Give any exit handlers a chance to clean up, no matter
+        // how we exited, they will run until they finish or time out
+        self.run_exit_handlers(lua).await;
+
+        ctrl_c_listener.abort();
+
         if let Some(code) = self.state.exit_code() {
             debug! {
                 %code,
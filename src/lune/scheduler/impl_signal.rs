@@ -0,0 +1,64 @@
+use mlua::prelude::*;
+
+use crate::lune::util::traits::LuaEmitErrorExt;
+
+use super::{Scheduler, SchedulerSignal};
+
+impl<'fut> Scheduler<'fut> {
+    /**
+        Adds a handler function that will run every time the given signal is received.
+
+        Handlers run in order of registration, and receive the name of the signal
+        as their only argument. Once a signal has any handlers, it will no longer
+        cancel the scheduler or terminate the process when it is received, except
+        for a second Ctrl-C, see [`Scheduler::spawn_ctrl_c_listener`].
+    */
+    pub fn add_signal_handler<'lua>(
+        &self,
+        lua: &'lua Lua,
+        signal: SchedulerSignal,
+        handler: LuaFunction<'lua>,
+    ) -> LuaResult<()> {
+        let key = lua.create_registry_value(handler)?;
+        self.signal_handlers
+            .try_lock()
+            .into_lua_err()
+            .context("Failed to lock signal handlers map")?
+            .entry(signal)
+            .or_default()
+            .push(key);
+        if self.state.set_signal_handled(signal) {
+            signal
+                .spawn_listener(self.state.clone())
+                .into_lua_err()
+                .with_context(|_| format!("Failed to listen for {}", signal.name()))?;
+        }
+        Ok(())
+    }
+
+    /**
+        Schedules handlers for any signals that have been received since the last
+        call, to be resumed after all other currently scheduled lua threads.
+    */
+    pub(super) fn push_signal_handlers(&self, lua: &Lua) {
+        let signals = self.state.take_pending_signals();
+        if signals.is_empty() || self.state.should_stop() {
+            return;
+        }
+
+        let handlers = self
+            .signal_handlers
+            .try_lock()
+            .expect("Failed to lock signal handlers map");
+        for signal in signals {
+            for key in handlers.get(&signal).into_iter().flatten() {
+                let handler = lua
+                    .registry_value::<LuaFunction>(key)
+                    .expect("Failed to get signal handler from registry");
+                if let Err(e) = self.push_back(lua, handler, signal.name()) {
+                    lua.emit_error(e);
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use std::sync::Arc;
 
+use futures_util::Future;
 use mlua::prelude::*;
+use tokio::sync::broadcast::Receiver;
 
 use super::{
-    thread::{SchedulerThread, SchedulerThreadId, SchedulerThreadSender},
-    IntoLuaThread, Scheduler,
+    thread::{load_multi_value, SchedulerThread, SchedulerThreadId, SchedulerThreadSender},
+    IntoLuaThread, Scheduler, SchedulerPriority,
 };
 
 impl<'fut> Scheduler<'fut> {
@@ -20,7 +22,8 @@ impl<'fut> Scheduler<'fut> {
     }
 
     /**
-        Pops the next thread to run, from the front of the scheduler.
+        Pops the next thread to run, from the front of the scheduler,
+        respecting thread priorities as described in [`SchedulerPriority`].
 
         Returns `None` if there are no threads left to run.
     */
@@ -57,7 +60,7 @@ impl<'fut> Scheduler<'fut> {
             .try_lock()
             .into_lua_err()
             .context("Failed to lock threads vec")?
-            .push_front(thread);
+            .push_front(self.state.get_thread_priority(thread_id), thread);
 
         // NOTE: We might be resuming futures, need to signal that a
         // new lua thread is ready to break out of futures resumption
@@ -86,7 +89,7 @@ impl<'fut> Scheduler<'fut> {
             .try_lock()
             .into_lua_err()
             .context("Failed to lock threads vec")?
-            .push_front(thread);
+            .push_front(self.state.get_thread_priority(thread_id), thread);
 
         // NOTE: We might be resuming the same thread several times and
         // pushing it to the scheduler several times before it is done,
@@ -125,7 +128,7 @@ impl<'fut> Scheduler<'fut> {
             .try_lock()
             .into_lua_err()
             .context("Failed to lock threads vec")?
-            .push_back(thread);
+            .push_back(self.state.get_thread_priority(thread_id), thread);
 
         // NOTE: We might be resuming the same thread several times and
         // pushing it to the scheduler several times before it is done,
@@ -144,6 +147,25 @@ impl<'fut> Scheduler<'fut> {
         Ok(thread_id)
     }
 
+    /**
+        Gets the name of the given thread, if one has been set.
+
+        Note that names are removed once their thread has finished running.
+    */
+    pub fn get_thread_name(&self, thread_id: SchedulerThreadId) -> Option<String> {
+        self.state.get_thread_name(thread_id)
+    }
+
+    /**
+        Sets the name of the given thread.
+
+        This name will be used when reporting errors for
+        the thread, and when gathering scheduler stats.
+    */
+    pub fn set_thread_name(&self, thread_id: SchedulerThreadId, name: impl Into<String>) {
+        self.state.set_thread_name(thread_id, name);
+    }
+
     /**
         Waits for the given thread to finish running, and returns its result.
     */
@@ -152,34 +174,169 @@ impl<'fut> Scheduler<'fut> {
         lua: &'a Lua,
         thread_id: SchedulerThreadId,
     ) -> LuaResult<LuaMultiValue<'a>> {
-        let mut recv = {
+        let recv = {
             let senders = self.thread_senders.lock().await;
             let sender = senders
                 .get(&thread_id)
                 .expect("Tried to wait for thread that is not queued");
             sender.subscribe()
         };
-        let res = match recv.recv().await {
-            Err(_) => panic!("Sender was dropped while waiting for {thread_id:?}"),
-            Ok(r) => r,
-        };
-        match res {
-            Err(e) => Err(e),
-            Ok(k) => {
-                let vals = lua
-                    .registry_value::<Vec<LuaValue>>(&k)
-                    .expect("Received invalid registry key for thread");
-
-                // NOTE: This is not strictly necessary, mlua can clean
-                // up registry values on its own, but doing this will add
-                // some extra safety and clean up registry values faster
-                if let Some(key) = Arc::into_inner(k) {
-                    lua.remove_registry_value(key)
-                        .expect("Failed to remove registry key for thread");
-                }
-
-                Ok(LuaMultiValue::from_vec(vals))
+        receive_thread_result(lua, thread_id, recv).await
+    }
+
+    /**
+        Same as [`Scheduler::wait_for_thread`], but subscribes to the result of the
+        thread right away, instead of once the returned future is first polled.
+
+        This makes it possible to wait for a thread from a future that
+        is spawned before the thread is resumed, but polled after.
+    */
+    pub fn subscribe_to_thread<'a>(
+        &self,
+        lua: &'a Lua,
+        thread_id: SchedulerThreadId,
+    ) -> LuaResult<impl Future<Output = LuaResult<LuaMultiValue<'a>>>> {
+        let recv = self
+            .thread_senders
+            .try_lock()
+            .into_lua_err()
+            .context("Failed to lock thread senders vec")?
+            .get(&thread_id)
+            .ok_or_else(|| LuaError::runtime("Tried to subscribe to thread that is not queued"))?
+            .subscribe();
+        Ok(receive_thread_result(lua, thread_id, recv))
+    }
+
+    /**
+        Sets the priority of the given thread.
+
+        Threads inherit no priority from the thread that spawned them, and
+        this must be set before the thread is scheduled for it to take effect.
+    */
+    pub fn set_thread_priority(&self, thread_id: SchedulerThreadId, priority: SchedulerPriority) {
+        self.state.set_thread_priority(thread_id, priority);
+    }
+
+    /**
+        Sets if the given thread may be preempted.
+
+        Preemptible threads that run for longer than a short time slice without
+        yielding are yielded automatically, and resumed again after any other
+        currently scheduled threads with the same priority have been resumed.
+
+        Note that a thread can only be preempted where it could also
+        yield normally, and not inside metamethods or sort functions.
+    */
+    pub fn set_thread_preemptible(&self, thread_id: SchedulerThreadId, preemptible: bool) {
+        self.state.set_thread_preemptible(thread_id, preemptible);
+    }
+
+    /**
+        Sets if errors for the given thread are handled by whatever spawned it.
+
+        Errors for threads that are handled are not reported, and do not make
+        Lune exit with a non-zero exit code, but are still sent to anything
+        waiting for the thread to finish, such as [`Scheduler::wait_for_thread`].
+    */
+    pub fn set_thread_errors_handled(&self, thread_id: SchedulerThreadId, handled: bool) {
+        self.state.set_thread_errors_handled(thread_id, handled);
+    }
+
+    /**
+        Cancels the given thread, closing it so that it will never resume again.
+
+        Anything waiting for the thread to finish, such as [`Scheduler::wait_for_thread`],
+        will receive an error saying that the thread was cancelled, and any future that the
+        thread was waiting on, such as a request or a sleep, is dropped without completing.
+    */
+    pub fn cancel_thread<'a>(&self, lua: &'a Lua, thread: LuaThread<'a>) -> LuaResult<()> {
+        let thread_id = SchedulerThreadId::from(&thread);
+
+        let close = lua
+            .globals()
+            .get::<_, LuaTable>("coroutine")?
+            .get::<_, LuaFunction>("close")?;
+        match close.call::<_, ()>(thread) {
+            Ok(()) | Err(LuaError::CoroutineInactive) => {}
+            Err(e) => return Err(e),
+        }
+
+        self.state.remove_thread_name(thread_id);
+        self.state.remove_thread_options(thread_id);
+        if let Some(abort) = self
+            .thread_aborts
+            .try_lock()
+            .into_lua_err()
+            .context("Failed to lock thread aborts")?
+            .remove(&thread_id)
+        {
+            abort.send(()).ok();
+        }
+        if let Some(sender) = self
+            .thread_senders
+            .try_lock()
+            .into_lua_err()
+            .context("Failed to lock thread senders vec")?
+            .remove(&thread_id)
+        {
+            if sender.receiver_count() > 0 {
+                sender
+                    .send(Err(LuaError::runtime("Thread was cancelled")))
+                    .expect("Failed to broadcast thread results");
             }
         }
+
+        Ok(())
+    }
+
+    /**
+        Creates a future that completes once the given thread has finished running.
+
+        Returns `None` if the thread is not currently tracked by the
+        scheduler, meaning it has either already finished running, or
+        has never been scheduled, and may be resumed some other way.
+    */
+    pub fn thread_finished(
+        &self,
+        thread_id: SchedulerThreadId,
+    ) -> Option<impl Future<Output = ()>> {
+        let mut recv = self
+            .thread_senders
+            .try_lock()
+            .ok()?
+            .get(&thread_id)?
+            .subscribe();
+        Some(async move {
+            // NOTE: We only care about the thread finishing, not its result,
+            // and a dropped sender also means the thread will never resume
+            recv.recv().await.ok();
+        })
+    }
+}
+
+async fn receive_thread_result(
+    lua: &Lua,
+    thread_id: SchedulerThreadId,
+    mut recv: Receiver<LuaResult<Arc<LuaRegistryKey>>>,
+) -> LuaResult<LuaMultiValue<'_>> {
+    let res = match recv.recv().await {
+        Err(_) => panic!("Sender was dropped while waiting for {thread_id:?}"),
+        Ok(r) => r,
+    };
+    match res {
+        Err(e) => Err(e),
+        Ok(k) => {
+            let vals = load_multi_value(lua, &k).expect("Received invalid registry key for thread");
+
+            // NOTE: This is not strictly necessary, mlua can clean
+            // up registry values on its own, but doing this will add
+            // some extra safety and clean up registry values faster
+            if let Some(key) = Arc::into_inner(k) {
+                lua.remove_registry_value(key)
+                    .expect("Failed to remove registry key for thread");
+            }
+
+            Ok(vals)
+        }
     }
 }
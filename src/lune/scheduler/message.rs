@@ -10,11 +10,15 @@ pub(crate) enum SchedulerMessage {
     PushedLuaThread,
     SpawnedLuaFuture,
     SpawnedBackgroundFuture,
+    ReceivedSignal,
 }
 
 impl SchedulerMessage {
     pub fn should_break_futures(self) -> bool {
-        matches!(self, Self::ExitCodeSet | Self::PushedLuaThread)
+        matches!(
+            self,
+            Self::ExitCodeSet | Self::PushedLuaThread | Self::ReceivedSignal
+        )
     }
 
     pub fn should_break_lua_futures(self) -> bool {
@@ -63,6 +67,10 @@ impl SchedulerMessageSender {
     pub fn send_spawned_background_future(&self) {
         self.0.send(SchedulerMessage::SpawnedBackgroundFuture).ok();
     }
+
+    pub fn send_received_signal(&self) {
+        self.0.send(SchedulerMessage::ReceivedSignal).ok();
+    }
 }
 
 /**
@@ -1,28 +1,34 @@
-use std::{
-    collections::{HashMap, VecDeque},
-    pin::Pin,
-    sync::Arc,
-};
+use std::{collections::HashMap, pin::Pin, sync::Arc};
 
 use futures_util::{stream::FuturesUnordered, Future};
 use mlua::prelude::*;
-use tokio::sync::Mutex as AsyncMutex;
+use tokio::sync::{oneshot, Mutex as AsyncMutex};
 
+mod clock;
 mod message;
+mod priority;
+mod signal;
 mod state;
+mod stats;
 mod thread;
 mod traits;
 
 mod impl_async;
+mod impl_clock;
+mod impl_exit;
 mod impl_runner;
+mod impl_signal;
 mod impl_threads;
 
+pub use self::clock::SchedulerTimerId;
+pub use self::priority::SchedulerPriority;
+pub use self::signal::SchedulerSignal;
 pub use self::thread::SchedulerThreadId;
 pub use self::traits::*;
 
 use self::{
-    state::SchedulerState,
-    thread::{SchedulerThread, SchedulerThreadSender},
+    clock::SchedulerClock, priority::SchedulerThreadQueue, state::SchedulerState,
+    thread::SchedulerThreadSender,
 };
 
 type SchedulerFuture<'fut> = Pin<Box<dyn Future<Output = ()> + 'fut>>;
@@ -36,8 +42,12 @@ type SchedulerFuture<'fut> = Pin<Box<dyn Future<Output = ()> + 'fut>>;
 #[derive(Debug, Clone)]
 pub(crate) struct Scheduler<'fut> {
     state: Arc<SchedulerState>,
-    threads: Arc<AsyncMutex<VecDeque<SchedulerThread>>>,
+    threads: Arc<AsyncMutex<SchedulerThreadQueue>>,
     thread_senders: Arc<AsyncMutex<HashMap<SchedulerThreadId, SchedulerThreadSender>>>,
+    thread_aborts: Arc<AsyncMutex<HashMap<SchedulerThreadId, oneshot::Sender<()>>>>,
+    exit_handlers: Arc<AsyncMutex<Vec<LuaRegistryKey>>>,
+    signal_handlers: Arc<AsyncMutex<HashMap<SchedulerSignal, Vec<LuaRegistryKey>>>>,
+    clock: Arc<AsyncMutex<Option<SchedulerClock>>>,
     /*
         FUTURE: Get rid of these, let the tokio runtime handle running
         and resumption of futures completely, just use our scheduler
@@ -67,8 +77,12 @@ impl<'fut> Scheduler<'fut> {
     pub fn new() -> Self {
         Self {
             state: Arc::new(SchedulerState::new()),
-            threads: Arc::new(AsyncMutex::new(VecDeque::new())),
+            threads: Arc::new(AsyncMutex::new(SchedulerThreadQueue::default())),
             thread_senders: Arc::new(AsyncMutex::new(HashMap::new())),
+            thread_aborts: Arc::new(AsyncMutex::new(HashMap::new())),
+            exit_handlers: Arc::new(AsyncMutex::new(Vec::new())),
+            signal_handlers: Arc::new(AsyncMutex::new(HashMap::new())),
+            clock: Arc::new(AsyncMutex::new(None)),
             futures_lua: Arc::new(AsyncMutex::new(FuturesUnordered::new())),
             futures_background: Arc::new(AsyncMutex::new(FuturesUnordered::new())),
         }
@@ -78,17 +92,25 @@ impl<'fut> Scheduler<'fut> {
         Sets the luau interrupt for this scheduler.
 
         This will propagate errors from any lua-spawned
-        futures back to the lua threads that spawned them.
+        futures back to the lua threads that spawned them,
+        and preempt preemptible threads that run for too long.
     */
     pub fn set_interrupt_for(&self, lua: &Lua) {
         // Propagate errors given to the scheduler back to their lua threads
         // FUTURE: Do profiling and anything else we need inside of this interrupt
         let state = self.state.clone();
-        lua.set_interrupt(move |_| {
+        lua.set_interrupt(move |lua| {
             if let Some(id) = state.get_current_thread_id() {
                 if let Some(err) = state.get_thread_error(id) {
                     return Err(err);
                 }
+                // NOTE: We must only ever yield the thread that the scheduler
+                // resumed, yielding any coroutine that it resumed by itself
+                // would instead return control back to the resuming thread
+                if state.should_preempt() && SchedulerThreadId::from(&lua.current_thread()) == id {
+                    state.set_preempted();
+                    return Ok(LuaVmState::Yield);
+                }
             }
             Ok(LuaVmState::Continue)
         });
@@ -99,11 +121,12 @@ impl<'fut> Scheduler<'fut> {
 
         This will stop the scheduler from resuming any more lua threads or futures.
 
-        Panics if the exit code is set more than once.
+        Panics if the exit code is set more than once, unless
+        it is being set from within one of the exit handlers.
     */
     pub fn set_exit_code(&self, code: impl Into<u8>) {
         assert!(
-            self.state.exit_code().is_none(),
+            self.state.exit_code().is_none() || self.state.is_running_exit_handlers(),
             "Exit code may only be set exactly once"
         );
         self.state.set_exit_code(code.into());
@@ -113,4 +136,16 @@ impl<'fut> Scheduler<'fut> {
     pub fn into_static(self) -> &'static Self {
         Box::leak(Box::new(self))
     }
+
+    /**
+        Constructs a scheduler from a static reference created using [`Scheduler::into_static`].
+
+        # Safety
+
+        The static reference, and any copies of it, must not be used after this.
+    */
+    #[doc(hidden)]
+    pub unsafe fn from_static(sched: &'static Self) -> Self {
+        *Box::from_raw(sched as *const Self as *mut Self)
+    }
 }
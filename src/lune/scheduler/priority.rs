@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+
+use super::thread::SchedulerThread;
+
+/**
+    The number of times in a row that a non-empty queue may be skipped in favor
+    of a higher priority queue, before one of its threads is resumed anyway.
+
+    This makes sure that threads with a lower priority can never be
+    starved completely by threads with a higher priority that keep
+    scheduling themselves, such as a busy web socket handler.
+*/
+const MAX_SKIPPED_POPS: usize = 8;
+
+/**
+    The priority of a lua thread in the [`Scheduler`].
+
+    Threads with a higher priority are resumed before threads with a lower
+    priority, but lower priorities are still guaranteed to eventually resume.
+*/
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum SchedulerPriority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl SchedulerPriority {
+    pub const ALL: [Self; 3] = [Self::High, Self::Normal, Self::Low];
+
+    /**
+        Gets the name of the priority, as used from lua.
+    */
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::High => "high",
+            Self::Normal => "normal",
+            Self::Low => "low",
+        }
+    }
+
+    /**
+        Parses a priority from its name, as used from lua.
+    */
+    pub fn from_name(name: impl AsRef<str>) -> Option<Self> {
+        let name = name.as_ref();
+        Self::ALL
+            .into_iter()
+            .find(|priority| priority.name() == name)
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Self::High => 0,
+            Self::Normal => 1,
+            Self::Low => 2,
+        }
+    }
+}
+
+/**
+    Queues of lua threads waiting to be resumed, one for each [`SchedulerPriority`].
+*/
+#[derive(Debug, Default)]
+pub(super) struct SchedulerThreadQueue {
+    queues: [VecDeque<SchedulerThread>; 3],
+    skipped: [usize; 3],
+}
+
+impl SchedulerThreadQueue {
+    pub fn is_empty(&self) -> bool {
+        self.queues.iter().all(VecDeque::is_empty)
+    }
+
+    pub fn push_front(&mut self, priority: SchedulerPriority, thread: SchedulerThread) {
+        self.queues[priority.index()].push_front(thread);
+    }
+
+    pub fn push_back(&mut self, priority: SchedulerPriority, thread: SchedulerThread) {
+        self.queues[priority.index()].push_back(thread);
+    }
+
+    /**
+        Pops the next thread to resume, from the highest priority queue that has
+        any threads, unless a lower priority queue has been skipped too many times.
+    */
+    pub fn pop_front(&mut self) -> Option<SchedulerThread> {
+        let mut chosen = None;
+        for (index, queue) in self.queues.iter().enumerate() {
+            if queue.is_empty() {
+                self.skipped[index] = 0;
+            } else if chosen.is_none() || self.skipped[index] >= MAX_SKIPPED_POPS {
+                chosen = Some(index);
+            }
+        }
+        let chosen = chosen?;
+        for (index, queue) in self.queues.iter().enumerate() {
+            if index == chosen {
+                self.skipped[index] = 0;
+            } else if index > chosen && !queue.is_empty() {
+                self.skipped[index] += 1;
+            }
+        }
+        self.queues[chosen].pop_front()
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = SchedulerThread> + '_ {
+        self.skipped = [0; 3];
+        self.queues.iter_mut().flat_map(|queue| queue.drain(..))
+    }
+}
@@ -0,0 +1,93 @@
+use std::{io, sync::Arc};
+
+use super::state::SchedulerState;
+
+/**
+    A signal that may be sent to the Lune process, and handled by the scheduler.
+
+    On Windows, where signals do not exist, these map to the closest console events:
+
+    * `Interrupt` - Ctrl-C
+    * `Terminate` - The system shutting down
+    * `Hangup` - The console window being closed
+*/
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum SchedulerSignal {
+    Interrupt,
+    Terminate,
+    Hangup,
+}
+
+impl SchedulerSignal {
+    pub const ALL: [Self; 3] = [Self::Interrupt, Self::Terminate, Self::Hangup];
+
+    /**
+        Gets the conventional name of the signal, such as `SIGINT`.
+    */
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Interrupt => "SIGINT",
+            Self::Terminate => "SIGTERM",
+            Self::Hangup => "SIGHUP",
+        }
+    }
+
+    /**
+        Parses a signal from its conventional name, such as `SIGINT`.
+    */
+    pub fn from_name(name: impl AsRef<str>) -> Option<Self> {
+        let name = name.as_ref();
+        Self::ALL.into_iter().find(|signal| signal.name() == name)
+    }
+
+    /**
+        Spawns a background task that listens for this signal,
+        and queues it for the scheduler every time it is received.
+
+        Note that Ctrl-C is always listened for by the scheduler
+        itself, so this does nothing for [`SchedulerSignal::Interrupt`].
+    */
+    pub(super) fn spawn_listener(self, state: Arc<SchedulerState>) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let kind = match self {
+                Self::Interrupt => return Ok(()),
+                Self::Terminate => SignalKind::terminate(),
+                Self::Hangup => SignalKind::hangup(),
+            };
+            let mut stream = signal(kind)?;
+            tokio::spawn(async move {
+                while stream.recv().await.is_some() {
+                    state.push_pending_signal(self);
+                }
+            });
+        }
+
+        #[cfg(windows)]
+        {
+            use tokio::signal::windows::{ctrl_close, ctrl_shutdown};
+            match self {
+                Self::Interrupt => {}
+                Self::Terminate => {
+                    let mut stream = ctrl_shutdown()?;
+                    tokio::spawn(async move {
+                        while stream.recv().await.is_some() {
+                            state.push_pending_signal(self);
+                        }
+                    });
+                }
+                Self::Hangup => {
+                    let mut stream = ctrl_close()?;
+                    tokio::spawn(async move {
+                        while stream.recv().await.is_some() {
+                            state.push_pending_signal(self);
+                        }
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
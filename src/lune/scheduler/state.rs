@@ -1,9 +1,10 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     sync::{
         atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
         Arc, Mutex,
     },
+    time::{Duration, Instant},
 };
 
 use mlua::Error as LuaError;
@@ -12,7 +13,7 @@ use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 use super::{
     message::{SchedulerMessage, SchedulerMessageReceiver, SchedulerMessageSender},
-    SchedulerThreadId,
+    SchedulerPriority, SchedulerSignal, SchedulerThreadId,
 };
 
 /**
@@ -25,10 +26,20 @@ use super::{
 pub(crate) struct SchedulerState {
     exit_state: AtomicBool,
     exit_code: AtomicU8,
+    exit_handlers_running: AtomicBool,
     num_resumptions: AtomicUsize,
     num_errors: AtomicUsize,
     thread_id: Arc<Mutex<Option<SchedulerThreadId>>>,
     thread_errors: Arc<Mutex<HashMap<SchedulerThreadId, LuaError>>>,
+    thread_names: Arc<Mutex<HashMap<SchedulerThreadId, String>>>,
+    thread_profile: Arc<Mutex<HashMap<String, (usize, Duration)>>>,
+    thread_priorities: Arc<Mutex<HashMap<SchedulerThreadId, SchedulerPriority>>>,
+    thread_preemptible: Arc<Mutex<HashSet<SchedulerThreadId>>>,
+    thread_errors_handled: Arc<Mutex<HashSet<SchedulerThreadId>>>,
+    preempt_deadline: Arc<Mutex<Option<Instant>>>,
+    preempted: AtomicBool,
+    signals_handled: Arc<Mutex<HashSet<SchedulerSignal>>>,
+    signals_pending: Arc<Mutex<Vec<SchedulerSignal>>>,
     pub(super) message_sender: Arc<Mutex<UnboundedSender<SchedulerMessage>>>,
     pub(super) message_receiver: Arc<Mutex<UnboundedReceiver<SchedulerMessage>>>,
 }
@@ -43,10 +54,20 @@ impl SchedulerState {
         Self {
             exit_state: AtomicBool::new(false),
             exit_code: AtomicU8::new(0),
+            exit_handlers_running: AtomicBool::new(false),
             num_resumptions: AtomicUsize::new(0),
             num_errors: AtomicUsize::new(0),
             thread_id: Arc::new(Mutex::new(None)),
             thread_errors: Arc::new(Mutex::new(HashMap::new())),
+            thread_names: Arc::new(Mutex::new(HashMap::new())),
+            thread_profile: Arc::new(Mutex::new(HashMap::new())),
+            thread_priorities: Arc::new(Mutex::new(HashMap::new())),
+            thread_preemptible: Arc::new(Mutex::new(HashSet::new())),
+            thread_errors_handled: Arc::new(Mutex::new(HashSet::new())),
+            preempt_deadline: Arc::new(Mutex::new(None)),
+            preempted: AtomicBool::new(false),
+            signals_handled: Arc::new(Mutex::new(HashSet::new())),
+            signals_pending: Arc::new(Mutex::new(Vec::new())),
             message_sender: Arc::new(Mutex::new(message_sender)),
             message_receiver: Arc::new(Mutex::new(message_receiver)),
         }
@@ -72,6 +93,20 @@ impl SchedulerState {
         self.num_errors.load(Ordering::SeqCst) > 0
     }
 
+    /**
+        Gets the total lua error count for the scheduler.
+    */
+    pub fn error_count(&self) -> usize {
+        self.num_errors.load(Ordering::SeqCst)
+    }
+
+    /**
+        Gets the total number of lua thread resumptions for the scheduler.
+    */
+    pub fn resumption_count(&self) -> usize {
+        self.num_resumptions.load(Ordering::SeqCst)
+    }
+
     /**
         Gets the currently set exit code for the scheduler, if any.
     */
@@ -99,6 +134,39 @@ impl SchedulerState {
         self.message_sender().send_exit_code_set();
     }
 
+    /**
+        Checks if the scheduler is currently running its exit handlers.
+    */
+    pub fn is_running_exit_handlers(&self) -> bool {
+        self.exit_handlers_running.load(Ordering::SeqCst)
+    }
+
+    /**
+        Sets if the scheduler is currently running its exit handlers.
+
+        While exit handlers are running, lua threads and futures
+        may be resumed even though an exit code has been set.
+    */
+    pub fn set_running_exit_handlers(&self, running: bool) {
+        self.exit_handlers_running.store(running, Ordering::SeqCst);
+    }
+
+    /**
+        Checks if the scheduler should stop resuming lua threads and futures.
+    */
+    pub fn should_stop(&self) -> bool {
+        self.has_exit_code() && !self.is_running_exit_handlers()
+    }
+
+    /**
+        Cancels the scheduler, setting the given exit code unless one has already been set.
+    */
+    pub fn cancel(&self, code: impl Into<u8>) {
+        if !self.has_exit_code() {
+            self.set_exit_code(code);
+        }
+    }
+
     /**
         Gets the currently running lua scheduler thread id, if any.
     */
@@ -120,7 +188,9 @@ impl SchedulerState {
         is not checked at runtime for performance reasons.
     */
     pub fn set_current_thread_id(&self, id: Option<SchedulerThreadId>) {
-        self.num_resumptions.fetch_add(1, Ordering::Relaxed);
+        if id.is_some() {
+            self.num_resumptions.fetch_add(1, Ordering::Relaxed);
+        }
         let mut thread_id = self
             .thread_id
             .lock()
@@ -158,6 +228,277 @@ impl SchedulerState {
         thread_errors.insert(id, err);
     }
 
+    /**
+        Gets the name for the given `id`, if one has been set.
+    */
+    pub fn get_thread_name(&self, id: SchedulerThreadId) -> Option<String> {
+        let thread_names = self
+            .thread_names
+            .lock()
+            .expect("Failed to lock thread names");
+        thread_names.get(&id).cloned()
+    }
+
+    /**
+        Sets the name for the given `id`.
+
+        Note that this will replace any already existing name.
+    */
+    pub fn set_thread_name(&self, id: SchedulerThreadId, name: impl Into<String>) {
+        let mut thread_names = self
+            .thread_names
+            .lock()
+            .expect("Failed to lock thread names");
+        thread_names.insert(id, name.into());
+    }
+
+    /**
+        Removes the name for the given `id`, if one has been set.
+
+        This should be called once a thread has finished running, since
+        thread ids are not guaranteed to be unique after that point.
+    */
+    pub fn remove_thread_name(&self, id: SchedulerThreadId) -> Option<String> {
+        let mut thread_names = self
+            .thread_names
+            .lock()
+            .expect("Failed to lock thread names");
+        thread_names.remove(&id)
+    }
+
+    /**
+        Records that the thread with the given `id` was resumed and ran for `elapsed`.
+
+        Only threads that have been given a name are profiled, and all
+        threads with the same name are counted together, so that handlers
+        which run in a new thread each time show up as a single entry.
+    */
+    pub fn record_thread_resume(&self, id: SchedulerThreadId, elapsed: Duration) {
+        let Some(name) = self.get_thread_name(id) else {
+            return;
+        };
+        let mut thread_profile = self
+            .thread_profile
+            .lock()
+            .expect("Failed to lock thread profile");
+        let entry = thread_profile.entry(name).or_default();
+        entry.0 += 1;
+        entry.1 += elapsed;
+    }
+
+    /**
+        Gets the number of resumptions and total time spent
+        running for each thread name, see [`record_thread_resume`].
+
+        [`record_thread_resume`]: Self::record_thread_resume
+    */
+    pub fn thread_profile(&self) -> Vec<(String, usize, Duration)> {
+        let thread_profile = self
+            .thread_profile
+            .lock()
+            .expect("Failed to lock thread profile");
+        thread_profile
+            .iter()
+            .map(|(name, (resumptions, time))| (name.clone(), *resumptions, *time))
+            .collect()
+    }
+
+    /**
+        Gets the priority for the given `id`, defaulting to [`SchedulerPriority::Normal`].
+    */
+    pub fn get_thread_priority(&self, id: SchedulerThreadId) -> SchedulerPriority {
+        let thread_priorities = self
+            .thread_priorities
+            .lock()
+            .expect("Failed to lock thread priorities");
+        thread_priorities.get(&id).copied().unwrap_or_default()
+    }
+
+    /**
+        Sets the priority for the given `id`.
+    */
+    pub fn set_thread_priority(&self, id: SchedulerThreadId, priority: SchedulerPriority) {
+        let mut thread_priorities = self
+            .thread_priorities
+            .lock()
+            .expect("Failed to lock thread priorities");
+        if priority == SchedulerPriority::Normal {
+            thread_priorities.remove(&id);
+        } else {
+            thread_priorities.insert(id, priority);
+        }
+    }
+
+    /**
+        Checks if the given `id` may be preempted while running.
+    */
+    pub fn is_thread_preemptible(&self, id: SchedulerThreadId) -> bool {
+        self.thread_preemptible
+            .lock()
+            .expect("Failed to lock preemptible threads")
+            .contains(&id)
+    }
+
+    /**
+        Sets if the given `id` may be preempted while running.
+    */
+    pub fn set_thread_preemptible(&self, id: SchedulerThreadId, preemptible: bool) {
+        let mut thread_preemptible = self
+            .thread_preemptible
+            .lock()
+            .expect("Failed to lock preemptible threads");
+        if preemptible {
+            thread_preemptible.insert(id);
+        } else {
+            thread_preemptible.remove(&id);
+        }
+    }
+
+    /**
+        Checks if errors for the given `id` are handled by something else
+        than the scheduler, and should not be reported or counted by it.
+    */
+    pub fn are_thread_errors_handled(&self, id: SchedulerThreadId) -> bool {
+        self.thread_errors_handled
+            .lock()
+            .expect("Failed to lock handled thread errors")
+            .contains(&id)
+    }
+
+    /**
+        Sets if errors for the given `id` are handled by something else than the scheduler.
+    */
+    pub fn set_thread_errors_handled(&self, id: SchedulerThreadId, handled: bool) {
+        let mut thread_errors_handled = self
+            .thread_errors_handled
+            .lock()
+            .expect("Failed to lock handled thread errors");
+        if handled {
+            thread_errors_handled.insert(id);
+        } else {
+            thread_errors_handled.remove(&id);
+        }
+    }
+
+    /**
+        Removes the priority and any other options for the given `id`.
+
+        Same as [`SchedulerState::remove_thread_name`], this
+        should be called once a thread has finished running.
+    */
+    pub fn remove_thread_options(&self, id: SchedulerThreadId) {
+        self.thread_priorities
+            .lock()
+            .expect("Failed to lock thread priorities")
+            .remove(&id);
+        self.thread_preemptible
+            .lock()
+            .expect("Failed to lock preemptible threads")
+            .remove(&id);
+        self.thread_errors_handled
+            .lock()
+            .expect("Failed to lock handled thread errors")
+            .remove(&id);
+    }
+
+    /**
+        Sets the deadline after which the currently running
+        thread should be preempted, or `None` to never preempt it.
+
+        This also resets the flag for if the thread was preempted.
+    */
+    pub fn set_preempt_deadline(&self, deadline: Option<Instant>) {
+        self.preempted.store(false, Ordering::Relaxed);
+        *self
+            .preempt_deadline
+            .lock()
+            .expect("Failed to lock preempt deadline") = deadline;
+    }
+
+    /**
+        Checks if the currently running thread has passed its preempt deadline.
+    */
+    pub fn should_preempt(&self) -> bool {
+        match *self
+            .preempt_deadline
+            .lock()
+            .expect("Failed to lock preempt deadline")
+        {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+
+    /**
+        Marks the currently running thread as having been preempted.
+    */
+    pub fn set_preempted(&self) {
+        self.preempted.store(true, Ordering::Relaxed);
+    }
+
+    /**
+        Checks if the most recently resumed thread was preempted.
+    */
+    pub fn was_preempted(&self) -> bool {
+        self.preempted.load(Ordering::Relaxed)
+    }
+
+    /**
+        Checks if the given signal has any handlers.
+    */
+    pub fn is_signal_handled(&self, signal: SchedulerSignal) -> bool {
+        self.signals_handled
+            .lock()
+            .expect("Failed to lock handled signals")
+            .contains(&signal)
+    }
+
+    /**
+        Marks the given signal as having handlers.
+
+        Returns `true` if the signal was not already marked as handled.
+    */
+    pub fn set_signal_handled(&self, signal: SchedulerSignal) -> bool {
+        self.signals_handled
+            .lock()
+            .expect("Failed to lock handled signals")
+            .insert(signal)
+    }
+
+    /**
+        Queues a received signal, for its handlers to run on the next scheduler step.
+    */
+    pub fn push_pending_signal(&self, signal: SchedulerSignal) {
+        self.signals_pending
+            .lock()
+            .expect("Failed to lock pending signals")
+            .push(signal);
+        self.message_sender().send_received_signal();
+    }
+
+    /**
+        Checks if there are any received signals that have not yet been handled.
+    */
+    pub fn has_pending_signals(&self) -> bool {
+        !self
+            .signals_pending
+            .lock()
+            .expect("Failed to lock pending signals")
+            .is_empty()
+    }
+
+    /**
+        Takes all received signals that have not yet been handled, in the order they were received.
+    */
+    pub fn take_pending_signals(&self) -> Vec<SchedulerSignal> {
+        std::mem::take(
+            &mut *self
+                .signals_pending
+                .lock()
+                .expect("Failed to lock pending signals"),
+        )
+    }
+
     /**
         Creates a new message sender for the scheduler.
     */
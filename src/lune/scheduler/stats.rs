@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use mlua::prelude::*;
+
+use super::Scheduler;
+
+/**
+    A snapshot of statistics for a [`Scheduler`].
+*/
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerStats {
+    /// Total number of lua thread resumptions
+    pub resumptions: usize,
+    /// Total number of lua errors that were not caught
+    pub errors: usize,
+    /// Number of lua threads that have been scheduled but not yet finished
+    pub pending_threads: usize,
+    /// Names of all pending lua threads that have been given a name, sorted
+    pub pending_thread_names: Vec<String>,
+    /// Time spent running named lua threads, with the most time spent first
+    pub profile: Vec<SchedulerThreadProfile>,
+}
+
+/**
+    Profiling information for all lua threads that were given the same name.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerThreadProfile {
+    /// Name of the lua threads
+    pub name: String,
+    /// Number of times lua threads with this name were resumed
+    pub resumptions: usize,
+    /// Total time spent running lua threads with this name
+    pub time: Duration,
+}
+
+impl<'fut> Scheduler<'fut> {
+    /**
+        Gets a snapshot of statistics for the scheduler.
+
+        This includes the names of any pending threads, which may be set
+        from lua using `task.name`, and is useful for finding threads that
+        are stuck waiting for something that will never happen, as well as
+        the time spent running named threads, for finding slow ones.
+    */
+    pub fn stats(&self) -> LuaResult<SchedulerStats> {
+        let senders = self
+            .thread_senders
+            .try_lock()
+            .into_lua_err()
+            .context("Failed to lock thread senders for stats")?;
+
+        let mut pending_thread_names = senders
+            .keys()
+            .filter_map(|id| self.state.get_thread_name(*id))
+            .collect::<Vec<_>>();
+        pending_thread_names.sort();
+
+        let mut profile = self
+            .state
+            .thread_profile()
+            .into_iter()
+            .map(|(name, resumptions, time)| SchedulerThreadProfile {
+                name,
+                resumptions,
+                time,
+            })
+            .collect::<Vec<_>>();
+        profile.sort_by(|a, b| b.time.cmp(&a.time).then_with(|| a.name.cmp(&b.name)));
+
+        Ok(SchedulerStats {
+            resumptions: self.state.resumption_count(),
+            errors: self.state.error_count(),
+            pending_threads: senders.len(),
+            pending_thread_names,
+            profile,
+        })
+    }
+}
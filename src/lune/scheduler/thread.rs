@@ -7,8 +7,8 @@ use tokio::sync::broadcast::Sender;
     Type alias for a broadcast [`Sender`], which will
     broadcast the result and return values of a lua thread.
 
-    The return values are stored in the lua registry as a
-    `Vec<LuaValue<'_>>`, and the registry key pointing to
+    The return values are stored in the lua registry using
+    [`store_multi_value`], and the registry key pointing to
     those values will be sent using the broadcast sender.
 */
 pub type SchedulerThreadSender = Sender<LuaResult<Arc<LuaRegistryKey>>>;
@@ -58,14 +58,12 @@ impl SchedulerThread {
         thread: LuaThread<'lua>,
         args: LuaMultiValue<'lua>,
     ) -> Self {
-        let args_vec = args.into_vec();
         let thread_id = SchedulerThreadId::from(&thread);
 
         let key_thread = lua
             .create_registry_value(thread)
             .expect("Failed to store thread in registry - out of memory");
-        let key_args = lua
-            .create_registry_value(args_vec)
+        let key_args = store_multi_value(lua, args)
             .expect("Failed to store thread args in registry - out of memory");
 
         Self {
@@ -82,11 +80,8 @@ impl SchedulerThread {
         let thread = lua
             .registry_value(&self.key_thread)
             .expect("Failed to get thread from registry");
-        let args_vec = lua
-            .registry_value(&self.key_args)
-            .expect("Failed to get thread args from registry");
-
-        let args = LuaMultiValue::from_vec(args_vec);
+        let args =
+            load_multi_value(lua, &self.key_args).expect("Failed to get thread args from registry");
 
         lua.remove_registry_value(self.key_thread)
             .expect("Failed to remove thread from registry");
@@ -103,3 +98,36 @@ impl SchedulerThread {
         self.thread_id
     }
 }
+
+/**
+    Stores the given values in the lua registry, including any nils.
+
+    Note that we can not store a `Vec<LuaValue<'_>>` directly, since it
+    is stored as a sequence table, and nils would create holes in it.
+*/
+pub(super) fn store_multi_value<'lua>(
+    lua: &'lua Lua,
+    values: LuaMultiValue<'lua>,
+) -> LuaResult<LuaRegistryKey> {
+    let table = lua.create_table_with_capacity(values.len(), 1)?;
+    table.raw_set("n", values.len())?;
+    for (index, value) in values.into_iter().enumerate() {
+        table.raw_set(index + 1, value)?;
+    }
+    lua.create_registry_value(table)
+}
+
+/**
+    Loads values that were stored using [`store_multi_value`].
+*/
+pub(super) fn load_multi_value<'lua>(
+    lua: &'lua Lua,
+    key: &LuaRegistryKey,
+) -> LuaResult<LuaMultiValue<'lua>> {
+    let table = lua.registry_value::<LuaTable>(key)?;
+    let count = table.raw_get::<_, usize>("n")?;
+    (1..=count)
+        .map(|index| table.raw_get::<_, LuaValue>(index))
+        .collect::<LuaResult<Vec<_>>>()
+        .map(LuaMultiValue::from_vec)
+}
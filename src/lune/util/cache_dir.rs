@@ -0,0 +1,21 @@
+use std::{env, path::PathBuf};
+
+use directories::UserDirs;
+use mlua::prelude::*;
+
+const CACHE_DIR_ENV_VAR: &str = "LUNE_CACHE_DIR";
+
+/**
+    Gets the directory that Lune caches things with the given name in.
+
+    This is `~/.lune/.cache/<name>` unless the `LUNE_CACHE_DIR`
+    environment variable is set, in which case it will be used instead.
+*/
+pub fn cache_dir(name: &str) -> LuaResult<PathBuf> {
+    if let Some(dir) = env::var_os(CACHE_DIR_ENV_VAR).filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir).join(name));
+    }
+    let user_dirs =
+        UserDirs::new().ok_or_else(|| LuaError::runtime("Failed to find user home directory"))?;
+    Ok(user_dirs.home_dir().join(".lune").join(".cache").join(name))
+}
@@ -1,6 +1,8 @@
+mod cache_dir;
 mod table_builder;
 
 pub mod formatting;
 pub mod traits;
 
+pub use cache_dir::cache_dir;
 pub use table_builder::TableBuilder;
@@ -5,6 +5,7 @@ use crate::LuneError;
 
 pub trait LuaEmitErrorExt {
     fn emit_error(&self, err: LuaError);
+    fn emit_error_in_thread(&self, err: LuaError, thread_name: impl AsRef<str>);
 }
 
 impl LuaEmitErrorExt for Lua {
@@ -12,4 +13,13 @@ impl LuaEmitErrorExt for Lua {
         // NOTE: LuneError will pretty-format this error
         eprintln!("{}\n{}", format_label("error"), LuneError::from(err));
     }
+
+    fn emit_error_in_thread(&self, err: LuaError, thread_name: impl AsRef<str>) {
+        eprintln!(
+            "{}in thread '{}'\n{}",
+            format_label("error"),
+            thread_name.as_ref(),
+            LuneError::from(err)
+        );
+    }
 }
@@ -3,9 +3,11 @@ pub(crate) use rbx_dom_weak::types::{Variant as DomValue, VariantType as DomType
 pub mod attributes;
 pub mod conversion;
 pub mod extension;
+pub mod overloads;
 pub mod result;
 pub mod types;
 
+use overloads::*;
 use result::*;
 
 pub use crate::roblox::shared::userdata::*;
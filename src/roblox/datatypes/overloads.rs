@@ -0,0 +1,48 @@
+use mlua::prelude::*;
+
+use super::extension::RobloxUserdataTypenameExt;
+
+/**
+    A declarative table of the signatures accepted by a datatype constructor.
+
+    Constructors try each of their overloads in order, and use this table to
+    create a descriptive error when none of them match the given arguments.
+*/
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Overloads {
+    pub(crate) constructor: &'static str,
+    pub(crate) signatures: &'static [&'static str],
+}
+
+impl Overloads {
+    /**
+        Creates an error for arguments that did not match any of the
+        overloads, listing both the given argument types and all signatures.
+    */
+    pub(crate) fn invalid_args(&self, args: &LuaMultiValue) -> LuaError {
+        let mut message = format!(
+            "Invalid arguments to constructor {}({}), expected one of:",
+            self.constructor,
+            describe_args(args)
+        );
+        for signature in self.signatures {
+            message.push_str(&format!("\n    {}{signature}", self.constructor));
+        }
+        LuaError::RuntimeError(message)
+    }
+}
+
+/**
+    Describes the types of the given arguments, using
+    Roblox datatype names for Roblox userdata values.
+*/
+pub(crate) fn describe_args(args: &LuaMultiValue) -> String {
+    args.iter()
+        .map(|arg| match arg {
+            LuaValue::Integer(_) => "number",
+            LuaValue::UserData(ud) => ud.roblox_type_name().unwrap_or("userdata"),
+            arg => arg.type_name(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -5,7 +5,7 @@ use rbx_dom_weak::types::Axes as DomAxes;
 
 use crate::{lune::util::TableBuilder, roblox::exports::LuaExportsTable};
 
-use super::{super::*, EnumItem};
+use super::{super::*, faces::normal_id_name, EnumItem};
 
 /**
     An implementation of the [Axes](https://create.roblox.com/docs/reference/engine/datatypes/Axes) Roblox datatype.
@@ -28,40 +28,26 @@ impl LuaExportsTable<'_> for Axes {
             let mut y = false;
             let mut z = false;
 
-            let mut check = |e: &EnumItem| {
-                if e.parent.desc.name == "Axis" {
-                    match &e.name {
-                        name if name == "X" => x = true,
-                        name if name == "Y" => y = true,
-                        name if name == "Z" => z = true,
-                        _ => {}
-                    }
-                } else if e.parent.desc.name == "NormalId" {
-                    match &e.name {
-                        name if name == "Left" || name == "Right" => x = true,
-                        name if name == "Top" || name == "Bottom" => y = true,
-                        name if name == "Front" || name == "Back" => z = true,
-                        _ => {}
-                    }
-                }
-            };
-
             for (index, arg) in args.into_iter().enumerate() {
-                if let LuaValue::UserData(u) = arg {
-                    if let Ok(e) = u.borrow::<EnumItem>() {
-                        check(&e);
-                    } else {
+                // NOTE: Axis enum items are accepted in addition to NormalId, and
+                // since their names do not overlap, strings can be either of them
+                let name = match &arg {
+                    LuaValue::UserData(u) => match u.borrow::<EnumItem>() {
+                        Ok(e) if e.parent.desc.name == "Axis" => e.name.clone(),
+                        _ => normal_id_name(index + 1, &arg)?,
+                    },
+                    _ => normal_id_name(index + 1, &arg)?,
+                };
+                match name.as_str() {
+                    "X" | "Left" | "Right" => x = true,
+                    "Y" | "Top" | "Bottom" => y = true,
+                    "Z" | "Front" | "Back" => z = true,
+                    name => {
                         return Err(LuaError::RuntimeError(format!(
-                            "Expected argument #{} to be an EnumItem, got userdata",
-                            index
-                        )));
+                            "Expected argument #{} to be an Axis or NormalId, got '{name}'",
+                            index + 1
+                        )))
                     }
-                } else {
-                    return Err(LuaError::RuntimeError(format!(
-                        "Expected argument #{} to be an EnumItem, got {}",
-                        index,
-                        arg.type_name()
-                    )));
                 }
             }
 
@@ -22,6 +22,16 @@ pub struct BrickColor {
     pub(crate) rgb: (u8, u8, u8),
 }
 
+const BRICK_COLOR_NEW: Overloads = Overloads {
+    constructor: "BrickColor.new",
+    signatures: &[
+        "(number: number)",
+        "(name: string)",
+        "(r: number, g: number, b: number)",
+        "(color: Color3)",
+    ],
+};
+
 impl LuaExportsTable<'_> for BrickColor {
     const EXPORT_NAME: &'static str = "BrickColor";
 
@@ -41,10 +51,7 @@ impl LuaExportsTable<'_> for BrickColor {
             } else if let Ok(color) = ArgsColor3::from_lua_multi(args.clone(), lua) {
                 Ok(Self::from(*color))
             } else {
-                // FUTURE: Better error message here using given arg types
-                Err(LuaError::RuntimeError(
-                    "Invalid arguments to constructor".to_string(),
-                ))
+                Err(BRICK_COLOR_NEW.invalid_args(&args))
             }
         };
 
@@ -7,7 +7,7 @@ use rbx_dom_weak::types::{CFrame as DomCFrame, Matrix3 as DomMatrix3, Vector3 as
 
 use crate::{lune::util::TableBuilder, roblox::exports::LuaExportsTable};
 
-use super::{super::*, Vector3};
+use super::{super::*, EnumItem, Vector3};
 
 /**
     An implementation of the [CFrame](https://create.roblox.com/docs/reference/engine/datatypes/CFrame)
@@ -37,8 +37,121 @@ impl CFrame {
     fn inverse(&self) -> Self {
         Self(self.0.inverse())
     }
+
+    #[rustfmt::skip]
+    #[allow(clippy::type_complexity)]
+    fn components(&self) -> (f32, f32, f32, f32, f32, f32, f32, f32, f32, f32, f32, f32) {
+        let pos = self.position();
+        let (rx, ry, rz) = self.orientation();
+        (
+            pos.x, pos.y, -pos.z,
+             rx.x,  rx.y,   rx.z,
+             ry.x,  ry.y,   ry.z,
+             rz.x,  rz.y,   rz.z,
+        )
+    }
+
+    fn rotation(&self) -> Quat {
+        Quat::from_mat4(&self.0).normalize()
+    }
+}
+
+/**
+    The order that rotations around each axis are applied in,
+    from the [RotationOrder](https://create.roblox.com/docs/reference/engine/enums/RotationOrder) enum.
+
+    Angles are always given and returned in X, Y, Z order,
+    and are reordered to match the order of the rotation.
+*/
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum RotationOrder {
+    #[default]
+    XYZ,
+    XZY,
+    YZX,
+    YXZ,
+    ZXY,
+    ZYX,
+}
+
+impl RotationOrder {
+    fn from_enum_item(item: Option<LuaUserDataRef<EnumItem>>) -> LuaResult<Self> {
+        let item = match item {
+            Some(item) => item,
+            None => return Ok(Self::default()),
+        };
+        if item.parent.desc.name != "RotationOrder" {
+            return Err(LuaError::RuntimeError(format!(
+                "EnumItem must be a RotationOrder, got {}",
+                item.parent.desc.name
+            )));
+        }
+        Ok(match item.name.as_str() {
+            "XYZ" => Self::XYZ,
+            "XZY" => Self::XZY,
+            "YZX" => Self::YZX,
+            "YXZ" => Self::YXZ,
+            "ZXY" => Self::ZXY,
+            "ZYX" => Self::ZYX,
+            name => {
+                return Err(LuaError::RuntimeError(format!(
+                    "RotationOrder '{}' is not known",
+                    name
+                )))
+            }
+        })
+    }
+
+    /**
+        Gets the matching [`EulerRot`], along with the index of the
+        X, Y and Z axes in the order that [`glam`] expects angles in.
+    */
+    fn euler_rot(self) -> (EulerRot, [usize; 3]) {
+        match self {
+            Self::XYZ => (EulerRot::XYZ, [0, 1, 2]),
+            Self::XZY => (EulerRot::XZY, [0, 2, 1]),
+            Self::YZX => (EulerRot::YZX, [1, 2, 0]),
+            Self::YXZ => (EulerRot::YXZ, [1, 0, 2]),
+            Self::ZXY => (EulerRot::ZXY, [2, 0, 1]),
+            Self::ZYX => (EulerRot::ZYX, [2, 1, 0]),
+        }
+    }
+
+    fn to_mat4(self, rx: f32, ry: f32, rz: f32) -> Mat4 {
+        let (rot, [a, b, c]) = self.euler_rot();
+        let angles = [rx, ry, rz];
+        Mat4::from_euler(rot, angles[a], angles[b], angles[c])
+    }
+
+    fn angles_from_quat(self, quat: Quat) -> (f32, f32, f32) {
+        let (rot, [a, b, c]) = self.euler_rot();
+        let (first, second, third) = quat.to_euler(rot);
+        let mut angles = [0.0; 3];
+        angles[a] = first;
+        angles[b] = second;
+        angles[c] = third;
+        (angles[0], angles[1], angles[2])
+    }
 }
 
+/**
+    The default tolerance used by `FuzzyEq`, when no epsilon is given.
+*/
+const FUZZY_EQ_EPSILON: f32 = 1e-5;
+
+const CFRAME_NEW: Overloads = Overloads {
+    constructor: "CFrame.new",
+    signatures: &[
+        "()",
+        "(pos: Vector3)",
+        "(pos: Vector3, lookAt: Vector3, up: Vector3?)",
+        "(x: number, y: number, z: number)",
+        "(x: number, y: number, z: number, qX: number, qY: number, qZ: number, qW: number)",
+        "(x: number, y: number, z: number, R00: number, R01: number, R02: number, R10: number, R11: number, R12: number, R20: number, R21: number, R22: number)",
+    ],
+};
+
 impl LuaExportsTable<'_> for CFrame {
     const EXPORT_NAME: &'static str = "CFrame";
 
@@ -50,6 +163,12 @@ impl LuaExportsTable<'_> for CFrame {
         let cframe_from_axis_angle =
             |_, (v, r): (LuaUserDataRef<Vector3>, f32)| Ok(CFrame(Mat4::from_axis_angle(v.0, r)));
 
+        let cframe_from_euler_angles =
+            |_, (rx, ry, rz, order): (f32, f32, f32, Option<LuaUserDataRef<EnumItem>>)| {
+                let order = RotationOrder::from_enum_item(order)?;
+                Ok(CFrame(order.to_mat4(rx, ry, rz)))
+            };
+
         let cframe_from_euler_angles_xyz = |_, (rx, ry, rz): (f32, f32, f32)| {
             Ok(CFrame(Mat4::from_euler(EulerRot::XYZ, rx, ry, rz)))
         };
@@ -125,7 +244,7 @@ impl LuaExportsTable<'_> for CFrame {
                     Vec3::new(x, y, z),
                 )))
             } else if let Ok((x, y, z, r00, r01, r02, r10, r11, r12, r20, r21, r22)) =
-                ArgsMatrix::from_lua_multi(args, lua)
+                ArgsMatrix::from_lua_multi(args.clone(), lua)
             {
                 Ok(CFrame(Mat4::from_cols_array_2d(&[
                     [r00, r01, r02, 0.0],
@@ -134,10 +253,7 @@ impl LuaExportsTable<'_> for CFrame {
                     [x, y, z, 1.0],
                 ])))
             } else {
-                // FUTURE: Better error message here using given arg types
-                Err(LuaError::RuntimeError(
-                    "Invalid arguments to constructor".to_string(),
-                ))
+                Err(CFRAME_NEW.invalid_args(&args))
             }
         };
 
@@ -145,6 +261,7 @@ impl LuaExportsTable<'_> for CFrame {
             .with_function("Angles", cframe_angles)?
             .with_value("identity", CFrame(Mat4::IDENTITY))?
             .with_function("fromAxisAngle", cframe_from_axis_angle)?
+            .with_function("fromEulerAngles", cframe_from_euler_angles)?
             .with_function("fromEulerAnglesXYZ", cframe_from_euler_angles_xyz)?
             .with_function("fromEulerAnglesYXZ", cframe_from_euler_angles_yxz)?
             .with_function("fromMatrix", cframe_from_matrix)?
@@ -231,6 +348,22 @@ impl LuaUserData for CFrame {
                 Ok(Variadic::from_iter(rhs.into_iter().map(|v3| inverse * *v3)))
             },
         );
+        methods.add_method(
+            "PointsToWorldSpace",
+            |_, this, points: Vec<LuaUserDataRef<Vector3>>| {
+                Ok(points.into_iter().map(|v3| *this * *v3).collect::<Vec<_>>())
+            },
+        );
+        methods.add_method(
+            "PointsToObjectSpace",
+            |_, this, points: Vec<LuaUserDataRef<Vector3>>| {
+                let inverse = this.inverse();
+                Ok(points
+                    .into_iter()
+                    .map(|v3| inverse * *v3)
+                    .collect::<Vec<_>>())
+            },
+        );
         methods.add_method(
             "VectorToWorldSpace",
             |_, this, rhs: Variadic<LuaUserDataRef<Vector3>>| {
@@ -247,17 +380,15 @@ impl LuaUserData for CFrame {
                 Ok(Variadic::from_iter(rhs.into_iter().map(|v3| result * *v3)))
             },
         );
-        #[rustfmt::skip]
-        methods.add_method("GetComponents", |_, this, ()| {
-            let pos = this.position();
-            let (rx, ry, rz) = this.orientation();
-            Ok((
-                pos.x, pos.y, -pos.z,
-				 rx.x,  rx.y,   rx.z,
-				 ry.x,  ry.y,   ry.z,
-				 rz.x,  rz.y,   rz.z,
-            ))
-        });
+        methods.add_method("GetComponents", |_, this, ()| Ok(this.components()));
+        methods.add_method("components", |_, this, ()| Ok(this.components()));
+        methods.add_method(
+            "ToEulerAngles",
+            |_, this, order: Option<LuaUserDataRef<EnumItem>>| {
+                let order = RotationOrder::from_enum_item(order)?;
+                Ok(order.angles_from_quat(Quat::from_mat4(&this.0)))
+            },
+        );
         methods.add_method("ToEulerAnglesXYZ", |_, this, ()| {
             Ok(Quat::from_mat4(&this.0).to_euler(EulerRot::XYZ))
         });
@@ -273,6 +404,17 @@ impl LuaUserData for CFrame {
             let (axis, angle) = Quat::from_mat4(&this.0).to_axis_angle();
             Ok((Vector3(axis), angle))
         });
+        methods.add_method(
+            "FuzzyEq",
+            |_, this, (other, epsilon): (LuaUserDataRef<CFrame>, Option<f32>)| {
+                Ok(this
+                    .0
+                    .abs_diff_eq(other.0, epsilon.unwrap_or(FUZZY_EQ_EPSILON)))
+            },
+        );
+        methods.add_method("AngleBetween", |_, this, other: LuaUserDataRef<CFrame>| {
+            Ok(this.rotation().angle_between(other.rotation()))
+        });
         // Metamethods
         methods.add_meta_method(LuaMetaMethod::Eq, userdata_impl_eq);
         methods.add_meta_method(LuaMetaMethod::ToString, userdata_impl_to_string);
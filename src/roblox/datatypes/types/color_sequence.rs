@@ -19,6 +19,15 @@ pub struct ColorSequence {
     pub(crate) keypoints: Vec<ColorSequenceKeypoint>,
 }
 
+const COLOR_SEQUENCE_NEW: Overloads = Overloads {
+    constructor: "ColorSequence.new",
+    signatures: &[
+        "(color: Color3)",
+        "(c0: Color3, c1: Color3)",
+        "(keypoints: { ColorSequenceKeypoint })",
+    ],
+};
+
 impl LuaExportsTable<'_> for ColorSequence {
     const EXPORT_NAME: &'static str = "ColorSequence";
 
@@ -28,41 +37,40 @@ impl LuaExportsTable<'_> for ColorSequence {
         type ArgsKeypoints<'lua> = Vec<LuaUserDataRef<'lua, ColorSequenceKeypoint>>;
 
         let color_sequence_new = |lua, args: LuaMultiValue| {
-            if let Ok(color) = ArgsColor::from_lua_multi(args.clone(), lua) {
+            // NOTE: The two value overload must be checked first, since the
+            // single value overload would otherwise ignore the second value
+            if let Ok((c0, c1)) = ArgsColors::from_lua_multi(args.clone(), lua) {
                 Ok(ColorSequence {
                     keypoints: vec![
                         ColorSequenceKeypoint {
                             time: 0.0,
-                            color: *color,
+                            color: *c0,
                         },
                         ColorSequenceKeypoint {
                             time: 1.0,
-                            color: *color,
+                            color: *c1,
                         },
                     ],
                 })
-            } else if let Ok((c0, c1)) = ArgsColors::from_lua_multi(args.clone(), lua) {
+            } else if let Ok(color) = ArgsColor::from_lua_multi(args.clone(), lua) {
                 Ok(ColorSequence {
                     keypoints: vec![
                         ColorSequenceKeypoint {
                             time: 0.0,
-                            color: *c0,
+                            color: *color,
                         },
                         ColorSequenceKeypoint {
                             time: 1.0,
-                            color: *c1,
+                            color: *color,
                         },
                     ],
                 })
-            } else if let Ok(keypoints) = ArgsKeypoints::from_lua_multi(args, lua) {
+            } else if let Ok(keypoints) = ArgsKeypoints::from_lua_multi(args.clone(), lua) {
                 Ok(ColorSequence {
                     keypoints: keypoints.iter().map(|k| **k).collect(),
                 })
             } else {
-                // FUTURE: Better error message here using given arg types
-                Err(LuaError::RuntimeError(
-                    "Invalid arguments to constructor".to_string(),
-                ))
+                Err(COLOR_SEQUENCE_NEW.invalid_args(&args))
             }
         };
 
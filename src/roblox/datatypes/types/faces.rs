@@ -34,36 +34,20 @@ impl LuaExportsTable<'_> for Faces {
             let mut bottom = false;
             let mut front = false;
 
-            let mut check = |e: &EnumItem| {
-                if e.parent.desc.name == "NormalId" {
-                    match &e.name {
-                        name if name == "Right" => right = true,
-                        name if name == "Top" => top = true,
-                        name if name == "Back" => back = true,
-                        name if name == "Left" => left = true,
-                        name if name == "Bottom" => bottom = true,
-                        name if name == "Front" => front = true,
-                        _ => {}
-                    }
-                }
-            };
-
             for (index, arg) in args.into_iter().enumerate() {
-                if let LuaValue::UserData(u) = arg {
-                    if let Ok(e) = u.borrow::<EnumItem>() {
-                        check(&e);
-                    } else {
+                match normal_id_name(index + 1, &arg)?.as_str() {
+                    "Right" => right = true,
+                    "Top" => top = true,
+                    "Back" => back = true,
+                    "Left" => left = true,
+                    "Bottom" => bottom = true,
+                    "Front" => front = true,
+                    name => {
                         return Err(LuaError::RuntimeError(format!(
-                            "Expected argument #{} to be an EnumItem, got userdata",
-                            index
-                        )));
+                            "Expected argument #{} to be a NormalId, got '{name}'",
+                            index + 1
+                        )))
                     }
-                } else {
-                    return Err(LuaError::RuntimeError(format!(
-                        "Expected argument #{} to be an EnumItem, got {}",
-                        index,
-                        arg.type_name()
-                    )));
                 }
             }
 
@@ -83,6 +67,30 @@ impl LuaExportsTable<'_> for Faces {
     }
 }
 
+/**
+    Gets the name of a face from a `NormalId` enum item,
+    or from a string such as `"Top"`, given as an argument.
+*/
+pub(super) fn normal_id_name(index: usize, arg: &LuaValue) -> LuaResult<String> {
+    match arg {
+        LuaValue::String(s) => Ok(s.to_str()?.to_string()),
+        LuaValue::UserData(u) => match u.borrow::<EnumItem>() {
+            Ok(e) if e.parent.desc.name == "NormalId" => Ok(e.name.clone()),
+            Ok(e) => Err(LuaError::RuntimeError(format!(
+                "Expected argument #{index} to be a NormalId, got Enum.{}",
+                e.parent.desc.name
+            ))),
+            Err(_) => Err(LuaError::RuntimeError(format!(
+                "Expected argument #{index} to be an EnumItem or string, got userdata"
+            ))),
+        },
+        arg => Err(LuaError::RuntimeError(format!(
+            "Expected argument #{index} to be an EnumItem or string, got {}",
+            arg.type_name()
+        ))),
+    }
+}
+
 impl LuaUserData for Faces {
     fn add_fields<'lua, F: LuaUserDataFields<'lua, Self>>(fields: &mut F) {
         fields.add_field_method_get("Right", |_, this| Ok(this.right));
@@ -13,6 +13,7 @@ mod number_range;
 mod number_sequence;
 mod number_sequence_keypoint;
 mod physical_properties;
+mod random;
 mod ray;
 mod rect;
 mod region3;
@@ -39,6 +40,7 @@ pub use physical_properties::PhysicalProperties;
 pub use r#enum::Enum;
 pub use r#enum_item::EnumItem;
 pub use r#enums::Enums;
+pub use random::Random;
 pub use ray::Ray;
 pub use rect::Rect;
 pub use region3::Region3;
@@ -19,6 +19,15 @@ pub struct NumberSequence {
     pub(crate) keypoints: Vec<NumberSequenceKeypoint>,
 }
 
+const NUMBER_SEQUENCE_NEW: Overloads = Overloads {
+    constructor: "NumberSequence.new",
+    signatures: &[
+        "(n: number)",
+        "(n0: number, n1: number)",
+        "(keypoints: { NumberSequenceKeypoint })",
+    ],
+};
+
 impl LuaExportsTable<'_> for NumberSequence {
     const EXPORT_NAME: &'static str = "NumberSequence";
 
@@ -28,45 +37,44 @@ impl LuaExportsTable<'_> for NumberSequence {
         type ArgsKeypoints<'lua> = Vec<LuaUserDataRef<'lua, NumberSequenceKeypoint>>;
 
         let number_sequence_new = |lua, args: LuaMultiValue| {
-            if let Ok(value) = ArgsColor::from_lua_multi(args.clone(), lua) {
+            // NOTE: The two value overload must be checked first, since the
+            // single value overload would otherwise ignore the second value
+            if let Ok((v0, v1)) = ArgsColors::from_lua_multi(args.clone(), lua) {
                 Ok(NumberSequence {
                     keypoints: vec![
                         NumberSequenceKeypoint {
                             time: 0.0,
-                            value,
+                            value: v0,
                             envelope: 0.0,
                         },
                         NumberSequenceKeypoint {
                             time: 1.0,
-                            value,
+                            value: v1,
                             envelope: 0.0,
                         },
                     ],
                 })
-            } else if let Ok((v0, v1)) = ArgsColors::from_lua_multi(args.clone(), lua) {
+            } else if let Ok(value) = ArgsColor::from_lua_multi(args.clone(), lua) {
                 Ok(NumberSequence {
                     keypoints: vec![
                         NumberSequenceKeypoint {
                             time: 0.0,
-                            value: v0,
+                            value,
                             envelope: 0.0,
                         },
                         NumberSequenceKeypoint {
                             time: 1.0,
-                            value: v1,
+                            value,
                             envelope: 0.0,
                         },
                     ],
                 })
-            } else if let Ok(keypoints) = ArgsKeypoints::from_lua_multi(args, lua) {
+            } else if let Ok(keypoints) = ArgsKeypoints::from_lua_multi(args.clone(), lua) {
                 Ok(NumberSequence {
                     keypoints: keypoints.iter().map(|k| **k).collect(),
                 })
             } else {
-                // FUTURE: Better error message here using given arg types
-                Err(LuaError::RuntimeError(
-                    "Invalid arguments to constructor".to_string(),
-                ))
+                Err(NUMBER_SEQUENCE_NEW.invalid_args(&args))
             }
         };
 
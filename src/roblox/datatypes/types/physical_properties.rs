@@ -36,6 +36,14 @@ impl PhysicalProperties {
     }
 }
 
+const PHYSICAL_PROPERTIES_NEW: Overloads = Overloads {
+    constructor: "PhysicalProperties.new",
+    signatures: &[
+        "(material: Enum.Material)",
+        "(density: number, friction: number, elasticity: number, frictionWeight: number?, elasticityWeight: number?)",
+    ],
+};
+
 impl LuaExportsTable<'_> for PhysicalProperties {
     const EXPORT_NAME: &'static str = "PhysicalProperties";
 
@@ -60,7 +68,7 @@ impl LuaExportsTable<'_> for PhysicalProperties {
                     )))
                 }
             } else if let Ok((density, friction, elasticity, friction_weight, elasticity_weight)) =
-                ArgsNumbers::from_lua_multi(args, lua)
+                ArgsNumbers::from_lua_multi(args.clone(), lua)
             {
                 Ok(PhysicalProperties {
                     density,
@@ -70,10 +78,7 @@ impl LuaExportsTable<'_> for PhysicalProperties {
                     elasticity_weight: elasticity_weight.unwrap_or(1.0),
                 })
             } else {
-                // FUTURE: Better error message here using given arg types
-                Err(LuaError::RuntimeError(
-                    "Invalid arguments to constructor".to_string(),
-                ))
+                Err(PHYSICAL_PROPERTIES_NEW.invalid_args(&args))
             }
         };
 
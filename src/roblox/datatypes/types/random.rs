@@ -0,0 +1,147 @@
+use std::f64::consts::TAU;
+
+use glam::Vec3;
+use mlua::prelude::*;
+
+use crate::{lune::util::TableBuilder, roblox::exports::LuaExportsTable};
+
+use super::Vector3;
+
+const PCG_MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const PCG_INCREMENT: u64 = 1_442_695_040_888_963_407;
+
+/**
+    An implementation of the [Random](https://create.roblox.com/docs/reference/engine/datatypes/Random) Roblox datatype,
+    backed by a [PCG32](https://www.pcg-random.org) generator.
+
+    Random objects created with the same seed always produce the same sequence of numbers,
+    but the sequences are not guaranteed to be the same as the ones produced in Roblox.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        let mut random = Self { state: 0 };
+        random.next_u32();
+        random.state = random.state.wrapping_add(seed);
+        random.next_u32();
+        random
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(PCG_MULTIPLIER).wrapping_add(PCG_INCREMENT);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (u64::from(self.next_u32()) << 32) | u64::from(self.next_u32())
+    }
+
+    /**
+        Generates a number in the range `[0, 1)`, with 53 bits of precision.
+    */
+    fn next_unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /**
+        Generates an integer in the range `[min, max]`, without any bias.
+    */
+    fn next_integer(&mut self, min: i64, max: i64) -> i64 {
+        let range = max.wrapping_sub(min) as u64;
+        if range == u64::MAX {
+            return self.next_u64() as i64;
+        }
+        let span = range + 1;
+        let zone = u64::MAX - (u64::MAX - span + 1) % span;
+        loop {
+            let n = self.next_u64();
+            if n <= zone {
+                return min.wrapping_add((n % span) as i64);
+            }
+        }
+    }
+
+    fn next_unit_vector(&mut self) -> Vec3 {
+        // NOTE: Picking a uniform height and angle gives
+        // points that are uniformly distributed on the sphere
+        let z = self.next_unit() * 2.0 - 1.0;
+        let angle = self.next_unit() * TAU;
+        let radius = (1.0 - z * z).sqrt();
+        Vec3::new(
+            (radius * angle.cos()) as f32,
+            (radius * angle.sin()) as f32,
+            z as f32,
+        )
+    }
+}
+
+impl LuaExportsTable<'_> for Random {
+    const EXPORT_NAME: &'static str = "Random";
+
+    fn create_exports_table(lua: &Lua) -> LuaResult<LuaTable> {
+        let random_new = |_, seed: Option<f64>| {
+            Ok(match seed {
+                Some(seed) if seed.is_finite() => Random::new(seed.floor() as i64 as u64),
+                Some(_) => {
+                    return Err(LuaError::RuntimeError(
+                        "Random seed must be a finite number".to_string(),
+                    ))
+                }
+                None => Random::new(rand::random()),
+            })
+        };
+
+        TableBuilder::new(lua)?
+            .with_function("new", random_new)?
+            .build_readonly()
+    }
+}
+
+impl LuaUserData for Random {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("NextInteger", |_, this, (min, max): (f64, f64)| {
+            let (min, max) = (min.floor(), max.floor());
+            if !(min.is_finite() && max.is_finite()) || min > max {
+                return Err(LuaError::RuntimeError(format!(
+                    "Invalid interval [{min}, {max}] for NextInteger - \
+                    max must be greater than or equal to min"
+                )));
+            }
+            Ok(this.next_integer(min as i64, max as i64))
+        });
+        methods.add_method_mut(
+            "NextNumber",
+            |_, this, (min, max): (Option<f64>, Option<f64>)| {
+                let min = min.unwrap_or(0.0);
+                let max = max.unwrap_or(1.0);
+                Ok(min + (max - min) * this.next_unit())
+            },
+        );
+        methods.add_method_mut("NextUnitVector", |_, this, ()| {
+            Ok(Vector3(this.next_unit_vector()))
+        });
+        methods.add_method_mut("Shuffle", |_, this, tab: LuaTable| {
+            // NOTE: This is a Fisher-Yates shuffle of the array part of the table
+            let len = tab.raw_len();
+            for i in (2..=len).rev() {
+                let j = this.next_integer(1, i as i64) as usize;
+                if i != j {
+                    let a: LuaValue = tab.raw_get(i)?;
+                    let b: LuaValue = tab.raw_get(j)?;
+                    tab.raw_set(i, b)?;
+                    tab.raw_set(j, a)?;
+                }
+            }
+            Ok(())
+        });
+        methods.add_method("Clone", |_, this, ()| Ok(*this));
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, _, ()| Ok("Random"));
+    }
+}
@@ -1,12 +1,12 @@
 use core::fmt;
 
-use glam::Vec3;
+use glam::{Mat4, Vec3};
 use mlua::prelude::*;
 use rbx_dom_weak::types::Ray as DomRay;
 
 use crate::{lune::util::TableBuilder, roblox::exports::LuaExportsTable};
 
-use super::{super::*, Vector3};
+use super::{super::*, CFrame, Region3, Vector3};
 
 /**
     An implementation of the [Ray](https://create.roblox.com/docs/reference/engine/datatypes/Ray)
@@ -28,6 +28,57 @@ impl Ray {
         let dot_product = lhs.dot(norm).max(0.0);
         self.origin + norm * dot_product
     }
+
+    /**
+        Finds where the ray, extending infinitely from its origin, hits a plane.
+
+        Returns `None` if the ray is parallel to the plane or points away from it.
+    */
+    fn intersect_plane(&self, point: Vec3, normal: Vec3) -> Option<Vec3> {
+        let denom = normal.dot(self.direction);
+        if denom.abs() <= f32::EPSILON {
+            return None;
+        }
+        let t = normal.dot(point - self.origin) / denom;
+        if t >= 0.0 {
+            Some(self.origin + self.direction * t)
+        } else {
+            None
+        }
+    }
+
+    /**
+        Finds where the ray, extending infinitely from its origin,
+        first enters a box with the given center and size.
+
+        If the origin is already inside the box, the origin is returned.
+    */
+    fn intersect_box(&self, cframe: CFrame, size: Vec3) -> Option<Vec3> {
+        // Move the ray into the space of the box, making it axis-aligned
+        let inverse = cframe.0.inverse();
+        let origin = inverse.transform_point3(self.origin);
+        let direction = inverse.transform_vector3(self.direction);
+        let half = size.abs() / 2.0;
+
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::INFINITY;
+        for axis in 0..3 {
+            if direction[axis].abs() <= f32::EPSILON {
+                if origin[axis].abs() > half[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (-half[axis] - origin[axis]) / direction[axis];
+            let t2 = (half[axis] - origin[axis]) / direction[axis];
+            t_min = t_min.max(t1.min(t2));
+            t_max = t_max.min(t1.max(t2));
+            if t_min > t_max {
+                return None;
+            }
+        }
+        Some(self.origin + self.direction * t_min)
+    }
 }
 
 impl LuaExportsTable<'_> for Ray {
@@ -69,6 +120,27 @@ impl LuaUserData for Ray {
             let closest = this.closest_point(to.0);
             Ok((closest - to.0).length())
         });
+        methods.add_method(
+            "IntersectPlane",
+            |_, this, (point, normal): (LuaUserDataRef<Vector3>, LuaUserDataRef<Vector3>)| {
+                Ok(this.intersect_plane(point.0, normal.0).map(Vector3))
+            },
+        );
+        methods.add_method(
+            "IntersectBox",
+            |_, this, (cframe, size): (LuaUserDataRef<CFrame>, LuaUserDataRef<Vector3>)| {
+                Ok(this.intersect_box(*cframe, size.0).map(Vector3))
+            },
+        );
+        methods.add_method(
+            "IntersectRegion3",
+            |_, this, region: LuaUserDataRef<Region3>| {
+                let cframe = CFrame(Mat4::from_translation(region.min.lerp(region.max, 0.5)));
+                Ok(this
+                    .intersect_box(cframe, region.max - region.min)
+                    .map(Vector3))
+            },
+        );
         // Metamethods
         methods.add_meta_method(LuaMetaMethod::Eq, userdata_impl_eq);
         methods.add_meta_method(LuaMetaMethod::ToString, userdata_impl_to_string);
@@ -30,6 +30,14 @@ impl Rect {
     }
 }
 
+const RECT_NEW: Overloads = Overloads {
+    constructor: "Rect.new",
+    signatures: &[
+        "(min: Vector2, max: Vector2)",
+        "(minX: number, minY: number, maxX: number, maxY: number)",
+    ],
+};
+
 impl LuaExportsTable<'_> for Rect {
     const EXPORT_NAME: &'static str = "Rect";
 
@@ -46,15 +54,12 @@ impl LuaExportsTable<'_> for Rect {
                     min.map(|m| *m).unwrap_or_default().0,
                     max.map(|m| *m).unwrap_or_default().0,
                 ))
-            } else if let Ok((x0, y0, x1, y1)) = ArgsNums::from_lua_multi(args, lua) {
+            } else if let Ok((x0, y0, x1, y1)) = ArgsNums::from_lua_multi(args.clone(), lua) {
                 let min = Vec2::new(x0.unwrap_or_default(), y0.unwrap_or_default());
                 let max = Vec2::new(x1.unwrap_or_default(), y1.unwrap_or_default());
                 Ok(Rect::new(min, max))
             } else {
-                // FUTURE: Better error message here using given arg types
-                Err(LuaError::RuntimeError(
-                    "Invalid arguments to constructor".to_string(),
-                ))
+                Err(RECT_NEW.invalid_args(&args))
             }
         };
 
@@ -20,6 +20,14 @@ pub struct UDim2 {
     pub(crate) y: UDim,
 }
 
+const UDIM2_NEW: Overloads = Overloads {
+    constructor: "UDim2.new",
+    signatures: &[
+        "(x: UDim, y: UDim)",
+        "(xScale: number, xOffset: number, yScale: number, yOffset: number)",
+    ],
+};
+
 impl LuaExportsTable<'_> for UDim2 {
     const EXPORT_NAME: &'static str = "UDim2";
 
@@ -49,16 +57,13 @@ impl LuaExportsTable<'_> for UDim2 {
                     x: x.map(|x| *x).unwrap_or_default(),
                     y: y.map(|y| *y).unwrap_or_default(),
                 })
-            } else if let Ok((sx, ox, sy, oy)) = ArgsNums::from_lua_multi(args, lua) {
+            } else if let Ok((sx, ox, sy, oy)) = ArgsNums::from_lua_multi(args.clone(), lua) {
                 Ok(UDim2 {
                     x: UDim::new(sx.unwrap_or_default(), ox.unwrap_or_default()),
                     y: UDim::new(sy.unwrap_or_default(), oy.unwrap_or_default()),
                 })
             } else {
-                // FUTURE: Better error message here using given arg types
-                Err(LuaError::RuntimeError(
-                    "Invalid arguments to constructor".to_string(),
-                ))
+                Err(UDIM2_NEW.invalid_args(&args))
             }
         };
 
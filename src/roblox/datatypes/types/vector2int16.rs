@@ -1,13 +1,13 @@
 use core::fmt;
 use std::ops;
 
-use glam::IVec2;
+use glam::{DVec2, IVec2};
 use mlua::prelude::*;
 use rbx_dom_weak::types::Vector2int16 as DomVector2int16;
 
 use crate::{lune::util::TableBuilder, roblox::exports::LuaExportsTable};
 
-use super::super::*;
+use super::{super::*, Vector2};
 
 /**
     An implementation of the [Vector2int16](https://create.roblox.com/docs/reference/engine/datatypes/Vector2int16)
@@ -15,6 +15,9 @@ use super::super::*;
 
     This implements all documented properties, methods &
     constructors of the Vector2int16 class as of March 2023.
+
+    Arithmetic saturates at the limits of a 16-bit integer instead of overflowing,
+    and any fractional part of the result of an operation is truncated.
 */
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vector2int16(pub IVec2);
@@ -30,8 +33,12 @@ impl LuaExportsTable<'_> for Vector2int16 {
             }))
         };
 
+        let vector2int16_from_vector2 =
+            |_, v: LuaUserDataRef<Vector2>| Ok(Vector2int16::from_dvec2(v.0.as_dvec2()));
+
         TableBuilder::new(lua)?
             .with_function("new", vector2int16_new)?
+            .with_function("fromVector2", vector2int16_from_vector2)?
             .build_readonly()
     }
 }
@@ -48,8 +55,20 @@ impl LuaUserData for Vector2int16 {
         methods.add_meta_method(LuaMetaMethod::Unm, userdata_impl_unm);
         methods.add_meta_method(LuaMetaMethod::Add, userdata_impl_add);
         methods.add_meta_method(LuaMetaMethod::Sub, userdata_impl_sub);
-        methods.add_meta_method(LuaMetaMethod::Mul, userdata_impl_mul_i32);
-        methods.add_meta_method(LuaMetaMethod::Div, userdata_impl_div_i32);
+        methods.add_method("ToVector2", |_, this, ()| Ok(Vector2(this.0.as_vec2())));
+        methods.add_meta_method(LuaMetaMethod::Mul, |_, this, rhs: LuaValue| {
+            let rhs = Vector2int16::operand(&rhs)?;
+            Ok(Vector2int16::from_dvec2(this.0.as_dvec2() * rhs))
+        });
+        methods.add_meta_method(LuaMetaMethod::Div, |_, this, rhs: LuaValue| {
+            let rhs = Vector2int16::operand(&rhs)?;
+            if rhs.cmpeq(DVec2::ZERO).any() {
+                return Err(LuaError::RuntimeError(
+                    "Attempt to divide Vector2int16 by zero".to_string(),
+                ));
+            }
+            Ok(Vector2int16::from_dvec2(this.0.as_dvec2() / rhs))
+        });
     }
 }
 
@@ -59,52 +78,61 @@ impl fmt::Display for Vector2int16 {
     }
 }
 
+impl Vector2int16 {
+    /**
+        Creates a new Vector2int16 from a vector of floats, truncating
+        and then clamping each component to the range of a 16-bit integer.
+    */
+    pub(crate) fn from_dvec2(v: DVec2) -> Self {
+        let min = DVec2::splat(f64::from(i16::MIN));
+        let max = DVec2::splat(f64::from(i16::MAX));
+        Self(v.trunc().clamp(min, max).as_ivec2())
+    }
+
+    /**
+        Converts the right hand side of a multiplication or
+        division, either a number or another Vector2int16, into floats.
+    */
+    fn operand(rhs: &LuaValue) -> LuaResult<DVec2> {
+        match rhs {
+            LuaValue::Number(n) => return Ok(DVec2::splat(*n)),
+            LuaValue::Integer(i) => return Ok(DVec2::splat(f64::from(*i))),
+            LuaValue::UserData(ud) => {
+                if let Ok(vec) = ud.borrow::<Vector2int16>() {
+                    return Ok(vec.0.as_dvec2());
+                }
+            }
+            _ => {}
+        };
+        Err(LuaError::FromLuaConversionError {
+            from: rhs.type_name(),
+            to: "Vector2int16",
+            message: Some(format!(
+                "Expected Vector2int16 or number, got {}",
+                rhs.type_name()
+            )),
+        })
+    }
+}
+
 impl ops::Neg for Vector2int16 {
     type Output = Self;
     fn neg(self) -> Self::Output {
-        Vector2int16(-self.0)
+        Self::from_dvec2(-self.0.as_dvec2())
     }
 }
 
 impl ops::Add for Vector2int16 {
     type Output = Self;
     fn add(self, rhs: Self) -> Self::Output {
-        Vector2int16(self.0 + rhs.0)
+        Self::from_dvec2(self.0.as_dvec2() + rhs.0.as_dvec2())
     }
 }
 
 impl ops::Sub for Vector2int16 {
     type Output = Self;
     fn sub(self, rhs: Self) -> Self::Output {
-        Vector2int16(self.0 - rhs.0)
-    }
-}
-
-impl ops::Mul for Vector2int16 {
-    type Output = Vector2int16;
-    fn mul(self, rhs: Self) -> Self::Output {
-        Self(self.0 * rhs.0)
-    }
-}
-
-impl ops::Mul<i32> for Vector2int16 {
-    type Output = Vector2int16;
-    fn mul(self, rhs: i32) -> Self::Output {
-        Self(self.0 * rhs)
-    }
-}
-
-impl ops::Div for Vector2int16 {
-    type Output = Vector2int16;
-    fn div(self, rhs: Self) -> Self::Output {
-        Self(self.0 / rhs.0)
-    }
-}
-
-impl ops::Div<i32> for Vector2int16 {
-    type Output = Vector2int16;
-    fn div(self, rhs: i32) -> Self::Output {
-        Self(self.0 / rhs)
+        Self::from_dvec2(self.0.as_dvec2() - rhs.0.as_dvec2())
     }
 }
 
@@ -1,13 +1,13 @@
 use core::fmt;
 use std::ops;
 
-use glam::IVec3;
+use glam::{DVec3, IVec3};
 use mlua::prelude::*;
 use rbx_dom_weak::types::Vector3int16 as DomVector3int16;
 
 use crate::{lune::util::TableBuilder, roblox::exports::LuaExportsTable};
 
-use super::super::*;
+use super::{super::*, Vector3};
 
 /**
     An implementation of the [Vector3int16](https://create.roblox.com/docs/reference/engine/datatypes/Vector3int16)
@@ -15,6 +15,9 @@ use super::super::*;
 
     This implements all documented properties, methods &
     constructors of the Vector3int16 class as of March 2023.
+
+    Arithmetic saturates at the limits of a 16-bit integer instead of overflowing,
+    and any fractional part of the result of an operation is truncated.
 */
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vector3int16(pub IVec3);
@@ -31,8 +34,12 @@ impl LuaExportsTable<'_> for Vector3int16 {
             }))
         };
 
+        let vector3int16_from_vector3 =
+            |_, v: LuaUserDataRef<Vector3>| Ok(Vector3int16::from_dvec3(v.0.as_dvec3()));
+
         TableBuilder::new(lua)?
             .with_function("new", vector3int16_new)?
+            .with_function("fromVector3", vector3int16_from_vector3)?
             .build_readonly()
     }
 }
@@ -50,63 +57,84 @@ impl LuaUserData for Vector3int16 {
         methods.add_meta_method(LuaMetaMethod::Unm, userdata_impl_unm);
         methods.add_meta_method(LuaMetaMethod::Add, userdata_impl_add);
         methods.add_meta_method(LuaMetaMethod::Sub, userdata_impl_sub);
-        methods.add_meta_method(LuaMetaMethod::Mul, userdata_impl_mul_i32);
-        methods.add_meta_method(LuaMetaMethod::Div, userdata_impl_div_i32);
+        methods.add_method("ToVector3", |_, this, ()| Ok(Vector3(this.0.as_vec3())));
+        methods.add_meta_method(LuaMetaMethod::Mul, |_, this, rhs: LuaValue| {
+            let rhs = Vector3int16::operand(&rhs)?;
+            Ok(Vector3int16::from_dvec3(this.0.as_dvec3() * rhs))
+        });
+        methods.add_meta_method(LuaMetaMethod::Div, |_, this, rhs: LuaValue| {
+            let rhs = Vector3int16::operand(&rhs)?;
+            if rhs.cmpeq(DVec3::ZERO).any() {
+                return Err(LuaError::RuntimeError(
+                    "Attempt to divide Vector3int16 by zero".to_string(),
+                ));
+            }
+            Ok(Vector3int16::from_dvec3(this.0.as_dvec3() / rhs))
+        });
     }
 }
 
 impl fmt::Display for Vector3int16 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}, {}", self.0.x, self.0.y)
+        write!(f, "{}, {}, {}", self.0.x, self.0.y, self.0.z)
+    }
+}
+
+impl Vector3int16 {
+    /**
+        Creates a new Vector3int16 from a vector of floats, truncating
+        and then clamping each component to the range of a 16-bit integer.
+    */
+    pub(crate) fn from_dvec3(v: DVec3) -> Self {
+        let min = DVec3::splat(f64::from(i16::MIN));
+        let max = DVec3::splat(f64::from(i16::MAX));
+        Self(v.trunc().clamp(min, max).as_ivec3())
+    }
+
+    /**
+        Converts the right hand side of a multiplication or
+        division, either a number or another Vector3int16, into floats.
+    */
+    fn operand(rhs: &LuaValue) -> LuaResult<DVec3> {
+        match rhs {
+            LuaValue::Number(n) => return Ok(DVec3::splat(*n)),
+            LuaValue::Integer(i) => return Ok(DVec3::splat(f64::from(*i))),
+            LuaValue::UserData(ud) => {
+                if let Ok(vec) = ud.borrow::<Vector3int16>() {
+                    return Ok(vec.0.as_dvec3());
+                }
+            }
+            _ => {}
+        };
+        Err(LuaError::FromLuaConversionError {
+            from: rhs.type_name(),
+            to: "Vector3int16",
+            message: Some(format!(
+                "Expected Vector3int16 or number, got {}",
+                rhs.type_name()
+            )),
+        })
     }
 }
 
 impl ops::Neg for Vector3int16 {
     type Output = Self;
     fn neg(self) -> Self::Output {
-        Vector3int16(-self.0)
+        Self::from_dvec3(-self.0.as_dvec3())
     }
 }
 
 impl ops::Add for Vector3int16 {
     type Output = Self;
     fn add(self, rhs: Self) -> Self::Output {
-        Vector3int16(self.0 + rhs.0)
+        Self::from_dvec3(self.0.as_dvec3() + rhs.0.as_dvec3())
     }
 }
 
 impl ops::Sub for Vector3int16 {
     type Output = Self;
     fn sub(self, rhs: Self) -> Self::Output {
-        Vector3int16(self.0 - rhs.0)
-    }
-}
-
-impl ops::Mul for Vector3int16 {
-    type Output = Vector3int16;
-    fn mul(self, rhs: Self) -> Self::Output {
-        Self(self.0 * rhs.0)
-    }
-}
-
-impl ops::Mul<i32> for Vector3int16 {
-    type Output = Vector3int16;
-    fn mul(self, rhs: i32) -> Self::Output {
-        Self(self.0 * rhs)
-    }
-}
-
-impl ops::Div for Vector3int16 {
-    type Output = Vector3int16;
-    fn div(self, rhs: Self) -> Self::Output {
-        Self(self.0 / rhs.0)
-    }
-}
-
-impl ops::Div<i32> for Vector3int16 {
-    type Output = Vector3int16;
-    fn div(self, rhs: i32) -> Self::Output {
-        Self(self.0 / rhs)
+        Self::from_dvec3(self.0.as_dvec3() - rhs.0.as_dvec3())
     }
 }
 
@@ -19,6 +19,8 @@ pub enum DocumentError {
     FromDataModelInvalidArgs,
     #[error("Failed to convert into a model - a given instance is a DataModel")]
     FromInstanceArrayInvalidArgs,
+    #[error("Failed to convert place into a model - the given place has no Workspace")]
+    IntoModelMissingWorkspace,
 }
 
 impl From<DocumentError> for LuaError {
@@ -8,14 +8,16 @@ mod error;
 mod format;
 mod kind;
 mod postprocessing;
+mod validation;
 
 pub use error::*;
 pub use format::*;
 pub use kind::*;
+pub use validation::*;
 
 use postprocessing::*;
 
-use crate::roblox::instance::{data_model, Instance};
+use crate::roblox::instance::{data_model, terrain, workspace, Instance};
 
 pub type DocumentResult<T> = Result<T, DocumentError>;
 
@@ -236,6 +238,73 @@ impl Document {
         Ok(root_child_instances)
     }
 
+    /**
+        Converts this document into a document of the given kind, without going through instances.
+
+        Converting a model into a place puts all of the instances in the model
+        into the `Workspace` of a new place, and converting a place into a model
+        creates a model out of the children of its `Workspace`, except for any
+        `Terrain` and `Camera`, which every place already has its own of.
+
+        Will error if converting a place that does not have a `Workspace` into a model.
+    */
+    pub fn into_kind(mut self, kind: DocumentKind) -> DocumentResult<Self> {
+        match (self.kind, kind) {
+            (DocumentKind::Model, DocumentKind::Place) => {
+                let dom_root = self.dom.root_ref();
+                let model_child_refs = self.dom.root().children().to_vec();
+                let workspace_ref = self
+                    .dom
+                    .insert(dom_root, DomInstanceBuilder::new(workspace::CLASS_NAME));
+                for child_ref in model_child_refs {
+                    self.dom.transfer_within(child_ref, workspace_ref);
+                }
+                postprocess_dom_for_place(&mut self.dom);
+                self.kind = DocumentKind::Place;
+                Ok(self)
+            }
+            (DocumentKind::Place, DocumentKind::Model) => {
+                let workspace_ref = self
+                    .dom
+                    .root()
+                    .children()
+                    .iter()
+                    .copied()
+                    .find(|child_ref| {
+                        self.dom
+                            .get_by_ref(*child_ref)
+                            .is_some_and(|child| child.class == workspace::CLASS_NAME)
+                    })
+                    .ok_or(DocumentError::IntoModelMissingWorkspace)?;
+                let workspace_child_refs = self
+                    .dom
+                    .get_by_ref(workspace_ref)
+                    .map(|workspace| workspace.children().to_vec())
+                    .unwrap_or_default();
+
+                let mut dom = WeakDom::new(DomInstanceBuilder::new("ROOT"));
+                let dom_root = dom.root_ref();
+                for child_ref in workspace_child_refs {
+                    let is_place_only = self.dom.get_by_ref(child_ref).is_some_and(|child| {
+                        child.class == terrain::CLASS_NAME || child.class == "Camera"
+                    });
+                    if !is_place_only {
+                        self.dom.transfer(child_ref, &mut dom, dom_root);
+                    }
+                }
+
+                postprocess_dom_for_model(&mut dom);
+
+                Ok(Self {
+                    kind: DocumentKind::Model,
+                    format: self.format,
+                    dom,
+                })
+            }
+            _ => Ok(self),
+        }
+    }
+
     /**
         Creates a place document out of a DataModel instance.
 
@@ -0,0 +1,251 @@
+use std::fmt;
+
+use rbx_dom_weak::{
+    types::{Ref as DomRef, Variant as DomValue},
+    Instance as DomInstance, WeakDom,
+};
+
+use crate::roblox::shared::instance::{class_exists, find_property_info};
+
+use super::Document;
+
+/**
+    Maximum length of an attribute name, matching the limit enforced by Roblox.
+*/
+const MAX_ATTRIBUTE_NAME_LENGTH: usize = 100;
+
+/**
+    Maximum size of the serialized attributes of a single instance.
+
+    Roblox does not document an exact limit, but instances with attribute
+    payloads larger than this are slow to replicate and load, and are
+    almost always the result of a bug in whatever created the file.
+*/
+const MAX_ATTRIBUTES_SIZE: usize = 64 * 1024;
+
+/**
+    The kind of a structural problem found in a document.
+*/
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ValidationProblemKind {
+    DanglingReferent,
+    UnknownClass,
+    UnknownProperty,
+    OversizedAttributes,
+}
+
+impl ValidationProblemKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::DanglingReferent => "DanglingReferent",
+            Self::UnknownClass => "UnknownClass",
+            Self::UnknownProperty => "UnknownProperty",
+            Self::OversizedAttributes => "OversizedAttributes",
+        }
+    }
+}
+
+impl fmt::Display for ValidationProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/**
+    A structural problem found in a document, together
+    with the path of the instance that it was found in.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ValidationProblem {
+    pub path: String,
+    pub kind: ValidationProblemKind,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}] {}", self.path, self.kind, self.message)
+    }
+}
+
+impl Document {
+    /**
+        Checks the document for structural problems, such as referents to
+        instances that do not exist, classes and properties that are not
+        known to the reflection database, and oversized attribute payloads.
+
+        Problems are sorted by the path of the instance they were found in.
+    */
+    pub fn validate(&self) -> Vec<ValidationProblem> {
+        let mut problems = Vec::new();
+        let mut stack = self
+            .dom
+            .root()
+            .children()
+            .iter()
+            .rev()
+            .map(|child_ref| (*child_ref, None::<String>))
+            .collect::<Vec<_>>();
+        while let Some((dom_ref, parent_path)) = stack.pop() {
+            let instance = match self.dom.get_by_ref(dom_ref) {
+                Some(instance) => instance,
+                None => continue,
+            };
+            let path = match parent_path {
+                Some(parent_path) => format!("{parent_path}.{}", instance.name),
+                None => instance.name.clone(),
+            };
+            validate_instance(&self.dom, instance, &path, &mut problems);
+            for child_ref in instance.children().iter().rev() {
+                stack.push((*child_ref, Some(path.clone())));
+            }
+        }
+        problems.sort();
+        problems
+    }
+}
+
+fn validate_instance(
+    dom: &WeakDom,
+    instance: &DomInstance,
+    path: &str,
+    problems: &mut Vec<ValidationProblem>,
+) {
+    let mut push = |kind, message| {
+        problems.push(ValidationProblem {
+            path: path.to_string(),
+            kind,
+            message,
+        });
+    };
+
+    // NOTE: We can not know which properties an unknown class
+    // has, so we only check its referents and attributes below
+    let known_class = class_exists(&instance.class);
+    if !known_class {
+        push(
+            ValidationProblemKind::UnknownClass,
+            format!("Class '{}' does not exist", instance.class),
+        );
+    }
+
+    for (name, value) in &instance.properties {
+        match value {
+            DomValue::Ref(referent) if is_dangling(dom, *referent) => {
+                push(
+                    ValidationProblemKind::DanglingReferent,
+                    format!("Property '{name}' refers to an instance that does not exist"),
+                );
+            }
+            DomValue::Attributes(attributes) => {
+                for (attribute_name, _) in attributes.iter() {
+                    if attribute_name.len() > MAX_ATTRIBUTE_NAME_LENGTH {
+                        push(
+                            ValidationProblemKind::OversizedAttributes,
+                            format!(
+                                "Attribute name '{attribute_name}' is longer than {MAX_ATTRIBUTE_NAME_LENGTH} characters"
+                            ),
+                        );
+                    }
+                }
+                let mut bytes = Vec::new();
+                if attributes.to_writer(&mut bytes).is_ok() && bytes.len() > MAX_ATTRIBUTES_SIZE {
+                    push(
+                        ValidationProblemKind::OversizedAttributes,
+                        format!(
+                            "Attributes are {} bytes, which is more than the maximum of {MAX_ATTRIBUTES_SIZE} bytes",
+                            bytes.len()
+                        ),
+                    );
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if known_class
+            && !matches!(name.as_str(), "Attributes" | "Tags")
+            && find_property_info(&instance.class, name).is_none()
+        {
+            push(
+                ValidationProblemKind::UnknownProperty,
+                format!(
+                    "Property '{name}' does not exist on class '{}'",
+                    instance.class
+                ),
+            );
+        }
+    }
+}
+
+fn is_dangling(dom: &WeakDom, referent: DomRef) -> bool {
+    !referent.is_none() && dom.get_by_ref(referent).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use rbx_dom_weak::InstanceBuilder as DomInstanceBuilder;
+
+    use super::*;
+    use crate::roblox::document::{DocumentFormat, DocumentKind};
+
+    fn document(builder: DomInstanceBuilder) -> Document {
+        let mut dom = WeakDom::new(DomInstanceBuilder::new("ROOT"));
+        let root = dom.root_ref();
+        dom.insert(root, builder);
+        Document {
+            kind: DocumentKind::Model,
+            format: DocumentFormat::Binary,
+            dom,
+        }
+    }
+
+    fn kinds(doc: &Document) -> Vec<(String, ValidationProblemKind)> {
+        doc.validate()
+            .into_iter()
+            .map(|problem| (problem.path, problem.kind))
+            .collect()
+    }
+
+    #[test]
+    fn valid_document() {
+        let doc = document(
+            DomInstanceBuilder::new("Folder")
+                .with_name("Root")
+                .with_child(DomInstanceBuilder::new("Part").with_property("Anchored", true)),
+        );
+        assert_eq!(kinds(&doc), Vec::new());
+    }
+
+    #[test]
+    fn unknown_classes_and_properties() {
+        let doc = document(
+            DomInstanceBuilder::new("Folder")
+                .with_name("Root")
+                .with_property("NotAProperty", true)
+                .with_child(DomInstanceBuilder::new("NotAClass").with_name("Child")),
+        );
+        assert_eq!(
+            kinds(&doc),
+            vec![
+                ("Root".to_string(), ValidationProblemKind::UnknownProperty),
+                (
+                    "Root.Child".to_string(),
+                    ValidationProblemKind::UnknownClass
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn dangling_referents() {
+        let doc = document(
+            DomInstanceBuilder::new("ObjectValue")
+                .with_name("Value")
+                .with_property("Value", DomRef::new()),
+        );
+        assert_eq!(
+            kinds(&doc),
+            vec![("Value".to_string(), ValidationProblemKind::DanglingReferent)]
+        );
+    }
+}
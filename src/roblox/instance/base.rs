@@ -12,7 +12,7 @@ use crate::roblox::{
         types::EnumItem,
         userdata_impl_eq, userdata_impl_to_string,
     },
-    shared::instance::{class_is_a, find_property_info},
+    shared::instance::{class_is_a, find_all_property_names, find_property_info},
 };
 
 use super::{data_model, Instance};
@@ -159,6 +159,12 @@ pub fn add_methods<'lua, M: LuaUserDataMethods<'lua, Instance>>(m: &mut M) {
             }
         },
     );
+    m.add_method(
+        "GetProperties",
+        |lua, this, include_defaults: Option<bool>| {
+            instance_properties_get(lua, this, matches!(include_defaults, Some(true)))
+        },
+    );
     m.add_method("GetTags", |_, this, ()| {
         ensure_not_destroyed(this)?;
         Ok(this.get_tags())
@@ -201,7 +207,7 @@ fn ensure_not_destroyed(inst: &Instance) -> LuaResult<()> {
     3. Get a current child of the instance
     4. No valid property or instance found, throw error
 */
-fn instance_property_get<'lua>(
+pub(crate) fn instance_property_get<'lua>(
     lua: &'lua Lua,
     this: &Instance,
     prop_name: String,
@@ -275,6 +281,65 @@ fn instance_property_get<'lua>(
     }
 }
 
+/*
+    Gets all property values for an instance, as a table keyed by property name.
+
+    Getting all values does the following:
+
+    1. Add the special "Name" property
+    2. Convert all properties that are currently set for the instance
+        2a. Convert enums into their known EnumItem, if any OR
+        2b. Convert any other value into its Lua representation
+    3. Optionally add known default values for any properties not yet set
+
+    Properties that can not be converted are skipped instead of erroring,
+    since a single unknown property type should not make the rest inaccessible.
+*/
+fn instance_properties_get<'lua>(
+    lua: &'lua Lua,
+    this: &Instance,
+    include_defaults: bool,
+) -> LuaResult<LuaTable<'lua>> {
+    ensure_not_destroyed(this)?;
+
+    let props = this.get_properties();
+    let tab = lua.create_table_with_capacity(0, props.len() + 1)?;
+    tab.set("Name", this.get_name())?;
+
+    for (prop_name, prop) in props {
+        let enum_name =
+            find_property_info(&this.class_name, &prop_name).and_then(|info| info.enum_name);
+        let value = match (prop, enum_name) {
+            (DomValue::Enum(enum_value), Some(enum_name)) => {
+                match EnumItem::from_enum_name_and_value(&enum_name, enum_value.to_u32()) {
+                    Some(item) => item.into_lua(lua)?,
+                    None => continue,
+                }
+            }
+            (prop, _) => match LuaValue::dom_value_to_lua(lua, &prop) {
+                Ok(value) => value,
+                Err(_) => continue,
+            },
+        };
+        tab.set(prop_name, value)?;
+    }
+
+    if include_defaults {
+        for prop_name in find_all_property_names(&this.class_name) {
+            if matches!(prop_name, "ClassName" | "Name" | "Parent")
+                || tab.contains_key(prop_name)?
+            {
+                continue;
+            }
+            if let Ok(value) = instance_property_get(lua, this, prop_name.to_string()) {
+                tab.set(prop_name, value)?;
+            }
+        }
+    }
+
+    Ok(tab)
+}
+
 /*
     Sets a property value for an instance.
 
@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeMap, VecDeque},
+    collections::{BTreeMap, HashMap, VecDeque},
     fmt,
     hash::{Hash, Hasher},
     sync::Mutex,
@@ -330,6 +330,27 @@ impl Instance {
             .cloned()
     }
 
+    /**
+        Gets all properties that are currently set for the instance.
+
+        Note that this will not include attributes or tags, and that properties
+        which have never been set will not be present, default values for
+        those must be looked up in the reflection database separately.
+    */
+    pub fn get_properties(&self) -> BTreeMap<String, DomValue> {
+        let dom = INTERNAL_DOM.lock().expect("Failed to lock document");
+        let inst = dom
+            .get_by_ref(self.dom_ref)
+            .expect("Failed to find instance in document");
+        inst.properties
+            .iter()
+            .filter(|(name, _)| {
+                !matches!(name.as_str(), PROPERTY_NAME_ATTRIBUTES | PROPERTY_NAME_TAGS)
+            })
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
     /**
         Sets a property for the instance.
 
@@ -346,6 +367,22 @@ impl Instance {
             .insert(name.as_ref().to_string(), value);
     }
 
+    /**
+        Removes a property from the instance, resetting it to its default value.
+
+        Returns `true` if the property was set and has been removed, `false` otherwise.
+    */
+    pub fn remove_property(&self, name: impl AsRef<str>) -> bool {
+        INTERNAL_DOM
+            .lock()
+            .expect("Failed to lock document")
+            .get_by_ref_mut(self.dom_ref)
+            .expect("Failed to find instance in document")
+            .properties
+            .remove(name.as_ref())
+            .is_some()
+    }
+
     /**
         Gets an attribute for the instance, if it exists.
 
@@ -689,6 +726,111 @@ impl Instance {
 
         None
     }
+
+    /**
+        Moves or copies the instance and all of its descendants into the
+        tree of another instance, such as a `DataModel` from a different place.
+
+        Referent properties in the adopted tree are remapped so that they stay valid:
+
+        - When copying, referents to instances within the copied
+          tree are changed to point to their matching copies.
+        - Referents to instances that are neither in the adopted tree nor in the
+          tree that the target instance belongs to are cleared, since they could
+          never be saved together with the adopted tree.
+
+        All other properties, including attributes and tags, are preserved as-is.
+
+        Returns the adopted instance, which is the instance itself when moving.
+
+        Panics if moving an instance into itself or one of its descendants.
+    */
+    pub fn adopt_into(&self, target: &Instance, copy: bool) -> Instance {
+        let mut dom = INTERNAL_DOM.lock().expect("Failed to lock document");
+        let dom_root = dom.root_ref();
+
+        let adopted_ref = if copy {
+            dom.clone_within(self.dom_ref)
+        } else {
+            if is_within(&dom, target.dom_ref, self.dom_ref) {
+                panic!("Instances can not be adopted into themselves or their descendants")
+            }
+            self.dom_ref
+        };
+        dom.transfer_within(adopted_ref, target.dom_ref);
+
+        // Clones keep the order of their children, so we can find the
+        // copy of each original instance by walking both trees at once
+        let mut remapped = HashMap::new();
+        if copy {
+            let mut stack = vec![(self.dom_ref, adopted_ref)];
+            while let Some((original_ref, copy_ref)) = stack.pop() {
+                remapped.insert(original_ref, copy_ref);
+                let original = dom
+                    .get_by_ref(original_ref)
+                    .expect("Failed to find instance");
+                let copy = dom.get_by_ref(copy_ref).expect("Failed to find instance");
+                stack.extend(
+                    original
+                        .children()
+                        .iter()
+                        .copied()
+                        .zip(copy.children().iter().copied()),
+                );
+            }
+        }
+
+        let mut target_root = target.dom_ref;
+        while let Some(parent_ref) = dom.get_by_ref(target_root).map(DomInstance::parent) {
+            if parent_ref == dom_root || parent_ref.is_none() {
+                break;
+            }
+            target_root = parent_ref;
+        }
+
+        let mut changes = Vec::new();
+        let mut queue = VecDeque::from([adopted_ref]);
+        while let Some(inst) = queue
+            .pop_front()
+            .and_then(|inst_ref| dom.get_by_ref(inst_ref))
+        {
+            for (name, value) in &inst.properties {
+                if let DomValue::Ref(referent) = value {
+                    if referent.is_none() {
+                        continue;
+                    }
+                    if let Some(remapped_ref) = remapped.get(referent) {
+                        changes.push((inst.referent(), name.clone(), *remapped_ref));
+                    } else if !is_within(&dom, *referent, target_root) {
+                        changes.push((inst.referent(), name.clone(), DomRef::none()));
+                    }
+                }
+            }
+            queue.extend(inst.children());
+        }
+        for (inst_ref, name, referent) in changes {
+            dom.get_by_ref_mut(inst_ref)
+                .expect("Failed to find instance in document")
+                .properties
+                .insert(name, DomValue::Ref(referent));
+        }
+
+        drop(dom); // Self::new needs mutex handle, drop it first
+        Self::new(adopted_ref)
+    }
+}
+
+/**
+    Checks if the instance with the given referent is the given ancestor or one of its descendants.
+*/
+fn is_within(dom: &WeakDom, mut dom_ref: DomRef, ancestor_ref: DomRef) -> bool {
+    while let Some(inst) = dom.get_by_ref(dom_ref) {
+        if dom_ref == ancestor_ref {
+            return true;
+        }
+        dom_ref = inst.parent();
+    }
+    false
 }
 
 impl LuaExportsTable<'_> for Instance {
@@ -29,6 +29,7 @@ fn create_all_exports(lua: &Lua) -> LuaResult<Vec<(&'static str, LuaValue)>> {
         export::<NumberSequence>(lua)?,
         export::<NumberSequenceKeypoint>(lua)?,
         export::<PhysicalProperties>(lua)?,
+        export::<Random>(lua)?,
         export::<Ray>(lua)?,
         export::<Rect>(lua)?,
         export::<UDim>(lua)?,
@@ -97,6 +97,40 @@ pub(crate) fn find_property_info(
     class_info
 }
 
+/**
+    Finds the names of all properties of the given class.
+
+    This will also include properties from all superclasses, properties
+    for subclasses will come first, and each name is only returned once.
+
+    Returns an empty list if the class does not exist.
+*/
+pub(crate) fn find_all_property_names(instance_class: impl AsRef<str>) -> Vec<&'static str> {
+    let db = rbx_reflection_database::get();
+
+    let mut names = Vec::new();
+    let mut class_name = Cow::Borrowed(instance_class.as_ref());
+
+    while let Some(class) = db.classes.get(class_name.as_ref()) {
+        for property_name in class.properties.keys() {
+            let property_name: &'static str = property_name.as_ref();
+            // Same as in find_property_info, attributes and tags
+            // should never be treated as properties, skip them
+            if matches!(property_name, "Attributes" | "Tags") || names.contains(&property_name) {
+                continue;
+            }
+            names.push(property_name);
+        }
+        if let Some(sup) = &class.superclass {
+            class_name = Cow::Borrowed(sup)
+        } else {
+            break;
+        }
+    }
+
+    names
+}
+
 /**
     Checks if an instance class exists in the reflection database.
 */
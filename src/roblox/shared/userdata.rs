@@ -89,31 +89,6 @@ where
     })
 }
 
-pub fn userdata_impl_mul_i32<D>(_: &Lua, datatype: &D, rhs: LuaValue) -> LuaResult<D>
-where
-    D: LuaUserData + ops::Mul<D, Output = D> + ops::Mul<i32, Output = D> + Copy + 'static,
-{
-    match &rhs {
-        LuaValue::Number(n) => return Ok(*datatype * *n as i32),
-        LuaValue::Integer(i) => return Ok(*datatype * *i),
-        LuaValue::UserData(ud) => {
-            if let Ok(vec) = ud.borrow::<D>() {
-                return Ok(*datatype * *vec);
-            }
-        }
-        _ => {}
-    };
-    Err(LuaError::FromLuaConversionError {
-        from: rhs.type_name(),
-        to: type_name::<D>(),
-        message: Some(format!(
-            "Expected {} or number, got {}",
-            type_name::<D>(),
-            rhs.type_name()
-        )),
-    })
-}
-
 pub fn userdata_impl_div_f32<D>(_: &Lua, datatype: &D, rhs: LuaValue) -> LuaResult<D>
 where
     D: LuaUserData + ops::Div<D, Output = D> + ops::Div<f32, Output = D> + Copy + 'static,
@@ -138,28 +113,3 @@ where
         )),
     })
 }
-
-pub fn userdata_impl_div_i32<D>(_: &Lua, datatype: &D, rhs: LuaValue) -> LuaResult<D>
-where
-    D: LuaUserData + ops::Div<D, Output = D> + ops::Div<i32, Output = D> + Copy + 'static,
-{
-    match &rhs {
-        LuaValue::Number(n) => return Ok(*datatype / *n as i32),
-        LuaValue::Integer(i) => return Ok(*datatype / *i),
-        LuaValue::UserData(ud) => {
-            if let Ok(vec) = ud.borrow::<D>() {
-                return Ok(*datatype / *vec);
-            }
-        }
-        _ => {}
-    };
-    Err(LuaError::FromLuaConversionError {
-        from: rhs.type_name(),
-        to: type_name::<D>(),
-        message: Some(format!(
-            "Expected {} or number, got {}",
-            type_name::<D>(),
-            rhs.type_name()
-        )),
-    })
-}
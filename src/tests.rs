@@ -3,14 +3,36 @@ use std::process::ExitCode;
 use anyhow::Result;
 use console::set_colors_enabled;
 use console::set_colors_enabled_stderr;
-use tokio::fs::read_to_string;
+use tokio::{fs::read_to_string, task::JoinHandle};
 
 use crate::Lune;
 
+#[cfg(feature = "acme")]
+mod acme_server;
+mod ftp_server;
+#[cfg(feature = "ssh")]
+mod ssh_server;
+
 const ARGS: &[&str] = &["Foo", "Bar"];
 
+/**
+    A server that a test script connects to, which is stopped when the test finishes.
+
+    Its args, such as the port that it listens on, are given to the script after the usual test args.
+*/
+struct TestServer {
+    args: Vec<String>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
 macro_rules! create_tests {
-    ($($name:ident: $value:expr,)*) => { $(
+    ($($name:ident: $value:expr $(=> $server:path)?,)*) => { $(
         #[tokio::test(flavor = "multi_thread")]
         async fn $name() -> Result<ExitCode> {
             // Disable styling for stdout and stderr since
@@ -20,13 +42,16 @@ macro_rules! create_tests {
             // The rest of the test logic can continue as normal
             let full_name = format!("tests/{}.luau", $value);
             let script = read_to_string(&full_name).await?;
-            let mut lune = Lune::new().with_args(
-                ARGS
-                    .clone()
-                    .iter()
-                    .map(ToString::to_string)
-                    .collect::<Vec<_>>()
-            );
+            #[allow(unused_mut)]
+            let mut args = ARGS
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            $(
+                let server: TestServer = $server().await?;
+                args.extend(server.args.iter().cloned());
+            )?
+            let mut lune = Lune::new().with_args(args);
             let script_name = full_name
 				.trim_end_matches(".luau")
 				.trim_end_matches(".lua")
@@ -38,32 +63,86 @@ macro_rules! create_tests {
 }
 
 create_tests! {
+    cache_artifacts: "cache/artifacts",
+    collections_heap: "collections/heap",
+    collections_map: "collections/map",
+    collections_set: "collections/set",
+    collections_sorted_map: "collections/sorted_map",
+    diff_lines: "diff/lines",
+    diff_tables: "diff/tables",
     fs_files: "fs/files",
     fs_copy: "fs/copy",
     fs_dirs: "fs/dirs",
+    fs_glob: "fs/glob",
+    fs_lines: "fs/lines",
+    fs_links: "fs/links",
+    fs_memory: "fs/memory",
     fs_metadata: "fs/metadata",
     fs_move: "fs/move",
+    fs_open: "fs/open",
+    fs_rename: "fs/rename",
+    fs_temp: "fs/temp",
+
+    log: "log/log",
 
     luau_compile: "luau/compile",
     luau_load: "luau/load",
     luau_options: "luau/options",
 
+    metrics: "metrics/metrics",
+
+    net_dns: "net/dns",
+    net_file_response: "net/file_response",
+    net_ftp: "net/ftp" => ftp_server::start,
+    net_limiter: "net/limiter",
+    net_mock: "net/mock",
+    net_request_auth: "net/request/auth",
+    net_request_client: "net/request/client",
     net_request_codes: "net/request/codes",
     net_request_compression: "net/request/compression",
+    net_request_compression_body: "net/request/compression_body",
+    net_request_cookies: "net/request/cookies",
+    net_request_decode_text: "net/request/decode_text",
+    net_request_digest: "net/request/digest",
+    net_request_form: "net/request/form",
+    net_request_ip_version: "net/request/ip_version",
     net_request_methods: "net/request/methods",
+    net_request_proxy: "net/request/proxy",
     net_request_query: "net/request/query",
     net_request_redirect: "net/request/redirect",
+    net_request_retry: "net/request/retry",
+    net_request_sse: "net/request/sse",
+    net_request_stream: "net/request/stream",
+    net_request_timeout: "net/request/timeout",
     net_url_encode: "net/url/encode",
     net_url_decode: "net/url/decode",
+    net_url_parse: "net/url/parse",
+    net_serve_address: "net/serve/address",
+    net_serve_cancel: "net/serve/cancel",
+    net_serve_handle: "net/serve/handle",
     net_serve_requests: "net/serve/requests",
+    net_serve_routes: "net/serve/routes",
+    net_serve_threads: "net/serve/threads",
+    net_serve_timeout: "net/serve/timeout",
+    net_serve_tls: "net/serve/tls",
+    net_serve_update: "net/serve/update",
     net_serve_websockets: "net/serve/websockets",
+    net_single_flight: "net/single_flight",
+    net_socket_backpressure: "net/socket/backpressure",
+    net_socket_ping: "net/socket/ping",
+    net_socket_proxy: "net/socket/proxy",
     net_socket_wss: "net/socket/wss",
     net_socket_wss_rw: "net/socket/wss_rw",
+    net_tcp: "net/tcp",
+    net_udp: "net/udp",
 
     process_args: "process/args",
+    process_create: "process/create",
     process_cwd: "process/cwd",
     process_env: "process/env",
     process_exit: "process/exit",
+    process_on_exit: "process/onExit",
+    process_on_signal: "process/onSignal",
     process_spawn: "process/spawn",
 
     require_async: "require/tests/async",
@@ -77,6 +156,7 @@ create_tests! {
     require_multi_ext: "require/tests/multi_ext",
     require_nested: "require/tests/nested",
     require_parents: "require/tests/parents",
+    require_remote: "require/tests/remote",
     require_siblings: "require/tests/siblings",
 
     global_g_table: "globals/_G",
@@ -88,10 +168,19 @@ create_tests! {
     global_typeof: "globals/typeof",
     global_warn: "globals/warn",
 
+    serde_base64_roundtrip: "serde/base64/roundtrip",
     serde_compression_files: "serde/compression/files",
     serde_compression_roundtrip: "serde/compression/roundtrip",
+    serde_compression_stream: "serde/compression/stream",
+    serde_hash: "serde/hash/hash",
+    serde_hash_hmac: "serde/hash/hmac",
     serde_json_decode: "serde/json/decode",
+    serde_json_decode_stream: "serde/json/decodeStream",
     serde_json_encode: "serde/json/encode",
+    serde_json_lenient: "serde/json/lenient",
+    serde_json_ordered: "serde/json/ordered",
+    serde_ndjson: "serde/json/ndjson",
+    serde_text_encoding: "serde/text/encoding",
     serde_toml_decode: "serde/toml/decode",
     serde_toml_encode: "serde/toml/encode",
 
@@ -100,16 +189,77 @@ create_tests! {
     stdio_style: "stdio/style",
     stdio_write: "stdio/write",
     stdio_ewrite: "stdio/ewrite",
+    stdio_terminal: "stdio/terminal",
+
+    struct_layout: "struct/layout",
 
     task_cancel: "task/cancel",
+    task_channel: "task/channel",
     task_defer: "task/defer",
     task_delay: "task/delay",
+    task_group: "task/group",
+    task_name: "task/name",
+    task_parallel: "task/parallel",
+    task_priority: "task/priority",
     task_spawn: "task/spawn",
+    task_timeout: "task/timeout",
     task_wait: "task/wait",
+
+    trace: "trace/trace",
+
+    unicode: "unicode/unicode",
+}
+
+#[cfg(feature = "bignum")]
+create_tests! {
+    bignum_bigint: "bignum/bigint",
+    bignum_decimal: "bignum/decimal",
+    bignum_serde: "bignum/serde",
+}
+
+#[cfg(feature = "watch")]
+create_tests! {
+    fs_watch: "fs/watch",
+}
+
+#[cfg(feature = "csv")]
+create_tests! {
+    serde_csv_roundtrip: "serde/csv/roundtrip",
+}
+
+#[cfg(feature = "msgpack")]
+create_tests! {
+    serde_msgpack_roundtrip: "serde/msgpack/roundtrip",
+}
+
+#[cfg(feature = "image")]
+create_tests! {
+    image_encode: "image/encode",
+    image_qrcode: "image/qrcode",
+    image_transform: "image/transform",
+}
+
+#[cfg(feature = "sqlite")]
+create_tests! {
+    queue: "queue/queue",
+}
+
+#[cfg(feature = "acme")]
+create_tests! {
+    net_serve_acme: "net/serve/acme" => acme_server::start,
+}
+
+#[cfg(feature = "ssh")]
+create_tests! {
+    net_ssh_exec: "net/ssh/exec" => ssh_server::start,
+    net_ssh_forward: "net/ssh/forward" => ssh_server::start,
+    net_ssh_sftp: "net/ssh/sftp" => ssh_server::start,
 }
 
 #[cfg(feature = "roblox")]
 create_tests! {
+    roblox_api_endpoints: "roblox/api/endpoints",
+
     roblox_datatype_axes: "roblox/datatypes/Axes",
     roblox_datatype_brick_color: "roblox/datatypes/BrickColor",
     roblox_datatype_cframe: "roblox/datatypes/CFrame",
@@ -123,6 +273,7 @@ create_tests! {
     roblox_datatype_number_sequence: "roblox/datatypes/NumberSequence",
     roblox_datatype_number_sequence_keypoint: "roblox/datatypes/NumberSequenceKeypoint",
     roblox_datatype_physical_properties: "roblox/datatypes/PhysicalProperties",
+    roblox_datatype_random: "roblox/datatypes/Random",
     roblox_datatype_ray: "roblox/datatypes/Ray",
     roblox_datatype_rect: "roblox/datatypes/Rect",
     roblox_datatype_udim: "roblox/datatypes/UDim",
@@ -134,10 +285,16 @@ create_tests! {
     roblox_datatype_vector3: "roblox/datatypes/Vector3",
     roblox_datatype_vector3int16: "roblox/datatypes/Vector3int16",
 
+    roblox_files_adopt: "roblox/files/adopt",
+    roblox_files_bake_gradient: "roblox/files/bakeGradient",
+    roblox_files_convert: "roblox/files/convert",
     roblox_files_deserialize_model: "roblox/files/deserializeModel",
     roblox_files_deserialize_place: "roblox/files/deserializePlace",
+    roblox_files_export: "roblox/files/export",
+    roblox_files_property_filter: "roblox/files/propertyFilter",
     roblox_files_serialize_model: "roblox/files/serializeModel",
     roblox_files_serialize_place: "roblox/files/serializePlace",
+    roblox_files_validate: "roblox/files/validate",
 
     roblox_instance_attributes: "roblox/instance/attributes",
     roblox_instance_new: "roblox/instance/new",
@@ -160,6 +317,7 @@ create_tests! {
     roblox_instance_methods_get_children: "roblox/instance/methods/GetChildren",
     roblox_instance_methods_get_descendants: "roblox/instance/methods/GetDescendants",
     roblox_instance_methods_get_full_name: "roblox/instance/methods/GetFullName",
+    roblox_instance_methods_get_properties: "roblox/instance/methods/GetProperties",
     roblox_instance_methods_is_a: "roblox/instance/methods/IsA",
     roblox_instance_methods_is_ancestor_of: "roblox/instance/methods/IsAncestorOf",
     roblox_instance_methods_is_descendant_of: "roblox/instance/methods/IsDescendantOf",
@@ -171,3 +329,102 @@ create_tests! {
     roblox_reflection_enums: "roblox/reflection/enums",
     roblox_reflection_property: "roblox/reflection/property",
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn embedding_channel() -> Result<ExitCode> {
+    let mut lune = Lune::new();
+    let sender = lune.channel::<(u32, String)>("events")?;
+
+    std::thread::spawn(move || {
+        for i in 1..=3 {
+            sender
+                .send((i, format!("event {i}")))
+                .expect("Failed to send event");
+        }
+    });
+
+    let script = r#"
+        local received = {}
+        while true do
+            local event = events:next()
+            if event == nil then
+                break
+            end
+            table.insert(received, event)
+        end
+        assert(#received == 3, "Expected 3 events, got " .. tostring(#received))
+        for i, event in received do
+            assert(event[1] == i, "Events were received out of order")
+            assert(event[2] == "event " .. tostring(i), "Event contents were invalid")
+        end
+    "#;
+
+    Ok(lune.run("embedding_channel", script).await?)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn embedding_sandbox() -> Result<ExitCode> {
+    let mut lune = Lune::new().with_sandbox("tests/require/tests/sandbox", ["serde"])?;
+
+    let script = r#"
+        local helper = require("tests/require/tests/sandbox_helper")
+        assert(helper.fs ~= nil, "Modules outside of the sandbox should be able to re-export builtins")
+
+        local plugin = require("tests/require/tests/sandbox/plugin")
+        assert(plugin.hasSerde, "Sandboxed module should be able to require allowed builtins")
+        assert(not plugin.hasProcess, "Sandboxed module should not be able to require other builtins")
+        assert(plugin.hasGlobal, "Sandboxed module should be able to read its own globals")
+        assert(GLOBAL_FROM_PLUGIN == nil, "Sandboxed module globals should not leak")
+        assert(not plugin.escapedThroughEnv, "Sandboxed module should not reach the global environment")
+        assert(not plugin.escapedThroughModule, "Sandboxed module should not get builtins through other modules")
+        assert(not plugin.escapedThroughStrings, "Sandboxed module should not reach the string library through strings")
+        assert(string.format("%d", 1) == "1", "Sandboxed module should not replace library functions")
+        assert(("%d"):format(1) == "1", "Sandboxed module should not replace string methods")
+
+        local fs = require("@lune/fs")
+        assert(fs ~= nil, "Modules outside of the sandbox should be able to require any builtin")
+
+        local task = require("@lune/task")
+        assert(not pcall(task.parallel, "tests/task/worker.luau", {}), "Workers should not start while sandboxes are in use")
+    "#;
+
+    Ok(lune.run("embedding_sandbox", script).await?)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn embedding_deterministic() -> Result<ExitCode> {
+    let mut lune = Lune::new().with_deterministic_mode(1234)?;
+
+    let script = r#"
+        local task = require("@lune/task")
+
+        local events = {}
+        task.delay(2, function()
+            table.insert(events, "delay 2")
+        end)
+        task.spawn(function()
+            table.insert(events, "wait " .. tostring(task.wait(1)))
+            table.insert(events, "wait " .. tostring(task.wait(1.5)))
+        end)
+        task.delay(1, function()
+            table.insert(events, "delay 1")
+        end)
+
+        assert(#events == 0, "Threads should not resume before time is advanced")
+
+        local before = os.clock()
+        task.advanceTime(5)
+        assert(os.clock() - before < 1, "Advancing time should not wait for real time")
+
+        local expected = { "wait 1", "delay 1", "delay 2", "wait 1.5" }
+        for index, event in expected do
+            assert(events[index] == event, `Expected event #{index} to be '{event}', got '{events[index]}'`)
+        end
+
+        local first = math.random()
+        math.randomseed(1234)
+        assert(math.random() == first, "Random number generator should be seeded")
+    "#;
+
+    Ok(lune.run("embedding_deterministic", script).await?)
+}
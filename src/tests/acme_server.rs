@@ -0,0 +1,407 @@
+//! A loopback ACME server for testing certificates from `net.serve`, which
+//! validates TLS-ALPN-01 challenges and issues certificates from a test CA.
+
+use std::{
+    convert::Infallible,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hyper::{server::conn::Http, service::service_fn, Body, Method, Request, Response};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, CertificateSigningRequest, IsCa};
+use ring::digest::{digest, SHA256};
+use serde_json::{json, Value};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
+use tokio_rustls::{
+    rustls::{
+        client::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        Certificate as RustlsCertificate, ClientConfig, DigitallySignedStruct, ServerName,
+    },
+    TlsConnector,
+};
+use x509_parser::{certificate::X509Certificate, prelude::FromDer, time::ASN1Time};
+
+use super::TestServer;
+
+const ACME_IDENTIFIER_OID: &str = "1.3.6.1.5.5.7.1.31";
+
+/**
+    Starts the server on a random port.
+
+    The script is given the port, and uses the following endpoints besides the ACME ones:
+
+    - `GET /ca.pem` - The certificate of the test CA, to trust issued certificates
+    - `POST /validation-port` - Which port to validate challenges on, instead of 443
+    - `POST /lifetime` - How many seconds issued certificates are valid for
+    - `GET /issued` - How many certificates have been issued
+*/
+pub async fn start() -> Result<TestServer> {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let port = listener.local_addr()?.port();
+
+    let mut ca_params = CertificateParams::new(Vec::new());
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = Certificate::from_params(ca_params)?;
+    let ca_pem = ca.serialize_pem()?;
+
+    let (validation_port, _) = watch::channel(None);
+    let state = Arc::new(AcmeState {
+        base: format!("http://127.0.0.1:{port}"),
+        ca,
+        ca_pem,
+        validation_port,
+        inner: Mutex::new(AcmeStateInner {
+            nonce: 0,
+            thumbprint: String::new(),
+            lifetime: Duration::from_secs(60 * 60 * 24),
+            orders: Vec::new(),
+            issued: 0,
+        }),
+    });
+
+    let task = tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let state = Arc::clone(&state);
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let state = Arc::clone(&state);
+                    async move {
+                        let response = match handle_request(&state, req).await {
+                            Ok(response) => response,
+                            Err(e) => Response::builder()
+                                .status(400)
+                                .body(Body::from(e.to_string()))
+                                .unwrap(),
+                        };
+                        Ok::<_, Infallible>(state.with_nonce(response))
+                    }
+                });
+                Http::new().serve_connection(stream, service).await.ok();
+            });
+        }
+    });
+
+    Ok(TestServer {
+        args: vec![port.to_string()],
+        task,
+    })
+}
+
+struct AcmeState {
+    base: String,
+    ca: Certificate,
+    ca_pem: String,
+    validation_port: watch::Sender<Option<u16>>,
+    inner: Mutex<AcmeStateInner>,
+}
+
+struct AcmeStateInner {
+    nonce: usize,
+    thumbprint: String,
+    lifetime: Duration,
+    orders: Vec<AcmeOrder>,
+    issued: usize,
+}
+
+struct AcmeOrder {
+    authorizations: Vec<AcmeAuthorization>,
+    certificate: Option<String>,
+}
+
+struct AcmeAuthorization {
+    domain: String,
+    token: String,
+    status: &'static str,
+}
+
+impl AcmeState {
+    fn with_nonce(&self, mut response: Response<Body>) -> Response<Body> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.nonce += 1;
+        let nonce = format!("nonce-{}", inner.nonce);
+        response
+            .headers_mut()
+            .insert("Replay-Nonce", nonce.parse().unwrap());
+        response
+    }
+
+    fn order_json(&self, id: usize, order: &AcmeOrder) -> Value {
+        let status = if order.certificate.is_some() {
+            "valid"
+        } else if order.authorizations.iter().any(|a| a.status == "invalid") {
+            "invalid"
+        } else if order.authorizations.iter().all(|a| a.status == "valid") {
+            "ready"
+        } else {
+            "pending"
+        };
+        let authorizations = (0..order.authorizations.len())
+            .map(|index| format!("{}/authz/{id}/{index}", self.base))
+            .collect::<Vec<_>>();
+        json!({
+            "status": status,
+            "authorizations": authorizations,
+            "finalize": format!("{}/finalize/{id}", self.base),
+            "certificate": order
+                .certificate
+                .as_ref()
+                .map(|_| format!("{}/cert/{id}", self.base)),
+        })
+    }
+
+    fn challenge_json(&self, id: usize, index: usize, authorization: &AcmeAuthorization) -> Value {
+        json!({
+            "type": "tls-alpn-01",
+            "url": format!("{}/challenge/{id}/{index}", self.base),
+            "token": authorization.token,
+            "status": authorization.status,
+        })
+    }
+}
+
+async fn handle_request(state: &Arc<AcmeState>, req: Request<Body>) -> Result<Response<Body>> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let body = hyper::body::to_bytes(req.into_body()).await?;
+    let segments = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+
+    let (status, location, body) = match (method, segments.as_slice()) {
+        (Method::GET, ["directory"]) => {
+            let base = &state.base;
+            let directory = json!({
+                "newNonce": format!("{base}/nonce"),
+                "newAccount": format!("{base}/account"),
+                "newOrder": format!("{base}/order"),
+            });
+            (200, None, directory.to_string())
+        }
+        (Method::HEAD | Method::GET, ["nonce"]) => (200, None, String::new()),
+        (Method::GET, ["ca.pem"]) => (200, None, state.ca_pem.clone()),
+        (Method::GET, ["issued"]) => {
+            let issued = state.inner.lock().unwrap().issued;
+            (200, None, issued.to_string())
+        }
+        (Method::POST, ["validation-port"]) => {
+            let port = std::str::from_utf8(&body)?.trim().parse::<u16>()?;
+            state.validation_port.send_replace(Some(port));
+            (200, None, String::new())
+        }
+        (Method::POST, ["lifetime"]) => {
+            let secs = std::str::from_utf8(&body)?.trim().parse::<u64>()?;
+            state.inner.lock().unwrap().lifetime = Duration::from_secs(secs);
+            (200, None, String::new())
+        }
+        (Method::POST, ["account"]) => {
+            let (protected, _) = parse_jws(&body)?;
+            let jwk = &protected["jwk"];
+            let thumbprint_json = json!({
+                "crv": jwk["crv"],
+                "kty": jwk["kty"],
+                "x": jwk["x"],
+                "y": jwk["y"],
+            });
+            let thumbprint = digest(&SHA256, thumbprint_json.to_string().as_bytes());
+            state.inner.lock().unwrap().thumbprint = URL_SAFE_NO_PAD.encode(thumbprint);
+            let location = format!("{}/account/0", state.base);
+            (
+                201,
+                Some(location),
+                json!({ "status": "valid" }).to_string(),
+            )
+        }
+        (Method::POST, ["order"]) => {
+            let (_, payload) = parse_jws(&body)?;
+            let mut inner = state.inner.lock().unwrap();
+            let id = inner.orders.len();
+            let authorizations = payload["identifiers"]
+                .as_array()
+                .context("Missing identifiers")?
+                .iter()
+                .enumerate()
+                .map(|(index, identifier)| AcmeAuthorization {
+                    domain: identifier["value"].as_str().unwrap_or_default().to_string(),
+                    token: format!("token-{id}-{index}"),
+                    status: "pending",
+                })
+                .collect();
+            let order = AcmeOrder {
+                authorizations,
+                certificate: None,
+            };
+            let json = state.order_json(id, &order);
+            inner.orders.push(order);
+            let location = format!("{}/order/{id}", state.base);
+            (201, Some(location), json.to_string())
+        }
+        (Method::POST, ["order", id]) => {
+            let id = id.parse::<usize>()?;
+            let inner = state.inner.lock().unwrap();
+            let order = inner.orders.get(id).context("No such order")?;
+            (200, None, state.order_json(id, order).to_string())
+        }
+        (Method::POST, ["authz", id, index]) => {
+            let (id, index) = (id.parse::<usize>()?, index.parse::<usize>()?);
+            let inner = state.inner.lock().unwrap();
+            let order = inner.orders.get(id).context("No such order")?;
+            let authorization = order.authorizations.get(index).context("No such authz")?;
+            let json = json!({
+                "identifier": { "type": "dns", "value": authorization.domain },
+                "status": authorization.status,
+                "challenges": [state.challenge_json(id, index, authorization)],
+            });
+            (200, None, json.to_string())
+        }
+        (Method::POST, ["challenge", id, index]) => {
+            let (id, index) = (id.parse::<usize>()?, index.parse::<usize>()?);
+            let mut inner = state.inner.lock().unwrap();
+            let thumbprint = inner.thumbprint.clone();
+            let order = inner.orders.get_mut(id).context("No such order")?;
+            let authorization = order
+                .authorizations
+                .get_mut(index)
+                .context("No such authz")?;
+            if authorization.status == "pending" {
+                authorization.status = "processing";
+                let key_authorization = format!("{}.{thumbprint}", authorization.token);
+                let domain = authorization.domain.clone();
+                let state = Arc::clone(state);
+                tokio::spawn(async move {
+                    let valid = validate(&state, &domain, &key_authorization).await.is_ok();
+                    let mut inner = state.inner.lock().unwrap();
+                    inner.orders[id].authorizations[index].status =
+                        if valid { "valid" } else { "invalid" };
+                });
+            }
+            let json = state.challenge_json(id, index, &order.authorizations[index]);
+            (200, None, json.to_string())
+        }
+        (Method::POST, ["finalize", id]) => {
+            let id = id.parse::<usize>()?;
+            let (_, payload) = parse_jws(&body)?;
+            let csr = URL_SAFE_NO_PAD.decode(payload["csr"].as_str().context("Missing csr")?)?;
+            let mut csr = CertificateSigningRequest::from_der(&csr)?;
+            let mut inner = state.inner.lock().unwrap();
+            let now = ASN1Time::now().to_datetime();
+            csr.params.not_before = now;
+            csr.params.not_after = now + inner.lifetime;
+            let chain = csr.serialize_pem_with_signer(&state.ca)? + &state.ca_pem;
+            inner.issued += 1;
+            let order = inner.orders.get_mut(id).context("No such order")?;
+            order.certificate = Some(chain);
+            (200, None, state.order_json(id, order).to_string())
+        }
+        (Method::POST, ["cert", id]) => {
+            let id = id.parse::<usize>()?;
+            let inner = state.inner.lock().unwrap();
+            let order = inner.orders.get(id).context("No such order")?;
+            let chain = order.certificate.clone().context("Order is not valid")?;
+            (200, None, chain)
+        }
+        _ => (404, None, String::new()),
+    };
+
+    let mut response = Response::builder().status(status);
+    if let Some(location) = location {
+        response = response.header("Location", location);
+    }
+    Ok(response.body(Body::from(body))?)
+}
+
+/**
+    Parses the protected header and payload of a JWS, without checking its signature.
+*/
+fn parse_jws(body: &[u8]) -> Result<(Value, Value)> {
+    let jws = serde_json::from_slice::<Value>(body)?;
+    let decode = |field: &str| -> Result<Value> {
+        let encoded = jws[field].as_str().context("Missing jws field")?;
+        if encoded.is_empty() {
+            return Ok(Value::Null);
+        }
+        Ok(serde_json::from_slice(&URL_SAFE_NO_PAD.decode(encoded)?)?)
+    };
+    Ok((decode("protected")?, decode("payload")?))
+}
+
+/**
+    Validates a TLS-ALPN-01 challenge, once the script has told us which port to connect to.
+*/
+async fn validate(state: &AcmeState, domain: &str, key_authorization: &str) -> Result<()> {
+    let mut port = state.validation_port.subscribe();
+    let port = port.wait_for(Option::is_some).await?.unwrap();
+
+    let mut config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate))
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"acme-tls/1".to_vec()];
+    let stream = TcpStream::connect(("127.0.0.1", port)).await?;
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from(domain)?, stream)
+        .await?;
+
+    let (_, connection) = stream.get_ref();
+    if connection.alpn_protocol() != Some(b"acme-tls/1") {
+        bail!("Server did not negotiate acme-tls/1");
+    }
+    let certificate = connection
+        .peer_certificates()
+        .and_then(|certs| certs.first())
+        .context("Server sent no certificate")?;
+    let (_, parsed) = X509Certificate::from_der(&certificate.0)?;
+    let extension = parsed
+        .extensions()
+        .iter()
+        .find(|ext| ext.oid.to_id_string() == ACME_IDENTIFIER_OID)
+        .context("Certificate has no acmeIdentifier extension")?;
+
+    let expected = digest(&SHA256, key_authorization.as_bytes());
+    let mut expected_value = vec![0x04, 0x20];
+    expected_value.extend_from_slice(expected.as_ref());
+    if !extension.critical || extension.value != expected_value {
+        bail!("Certificate has an invalid acmeIdentifier extension");
+    }
+    Ok(())
+}
+
+/**
+    Accepts any certificate without checking it, since the critical acmeIdentifier extension
+    of challenge certificates is unknown to the usual verifier, and they are self-signed.
+*/
+struct AcceptAnyCertificate;
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &RustlsCertificate,
+        _intermediates: &[RustlsCertificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &RustlsCertificate,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &RustlsCertificate,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+}
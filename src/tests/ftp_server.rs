@@ -0,0 +1,155 @@
+//! A loopback FTP server for testing `net.ftp`, which serves
+//! files from a temporary directory using extended passive mode.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use tempfile::TempDir;
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use super::TestServer;
+
+const USER: &str = "lune";
+const PASSWORD: &str = "hunter2";
+
+/**
+    Starts the server on a random port.
+
+    The script is given the port, and can log in as `lune` with the password `hunter2`.
+*/
+pub async fn start() -> Result<TestServer> {
+    let root = Arc::new(TempDir::new()?);
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let port = listener.local_addr()?.port();
+
+    let task = tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let root = Arc::clone(&root);
+            tokio::spawn(async move {
+                handle_connection(stream, root.path()).await.ok();
+            });
+        }
+    });
+
+    Ok(TestServer {
+        args: vec![port.to_string()],
+        task,
+    })
+}
+
+async fn handle_connection(stream: TcpStream, root: &Path) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut user = None;
+    let mut logged_in = false;
+    let mut passive: Option<TcpListener> = None;
+
+    // NOTE: The greeting spans multiple lines, to test reading those replies
+    writer
+        .write_all(b"220-Welcome\r\n220 Lune test server\r\n")
+        .await?;
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let command = command.to_ascii_uppercase();
+
+        let reply = match command.as_str() {
+            "USER" => {
+                user = Some(arg.to_string());
+                "331 Password required".to_string()
+            }
+            "PASS" => {
+                logged_in = user.as_deref() == Some(USER) && arg == PASSWORD;
+                if logged_in {
+                    "230 Logged in".to_string()
+                } else {
+                    "530 Login incorrect".to_string()
+                }
+            }
+            "QUIT" => {
+                writer.write_all(b"221 Goodbye\r\n").await?;
+                return Ok(());
+            }
+            _ if !logged_in => "530 Please log in".to_string(),
+            "TYPE" => "200 Type set".to_string(),
+            "EPSV" => {
+                let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+                let port = listener.local_addr()?.port();
+                passive = Some(listener);
+                format!("229 Entering Extended Passive Mode (|||{port}|)")
+            }
+            "NLST" | "RETR" | "STOR" => {
+                let Some(listener) = passive.take() else {
+                    writer.write_all(b"425 Use EPSV first\r\n").await?;
+                    continue;
+                };
+                let path = resolve(root, arg);
+                let contents = match command.as_str() {
+                    "NLST" => list(&path).await,
+                    "RETR" => fs::read(&path).await.ok(),
+                    _ => Some(Vec::new()),
+                };
+                let Some(contents) = contents else {
+                    writer.write_all(b"550 No such file\r\n").await?;
+                    continue;
+                };
+                writer.write_all(b"150 Opening data connection\r\n").await?;
+                let (mut data, _) = listener.accept().await?;
+                if command == "STOR" {
+                    let mut received = Vec::new();
+                    data.read_to_end(&mut received).await?;
+                    fs::write(&path, received).await?;
+                } else {
+                    data.write_all(&contents).await?;
+                    data.shutdown().await?;
+                }
+                "226 Transfer complete".to_string()
+            }
+            "MKD" => match fs::create_dir(resolve(root, arg)).await {
+                Ok(()) => format!("257 \"{arg}\" created"),
+                Err(_) => "550 Failed to create directory".to_string(),
+            },
+            "DELE" => match fs::remove_file(resolve(root, arg)).await {
+                Ok(()) => "250 Deleted".to_string(),
+                Err(_) => "550 No such file".to_string(),
+            },
+            "RMD" => match fs::remove_dir(resolve(root, arg)).await {
+                Ok(()) => "250 Deleted".to_string(),
+                Err(_) => "550 Failed to remove directory".to_string(),
+            },
+            _ => "502 Command not implemented".to_string(),
+        };
+        writer.write_all(format!("{reply}\r\n").as_bytes()).await?;
+    }
+}
+
+fn resolve(root: &Path, path: &str) -> PathBuf {
+    let path = Path::new(path.trim_start_matches('/'));
+    root.join(path)
+}
+
+/**
+    Lists names in a directory, one per line, or `None` if it does not exist.
+*/
+async fn list(path: &Path) -> Option<Vec<u8>> {
+    let mut entries = fs::read_dir(path).await.ok()?;
+    let mut names = String::new();
+    while let Some(entry) = entries.next_entry().await.ok()? {
+        names.push_str(&entry.file_name().to_string_lossy());
+        names.push_str("\r\n");
+    }
+    Some(names.into_bytes())
+}
@@ -0,0 +1,358 @@
+//! A loopback SSH server for testing `net.ssh`, which supports
+//! a couple of commands, SFTP in a temporary directory, and
+//! forwarding connections to local ports.
+
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use russh::{
+    server::{self, Auth, Msg, Session},
+    Channel, ChannelId, CryptoVec,
+};
+use russh_keys::key::KeyPair;
+use russh_sftp::protocol::{
+    Data, File, FileAttributes, Handle, Name, OpenFlags, Status, StatusCode,
+};
+use tempfile::TempDir;
+use tokio::{
+    fs,
+    io::{copy_bidirectional, AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use super::TestServer;
+
+const USER: &str = "lune";
+const PASSWORD: &str = "hunter2";
+
+/**
+    Starts the server on a random port.
+
+    The script is given the port and the fingerprint of the host key, and
+    can log in as `lune` with the password `hunter2`.
+*/
+pub async fn start() -> Result<TestServer> {
+    let key = KeyPair::generate_ed25519().expect("Failed to generate host key");
+    let fingerprint = key.clone_public_key()?.fingerprint();
+    let config = Arc::new(server::Config {
+        keys: vec![key],
+        auth_rejection_time: std::time::Duration::ZERO,
+        ..Default::default()
+    });
+
+    let root = Arc::new(TempDir::new()?);
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let port = listener.local_addr()?.port();
+
+    let task = tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let config = Arc::clone(&config);
+            let handler = SshHandler {
+                root: Arc::clone(&root),
+                channels: HashMap::new(),
+            };
+            tokio::spawn(async move {
+                if let Ok(session) = server::run_stream(config, stream, handler).await {
+                    session.await.ok();
+                }
+            });
+        }
+    });
+
+    Ok(TestServer {
+        args: vec![port.to_string(), fingerprint],
+        task,
+    })
+}
+
+struct SshHandler {
+    root: Arc<TempDir>,
+    channels: HashMap<ChannelId, Channel<Msg>>,
+}
+
+#[async_trait]
+impl server::Handler for SshHandler {
+    type Error = anyhow::Error;
+
+    async fn auth_password(self, user: &str, password: &str) -> Result<(Self, Auth)> {
+        let auth = if user == USER && password == PASSWORD {
+            Auth::Accept
+        } else {
+            Auth::Reject {
+                proceed_with_methods: None,
+            }
+        };
+        Ok((self, auth))
+    }
+
+    async fn channel_open_session(
+        mut self,
+        channel: Channel<Msg>,
+        session: Session,
+    ) -> Result<(Self, bool, Session)> {
+        self.channels.insert(channel.id(), channel);
+        Ok((self, true, session))
+    }
+
+    /**
+        Runs `echo <text>`, which writes the text to stdout, and `fail <code>`,
+        which writes to stderr and exits with the code. Other commands exit with 127.
+    */
+    async fn exec_request(
+        mut self,
+        channel: ChannelId,
+        data: &[u8],
+        mut session: Session,
+    ) -> Result<(Self, Session)> {
+        self.channels.remove(&channel);
+        let command = String::from_utf8_lossy(data).to_string();
+        let (name, rest) = command.split_once(' ').unwrap_or((&command, ""));
+        session.channel_success(channel);
+        let code = match name {
+            "echo" => {
+                session.data(channel, CryptoVec::from(format!("{rest}\n")));
+                0
+            }
+            "fail" => {
+                session.extended_data(channel, 1, CryptoVec::from_slice(b"failed\n"));
+                rest.parse().unwrap_or(1)
+            }
+            _ => {
+                let message = format!("{name}: command not found\n");
+                session.extended_data(channel, 1, CryptoVec::from(message));
+                127
+            }
+        };
+        session.exit_status_request(channel, code);
+        session.eof(channel);
+        session.close(channel);
+        Ok((self, session))
+    }
+
+    async fn subsystem_request(
+        mut self,
+        channel: ChannelId,
+        name: &str,
+        mut session: Session,
+    ) -> Result<(Self, Session)> {
+        match self.channels.remove(&channel) {
+            Some(stream) if name == "sftp" => {
+                session.channel_success(channel);
+                let handler = SftpHandler {
+                    root: Arc::clone(&self.root),
+                    files: HashMap::new(),
+                    dirs: HashMap::new(),
+                    next_handle: 0,
+                };
+                russh_sftp::server::run(stream.into_stream(), handler).await;
+            }
+            _ => session.channel_failure(channel),
+        }
+        Ok((self, session))
+    }
+
+    async fn channel_open_direct_tcpip(
+        self,
+        channel: Channel<Msg>,
+        host_to_connect: &str,
+        port_to_connect: u32,
+        _originator_address: &str,
+        _originator_port: u32,
+        session: Session,
+    ) -> Result<(Self, bool, Session)> {
+        let Ok(port) = u16::try_from(port_to_connect) else {
+            return Ok((self, false, session));
+        };
+        let Ok(mut stream) = TcpStream::connect((host_to_connect, port)).await else {
+            return Ok((self, false, session));
+        };
+        tokio::spawn(async move {
+            let mut channel = channel.into_stream();
+            copy_bidirectional(&mut stream, &mut channel).await.ok();
+        });
+        Ok((self, true, session))
+    }
+}
+
+/**
+    Serves SFTP requests from a directory, with paths relative to it.
+*/
+struct SftpHandler {
+    root: Arc<TempDir>,
+    files: HashMap<String, fs::File>,
+    dirs: HashMap<String, Option<PathBuf>>,
+    next_handle: u32,
+}
+
+impl SftpHandler {
+    fn resolve(&self, path: &str) -> Result<PathBuf, StatusCode> {
+        let path = Path::new(path.trim_start_matches('/'));
+        if path.components().any(|c| c.as_os_str() == "..") {
+            return Err(StatusCode::PermissionDenied);
+        }
+        Ok(self.root.path().join(path))
+    }
+
+    fn handle(&mut self) -> String {
+        self.next_handle += 1;
+        self.next_handle.to_string()
+    }
+}
+
+fn status_code(error: &std::io::Error) -> StatusCode {
+    match error.kind() {
+        ErrorKind::NotFound => StatusCode::NoSuchFile,
+        ErrorKind::PermissionDenied => StatusCode::PermissionDenied,
+        _ => StatusCode::Failure,
+    }
+}
+
+fn ok(id: u32) -> Status {
+    Status {
+        id,
+        status_code: StatusCode::Ok,
+        error_message: "Ok".to_string(),
+        language_tag: "en-US".to_string(),
+    }
+}
+
+impl russh_sftp::server::Handler for SftpHandler {
+    type Error = StatusCode;
+
+    fn unimplemented(&self) -> Self::Error {
+        StatusCode::OpUnsupported
+    }
+
+    async fn open(
+        &mut self,
+        id: u32,
+        filename: String,
+        pflags: OpenFlags,
+        _attrs: FileAttributes,
+    ) -> Result<Handle, Self::Error> {
+        let path = self.resolve(&filename)?;
+        let options = std::fs::OpenOptions::from(pflags);
+        let file = fs::OpenOptions::from(options)
+            .open(path)
+            .await
+            .map_err(|e| status_code(&e))?;
+        let handle = self.handle();
+        self.files.insert(handle.clone(), file);
+        Ok(Handle { id, handle })
+    }
+
+    async fn close(&mut self, id: u32, handle: String) -> Result<Status, Self::Error> {
+        let file = self.files.remove(&handle);
+        let dir = self.dirs.remove(&handle);
+        match (file, dir) {
+            (Some(mut file), _) => {
+                file.flush().await.map_err(|e| status_code(&e))?;
+                Ok(ok(id))
+            }
+            (None, Some(_)) => Ok(ok(id)),
+            (None, None) => Err(StatusCode::Failure),
+        }
+    }
+
+    async fn read(
+        &mut self,
+        id: u32,
+        handle: String,
+        offset: u64,
+        len: u32,
+    ) -> Result<Data, Self::Error> {
+        let file = self.files.get_mut(&handle).ok_or(StatusCode::Failure)?;
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(|e| status_code(&e))?;
+        let mut data = vec![0; len as usize];
+        let read = file.read(&mut data).await.map_err(|e| status_code(&e))?;
+        if read == 0 {
+            return Err(StatusCode::Eof);
+        }
+        data.truncate(read);
+        Ok(Data { id, data })
+    }
+
+    async fn write(
+        &mut self,
+        id: u32,
+        handle: String,
+        offset: u64,
+        data: Vec<u8>,
+    ) -> Result<Status, Self::Error> {
+        let file = self.files.get_mut(&handle).ok_or(StatusCode::Failure)?;
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(|e| status_code(&e))?;
+        file.write_all(&data).await.map_err(|e| status_code(&e))?;
+        Ok(ok(id))
+    }
+
+    async fn opendir(&mut self, id: u32, path: String) -> Result<Handle, Self::Error> {
+        let path = self.resolve(&path)?;
+        if !fs::metadata(&path)
+            .await
+            .map_err(|e| status_code(&e))?
+            .is_dir()
+        {
+            return Err(StatusCode::NoSuchFile);
+        }
+        let handle = self.handle();
+        self.dirs.insert(handle.clone(), Some(path));
+        Ok(Handle { id, handle })
+    }
+
+    /**
+        Sends all entries in the first reply, and `Eof` for any replies after it.
+    */
+    async fn readdir(&mut self, id: u32, handle: String) -> Result<Name, Self::Error> {
+        let dir = self.dirs.get_mut(&handle).ok_or(StatusCode::Failure)?;
+        let Some(path) = dir.take() else {
+            return Err(StatusCode::Eof);
+        };
+        let mut entries = fs::read_dir(path).await.map_err(|e| status_code(&e))?;
+        let mut files = Vec::new();
+        while let Some(entry) = entries.next_entry().await.map_err(|e| status_code(&e))? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            files.push(File::new(name, FileAttributes::default()));
+        }
+        Ok(Name { id, files })
+    }
+
+    async fn remove(&mut self, id: u32, filename: String) -> Result<Status, Self::Error> {
+        let path = self.resolve(&filename)?;
+        fs::remove_file(path).await.map_err(|e| status_code(&e))?;
+        Ok(ok(id))
+    }
+
+    async fn mkdir(
+        &mut self,
+        id: u32,
+        path: String,
+        _attrs: FileAttributes,
+    ) -> Result<Status, Self::Error> {
+        let path = self.resolve(&path)?;
+        fs::create_dir(path).await.map_err(|e| status_code(&e))?;
+        Ok(ok(id))
+    }
+
+    async fn rmdir(&mut self, id: u32, path: String) -> Result<Status, Self::Error> {
+        let path = self.resolve(&path)?;
+        fs::remove_dir(path).await.map_err(|e| status_code(&e))?;
+        Ok(ok(id))
+    }
+
+    async fn realpath(&mut self, id: u32, path: String) -> Result<Name, Self::Error> {
+        Ok(Name {
+            id,
+            files: vec![File::dummy(format!("/{}", path.trim_start_matches('/')))],
+        })
+    }
+}
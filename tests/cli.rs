@@ -0,0 +1,56 @@
+//! Tests for command line usage, which need to run a separate `lune` process.
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+};
+
+fn lune(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lune"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("Failed to run lune")
+}
+
+#[test]
+fn scripts_can_be_named_roblox() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("roblox.luau"),
+        r#"print("ran " .. table.concat(require("@lune/process").args, " "))"#,
+    )
+    .unwrap();
+
+    let output = lune(dir.path(), &["roblox", "validate", "place.rbxl"]);
+    assert!(output.status.success(), "Script named roblox should run");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "ran validate place.rbxl"
+    );
+}
+
+#[cfg(feature = "roblox")]
+#[test]
+fn validate_roblox_files() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("make.luau"),
+        r#"
+        local fs = require("@lune/fs")
+        local roblox = require("@lune/roblox")
+        local game = roblox.Instance.new("DataModel")
+        roblox.Instance.new("Folder").Parent = game
+        fs.writeFile("place.rbxl", roblox.serializePlace(game))
+        "#,
+    )
+    .unwrap();
+    assert!(lune(dir.path(), &["make"]).status.success());
+
+    let output = lune(dir.path(), &["--validate-roblox", "place.rbxl"]);
+    assert!(output.status.success(), "Valid place file should pass");
+
+    let output = lune(dir.path(), &["--validate-roblox", "missing.rbxl"]);
+    assert!(!output.status.success(), "Missing place file should fail");
+}
@@ -0,0 +1,177 @@
+//! Tests for Ctrl-C handling, which need to send signals to a separate `lune` process.
+
+#![cfg(unix)]
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{channel, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/**
+    A `lune` process running a script, with its stdout lines sent to a channel.
+*/
+struct LuneProcess {
+    child: Child,
+    lines: Receiver<String>,
+}
+
+impl LuneProcess {
+    fn spawn(script: &str) -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_lune"))
+            .arg("-")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to spawn lune");
+
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(script.as_bytes()).unwrap();
+        drop(stdin);
+
+        let stdout = child.stdout.take().unwrap();
+        let (tx, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self { child, lines }
+    }
+
+    fn wait_for_line(&self, expected: &str) {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) if line == expected => return,
+                Ok(_) => continue,
+                Err(_) => panic!("Did not receive line '{expected}' from lune"),
+            }
+        }
+    }
+
+    fn interrupt(&self) {
+        let pid = libc::pid_t::try_from(self.child.id()).unwrap();
+        assert_eq!(unsafe { libc::kill(pid, libc::SIGINT) }, 0);
+    }
+
+    fn wait_for_exit(&mut self) -> (i32, Duration) {
+        let start = Instant::now();
+        loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                let code = status.code().expect("Lune should exit with a code");
+                return (code, start.elapsed());
+            }
+            if start.elapsed() > TIMEOUT {
+                self.child.kill().ok();
+                panic!("Lune did not exit after Ctrl-C");
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn remaining_lines(&self) -> Vec<String> {
+        self.lines.try_iter().collect()
+    }
+}
+
+#[test]
+fn first_ctrl_c_cancels_gracefully() {
+    let mut lune = LuneProcess::spawn(
+        r#"
+        local process = require("@lune/process")
+        local task = require("@lune/task")
+        process.onExit(function(code)
+            print("exit handler " .. tostring(code))
+        end)
+        print("ready")
+        task.wait(30)
+        "#,
+    );
+
+    lune.wait_for_line("ready");
+    lune.interrupt();
+
+    let (code, _) = lune.wait_for_exit();
+    assert_eq!(code, 130, "First Ctrl-C should exit with code 130");
+    assert!(
+        lune.remaining_lines()
+            .contains(&"exit handler 130".to_string()),
+        "First Ctrl-C should run exit handlers"
+    );
+}
+
+#[test]
+fn second_ctrl_c_forces_exit() {
+    let mut lune = LuneProcess::spawn(
+        r#"
+        local process = require("@lune/process")
+        local task = require("@lune/task")
+        process.onExit(function()
+            print("exit handler started")
+            task.wait(4)
+            print("exit handler finished")
+        end)
+        print("ready")
+        task.wait(30)
+        "#,
+    );
+
+    lune.wait_for_line("ready");
+    lune.interrupt();
+    lune.wait_for_line("exit handler started");
+    lune.interrupt();
+
+    let (code, elapsed) = lune.wait_for_exit();
+    assert_eq!(code, 130, "Second Ctrl-C should exit with code 130");
+    assert!(
+        elapsed < Duration::from_secs(2),
+        "Second Ctrl-C should not wait for exit handlers"
+    );
+    assert!(
+        !lune
+            .remaining_lines()
+            .contains(&"exit handler finished".to_string()),
+        "Second Ctrl-C should not let exit handlers finish"
+    );
+}
+
+#[test]
+fn second_ctrl_c_forces_exit_with_signal_handlers() {
+    let mut lune = LuneProcess::spawn(
+        r#"
+        local process = require("@lune/process")
+        local task = require("@lune/task")
+        process.onSignal("SIGINT", function()
+            print("signal handler")
+        end)
+        print("ready")
+        task.wait(30)
+        "#,
+    );
+
+    lune.wait_for_line("ready");
+    lune.interrupt();
+    lune.wait_for_line("signal handler");
+    assert!(
+        lune.child.try_wait().unwrap().is_none(),
+        "First Ctrl-C should only run signal handlers"
+    );
+    lune.interrupt();
+
+    let (code, _) = lune.wait_for_exit();
+    assert_eq!(
+        code, 130,
+        "Second Ctrl-C should exit even with signal handlers"
+    );
+}